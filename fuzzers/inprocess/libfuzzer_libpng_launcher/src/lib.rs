@@ -8,7 +8,10 @@ use std::{env, net::SocketAddr, path::PathBuf};
 use clap::{self, Parser};
 use libafl::{
     corpus::{Corpus, InMemoryCorpus, OnDiskCorpus},
-    events::{launcher::Launcher, EventConfig},
+    events::{
+        launcher::{client_seed, Launcher},
+        EventConfig,
+    },
     executors::{inprocess::InProcessExecutor, ExitKind},
     feedback_or, feedback_or_fast,
     feedbacks::{CrashFeedback, MaxMapFeedback, TimeFeedback, TimeoutFeedback},
@@ -100,6 +103,14 @@ struct Opt {
     default_value = "10000"
     )]
     timeout: Duration,
+
+    #[arg(
+        short,
+        long,
+        help = "Set the master seed of the campaign, the seed of each client is derived from it. Random if not set",
+        name = "SEED"
+    )]
+    seed: Option<u64>,
     /*
     /// This fuzzer has hard-coded tokens
     #[arg(
@@ -137,7 +148,7 @@ pub extern "C" fn libafl_main() {
         MultiMonitor::new(|s| println!("{s}")),
     );
 
-    let mut run_client = |state: Option<_>, mut restarting_mgr, _core_id| {
+    let mut run_client = |state: Option<_>, mut restarting_mgr, core_id| {
         // Create an observation channel using the coverage map
         let edges_observer =
             HitcountsMapObserver::new(unsafe { std_edges_map_observer("edges") }).track_indices();
@@ -160,8 +171,8 @@ pub extern "C" fn libafl_main() {
         // If not restarting, create a State from scratch
         let mut state = state.unwrap_or_else(|| {
             StdState::new(
-                // RNG
-                StdRand::new(),
+                // RNG, derived from the master seed for reproducible campaigns
+                StdRand::with_seed(client_seed(core_id)),
                 // Corpus that will be evolved, we keep it in memory for performance
                 InMemoryCorpus::new(),
                 // Corpus in which we store solutions (crashes in this example),
@@ -258,6 +269,7 @@ pub extern "C" fn libafl_main() {
         .cores(&cores)
         .broker_port(broker_port)
         .remote_broker_addr(opt.remote_broker_addr)
+        .master_seed(opt.seed)
         .stdout_file(Some("/dev/null"))
         .build()
         .launch()
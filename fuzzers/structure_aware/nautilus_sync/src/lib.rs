@@ -9,7 +9,7 @@ use std::{env, net::SocketAddr, path::PathBuf, time::Duration};
 use clap::Parser;
 use libafl::{
     corpus::{InMemoryCorpus, OnDiskCorpus},
-    events::{launcher::Launcher, llmp::LlmpEventConverter, EventConfig},
+    events::{launcher::Launcher, llmp::LlmpEventConverter, EventConfig, NautilusContextHook},
     executors::{inprocess::InProcessExecutor, ExitKind},
     feedback_or,
     feedbacks::{CrashFeedback, MaxMapFeedback, NautilusChunksMetadata, NautilusFeedback},
@@ -119,6 +119,7 @@ pub extern "C" fn libafl_main() {
     let monitor = SimpleMonitor::new(|s| println!("{s}"));
 
     let context = NautilusContext::from_file(15, "grammar.json");
+    let context_hook = NautilusContextHook::new(&context);
 
     let mut event_converter = opt.bytes_broker_port.map(|port| {
         LlmpEventConverter::builder()
@@ -262,7 +263,8 @@ pub extern "C" fn libafl_main() {
         .remote_broker_addr(opt.remote_broker_addr)
         .stdout_file(Some("/dev/null"))
         .build()
-        .launch()
+        // Drop the testcases of clients with another grammar
+        .launch_with_hooks(tuple_list!(context_hook))
     {
         Ok(()) => (),
         Err(Error::ShuttingDown) => println!("Fuzzing stopped by user. Good bye."),
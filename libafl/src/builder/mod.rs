@@ -0,0 +1,308 @@
+//! Construct standard fuzzers from a declarative campaign spec, instead of code.
+//!
+//! A [`CampaignSpec`] describes the corpus directories, the scheduler, the objectives, the stages,
+//! the timeout and the cores of a campaign, so that campaigns can be versioned and reviewed like any
+//! other config file:
+//!
+//! ```toml
+//! timeout_ms = 1000
+//! tokens_file = "target.dict"
+//!
+//! [corpus]
+//! input_dirs = ["seeds"]
+//! queue_dir = "out/queue"
+//! solutions_dir = "out/crashes"
+//!
+//! [scheduler]
+//! kind = "power"
+//! schedule = "fast"
+//!
+//! [[stages]]
+//! kind = "calibration"
+//!
+//! [[stages]]
+//! kind = "power"
+//! mutator = "havoc_tokens"
+//!
+//! [launcher]
+//! cores = "0-7"
+//! broker_port = 1337
+//! ```
+//!
+//! Unknown keys and combinations which can not work, like a power stage without calibration, are
+//! rejected when loading the spec. [`CampaignSpec::launch_inprocess`] then runs the campaign on an
+//! in-process harness and its coverage map.
+//!
+//! The feedback is out of scope of the spec: campaigns always keep the inputs reaching new edges of the
+//! coverage map, and only the objectives are configurable. Campaigns needing other feedbacks are built in
+//! code.
+
+pub mod scheduler;
+pub use scheduler::SpecScheduler;
+
+pub mod spec;
+pub use spec::*;
+
+use alloc::{boxed::Box, vec::Vec};
+use core::time::Duration;
+
+use libafl_bolts::{
+    core_affinity::CoreId,
+    ownedref::OwnedMutSlice,
+    rands::StdRand,
+    shmem::{ShMemProvider, StdShMemProvider},
+    tuples::{tuple_list, Handled, Merge},
+};
+
+use crate::{
+    corpus::{CachedOnDiskCorpus, Corpus, OnDiskCorpus},
+    events::{launcher::Launcher, EventConfig, LlmpRestartingEventManager},
+    executors::{inprocess::OwnedInProcessExecutor, ExitKind},
+    feedback_and_fast, feedback_or, feedback_or_fast,
+    feedbacks::{ConstFeedback, CrashFeedback, MaxMapFeedback, TimeFeedback, TimeoutFeedback},
+    fuzzer::{Fuzzer, StdFuzzer},
+    generators::RandBytesGenerator,
+    inputs::BytesInput,
+    monitors::MultiMonitor,
+    mutators::{
+        havoc_mutations::havoc_mutations,
+        scheduled::{tokens_mutations, StdScheduledMutator},
+        token_mutations::Tokens,
+        StdMOptMutator,
+    },
+    nonzero,
+    observers::{CanTrack, HitcountsMapObserver, StdMapObserver, TimeObserver},
+    schedulers::IndexesLenTimeMinimizerScheduler,
+    stages::{
+        BoxedStage, CalibrationStage, Stage, StdMutationalStage, StdPowerMutationalStage,
+        SyncFromDiskStage,
+    },
+    state::{HasCorpus, StdState, UsesState},
+    Error, HasMetadata,
+};
+
+/// An empty list of boxed stages, for the fuzzer, executor and event manager given by reference
+fn stage_list<E, EM, Z>(_executor: &E, _mgr: &EM, _fuzzer: &Z) -> Vec<BoxedStage<E, EM, Z>>
+where
+    E: UsesState,
+{
+    Vec::new()
+}
+
+/// Box a stage, to add it to a list of [`BoxedStage`]s
+fn boxed_stage<E, EM, ST, Z>(stage: ST) -> BoxedStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: UsesState<State = E::State>,
+    Z: UsesState<State = E::State>,
+    ST: Stage<E, EM, Z, State = E::State> + 'static,
+{
+    Box::new(stage)
+}
+
+impl CampaignSpec {
+    /// Run the campaign on the cores of the [`LauncherSpec`], calling the in-process `harness` for
+    /// each input and observing the coverage map of `map_len` bytes at `map_ptr`.
+    ///
+    /// # Safety
+    /// `map_ptr` must be valid for reads and writes of `map_len` bytes for the life of the clients,
+    /// e.g. a `SanitizerCoverage` map in the data section of the target.
+    #[allow(clippy::too_many_lines)]
+    pub unsafe fn launch_inprocess<H>(
+        &self,
+        map_ptr: *mut u8,
+        map_len: usize,
+        harness: H,
+    ) -> Result<(), Error>
+    where
+        H: FnMut(&BytesInput) -> ExitKind + Clone + 'static,
+    {
+        self.validate()?;
+        let cores = self.launcher.cores()?;
+        let conf = match &self.launcher.configuration {
+            Some(name) => EventConfig::from_name(name),
+            None => EventConfig::AlwaysUnique,
+        };
+
+        let shmem_provider = StdShMemProvider::new()?;
+        let monitor = MultiMonitor::new(|s| log::info!("{s}"));
+
+        // Create an observation channel to keep track of the execution time
+        let time_observer = TimeObserver::new("time");
+        let time_ref = time_observer.handle();
+
+        let mut run_client = |state: Option<_>,
+                              mut mgr: LlmpRestartingEventManager<_, _, _>,
+                              _core_id: CoreId| {
+            let time_observer = time_observer.clone();
+
+            // Create an observation channel using the coverage map
+            let edges_observer = HitcountsMapObserver::new(unsafe {
+                StdMapObserver::from_mut_slice(
+                    "edges",
+                    OwnedMutSlice::from_raw_parts_mut(map_ptr, map_len),
+                )
+            })
+            .track_indices();
+
+            // For the calibration stages
+            let calibration_feedback = MaxMapFeedback::new(&edges_observer);
+
+            // Feedback to rate the interestingness of an input
+            let mut feedback = feedback_or!(
+                MaxMapFeedback::new(&edges_observer),
+                TimeFeedback::new(&time_observer)
+            );
+
+            // The objectives enabled in the spec
+            let mut objective = feedback_or_fast!(
+                feedback_and_fast!(
+                    ConstFeedback::new(self.objectives.crashes),
+                    CrashFeedback::new()
+                ),
+                feedback_and_fast!(
+                    ConstFeedback::new(self.objectives.timeouts),
+                    TimeoutFeedback::new()
+                )
+            );
+
+            // If not restarting, create a State from scratch
+            let mut state = match state {
+                Some(state) => state,
+                None => StdState::new(
+                    StdRand::new(),
+                    CachedOnDiskCorpus::new(self.corpus.queue_dir.clone(), self.corpus.cache_size)?,
+                    OnDiskCorpus::new(self.corpus.solutions_dir.clone())?,
+                    &mut feedback,
+                    &mut objective,
+                )?,
+            };
+
+            if let Some(tokens_file) = &self.tokens_file {
+                if state.metadata_map().get::<Tokens>().is_none() {
+                    state.add_metadata(Tokens::from_file(tokens_file)?);
+                }
+            }
+
+            // A minimization policy on top of the scheduler of the spec
+            let scheduler = IndexesLenTimeMinimizerScheduler::new(
+                &edges_observer,
+                SpecScheduler::new(&self.scheduler, &mut state, &edges_observer),
+            );
+
+            let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
+
+            let harness: Box<dyn FnMut(&BytesInput) -> ExitKind> = Box::new(harness.clone());
+            let mut executor = OwnedInProcessExecutor::with_timeout_generic(
+                tuple_list!(),
+                harness,
+                tuple_list!(edges_observer, time_observer),
+                &mut fuzzer,
+                &mut state,
+                &mut mgr,
+                self.timeout(),
+            )?;
+
+            // In case the corpus is empty (on first run), reset
+            if state.must_load_initial_inputs() {
+                if self.corpus.input_dirs.is_empty() {
+                    // Generate 8 initial inputs of max size 32
+                    let mut generator = RandBytesGenerator::new(nonzero!(32));
+                    state.generate_initial_inputs(
+                        &mut fuzzer,
+                        &mut executor,
+                        &mut generator,
+                        &mut mgr,
+                        8,
+                    )?;
+                } else {
+                    state.load_initial_inputs(
+                        &mut fuzzer,
+                        &mut executor,
+                        &mut mgr,
+                        &self.corpus.input_dirs,
+                    )?;
+                }
+                log::info!("We imported {} inputs.", state.corpus().count());
+            }
+
+            let mut stages = stage_list(&executor, &mgr, &fuzzer);
+            for stage in &self.stages {
+                stages.push(match stage {
+                    StageSpec::Calibration => {
+                        boxed_stage(CalibrationStage::new(&calibration_feedback))
+                    }
+                    StageSpec::Mutational { mutator } => match mutator {
+                        MutatorSpec::Havoc => boxed_stage(StdMutationalStage::new(
+                            StdScheduledMutator::new(havoc_mutations()),
+                        )),
+                        MutatorSpec::HavocTokens => boxed_stage(StdMutationalStage::new(
+                            StdScheduledMutator::new(havoc_mutations().merge(tokens_mutations())),
+                        )),
+                        MutatorSpec::Mopt => {
+                            boxed_stage(StdMutationalStage::new(StdMOptMutator::new::<
+                                BytesInput,
+                                _,
+                            >(
+                                &mut state,
+                                havoc_mutations().merge(tokens_mutations()),
+                                7,
+                                5,
+                            )?))
+                        }
+                    },
+                    StageSpec::Power { mutator } => match mutator {
+                        MutatorSpec::Havoc => {
+                            boxed_stage(StdPowerMutationalStage::<_, _, BytesInput, _, _>::new(
+                                StdScheduledMutator::new(havoc_mutations()),
+                            ))
+                        }
+                        MutatorSpec::HavocTokens => {
+                            boxed_stage(StdPowerMutationalStage::<_, _, BytesInput, _, _>::new(
+                                StdScheduledMutator::new(
+                                    havoc_mutations().merge(tokens_mutations()),
+                                ),
+                            ))
+                        }
+                        MutatorSpec::Mopt => {
+                            boxed_stage(StdPowerMutationalStage::<_, _, BytesInput, _, _>::new(
+                                StdMOptMutator::new::<BytesInput, _>(
+                                    &mut state,
+                                    havoc_mutations().merge(tokens_mutations()),
+                                    7,
+                                    5,
+                                )?,
+                            ))
+                        }
+                    },
+                    StageSpec::Sync {
+                        dirs,
+                        interval_secs,
+                    } => boxed_stage(SyncFromDiskStage::with_from_file(
+                        dirs.clone(),
+                        Duration::from_secs(*interval_secs),
+                    )),
+                });
+            }
+
+            fuzzer.fuzz_loop(&mut stages, &mut executor, &mut state, &mut mgr)?;
+            Ok(())
+        };
+
+        let launcher = Launcher::builder()
+            .shmem_provider(shmem_provider)
+            .configuration(conf)
+            .monitor(monitor)
+            .run_client(&mut run_client)
+            .cores(&cores)
+            .broker_port(self.launcher.broker_port)
+            .remote_broker_addr(self.launcher.remote_broker_addr)
+            .time_ref(Some(time_ref));
+        #[cfg(unix)]
+        let launcher = launcher.stdout_file(self.launcher.stdout_file.as_deref());
+        match launcher.build().launch() {
+            Err(Error::ShuttingDown) => Ok(()),
+            res => res,
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! A scheduler picked at runtime, from the [`SchedulerSpec`] of a campaign.
+
+use libafl_bolts::{tuples::MatchName, Named};
+
+use crate::{
+    builder::spec::SchedulerSpec,
+    corpus::{CorpusId, Testcase},
+    observers::MapObserver,
+    schedulers::{
+        HasQueueCycles, PowerQueueScheduler, QueueScheduler, RemovableScheduler, Scheduler,
+        StdWeightedScheduler,
+    },
+    Error, HasMetadata,
+};
+
+/// One of the schedulers supported by campaign specs, for the map observer `C`
+#[derive(Debug, Clone)]
+pub enum SpecScheduler<C, O> {
+    /// A [`QueueScheduler`]
+    Queue(QueueScheduler),
+    /// A [`PowerQueueScheduler`]
+    Power(PowerQueueScheduler<C, O>),
+    /// A [`StdWeightedScheduler`]
+    Weighted(StdWeightedScheduler<C, O>),
+}
+
+impl<C, O> SpecScheduler<C, O>
+where
+    O: MapObserver,
+    C: AsRef<O> + Named,
+{
+    /// Create the scheduler of `spec`, adding the metadata it needs to `state`
+    #[must_use]
+    pub fn new<S>(spec: &SchedulerSpec, state: &mut S, map_observer: &C) -> Self
+    where
+        S: HasMetadata,
+    {
+        match spec {
+            SchedulerSpec::Queue => Self::Queue(QueueScheduler::new()),
+            SchedulerSpec::Power { schedule } => Self::Power(PowerQueueScheduler::new(
+                state,
+                map_observer,
+                (*schedule).into(),
+            )),
+            SchedulerSpec::Weighted { schedule } => Self::Weighted(
+                StdWeightedScheduler::with_schedule(state, map_observer, schedule.map(Into::into)),
+            ),
+        }
+    }
+}
+
+impl<C, I, O, S> RemovableScheduler<I, S> for SpecScheduler<C, O>
+where
+    QueueScheduler: RemovableScheduler<I, S>,
+    PowerQueueScheduler<C, O>: RemovableScheduler<I, S>,
+    StdWeightedScheduler<C, O>: RemovableScheduler<I, S>,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        match self {
+            Self::Queue(scheduler) => scheduler.on_remove(state, id, testcase),
+            Self::Power(scheduler) => scheduler.on_remove(state, id, testcase),
+            Self::Weighted(scheduler) => scheduler.on_remove(state, id, testcase),
+        }
+    }
+
+    fn on_replace(&mut self, state: &mut S, id: CorpusId, prev: &Testcase<I>) -> Result<(), Error> {
+        match self {
+            Self::Queue(scheduler) => scheduler.on_replace(state, id, prev),
+            Self::Power(scheduler) => scheduler.on_replace(state, id, prev),
+            Self::Weighted(scheduler) => scheduler.on_replace(state, id, prev),
+        }
+    }
+}
+
+impl<C, I, O, S> Scheduler<I, S> for SpecScheduler<C, O>
+where
+    QueueScheduler: Scheduler<I, S>,
+    PowerQueueScheduler<C, O>: Scheduler<I, S>,
+    StdWeightedScheduler<C, O>: Scheduler<I, S>,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        match self {
+            Self::Queue(scheduler) => scheduler.on_add(state, id),
+            Self::Power(scheduler) => scheduler.on_add(state, id),
+            Self::Weighted(scheduler) => scheduler.on_add(state, id),
+        }
+    }
+
+    fn on_evaluation<OT>(&mut self, state: &mut S, input: &I, observers: &OT) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        match self {
+            Self::Queue(scheduler) => scheduler.on_evaluation(state, input, observers),
+            Self::Power(scheduler) => scheduler.on_evaluation(state, input, observers),
+            Self::Weighted(scheduler) => scheduler.on_evaluation(state, input, observers),
+        }
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        match self {
+            Self::Queue(scheduler) => scheduler.next(state),
+            Self::Power(scheduler) => scheduler.next(state),
+            Self::Weighted(scheduler) => scheduler.next(state),
+        }
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        match self {
+            Self::Queue(scheduler) => scheduler.set_current_scheduled(state, next_id),
+            Self::Power(scheduler) => scheduler.set_current_scheduled(state, next_id),
+            Self::Weighted(scheduler) => scheduler.set_current_scheduled(state, next_id),
+        }
+    }
+}
+
+impl<C, O> HasQueueCycles for SpecScheduler<C, O> {
+    fn queue_cycles(&self) -> u64 {
+        match self {
+            Self::Queue(scheduler) => scheduler.queue_cycles(),
+            Self::Power(scheduler) => scheduler.queue_cycles(),
+            Self::Weighted(scheduler) => scheduler.queue_cycles(),
+        }
+    }
+}
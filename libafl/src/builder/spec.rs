@@ -0,0 +1,414 @@
+//! The declarative spec of a fuzzing campaign, deserialized from TOML or JSON.
+//!
+//! All structs reject unknown keys, so a typo in a campaign file is an error instead of a silently
+//! ignored option.
+
+use alloc::{format, string::String, vec::Vec};
+use core::time::Duration;
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use libafl_bolts::core_affinity::Cores;
+use serde::{Deserialize, Serialize};
+
+use crate::{schedulers::powersched::PowerSchedule, Error};
+
+/// The spec of a fuzzing campaign. The feedback is fixed to edge coverage, see [`crate::builder`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CampaignSpec {
+    /// Where to load the seeds from, and store the queue and the solutions
+    pub corpus: CorpusSpec,
+    /// The scheduler picking the next testcase to fuzz
+    #[serde(default)]
+    pub scheduler: SchedulerSpec,
+    /// Which executions are solutions
+    #[serde(default)]
+    pub objectives: ObjectivesSpec,
+    /// The stages, run in this order for each scheduled testcase
+    #[serde(default = "default_stages")]
+    pub stages: Vec<StageSpec>,
+    /// The timeout of an execution, in milliseconds
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// A dictionary of tokens for the token mutations
+    #[serde(default)]
+    pub tokens_file: Option<PathBuf>,
+    /// Where to run the clients
+    #[serde(default)]
+    pub launcher: LauncherSpec,
+}
+
+/// The corpus directories of a campaign
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorpusSpec {
+    /// The directories to load the seeds from. Without any, random seeds are generated.
+    #[serde(default)]
+    pub input_dirs: Vec<PathBuf>,
+    /// The directory of the queue
+    pub queue_dir: PathBuf,
+    /// The directory of the solutions
+    pub solutions_dir: PathBuf,
+    /// How many testcases of the queue to keep in memory
+    #[serde(default = "default_cache_size")]
+    pub cache_size: usize,
+}
+
+/// The scheduler of a campaign, always wrapped in an `IndexesLenTimeMinimizerScheduler`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SchedulerSpec {
+    /// A plain `QueueScheduler`
+    #[default]
+    Queue,
+    /// A `PowerQueueScheduler` with the given power schedule
+    Power {
+        /// The power schedule
+        #[serde(default)]
+        schedule: ScheduleSpec,
+    },
+    /// A `StdWeightedScheduler`, with an optional power schedule
+    Weighted {
+        /// The power schedule
+        #[serde(default)]
+        schedule: Option<ScheduleSpec>,
+    },
+}
+
+impl SchedulerSpec {
+    /// If this scheduler keeps the metadata needed by power mutational stages
+    #[must_use]
+    pub fn supports_power(&self) -> bool {
+        !matches!(self, Self::Queue)
+    }
+}
+
+/// A power schedule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleSpec {
+    /// The `explore` schedule
+    Explore,
+    /// The `exploit` schedule
+    Exploit,
+    /// The `fast` schedule
+    #[default]
+    Fast,
+    /// The `coe` schedule
+    Coe,
+    /// The `lin` schedule
+    Lin,
+    /// The `quad` schedule
+    Quad,
+}
+
+impl From<ScheduleSpec> for PowerSchedule {
+    fn from(schedule: ScheduleSpec) -> Self {
+        match schedule {
+            ScheduleSpec::Explore => PowerSchedule::explore(),
+            ScheduleSpec::Exploit => PowerSchedule::exploit(),
+            ScheduleSpec::Fast => PowerSchedule::fast(),
+            ScheduleSpec::Coe => PowerSchedule::coe(),
+            ScheduleSpec::Lin => PowerSchedule::lin(),
+            ScheduleSpec::Quad => PowerSchedule::quad(),
+        }
+    }
+}
+
+/// The objectives of a campaign
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObjectivesSpec {
+    /// If crashes are solutions
+    #[serde(default = "default_true")]
+    pub crashes: bool,
+    /// If timeouts are solutions
+    #[serde(default = "default_true")]
+    pub timeouts: bool,
+}
+
+impl Default for ObjectivesSpec {
+    fn default() -> Self {
+        Self {
+            crashes: true,
+            timeouts: true,
+        }
+    }
+}
+
+/// A stage of a campaign
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum StageSpec {
+    /// A `CalibrationStage`, needed by power schedules
+    Calibration,
+    /// A `StdMutationalStage`
+    Mutational {
+        /// The mutators of the stage
+        #[serde(default)]
+        mutator: MutatorSpec,
+    },
+    /// A `StdPowerMutationalStage`, needing a power or weighted scheduler and a calibration stage
+    Power {
+        /// The mutators of the stage
+        #[serde(default)]
+        mutator: MutatorSpec,
+    },
+    /// A `SyncFromDiskStage`, importing new inputs from other fuzzers
+    Sync {
+        /// The directories to import from
+        dirs: Vec<PathBuf>,
+        /// How often to look for new inputs, in seconds
+        #[serde(default = "default_sync_interval_secs")]
+        interval_secs: u64,
+    },
+}
+
+impl StageSpec {
+    /// If this stage mutates inputs
+    #[must_use]
+    pub fn is_mutational(&self) -> bool {
+        matches!(self, Self::Mutational { .. } | Self::Power { .. })
+    }
+}
+
+/// The mutators of a mutational stage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MutatorSpec {
+    /// The havoc mutations
+    #[default]
+    Havoc,
+    /// The havoc mutations and the token mutations
+    HavocTokens,
+    /// The havoc mutations and the token mutations, scheduled by `MOpt`
+    Mopt,
+}
+
+/// Where to run the clients of a campaign
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LauncherSpec {
+    /// The cores to run a client on, e.g. `0-3` or `all`
+    #[serde(default = "default_cores")]
+    pub cores: String,
+    /// The port of the broker
+    #[serde(default = "default_broker_port")]
+    pub broker_port: u16,
+    /// The `ip:port` address of another broker to connect to
+    #[serde(default)]
+    pub remote_broker_addr: Option<SocketAddr>,
+    /// The configuration name of the clients. Without it, the clients never share testcases
+    /// without re-executing them.
+    #[serde(default)]
+    pub configuration: Option<String>,
+    /// Where to redirect the output of the clients to, e.g. `/dev/null`
+    #[serde(default)]
+    pub stdout_file: Option<String>,
+}
+
+impl Default for LauncherSpec {
+    fn default() -> Self {
+        Self {
+            cores: default_cores(),
+            broker_port: default_broker_port(),
+            remote_broker_addr: None,
+            configuration: None,
+            stdout_file: None,
+        }
+    }
+}
+
+impl LauncherSpec {
+    /// The cores to run a client on
+    pub fn cores(&self) -> Result<Cores, Error> {
+        Cores::from_cmdline(&self.cores)
+    }
+}
+
+fn default_stages() -> Vec<StageSpec> {
+    vec![StageSpec::Mutational {
+        mutator: MutatorSpec::Havoc,
+    }]
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_cache_size() -> usize {
+    4096
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_sync_interval_secs() -> u64 {
+    30
+}
+
+fn default_cores() -> String {
+    "0".into()
+}
+
+fn default_broker_port() -> u16 {
+    1337
+}
+
+impl CampaignSpec {
+    /// Parse and validate a campaign spec in TOML
+    pub fn from_toml_str(spec: &str) -> Result<Self, Error> {
+        let spec: Self = toml::from_str(spec)
+            .map_err(|err| Error::illegal_argument(format!("Invalid campaign spec: {err}")))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Parse and validate a campaign spec in JSON
+    pub fn from_json_str(spec: &str) -> Result<Self, Error> {
+        let spec: Self = serde_json::from_str(spec)
+            .map_err(|err| Error::illegal_argument(format!("Invalid campaign spec: {err}")))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Load and validate a campaign spec from a file, in JSON if it ends in `.json`, else in TOML
+    pub fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let spec = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json_str(&spec)
+        } else {
+            Self::from_toml_str(&spec)
+        }
+    }
+
+    /// The timeout of an execution
+    #[must_use]
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// Check the spec for combinations which can not work, beyond unknown keys
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.timeout_ms == 0 {
+            return Err(Error::illegal_argument("The timeout may not be 0"));
+        }
+        if !self.stages.iter().any(StageSpec::is_mutational) {
+            return Err(Error::illegal_argument(
+                "The campaign needs at least one mutational or power stage",
+            ));
+        }
+        let mut calibrated = false;
+        for stage in &self.stages {
+            match stage {
+                StageSpec::Calibration => calibrated = true,
+                StageSpec::Power { .. } => {
+                    if !self.scheduler.supports_power() {
+                        return Err(Error::illegal_argument(
+                            "Power stages need a power or weighted scheduler",
+                        ));
+                    }
+                    if !calibrated {
+                        return Err(Error::illegal_argument(
+                            "Power stages need a calibration stage before them",
+                        ));
+                    }
+                }
+                StageSpec::Sync {
+                    dirs,
+                    interval_secs,
+                } => {
+                    if dirs.is_empty() || *interval_secs == 0 {
+                        return Err(Error::illegal_argument(
+                            "Sync stages need directories and a non-zero interval",
+                        ));
+                    }
+                }
+                StageSpec::Mutational { .. } => (),
+            }
+        }
+        self.launcher.cores()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CampaignSpec, MutatorSpec, ScheduleSpec, SchedulerSpec, StageSpec};
+
+    const SPEC: &str = r#"
+        timeout_ms = 500
+
+        [corpus]
+        input_dirs = ["seeds"]
+        queue_dir = "out/queue"
+        solutions_dir = "out/crashes"
+
+        [scheduler]
+        kind = "power"
+        schedule = "explore"
+
+        [[stages]]
+        kind = "calibration"
+
+        [[stages]]
+        kind = "power"
+        mutator = "mopt"
+
+        [launcher]
+        cores = "0-3"
+    "#;
+
+    #[test]
+    fn test_parse_spec() {
+        let spec = CampaignSpec::from_toml_str(SPEC).unwrap();
+        assert_eq!(
+            spec.scheduler,
+            SchedulerSpec::Power {
+                schedule: ScheduleSpec::Explore
+            }
+        );
+        assert_eq!(
+            spec.stages,
+            vec![
+                StageSpec::Calibration,
+                StageSpec::Power {
+                    mutator: MutatorSpec::Mopt
+                }
+            ]
+        );
+        assert_eq!(spec.timeout_ms, 500);
+        assert_eq!(spec.corpus.cache_size, 4096);
+        assert!(spec.objectives.crashes);
+
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(CampaignSpec::from_json_str(&json).unwrap(), spec);
+    }
+
+    #[test]
+    fn test_reject_spec() {
+        // unknown key
+        let typo = SPEC.replace("timeout_ms", "timeout_msec");
+        assert!(CampaignSpec::from_toml_str(&typo).is_err());
+
+        // power stage without calibration
+        let uncalibrated = SPEC.replace("kind = \"calibration\"", "kind = \"mutational\"");
+        assert!(CampaignSpec::from_toml_str(&uncalibrated).is_err());
+
+        // power stage without power scheduler
+        let queue = SPEC.replace(
+            "kind = \"power\"\n        schedule = \"explore\"",
+            "kind = \"queue\"",
+        );
+        assert!(CampaignSpec::from_toml_str(&queue).is_err());
+    }
+}
@@ -3,13 +3,13 @@ use core::num::NonZero;
 
 use hashbrown::HashMap;
 use libafl_bolts::{
-    nonzero,
+    hash_std, nonzero,
     rands::{Rand, RomuDuoJrRand},
 };
 use pyo3::prelude::PyObject;
 
 use super::{
-    newtypes::{NTermId, RuleId},
+    newtypes::{NTermId, NodeId, RuleId},
     rule::{Rule, RuleIdOrCustom},
     tree::Tree,
 };
@@ -27,6 +27,7 @@ pub struct Context {
     rules_to_num_options: HashMap<RuleId, usize>,
     nts_to_num_options: HashMap<NTermId, usize>,
     max_len: usize,
+    fingerprint: u64,
 }
 
 impl Default for Context {
@@ -50,6 +51,7 @@ impl Context {
             rules_to_num_options: HashMap::new(),
             nts_to_num_options: HashMap::new(),
             max_len: 0,
+            fingerprint: 0,
         }
     }
 
@@ -57,6 +59,65 @@ impl Context {
         self.calc_min_len();
         self.calc_num_options();
         self.max_len = max_len + 2;
+        self.calc_fingerprint();
+    }
+
+    /// The hash of all rules, to tell if trees were derived from the same grammar, e.g. on other nodes
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    fn calc_fingerprint(&mut self) {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| rule.debug_show(self))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.fingerprint = hash_std(rules.as_bytes());
+    }
+
+    /// If the tree is a derivation of this context, i.e. it can be unparsed and mutated.
+    ///
+    /// All rules have to exist, the children of each node have to match the nonterminals of its rule, and the
+    /// parents and sizes have to match the rules. Trees of another grammar pass, if they are valid in this one.
+    #[must_use]
+    pub fn is_valid_tree(&self, tree: &Tree) -> bool {
+        let len = tree.rules.len();
+        if tree.sizes.len() != len
+            || tree.paren.len() != len
+            || tree
+                .rules
+                .iter()
+                .any(|rule| rule.id().to_i() >= self.rules.len())
+        {
+            return false;
+        }
+        if len == 0 {
+            return true;
+        }
+        let mut stack: Vec<(NTermId, NodeId)> =
+            vec![(self.get_nt(&tree.rules[0]), NodeId::from(0))];
+        for (i, rule) in tree.rules.iter().enumerate() {
+            match stack.pop() {
+                Some((nonterm, parent))
+                    if nonterm == self.get_nt(rule) && tree.paren[i] == parent => {}
+                _ => return false,
+            }
+            let node_id = NodeId::from(i);
+            for nonterm in self.get_rule(rule.id()).nonterms().iter().rev() {
+                stack.push((*nonterm, node_id));
+            }
+        }
+        if !stack.is_empty() {
+            return false;
+        }
+        let mut sizes = vec![1; len];
+        for i in (1..len).rev() {
+            sizes[tree.paren[i].to_i()] += sizes[i];
+        }
+        sizes == tree.sizes
     }
 
     #[must_use]
@@ -411,6 +472,48 @@ mod tests {
         assert_eq!(String::from_utf8(data).expect("RAND_3377050372"), "cbabc");
     }
 
+    #[test]
+    fn test_is_valid_tree() {
+        let mut rand = StdRand::new();
+        let mut ctx = Context::new();
+        let _ = ctx.add_rule("E", b"({E}+{E})");
+        let _ = ctx.add_rule("E", b"1");
+        ctx.initialize(11);
+        let mut other = Context::new();
+        let _ = other.add_rule("E", b"-{E}");
+        let _ = other.add_rule("E", b"2");
+        other.initialize(11);
+        assert_ne!(ctx.fingerprint(), other.fingerprint());
+
+        for _ in 0..100 {
+            let mut tree = Tree::from_rule_vec(vec![], &ctx);
+            tree.generate_from_nt(&mut rand, ctx.nt_id("E"), 9, &ctx);
+            assert!(ctx.is_valid_tree(&tree));
+        }
+
+        let tree = Tree::from_rule_vec(
+            vec![
+                RuleIdOrCustom::Rule(0.into()),
+                RuleIdOrCustom::Rule(1.into()),
+                RuleIdOrCustom::Rule(1.into()),
+            ],
+            &ctx,
+        );
+        assert!(ctx.is_valid_tree(&tree));
+        // The rules exist in the other grammar, but `-{E}` has a single child
+        assert!(!other.is_valid_tree(&tree));
+
+        let mut broken = tree.clone();
+        broken.sizes[0] = 2;
+        assert!(!ctx.is_valid_tree(&broken));
+        let mut broken = tree.clone();
+        broken.rules[2] = RuleIdOrCustom::Rule(2.into());
+        assert!(!ctx.is_valid_tree(&broken));
+        let mut broken = tree;
+        broken.rules.pop();
+        assert!(!ctx.is_valid_tree(&broken));
+    }
+
     #[test]
     fn test_generate_len() {
         let mut rand = StdRand::new();
@@ -1,6 +1,6 @@
 //! The [`CachedOnDiskCorpus`] stores [`Testcase`]s to disk, keeping a subset of them in memory/cache, evicting in a FIFO manner.
 
-use alloc::{collections::vec_deque::VecDeque, string::String};
+use alloc::{collections::vec_deque::VecDeque, string::String, vec::Vec};
 use core::cell::RefCell;
 use std::path::Path;
 
@@ -178,6 +178,21 @@ where
     fn store_input_from(&self, testcase: &Testcase<Self::Input>) -> Result<(), Error> {
         self.inner.store_input_from(testcase)
     }
+
+    #[inline]
+    fn mark_snapshot(&mut self) {
+        self.inner.mark_snapshot();
+    }
+
+    #[inline]
+    fn take_snapshot_inputs(&self) -> Vec<(CorpusId, I)> {
+        self.inner.take_snapshot_inputs()
+    }
+
+    #[inline]
+    fn put_snapshot_inputs(&self, inputs: Vec<(CorpusId, I)>) {
+        self.inner.put_snapshot_inputs(inputs);
+    }
 }
 
 impl<I> HasTestcase for CachedOnDiskCorpus<I>
@@ -0,0 +1,216 @@
+//! Identify testcases across nodes, independent of the [`crate::corpus::CorpusId`] they got on each node.
+//!
+//! Each [`crate::events::Event::NewTestcase`] carries the [`GlobalTestcaseId`] of its input, and the one of
+//! the testcase it was mutated from. Both are stored in the [`GlobalIdMetadata`] of the testcase, on the
+//! node that found it and on all nodes that received it, together with the client it originates from.
+//! Nodes skip received testcases whose identity is in their corpus already, see [`is_known_global_id`].
+
+use core::fmt;
+
+use hashbrown::HashSet;
+use libafl_bolts::{hash_std, impl_serdeany, ClientId};
+use serde::{Deserialize, Serialize};
+
+use crate::{corpus::Corpus, inputs::Input, state::HasCorpus, Error, HasMetadata};
+
+/// A globally unique identity of a testcase, the hash of its input.
+///
+/// Unlike a [`crate::corpus::CorpusId`], which depends on the order testcases were added to a corpus, it is the same on all nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[repr(transparent)]
+pub struct GlobalTestcaseId(pub u64);
+
+impl GlobalTestcaseId {
+    /// The identity of the given input
+    pub fn of<I>(input: &I) -> Result<Self, Error>
+    where
+        I: Input,
+    {
+        Ok(Self(hash_std(&postcard::to_allocvec(input)?)))
+    }
+}
+
+impl fmt::Display for GlobalTestcaseId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// The global identity and lineage of a testcase
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GlobalIdMetadata {
+    /// The identity of the testcase
+    pub id: GlobalTestcaseId,
+    /// The client which found the testcase, `None` if it was found by this client
+    pub origin: Option<ClientId>,
+    /// The identity of the testcase it was mutated from, if known
+    pub parent: Option<GlobalTestcaseId>,
+}
+
+impl_serdeany!(GlobalIdMetadata);
+
+impl GlobalIdMetadata {
+    /// The identity of an input about to be added to the corpus.
+    ///
+    /// For the testcase announced by [`expect_remote_testcase`], this is the identity it was sent with.
+    /// Otherwise, the input was found by this client, while fuzzing the current testcase of the corpus.
+    pub fn local<I, S>(state: &S, input: &I) -> Result<Self, Error>
+    where
+        I: Input,
+        S: HasCorpus + HasMetadata,
+    {
+        let id = GlobalTestcaseId::of(input)?;
+        if let Some(remote) = state
+            .metadata_map()
+            .get::<GlobalIdsMetadata>()
+            .and_then(|ids| ids.remote)
+            .filter(|remote| remote.id == id)
+        {
+            return Ok(remote);
+        }
+        let parent = (*state.corpus().current()).and_then(|id| {
+            state.corpus().get(id).ok().and_then(|testcase| {
+                testcase
+                    .borrow()
+                    .metadata_map()
+                    .get::<GlobalIdMetadata>()
+                    .map(|meta| meta.id)
+            })
+        });
+        Ok(Self {
+            id,
+            origin: None,
+            parent,
+        })
+    }
+}
+
+/// The identities of all testcases added to the corpus of this client, to skip duplicates sent by other nodes
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GlobalIdsMetadata {
+    ids: HashSet<GlobalTestcaseId>,
+    remote: Option<GlobalIdMetadata>,
+}
+
+impl_serdeany!(GlobalIdsMetadata);
+
+impl GlobalIdsMetadata {
+    /// Add an identity, returns `false` if it was known already
+    pub fn insert(&mut self, id: GlobalTestcaseId) -> bool {
+        self.ids.insert(id)
+    }
+
+    /// If a testcase with this identity was added to the corpus
+    #[must_use]
+    pub fn contains(&self, id: GlobalTestcaseId) -> bool {
+        self.ids.contains(&id)
+    }
+
+    /// The number of known identities
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// If no identities are known
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+/// Returns `true` if a testcase with this identity was added to the corpus of this client before
+pub fn is_known_global_id<S>(state: &S, id: GlobalTestcaseId) -> bool
+where
+    S: HasMetadata,
+{
+    state
+        .metadata_map()
+        .get::<GlobalIdsMetadata>()
+        .is_some_and(|ids| ids.contains(id))
+}
+
+/// Announce that the next input evaluated is a testcase received from another client, with the given identity
+pub fn expect_remote_testcase<S>(state: &mut S, meta: GlobalIdMetadata)
+where
+    S: HasMetadata,
+{
+    state
+        .metadata_or_insert_with(GlobalIdsMetadata::default)
+        .remote = Some(meta);
+}
+
+/// Forget the testcase announced by [`expect_remote_testcase`], once it was evaluated
+pub fn clear_remote_testcase<S>(state: &mut S)
+where
+    S: HasMetadata,
+{
+    if let Some(ids) = state.metadata_map_mut().get_mut::<GlobalIdsMetadata>() {
+        ids.remote = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::ClientId;
+
+    use crate::{
+        corpus::{
+            global_id::{
+                clear_remote_testcase, expect_remote_testcase, is_known_global_id,
+                GlobalIdMetadata, GlobalIdsMetadata,
+            },
+            Corpus, GlobalTestcaseId, Testcase,
+        },
+        inputs::BytesInput,
+        state::{HasCorpus, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_global_id() {
+        let input = BytesInput::new(vec![1, 2, 3]);
+        let id = GlobalTestcaseId::of(&input).unwrap();
+        assert_eq!(id, GlobalTestcaseId::of(&input.clone()).unwrap());
+        assert_ne!(id, GlobalTestcaseId::of(&BytesInput::new(vec![3])).unwrap());
+
+        let mut state = StdState::nop::<BytesInput>().unwrap();
+        let meta = GlobalIdMetadata::local(&state, &input).unwrap();
+        assert_eq!(meta.id, id);
+        assert_eq!(meta.origin, None);
+        assert!(!is_known_global_id(&state, id));
+
+        let mut testcase = Testcase::new(input.clone());
+        testcase.add_metadata(meta);
+        let corpus_id = state.corpus_mut().add(testcase).unwrap();
+        *state.corpus_mut().current_mut() = Some(corpus_id);
+        state
+            .metadata_or_insert_with(GlobalIdsMetadata::default)
+            .insert(id);
+        assert!(is_known_global_id(&state, id));
+
+        // A mutant of the current testcase
+        let child = BytesInput::new(vec![1, 2]);
+        let child_meta = GlobalIdMetadata::local(&state, &child).unwrap();
+        assert_eq!(child_meta.parent, Some(id));
+
+        // The same input, received from another client
+        let remote = GlobalIdMetadata {
+            id: child_meta.id,
+            origin: Some(ClientId(7)),
+            parent: None,
+        };
+        expect_remote_testcase(&mut state, remote);
+        assert_eq!(GlobalIdMetadata::local(&state, &child).unwrap(), remote);
+        clear_remote_testcase(&mut state);
+        assert_eq!(GlobalIdMetadata::local(&state, &child).unwrap(), child_meta);
+    }
+}
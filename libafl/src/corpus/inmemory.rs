@@ -312,6 +312,9 @@ impl<I> TestcaseStorage<I> {
 pub struct InMemoryCorpus<I> {
     storage: TestcaseStorage<I>,
     current: Option<CorpusId>,
+    /// The testcases with an id below this are part of the base snapshot, see [`Corpus::mark_snapshot`]
+    #[serde(default)]
+    snapshot_id: usize,
 }
 
 impl<I> Corpus for InMemoryCorpus<I> {
@@ -353,6 +356,10 @@ impl<I> Corpus for InMemoryCorpus<I> {
     /// Replaces the testcase at the given id
     #[inline]
     fn replace(&mut self, id: CorpusId, testcase: Testcase<I>) -> Result<Testcase<I>, Error> {
+        if id.0 < self.snapshot_id {
+            // The base snapshot holds the old input, so deltas have to contain all inputs
+            self.snapshot_id = 0;
+        }
         self.storage.enabled.replace(id, testcase).ok_or_else(|| {
             Error::key_not_found(format!("Index {id} not found, could not replace."))
         })
@@ -452,6 +459,39 @@ impl<I> Corpus for InMemoryCorpus<I> {
     fn store_input_from(&self, _: &Testcase<Self::Input>) -> Result<(), Error> {
         Ok(())
     }
+
+    fn mark_snapshot(&mut self) {
+        self.snapshot_id = self.storage.peek_free_id().0;
+    }
+
+    fn take_snapshot_inputs(&self) -> Vec<(CorpusId, I)> {
+        let mut inputs = Vec::new();
+        for map in [&self.storage.enabled, &self.storage.disabled] {
+            let mut cur = map.first();
+            while let Some(id) = cur {
+                if id.0 < self.snapshot_id {
+                    if let Some(testcase) = map.get(id) {
+                        if let Some(input) = testcase.borrow_mut().input_mut().take() {
+                            inputs.push((id, input));
+                        }
+                    }
+                }
+                cur = map.next(id);
+            }
+        }
+        inputs
+    }
+
+    fn put_snapshot_inputs(&self, inputs: Vec<(CorpusId, I)>) {
+        for (id, input) in inputs {
+            if let Ok(testcase) = self.get_from_all(id) {
+                let mut testcase = testcase.borrow_mut();
+                if testcase.input().is_none() {
+                    *testcase.input_mut() = Some(input);
+                }
+            }
+        }
+    }
 }
 
 impl<I> HasTestcase for InMemoryCorpus<I> {
@@ -478,6 +518,7 @@ impl<I> InMemoryCorpus<I> {
         Self {
             storage: TestcaseStorage::new(),
             current: None,
+            snapshot_id: 0,
         }
     }
 }
@@ -4,7 +4,7 @@
 //! For a lower memory footprint, consider using [`crate::corpus::CachedOnDiskCorpus`]
 //! which only stores a certain number of [`Testcase`]s and removes additional ones in a FIFO manner.
 
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 use core::cell::RefCell;
 #[cfg(feature = "std")]
 use std::{fs, fs::File, io::Write};
@@ -14,11 +14,10 @@ use std::{
     path::{Path, PathBuf},
 };
 
-#[cfg(feature = "gzip")]
-use libafl_bolts::compress::GzipCompressor;
 use serde::{Deserialize, Serialize};
 
 use super::{
+    migration::OnDiskMetadataMigrator,
     ondisk::{OnDiskMetadata, OnDiskMetadataFormat},
     HasTestcase,
 };
@@ -58,6 +57,10 @@ pub struct InMemoryOnDiskCorpus<I> {
     meta_format: Option<OnDiskMetadataFormat>,
     prefix: Option<String>,
     locking: bool,
+    /// Migrates the metadata files of earlier versions when they are loaded.
+    /// Not serialized, a restored corpus uses the built-in and the registered migrations.
+    #[serde(skip)]
+    migrator: OnDiskMetadataMigrator,
 }
 
 impl<I> Corpus for InMemoryOnDiskCorpus<I>
@@ -210,6 +213,21 @@ where
         };
         input.to_file(file_path)
     }
+
+    #[inline]
+    fn mark_snapshot(&mut self) {
+        self.inner.mark_snapshot();
+    }
+
+    #[inline]
+    fn take_snapshot_inputs(&self) -> Vec<(CorpusId, I)> {
+        self.inner.take_snapshot_inputs()
+    }
+
+    #[inline]
+    fn put_snapshot_inputs(&self, inputs: Vec<(CorpusId, I)>) {
+        self.inner.put_snapshot_inputs(inputs);
+    }
 }
 
 impl<I> HasTestcase for InMemoryOnDiskCorpus<I>
@@ -313,9 +331,22 @@ impl<I> InMemoryOnDiskCorpus<I> {
             meta_format,
             prefix,
             locking,
+            migrator: OnDiskMetadataMigrator::new(),
         })
     }
 
+    /// Sets the [`OnDiskMetadataMigrator`] used to load the metadata files written by earlier versions,
+    /// e.g. to add migrations of your own metadata types.
+    ///
+    /// The migrator is not serialized with the corpus, so a corpus restored by a restarting event manager is back
+    /// to the default migrator. Register the migrations of your own metadata types with
+    /// [`crate::corpus::migration::register_metadata_migration`] instead, to keep them after restarts.
+    #[must_use]
+    pub fn with_migrator(mut self, migrator: OnDiskMetadataMigrator) -> Self {
+        self.migrator = migrator;
+        self
+    }
+
     /// Sets the filename for a [`Testcase`].
     /// If an error gets returned from the corpus (i.e., file exists), we'll have to retry with a different filename.
     #[inline]
@@ -410,28 +441,30 @@ impl<I> InMemoryOnDiskCorpus<I> {
         }
         *testcase.filename_mut() = Some(file_name);
 
-        if self.meta_format.is_some() {
+        if let Some(meta_format) = &self.meta_format {
             let metafile_name = format!(".{}.metadata", testcase.filename().as_ref().unwrap());
             let metafile_path = self.dir_path.join(&metafile_name);
             let mut tmpfile_path = metafile_path.clone();
             tmpfile_path.set_file_name(format!(".{metafile_name}.tmp",));
 
-            let ondisk_meta = OnDiskMetadata {
-                metadata: testcase.metadata_map(),
-                exec_time: testcase.exec_time(),
-            };
+            // A testcase added again, e.g. in a resumed campaign, keeps the metadata of its earlier run
+            if testcase.metadata_map().is_empty() && metafile_path.exists() {
+                match self.migrator.load(&metafile_path, meta_format) {
+                    Ok(loaded) => {
+                        *testcase.metadata_map_mut() = loaded.metadata;
+                        if testcase.exec_time().is_none() {
+                            *testcase.exec_time_mut() = loaded.exec_time;
+                        }
+                    }
+                    Err(err) => log::warn!("Overwriting unreadable metadata: {err}"),
+                }
+            }
+
+            let ondisk_meta = OnDiskMetadata::new(testcase.metadata_map(), testcase.exec_time());
 
             let mut tmpfile = File::create(&tmpfile_path)?;
 
-            let serialized = match self.meta_format.as_ref().unwrap() {
-                OnDiskMetadataFormat::Postcard => postcard::to_allocvec(&ondisk_meta)?,
-                OnDiskMetadataFormat::Json => serde_json::to_vec(&ondisk_meta)?,
-                OnDiskMetadataFormat::JsonPretty => serde_json::to_vec_pretty(&ondisk_meta)?,
-                #[cfg(feature = "gzip")]
-                OnDiskMetadataFormat::JsonGzip => {
-                    GzipCompressor::new().compress(&serde_json::to_vec_pretty(&ondisk_meta)?)
-                }
-            };
+            let serialized = meta_format.to_bytes(&ondisk_meta)?;
             tmpfile.write_all(&serialized)?;
             fs::rename(&tmpfile_path, &metafile_path)?;
             *testcase.metadata_path_mut() = Some(metafile_path);
@@ -456,6 +489,25 @@ impl<I> InMemoryOnDiskCorpus<I> {
         Ok(())
     }
 
+    /// Restores the metadata of a [`Testcase`] from its `.metadata` file, migrating files written by older
+    /// versions of `LibAFL` with the [`OnDiskMetadataMigrator`] of this corpus.
+    ///
+    /// Returns `false` if the corpus stores no metadata, or the testcase has no metadata file.
+    pub fn load_metadata(&self, testcase: &mut Testcase<I>) -> Result<bool, Error> {
+        let (Some(meta_format), Some(filename)) = (&self.meta_format, testcase.filename()) else {
+            return Ok(false);
+        };
+        let metafile_path = self.dir_path.join(format!(".{filename}.metadata"));
+        if !metafile_path.exists() {
+            return Ok(false);
+        }
+        self.migrator
+            .load(&metafile_path, meta_format)?
+            .apply_to(testcase);
+        *testcase.metadata_path_mut() = Some(metafile_path);
+        Ok(true)
+    }
+
     /// Path to the corpus directory associated with this corpus
     #[must_use]
     pub fn dir_path(&self) -> &PathBuf {
@@ -465,9 +517,20 @@ impl<I> InMemoryOnDiskCorpus<I> {
 
 #[cfg(test)]
 mod tests {
-    use std::{env, fs, io::Write};
+    use core::time::Duration;
+    use std::{env, fs, io::Write, process};
+
+    use libafl_bolts::serdeany::SerdeAnyMap;
+    use serde_json::json;
 
-    use super::{create_new, try_create_new};
+    use super::{create_new, try_create_new, InMemoryOnDiskCorpus};
+    use crate::{
+        corpus::{
+            migration::register_metadata_migration, ondisk::OnDiskMetadataFormat, Corpus, Testcase,
+        },
+        inputs::BytesInput,
+        Error,
+    };
 
     #[test]
     fn test() {
@@ -485,4 +548,93 @@ mod tests {
         drop(f);
         fs::remove_file(path).unwrap();
     }
+
+    #[test]
+    fn test_migrate_metadata_on_add() {
+        let dir = env::temp_dir().join(format!("libafl_inmemory_ondisk_{}", process::id()));
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // Metadata written before the envelope was versioned
+        let unversioned = json!({
+            "metadata": serde_json::to_value(SerdeAnyMap::new()).unwrap(),
+            "exec_time": { "secs": 1, "nanos": 0 },
+        });
+        let metafile_path = dir.join(".testcase.metadata");
+        fs::write(&metafile_path, serde_json::to_vec(&unversioned).unwrap()).unwrap();
+
+        let mut corpus = InMemoryOnDiskCorpus::<BytesInput>::with_meta_format_and_prefix(
+            &dir,
+            Some(OnDiskMetadataFormat::Json),
+            None,
+            false,
+        )
+        .unwrap();
+        let id = corpus
+            .add(Testcase::with_filename(
+                BytesInput::new(vec![0; 4]),
+                "testcase".into(),
+            ))
+            .unwrap();
+        assert_eq!(
+            *corpus.get(id).unwrap().borrow().exec_time(),
+            Some(Duration::from_secs(1))
+        );
+        let rewritten: serde_json::Value =
+            serde_json::from_slice(&fs::read(&metafile_path).unwrap()).unwrap();
+        assert_eq!(rewritten["version"], json!(1));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn rename_old_exec_time(value: &mut serde_json::Value) -> Result<(), Error> {
+        if let Some(old) = value
+            .as_object_mut()
+            .and_then(|obj| obj.remove("old_exec_time"))
+        {
+            value["exec_time"] = old;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_registered_migration_after_restore() {
+        let dir = env::temp_dir().join(format!("libafl_inmemory_ondisk_restore_{}", process::id()));
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // Metadata of a user type, renamed since it was written
+        let old = json!({
+            "metadata": serde_json::to_value(SerdeAnyMap::new()).unwrap(),
+            "old_exec_time": { "secs": 2, "nanos": 0 },
+        });
+        fs::write(
+            dir.join(".testcase.metadata"),
+            serde_json::to_vec(&old).unwrap(),
+        )
+        .unwrap();
+
+        register_metadata_migration(0, rename_old_exec_time);
+        let corpus = InMemoryOnDiskCorpus::<BytesInput>::with_meta_format_and_prefix(
+            &dir,
+            Some(OnDiskMetadataFormat::Json),
+            None,
+            false,
+        )
+        .unwrap();
+        // The corpus of a restarted fuzzer is restored from the serialized state
+        let mut corpus: InMemoryOnDiskCorpus<BytesInput> =
+            postcard::from_bytes(&postcard::to_allocvec(&corpus).unwrap()).unwrap();
+        let id = corpus
+            .add(Testcase::with_filename(
+                BytesInput::new(vec![0; 4]),
+                "testcase".into(),
+            ))
+            .unwrap();
+        assert_eq!(
+            *corpus.get(id).unwrap().borrow().exec_time(),
+            Some(Duration::from_secs(2))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
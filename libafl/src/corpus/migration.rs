@@ -0,0 +1,304 @@
+//! Reading the on-disk metadata of [`Testcase`]s written by older versions of `LibAFL`, see
+//! [`OnDiskMetadataMigrator`].
+//!
+//! The `.metadata` files of the on-disk corpora are wrapped in a versioned [`OnDiskMetadata`] envelope. When the
+//! format changes, [`ONDISK_METADATA_VERSION`] is bumped, and a migration from the previous version is added, so
+//! a campaign resumed with a newer `LibAFL` keeps the scheduling information of its testcases instead of failing
+//! to deserialize them.
+
+use alloc::{format, vec::Vec};
+use core::time::Duration;
+use std::{fs, path::Path, sync::RwLock};
+
+#[cfg(feature = "gzip")]
+use libafl_bolts::compress::GzipCompressor;
+use libafl_bolts::serdeany::SerdeAnyMap;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    corpus::{
+        ondisk::{OnDiskMetadata, OnDiskMetadataFormat, ONDISK_METADATA_VERSION},
+        Testcase,
+    },
+    Error, HasMetadata,
+};
+
+/// A migration of the JSON of a metadata file from one version to the next
+pub type MetadataMigration = fn(&mut Value) -> Result<(), Error>;
+
+/// The metadata of a [`Testcase`], read from disk
+#[derive(Debug, Deserialize)]
+pub struct LoadedOnDiskMetadata {
+    /// The version the file was written in, `0` for files from before the envelope was versioned
+    #[serde(default)]
+    pub version: u32,
+    /// The dynamic metadata of the testcase
+    pub metadata: SerdeAnyMap,
+    /// The exec time of the testcase
+    #[serde(default)]
+    pub exec_time: Option<Duration>,
+}
+
+impl LoadedOnDiskMetadata {
+    /// Restores the metadata and the exec time of the testcase
+    pub fn apply_to<I>(self, testcase: &mut Testcase<I>) {
+        *testcase.metadata_map_mut() = self.metadata;
+        *testcase.exec_time_mut() = self.exec_time;
+    }
+}
+
+/// The migrations of the metadata types of the user, for all [`OnDiskMetadataMigrator`]s of this process
+static REGISTERED_MIGRATIONS: RwLock<Vec<(u32, MetadataMigration)>> = RwLock::new(Vec::new());
+
+/// Registers a migration of metadata files of version `from_version` to the next version, used by all
+/// [`OnDiskMetadataMigrator`]s of this process after their own migrations.
+///
+/// Unlike the migrations added with [`OnDiskMetadataMigrator::with_migration`], these also apply to corpora restored
+/// after a restart: the restarting event managers serialize the corpus with the state, but not its migrator.
+/// Register the migrations at startup, in each client, before the state is created or restored.
+pub fn register_metadata_migration(from_version: u32, migration: MetadataMigration) {
+    REGISTERED_MIGRATIONS
+        .write()
+        .unwrap()
+        .push((from_version, migration));
+}
+
+/// The envelope of version `0` only gained the version, the contents stayed the same
+#[allow(clippy::unnecessary_wraps)]
+fn migrate_unversioned(_value: &mut Value) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Reads on-disk metadata files of any version, migrating them to the current [`ONDISK_METADATA_VERSION`].
+///
+/// The [`crate::corpus::InMemoryOnDiskCorpus`] migrates the metadata files it loads with one, see
+/// [`crate::corpus::InMemoryOnDiskCorpus::with_migrator`].
+///
+/// The built-in migrations of `LibAFL` run first; add migrations for changes of your own metadata types with
+/// [`register_metadata_migration`], or with [`OnDiskMetadataMigrator::with_migration`] for a migrator that is not
+/// part of a restarted state. Migrations work on the JSON of the files, so files in the
+/// [`OnDiskMetadataFormat::Postcard`] format can only be read in the current version.
+#[derive(Debug, Clone)]
+pub struct OnDiskMetadataMigrator {
+    /// The migrations from each version to the next, in order
+    migrations: Vec<(u32, MetadataMigration)>,
+}
+
+impl Default for OnDiskMetadataMigrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OnDiskMetadataMigrator {
+    /// Creates a new [`OnDiskMetadataMigrator`], with the built-in migrations
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            migrations: vec![(0, migrate_unversioned as MetadataMigration)],
+        }
+    }
+
+    /// Adds a migration of files of version `from_version` to the next version
+    #[must_use]
+    pub fn with_migration(mut self, from_version: u32, migration: MetadataMigration) -> Self {
+        self.migrations.push((from_version, migration));
+        self
+    }
+
+    /// Migrates the JSON of a metadata file to the current version, returning the version it was in
+    pub fn migrate(&self, value: &mut Value) -> Result<u32, Error> {
+        let version = match value.get("version") {
+            None => 0,
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| Error::serialize(format!("Invalid metadata version {version}")))?,
+        };
+        if version > ONDISK_METADATA_VERSION {
+            return Err(Error::serialize(format!(
+                "Metadata of version {version} was written by a newer LibAFL, which writes version {ONDISK_METADATA_VERSION}"
+            )));
+        }
+        let registered = REGISTERED_MIGRATIONS.read().unwrap();
+        for from_version in version..ONDISK_METADATA_VERSION {
+            let mut migrated = false;
+            for (_, migration) in self
+                .migrations
+                .iter()
+                .chain(registered.iter())
+                .filter(|(version, _)| *version == from_version)
+            {
+                migration(value)?;
+                migrated = true;
+            }
+            if !migrated {
+                return Err(Error::serialize(format!(
+                    "No migration of metadata from version {from_version}"
+                )));
+            }
+        }
+        Ok(version)
+    }
+
+    /// Deserializes the metadata of a file in the given format, migrating it if needed
+    pub fn decode(
+        &self,
+        bytes: &[u8],
+        format: &OnDiskMetadataFormat,
+    ) -> Result<LoadedOnDiskMetadata, Error> {
+        let json = match format {
+            OnDiskMetadataFormat::Postcard => {
+                let loaded: LoadedOnDiskMetadata = postcard::from_bytes(bytes)?;
+                if loaded.version != ONDISK_METADATA_VERSION {
+                    return Err(Error::serialize(format!(
+                        "Postcard metadata of version {} can not be migrated to version {ONDISK_METADATA_VERSION}",
+                        loaded.version
+                    )));
+                }
+                return Ok(loaded);
+            }
+            OnDiskMetadataFormat::Json | OnDiskMetadataFormat::JsonPretty => bytes.to_vec(),
+            #[cfg(feature = "gzip")]
+            OnDiskMetadataFormat::JsonGzip => GzipCompressor::new().decompress(bytes)?,
+        };
+        let mut value: Value = serde_json::from_slice(&json)?;
+        let version = self.migrate(&mut value)?;
+        let mut loaded: LoadedOnDiskMetadata = serde_json::from_value(value).map_err(|err| {
+            Error::serialize(format!(
+                "Could not deserialize metadata migrated from version {version}: {err}"
+            ))
+        })?;
+        loaded.version = version;
+        Ok(loaded)
+    }
+
+    /// Reads the metadata file at `path`, migrating it if needed
+    pub fn load<P>(
+        &self,
+        path: P,
+        format: &OnDiskMetadataFormat,
+    ) -> Result<LoadedOnDiskMetadata, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        self.decode(&fs::read(path)?, format)
+            .map_err(|err| Error::serialize(format!("{}: {err}", path.display())))
+    }
+
+    /// Rewrites the metadata file at `path` in the current version, if it is older.
+    ///
+    /// Returns if the file was migrated.
+    pub fn migrate_file<P>(&self, path: P, format: &OnDiskMetadataFormat) -> Result<bool, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let loaded = self.load(path, format)?;
+        if loaded.version == ONDISK_METADATA_VERSION {
+            return Ok(false);
+        }
+        let ondisk_meta = OnDiskMetadata::new(&loaded.metadata, &loaded.exec_time);
+        // Write to a temporary file first, so an interrupted migration leaves the old file intact
+        let mut tmpfile_path = path.to_path_buf();
+        tmpfile_path.as_mut_os_string().push(".tmp");
+        fs::write(&tmpfile_path, format.to_bytes(&ondisk_meta)?)?;
+        fs::rename(&tmpfile_path, path)?;
+        Ok(true)
+    }
+
+    /// Rewrites all `.<testcase>.metadata` files of the corpus directory `dir` in the current version.
+    ///
+    /// Returns the number of migrated files.
+    pub fn migrate_dir<P>(&self, dir: P, format: &OnDiskMetadataFormat) -> Result<usize, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut migrated = 0;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_metadata = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with('.') && name.ends_with(".metadata"));
+            if is_metadata && path.is_file() && self.migrate_file(&path, format)? {
+                migrated += 1;
+            }
+        }
+        Ok(migrated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{env, fs, process};
+
+    use libafl_bolts::serdeany::SerdeAnyMap;
+    use serde_json::{json, Value};
+
+    use super::OnDiskMetadataMigrator;
+    use crate::{
+        corpus::ondisk::{OnDiskMetadataFormat, ONDISK_METADATA_VERSION},
+        Error,
+    };
+
+    fn unversioned() -> Value {
+        json!({
+            "metadata": serde_json::to_value(SerdeAnyMap::new()).unwrap(),
+            "exec_time": { "secs": 1, "nanos": 0 },
+        })
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn drop_exec_time(value: &mut Value) -> Result<(), Error> {
+        value["exec_time"] = Value::Null;
+        Ok(())
+    }
+
+    #[test]
+    fn test_metadata_migration() {
+        let migrator = OnDiskMetadataMigrator::new();
+        let bytes = serde_json::to_vec(&unversioned()).unwrap();
+        let loaded = migrator
+            .decode(&bytes, &OnDiskMetadataFormat::Json)
+            .unwrap();
+        assert_eq!(loaded.version, 0);
+        assert_eq!(loaded.exec_time, Some(Duration::from_secs(1)));
+
+        let migrator = migrator.with_migration(0, drop_exec_time);
+        let loaded = migrator
+            .decode(&bytes, &OnDiskMetadataFormat::Json)
+            .unwrap();
+        assert_eq!(loaded.exec_time, None);
+
+        let mut newer = unversioned();
+        newer["version"] = json!(ONDISK_METADATA_VERSION + 1);
+        let bytes = serde_json::to_vec(&newer).unwrap();
+        assert!(migrator
+            .decode(&bytes, &OnDiskMetadataFormat::Json)
+            .is_err());
+    }
+
+    #[test]
+    fn test_metadata_migrate_dir() {
+        let dir = env::temp_dir().join(format!("libafl_migration_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".testcase.metadata");
+        fs::write(&path, serde_json::to_vec(&unversioned()).unwrap()).unwrap();
+        fs::write(dir.join("testcase"), b"input").unwrap();
+
+        let migrator = OnDiskMetadataMigrator::new();
+        let format = OnDiskMetadataFormat::JsonPretty;
+        assert_eq!(migrator.migrate_dir(&dir, &format).unwrap(), 1);
+        let loaded = migrator.load(&path, &format).unwrap();
+        assert_eq!(loaded.version, ONDISK_METADATA_VERSION);
+        assert_eq!(loaded.exec_time, Some(Duration::from_secs(1)));
+        assert_eq!(migrator.migrate_dir(&dir, &format).unwrap(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
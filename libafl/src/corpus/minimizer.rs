@@ -19,13 +19,17 @@ use crate::{
     executors::{Executor, HasObservers},
     monitors::{AggregatorOps, UserStats, UserStatsValue},
     observers::{MapObserver, ObserversTuple},
-    schedulers::{LenTimeMulTestcaseScore, RemovableScheduler, Scheduler, TestcaseScore},
+    schedulers::{
+        is_pinned, LenTimeMulTestcaseScore, RemovableScheduler, Scheduler, TestcaseScore,
+    },
     state::{HasCorpus, HasExecutions, UsesState},
     Error, HasMetadata, HasScheduler,
 };
 
 /// Minimizes a corpus according to coverage maps, weighting by the specified `TestcaseScore`.
 ///
+/// Pinned testcases are always kept, see [`crate::schedulers::pinned`].
+///
 /// Algorithm based on WMOPT: <https://hexhive.epfl.ch/publications/files/21ISSTA2.pdf>
 #[derive(Debug)]
 pub struct MapCorpusMinimizer<C, E, O, T, TS> {
@@ -139,6 +143,7 @@ where
                     time: current_time(),
                     phantom: PhantomData,
                     executions,
+                    host_metrics: None,
                 },
             )?;
 
@@ -159,6 +164,11 @@ where
                 }
             }
 
+            // Pinned seeds are never removed
+            if is_pinned(state, id) {
+                opt.assert(&seed_expr);
+            }
+
             // Keep track of that seed's index and weight
             seed_exprs.insert(seed_expr, (id, weight));
 
@@ -3,6 +3,9 @@
 pub mod testcase;
 pub use testcase::{HasTestcase, SchedulerTestcaseMetadata, Testcase};
 
+pub mod global_id;
+pub use global_id::{GlobalIdMetadata, GlobalTestcaseId};
+
 pub mod inmemory;
 pub use inmemory::InMemoryCorpus;
 
@@ -16,19 +19,34 @@ pub mod ondisk;
 #[cfg(feature = "std")]
 pub use ondisk::OnDiskCorpus;
 
+#[cfg(feature = "std")]
+pub mod migration;
+#[cfg(feature = "std")]
+pub use migration::OnDiskMetadataMigrator;
+
 #[cfg(feature = "std")]
 pub mod cached;
 #[cfg(feature = "std")]
 pub use cached::CachedOnDiskCorpus;
 
+#[cfg(feature = "std")]
+pub mod spilling;
+#[cfg(feature = "std")]
+pub use spilling::SpillingCorpus;
+
 #[cfg(all(feature = "cmin", unix))]
 pub mod minimizer;
+use alloc::vec::Vec;
 use core::{cell::RefCell, fmt};
 
 pub mod nop;
 #[cfg(all(feature = "cmin", unix))]
 pub use minimizer::*;
 pub use nop::NopCorpus;
+#[cfg(feature = "std")]
+pub mod seed_selection;
+#[cfg(feature = "std")]
+pub use seed_selection::{SeedSelectionMetadata, SeedSelectionProgressMetadata, SeedSelector};
 use serde::{Deserialize, Serialize};
 
 use crate::Error;
@@ -189,6 +207,12 @@ pub trait Corpus: Sized {
     /// Method to store the input of this `Testcase` to persistent storage, if necessary.
     fn store_input_from(&self, testcase: &Testcase<Self::Input>) -> Result<(), Error>;
 
+    /// Hints that the input of the testcase is used soon, e.g. after the scheduler picked it.
+    /// Corpora loading inputs from disk can start reading it in the background.
+    fn prefetch(&self, _id: CorpusId) -> Result<(), Error> {
+        Ok(())
+    }
+
     /// Loads the `Input` for a given [`CorpusId`] from the [`Corpus`], and returns the clone.
     fn cloned_input_for_id(&self, id: CorpusId) -> Result<Self::Input, Error>
     where
@@ -197,6 +221,21 @@ pub trait Corpus: Sized {
         let mut testcase = self.get(id)?.borrow_mut();
         Ok(testcase.load_input(self)?.clone())
     }
+
+    /// Marks all testcases currently in the corpus as part of the base snapshot.
+    ///
+    /// Inputs never change once added, so delta snapshots of the state only need the inputs added since the
+    /// base snapshot, see `libafl_bolts::staterestore::DeltaSerialize`.
+    /// Corpora that do not implement this are fully serialized in every delta.
+    fn mark_snapshot(&mut self) {}
+
+    /// Takes the inputs of the testcases in the base snapshot out of the corpus
+    fn take_snapshot_inputs(&self) -> Vec<(CorpusId, Self::Input)> {
+        Vec::new()
+    }
+
+    /// Puts back the inputs of [`Corpus::take_snapshot_inputs`] into the testcases that have no input
+    fn put_snapshot_inputs(&self, _inputs: Vec<(CorpusId, Self::Input)>) {}
 }
 
 /// Trait for types which track the current corpus index
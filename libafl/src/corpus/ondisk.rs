@@ -5,13 +5,15 @@
 //! For any other occasions, consider using [`crate::corpus::CachedOnDiskCorpus`]
 //! which stores a certain number of [`Testcase`]s in memory and removes additional ones in a FIFO manner.
 
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 use core::{
     cell::{Ref, RefCell, RefMut},
     time::Duration,
 };
 use std::path::{Path, PathBuf};
 
+#[cfg(feature = "gzip")]
+use libafl_bolts::compress::GzipCompressor;
 use libafl_bolts::serdeany::SerdeAnyMap;
 use serde::{Deserialize, Serialize};
 
@@ -37,15 +39,60 @@ pub enum OnDiskMetadataFormat {
     JsonGzip,
 }
 
+#[cfg(feature = "std")]
+impl OnDiskMetadataFormat {
+    /// Serializes the metadata in this format
+    pub fn to_bytes<T>(&self, metadata: &T) -> Result<Vec<u8>, Error>
+    where
+        T: Serialize,
+    {
+        Ok(match self {
+            OnDiskMetadataFormat::Postcard => postcard::to_allocvec(metadata)?,
+            OnDiskMetadataFormat::Json => serde_json::to_vec(metadata)?,
+            OnDiskMetadataFormat::JsonPretty => serde_json::to_vec_pretty(metadata)?,
+            #[cfg(feature = "gzip")]
+            OnDiskMetadataFormat::JsonGzip => {
+                GzipCompressor::new().compress(&serde_json::to_vec_pretty(metadata)?)
+            }
+        })
+    }
+
+    /// If the format is self-describing JSON, which can be migrated between versions
+    #[must_use]
+    pub fn is_json(&self) -> bool {
+        !matches!(self, OnDiskMetadataFormat::Postcard)
+    }
+}
+
+/// The version of the [`OnDiskMetadata`] envelope written by this version of `LibAFL`.
+///
+/// Files written before the envelope was versioned are version `0`. See [`crate::corpus::migration`] to read
+/// files of older versions.
+pub const ONDISK_METADATA_VERSION: u32 = 1;
+
 /// The [`Testcase`] metadata that'll be stored to disk
 #[derive(Debug, Serialize)]
 pub struct OnDiskMetadata<'a> {
+    /// The version of the envelope, [`ONDISK_METADATA_VERSION`] for new files
+    pub version: u32,
     /// The dynamic metadata [`SerdeAnyMap`] stored to disk
     pub metadata: &'a SerdeAnyMap,
     /// The exec time for this [`Testcase`]
     pub exec_time: &'a Option<Duration>,
 }
 
+impl<'a> OnDiskMetadata<'a> {
+    /// Creates the [`OnDiskMetadata`] of a [`Testcase`], in the current [`ONDISK_METADATA_VERSION`]
+    #[must_use]
+    pub fn new(metadata: &'a SerdeAnyMap, exec_time: &'a Option<Duration>) -> Self {
+        Self {
+            version: ONDISK_METADATA_VERSION,
+            metadata,
+            exec_time,
+        }
+    }
+}
+
 /// A corpus able to store [`Testcase`]s to disk, and load them from disk, when they are being used.
 ///
 /// Metadata is written to a `.<filename>.metadata` file in the same folder by default.
@@ -173,6 +220,21 @@ where
     fn store_input_from(&self, testcase: &Testcase<Self::Input>) -> Result<(), Error> {
         self.inner.store_input_from(testcase)
     }
+
+    #[inline]
+    fn mark_snapshot(&mut self) {
+        self.inner.mark_snapshot();
+    }
+
+    #[inline]
+    fn take_snapshot_inputs(&self) -> Vec<(CorpusId, I)> {
+        self.inner.take_snapshot_inputs()
+    }
+
+    #[inline]
+    fn put_snapshot_inputs(&self, inputs: Vec<(CorpusId, I)>) {
+        self.inner.put_snapshot_inputs(inputs);
+    }
 }
 
 impl<I> HasTestcase for OnDiskCorpus<I>
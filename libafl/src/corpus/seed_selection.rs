@@ -0,0 +1,449 @@
+//! Seed selection, reducing huge seed dumps to a small, coverage-equivalent initial corpus before loading it.
+//!
+//! The [`SeedSelector`] executes every seed once, then greedily picks a set of seeds covering every map entry
+//! any seed covered, preferring seeds covering many new entries and, among those, faster ones.
+//! On top, the fastest seeds for each map entry (its novelty class) are kept.
+//! Use it with [`crate::state::StdState::load_initial_inputs_with_selection`].
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{marker::PhantomData, time::Duration};
+use std::path::PathBuf;
+
+use hashbrown::{HashMap, HashSet};
+use libafl_bolts::{
+    current_time, impl_serdeany,
+    tuples::{Handle, Handled},
+    AsIter, Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{Event, EventFirer, LogSeverity},
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::Input,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::{MapObserver, ObserversTuple},
+    state::{HasExecutions, UsesState},
+    Error, HasMetadata,
+};
+
+/// The outcome of the last seed selection, added to the state by [`SeedSelector::select`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct SeedSelectionMetadata {
+    /// The number of seeds that were executed
+    pub total: usize,
+    /// The number of executions the seed selection took
+    pub executions: u64,
+    /// The seeds that were rejected
+    pub rejected: Vec<PathBuf>,
+    /// The union of the map entries covered by the rejected seeds, sorted
+    pub rejected_coverage: Vec<usize>,
+}
+
+impl_serdeany!(SeedSelectionMetadata);
+
+/// The progress of a running seed selection, kept in the state by [`SeedSelector::select`],
+/// so that a fuzzer restarted by a crashing or hanging seed continues where it left off.
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct SeedSelectionProgressMetadata {
+    /// The seeds to select from
+    pub seeds: Vec<PathBuf>,
+    /// The runs of the seeds executed so far, in the order of `seeds`.
+    ///
+    /// The seed after the last run is the one currently executing.
+    pub runs: Vec<SeedRun>,
+    /// The executions of the state when the selection started
+    pub start_executions: u64,
+}
+
+impl_serdeany!(SeedSelectionProgressMetadata);
+
+/// Selects a coverage-minimized subset of seeds, see the [module docs](self).
+#[derive(Debug)]
+pub struct SeedSelector<C, O> {
+    observer_handle: Handle<C>,
+    fastest_per_class: usize,
+    phantom: PhantomData<O>,
+}
+
+impl<C, O> SeedSelector<C, O>
+where
+    C: Named,
+{
+    /// Create a new [`SeedSelector`] using the coverage of the given map observer
+    #[must_use]
+    pub fn new(obs: &C) -> Self {
+        Self {
+            observer_handle: obs.handle(),
+            fastest_per_class: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Additionally keep the `n` fastest seeds covering each map entry
+    #[must_use]
+    pub fn with_fastest_per_class(mut self, n: usize) -> Self {
+        self.fastest_per_class = n;
+        self
+    }
+}
+
+impl<C, O> SeedSelector<C, O>
+where
+    for<'a> O: MapObserver + AsIter<'a, Item = O::Entry>,
+    C: AsRef<O>,
+{
+    /// Execute all `seeds` and return the selected ones.
+    ///
+    /// Seeds that cannot be loaded, that crash or time out, or that do not cover anything, are rejected.
+    /// The rejected seeds are recorded in the [`SeedSelectionMetadata`].
+    /// If the fuzzer restarted during a selection, the selection continues with the seeds it started with,
+    /// see [`SeedSelectionProgressMetadata`].
+    pub fn select<E, EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut E::State,
+        seeds: Vec<PathBuf>,
+    ) -> Result<Vec<PathBuf>, Error>
+    where
+        E: Executor<EM, Z> + HasObservers,
+        E::Observers: ObserversTuple<E::Input, E::State>,
+        E::Input: Input,
+        E::State: HasExecutions + HasMetadata,
+        EM: EventFirer<State = E::State>,
+        Z: UsesState<State = E::State>,
+    {
+        if let Ok(progress) = state.metadata_mut::<SeedSelectionProgressMetadata>() {
+            if progress.runs.len() < progress.seeds.len() {
+                // We restarted while executing this seed
+                log::warn!(
+                    "Seed {} crashed or timed out during seed selection",
+                    progress.seeds[progress.runs.len()].display()
+                );
+                progress.runs.push(SeedRun::default());
+            }
+        } else {
+            manager.log(
+                state,
+                LogSeverity::Info,
+                format!("Executing {} seeds for seed selection...", seeds.len()),
+            )?;
+            let start_executions = *state.executions();
+            state.add_metadata(SeedSelectionProgressMetadata {
+                seeds,
+                runs: Vec::new(),
+                start_executions,
+            });
+        }
+
+        let progress = state.metadata::<SeedSelectionProgressMetadata>()?;
+        let (done, count) = (progress.runs.len(), progress.seeds.len());
+        let total = count as u64;
+        for curr in done..count {
+            let path = state.metadata::<SeedSelectionProgressMetadata>()?.seeds[curr].clone();
+            let run = match <E::Input as Input>::from_file(&path) {
+                Ok(input) => self.run_seed(fuzzer, executor, manager, state, &input)?,
+                Err(err) => {
+                    log::warn!("Could not load seed {}: {err}", path.display());
+                    SeedRun::default()
+                }
+            };
+            state
+                .metadata_mut::<SeedSelectionProgressMetadata>()?
+                .runs
+                .push(run);
+
+            let executions = *state.executions();
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::from("seed selection exec pass"),
+                    value: UserStats::new(
+                        UserStatsValue::Ratio(curr as u64 + 1, total),
+                        AggregatorOps::None,
+                    ),
+                    phantom: PhantomData,
+                },
+            )?;
+            manager.fire(
+                state,
+                Event::UpdateExecStats {
+                    time: current_time(),
+                    phantom: PhantomData,
+                    executions,
+                    host_metrics: None,
+                },
+            )?;
+        }
+
+        let Some(progress) = state
+            .metadata_map_mut()
+            .remove::<SeedSelectionProgressMetadata>()
+        else {
+            return Err(Error::illegal_state(
+                "Lost the progress of the seed selection",
+            ));
+        };
+        let SeedSelectionProgressMetadata {
+            seeds,
+            runs,
+            start_executions,
+        } = *progress;
+
+        let keep = select_seeds(&runs, self.fastest_per_class);
+
+        let mut rejected = Vec::new();
+        let mut rejected_coverage = HashSet::new();
+        let mut selected = Vec::with_capacity(keep.len());
+        for (idx, (path, run)) in seeds.into_iter().zip(runs).enumerate() {
+            if keep.binary_search(&idx).is_ok() {
+                selected.push(path);
+            } else {
+                rejected_coverage.extend(run.coverage);
+                rejected.push(path);
+            }
+        }
+        let mut rejected_coverage: Vec<usize> = rejected_coverage.into_iter().collect();
+        rejected_coverage.sort_unstable();
+
+        manager.log(
+            state,
+            LogSeverity::Info,
+            format!("Seed selection kept {} of {total} seeds.", selected.len()),
+        )?;
+        let executions = state.executions().saturating_sub(start_executions);
+        state.add_metadata(SeedSelectionMetadata {
+            total: count,
+            executions,
+            rejected,
+            rejected_coverage,
+        });
+        Ok(selected)
+    }
+
+    /// Execute a single seed, and record the map entries it covered
+    fn run_seed<E, EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut E::State,
+        input: &E::Input,
+    ) -> Result<SeedRun, Error>
+    where
+        E: Executor<EM, Z> + HasObservers,
+        E::Observers: ObserversTuple<E::Input, E::State>,
+        EM: UsesState<State = E::State>,
+        Z: UsesState<State = E::State>,
+    {
+        executor.observers_mut().pre_exec_all(state, input)?;
+        let start = current_time();
+        let kind = executor.run_target(fuzzer, state, manager, input)?;
+        let exec_time = current_time().saturating_sub(start);
+        executor
+            .observers_mut()
+            .post_exec_all(state, input, &kind)?;
+        if kind != ExitKind::Ok {
+            // The seed crashed or timed out without taking down the fuzzer
+            log::warn!("A seed exited with {kind:?} during seed selection, rejecting it");
+            return Ok(SeedRun::default());
+        }
+
+        let observers = executor.observers();
+        let obs = observers[&self.observer_handle].as_ref();
+        let initial = obs.initial();
+        let coverage = obs
+            .as_iter()
+            .enumerate()
+            .filter(|(_, e)| **e != initial)
+            .map(|(i, _)| i)
+            .collect();
+        Ok(SeedRun {
+            coverage,
+            exec_time,
+        })
+    }
+}
+
+/// The coverage and execution time of a single seed
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SeedRun {
+    /// The covered map entries
+    pub coverage: HashSet<usize>,
+    /// The time it took to execute the seed
+    pub exec_time: Duration,
+}
+
+/// Select seeds from the given runs, returning their indices in ascending order.
+///
+/// Greedily picks the seed covering the most not yet covered map entries (the faster one on ties),
+/// until all entries are covered, then adds the `fastest_per_class` fastest seeds for each entry.
+#[must_use]
+pub fn select_seeds(runs: &[SeedRun], fastest_per_class: usize) -> Vec<usize> {
+    let mut by_speed: Vec<usize> = (0..runs.len()).collect();
+    by_speed.sort_by_key(|&idx| runs[idx].exec_time);
+
+    let mut selected = HashSet::new();
+    let mut uncovered: HashSet<usize> = runs
+        .iter()
+        .flat_map(|run| run.coverage.iter().copied())
+        .collect();
+    while !uncovered.is_empty() {
+        // `max_by_key` returns the last maximum, so iterate from the slowest to the fastest seed
+        let Some(best) = by_speed
+            .iter()
+            .rev()
+            .copied()
+            .max_by_key(|&idx| runs[idx].coverage.intersection(&uncovered).count())
+        else {
+            break;
+        };
+        for entry in &runs[best].coverage {
+            uncovered.remove(entry);
+        }
+        selected.insert(best);
+    }
+
+    if fastest_per_class > 0 {
+        let mut kept_per_class: HashMap<usize, usize> = HashMap::new();
+        for &idx in &by_speed {
+            for entry in &runs[idx].coverage {
+                let kept = kept_per_class.entry(*entry).or_default();
+                if *kept < fastest_per_class {
+                    *kept += 1;
+                    selected.insert(idx);
+                }
+            }
+        }
+    }
+
+    let mut selected: Vec<usize> = selected.into_iter().collect();
+    selected.sort_unstable();
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{env, fs, process};
+
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list, AsSlice};
+
+    use super::{select_seeds, SeedRun, SeedSelector};
+    use crate::{
+        corpus::{InMemoryCorpus, SeedSelectionMetadata},
+        events::NopEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasTargetBytes, Input},
+        observers::StdMapObserver,
+        schedulers::RandScheduler,
+        state::StdState,
+        HasMetadata, StdFuzzer,
+    };
+
+    fn run(coverage: &[usize], millis: u64) -> SeedRun {
+        SeedRun {
+            coverage: coverage.iter().copied().collect(),
+            exec_time: Duration::from_millis(millis),
+        }
+    }
+
+    #[test]
+    fn test_select_seeds() {
+        let runs = [
+            run(&[1, 2], 5),
+            run(&[1, 2, 3], 10),
+            run(&[3], 1),
+            run(&[4], 20),
+            run(&[4], 2),
+            run(&[], 1),
+        ];
+        assert_eq!(select_seeds(&runs, 0), [1, 4]);
+        assert_eq!(select_seeds(&runs, 1), [0, 1, 2, 4]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_select_rejects_crashing_seeds() {
+        let dir = env::temp_dir().join(format!("libafl_seed_selection_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // The first byte is the covered map entry, a trailing `c` crashes
+        let seeds: Vec<_> = [&[1_u8][..], &[2, b'c'], &[1, 3]]
+            .iter()
+            .enumerate()
+            .map(|(idx, bytes)| {
+                let path = dir.join(format!("seed_{idx}"));
+                BytesInput::new(bytes.to_vec()).to_file(&path).unwrap();
+                path
+            })
+            .collect();
+
+        let mut map = vec![0_u8; 16];
+        let map_ptr = map.as_mut_ptr();
+        let observer = unsafe { StdMapObserver::from_mut_ptr("map", map_ptr, map.len()) };
+        let selector = SeedSelector::new(&observer);
+
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut mgr = NopEventManager::new();
+        let mut fuzzer = StdFuzzer::new(RandScheduler::new(), feedback, objective);
+        let mut harness = |input: &BytesInput| {
+            let bytes = input.target_bytes();
+            for byte in bytes.as_slice().iter().filter(|byte| **byte < 16) {
+                unsafe { *map_ptr.add(usize::from(*byte)) = 1 };
+            }
+            if bytes.as_slice().ends_with(b"c") {
+                ExitKind::Crash
+            } else {
+                ExitKind::Ok
+            }
+        };
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+
+        let selected = selector
+            .select(
+                &mut fuzzer,
+                &mut executor,
+                &mut mgr,
+                &mut state,
+                seeds.clone(),
+            )
+            .unwrap();
+        // The crashing seed covers entry 2 alone, but is rejected anyway
+        assert_eq!(selected, [seeds[2].clone()]);
+        let meta = state.metadata::<SeedSelectionMetadata>().unwrap();
+        assert_eq!(meta.rejected, [seeds[0].clone(), seeds[1].clone()]);
+        assert!(!meta.rejected_coverage.contains(&2));
+
+        drop(executor);
+        drop(map);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,494 @@
+//! The [`SpillingCorpus`] stores all [`Testcase`]s to disk, and keeps the most recently used inputs in memory, up to
+//! a memory budget in bytes.
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::cell::RefCell;
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{
+        inmemory_ondisk::InMemoryOnDiskCorpus, ondisk::OnDiskMetadataFormat, Corpus, CorpusId,
+        HasTestcase, Testcase,
+    },
+    inputs::Input,
+    Error,
+};
+
+/// The inputs in memory, in the order of their last use
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+struct MemoryBudget {
+    /// The last use and the size of each input in memory
+    cached: HashMap<CorpusId, (u64, usize)>,
+    /// The inputs in memory by their last use, least recently used first
+    lru: BTreeMap<u64, CorpusId>,
+    /// The sum of the sizes of the inputs in memory
+    bytes: usize,
+    /// The counter of uses
+    clock: u64,
+}
+
+impl MemoryBudget {
+    /// Marks the input as used now, adding it with the given size if it is not in memory yet
+    fn touch(&mut self, id: CorpusId, size: usize) {
+        self.clock += 1;
+        let stamp = self.clock;
+        if let Some((old_stamp, old_size)) = self.cached.insert(id, (stamp, size)) {
+            self.lru.remove(&old_stamp);
+            self.bytes -= old_size;
+        }
+        self.lru.insert(stamp, id);
+        self.bytes += size;
+    }
+
+    /// Marks the input as used now, keeping its size. Returns `false` if it is not in memory.
+    fn restamp(&mut self, id: CorpusId) -> bool {
+        let Some((stamp, _)) = self.cached.get_mut(&id) else {
+            return false;
+        };
+        self.clock += 1;
+        self.lru.remove(&*stamp);
+        *stamp = self.clock;
+        self.lru.insert(self.clock, id);
+        true
+    }
+
+    /// Forgets the input, if it is in memory
+    fn forget(&mut self, id: CorpusId) {
+        if let Some((stamp, size)) = self.cached.remove(&id) {
+            self.lru.remove(&stamp);
+            self.bytes -= size;
+        }
+    }
+}
+
+/// A corpus storing all [`Testcase`]s to disk, and keeping the inputs used most recently in memory, as long as their
+/// size on disk stays within a memory budget. The least recently used inputs are dropped from memory first, and
+/// loaded from disk again when they are used.
+///
+/// Unlike [`crate::corpus::CachedOnDiskCorpus`], which keeps a fixed number of inputs in memory, this bounds the
+/// memory used by the inputs, also when their sizes vary a lot. The input in use is never dropped, even if it alone
+/// exceeds the budget.
+///
+/// Loading an input from disk prefetches the next testcase of the corpus in the background, so schedulers
+/// walking the corpus in order rarely wait for the disk. The fuzzer also prefetches each testcase the scheduler
+/// picks, with [`Corpus::prefetch`].
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct SpillingCorpus<I> {
+    inner: InMemoryOnDiskCorpus<I>,
+    budget: RefCell<MemoryBudget>,
+    max_bytes: usize,
+    #[serde(skip)]
+    prefetcher: RefCell<Option<Sender<PathBuf>>>,
+}
+
+impl<I> SpillingCorpus<I>
+where
+    I: Input,
+{
+    /// The size of the input of the testcase, measured on disk
+    fn input_size(testcase: &Testcase<I>) -> usize {
+        testcase
+            .file_path()
+            .as_ref()
+            .and_then(|path| fs::metadata(path).ok())
+            .map_or(0, |metadata| {
+                usize::try_from(metadata.len()).unwrap_or(usize::MAX)
+            })
+    }
+
+    /// Drops the least recently used inputs from memory, until the inputs fit into the budget again
+    fn enforce_budget(&self, keep: CorpusId) -> Result<(), Error> {
+        let mut budget = self.budget.borrow_mut();
+        let mut skipped = Vec::new();
+        while budget.bytes > self.max_bytes {
+            let Some((_, id)) = budget.lru.pop_first() else {
+                break;
+            };
+            let (stamp, size) = budget.cached[&id];
+            if id != keep {
+                if let Ok(mut testcase) = self.inner.get_from_all(id)?.try_borrow_mut() {
+                    *testcase.input_mut() = None;
+                    budget.cached.remove(&id);
+                    budget.bytes -= size;
+                    continue;
+                }
+            }
+            // The input is in use, try the next one
+            skipped.push((stamp, id));
+        }
+        budget.lru.extend(skipped);
+        Ok(())
+    }
+
+    /// Loads the input of the testcase, if it is not in memory, and marks it as used.
+    ///
+    /// The size of the input is only measured on disk when it is loaded or added, not on every use.
+    fn use_testcase(&self, testcase: &RefCell<Testcase<I>>, id: CorpusId) -> Result<(), Error> {
+        let loaded = if testcase.borrow().input().is_none() {
+            self.load_input_into(&mut testcase.borrow_mut())?;
+            true
+        } else {
+            false
+        };
+        {
+            let mut budget = self.budget.borrow_mut();
+            if loaded || !budget.restamp(id) {
+                budget.touch(id, Self::input_size(&testcase.borrow()));
+            }
+        }
+        self.enforce_budget(id)?;
+        if loaded {
+            if let Some(next) = self.inner.next(id) {
+                self.prefetch(next)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The bytes of the inputs in memory
+    #[must_use]
+    pub fn cached_bytes(&self) -> usize {
+        self.budget.borrow().bytes
+    }
+
+    /// The number of inputs in memory
+    #[must_use]
+    pub fn cached_count(&self) -> usize {
+        self.budget.borrow().cached.len()
+    }
+}
+
+impl<I> Corpus for SpillingCorpus<I>
+where
+    I: Input,
+{
+    type Input = I;
+
+    #[inline]
+    fn count(&self) -> usize {
+        self.inner.count()
+    }
+
+    #[inline]
+    fn count_disabled(&self) -> usize {
+        self.inner.count_disabled()
+    }
+
+    #[inline]
+    fn count_all(&self) -> usize {
+        self.inner.count_all()
+    }
+
+    fn add(&mut self, testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        let id = self.inner.add(testcase)?;
+        self.use_testcase(self.inner.get(id)?, id)?;
+        Ok(id)
+    }
+
+    fn add_disabled(&mut self, testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        let id = self.inner.add_disabled(testcase)?;
+        self.use_testcase(self.inner.get_from_all(id)?, id)?;
+        Ok(id)
+    }
+
+    fn replace(&mut self, id: CorpusId, testcase: Testcase<I>) -> Result<Testcase<I>, Error> {
+        let old = self.inner.replace(id, testcase)?;
+        self.budget.borrow_mut().forget(id);
+        self.use_testcase(self.inner.get_from_all(id)?, id)?;
+        Ok(old)
+    }
+
+    fn remove(&mut self, id: CorpusId) -> Result<Testcase<I>, Error> {
+        let testcase = self.inner.remove(id)?;
+        self.budget.borrow_mut().forget(id);
+        Ok(testcase)
+    }
+
+    #[inline]
+    fn get(&self, id: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        let testcase = self.inner.get(id)?;
+        self.use_testcase(testcase, id)?;
+        Ok(testcase)
+    }
+
+    #[inline]
+    fn get_from_all(&self, id: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        let testcase = self.inner.get_from_all(id)?;
+        self.use_testcase(testcase, id)?;
+        Ok(testcase)
+    }
+
+    #[inline]
+    fn current(&self) -> &Option<CorpusId> {
+        self.inner.current()
+    }
+
+    #[inline]
+    fn current_mut(&mut self) -> &mut Option<CorpusId> {
+        self.inner.current_mut()
+    }
+
+    #[inline]
+    fn next(&self, id: CorpusId) -> Option<CorpusId> {
+        self.inner.next(id)
+    }
+
+    #[inline]
+    fn peek_free_id(&self) -> CorpusId {
+        self.inner.peek_free_id()
+    }
+
+    #[inline]
+    fn prev(&self, id: CorpusId) -> Option<CorpusId> {
+        self.inner.prev(id)
+    }
+
+    #[inline]
+    fn first(&self) -> Option<CorpusId> {
+        self.inner.first()
+    }
+
+    #[inline]
+    fn last(&self) -> Option<CorpusId> {
+        self.inner.last()
+    }
+
+    #[inline]
+    fn nth(&self, nth: usize) -> CorpusId {
+        self.inner.nth(nth)
+    }
+
+    #[inline]
+    fn nth_from_all(&self, nth: usize) -> CorpusId {
+        self.inner.nth_from_all(nth)
+    }
+
+    #[inline]
+    fn load_input_into(&self, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        self.inner.load_input_into(testcase)
+    }
+
+    #[inline]
+    fn store_input_from(&self, testcase: &Testcase<I>) -> Result<(), Error> {
+        self.inner.store_input_from(testcase)
+    }
+
+    #[inline]
+    fn mark_snapshot(&mut self) {
+        self.inner.mark_snapshot();
+    }
+
+    #[inline]
+    fn take_snapshot_inputs(&self) -> Vec<(CorpusId, I)> {
+        self.inner.take_snapshot_inputs()
+    }
+
+    #[inline]
+    fn put_snapshot_inputs(&self, inputs: Vec<(CorpusId, I)>) {
+        self.inner.put_snapshot_inputs(inputs);
+    }
+
+    /// Reads the input of the testcase into the page cache in the background, if it is not in memory, so that
+    /// using it later does not wait for the disk
+    fn prefetch(&self, id: CorpusId) -> Result<(), Error> {
+        if self.budget.borrow().cached.contains_key(&id) {
+            return Ok(());
+        }
+        let path = match self.inner.get_from_all(id)?.try_borrow() {
+            Ok(testcase) if testcase.input().is_none() => testcase.file_path().clone(),
+            _ => None,
+        };
+        let Some(path) = path else {
+            return Ok(());
+        };
+        let mut prefetcher = self.prefetcher.borrow_mut();
+        let sender = prefetcher.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel::<PathBuf>();
+            thread::spawn(move || {
+                for path in receiver {
+                    if let Ok(mut file) = File::open(path) {
+                        drop(io::copy(&mut file, &mut io::sink()));
+                    }
+                }
+            });
+            sender
+        });
+        if sender.send(path).is_err() {
+            // The prefetch thread is gone, start a new one next time
+            *prefetcher = None;
+        }
+        Ok(())
+    }
+}
+
+impl<I> HasTestcase for SpillingCorpus<I>
+where
+    I: Input,
+{
+    fn testcase(&self, id: CorpusId) -> Result<core::cell::Ref<Testcase<I>>, Error> {
+        Ok(self.get(id)?.borrow())
+    }
+
+    fn testcase_mut(&self, id: CorpusId) -> Result<core::cell::RefMut<Testcase<I>>, Error> {
+        Ok(self.get(id)?.borrow_mut())
+    }
+}
+
+impl<I> SpillingCorpus<I> {
+    /// Creates the [`SpillingCorpus`], keeping at most `max_bytes` of inputs in memory.
+    ///
+    /// It stores the metadata of each [`Testcase`] as prettified json, like [`InMemoryOnDiskCorpus::new`].
+    ///
+    /// Will error, if [`std::fs::create_dir_all()`] failed for `dir_path`.
+    pub fn new<P>(dir_path: P, max_bytes: usize) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::_new(InMemoryOnDiskCorpus::new(dir_path)?, max_bytes)
+    }
+
+    /// Creates the [`SpillingCorpus`] that does not store [`Testcase`] metadata to disk.
+    pub fn no_meta<P>(dir_path: P, max_bytes: usize) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::_new(InMemoryOnDiskCorpus::no_meta(dir_path)?, max_bytes)
+    }
+
+    /// Creates the [`SpillingCorpus`] specifying the metadata format and the prefix to prepend to each testcase.
+    ///
+    /// Will error, if [`std::fs::create_dir_all()`] failed for `dir_path`.
+    pub fn with_meta_format_and_prefix<P>(
+        dir_path: P,
+        max_bytes: usize,
+        meta_format: Option<OnDiskMetadataFormat>,
+        prefix: Option<String>,
+        locking: bool,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::_new(
+            InMemoryOnDiskCorpus::with_meta_format_and_prefix(
+                dir_path,
+                meta_format,
+                prefix,
+                locking,
+            )?,
+            max_bytes,
+        )
+    }
+
+    /// Internal constructor `fn`
+    fn _new(on_disk_corpus: InMemoryOnDiskCorpus<I>, max_bytes: usize) -> Result<Self, Error> {
+        if max_bytes == 0 {
+            return Err(Error::illegal_argument(
+                "The memory budget of the SpillingCorpus cannot be 0",
+            ));
+        }
+        Ok(Self {
+            inner: on_disk_corpus,
+            budget: RefCell::new(MemoryBudget::default()),
+            max_bytes,
+            prefetcher: RefCell::new(None),
+        })
+    }
+
+    /// The memory budget in bytes
+    #[must_use]
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Fetch the inner corpus
+    pub fn inner(&self) -> &InMemoryOnDiskCorpus<I> {
+        &self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::SpillingCorpus;
+    use crate::{
+        corpus::{Corpus, Testcase},
+        inputs::BytesInput,
+    };
+
+    #[test]
+    fn test_spilling_corpus() {
+        let dir = env::temp_dir().join(format!("libafl_spilling_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut corpus = SpillingCorpus::<BytesInput>::no_meta(&dir, 250).unwrap();
+        let ids: Vec<_> = (0..4u8)
+            .map(|i| {
+                corpus
+                    .add(Testcase::new(BytesInput::new(vec![i; 100])))
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(corpus.cached_count(), 2);
+        assert!(corpus.cached_bytes() <= corpus.max_bytes());
+        // The oldest inputs were spilled to disk
+        assert!(corpus
+            .inner()
+            .get(ids[0])
+            .unwrap()
+            .borrow()
+            .input()
+            .is_none());
+
+        let testcase = corpus.get(ids[0]).unwrap();
+        assert_eq!(
+            testcase.borrow().input().as_ref().unwrap(),
+            &BytesInput::new(vec![0; 100])
+        );
+        assert_eq!(corpus.cached_count(), 2);
+        assert!(corpus
+            .inner()
+            .get(ids[2])
+            .unwrap()
+            .borrow()
+            .input()
+            .is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_spilling_corpus_measures_once() {
+        let dir = env::temp_dir().join(format!("libafl_spilling_measure_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut corpus = SpillingCorpus::<BytesInput>::no_meta(&dir, 1000).unwrap();
+        let id = corpus
+            .add(Testcase::new(BytesInput::new(vec![0; 100])))
+            .unwrap();
+        assert_eq!(corpus.cached_bytes(), 100);
+
+        // Using an input in memory does not measure it on disk again
+        let path = corpus
+            .get(id)
+            .unwrap()
+            .borrow()
+            .file_path()
+            .clone()
+            .unwrap();
+        fs::write(&path, [0; 200]).unwrap();
+        corpus.get(id).unwrap();
+        assert_eq!(corpus.cached_bytes(), 100);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
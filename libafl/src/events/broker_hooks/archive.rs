@@ -0,0 +1,243 @@
+//! A [`BrokerHook`] writing every unique testcase of the campaign into a single archive, see [`CorpusArchiveHook`].
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{marker::PhantomData, time::Duration};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashSet;
+use libafl_bolts::{hash_std, ClientId};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::GlobalTestcaseId,
+    events::{BrokerHook, BrokerHookResult, Event},
+    executors::ExitKind,
+    inputs::Input,
+    Error,
+};
+
+/// The file name of the manifest in the archive directory, one JSON [`ArchiveEntry`] per line
+pub const ARCHIVE_MANIFEST: &str = "manifest.jsonl";
+
+/// The provenance of an archived testcase, one line of the [`ARCHIVE_MANIFEST`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    /// The file name of the input in the archive directory
+    pub name: String,
+    /// The identity of the testcase
+    pub id: GlobalTestcaseId,
+    /// The identity of the testcase it was mutated from, if known
+    pub parent: Option<GlobalTestcaseId>,
+    /// The client which found the testcase
+    pub client: ClientId,
+    /// The time the client found the testcase, since the epoch
+    pub time: Duration,
+    /// The exit kind of the execution which found the testcase
+    pub exit_kind: ExitKind,
+    /// The hash of the serialized observers, if the client sent them
+    pub observers_hash: Option<u64>,
+}
+
+/// A [`BrokerHook`] archiving each unique testcase found by any client, so the canonical corpus of a campaign is in
+/// one place instead of scattered across the corpus directories of the clients.
+///
+/// The inputs are written to the archive directory, named after their [`GlobalTestcaseId`], and their provenance is
+/// appended to the [`ARCHIVE_MANIFEST`]. Testcases are deduplicated by their [`GlobalTestcaseId`], including the ones
+/// archived by earlier runs into the same directory. Register it with
+/// [`crate::events::StdLlmpEventHook::with_hooks`]; it never drops or modifies events.
+#[derive(Debug)]
+pub struct CorpusArchiveHook<I> {
+    dir: PathBuf,
+    manifest: File,
+    known: HashSet<GlobalTestcaseId>,
+    phantom: PhantomData<I>,
+}
+
+impl<I> CorpusArchiveHook<I> {
+    /// Creates a new [`CorpusArchiveHook`], archiving to `dir`, which is created if needed.
+    ///
+    /// Testcases already in the manifest of `dir` are not archived again.
+    pub fn new<P>(dir: P) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let known = Self::entries(&dir)?
+            .into_iter()
+            .map(|entry| entry.id)
+            .collect();
+        let manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(ARCHIVE_MANIFEST))?;
+        Ok(Self {
+            dir,
+            manifest,
+            known,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Reads the manifest of the archive in `dir`, empty if there is none
+    pub fn entries<P>(dir: P) -> Result<Vec<ArchiveEntry>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = dir.as_ref().join(ARCHIVE_MANIFEST);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            entries.push(serde_json::from_str(&line)?);
+        }
+        Ok(entries)
+    }
+
+    /// The archive directory
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The amount of archived testcases
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.known.len()
+    }
+
+    /// If no testcase was archived yet
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty()
+    }
+
+    /// If the testcase with the given identity is archived
+    #[must_use]
+    pub fn contains(&self, id: GlobalTestcaseId) -> bool {
+        self.known.contains(&id)
+    }
+}
+
+impl<I> BrokerHook<I> for CorpusArchiveHook<I>
+where
+    I: Input,
+{
+    fn on_event(
+        &mut self,
+        client_id: ClientId,
+        event: &mut Event<I>,
+    ) -> Result<BrokerHookResult, Error> {
+        if let Event::NewTestcase {
+            input,
+            observers_buf,
+            exit_kind,
+            time,
+            forward_id,
+            global_id,
+            parent_global_id,
+            ..
+        } = event
+        {
+            if self.known.insert(*global_id) {
+                let name = global_id.to_string();
+                input.to_file(self.dir.join(&name))?;
+                let entry = ArchiveEntry {
+                    name,
+                    id: *global_id,
+                    parent: *parent_global_id,
+                    client: forward_id.unwrap_or(client_id),
+                    time: *time,
+                    exit_kind: *exit_kind,
+                    observers_hash: observers_buf.as_deref().map(hash_std),
+                };
+                // The manifest is written line by line, so an interrupted broker leaves it readable
+                writeln!(self.manifest, "{}", serde_json::to_string(&entry)?)?;
+                self.manifest.flush()?;
+            }
+        }
+        Ok(BrokerHookResult::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+    use std::{env, fs, process};
+
+    use libafl_bolts::ClientId;
+
+    use super::CorpusArchiveHook;
+    use crate::{
+        corpus::GlobalTestcaseId,
+        events::{BrokerHook, BrokerHookResult, Event, EventConfig},
+        executors::ExitKind,
+        inputs::BytesInput,
+    };
+
+    fn new_testcase(bytes: &[u8], forward_id: Option<ClientId>) -> Event<BytesInput> {
+        let input = BytesInput::new(bytes.to_vec());
+        Event::NewTestcase {
+            global_id: GlobalTestcaseId::of(&input).unwrap(),
+            input,
+            observers_buf: Some(vec![1, 2, 3]),
+            exit_kind: ExitKind::Ok,
+            corpus_size: 1,
+            client_config: EventConfig::AlwaysUnique,
+            time: Duration::from_secs(42),
+            forward_id,
+            parent_global_id: None,
+            #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
+            node_id: None,
+        }
+    }
+
+    #[test]
+    fn test_corpus_archive_hook() {
+        let dir = env::temp_dir().join(format!("libafl_archive_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut hook = CorpusArchiveHook::new(&dir).unwrap();
+        for (bytes, client) in [(b"a", 1), (b"b", 2), (b"a", 3)] {
+            let result = hook
+                .on_event(ClientId(client), &mut new_testcase(bytes, None))
+                .unwrap();
+            assert_eq!(result, BrokerHookResult::Continue);
+        }
+        hook.on_event(ClientId(0), &mut new_testcase(b"c", Some(ClientId(4))))
+            .unwrap();
+        assert_eq!(hook.len(), 3);
+        drop(hook);
+
+        let entries = CorpusArchiveHook::<BytesInput>::entries(&dir).unwrap();
+        let clients = entries.iter().map(|e| e.client.0).collect::<Vec<_>>();
+        assert_eq!(clients, [1, 2, 4]);
+        assert!(entries.iter().all(|e| dir.join(&e.name).exists()));
+
+        // Reopening the archive keeps deduplicating
+        let mut hook = CorpusArchiveHook::new(&dir).unwrap();
+        hook.on_event(ClientId(5), &mut new_testcase(b"b", None))
+            .unwrap();
+        assert_eq!(
+            CorpusArchiveHook::<BytesInput>::entries(&dir)
+                .unwrap()
+                .len(),
+            3
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -95,7 +95,11 @@ where
         event: &Event<I>,
     ) -> Result<BrokerEventResult, Error> {
         match &event {
-            Event::NewTestcase { .. } | Event::Stop => Ok(BrokerEventResult::Forward),
+            Event::NewTestcase { .. }
+            | Event::Stop
+            | Event::Command { .. }
+            | Event::NewTokens { .. }
+            | Event::ConfigUpdate { .. } => Ok(BrokerEventResult::Forward),
             _ => Ok(BrokerEventResult::Handled),
         }
     }
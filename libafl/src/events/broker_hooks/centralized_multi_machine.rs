@@ -24,6 +24,7 @@ use tokio::{
 use crate::{
     events::{
         centralized::_LLMP_TAG_TO_MAIN,
+        llmp::{SharedEventRef, LLMP_TAG_EVENT_SHMEM_REF},
         multi_machine::{MultiMachineMsg, TcpMultiMachineState},
         Event,
     },
@@ -167,15 +168,32 @@ where
     /// check for received messages, and forward them alongside the incoming message to inner.
     fn on_new_message(
         &mut self,
-        _broker_inner: &mut LlmpBrokerInner<SP>,
+        broker_inner: &mut LlmpBrokerInner<SP>,
         _client_id: ClientId,
-        _msg_tag: &mut Tag,
+        msg_tag: &mut Tag,
         _msg_flags: &mut Flags,
         msg: &mut [u8],
         _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
     ) -> Result<LlmpMsgHookResult, Error> {
         let shared_state = self.shared_state.clone();
 
+        // Other machines can not map our shared memory, forward the full event instead
+        let resolved_msg = if *msg_tag == LLMP_TAG_EVENT_SHMEM_REF {
+            let shared = SharedEventRef::from_msg(msg)?;
+            // The broker forwards the reference after the hooks ran, so the segment is still alive
+            match shared.peek(broker_inner.shmem_provider_mut(), |event_bytes| {
+                Ok(event_bytes.to_vec())
+            }) {
+                Ok(event_bytes) => Some(event_bytes),
+                Err(err) => {
+                    log::warn!("Not forwarding shared event to other machines: {err}");
+                    return Ok(LlmpMsgHookResult::ForwardToClients);
+                }
+            }
+        } else {
+            None
+        };
+
         // # Safety
         // Here, we suppose msg will *never* be written again and will always be available.
         // Thus, it is safe to handle this in a separate thread.
@@ -185,7 +203,10 @@ where
         let _handle: JoinHandle<Result<(), Error>> = self.rt.spawn(async move {
             let mut state_wr_lock = shared_state.write().await;
             let (msg_ptr, msg_len) = msg_lock.into_innter();
-            let msg: &[u8] = unsafe { slice::from_raw_parts(msg_ptr, msg_len) }; // most likely crash here
+            let msg: &[u8] = match &resolved_msg {
+                Some(resolved_msg) => resolved_msg,
+                None => unsafe { slice::from_raw_parts(msg_ptr, msg_len) }, // most likely crash here
+            };
 
             // #[cfg(not(feature = "llmp_compression"))]
             // let event_bytes = msg;
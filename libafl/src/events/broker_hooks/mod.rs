@@ -1,24 +1,50 @@
 //! Hooks called on broker side
-use alloc::vec::Vec;
-use core::marker::PhantomData;
+use alloc::{boxed::Box, format, vec::Vec};
+use core::{
+    any::{type_name, Any},
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+};
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
 
-#[cfg(feature = "llmp_compression")]
-use libafl_bolts::{compress::GzipCompressor, llmp::LLMP_FLAG_COMPRESSED};
 use libafl_bolts::{
-    llmp::{Flags, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag},
+    codec::{Codec, PostcardCodec},
+    llmp::{Flags, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag, LLMP_FLAG_INITIALIZED},
     shmem::ShMemProvider,
     ClientId,
 };
+#[cfg(feature = "llmp_compression")]
+use libafl_bolts::{compress::GzipCompressor, llmp::LLMP_FLAG_COMPRESSED};
 
 #[cfg(feature = "llmp_compression")]
 use crate::events::llmp::COMPRESS_THRESHOLD;
+#[cfg(feature = "std")]
+use crate::{events::EventCommand, state::RuntimeConfig};
 use crate::{
-    events::{llmp::LLMP_TAG_EVENT_TO_BOTH, BrokerEventResult, Event},
+    events::{
+        llmp::{SharedEventRef, LLMP_TAG_EVENT_SHMEM_REF, LLMP_TAG_EVENT_TO_BOTH},
+        BrokerEventResult, Event,
+    },
     inputs::Input,
     monitors::Monitor,
     Error,
 };
 
+/// Corpus archive hook
+#[cfg(feature = "std")]
+pub mod archive;
+#[cfg(feature = "std")]
+pub use archive::{ArchiveEntry, CorpusArchiveHook, ARCHIVE_MANIFEST};
+
+/// Bandwidth accounting and throttling hook
+#[cfg(feature = "std")]
+pub mod throttle;
+#[cfg(feature = "std")]
+pub use throttle::{
+    BandwidthStats, BandwidthThrottleHook, ClientBandwidth, DEFAULT_THROTTLE_BURST,
+};
+
 /// centralized hook
 #[cfg(all(unix, feature = "std"))]
 pub mod centralized;
@@ -31,61 +57,389 @@ pub mod centralized_multi_machine;
 #[cfg(all(unix, feature = "multi_machine"))]
 pub use centralized_multi_machine::*;
 
+/// A handle to inject [`Event::Command`]s and [`Event::ConfigUpdate`]s into a running broker,
+/// see [`StdLlmpEventHook::command_injector`].
+///
+/// Injected commands are broadcast to all clients along with the next message the broker handles.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct CommandInjector {
+    pending: Arc<Mutex<(u64, Vec<(u64, EventCommand)>)>>,
+    configs: Arc<Mutex<Vec<RuntimeConfig>>>,
+}
+
+#[cfg(feature = "std")]
+impl CommandInjector {
+    /// Queue a command for all clients. Returns the id the clients will acknowledge it with.
+    pub fn inject(&self, command: EventCommand) -> u64 {
+        let mut pending = self.pending.lock().unwrap();
+        let id = pending.0;
+        pending.0 += 1;
+        pending.1.push((id, command));
+        id
+    }
+
+    /// Queue a config update for all clients
+    pub fn inject_config(&self, config: RuntimeConfig) {
+        self.configs.lock().unwrap().push(config);
+    }
+
+    /// Take all commands queued since the last call
+    fn take(&self) -> Vec<(u64, EventCommand)> {
+        core::mem::take(&mut self.pending.lock().unwrap().1)
+    }
+
+    /// Take all config updates queued since the last call
+    fn take_configs(&self) -> Vec<RuntimeConfig> {
+        core::mem::take(&mut *self.configs.lock().unwrap())
+    }
+}
+
+/// What happens to an event after a [`BrokerHook`] saw it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrokerHookResult {
+    /// Pass the event on unchanged
+    Continue,
+    /// The hook modified the event, pass it on in its new form
+    Modified,
+    /// Drop the event, neither the monitor nor the clients will see it
+    Drop,
+}
+
+impl BrokerHookResult {
+    /// Combine the results of two hooks: a drop wins over a modification
+    #[must_use]
+    pub fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Drop, _) | (_, Self::Drop) => Self::Drop,
+            (Self::Modified, _) | (_, Self::Modified) => Self::Modified,
+            _ => Self::Continue,
+        }
+    }
+}
+
+/// A plugin of the [`StdLlmpEventHook`], called on every event passing through the broker,
+/// e.g. for custom deduplication, logging or export of events
+pub trait BrokerHook<I> {
+    /// Called for each event sent by a client, before the monitor handles it.
+    ///
+    /// The hook may modify the event in place and return [`BrokerHookResult::Modified`], or drop it.
+    /// Modified events are re-sent by the broker on behalf of the client that sent the event, so only
+    /// the other clients receive the modified version.
+    fn on_event(
+        &mut self,
+        client_id: ClientId,
+        event: &mut Event<I>,
+    ) -> Result<BrokerHookResult, Error>;
+
+    /// Called for each event like [`Self::on_event`], with the length of the message carrying it,
+    /// i.e. the bytes it takes up in the shared maps, after compression
+    fn on_event_with_len(
+        &mut self,
+        client_id: ClientId,
+        event: &mut Event<I>,
+        _msg_len: usize,
+    ) -> Result<BrokerHookResult, Error> {
+        self.on_event(client_id, event)
+    }
+
+    /// Called when the broker lost a client
+    fn on_client_lost(&mut self, _client_id: ClientId) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// A tuple of [`BrokerHook`]s, called in order
+pub trait BrokerHooksTuple<I> {
+    /// Call all hooks on an event, carried by a message of `msg_len` bytes.
+    /// Once a hook dropped the event, the remaining hooks do not see it.
+    fn on_event_all(
+        &mut self,
+        client_id: ClientId,
+        event: &mut Event<I>,
+        msg_len: usize,
+    ) -> Result<BrokerHookResult, Error>;
+
+    /// Call all hooks when the broker lost a client
+    fn on_client_lost_all(&mut self, client_id: ClientId) -> Result<(), Error>;
+}
+
+impl<I> BrokerHooksTuple<I> for () {
+    fn on_event_all(
+        &mut self,
+        _client_id: ClientId,
+        _event: &mut Event<I>,
+        _msg_len: usize,
+    ) -> Result<BrokerHookResult, Error> {
+        Ok(BrokerHookResult::Continue)
+    }
+
+    fn on_client_lost_all(&mut self, _client_id: ClientId) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<Head, Tail, I> BrokerHooksTuple<I> for (Head, Tail)
+where
+    Head: BrokerHook<I>,
+    Tail: BrokerHooksTuple<I>,
+{
+    fn on_event_all(
+        &mut self,
+        client_id: ClientId,
+        event: &mut Event<I>,
+        msg_len: usize,
+    ) -> Result<BrokerHookResult, Error> {
+        let first = self.0.on_event_with_len(client_id, event, msg_len)?;
+        if first == BrokerHookResult::Drop {
+            return Ok(first);
+        }
+        let second = self.1.on_event_all(client_id, event, msg_len)?;
+        Ok(first.and(second))
+    }
+
+    fn on_client_lost_all(&mut self, client_id: ClientId) -> Result<(), Error> {
+        self.0.on_client_lost(client_id)?;
+        self.1.on_client_lost_all(client_id)
+    }
+}
+
+/// Boxed hooks, as taken from the [`BrokerHooks`], are called in order as well
+impl<I> BrokerHooksTuple<I> for Vec<Box<dyn BrokerHook<I>>> {
+    fn on_event_all(
+        &mut self,
+        client_id: ClientId,
+        event: &mut Event<I>,
+        msg_len: usize,
+    ) -> Result<BrokerHookResult, Error> {
+        let mut result = BrokerHookResult::Continue;
+        for hook in self.iter_mut() {
+            result = result.and(hook.on_event_with_len(client_id, event, msg_len)?);
+            if result == BrokerHookResult::Drop {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    fn on_client_lost_all(&mut self, client_id: ClientId) -> Result<(), Error> {
+        for hook in self.iter_mut() {
+            hook.on_client_lost(client_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// [`BrokerHook`]s for the brokers of the [`crate::events::Launcher`] and the [`crate::events::RestartingMgr`],
+/// whose builders do not know the input type yet.
+///
+/// The input type of each hook is checked when the broker starts.
+#[derive(Default)]
+pub struct BrokerHooks {
+    hooks: Vec<Box<dyn Any>>,
+}
+
+impl Debug for BrokerHooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrokerHooks")
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+impl BrokerHooks {
+    /// Create an empty list of [`BrokerHook`]s
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a hook for events with inputs of type `I`, called after all previously added hooks.
+    ///
+    /// For hooks handling any input, name the input type, e.g. `.add::<BytesInput, _>(hook)`.
+    #[must_use]
+    pub fn add<I, H>(mut self, hook: H) -> Self
+    where
+        I: 'static,
+        H: BrokerHook<I> + 'static,
+    {
+        let hook: Box<dyn BrokerHook<I>> = Box::new(hook);
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// If no hooks were added
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Take all hooks, for a broker handling inputs of type `I`
+    pub fn take<I>(&mut self) -> Result<Vec<Box<dyn BrokerHook<I>>>, Error>
+    where
+        I: 'static,
+    {
+        core::mem::take(&mut self.hooks)
+            .into_iter()
+            .map(|hook| {
+                hook.downcast::<Box<dyn BrokerHook<I>>>()
+                    .map(|hook| *hook)
+                    .map_err(|_| {
+                        Error::illegal_argument(format!(
+                            "A broker hook does not handle inputs of type {}",
+                            type_name::<I>()
+                        ))
+                    })
+            })
+            .collect()
+    }
+}
+
 /// An LLMP-backed event hook for scalable multi-processed fuzzing
+///
+/// Additional [`BrokerHook`]s can be registered with [`StdLlmpEventHook::with_hooks`].
+/// The events are serialized with the [`Codec`] `C`, see [`StdLlmpEventHook::with_codec`].
 #[derive(Debug)]
-pub struct StdLlmpEventHook<I, MT> {
+pub struct StdLlmpEventHook<I, MT, BH = (), C = PostcardCodec> {
     monitor: MT,
+    hooks: BH,
     #[cfg(feature = "llmp_compression")]
     compressor: GzipCompressor,
-    phantom: PhantomData<I>,
+    #[cfg(feature = "std")]
+    injector: CommandInjector,
+    phantom: PhantomData<(I, C)>,
 }
 
-impl<I, MT, SP> LlmpHook<SP> for StdLlmpEventHook<I, MT>
+impl<I, MT, BH, C, SP> LlmpHook<SP> for StdLlmpEventHook<I, MT, BH, C>
 where
     I: Input,
     MT: Monitor,
+    BH: BrokerHooksTuple<I>,
+    C: Codec,
     SP: ShMemProvider,
 {
     fn on_new_message(
         &mut self,
-        _broker_inner: &mut LlmpBrokerInner<SP>,
+        broker_inner: &mut LlmpBrokerInner<SP>,
         client_id: ClientId,
         msg_tag: &mut Tag,
         #[cfg(feature = "llmp_compression")] msg_flags: &mut Flags,
         #[cfg(not(feature = "llmp_compression"))] _msg_flags: &mut Flags,
         msg: &mut [u8],
-        _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
+        new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
     ) -> Result<LlmpMsgHookResult, Error> {
+        #[cfg(feature = "std")]
+        for (id, command) in self.injector.take() {
+            log::info!("Injecting command {command} (id {id})");
+            let event: Event<I> = Event::Command { id, command };
+            new_msgs.push((
+                LLMP_TAG_EVENT_TO_BOTH,
+                LLMP_FLAG_INITIALIZED,
+                C::encode(&event)?,
+            ));
+        }
+        #[cfg(feature = "std")]
+        for config in self.injector.take_configs() {
+            log::info!("Injecting config update {config:?}");
+            let event: Event<I> = Event::ConfigUpdate { config };
+            new_msgs.push((
+                LLMP_TAG_EVENT_TO_BOTH,
+                LLMP_FLAG_INITIALIZED,
+                C::encode(&event)?,
+            ));
+        }
+
         let monitor = &mut self.monitor;
         #[cfg(feature = "llmp_compression")]
         let compressor = &self.compressor;
 
-        if *msg_tag == LLMP_TAG_EVENT_TO_BOTH {
-            #[cfg(not(feature = "llmp_compression"))]
-            let event_bytes = msg;
-            #[cfg(feature = "llmp_compression")]
-            let compressed;
-            #[cfg(feature = "llmp_compression")]
-            let event_bytes = if *msg_flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = compressor.decompress(msg)?;
-                &compressed
+        if *msg_tag == LLMP_TAG_EVENT_TO_BOTH || *msg_tag == LLMP_TAG_EVENT_SHMEM_REF {
+            let shared = if *msg_tag == LLMP_TAG_EVENT_SHMEM_REF {
+                Some(SharedEventRef::from_msg(msg)?)
+            } else {
+                None
+            };
+            // A shared event takes up its segment, not just the reference to it
+            let msg_len = shared.as_ref().map_or(msg.len(), |shared| shared.len);
+            let mut event: Event<I> = if let Some(shared) = &shared {
+                match shared.peek(broker_inner.shmem_provider_mut(), C::decode) {
+                    Ok(event) => event,
+                    Err(err) => {
+                        log::warn!("Dropping shared event from {client_id:?}: {err}");
+                        if let Err(err) = shared.forward(broker_inner.shmem_provider_mut(), 0) {
+                            log::debug!("Could not release the shared event: {err}");
+                        }
+                        return Ok(LlmpMsgHookResult::Handled);
+                    }
+                }
+            } else {
+                #[cfg(not(feature = "llmp_compression"))]
+                let event_bytes = msg;
+                #[cfg(feature = "llmp_compression")]
+                let compressed;
+                #[cfg(feature = "llmp_compression")]
+                let event_bytes = if *msg_flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
+                    compressed = compressor.decompress(msg)?;
+                    &compressed
+                } else {
+                    &*msg
+                };
+                C::decode(event_bytes)?
+            };
+            let hooks_result = self.hooks.on_event_all(client_id, &mut event, msg_len)?;
+            let result = if hooks_result == BrokerHookResult::Drop {
+                LlmpMsgHookResult::Handled
             } else {
-                &*msg
+                match Self::handle_in_broker(monitor, client_id, &event)? {
+                    BrokerEventResult::Forward if hooks_result == BrokerHookResult::Modified => {
+                        // The message can not grow in place, send the modified event as a new message.
+                        // Like a forwarded one, it comes from the sender, which then skips it.
+                        if let Event::NewTestcase { forward_id, .. } = &mut event {
+                            forward_id.get_or_insert(client_id);
+                        }
+                        broker_inner.forward_buf_with_flags(
+                            client_id,
+                            LLMP_TAG_EVENT_TO_BOTH,
+                            LLMP_FLAG_INITIALIZED,
+                            &C::encode(&event)?,
+                        )?;
+                        LlmpMsgHookResult::Handled
+                    }
+                    BrokerEventResult::Forward => LlmpMsgHookResult::ForwardToClients,
+                    BrokerEventResult::Handled => LlmpMsgHookResult::Handled,
+                }
             };
-            let event: Event<I> = postcard::from_bytes(event_bytes)?;
-            match Self::handle_in_broker(monitor, client_id, &event)? {
-                BrokerEventResult::Forward => Ok(LlmpMsgHookResult::ForwardToClients),
-                BrokerEventResult::Handled => Ok(LlmpMsgHookResult::Handled),
+            if let Some(shared) = shared {
+                // Every client but the sender reads the forwarded reference
+                let receivers = if matches!(result, LlmpMsgHookResult::ForwardToClients) {
+                    broker_inner.num_clients().saturating_sub(1)
+                } else {
+                    0
+                };
+                shared.forward(broker_inner.shmem_provider_mut(), receivers)?;
             }
+            Ok(result)
         } else {
             Ok(LlmpMsgHookResult::ForwardToClients)
         }
     }
 
     fn on_timeout(&mut self) -> Result<(), Error> {
+        self.monitor.tick();
         self.monitor.display("Broker Heartbeat", ClientId(0));
         Ok(())
     }
+
+    fn on_client_lost(
+        &mut self,
+        _broker_inner: &mut LlmpBrokerInner<SP>,
+        client_id: ClientId,
+    ) -> Result<(), Error> {
+        self.hooks.on_client_lost_all(client_id)?;
+        let event: Event<I> = Event::ClientLost { client_id };
+        Self::handle_in_broker(&mut self.monitor, client_id, &event)?;
+        Ok(())
+    }
 }
 
 impl<I, MT> StdLlmpEventHook<I, MT>
@@ -95,14 +449,62 @@ where
 {
     /// Create an event broker from a raw broker.
     pub fn new(monitor: MT) -> Result<Self, Error> {
+        Self::with_hooks(monitor, ())
+    }
+}
+
+impl<I, MT, BH> StdLlmpEventHook<I, MT, BH>
+where
+    I: Input,
+    MT: Monitor,
+    BH: BrokerHooksTuple<I>,
+{
+    /// Create an event broker from a raw broker, calling the given [`BrokerHook`]s on every event
+    pub fn with_hooks(monitor: MT, hooks: BH) -> Result<Self, Error> {
         Ok(Self {
             monitor,
+            hooks,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            #[cfg(feature = "std")]
+            injector: CommandInjector::default(),
             phantom: PhantomData,
         })
     }
 
+    /// Serialize the events with another [`Codec`]. All clients have to use the same codec, see
+    /// [`crate::events::LlmpEventManagerBuilder::codec`].
+    #[must_use]
+    pub fn with_codec<C>(self) -> StdLlmpEventHook<I, MT, BH, C>
+    where
+        C: Codec,
+    {
+        StdLlmpEventHook {
+            monitor: self.monitor,
+            hooks: self.hooks,
+            #[cfg(feature = "llmp_compression")]
+            compressor: self.compressor,
+            #[cfg(feature = "std")]
+            injector: self.injector,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, MT, BH, C> StdLlmpEventHook<I, MT, BH, C>
+where
+    I: Input,
+    MT: Monitor,
+    BH: BrokerHooksTuple<I>,
+    C: Codec,
+{
+    /// A handle to inject [`Event::Command`]s and [`Event::ConfigUpdate`]s, which will be broadcast to all clients
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn command_injector(&self) -> CommandInjector {
+        self.injector.clone()
+    }
+
     /// Handle arriving events in the broker
     #[allow(clippy::unnecessary_wraps)]
     fn handle_in_broker(
@@ -131,12 +533,16 @@ where
             Event::UpdateExecStats {
                 time,
                 executions,
+                host_metrics,
                 phantom: _,
             } => {
                 // TODO: The monitor buffer should be added on client add.
                 monitor.client_stats_insert(client_id);
                 let client = monitor.client_stats_mut_for(client_id);
                 client.update_executions(*executions, *time);
+                if let Some(host_metrics) = host_metrics {
+                    client.update_host_metrics(*host_metrics, *time);
+                }
                 monitor.display(event.name(), client_id);
                 Ok(BrokerEventResult::Handled)
             }
@@ -157,6 +563,7 @@ where
                 time,
                 executions,
                 introspection_monitor,
+                host_metrics,
                 phantom: _,
             } => {
                 // TODO: The monitor buffer should be added on client add.
@@ -167,6 +574,9 @@ where
 
                 // Update the normal monitor for this client
                 client.update_executions(*executions, *time);
+                if let Some(host_metrics) = host_metrics {
+                    client.update_host_metrics(*host_metrics, *time);
+                }
 
                 // Update the performance monitor for this client
                 client.update_introspection_monitor((**introspection_monitor).clone());
@@ -196,7 +606,149 @@ where
             }
             Event::CustomBuf { .. } => Ok(BrokerEventResult::Forward),
             Event::Stop => Ok(BrokerEventResult::Forward),
-            //_ => Ok(BrokerEventResult::Forward),
+            Event::Command { .. } | Event::NewTokens { .. } | Event::ConfigUpdate { .. } => {
+                Ok(BrokerEventResult::Forward)
+            }
+            Event::CommandAck { id, command } => {
+                log::info!("Client {client_id:?} acknowledged command {command} (id {id})");
+                Ok(BrokerEventResult::Handled)
+            }
+            Event::ClientLost { client_id } => {
+                // Stop counting the client, it is re-enabled by its next message
+                if let Some(client) = monitor.client_stats_mut().get_mut(client_id.0 as usize) {
+                    client.enabled = false;
+                }
+                monitor.display(event.name(), *client_id);
+                Ok(BrokerEventResult::Handled)
+            } //_ => Ok(BrokerEventResult::Forward),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+    use core::time::Duration;
+    use std::thread::sleep;
+
+    use libafl_bolts::{
+        llmp::{LlmpClient, LlmpConnection},
+        shmem::{ShMemProvider, StdShMemProvider},
+        tuples::tuple_list,
+        ClientId,
+    };
+    use serial_test::serial;
+
+    use super::{BrokerHook, BrokerHookResult, BrokerHooks, BrokerHooksTuple, StdLlmpEventHook};
+    use crate::{
+        events::{llmp::LLMP_TAG_EVENT_TO_BOTH, Event},
+        inputs::BytesInput,
+        monitors::NopMonitor,
+        Error,
+    };
+
+    /// Replaces the buffer of all custom buf events
+    struct ReplaceBufHook;
+
+    impl<I> BrokerHook<I> for ReplaceBufHook {
+        fn on_event(
+            &mut self,
+            _client_id: ClientId,
+            event: &mut Event<I>,
+        ) -> Result<BrokerHookResult, Error> {
+            if let Event::CustomBuf { buf, .. } = event {
+                *buf = b"replaced".to_vec();
+                return Ok(BrokerHookResult::Modified);
+            }
+            Ok(BrokerHookResult::Continue)
+        }
+    }
+
+    /// Drops all events
+    struct DropHook;
+
+    impl<I> BrokerHook<I> for DropHook {
+        fn on_event(
+            &mut self,
+            _client_id: ClientId,
+            _event: &mut Event<I>,
+        ) -> Result<BrokerHookResult, Error> {
+            Ok(BrokerHookResult::Drop)
+        }
+    }
+
+    fn custom_buf() -> Event<BytesInput> {
+        Event::CustomBuf {
+            buf: b"original".to_vec(),
+            tag: String::from("test"),
         }
     }
+
+    #[test]
+    fn test_broker_hooks() {
+        let mut hooks = BrokerHooks::new()
+            .add::<BytesInput, _>(ReplaceBufHook)
+            .add::<BytesInput, _>(DropHook);
+        let mut taken = hooks.take::<BytesInput>().unwrap();
+        assert!(hooks.is_empty());
+
+        let mut event = custom_buf();
+        assert_eq!(
+            taken.on_event_all(ClientId(1), &mut event, 0).unwrap(),
+            BrokerHookResult::Drop
+        );
+        let Event::CustomBuf { buf, .. } = event else {
+            panic!("The hook changed the event type");
+        };
+        assert_eq!(buf, b"replaced");
+
+        // The hooks were added for another input type
+        let mut hooks = BrokerHooks::new().add::<BytesInput, _>(DropHook);
+        assert!(hooks.take::<Vec<u8>>().is_err());
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    fn test_modified_event_forwarded_for_sender() {
+        let shmem_provider = StdShMemProvider::new().unwrap();
+        let port = 13_993;
+        let LlmpConnection::IsBroker { broker } =
+            LlmpConnection::on_port(shmem_provider.clone(), port).unwrap()
+        else {
+            panic!("Could not bind to port {port} as broker");
+        };
+        let hooks = BrokerHooks::new()
+            .add::<BytesInput, _>(ReplaceBufHook)
+            .take::<BytesInput>()
+            .unwrap();
+        let llmp_hook =
+            StdLlmpEventHook::<BytesInput, _, _>::with_hooks(NopMonitor::new(), hooks).unwrap();
+        let mut broker = broker.add_hooks(tuple_list!(llmp_hook));
+
+        let mut sender = LlmpClient::create_attach_to_tcp(shmem_provider.clone(), port).unwrap();
+        let mut receiver = LlmpClient::create_attach_to_tcp(shmem_provider, port).unwrap();
+        // Give the (background) tcp thread a few millis to post the new clients
+        sleep(Duration::from_millis(100));
+        broker.broker_once().unwrap();
+
+        sender
+            .send_buf(
+                LLMP_TAG_EVENT_TO_BOTH,
+                &postcard::to_allocvec(&custom_buf()).unwrap(),
+            )
+            .unwrap();
+        broker.broker_once().unwrap();
+
+        // The modified event comes from the sender, so the sender's event manager skips it
+        let sender_id = sender.sender().id();
+        let (client_id, _, buf) = receiver.recv_buf_blocking().unwrap();
+        assert_eq!(client_id, sender_id);
+        let Event::CustomBuf { buf, .. } = postcard::from_bytes::<Event<BytesInput>>(buf).unwrap()
+        else {
+            panic!("Received another event");
+        };
+        assert_eq!(buf, b"replaced");
+    }
 }
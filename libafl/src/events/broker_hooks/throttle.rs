@@ -0,0 +1,333 @@
+//! A [`BrokerHook`] accounting the bytes of testcases forwarded per client, and throttling them, see
+//! [`BandwidthThrottleHook`].
+
+use alloc::vec::Vec;
+use core::{marker::PhantomData, time::Duration};
+use std::sync::{Arc, Mutex};
+
+use hashbrown::HashMap;
+use libafl_bolts::{current_time, ClientId};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{BrokerHook, BrokerHookResult, Event},
+    inputs::Input,
+    Error,
+};
+
+/// The default time a client may send at more than its rate, after sending nothing for a while
+pub const DEFAULT_THROTTLE_BURST: Duration = Duration::from_secs(1);
+
+/// The testcase and objective bytes of one client which passed through the broker, and which were throttled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientBandwidth {
+    /// The bytes of the forwarded testcases and objectives
+    pub forwarded_bytes: u64,
+    /// The number of forwarded testcases
+    pub forwarded_testcases: u64,
+    /// The number of forwarded objectives
+    pub forwarded_objectives: u64,
+    /// The bytes of the testcases dropped by the throttling
+    pub throttled_bytes: u64,
+    /// The number of testcases dropped by the throttling
+    pub throttled_testcases: u64,
+}
+
+impl ClientBandwidth {
+    /// Add the bandwidth of another client
+    fn add(&mut self, other: &Self) {
+        self.forwarded_bytes += other.forwarded_bytes;
+        self.forwarded_testcases += other.forwarded_testcases;
+        self.forwarded_objectives += other.forwarded_objectives;
+        self.throttled_bytes += other.throttled_bytes;
+        self.throttled_testcases += other.throttled_testcases;
+    }
+}
+
+/// A handle to the bandwidth accounting of a running broker, see [`BandwidthThrottleHook::stats`]
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthStats {
+    clients: Arc<Mutex<HashMap<ClientId, ClientBandwidth>>>,
+}
+
+impl BandwidthStats {
+    /// The bandwidth of the given client
+    #[must_use]
+    pub fn client(&self, client_id: ClientId) -> ClientBandwidth {
+        self.clients
+            .lock()
+            .unwrap()
+            .get(&client_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The bandwidth of all clients
+    #[must_use]
+    pub fn clients(&self) -> Vec<(ClientId, ClientBandwidth)> {
+        let mut clients: Vec<_> = self
+            .clients
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, bandwidth)| (*id, *bandwidth))
+            .collect();
+        clients.sort_unstable_by_key(|(id, _)| *id);
+        clients
+    }
+
+    /// The bandwidth summed over all clients
+    #[must_use]
+    pub fn total(&self) -> ClientBandwidth {
+        let mut total = ClientBandwidth::default();
+        for bandwidth in self.clients.lock().unwrap().values() {
+            total.add(bandwidth);
+        }
+        total
+    }
+
+    fn update<F>(&self, client_id: ClientId, f: F)
+    where
+        F: FnOnce(&mut ClientBandwidth),
+    {
+        f(self.clients.lock().unwrap().entry(client_id).or_default());
+    }
+}
+
+/// The bytes a client may still send, refilled at the rate of the throttle
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    /// Negative after objectives exceeded the rate, which delays the next ordinary testcases
+    tokens: i64,
+    last_refill: Duration,
+}
+
+/// A [`BrokerHook`] accounting the bytes of the messages carrying the testcases each client sends, and dropping
+/// the [`Event::NewTestcase`]s of clients exceeding a maximum rate, so campaigns with gigantic inputs do not
+/// saturate the shared memory and the network bridges between the brokers.
+///
+/// The rate is enforced with a token bucket per client, which allows bursts of [`DEFAULT_THROTTLE_BURST`] at the
+/// full rate. [`Event::Objective`]s are never dropped, but they still use up the budget of their client, delaying
+/// its next testcases. The testcases forwarded by other brokers are accounted to the client which found them.
+/// Without a rate, the hook only does the accounting. Register it with
+/// [`crate::events::StdLlmpEventHook::with_hooks`].
+#[derive(Debug)]
+pub struct BandwidthThrottleHook<I> {
+    max_bytes_per_sec: Option<u64>,
+    burst: Duration,
+    buckets: HashMap<ClientId, TokenBucket>,
+    stats: BandwidthStats,
+    phantom: PhantomData<I>,
+}
+
+impl<I> Default for BandwidthThrottleHook<I> {
+    fn default() -> Self {
+        Self::accounting()
+    }
+}
+
+impl<I> BandwidthThrottleHook<I> {
+    /// Creates a new [`BandwidthThrottleHook`], limiting the testcases of each client to `max_kib_per_sec` KiB/s
+    #[must_use]
+    pub fn new(max_kib_per_sec: u64) -> Self {
+        Self {
+            max_bytes_per_sec: Some(max_kib_per_sec * 1024),
+            ..Self::accounting()
+        }
+    }
+
+    /// Creates a new [`BandwidthThrottleHook`] only accounting the bytes, without throttling
+    #[must_use]
+    pub fn accounting() -> Self {
+        Self {
+            max_bytes_per_sec: None,
+            burst: DEFAULT_THROTTLE_BURST,
+            buckets: HashMap::new(),
+            stats: BandwidthStats::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets how long a client may send at its full rate after sending nothing for a while
+    #[must_use]
+    pub fn with_burst(mut self, burst: Duration) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    /// A handle to the bandwidth accounting, which stays valid once the hook is registered in the broker
+    #[must_use]
+    pub fn stats(&self) -> BandwidthStats {
+        self.stats.clone()
+    }
+
+    /// If the client may send a testcase of `len` bytes at `now`, and uses up its budget if so.
+    ///
+    /// Testcases larger than the burst pass once the bucket is full, so they are slowed down but never starved.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        clippy::cast_precision_loss
+    )]
+    fn admit(&mut self, client_id: ClientId, len: u64, objective: bool, now: Duration) -> bool {
+        let Some(rate) = self.max_bytes_per_sec else {
+            return true;
+        };
+        let capacity = (rate as f64 * self.burst.as_secs_f64()) as i64;
+        let bucket = self.buckets.entry(client_id).or_insert(TokenBucket {
+            tokens: capacity,
+            last_refill: now,
+        });
+        let refill = (rate as f64 * now.saturating_sub(bucket.last_refill).as_secs_f64()) as i64;
+        bucket.tokens = bucket.tokens.saturating_add(refill).min(capacity);
+        bucket.last_refill = now;
+
+        let len = len as i64;
+        if objective || bucket.tokens >= len.min(capacity) {
+            bucket.tokens -= len;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn on_event_at(
+        &mut self,
+        client_id: ClientId,
+        event: &Event<I>,
+        msg_len: usize,
+        now: Duration,
+    ) -> BrokerHookResult {
+        let (client_id, objective) = match event {
+            Event::NewTestcase { forward_id, .. } => (forward_id.unwrap_or(client_id), false),
+            Event::Objective { .. } => (client_id, true),
+            _ => return BrokerHookResult::Continue,
+        };
+        let len = msg_len as u64;
+
+        if self.admit(client_id, len, objective, now) {
+            self.stats.update(client_id, |bandwidth| {
+                bandwidth.forwarded_bytes += len;
+                if objective {
+                    bandwidth.forwarded_objectives += 1;
+                } else {
+                    bandwidth.forwarded_testcases += 1;
+                }
+            });
+            BrokerHookResult::Continue
+        } else {
+            self.stats.update(client_id, |bandwidth| {
+                bandwidth.throttled_bytes += len;
+                bandwidth.throttled_testcases += 1;
+            });
+            BrokerHookResult::Drop
+        }
+    }
+}
+
+impl<I> BrokerHook<I> for BandwidthThrottleHook<I>
+where
+    I: Input,
+{
+    fn on_event(
+        &mut self,
+        client_id: ClientId,
+        event: &mut Event<I>,
+    ) -> Result<BrokerHookResult, Error> {
+        // Called without the message, e.g. directly, so account the size of the serialized event instead
+        let msg_len = postcard::to_allocvec(&*event)?.len();
+        self.on_event_with_len(client_id, event, msg_len)
+    }
+
+    fn on_event_with_len(
+        &mut self,
+        client_id: ClientId,
+        event: &mut Event<I>,
+        msg_len: usize,
+    ) -> Result<BrokerHookResult, Error> {
+        Ok(self.on_event_at(client_id, event, msg_len, current_time()))
+    }
+
+    fn on_client_lost(&mut self, client_id: ClientId) -> Result<(), Error> {
+        self.buckets.remove(&client_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl_bolts::ClientId;
+
+    use super::BandwidthThrottleHook;
+    use crate::{
+        corpus::GlobalTestcaseId,
+        events::{BrokerHookResult, Event, EventConfig},
+        executors::ExitKind,
+        inputs::BytesInput,
+    };
+
+    fn new_testcase() -> Event<BytesInput> {
+        let input = BytesInput::new(vec![0; 4]);
+        Event::NewTestcase {
+            global_id: GlobalTestcaseId::of(&input).unwrap(),
+            input,
+            observers_buf: None,
+            exit_kind: ExitKind::Ok,
+            corpus_size: 1,
+            client_config: EventConfig::AlwaysUnique,
+            time: Duration::ZERO,
+            forward_id: None,
+            parent_global_id: None,
+            #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
+            node_id: None,
+        }
+    }
+
+    #[test]
+    fn test_bandwidth_throttle_hook() {
+        let mut hook = BandwidthThrottleHook::new(1);
+        let stats = hook.stats();
+        let client = ClientId(1);
+        let at = Duration::from_millis;
+
+        // The hook accounts the length of the messages, not the size of the events
+        let testcase = new_testcase();
+        assert_eq!(
+            hook.on_event_at(client, &testcase, 600, at(0)),
+            BrokerHookResult::Continue
+        );
+        // The budget of 1 KiB is used up
+        assert_eq!(
+            hook.on_event_at(client, &testcase, 600, at(100)),
+            BrokerHookResult::Drop
+        );
+        // Objectives always pass
+        let objective = Event::Objective {
+            objective_size: 1,
+            time: Duration::ZERO,
+        };
+        assert_eq!(
+            hook.on_event_at(client, &objective, 600, at(100)),
+            BrokerHookResult::Continue
+        );
+        // Other clients have their own budget
+        assert_eq!(
+            hook.on_event_at(ClientId(2), &testcase, 600, at(100)),
+            BrokerHookResult::Continue
+        );
+        // The budget refills over time
+        assert_eq!(
+            hook.on_event_at(client, &testcase, 600, at(1500)),
+            BrokerHookResult::Continue
+        );
+
+        let bandwidth = stats.client(client);
+        assert_eq!(bandwidth.forwarded_testcases, 2);
+        assert_eq!(bandwidth.forwarded_objectives, 1);
+        assert_eq!(bandwidth.forwarded_bytes, 1800);
+        assert_eq!(bandwidth.throttled_testcases, 1);
+        assert_eq!(stats.total().forwarded_testcases, 3);
+    }
+}
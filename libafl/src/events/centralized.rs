@@ -30,6 +30,10 @@ use crate::events::llmp::COMPRESS_THRESHOLD;
 #[cfg(feature = "scalability_introspection")]
 use crate::state::HasScalabilityMonitor;
 use crate::{
+    corpus::{
+        global_id::{clear_remote_testcase, expect_remote_testcase, is_known_global_id},
+        GlobalIdMetadata,
+    },
     events::{
         AdaptiveSerializer, CustomBufEventResult, Event, EventConfig, EventFirer, EventManager,
         EventManagerHooksTuple, EventManagerId, EventProcessor, EventRestarter,
@@ -39,7 +43,10 @@ use crate::{
     fuzzer::{EvaluatorObservers, ExecutionProcessor},
     inputs::{Input, NopInput, UsesInput},
     observers::{ObserversTuple, TimeObserver},
-    state::{HasExecutions, HasLastReportTime, NopState, State, Stoppable, UsesState},
+    stages::import_tokens,
+    state::{
+        HasExecutions, HasLastReportTime, HasRuntimeConfig, NopState, State, Stoppable, UsesState,
+    },
     Error, HasMetadata,
 };
 
@@ -288,7 +295,7 @@ where
         if !self.is_main {
             // secondary node
             let mut is_tc = false;
-            // Forward to main only if new tc or heartbeat
+            // Forward to main only if new tc, heartbeat, stop, new tokens or config update
             let should_be_forwarded = match &mut event {
                 Event::NewTestcase { forward_id, .. } => {
                     *forward_id = Some(ClientId(self.inner.mgr_id().0 as u32));
@@ -296,7 +303,7 @@ where
                     true
                 }
                 Event::UpdateExecStats { .. } => true, // send it but this guy won't be handled. the only purpose is to keep this client alive else the broker thinks it is dead and will dc it
-                Event::Stop => true,
+                Event::Stop | Event::NewTokens { .. } | Event::ConfigUpdate { .. } => true,
                 _ => false,
             };
 
@@ -593,6 +600,9 @@ where
         let event_name = event.name_detailed();
 
         match event {
+            Event::NewTestcase { global_id, .. } if is_known_global_id(state, global_id) => {
+                log::debug!("{event_name} ({global_id}) is in the corpus already, discarding");
+            }
             Event::NewTestcase {
                 input,
                 client_config,
@@ -601,6 +611,8 @@ where
                 observers_buf,
                 time,
                 forward_id,
+                global_id,
+                parent_global_id,
                 #[cfg(feature = "multi_machine")]
                 node_id,
             } => {
@@ -609,6 +621,15 @@ where
                     event_name
                 );
 
+                expect_remote_testcase(
+                    state,
+                    GlobalIdMetadata {
+                        id: global_id,
+                        origin: Some(forward_id.unwrap_or(client_id)),
+                        parent: parent_global_id,
+                    },
+                );
+
                 let res =
                     if client_config.match_with(&self.configuration()) && observers_buf.is_some() {
                         let observers: E::Observers =
@@ -648,6 +669,7 @@ where
                             false,
                         )?
                     };
+                clear_remote_testcase(state);
 
                 if let Some(item) = res.1 {
                     let event = Event::NewTestcase {
@@ -658,6 +680,8 @@ where
                         observers_buf,
                         time,
                         forward_id,
+                        global_id,
+                        parent_global_id,
                         #[cfg(feature = "multi_machine")]
                         node_id,
                     };
@@ -678,6 +702,12 @@ where
             Event::Stop => {
                 state.request_stop();
             }
+            Event::NewTokens { tokens } => {
+                import_tokens(state, &tokens);
+            }
+            Event::ConfigUpdate { config } => {
+                state.apply_runtime_config(&config);
+            }
             _ => {
                 return Err(Error::unknown(format!(
                     "Received illegal message that message should not have arrived: {:?}.",
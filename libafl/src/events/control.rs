@@ -0,0 +1,385 @@
+//! An HTTP control plane for a running fuzzer.
+//!
+//! The [`ControlServer`] listens for REST requests and queues up [`ControlCommand`]s.
+//! A [`crate::stages::ControlStage`] in the fuzzer picks them up, applies them locally, and sends them
+//! off as [`Event::CustomBuf`] with the [`CONTROL_TAG`], so they propagate through the broker to all clients.
+//! Clients apply commands they receive after calling [`install_control_handler`] on their event manager.
+//!
+//! Endpoints:
+//! - `GET /stats`: the last stats reported by the fuzzer, as JSON
+//! - `POST /pause`, `POST /resume`: pause or resume all clients
+//! - `POST /sync`: sync the corpus in the next [`crate::stages::SyncFromDiskStage`], regardless of its interval
+//! - `POST /minimize`: minimize the corpus in the next [`crate::stages::control::ControlMinimizeStage`]
+//! - `POST /timeout`: set a new execution timeout, body `{"millis": 1000}`, applied by the
+//!   [`crate::stages::RuntimeConfigStage`]
+//! - `POST /schedule`: set the power schedule, body `{"schedule": "fast"}` (or `null` for none)
+//! - `GET /objectives`, `GET /objectives/:name`: list and download the objectives
+
+use alloc::{boxed::Box, collections::VecDeque, string::String, vec::Vec};
+use core::time::Duration;
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use async_std::task::block_on;
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+use tide::Request;
+
+use crate::{
+    events::{
+        CommandMetadata, CustomBufEventResult, Event, EventCommand, EventFirer,
+        HasCustomBufHandlers,
+    },
+    inputs::UsesInput,
+    schedulers::powersched::BaseSchedule,
+    state::{HasRuntimeConfig, RuntimeConfig},
+    Error, HasMetadata,
+};
+
+/// The tag of [`Event::CustomBuf`]s carrying a [`ControlCommand`]
+pub const CONTROL_TAG: &str = "libafl_control";
+
+/// A command issued by the operator of a campaign
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Stop fuzzing until [`ControlCommand::Resume`] is received
+    Pause,
+    /// Continue fuzzing after a [`ControlCommand::Pause`]
+    Resume,
+    /// Request a sync of the corpus, e.g. from disk
+    SyncCorpus,
+    /// Request a minimization of the corpus
+    MinimizeCorpus,
+    /// Change the execution timeout
+    SetTimeout(Duration),
+    /// Change the power schedule, `None` disables power scheduling
+    SetPowerSchedule(Option<BaseSchedule>),
+}
+
+impl ControlCommand {
+    /// Serialize this command and wrap it into an [`Event::CustomBuf`]
+    pub fn to_event<I>(&self) -> Result<Event<I>, Error>
+    where
+        I: crate::inputs::Input,
+    {
+        Ok(Event::CustomBuf {
+            buf: postcard::to_allocvec(self)?,
+            tag: CONTROL_TAG.into(),
+        })
+    }
+
+    /// Apply this command to the given state.
+    ///
+    /// Pausing works like an [`EventCommand::Pause`], see [`crate::events::wait_while_paused`].
+    /// The timeout and the power schedule are applied to the [`RuntimeConfig`] of the state.
+    pub fn apply<S>(&self, state: &mut S)
+    where
+        S: HasMetadata,
+    {
+        match self {
+            ControlCommand::Pause => state
+                .metadata_or_insert_with(CommandMetadata::default)
+                .apply(EventCommand::Pause),
+            ControlCommand::Resume => state
+                .metadata_or_insert_with(CommandMetadata::default)
+                .apply(EventCommand::Resume),
+            ControlCommand::SetTimeout(timeout) => state.apply_runtime_config(&RuntimeConfig {
+                timeout: Some(*timeout),
+                ..RuntimeConfig::default()
+            }),
+            ControlCommand::SetPowerSchedule(schedule) => {
+                state.apply_runtime_config(&RuntimeConfig {
+                    power_schedule: Some(*schedule),
+                    ..RuntimeConfig::default()
+                });
+            }
+            _ => state
+                .metadata_or_insert_with(ControlMetadata::default)
+                .apply(self),
+        }
+    }
+}
+
+/// The corpus sync and minimization requests received by this client, that were not acted on yet
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct ControlMetadata {
+    sync_requested: bool,
+    minimize_requested: bool,
+}
+
+impl_serdeany!(ControlMetadata);
+
+impl ControlMetadata {
+    /// Update this metadata according to the command
+    pub fn apply(&mut self, command: &ControlCommand) {
+        match command {
+            ControlCommand::SyncCorpus => self.sync_requested = true,
+            ControlCommand::MinimizeCorpus => self.minimize_requested = true,
+            ControlCommand::Pause
+            | ControlCommand::Resume
+            | ControlCommand::SetTimeout(_)
+            | ControlCommand::SetPowerSchedule(_) => {}
+        }
+    }
+
+    /// Returns `true` once, if a corpus sync was requested since the last call.
+    ///
+    /// Used by the [`crate::stages::SyncFromDiskStage`].
+    pub fn take_sync_request(&mut self) -> bool {
+        core::mem::take(&mut self.sync_requested)
+    }
+
+    /// Returns `true` once, if a corpus minimization was requested since the last call.
+    ///
+    /// Used by the [`crate::stages::control::ControlMinimizeStage`].
+    pub fn take_minimize_request(&mut self) -> bool {
+        core::mem::take(&mut self.minimize_requested)
+    }
+}
+
+/// Adds a custom buf handler to the event manager, applying all incoming [`ControlCommand`]s to the state
+pub fn install_control_handler<EM>(mgr: &mut EM)
+where
+    EM: HasCustomBufHandlers,
+    EM::State: HasMetadata,
+{
+    mgr.add_custom_buf_handler(Box::new(|state, tag, buf| {
+        if tag != CONTROL_TAG {
+            return Ok(CustomBufEventResult::Next);
+        }
+        let command: ControlCommand = postcard::from_bytes(buf)?;
+        log::info!("Received control command {command:?}");
+        command.apply(state);
+        Ok(CustomBufEventResult::Handled)
+    }));
+}
+
+/// Fire a [`ControlCommand`] to all other clients and apply it locally
+pub fn fire_control_command<EM>(
+    mgr: &mut EM,
+    state: &mut EM::State,
+    command: &ControlCommand,
+) -> Result<(), Error>
+where
+    EM: EventFirer,
+    EM::State: HasMetadata,
+{
+    command.apply(state);
+    mgr.fire(
+        state,
+        command.to_event::<<EM::State as UsesInput>::Input>()?,
+    )
+}
+
+/// The stats exposed by the [`ControlServer`] at `/stats`
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ControlStats {
+    /// The executions of this client
+    pub executions: u64,
+    /// The corpus size of this client
+    pub corpus_size: usize,
+    /// The amount of objectives found by this client
+    pub objective_size: usize,
+    /// If the fuzzer is paused
+    pub paused: bool,
+    /// The execution timeout set at runtime, in milliseconds
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct ControlShared {
+    pending: VecDeque<ControlCommand>,
+    stats: ControlStats,
+}
+
+#[derive(Clone)]
+struct ServerState {
+    shared: Arc<Mutex<ControlShared>>,
+    objectives_dir: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct TimeoutBody {
+    millis: u64,
+}
+
+#[derive(Deserialize)]
+struct ScheduleBody {
+    schedule: Option<String>,
+}
+
+/// Parse the (case-insensitive) name of a [`BaseSchedule`]
+fn parse_schedule(name: &str) -> Option<BaseSchedule> {
+    match name.to_lowercase().as_str() {
+        "explore" => Some(BaseSchedule::EXPLORE),
+        "exploit" => Some(BaseSchedule::EXPLOIT),
+        "fast" => Some(BaseSchedule::FAST),
+        "coe" => Some(BaseSchedule::COE),
+        "lin" => Some(BaseSchedule::LIN),
+        "quad" => Some(BaseSchedule::QUAD),
+        _ => None,
+    }
+}
+
+/// An HTTP server to control a running fuzzer.
+///
+/// Commands are only queued by the server, a [`crate::stages::ControlStage`] has to pick them up.
+#[derive(Debug, Clone)]
+pub struct ControlServer {
+    shared: Arc<Mutex<ControlShared>>,
+}
+
+impl ControlServer {
+    /// Start a new [`ControlServer`] listening at `listener`, e.g. `127.0.0.1:8080`, in a new thread.
+    ///
+    /// If `objectives_dir` is set, the objectives in it can be listed and downloaded.
+    #[must_use]
+    pub fn new(listener: String, objectives_dir: Option<PathBuf>) -> Self {
+        let shared = Arc::new(Mutex::new(ControlShared::default()));
+        let state = ServerState {
+            shared: shared.clone(),
+            objectives_dir,
+        };
+        thread::spawn(move || {
+            block_on(serve_control(listener, state))
+                .map_err(|err| log::error!("{err:?}"))
+                .ok();
+        });
+        Self { shared }
+    }
+
+    /// Take all commands received since the last call
+    #[must_use]
+    pub fn take_commands(&self) -> Vec<ControlCommand> {
+        self.shared.lock().unwrap().pending.drain(..).collect()
+    }
+
+    /// Update the stats exposed at `/stats`
+    pub fn update_stats(&self, stats: ControlStats) {
+        self.shared.lock().unwrap().stats = stats;
+    }
+}
+
+fn queue(req: &Request<ServerState>, command: ControlCommand) -> tide::Result {
+    req.state()
+        .shared
+        .lock()
+        .unwrap()
+        .pending
+        .push_back(command);
+    Ok(tide::Response::new(202))
+}
+
+/// Set up the HTTP endpoints
+async fn serve_control(listener: String, state: ServerState) -> Result<(), std::io::Error> {
+    let mut app = tide::with_state(state);
+
+    app.at("/").get(|_| async { Ok("LibAFL Control Server") });
+    app.at("/stats")
+        .get(|req: Request<ServerState>| async move {
+            let stats = req.state().shared.lock().unwrap().stats.clone();
+            Ok(tide::Body::from_json(&stats)?)
+        });
+    app.at("/pause")
+        .post(|req: Request<ServerState>| async move { queue(&req, ControlCommand::Pause) });
+    app.at("/resume")
+        .post(|req: Request<ServerState>| async move { queue(&req, ControlCommand::Resume) });
+    app.at("/sync")
+        .post(|req: Request<ServerState>| async move { queue(&req, ControlCommand::SyncCorpus) });
+    app.at("/minimize")
+        .post(
+            |req: Request<ServerState>| async move { queue(&req, ControlCommand::MinimizeCorpus) },
+        );
+    app.at("/timeout")
+        .post(|mut req: Request<ServerState>| async move {
+            let body: TimeoutBody = req.body_json().await?;
+            queue(
+                &req,
+                ControlCommand::SetTimeout(Duration::from_millis(body.millis)),
+            )
+        });
+    app.at("/schedule")
+        .post(|mut req: Request<ServerState>| async move {
+            let body: ScheduleBody = req.body_json().await?;
+            let schedule = match body.schedule {
+                None => None,
+                Some(name) => match parse_schedule(&name) {
+                    Some(schedule) => Some(schedule),
+                    None => return Ok(tide::Response::new(400)),
+                },
+            };
+            queue(&req, ControlCommand::SetPowerSchedule(schedule))
+        });
+    app.at("/objectives")
+        .get(|req: Request<ServerState>| async move {
+            let Some(dir) = &req.state().objectives_dir else {
+                return Ok(tide::Response::new(404));
+            };
+            let names: Vec<String> = fs::read_dir(dir)?
+                .filter_map(Result::ok)
+                .filter(|entry| entry.path().is_file())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect();
+            Ok(tide::Body::from_json(&names)?.into())
+        });
+    app.at("/objectives/:name")
+        .get(|req: Request<ServerState>| async move {
+            let name = req.param("name")?;
+            let Some(dir) = &req.state().objectives_dir else {
+                return Ok(tide::Response::new(404));
+            };
+            if name.contains('/') || name.contains('\\') || name.starts_with('.') {
+                return Ok(tide::Response::new(400));
+            }
+            let bytes = fs::read(dir.join(name))?;
+            Ok(tide::Response::builder(200)
+                .body(bytes)
+                .content_type("application/octet-stream")
+                .build())
+        });
+    app.listen(listener).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{ControlCommand, ControlMetadata};
+    use crate::{
+        events::CommandMetadata,
+        inputs::BytesInput,
+        state::{HasRuntimeConfig, NopState},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_control_metadata() {
+        let mut state = NopState::<BytesInput>::new();
+        ControlCommand::Pause.apply(&mut state);
+        assert!(state.metadata::<CommandMetadata>().unwrap().paused());
+        ControlCommand::SetTimeout(Duration::from_millis(10)).apply(&mut state);
+        ControlCommand::SyncCorpus.apply(&mut state);
+        ControlCommand::Resume.apply(&mut state);
+        assert_eq!(
+            state.runtime_config().timeout,
+            Some(Duration::from_millis(10))
+        );
+
+        assert!(!state.metadata::<CommandMetadata>().unwrap().paused());
+
+        let meta = state.metadata_mut::<ControlMetadata>().unwrap();
+        assert!(meta.take_sync_request());
+        assert!(!meta.take_sync_request());
+        assert!(!meta.take_minimize_request());
+    }
+}
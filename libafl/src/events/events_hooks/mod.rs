@@ -6,6 +6,11 @@ use libafl_bolts::ClientId;
 
 use crate::{events::Event, state::State, Error};
 
+#[cfg(feature = "nautilus")]
+pub mod nautilus;
+#[cfg(feature = "nautilus")]
+pub use nautilus::NautilusContextHook;
+
 /// The `broker_hooks` that are run before and after the event manager calls `handle_in_client`
 pub trait EventManagerHook<S>
 where
@@ -0,0 +1,126 @@
+//! An [`EventManagerHook`] for [`NautilusInput`]s, dropping the trees that are not valid in this grammar.
+
+use libafl_bolts::ClientId;
+
+use crate::{
+    events::{Event, EventManagerHook},
+    generators::NautilusContext,
+    inputs::{NautilusInput, UsesInput},
+    state::State,
+    Error,
+};
+
+/// Drops the [`NautilusInput`]s of other clients that are not derivations of this grammar, before they are evaluated.
+///
+/// A derivation tree is a list of rule ids, which are meaningless in another context: these inputs would be
+/// unparsed to garbage, or crash the mutators. Trees of an unknown or another context are synced, as long as they
+/// are valid in this context, see [`NautilusInput::is_valid_for`].
+#[derive(Debug, Clone, Copy)]
+pub struct NautilusContextHook<'a> {
+    context: &'a NautilusContext,
+}
+
+impl<'a> NautilusContextHook<'a> {
+    /// Create a new [`NautilusContextHook`], accepting the inputs that are valid in `context`
+    #[must_use]
+    pub fn new(context: &'a NautilusContext) -> Self {
+        Self { context }
+    }
+}
+
+impl<S> EventManagerHook<S> for NautilusContextHook<'_>
+where
+    S: State + UsesInput<Input = NautilusInput>,
+{
+    fn pre_exec(
+        &mut self,
+        _state: &mut S,
+        client_id: ClientId,
+        event: &Event<S::Input>,
+    ) -> Result<bool, Error> {
+        if let Event::NewTestcase { input, .. } = event {
+            if !input.is_valid_for(self.context) {
+                log::warn!(
+                    "Dropping a testcase of client {client_id:?}, not valid in this grammar"
+                );
+                return Ok(false);
+            }
+            if input.context_hash().is_some() && !input.is_derived_from(self.context) {
+                log::debug!(
+                    "Syncing a testcase of client {client_id:?}, derived from another grammar"
+                );
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl_bolts::ClientId;
+
+    use super::NautilusContextHook;
+    use crate::{
+        common::nautilus::grammartec::{rule::RuleIdOrCustom, tree::Tree},
+        corpus::GlobalTestcaseId,
+        events::{Event, EventConfig, EventManagerHook},
+        executors::ExitKind,
+        generators::NautilusContext,
+        inputs::NautilusInput,
+        state::NopState,
+    };
+
+    fn new_testcase(input: NautilusInput) -> Event<NautilusInput> {
+        Event::NewTestcase {
+            global_id: GlobalTestcaseId::of(&input).unwrap(),
+            input,
+            observers_buf: None,
+            exit_kind: ExitKind::Ok,
+            corpus_size: 1,
+            client_config: EventConfig::AlwaysUnique,
+            time: Duration::from_secs(42),
+            forward_id: None,
+            parent_global_id: None,
+            #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
+            node_id: None,
+        }
+    }
+
+    #[test]
+    fn test_nautilus_context_hook() {
+        let context = NautilusContext::with_rules(10, &[("E", b"({E}+{E})"), ("E", b"1")]).unwrap();
+        let other = NautilusContext::with_rules(10, &[("E", b"-{E}"), ("E", b"2")]).unwrap();
+        let mut hook = NautilusContextHook::new(&context);
+        let mut state = NopState::<NautilusInput>::new();
+
+        // `({E}+{E})` with two `1`s, below the `START` rule
+        let rules = [2, 0, 1, 1].map(|id| RuleIdOrCustom::Rule(id.into()));
+        let tree = Tree::from_rule_vec(rules.to_vec(), &context.ctx);
+        let cases = [
+            (NautilusInput::with_context(tree.clone(), &context), true),
+            (NautilusInput::new(tree.clone()), true),
+            // Derived from another grammar, but a derivation of this one as well
+            (NautilusInput::with_context(tree.clone(), &other), true),
+        ];
+        for (input, accepted) in cases {
+            let event = new_testcase(input);
+            assert_eq!(
+                hook.pre_exec(&mut state, ClientId(1), &event).unwrap(),
+                accepted
+            );
+        }
+
+        // `-{E}` has a single child, `2` none
+        let rules = [2, 0, 1].map(|id| RuleIdOrCustom::Rule(id.into()));
+        let tree = Tree::from_rule_vec(rules.to_vec(), &other.ctx);
+        for input in [
+            NautilusInput::with_context(tree.clone(), &other),
+            NautilusInput::new(tree),
+        ] {
+            let event = new_testcase(input);
+            assert!(!hook.pre_exec(&mut state, ClientId(1), &event).unwrap());
+        }
+    }
+}
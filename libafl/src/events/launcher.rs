@@ -38,16 +38,19 @@ use libafl_bolts::llmp::LlmpBroker;
 use libafl_bolts::os::dup2;
 #[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
 use libafl_bolts::os::startable_self;
-#[cfg(all(unix, feature = "std", feature = "fork"))]
-use libafl_bolts::{
-    core_affinity::get_core_ids,
-    os::{fork, ForkResult},
-};
+#[cfg(feature = "std")]
+use libafl_bolts::rands::{derive_seed, node_id, random_seed};
 use libafl_bolts::{
+    codec::{Codec, PostcardCodec},
     core_affinity::{CoreId, Cores},
     shmem::ShMemProvider,
     tuples::{tuple_list, Handle},
 };
+#[cfg(all(unix, feature = "std", feature = "fork"))]
+use libafl_bolts::{
+    core_affinity::get_core_ids,
+    os::{fork, ForkResult},
+};
 #[cfg(feature = "std")]
 use typed_builder::TypedBuilder;
 
@@ -68,8 +71,11 @@ use crate::state::UsesState;
 #[cfg(feature = "std")]
 use crate::{
     events::{
-        llmp::{LlmpRestartingEventManager, LlmpShouldSaveState, ManagerKind, RestartingMgr},
-        EventConfig,
+        llmp::{
+            CrashLoopDetector, LlmpRestartingEventManager, LlmpShouldSaveState, ManagerKind,
+            RestartingMgr,
+        },
+        BrokerHooks, EventConfig,
     },
     monitors::Monitor,
     state::{HasExecutions, State},
@@ -83,6 +89,76 @@ const _AFL_LAUNCHER_CLIENT: &str = "AFL_LAUNCHER_CLIENT";
 #[cfg(all(feature = "fork", unix))]
 const LIBAFL_DEBUG_OUTPUT: &str = "LIBAFL_DEBUG_OUTPUT";
 
+/// The env variable holding the master seed of the campaign, set by the [`Launcher`] for its clients.
+///
+/// Can also be set by the user, to reproduce a previous campaign.
+pub const LIBAFL_MASTER_SEED: &str = "LIBAFL_MASTER_SEED";
+
+/// The env variable holding the id of this fuzzing node, mixed into the seeds of its clients, see [`client_seed`].
+///
+/// Set by the [`Launcher`] from the hostname and the broker port, unless the user set it already,
+/// so clients on the same cores of different nodes do not share their seeds.
+pub const LIBAFL_NODE_ID: &str = "LIBAFL_NODE_ID";
+
+/// The id of this node from [`LIBAFL_NODE_ID`], if it is set
+#[cfg(feature = "std")]
+fn env_node_id() -> Option<u64> {
+    std::env::var(LIBAFL_NODE_ID).ok()?.parse().ok()
+}
+
+/// Set the master seed and the node id for the clients to come, see [`client_seed`].
+///
+/// An explicit `master_seed` wins over [`LIBAFL_MASTER_SEED`], a random seed is picked if neither is set.
+#[cfg(feature = "std")]
+fn distribute_master_seed(master_seed: Option<u64>, broker_port: u16) {
+    if env_node_id().is_none() {
+        let node_id = node_id(broker_port);
+        log::info!("Id of this node: {node_id} (set {LIBAFL_NODE_ID}={node_id} to reproduce it)");
+        std::env::set_var(LIBAFL_NODE_ID, node_id.to_string());
+    }
+
+    let master_seed = match master_seed {
+        Some(master_seed) => master_seed,
+        None => match std::env::var(LIBAFL_MASTER_SEED).map(|seed| seed.parse::<u64>()) {
+            Ok(Ok(_)) => return,
+            _ => random_seed(),
+        },
+    };
+    log::info!("Master seed of the campaign: {master_seed} (set {LIBAFL_MASTER_SEED}={master_seed} to reproduce it)");
+    std::env::set_var(LIBAFL_MASTER_SEED, master_seed.to_string());
+}
+
+/// The seed for the RNG of the client on `core_id`, derived from the master seed and the node id distributed
+/// by the [`Launcher`].
+///
+/// Outside of a [`Launcher`], without [`LIBAFL_MASTER_SEED`], a random seed is returned.
+/// Without [`LIBAFL_NODE_ID`], the node id is derived from the hostname alone.
+/// The seed is logged either way, so it can be used to reproduce a single client, e.g.
+/// `StdRand::with_seed(client_seed(core_id))`.
+#[cfg(feature = "std")]
+#[must_use]
+pub fn client_seed(core_id: CoreId) -> u64 {
+    match std::env::var(LIBAFL_MASTER_SEED).map(|seed| seed.parse::<u64>()) {
+        Ok(Ok(master_seed)) => {
+            let node_id = env_node_id().unwrap_or_else(|| node_id(0));
+            let seed = derive_seed(master_seed, node_id, core_id.0 as u64);
+            log::info!(
+                "Client on core {}: seed {seed}, derived from the master seed {master_seed} on node {node_id}",
+                core_id.0
+            );
+            seed
+        }
+        _ => {
+            let seed = random_seed();
+            log::info!(
+                "Client on core {}: random seed {seed}, no master seed set",
+                core_id.0
+            );
+            seed
+        }
+    }
+}
+
 /// Provides a [`Launcher`], which can be used to launch a fuzzing run on a specified list of cores
 ///
 /// Will hide child output, unless the settings indicate otherwise, or the `LIBAFL_DEBUG_OUTPUT` env variable is set.
@@ -144,6 +220,28 @@ pub struct Launcher<'a, CF, MT, SP> {
     /// Tell the manager to serialize or not the state on restart
     #[builder(default = LlmpShouldSaveState::OnRestart)]
     serialize_state: LlmpShouldSaveState,
+    /// Serialize the state as a delta to a base snapshot, see [`LlmpRestartingEventManager::with_delta_snapshots`]
+    #[builder(default = false)]
+    delta_snapshots: bool,
+    /// If set, the broker considers clients lost that did not send any message for this long,
+    /// and drops them from the monitor, see [`libafl_bolts::llmp::LlmpBrokerInner::set_client_timeout`].
+    /// With an OOM-safe `serialize_state`, clients that did not process events for this long are respawned.
+    #[builder(default = None)]
+    client_timeout: Option<Duration>,
+    /// The master seed of the campaign, from which each client derives its seed, see [`client_seed`].
+    /// If not set, [`LIBAFL_MASTER_SEED`] or a random seed is used, and logged.
+    #[builder(default = None)]
+    master_seed: Option<u64>,
+    /// If set, the clients pass testcases serializing to at least this many bytes in shared memory,
+    /// see [`crate::events::LlmpEventManagerBuilder::shared_events`]
+    #[builder(default = None)]
+    shared_events: Option<usize>,
+    /// The [`crate::events::BrokerHook`]s of the spawned broker
+    #[builder(default)]
+    broker_hooks: BrokerHooks,
+    /// If set, stop respawning a client that keeps exiting right after it started, see [`CrashLoopDetector`]
+    #[builder(default = None)]
+    crash_loop_detector: Option<CrashLoopDetector>,
 }
 
 impl<CF, MT, SP> Debug for Launcher<'_, CF, MT, SP> {
@@ -154,7 +252,12 @@ impl<CF, MT, SP> Debug for Launcher<'_, CF, MT, SP> {
             .field("broker_port", &self.broker_port)
             .field("core", &self.cores)
             .field("spawn_broker", &self.spawn_broker)
-            .field("remote_broker_addr", &self.remote_broker_addr);
+            .field("remote_broker_addr", &self.remote_broker_addr)
+            .field("client_timeout", &self.client_timeout)
+            .field("master_seed", &self.master_seed)
+            .field("shared_events", &self.shared_events)
+            .field("broker_hooks", &self.broker_hooks)
+            .field("crash_loop_detector", &self.crash_loop_detector);
         #[cfg(all(unix, feature = "std"))]
         {
             dbg_struct
@@ -176,6 +279,7 @@ where
     pub fn launch<S>(&mut self) -> Result<(), Error>
     where
         S: State + HasExecutions,
+        S::Input: 'static,
         CF: FnOnce(Option<S>, LlmpRestartingEventManager<(), S, SP>, CoreId) -> Result<(), Error>,
     {
         Self::launch_with_hooks(self, tuple_list!())
@@ -187,6 +291,7 @@ where
     pub fn launch<S>(&mut self) -> Result<(), Error>
     where
         S: State + HasExecutions,
+        S::Input: 'static,
         CF: FnOnce(Option<S>, LlmpRestartingEventManager<(), S, SP>, CoreId) -> Result<(), Error>,
     {
         Self::launch_with_hooks(self, tuple_list!())
@@ -200,14 +305,32 @@ where
     SP: ShMemProvider,
 {
     /// Launch the broker and the clients and fuzz with a user-supplied hook
+    pub fn launch_with_hooks<EMH, S>(&mut self, hooks: EMH) -> Result<(), Error>
+    where
+        S: State + HasExecutions,
+        S::Input: 'static,
+        EMH: EventManagerHooksTuple<S> + Clone + Copy,
+        CF: FnOnce(Option<S>, LlmpRestartingEventManager<EMH, S, SP>, CoreId) -> Result<(), Error>,
+    {
+        self.launch_with_codec::<PostcardCodec, EMH, S>(hooks)
+    }
+
+    /// Launch the broker and the clients and fuzz with a user-supplied hook, serializing the events and the
+    /// states with the [`Codec`] `C`, see [`RestartingMgr::launch_with_codec`]
     #[cfg(all(unix, feature = "std", feature = "fork"))]
     #[allow(clippy::similar_names)]
     #[allow(clippy::too_many_lines)]
-    pub fn launch_with_hooks<EMH, S>(&mut self, hooks: EMH) -> Result<(), Error>
+    pub fn launch_with_codec<C, EMH, S>(&mut self, hooks: EMH) -> Result<(), Error>
     where
+        C: Codec,
         S: State + HasExecutions,
+        S::Input: 'static,
         EMH: EventManagerHooksTuple<S> + Clone + Copy,
-        CF: FnOnce(Option<S>, LlmpRestartingEventManager<EMH, S, SP>, CoreId) -> Result<(), Error>,
+        CF: FnOnce(
+            Option<S>,
+            LlmpRestartingEventManager<EMH, S, SP, C>,
+            CoreId,
+        ) -> Result<(), Error>,
     {
         if self.cores.ids.is_empty() {
             return Err(Error::illegal_argument(
@@ -226,6 +349,7 @@ where
         let mut handles = vec![];
 
         log::info!("spawning on cores: {:?}", self.cores);
+        distribute_master_seed(self.master_seed, self.broker_port);
 
         self.opened_stdout_file = self
             .stdout_file
@@ -282,9 +406,13 @@ where
                             })
                             .configuration(self.configuration)
                             .serialize_state(self.serialize_state)
+                            .delta_snapshots(self.delta_snapshots)
+                            .client_timeout(self.client_timeout)
+                            .shared_events(self.shared_events)
+                            .crash_loop_detector(self.crash_loop_detector)
                             .hooks(hooks);
                         let builder = builder.time_ref(self.time_ref.clone());
-                        let (state, mgr) = builder.build().launch()?;
+                        let (state, mgr) = builder.build().launch_with_codec::<C>()?;
 
                         return (self.run_client.take().unwrap())(state, mgr, *bind_to);
                     }
@@ -304,13 +432,16 @@ where
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
                 .exit_cleanly_after(Some(NonZeroUsize::try_from(self.cores.ids.len()).unwrap()))
+                .client_timeout(self.client_timeout)
                 .configuration(self.configuration)
                 .serialize_state(self.serialize_state)
-                .hooks(hooks);
+                .delta_snapshots(self.delta_snapshots)
+                .hooks(hooks)
+                .broker_hooks(core::mem::take(&mut self.broker_hooks));
 
             let builder = builder.time_ref(self.time_ref.clone());
 
-            builder.build().launch()?;
+            builder.build().launch_with_codec::<C>()?;
 
             // Broker exited. kill all clients.
             for handle in &handles {
@@ -336,14 +467,21 @@ where
         Ok(())
     }
 
-    /// Launch the broker and the clients and fuzz
+    /// Launch the broker and the clients and fuzz with a user-supplied hook, serializing the events and the
+    /// states with the [`Codec`] `C`, see [`RestartingMgr::launch_with_codec`]
     #[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
     #[allow(unused_mut, clippy::match_wild_err_arm, clippy::too_many_lines)]
-    pub fn launch_with_hooks<EMH, S>(&mut self, hooks: EMH) -> Result<(), Error>
+    pub fn launch_with_codec<C, EMH, S>(&mut self, hooks: EMH) -> Result<(), Error>
     where
+        C: Codec,
         S: State + HasExecutions,
+        S::Input: 'static,
         EMH: EventManagerHooksTuple<S> + Clone + Copy,
-        CF: FnOnce(Option<S>, LlmpRestartingEventManager<EMH, S, SP>, CoreId) -> Result<(), Error>,
+        CF: FnOnce(
+            Option<S>,
+            LlmpRestartingEventManager<EMH, S, SP, C>,
+            CoreId,
+        ) -> Result<(), Error>,
     {
         use libafl_bolts::core_affinity;
 
@@ -362,11 +500,15 @@ where
                     })
                     .configuration(self.configuration)
                     .serialize_state(self.serialize_state)
+                    .delta_snapshots(self.delta_snapshots)
+                    .client_timeout(self.client_timeout)
+                    .shared_events(self.shared_events)
+                    .crash_loop_detector(self.crash_loop_detector)
                     .hooks(hooks);
 
                 let builder = builder.time_ref(self.time_ref.clone());
 
-                let (state, mgr) = builder.build().launch()?;
+                let (state, mgr) = builder.build().launch_with_codec::<C>()?;
 
                 return (self.run_client.take().unwrap())(state, mgr, CoreId(core_id));
             }
@@ -379,6 +521,8 @@ where
                 let mut handles = vec![];
 
                 log::info!("spawning on cores: {:?}", self.cores);
+                // Inherited by the spawned clients
+                distribute_master_seed(self.master_seed, self.broker_port);
 
                 let debug_output = std::env::var("LIBAFL_DEBUG_OUTPUT").is_ok();
                 #[cfg(all(feature = "std", unix))]
@@ -454,13 +598,16 @@ where
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
                 .exit_cleanly_after(Some(NonZeroUsize::try_from(self.cores.ids.len()).unwrap()))
+                .client_timeout(self.client_timeout)
                 .configuration(self.configuration)
                 .serialize_state(self.serialize_state)
-                .hooks(hooks);
+                .delta_snapshots(self.delta_snapshots)
+                .hooks(hooks)
+                .broker_hooks(core::mem::take(&mut self.broker_hooks));
 
             let builder = builder.time_ref(self.time_ref.clone());
 
-            builder.build().launch()?;
+            builder.build().launch_with_codec::<C>()?;
 
             //broker exited. kill all clients.
             for handle in &mut handles {
@@ -547,6 +694,13 @@ pub struct CentralizedLauncher<'a, CF, MF, MT, SP> {
     /// Tell the manager to serialize or not the state on restart
     #[builder(default = LlmpShouldSaveState::OnRestart)]
     serialize_state: LlmpShouldSaveState,
+    /// Serialize the state as a delta to a base snapshot, see [`LlmpRestartingEventManager::with_delta_snapshots`]
+    #[builder(default = false)]
+    delta_snapshots: bool,
+    /// The master seed of the campaign, from which each client derives its seed, see [`client_seed`].
+    /// If not set, [`LIBAFL_MASTER_SEED`] or a random seed is used, and logged.
+    #[builder(default = None)]
+    master_seed: Option<u64>,
 }
 
 #[cfg(all(unix, feature = "std", feature = "fork"))]
@@ -560,6 +714,7 @@ impl<CF, MF, MT, SP> Debug for CentralizedLauncher<'_, CF, MF, MT, SP> {
             .field("remote_broker_addr", &self.remote_broker_addr)
             .field("stdout_file", &self.stdout_file)
             .field("stderr_file", &self.stderr_file)
+            .field("master_seed", &self.master_seed)
             .finish_non_exhaustive()
     }
 }
@@ -600,6 +755,7 @@ where
                 })
                 .configuration(centralized_launcher.configuration)
                 .serialize_state(centralized_launcher.serialize_state)
+                .delta_snapshots(centralized_launcher.delta_snapshots)
                 .hooks(tuple_list!());
 
             let builder = builder.time_ref(centralized_launcher.time_obs.clone());
@@ -660,6 +816,7 @@ where
         let mut handles = vec![];
 
         log::debug!("spawning on cores: {:?}", self.cores);
+        distribute_master_seed(self.master_seed, self.broker_port);
 
         self.opened_stdout_file = self
             .stdout_file
@@ -3,23 +3,24 @@
 
 #[cfg(feature = "std")]
 use alloc::string::ToString;
-use alloc::{boxed::Box, vec::Vec};
-use core::{marker::PhantomData, time::Duration};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::{marker::PhantomData, num::NonZeroUsize, time::Duration};
 #[cfg(feature = "std")]
 use std::net::TcpStream;
 
-#[cfg(feature = "llmp_compression")]
-use libafl_bolts::{
-    compress::GzipCompressor,
-    llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
-};
 use libafl_bolts::{
+    codec::{Codec, PostcardCodec},
     current_time,
     llmp::{LlmpClient, LlmpClientDescription, LLMP_FLAG_FROM_MM},
     shmem::{NopShMemProvider, ShMemProvider},
     tuples::Handle,
     ClientId,
 };
+#[cfg(feature = "llmp_compression")]
+use libafl_bolts::{
+    compress::GzipCompressor,
+    llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
+};
 #[cfg(feature = "std")]
 use libafl_bolts::{
     llmp::{recv_tcp_msg, send_tcp_msg, TcpRequest, TcpResponse},
@@ -30,8 +31,15 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "llmp_compression")]
 use crate::events::llmp::COMPRESS_THRESHOLD;
 use crate::{
+    corpus::{
+        global_id::{clear_remote_testcase, expect_remote_testcase, is_known_global_id},
+        GlobalIdMetadata,
+    },
     events::{
-        llmp::{LLMP_TAG_EVENT_TO_BOTH, _LLMP_TAG_EVENT_TO_BROKER},
+        llmp::{
+            SharedEventPool, SharedEventRef, LLMP_TAG_EVENT_SHMEM_REF, LLMP_TAG_EVENT_TO_BOTH,
+            _LLMP_TAG_EVENT_TO_BROKER,
+        },
         AdaptiveSerializer, CustomBufEventResult, CustomBufHandlerFn, Event, EventConfig,
         EventFirer, EventManager, EventManagerHooksTuple, EventManagerId, EventProcessor,
         EventRestarter, HasCustomBufHandlers, HasEventManagerId, ProgressReporter,
@@ -40,13 +48,19 @@ use crate::{
     fuzzer::{Evaluator, EvaluatorObservers, ExecutionProcessor},
     inputs::{NopInput, UsesInput},
     observers::{ObserversTuple, TimeObserver},
-    state::{HasExecutions, HasImported, HasLastReportTime, NopState, State, UsesState},
+    stages::import_tokens,
+    state::{
+        HasExecutions, HasImported, HasLastReportTime, HasRuntimeConfig, NopState, State, UsesState,
+    },
     Error, HasMetadata,
 };
 
 /// An [`EventManager`] that forwards all events to other attached fuzzers on shared maps or via tcp,
 /// using low-level message passing, `llmp`.
-pub struct LlmpEventManager<EMH, S, SP>
+///
+/// The events are serialized with the [`Codec`] `C`, [`PostcardCodec`] by default, see
+/// [`LlmpEventManagerBuilder::codec`]. All clients and the broker of a campaign have to use the same codec.
+pub struct LlmpEventManager<EMH, S, SP, C = PostcardCodec>
 where
     S: State,
     SP: ShMemProvider,
@@ -55,6 +69,10 @@ where
     pub(crate) throttle: Option<Duration>,
     /// Treat the incoming testcase as interesting always without evaluating them
     always_interesting: bool,
+    /// Evaluate at most this many received testcases per call to `process`
+    import_limit: Option<NonZeroUsize>,
+    /// Received testcases waiting to be evaluated, if `import_limit` is set
+    import_queue: VecDeque<(ClientId, Event<S::Input>)>,
     /// We sent last message at `last_sent`
     last_sent: Duration,
     hooks: EMH,
@@ -64,6 +82,8 @@ where
     custom_buf_handlers: Vec<Box<CustomBufHandlerFn<S>>>,
     #[cfg(feature = "llmp_compression")]
     compressor: GzipCompressor,
+    /// Large testcases are passed in shared memory, if set
+    shared_events: Option<SharedEventPool<SP::ShMem>>,
     /// The configuration defines this specific fuzzer.
     /// A node will not re-use the observer values sent over LLMP
     /// from nodes with other configurations.
@@ -73,7 +93,7 @@ where
     serializations_cnt: usize,
     should_serialize_cnt: usize,
     pub(crate) time_ref: Option<Handle<TimeObserver>>,
-    phantom: PhantomData<S>,
+    phantom: PhantomData<(S, C)>,
 }
 
 impl LlmpEventManager<(), NopState<NopInput>, NopShMemProvider> {
@@ -86,10 +106,13 @@ impl LlmpEventManager<(), NopState<NopInput>, NopShMemProvider> {
 
 /// Builder for `LlmpEventManager`
 #[derive(Debug, Copy, Clone)]
-pub struct LlmpEventManagerBuilder<EMH> {
+pub struct LlmpEventManagerBuilder<EMH, C = PostcardCodec> {
     throttle: Option<Duration>,
     hooks: EMH,
     always_interesting: bool,
+    import_limit: Option<NonZeroUsize>,
+    shared_events: Option<usize>,
+    codec: PhantomData<C>,
 }
 
 impl Default for LlmpEventManagerBuilder<()> {
@@ -106,6 +129,9 @@ impl LlmpEventManagerBuilder<()> {
             throttle: None,
             hooks: (),
             always_interesting: false,
+            import_limit: None,
+            shared_events: None,
+            codec: PhantomData,
         }
     }
 
@@ -115,6 +141,9 @@ impl LlmpEventManagerBuilder<()> {
             throttle: self.throttle,
             hooks,
             always_interesting: self.always_interesting,
+            import_limit: self.import_limit,
+            shared_events: self.shared_events,
+            codec: PhantomData,
         }
     }
 
@@ -125,11 +154,31 @@ impl LlmpEventManagerBuilder<()> {
             throttle: self.throttle,
             hooks: self.hooks,
             always_interesting,
+            import_limit: self.import_limit,
+            shared_events: self.shared_events,
+            codec: PhantomData,
         }
     }
 }
 
-impl<EMH> LlmpEventManagerBuilder<EMH> {
+impl<EMH, C> LlmpEventManagerBuilder<EMH, C> {
+    /// Serialize the events with another [`Codec`], e.g. a self-describing one, which tolerates clients of
+    /// different versions. The broker has to use the same codec.
+    #[must_use]
+    pub fn codec<C2>(self) -> LlmpEventManagerBuilder<EMH, C2>
+    where
+        C2: Codec,
+    {
+        LlmpEventManagerBuilder {
+            throttle: self.throttle,
+            hooks: self.hooks,
+            always_interesting: self.always_interesting,
+            import_limit: self.import_limit,
+            shared_events: self.shared_events,
+            codec: PhantomData,
+        }
+    }
+
     /// Change the sampling rate
     #[must_use]
     pub fn throttle(mut self, throttle: Duration) -> Self {
@@ -137,14 +186,40 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         self
     }
 
+    /// Evaluate at most `limit` testcases received from other clients per call to `process`.
+    ///
+    /// The remaining testcases are queued and evaluated in later calls, so that a burst of imports,
+    /// e.g. when many clients find new testcases at the same time, does not stall the fuzzing loop.
+    /// Other events are still handled immediately.
+    ///
+    /// The queue lives in memory only: testcases still queued when the client restarts are dropped.
+    /// They stay in the corpora of the clients that found them.
+    #[must_use]
+    pub fn import_limit(mut self, limit: NonZeroUsize) -> Self {
+        self.import_limit = Some(limit);
+        self
+    }
+
+    /// Pass the [`Event::NewTestcase`]s which serialize to at least `threshold` bytes in shared memory, instead
+    /// of copying them through the LLMP pages, see [`crate::events::llmp::shared_event`].
+    ///
+    /// All clients on this machine have to be able to map the shared memory of each other, e.g. with the same
+    /// [`ShMemProvider`]. Testcases forwarded to other machines are sent in full.
+    #[must_use]
+    pub fn shared_events(mut self, threshold: usize) -> Self {
+        self.shared_events = Some(threshold);
+        self
+    }
+
     /// Create a manager from a raw LLMP client
     pub fn build_from_client<S, SP>(
         self,
         llmp: LlmpClient<SP>,
         configuration: EventConfig,
         time_ref: Option<Handle<TimeObserver>>,
-    ) -> Result<LlmpEventManager<EMH, S, SP>, Error>
+    ) -> Result<LlmpEventManager<EMH, S, SP, C>, Error>
     where
+        C: Codec,
         SP: ShMemProvider,
         S: State,
     {
@@ -153,9 +228,12 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
             last_sent: Duration::from_secs(0),
             hooks: self.hooks,
             always_interesting: self.always_interesting,
+            import_limit: self.import_limit,
+            import_queue: VecDeque::new(),
             llmp,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            shared_events: self.shared_events.map(SharedEventPool::new),
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -176,8 +254,9 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         port: u16,
         configuration: EventConfig,
         time_ref: Option<Handle<TimeObserver>>,
-    ) -> Result<LlmpEventManager<EMH, S, SP>, Error>
+    ) -> Result<LlmpEventManager<EMH, S, SP, C>, Error>
     where
+        C: Codec,
         SP: ShMemProvider,
         S: State,
     {
@@ -187,9 +266,12 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
             last_sent: Duration::from_secs(0),
             hooks: self.hooks,
             always_interesting: self.always_interesting,
+            import_limit: self.import_limit,
+            import_queue: VecDeque::new(),
             llmp,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            shared_events: self.shared_events.map(SharedEventPool::new),
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -210,8 +292,9 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         env_name: &str,
         configuration: EventConfig,
         time_ref: Option<Handle<TimeObserver>>,
-    ) -> Result<LlmpEventManager<EMH, S, SP>, Error>
+    ) -> Result<LlmpEventManager<EMH, S, SP, C>, Error>
     where
+        C: Codec,
         SP: ShMemProvider,
         S: State,
     {
@@ -221,9 +304,12 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
             last_sent: Duration::from_secs(0),
             hooks: self.hooks,
             always_interesting: self.always_interesting,
+            import_limit: self.import_limit,
+            import_queue: VecDeque::new(),
             llmp,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            shared_events: self.shared_events.map(SharedEventPool::new),
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -242,8 +328,9 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         description: &LlmpClientDescription,
         configuration: EventConfig,
         time_ref: Option<Handle<TimeObserver>>,
-    ) -> Result<LlmpEventManager<EMH, S, SP>, Error>
+    ) -> Result<LlmpEventManager<EMH, S, SP, C>, Error>
     where
+        C: Codec,
         SP: ShMemProvider,
         S: State,
     {
@@ -253,9 +340,12 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
             last_sent: Duration::from_secs(0),
             hooks: self.hooks,
             always_interesting: self.always_interesting,
+            import_limit: self.import_limit,
+            import_queue: VecDeque::new(),
             llmp,
             #[cfg(feature = "llmp_compression")]
             compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            shared_events: self.shared_events.map(SharedEventPool::new),
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -268,8 +358,9 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
     }
 }
 
-impl<EMH, S, SP> AdaptiveSerializer for LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> AdaptiveSerializer for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     SP: ShMemProvider,
     S: State,
 {
@@ -304,8 +395,9 @@ where
     }
 }
 
-impl<EMH, S, SP> core::fmt::Debug for LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> core::fmt::Debug for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     SP: ShMemProvider,
     S: State,
 {
@@ -322,8 +414,9 @@ where
     }
 }
 
-impl<EMH, S, SP> Drop for LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> Drop for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     SP: ShMemProvider,
     S: State,
 {
@@ -333,8 +426,9 @@ where
     }
 }
 
-impl<EMH, S, SP> LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
 {
@@ -371,6 +465,12 @@ where
         Ok(())
     }
 
+    /// The number of received testcases waiting to be evaluated, see [`LlmpEventManagerBuilder::import_limit`]
+    #[must_use]
+    pub fn pending_imports(&self) -> usize {
+        self.import_queue.len()
+    }
+
     /// Describe the client event manager's LLMP parts in a restorable fashion
     pub fn describe(&self) -> Result<LlmpClientDescription, Error> {
         self.llmp.describe()
@@ -382,10 +482,30 @@ where
     pub fn to_env(&self, env_name: &str) {
         self.llmp.to_env(env_name).unwrap();
     }
+
+    /// Sends a testcase large enough to be shared as a [`SharedEventRef`], see
+    /// [`LlmpEventManagerBuilder::shared_events`]. Returns if the event was sent.
+    fn try_send_shared(
+        &mut self,
+        event: &Event<S::Input>,
+        serialized: &[u8],
+    ) -> Result<bool, Error> {
+        let Some(pool) = &mut self.shared_events else {
+            return Ok(false);
+        };
+        if !event.is_new_testcase() || !pool.should_share(serialized.len()) {
+            return Ok(false);
+        }
+        let shared = pool.publish(self.llmp.shmem_provider_mut(), serialized)?;
+        self.llmp
+            .send_buf(LLMP_TAG_EVENT_SHMEM_REF, &postcard::to_allocvec(&shared)?)?;
+        Ok(true)
+    }
 }
 
-impl<EMH, S, SP> LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     EMH: EventManagerHooksTuple<S>,
     S: State + HasExecutions + HasMetadata + HasImported,
     SP: ShMemProvider,
@@ -413,18 +533,33 @@ where
         }
         let evt_name = event.name_detailed();
         match event {
+            Event::NewTestcase { global_id, .. } if is_known_global_id(state, global_id) => {
+                log::debug!(
+                    "Testcase {evt_name} ({global_id}) is in the corpus already, discarding"
+                );
+            }
             Event::NewTestcase {
                 input,
                 client_config,
                 exit_kind,
                 observers_buf,
-                #[cfg(feature = "std")]
                 forward_id,
+                global_id,
+                parent_global_id,
                 ..
             } => {
                 #[cfg(feature = "std")]
                 log::debug!("[{}] Received new Testcase {evt_name} from {client_id:?} ({client_config:?}, forward {forward_id:?})", std::process::id());
 
+                expect_remote_testcase(
+                    state,
+                    GlobalIdMetadata {
+                        id: global_id,
+                        origin: Some(forward_id.unwrap_or(client_id)),
+                        parent: parent_global_id,
+                    },
+                );
+
                 if self.always_interesting {
                     let item = fuzzer.add_input(state, executor, self, input)?;
                     log::debug!("Added received Testcase as item #{item}");
@@ -460,6 +595,7 @@ where
                         log::debug!("Testcase {evt_name} was discarded");
                     }
                 }
+                clear_remote_testcase(state);
             }
             Event::CustomBuf { tag, buf } => {
                 for handler in &mut self.custom_buf_handlers {
@@ -471,6 +607,22 @@ where
             Event::Stop => {
                 state.request_stop();
             }
+            Event::Command { id, command } => {
+                log::info!("Received command {command} (id {id}) from {client_id:?}");
+                command.apply(state);
+                self.fire(state, Event::CommandAck { id, command })?;
+            }
+            Event::NewTokens { tokens } => {
+                let added = import_tokens(state, &tokens);
+                log::debug!(
+                    "Received {} tokens from {client_id:?}, {added} new",
+                    tokens.len()
+                );
+            }
+            Event::ConfigUpdate { config } => {
+                log::info!("Received config update {config:?} from {client_id:?}");
+                state.apply_runtime_config(&config);
+            }
             _ => {
                 return Err(Error::unknown(format!(
                     "Received illegal message that message should not have arrived: {:?}.",
@@ -484,7 +636,7 @@ where
     }
 }
 
-impl<EMH, S: State, SP: ShMemProvider> LlmpEventManager<EMH, S, SP> {
+impl<EMH, S: State, SP: ShMemProvider, C: Codec> LlmpEventManager<EMH, S, SP, C> {
     /// Send information that this client is exiting.
     /// The other side may free up all allocated memory.
     /// We are no longer allowed to send anything afterwards.
@@ -493,16 +645,18 @@ impl<EMH, S: State, SP: ShMemProvider> LlmpEventManager<EMH, S, SP> {
     }
 }
 
-impl<EMH, S, SP> UsesState for LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> UsesState for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
 {
     type State = S;
 }
 
-impl<EMH, S, SP> EventFirer for LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> EventFirer for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
 {
@@ -520,7 +674,11 @@ where
         _state: &mut Self::State,
         event: Event<<Self::State as UsesInput>::Input>,
     ) -> Result<(), Error> {
-        let serialized = postcard::to_allocvec(&event)?;
+        let serialized = C::encode(&event)?;
+        if self.try_send_shared(&event, &serialized)? {
+            self.last_sent = current_time();
+            return Ok(());
+        }
         let flags = LLMP_FLAG_INITIALIZED;
 
         match self.compressor.maybe_compress(&serialized) {
@@ -546,8 +704,10 @@ where
         _state: &mut Self::State,
         event: Event<<Self::State as UsesInput>::Input>,
     ) -> Result<(), Error> {
-        let serialized = postcard::to_allocvec(&event)?;
-        self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &serialized)?;
+        let serialized = C::encode(&event)?;
+        if !self.try_send_shared(&event, &serialized)? {
+            self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &serialized)?;
+        }
         Ok(())
     }
 
@@ -569,8 +729,9 @@ where
     }
 }
 
-impl<EMH, S, SP> EventRestarter for LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> EventRestarter for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
 {
@@ -582,8 +743,9 @@ where
     }
 }
 
-impl<E, EMH, S, SP, Z> EventProcessor<E, Z> for LlmpEventManager<EMH, S, SP>
+impl<E, EMH, S, SP, Z, C> EventProcessor<E, Z> for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     EMH: EventManagerHooksTuple<S>,
     S: State + HasExecutions + HasMetadata + HasImported,
     SP: ShMemProvider,
@@ -612,18 +774,29 @@ where
             if client_id == self_id {
                 continue;
             }
-            #[cfg(not(feature = "llmp_compression"))]
-            let event_bytes = msg;
-            #[cfg(feature = "llmp_compression")]
-            let compressed;
-            #[cfg(feature = "llmp_compression")]
-            let event_bytes = if flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = self.compressor.decompress(msg)?;
-                &compressed
+            let event: Event<S::Input> = if tag == LLMP_TAG_EVENT_SHMEM_REF {
+                let shared = SharedEventRef::from_msg(msg)?;
+                match shared.read(self.llmp.shmem_provider_mut(), C::decode) {
+                    Ok(event) => event,
+                    Err(err) => {
+                        log::warn!("Dropping shared event from {client_id:?}: {err}");
+                        continue;
+                    }
+                }
             } else {
-                msg
+                #[cfg(not(feature = "llmp_compression"))]
+                let event_bytes = msg;
+                #[cfg(feature = "llmp_compression")]
+                let compressed;
+                #[cfg(feature = "llmp_compression")]
+                let event_bytes = if flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
+                    compressed = self.compressor.decompress(msg)?;
+                    &compressed
+                } else {
+                    msg
+                };
+                C::decode(event_bytes)?
             };
-            let event: Event<S::Input> = postcard::from_bytes(event_bytes)?;
             log::debug!("Received event in normal llmp {}", event.name_detailed());
 
             // If the message comes from another machine, do not
@@ -632,9 +805,27 @@ where
                 continue;
             }
 
+            if self.import_limit.is_some() && event.is_new_testcase() {
+                self.import_queue.push_back((client_id, event));
+                continue;
+            }
+
             self.handle_in_client(fuzzer, executor, state, client_id, event)?;
             count += 1;
         }
+
+        if let Some(limit) = self.import_limit {
+            for _ in 0..limit.get() {
+                let Some((client_id, event)) = self.import_queue.pop_front() else {
+                    break;
+                };
+                self.handle_in_client(fuzzer, executor, state, client_id, event)?;
+                count += 1;
+            }
+            if !self.import_queue.is_empty() {
+                log::debug!("{} received testcases pending", self.import_queue.len());
+            }
+        }
         Ok(count)
     }
 
@@ -643,8 +834,9 @@ where
     }
 }
 
-impl<E, EMH, S, SP, Z> EventManager<E, Z> for LlmpEventManager<EMH, S, SP>
+impl<E, EMH, S, SP, Z, C> EventManager<E, Z> for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     E: HasObservers + Executor<Self, Z, State = S>,
     E::Observers: ObserversTuple<S::Input, S> + Serialize,
     for<'a> E::Observers: Deserialize<'a>,
@@ -657,8 +849,9 @@ where
 {
 }
 
-impl<EMH, S, SP> HasCustomBufHandlers for LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> HasCustomBufHandlers for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
 {
@@ -670,15 +863,17 @@ where
     }
 }
 
-impl<EMH, S, SP> ProgressReporter for LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> ProgressReporter for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State + HasExecutions + HasMetadata + HasLastReportTime,
     SP: ShMemProvider,
 {
 }
 
-impl<EMH, S, SP> HasEventManagerId for LlmpEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> HasEventManagerId for LlmpEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
 {
@@ -3,16 +3,17 @@
 use alloc::{boxed::Box, vec::Vec};
 use core::{marker::PhantomData, time::Duration};
 
-#[cfg(feature = "llmp_compression")]
-use libafl_bolts::{
-    compress::GzipCompressor,
-    llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
-};
 use libafl_bolts::{
+    codec::{Codec, PostcardCodec},
     llmp::{LlmpClient, LlmpClientDescription, Tag},
     shmem::{NopShMemProvider, ShMemProvider},
     ClientId,
 };
+#[cfg(feature = "llmp_compression")]
+use libafl_bolts::{
+    compress::GzipCompressor,
+    llmp::{LLMP_FLAG_COMPRESSED, LLMP_FLAG_INITIALIZED},
+};
 use serde::Deserialize;
 
 use crate::{
@@ -20,7 +21,8 @@ use crate::{
     executors::{Executor, HasObservers},
     fuzzer::{EvaluatorObservers, ExecutionProcessor},
     inputs::{Input, InputConverter, NopInput, NopInputConverter, UsesInput},
-    state::{HasExecutions, NopState, State, Stoppable, UsesState},
+    stages::import_tokens,
+    state::{HasExecutions, HasRuntimeConfig, NopState, State, Stoppable, UsesState},
     Error, HasMetadata,
 };
 
@@ -34,6 +36,10 @@ pub mod restarting;
 #[cfg(feature = "std")]
 pub use restarting::*;
 
+/// Large events passed in shared memory
+pub mod shared_event;
+pub use shared_event::{SharedEventPool, SharedEventRef};
+
 /// Forward this to the client
 pub(crate) const _LLMP_TAG_EVENT_TO_CLIENT: Tag = Tag(0x2C11E471);
 /// Only handle this in the broker
@@ -43,6 +49,8 @@ pub(crate) const _LLMP_TAG_EVENT_TO_BROKER: Tag = Tag(0x2B80438);
 pub(crate) const LLMP_TAG_EVENT_TO_BOTH: Tag = Tag(0x2B0741);
 pub(crate) const _LLMP_TAG_RESTART: Tag = Tag(0x8357A87);
 pub(crate) const _LLMP_TAG_NO_RESTART: Tag = Tag(0x57A7EE71);
+/// A [`SharedEventRef`] to an event in shared memory, handle in both
+pub(crate) const LLMP_TAG_EVENT_SHMEM_REF: Tag = Tag(0x5E3AEF);
 
 /// The minimum buffer size at which to compress LLMP IPC messages.
 #[cfg(feature = "llmp_compression")]
@@ -84,7 +92,10 @@ impl LlmpShouldSaveState {
 }
 
 /// A manager-like llmp client that converts between input types
-pub struct LlmpEventConverter<DI, IC, ICB, S, SP>
+///
+/// The events are serialized with the [`Codec`] `C`, which has to be the one of the other clients, see
+/// [`LlmpEventConverterBuilder::codec`].
+pub struct LlmpEventConverter<DI, IC, ICB, S, SP, C = PostcardCodec>
 where
     S: UsesInput,
     SP: ShMemProvider,
@@ -101,7 +112,7 @@ where
     compressor: GzipCompressor,
     converter: Option<IC>,
     converter_back: Option<ICB>,
-    phantom: PhantomData<S>,
+    phantom: PhantomData<(S, C)>,
 }
 
 impl
@@ -122,22 +133,41 @@ impl
 
 /// Build `LlmpEventConverter`
 #[derive(Debug, Clone, Default)]
-pub struct LlmpEventConverterBuilder {
+pub struct LlmpEventConverterBuilder<C = PostcardCodec> {
     throttle: Option<Duration>,
+    codec: PhantomData<C>,
 }
 
 impl LlmpEventConverterBuilder {
     #[must_use]
     /// Constructor
     pub fn new() -> Self {
-        Self { throttle: None }
+        Self {
+            throttle: None,
+            codec: PhantomData,
+        }
     }
+}
 
+impl<C> LlmpEventConverterBuilder<C> {
     #[must_use]
     /// Sets the `throttle`
     pub fn throttle(self, throttle: Duration) -> Self {
         Self {
             throttle: Some(throttle),
+            codec: PhantomData,
+        }
+    }
+
+    /// Serialize the events with another [`Codec`], the one of the clients and the broker
+    #[must_use]
+    pub fn codec<C2>(self) -> LlmpEventConverterBuilder<C2>
+    where
+        C2: Codec,
+    {
+        LlmpEventConverterBuilder {
+            throttle: self.throttle,
+            codec: PhantomData,
         }
     }
 
@@ -147,8 +177,9 @@ impl LlmpEventConverterBuilder {
         llmp: LlmpClient<SP>,
         converter: Option<IC>,
         converter_back: Option<ICB>,
-    ) -> Result<LlmpEventConverter<DI, IC, ICB, S, SP>, Error>
+    ) -> Result<LlmpEventConverter<DI, IC, ICB, S, SP, C>, Error>
     where
+        C: Codec,
         SP: ShMemProvider,
         S: UsesInput,
         IC: InputConverter<From = S::Input, To = DI>,
@@ -176,8 +207,9 @@ impl LlmpEventConverterBuilder {
         port: u16,
         converter: Option<IC>,
         converter_back: Option<ICB>,
-    ) -> Result<LlmpEventConverter<DI, IC, ICB, S, SP>, Error>
+    ) -> Result<LlmpEventConverter<DI, IC, ICB, S, SP, C>, Error>
     where
+        C: Codec,
         SP: ShMemProvider,
         S: UsesInput,
         IC: InputConverter<From = S::Input, To = DI>,
@@ -206,8 +238,9 @@ impl LlmpEventConverterBuilder {
         env_name: &str,
         converter: Option<IC>,
         converter_back: Option<ICB>,
-    ) -> Result<LlmpEventConverter<DI, IC, ICB, S, SP>, Error>
+    ) -> Result<LlmpEventConverter<DI, IC, ICB, S, SP, C>, Error>
     where
+        C: Codec,
         SP: ShMemProvider,
         S: UsesInput,
         IC: InputConverter<From = S::Input, To = DI>,
@@ -229,8 +262,9 @@ impl LlmpEventConverterBuilder {
     }
 }
 
-impl<DI, IC, ICB, S, SP> core::fmt::Debug for LlmpEventConverter<DI, IC, ICB, S, SP>
+impl<DI, IC, ICB, S, SP, C> core::fmt::Debug for LlmpEventConverter<DI, IC, ICB, S, SP, C>
 where
+    C: Codec,
     SP: ShMemProvider,
     S: UsesInput,
     IC: InputConverter<From = S::Input, To = DI>,
@@ -251,8 +285,9 @@ where
     }
 }
 
-impl<DI, IC, ICB, S, SP> LlmpEventConverter<DI, IC, ICB, S, SP>
+impl<DI, IC, ICB, S, SP, C> LlmpEventConverter<DI, IC, ICB, S, SP, C>
 where
+    C: Codec,
     S: UsesInput + HasExecutions + HasMetadata + Stoppable,
     SP: ShMemProvider,
     IC: InputConverter<From = S::Input, To = DI>,
@@ -329,7 +364,15 @@ where
                 }
                 Ok(())
             }
-            Event::Stop => Ok(()),
+            Event::Stop | Event::Command { .. } => Ok(()),
+            Event::NewTokens { tokens } => {
+                import_tokens(state, &tokens);
+                Ok(())
+            }
+            Event::ConfigUpdate { config } => {
+                state.apply_runtime_config(&config);
+                Ok(())
+            }
             _ => Err(Error::unknown(format!(
                 "Received illegal message that message should not have arrived: {:?}.",
                 event.name()
@@ -364,19 +407,31 @@ where
             if client_id == self_id {
                 continue;
             }
-            #[cfg(not(feature = "llmp_compression"))]
-            let event_bytes = msg;
-            #[cfg(feature = "llmp_compression")]
-            let compressed;
-            #[cfg(feature = "llmp_compression")]
-            let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
-                compressed = self.compressor.decompress(msg)?;
-                &compressed
+            let event: Event<DI> = if tag == LLMP_TAG_EVENT_SHMEM_REF {
+                let shared = SharedEventRef::from_msg(msg)?;
+                match shared.read(self.llmp.shmem_provider_mut(), |event_bytes| {
+                    C::decode(event_bytes)
+                }) {
+                    Ok(event) => event,
+                    Err(err) => {
+                        log::warn!("Dropping shared event from {client_id:?}: {err}");
+                        continue;
+                    }
+                }
             } else {
-                msg
+                #[cfg(not(feature = "llmp_compression"))]
+                let event_bytes = msg;
+                #[cfg(feature = "llmp_compression")]
+                let compressed;
+                #[cfg(feature = "llmp_compression")]
+                let event_bytes = if _flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
+                    compressed = self.compressor.decompress(msg)?;
+                    &compressed
+                } else {
+                    msg
+                };
+                C::decode(event_bytes)?
             };
-
-            let event: Event<DI> = postcard::from_bytes(event_bytes)?;
             log::debug!("Processor received message {}", event.name_detailed());
             self.handle_in_client(fuzzer, executor, state, manager, client_id, event)?;
             count += 1;
@@ -385,8 +440,9 @@ where
     }
 }
 
-impl<DI, IC, ICB, S, SP> UsesState for LlmpEventConverter<DI, IC, ICB, S, SP>
+impl<DI, IC, ICB, S, SP, C> UsesState for LlmpEventConverter<DI, IC, ICB, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
     IC: InputConverter<From = S::Input, To = DI>,
@@ -396,8 +452,9 @@ where
     type State = S;
 }
 
-impl<DI, IC, ICB, S, SP> EventFirer for LlmpEventConverter<DI, IC, ICB, S, SP>
+impl<DI, IC, ICB, S, SP, C> EventFirer for LlmpEventConverter<DI, IC, ICB, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
     IC: InputConverter<From = S::Input, To = DI>,
@@ -432,6 +489,8 @@ where
                 observers_buf,
                 time,
                 forward_id,
+                global_id,
+                parent_global_id,
                 #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
                 node_id,
             } => Event::NewTestcase {
@@ -442,15 +501,19 @@ where
                 observers_buf,
                 time,
                 forward_id,
+                global_id,
+                parent_global_id,
                 #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
                 node_id,
             },
             Event::CustomBuf { buf, tag } => Event::CustomBuf { buf, tag },
+            Event::CommandAck { id, command } => Event::CommandAck { id, command },
+            Event::NewTokens { tokens } => Event::NewTokens { tokens },
             _ => {
                 return Ok(());
             }
         };
-        let serialized = postcard::to_allocvec(&converted_event)?;
+        let serialized = C::encode(&converted_event)?;
         let flags = LLMP_FLAG_INITIALIZED;
 
         match self.compressor.maybe_compress(&serialized) {
@@ -489,6 +552,8 @@ where
                 observers_buf,
                 time,
                 forward_id,
+                global_id,
+                parent_global_id,
                 #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
                 node_id,
             } => Event::NewTestcase {
@@ -499,15 +564,19 @@ where
                 observers_buf,
                 time,
                 forward_id,
+                global_id,
+                parent_global_id,
                 #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
                 node_id,
             },
             Event::CustomBuf { buf, tag } => Event::CustomBuf { buf, tag },
+            Event::CommandAck { id, command } => Event::CommandAck { id, command },
+            Event::NewTokens { tokens } => Event::NewTokens { tokens },
             _ => {
                 return Ok(());
             }
         };
-        let serialized = postcard::to_allocvec(&converted_event)?;
+        let serialized = C::encode(&converted_event)?;
         self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &serialized)?;
         Ok(())
     }
@@ -3,6 +3,8 @@
 //! When the target crashes, a watch process (the parent) will
 //! restart/refork it.
 
+#[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
+use alloc::string::ToString;
 use alloc::{boxed::Box, vec::Vec};
 #[cfg(all(unix, not(miri), feature = "std"))]
 use core::ptr::addr_of_mut;
@@ -12,7 +14,7 @@ use core::sync::atomic::{compiler_fence, Ordering};
 use core::time::Duration;
 use core::{marker::PhantomData, num::NonZeroUsize};
 #[cfg(feature = "std")]
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Instant};
 
 #[cfg(feature = "std")]
 use libafl_bolts::core_affinity::CoreId;
@@ -21,16 +23,17 @@ use libafl_bolts::os::startable_self;
 #[cfg(all(unix, feature = "std", not(miri)))]
 use libafl_bolts::os::unix_signals::setup_signal_handler;
 #[cfg(all(feature = "std", feature = "fork", unix))]
-use libafl_bolts::os::{fork, ForkResult};
-#[cfg(feature = "std")]
-use libafl_bolts::{
-    llmp::LlmpConnection, os::CTRL_C_EXIT, shmem::StdShMemProvider, staterestore::StateRestorer,
-};
+use libafl_bolts::os::{fork, ChildHandle, ForkResult};
 use libafl_bolts::{
+    codec::{Codec, PostcardCodec},
     llmp::{Broker, LlmpBroker},
     shmem::ShMemProvider,
     tuples::{tuple_list, Handle},
 };
+#[cfg(feature = "std")]
+use libafl_bolts::{
+    llmp::LlmpConnection, os::CTRL_C_EXIT, shmem::StdShMemProvider, staterestore::StateRestorer,
+};
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "std")]
 use typed_builder::TypedBuilder;
@@ -41,9 +44,9 @@ use crate::events::EVENTMGR_SIGHANDLER_STATE;
 use crate::events::{AdaptiveSerializer, CustomBufEventResult, HasCustomBufHandlers};
 use crate::{
     events::{
-        Event, EventConfig, EventFirer, EventManager, EventManagerHooksTuple, EventManagerId,
-        EventProcessor, EventRestarter, HasEventManagerId, LlmpEventManager, LlmpShouldSaveState,
-        ProgressReporter, StdLlmpEventHook,
+        BrokerHooks, Event, EventConfig, EventFirer, EventManager, EventManagerHooksTuple,
+        EventManagerId, EventProcessor, EventRestarter, HasEventManagerId, LlmpEventManager,
+        LlmpEventManagerBuilder, LlmpShouldSaveState, ProgressReporter, StdLlmpEventHook,
     },
     executors::{Executor, HasObservers},
     fuzzer::{Evaluator, EvaluatorObservers, ExecutionProcessor},
@@ -55,25 +58,31 @@ use crate::{
 };
 
 /// A manager that can restart on the fly, storing states in-between (in `on_restart`)
+///
+/// The events and the state are serialized with the [`Codec`] `C`, see [`RestartingMgr::launch_with_codec`].
 #[cfg(feature = "std")]
 #[derive(Debug)]
-pub struct LlmpRestartingEventManager<EMH, S, SP>
+pub struct LlmpRestartingEventManager<EMH, S, SP, C = PostcardCodec>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
     //CE: CustomEvent<I>,
 {
     /// The embedded LLMP event manager
-    llmp_mgr: LlmpEventManager<EMH, S, SP>,
+    llmp_mgr: LlmpEventManager<EMH, S, SP, C>,
     /// The staterestorer to serialize the state for the next runner
-    staterestorer: StateRestorer<SP>,
+    staterestorer: StateRestorer<SP, C>,
     /// Decide if the state restorer must save the serialized state
     save_state: LlmpShouldSaveState,
+    /// Save the state as a delta to a base snapshot, see [`StateRestorer::save_delta`]
+    delta_snapshots: bool,
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> AdaptiveSerializer for LlmpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> AdaptiveSerializer for LlmpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     SP: ShMemProvider,
     S: State,
 {
@@ -109,8 +118,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> UsesState for LlmpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> UsesState for LlmpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
 {
@@ -118,16 +128,18 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> ProgressReporter for LlmpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> ProgressReporter for LlmpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State + HasExecutions + HasMetadata + HasLastReportTime,
     SP: ShMemProvider,
 {
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> EventFirer for LlmpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> EventFirer for LlmpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     SP: ShMemProvider,
     S: State,
     //CE: CustomEvent<I>,
@@ -160,8 +172,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> EventRestarter for LlmpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> EventRestarter for LlmpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State + HasExecutions,
     SP: ShMemProvider,
     //CE: CustomEvent<I>,
@@ -177,16 +190,15 @@ where
     fn on_restart(&mut self, state: &mut S) -> Result<(), Error> {
         state.on_restart()?;
 
+        // Tell the restarter that we got this far, e.g. if we crash before we processed any events
+        self.staterestorer.heartbeat();
         // First, reset the page to 0 so the next iteration can read from the beginning of this page
         self.staterestorer.reset();
-        self.staterestorer.save(&(
-            if self.save_state.on_restart() {
-                Some(state)
-            } else {
-                None
-            },
-            &self.llmp_mgr.describe()?,
-        ))?;
+        self.save(if self.save_state.on_restart() {
+            Some(state)
+        } else {
+            None
+        })?;
 
         log::info!("Waiting for broker...");
         self.await_restart_safe();
@@ -202,20 +214,22 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<E, EMH, S, SP, Z> EventProcessor<E, Z> for LlmpRestartingEventManager<EMH, S, SP>
+impl<E, EMH, S, SP, Z, C> EventProcessor<E, Z> for LlmpRestartingEventManager<EMH, S, SP, C>
 where
-    E: HasObservers + Executor<LlmpEventManager<EMH, S, SP>, Z, State = S>,
+    C: Codec,
+    E: HasObservers + Executor<LlmpEventManager<EMH, S, SP, C>, Z, State = S>,
     E::Observers: ObserversTuple<S::Input, S> + Serialize,
     for<'a> E::Observers: Deserialize<'a>,
     EMH: EventManagerHooksTuple<S>,
     S: State + HasExecutions + HasMetadata + HasImported,
     SP: ShMemProvider,
-    Z: ExecutionProcessor<LlmpEventManager<EMH, S, SP>, E::Observers, State = S>
-        + EvaluatorObservers<LlmpEventManager<EMH, S, SP>, E::Observers>
-        + Evaluator<E, LlmpEventManager<EMH, S, SP>>,
+    Z: ExecutionProcessor<LlmpEventManager<EMH, S, SP, C>, E::Observers, State = S>
+        + EvaluatorObservers<LlmpEventManager<EMH, S, SP, C>, E::Observers>
+        + Evaluator<E, LlmpEventManager<EMH, S, SP, C>>,
 {
     fn process(&mut self, fuzzer: &mut Z, state: &mut S, executor: &mut E) -> Result<usize, Error> {
         let res = self.llmp_mgr.process(fuzzer, state, executor)?;
+        self.staterestorer.heartbeat();
         self.intermediate_save()?;
         Ok(res)
     }
@@ -226,23 +240,25 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<E, EMH, S, SP, Z> EventManager<E, Z> for LlmpRestartingEventManager<EMH, S, SP>
+impl<E, EMH, S, SP, Z, C> EventManager<E, Z> for LlmpRestartingEventManager<EMH, S, SP, C>
 where
-    E: HasObservers + Executor<LlmpEventManager<EMH, S, SP>, Z, State = S>,
+    C: Codec,
+    E: HasObservers + Executor<LlmpEventManager<EMH, S, SP, C>, Z, State = S>,
     E::Observers: ObserversTuple<S::Input, S> + Serialize,
     for<'a> E::Observers: Deserialize<'a>,
     EMH: EventManagerHooksTuple<S>,
     S: State + HasExecutions + HasMetadata + HasLastReportTime + HasImported,
     SP: ShMemProvider,
-    Z: ExecutionProcessor<LlmpEventManager<EMH, S, SP>, E::Observers, State = S>
-        + EvaluatorObservers<LlmpEventManager<EMH, S, SP>, E::Observers>
-        + Evaluator<E, LlmpEventManager<EMH, S, SP>>,
+    Z: ExecutionProcessor<LlmpEventManager<EMH, S, SP, C>, E::Observers, State = S>
+        + EvaluatorObservers<LlmpEventManager<EMH, S, SP, C>, E::Observers>
+        + Evaluator<E, LlmpEventManager<EMH, S, SP, C>>,
 {
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> HasEventManagerId for LlmpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> HasEventManagerId for LlmpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
 {
@@ -252,8 +268,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> HasCustomBufHandlers for LlmpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> HasCustomBufHandlers for LlmpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
 {
@@ -270,43 +287,117 @@ const _ENV_FUZZER_SENDER: &str = "_AFL_ENV_FUZZER_SENDER";
 const _ENV_FUZZER_RECEIVER: &str = "_AFL_ENV_FUZZER_RECEIVER";
 /// The llmp (2 way) connection from a fuzzer to the broker (broadcasting all other fuzzer messages)
 const _ENV_FUZZER_BROKER_CLIENT_INITIAL: &str = "_AFL_ENV_FUZZER_BROKER_CLIENT";
+/// The core a respawned fuzzer binds to, if it was spawned instead of forked
+#[cfg(all(feature = "std", any(windows, not(feature = "fork"))))]
+const _ENV_FUZZER_CORE_ID: &str = "_AFL_ENV_FUZZER_CORE_ID";
+
+/// Detects clients crashing over and over right after they were (re)spawned, e.g. while restoring their state,
+/// so that the restarter gives up instead of respawning them forever.
+///
+/// The restarter clears the heartbeat in the named shared map of the [`StateRestorer`] before it forks or
+/// spawns a client, and the client sends one whenever it processes events or saves its state on restart,
+/// i.e. once it restored its state and started fuzzing. A client that exits without a heartbeat, or before it ran for `min_uptime`,
+/// counts as an early exit. After `max_early_exits` consecutive early exits, the restarting manager detaches
+/// from the broker and returns an error.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrashLoopDetector {
+    min_uptime: Duration,
+    max_early_exits: usize,
+    early_exits: usize,
+}
+
+#[cfg(feature = "std")]
+impl CrashLoopDetector {
+    /// Creates a new [`CrashLoopDetector`], giving up after `max_early_exits` consecutive clients exited
+    /// within `min_uptime`
+    #[must_use]
+    pub fn new(min_uptime: Duration, max_early_exits: usize) -> Self {
+        Self {
+            min_uptime,
+            max_early_exits,
+            early_exits: 0,
+        }
+    }
+
+    /// Records the exit of a client that ran for `uptime`, and sent a heartbeat if `started`,
+    /// returns `true` if the clients are in a crash loop
+    pub fn on_exit(&mut self, uptime: Duration, started: bool) -> bool {
+        if !started || uptime < self.min_uptime {
+            self.early_exits += 1;
+        } else {
+            self.early_exits = 0;
+        }
+        self.early_exits >= self.max_early_exits
+    }
+
+    /// The amount of consecutive early exits so far
+    #[must_use]
+    pub fn early_exits(&self) -> usize {
+        self.early_exits
+    }
+}
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> LlmpRestartingEventManager<EMH, S, SP>
+impl Default for CrashLoopDetector {
+    /// Gives up after 16 consecutive clients exited within a second
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), 16)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<EMH, S, SP, C> LlmpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
     //CE: CustomEvent<I>,
 {
     /// Create a new runner, the executed child doing the actual fuzzing.
-    pub fn new(llmp_mgr: LlmpEventManager<EMH, S, SP>, staterestorer: StateRestorer<SP>) -> Self {
+    pub fn new(
+        llmp_mgr: LlmpEventManager<EMH, S, SP, C>,
+        staterestorer: StateRestorer<SP, C>,
+    ) -> Self {
         Self {
             llmp_mgr,
             staterestorer,
             save_state: LlmpShouldSaveState::OnRestart,
+            delta_snapshots: false,
         }
     }
 
     /// Create a new runner specifying if it must save the serialized state on restart.
     pub fn with_save_state(
-        llmp_mgr: LlmpEventManager<EMH, S, SP>,
-        staterestorer: StateRestorer<SP>,
+        llmp_mgr: LlmpEventManager<EMH, S, SP, C>,
+        staterestorer: StateRestorer<SP, C>,
         save_state: LlmpShouldSaveState,
     ) -> Self {
         Self {
             llmp_mgr,
             staterestorer,
             save_state,
+            delta_snapshots: false,
         }
     }
 
+    /// Save the state as a delta to a base snapshot kept in a tmpfile on restart, see [`StateRestorer::save_delta`].
+    ///
+    /// With a large corpus, restarts then only write the inputs added since the base snapshot.
+    /// The next runner has to restore it with [`StateRestorer::restore_delta`], see [`RestartingMgr`].
+    #[must_use]
+    pub fn with_delta_snapshots(mut self, delta_snapshots: bool) -> Self {
+        self.delta_snapshots = delta_snapshots;
+        self
+    }
+
     /// Get the staterestorer
-    pub fn staterestorer(&self) -> &StateRestorer<SP> {
+    pub fn staterestorer(&self) -> &StateRestorer<SP, C> {
         &self.staterestorer
     }
 
     /// Get the staterestorer (mutable)
-    pub fn staterestorer_mut(&mut self) -> &mut StateRestorer<SP> {
+    pub fn staterestorer_mut(&mut self) -> &mut StateRestorer<SP, C> {
         &mut self.staterestorer
     }
 
@@ -315,11 +406,64 @@ where
         // First, reset the page to 0 so the next iteration can read read from the beginning of this page
         if self.save_state.oom_safe() {
             self.staterestorer.reset();
-            self.staterestorer
-                .save(&(None::<S>, &self.llmp_mgr.describe()?))?;
+            self.save(None)?;
         }
         Ok(())
     }
+
+    /// Save the state, if any, and the description of the LLMP client in the staterestorer
+    fn save(&mut self, state: Option<&mut S>) -> Result<(), Error> {
+        let description = self.llmp_mgr.describe()?;
+        if self.delta_snapshots {
+            self.staterestorer.save_delta(state, &description)
+        } else {
+            self.staterestorer.save(&(state, &description))
+        }
+    }
+}
+
+/// Waits for the child to exit like [`ChildHandle::status`], but kills it once it did not send a heartbeat through
+/// the `staterestorer` for `timeout`, so that it gets respawned. Children are not killed before their first heartbeat,
+/// e.g. while they load the initial inputs.
+#[cfg(all(unix, feature = "std", feature = "fork"))]
+fn wait_or_kill_stalled<SP, C>(
+    handle: &ChildHandle,
+    staterestorer: &StateRestorer<SP, C>,
+    timeout: Duration,
+) -> Result<i32, Error>
+where
+    SP: ShMemProvider,
+{
+    loop {
+        let mut status = 0;
+        // # Safety
+        // Polls our own child, without blocking
+        match unsafe { libc::waitpid(handle.pid, &mut status, libc::WNOHANG) } {
+            0 => {}
+            -1 => {
+                return Err(Error::last_os_error(format!(
+                    "Failed to wait for client {}",
+                    handle.pid
+                )))
+            }
+            _ => return Ok(libc::WEXITSTATUS(status)),
+        }
+        if let Some(last_heartbeat) = staterestorer.last_heartbeat() {
+            if libafl_bolts::current_time().saturating_sub(last_heartbeat) > timeout {
+                log::warn!(
+                    "Client {} did not process events for {timeout:?}, killing it to respawn it",
+                    handle.pid
+                );
+                // # Safety
+                // Kills our own child, which we reap right after
+                unsafe {
+                    libc::kill(handle.pid, libc::SIGKILL);
+                }
+                return Ok(handle.status());
+            }
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
 }
 
 /// The kind of manager we're creating right now
@@ -357,6 +501,7 @@ pub fn setup_restarting_mgr_std<MT, S>(
 where
     MT: Monitor + Clone,
     S: State,
+    S::Input: 'static,
 {
     RestartingMgr::builder()
         .shmem_provider(StdShMemProvider::new()?)
@@ -390,6 +535,7 @@ pub fn setup_restarting_mgr_std_adaptive<MT, S>(
 where
     MT: Monitor + Clone,
     S: State,
+    S::Input: 'static,
 {
     RestartingMgr::builder()
         .shmem_provider(StdShMemProvider::new()?)
@@ -439,11 +585,33 @@ pub struct RestartingMgr<EMH, MT, S, SP> {
     /// but it will quit after client 2 connected and disconnected.
     #[builder(default = None)]
     exit_cleanly_after: Option<NonZeroUsize>,
+    /// If set, the broker considers clients lost that did not send any message for this long.
+    ///
+    /// Lost clients are dropped from the monitor, and picked up again if they ever send another message,
+    /// see [`libafl_bolts::llmp::LlmpBrokerInner::set_client_timeout`].
+    /// Crashed clients are respawned by their restarter anyway. For clients that hang, the restarter kills
+    /// them once they did not process events for this long, and respawns them. This needs an OOM-safe
+    /// `serialize_state`, so the respawned client finds its LLMP page, and fork (on Unix).
+    #[builder(default = None)]
+    client_timeout: Option<Duration>,
     /// Tell the manager to serialize or not the state on restart
     #[builder(default = LlmpShouldSaveState::OnRestart)]
     serialize_state: LlmpShouldSaveState,
+    /// Serialize the state as a delta to a base snapshot, see [`LlmpRestartingEventManager::with_delta_snapshots`]
+    #[builder(default = false)]
+    delta_snapshots: bool,
+    /// If set, stop respawning clients that keep exiting right after they started
+    #[builder(default = None)]
+    crash_loop_detector: Option<CrashLoopDetector>,
+    /// If set, pass testcases serializing to at least this many bytes in shared memory,
+    /// see [`LlmpEventManagerBuilder::shared_events`]
+    #[builder(default = None)]
+    shared_events: Option<usize>,
     /// The hooks passed to event manager:
     hooks: EMH,
+    /// The [`crate::events::BrokerHook`]s of the broker, if this manager starts one
+    #[builder(default)]
+    broker_hooks: BrokerHooks,
     #[builder(default = None)]
     time_ref: Option<Handle<TimeObserver>>,
     #[builder(setter(skip), default = PhantomData)]
@@ -459,10 +627,42 @@ where
     S: State,
     MT: Monitor + Clone,
 {
+    /// The builder for the client's [`LlmpEventManager`], with the options of this [`RestartingMgr`] applied
+    fn mgr_builder<C>(&self) -> LlmpEventManagerBuilder<EMH, C>
+    where
+        C: Codec,
+    {
+        let builder = LlmpEventManager::builder()
+            .always_interesting(self.always_interesting)
+            .hooks(self.hooks)
+            .codec::<C>();
+        if let Some(threshold) = self.shared_events {
+            builder.shared_events(threshold)
+        } else {
+            builder
+        }
+    }
+
     /// Launch the broker and the clients and fuzz
-    pub fn launch(&mut self) -> Result<(Option<S>, LlmpRestartingEventManager<EMH, S, SP>), Error> {
+    pub fn launch(&mut self) -> Result<(Option<S>, LlmpRestartingEventManager<EMH, S, SP>), Error>
+    where
+        S::Input: 'static,
+    {
+        self.launch_with_codec()
+    }
+
+    /// Launch the broker and the clients and fuzz, serializing the events and the state with the [`Codec`] `C`.
+    ///
+    /// The broker and all clients have to use the same codec.
+    pub fn launch_with_codec<C>(
+        &mut self,
+    ) -> Result<(Option<S>, LlmpRestartingEventManager<EMH, S, SP, C>), Error>
+    where
+        C: Codec,
+        S::Input: 'static,
+    {
         // We start ourselves as child process to actually fuzz
-        let (staterestorer, new_shmem_provider, core_id) = if std::env::var(_ENV_FUZZER_SENDER)
+        let (mut staterestorer, new_shmem_provider, core_id) = if std::env::var(_ENV_FUZZER_SENDER)
             .is_err()
         {
             let broker_things = |mut broker: LlmpBroker<_, SP>, remote_broker_addr| {
@@ -475,6 +675,10 @@ where
                     broker.set_exit_after(exit_cleanly_after);
                 }
 
+                if let Some(client_timeout) = self.client_timeout {
+                    broker.inner_mut().set_client_timeout(client_timeout);
+                }
+
                 broker.loop_with_timeouts(Duration::from_secs(30), Some(Duration::from_millis(5)));
 
                 #[cfg(feature = "llmp_debug")]
@@ -489,9 +693,11 @@ where
                         LlmpConnection::on_port(self.shmem_provider.clone(), self.broker_port)?;
                     match connection {
                         LlmpConnection::IsBroker { broker } => {
-                            let llmp_hook = StdLlmpEventHook::<S::Input, MT>::new(
+                            let llmp_hook = StdLlmpEventHook::<S::Input, MT, _>::with_hooks(
                                 self.monitor.take().unwrap(),
-                            )?;
+                                self.broker_hooks.take()?,
+                            )?
+                            .with_codec::<C>();
 
                             // Yep, broker. Just loop here.
                             log::info!(
@@ -506,10 +712,8 @@ where
                             return Err(Error::shutting_down());
                         }
                         LlmpConnection::IsClient { client } => {
-                            let mgr: LlmpEventManager<EMH, S, SP> = LlmpEventManager::builder()
-                                .always_interesting(self.always_interesting)
-                                .hooks(self.hooks)
-                                .build_from_client(
+                            let mgr: LlmpEventManager<EMH, S, SP, C> =
+                                self.mgr_builder().build_from_client(
                                     client,
                                     self.configuration,
                                     self.time_ref.clone(),
@@ -519,7 +723,11 @@ where
                     }
                 }
                 ManagerKind::Broker => {
-                    let llmp_hook = StdLlmpEventHook::new(self.monitor.take().unwrap())?;
+                    let llmp_hook = StdLlmpEventHook::<S::Input, MT, _>::with_hooks(
+                        self.monitor.take().unwrap(),
+                        self.broker_hooks.take()?,
+                    )?
+                    .with_codec::<C>();
 
                     let broker = LlmpBroker::create_attach_to_tcp(
                         self.shmem_provider.clone(),
@@ -532,15 +740,12 @@ where
                 }
                 ManagerKind::Client { cpu_core } => {
                     // We are a client
-                    let mgr = LlmpEventManager::builder()
-                        .always_interesting(self.always_interesting)
-                        .hooks(self.hooks)
-                        .build_on_port(
-                            self.shmem_provider.clone(),
-                            self.broker_port,
-                            self.configuration,
-                            self.time_ref.clone(),
-                        )?;
+                    let mgr = self.mgr_builder().build_on_port(
+                        self.shmem_provider.clone(),
+                        self.broker_port,
+                        self.configuration,
+                        self.time_ref.clone(),
+                    )?;
 
                     (mgr, cpu_core)
                 }
@@ -557,19 +762,32 @@ where
 
             // First, create a channel from the current fuzzer to the next to store state between restarts.
             #[cfg(unix)]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP, C> =
+                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?).with_codec();
 
             #[cfg(not(unix))]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP, C> =
+                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?).with_codec();
             // Store the information to a map.
             staterestorer.write_to_env(_ENV_FUZZER_SENDER)?;
 
+            #[cfg(all(unix, feature = "fork"))]
+            let stall_timeout = self.client_timeout.filter(|_| {
+                if !self.serialize_state.oom_safe() {
+                    log::warn!("Not respawning stalled clients, the state is not saved OOM-safe");
+                }
+                self.serialize_state.oom_safe()
+            });
+
             let mut ctr: u64 = 0;
+            let mut crash_loop_detector = self.crash_loop_detector;
             // Client->parent loop
             loop {
                 log::info!("Spawning next client (id {ctr})");
+                // The child finds the state restorer through the name of its shared map, and reports back
+                // through the heartbeat in it once it started fuzzing
+                staterestorer.clear_heartbeat();
+                let spawned_at = Instant::now();
 
                 // On Unix, we fork (when fork feature is enabled)
                 #[cfg(all(unix, feature = "fork"))]
@@ -581,7 +799,12 @@ where
                                 libc::signal(libc::SIGINT, libc::SIG_IGN);
                             }
                             self.shmem_provider.post_fork(false)?;
-                            handle.status()
+                            match stall_timeout {
+                                Some(timeout) => {
+                                    wait_or_kill_stalled(&handle, &staterestorer, timeout)?
+                                }
+                                None => handle.status(),
+                            }
                         }
                         ForkResult::Child => {
                             log::debug!(
@@ -608,9 +831,17 @@ where
                     libc::signal(libc::SIGINT, libc::SIG_IGN);
                 }
 
-                // On Windows (or in any case without fork), we spawn ourself again
+                // On Windows (or in any case without fork), we spawn ourself again.
+                // The child inherits our handles and environment, and finds the state restorer and the
+                // llmp client page through the names of their shared maps in the environment.
                 #[cfg(any(windows, not(feature = "fork")))]
-                let child_status = startable_self()?.status()?;
+                let child_status = {
+                    let mut child = startable_self()?;
+                    if let Some(core_id) = core_id {
+                        child.env(_ENV_FUZZER_CORE_ID, core_id.0.to_string());
+                    }
+                    child.status()?
+                };
                 #[cfg(any(windows, not(feature = "fork")))]
                 let child_status = child_status.code().unwrap_or_default();
 
@@ -624,6 +855,21 @@ where
                     return Err(Error::shutting_down());
                 }
 
+                if let Some(detector) = &mut crash_loop_detector {
+                    if detector.on_exit(
+                        spawned_at.elapsed(),
+                        staterestorer.last_heartbeat().is_some(),
+                    ) {
+                        if let Err(err) = mgr.detach_from_broker(self.broker_port) {
+                            log::error!("Failed to detach from broker: {err}");
+                        }
+                        return Err(Error::illegal_state(format!(
+                            "Fuzzer-respawner: the last {} clients exited right after they started (last exit status: {child_status}), giving up",
+                            detector.early_exits()
+                        )));
+                    }
+                }
+
                 #[allow(clippy::manual_assert)]
                 if !staterestorer.has_content() && !self.serialize_state.oom_safe() {
                     if let Err(err) = mgr.detach_from_broker(self.broker_port) {
@@ -643,10 +889,17 @@ where
             // We are the newly started fuzzing instance (i.e. on Windows), first, connect to our own restore map.
             // We get here *only on Windows*, if we were started by a restarting fuzzer.
             // A staterestorer and a receiver for single communication
+            #[cfg(any(windows, not(feature = "fork")))]
+            let core_id = std::env::var(_ENV_FUZZER_CORE_ID)
+                .ok()
+                .and_then(|core_id| core_id.parse().ok())
+                .map(CoreId);
+            #[cfg(not(any(windows, not(feature = "fork"))))]
+            let core_id = None;
             (
-                StateRestorer::from_env(&mut self.shmem_provider, _ENV_FUZZER_SENDER)?,
+                StateRestorer::from_env(&mut self.shmem_provider, _ENV_FUZZER_SENDER)?.with_codec(),
                 self.shmem_provider.clone(),
-                None,
+                core_id,
             )
         };
 
@@ -664,45 +917,59 @@ where
         }
 
         // If we're restarting, deserialize the old state.
-        let (state, mut mgr) =
-            if let Some((state_opt, mgr_description)) = staterestorer.restore()? {
-                let llmp_mgr = LlmpEventManager::builder()
-                    .hooks(self.hooks)
-                    .build_existing_client_from_description(
+        let restored = if self.delta_snapshots {
+            staterestorer.restore_delta()?
+        } else {
+            staterestorer.restore()?
+        };
+        let (state, mut mgr) = if let Some((state_opt, mgr_description)) = restored {
+            let llmp_mgr = match self.mgr_builder().build_existing_client_from_description(
+                new_shmem_provider.clone(),
+                &mgr_description,
+                self.configuration,
+                self.time_ref.clone(),
+            ) {
+                Ok(llmp_mgr) => llmp_mgr,
+                Err(err) => {
+                    // E.g. the broker released us while we were stalled
+                    log::warn!("Could not reattach to the broker ({err}), registering again");
+                    self.mgr_builder().build_on_port(
                         new_shmem_provider,
-                        &mgr_description,
+                        self.broker_port,
                         self.configuration,
                         self.time_ref.clone(),
-                    )?;
-                (
-                    state_opt,
-                    LlmpRestartingEventManager::with_save_state(
-                        llmp_mgr,
-                        staterestorer,
-                        self.serialize_state,
-                    ),
+                    )?
+                }
+            };
+            (
+                state_opt,
+                LlmpRestartingEventManager::with_save_state(
+                    llmp_mgr,
+                    staterestorer,
+                    self.serialize_state,
                 )
-            } else {
-                log::info!("First run. Let's set it all up");
-                // Mgr to send and receive msgs from/to all other fuzzer instances
-                let mgr = LlmpEventManager::builder()
-                    .hooks(self.hooks)
-                    .build_existing_client_from_env(
-                        new_shmem_provider,
-                        _ENV_FUZZER_BROKER_CLIENT_INITIAL,
-                        self.configuration,
-                        self.time_ref.clone(),
-                    )?;
+                .with_delta_snapshots(self.delta_snapshots),
+            )
+        } else {
+            log::info!("First run. Let's set it all up");
+            // Mgr to send and receive msgs from/to all other fuzzer instances
+            let mgr = self.mgr_builder().build_existing_client_from_env(
+                new_shmem_provider,
+                _ENV_FUZZER_BROKER_CLIENT_INITIAL,
+                self.configuration,
+                self.time_ref.clone(),
+            )?;
 
-                (
-                    None,
-                    LlmpRestartingEventManager::with_save_state(
-                        mgr,
-                        staterestorer,
-                        self.serialize_state,
-                    ),
+            (
+                None,
+                LlmpRestartingEventManager::with_save_state(
+                    mgr,
+                    staterestorer,
+                    self.serialize_state,
                 )
-            };
+                .with_delta_snapshots(self.delta_snapshots),
+            )
+        };
         // We reset the staterestorer, the next staterestorer and receiver (after crash) will reuse the page from the initial message.
         if self.serialize_state.oom_safe() {
             mgr.intermediate_save()?;
@@ -723,33 +990,71 @@ where
 #[cfg(test)]
 #[cfg(feature = "std")]
 mod tests {
-    use core::sync::atomic::{compiler_fence, Ordering};
+    use alloc::{
+        string::{String, ToString},
+        sync::Arc,
+        vec::Vec,
+    };
+    use core::{
+        num::NonZeroUsize,
+        sync::atomic::{compiler_fence, Ordering},
+        time::Duration,
+    };
+    use std::{sync::Mutex, thread::sleep};
 
     use libafl_bolts::{
-        llmp::{LlmpClient, LlmpSharedMap},
+        codec::Codec,
+        llmp::{LlmpClient, LlmpConnection, LlmpSharedMap},
         rands::StdRand,
         shmem::{ShMemProvider, StdShMemProvider},
         staterestore::StateRestorer,
         tuples::{tuple_list, Handled},
         ClientId,
     };
+    use serde::{de::DeserializeOwned, Serialize};
     use serial_test::serial;
 
     use crate::{
-        corpus::{Corpus, InMemoryCorpus, Testcase},
-        events::llmp::{restarting::_ENV_FUZZER_SENDER, LlmpEventManager},
+        corpus::{Corpus, GlobalTestcaseId, InMemoryCorpus, Testcase},
+        events::{
+            llmp::{restarting::_ENV_FUZZER_SENDER, CrashLoopDetector, LlmpEventManager},
+            Event, EventConfig, EventFirer, EventProcessor, StdLlmpEventHook,
+        },
         executors::{ExitKind, InProcessExecutor},
         feedbacks::ConstFeedback,
         fuzzer::Fuzzer,
         inputs::BytesInput,
+        monitors::SimpleMonitor,
         mutators::BitFlipMutator,
         observers::TimeObserver,
         schedulers::RandScheduler,
         stages::StdMutationalStage,
-        state::StdState,
-        StdFuzzer,
+        state::{HasExecutions, NopState, StdState},
+        Error, StdFuzzer,
     };
 
+    /// A self-describing [`Codec`] for the tests, which no other part of the campaign understands by chance
+    #[derive(Debug)]
+    struct JsonCodec;
+
+    impl Codec for JsonCodec {
+        const NAME: &'static str = "json";
+
+        fn encode<T>(value: &T) -> Result<Vec<u8>, Error>
+        where
+            T: Serialize + ?Sized,
+        {
+            serde_json::to_vec(value).map_err(|err| Error::serialize(err.to_string()))
+        }
+
+        fn decode<T>(bytes: &[u8]) -> Result<T, Error>
+        where
+            T: DeserializeOwned,
+        {
+            serde_json::from_slice(bytes).map_err(|err| Error::serialize(err.to_string()))
+        }
+    }
+
     #[test]
     #[serial]
     #[cfg_attr(miri, ignore)]
@@ -847,4 +1152,180 @@ mod tests {
             )
             .unwrap();
     }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    fn test_mgr_codec() {
+        let mut shmem_provider = StdShMemProvider::new().unwrap();
+
+        // The state goes through the codec of the state restorer
+        let mut staterestorer = StateRestorer::new(shmem_provider.new_shmem(1024 * 1024).unwrap())
+            .with_codec::<JsonCodec>();
+        staterestorer
+            .save(&(Some(42_u64), String::from("state")))
+            .unwrap();
+        let restored: (Option<u64>, String) = staterestorer.restore().unwrap().unwrap();
+        assert_eq!(restored, (Some(42), String::from("state")));
+
+        // Events go through the codec of the manager to the broker, and back to the other clients
+        let port = 13_991;
+        let LlmpConnection::IsBroker { broker } =
+            LlmpConnection::on_port(shmem_provider.clone(), port).unwrap()
+        else {
+            panic!("Could not bind to port {port} as broker");
+        };
+        let displayed = Arc::new(Mutex::new(Vec::new()));
+        let displayed_ref = displayed.clone();
+        let monitor = SimpleMonitor::new(move |s: &str| {
+            displayed_ref.lock().unwrap().push(s.to_string());
+        });
+        let llmp_hook = StdLlmpEventHook::<BytesInput, _>::new(monitor)
+            .unwrap()
+            .with_codec::<JsonCodec>();
+        let mut broker = broker.add_hooks(tuple_list!(llmp_hook));
+
+        let mut mgr = LlmpEventManager::builder()
+            .codec::<JsonCodec>()
+            .build_on_port(shmem_provider.clone(), port, "fuzzer".into(), None)
+            .unwrap();
+        let mut receiver = LlmpClient::create_attach_to_tcp(shmem_provider, port).unwrap();
+        // Give the (background) tcp thread a few millis to post the new clients
+        sleep(Duration::from_millis(100));
+        broker.broker_once().unwrap();
+
+        let input = BytesInput::new(vec![1, 2, 3]);
+        let mut state = NopState::<BytesInput>::new();
+        mgr.fire(
+            &mut state,
+            Event::NewTestcase {
+                global_id: GlobalTestcaseId::of(&input).unwrap(),
+                input: input.clone(),
+                observers_buf: None,
+                exit_kind: ExitKind::Ok,
+                corpus_size: 42,
+                client_config: EventConfig::AlwaysUnique,
+                time: Duration::from_secs(1),
+                forward_id: None,
+                parent_global_id: None,
+                #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
+                node_id: None,
+            },
+        )
+        .unwrap();
+        broker.broker_once().unwrap();
+        assert!(displayed
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|line| line.contains("corpus: 42")));
+
+        let (_, _, buf) = receiver.recv_buf_blocking().unwrap();
+        let Event::NewTestcase {
+            input: received,
+            corpus_size,
+            ..
+        } = JsonCodec::decode::<Event<BytesInput>>(buf).unwrap()
+        else {
+            panic!("Received another event");
+        };
+        assert_eq!(received, input);
+        assert_eq!(corpus_size, 42);
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    fn test_mgr_import_limit() {
+        let shmem_provider = StdShMemProvider::new().unwrap();
+        let port = 13_992;
+        let LlmpConnection::IsBroker { broker } =
+            LlmpConnection::on_port(shmem_provider.clone(), port).unwrap()
+        else {
+            panic!("Could not bind to port {port} as broker");
+        };
+        let monitor = SimpleMonitor::new(|_s: &str| {});
+        let llmp_hook = StdLlmpEventHook::<BytesInput, _>::new(monitor).unwrap();
+        let mut broker = broker.add_hooks(tuple_list!(llmp_hook));
+
+        let mut sender = LlmpEventManager::builder()
+            .build_on_port(shmem_provider.clone(), port, "fuzzer".into(), None)
+            .unwrap();
+        let mut mgr = LlmpEventManager::builder()
+            .import_limit(NonZeroUsize::new(2).unwrap())
+            .build_on_port(shmem_provider, port, "fuzzer".into(), None)
+            .unwrap();
+        // Give the (background) tcp thread a few millis to post the new clients
+        sleep(Duration::from_millis(100));
+        broker.broker_once().unwrap();
+
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut fuzzer = StdFuzzer::new(RandScheduler::new(), feedback, objective);
+        let mut harness = |_buf: &BytesInput| ExitKind::Ok;
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+
+        for i in 0..5_u8 {
+            let input = BytesInput::new(vec![i]);
+            sender
+                .fire(
+                    &mut NopState::<BytesInput>::new(),
+                    Event::NewTestcase {
+                        global_id: GlobalTestcaseId::of(&input).unwrap(),
+                        input,
+                        observers_buf: None,
+                        exit_kind: ExitKind::Ok,
+                        corpus_size: 1,
+                        client_config: EventConfig::AlwaysUnique,
+                        time: Duration::from_secs(1),
+                        forward_id: None,
+                        parent_global_id: None,
+                        #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
+                        node_id: None,
+                    },
+                )
+                .unwrap();
+        }
+        broker.broker_once().unwrap();
+
+        // At most two of the five received testcases are evaluated per call
+        let mut evaluated = Vec::new();
+        for _ in 0..3 {
+            let before = *state.executions();
+            mgr.process(&mut fuzzer, &mut state, &mut executor).unwrap();
+            evaluated.push(*state.executions() - before);
+        }
+        assert_eq!(evaluated, [2, 2, 1]);
+        assert_eq!(mgr.pending_imports(), 0);
+    }
+
+    #[test]
+    fn test_crash_loop_detector() {
+        let mut detector = CrashLoopDetector::new(Duration::from_secs(1), 3);
+        assert!(!detector.on_exit(Duration::from_millis(10), true));
+        assert!(!detector.on_exit(Duration::from_millis(10), true));
+        // A client that ran long enough resets the count
+        assert!(!detector.on_exit(Duration::from_secs(60), true));
+        assert_eq!(detector.early_exits(), 0);
+        // A client that never sent a heartbeat did not start, no matter how long it ran
+        assert!(!detector.on_exit(Duration::from_secs(60), false));
+        assert_eq!(detector.early_exits(), 1);
+        assert!(!detector.on_exit(Duration::from_millis(10), true));
+        assert!(detector.on_exit(Duration::from_millis(10), true));
+    }
 }
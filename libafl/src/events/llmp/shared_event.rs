@@ -0,0 +1,271 @@
+//! Zero-copy transfer of large events over LLMP, see [`SharedEventPool`].
+//!
+//! Instead of copying a multi-megabyte testcase into the LLMP pages of the sender, then into the broadcast pages
+//! of the broker, the serialized event is written once to a dedicated shared memory segment. Only a small
+//! [`SharedEventRef`], describing the segment, travels through LLMP; the broker and the clients map the segment
+//! and deserialize the event right from it.
+//!
+//! The first bytes of each segment count the receivers which still have to read it. The sender publishes a
+//! segment with a single pending receiver, the broker. Before the broker releases it, it adds one pending
+//! receiver per client it forwards the reference to, see [`SharedEventRef::forward`]. Each client releases its
+//! own once it read the event, and the sender frees the segment when none is left. Receivers which die before
+//! reading keep their segments alive: once [`DEFAULT_MAX_SHARED_EVENTS`] are pending, the sender copies its
+//! events through LLMP again. Shared memory can not be mapped on other machines: the multi-machine hooks
+//! forward the full event instead.
+
+use alloc::collections::VecDeque;
+use core::{
+    slice,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use libafl_bolts::{
+    shmem::{ShMem, ShMemDescription, ShMemProvider},
+    Error,
+};
+use serde::{Deserialize, Serialize};
+
+/// The serialized events at least this large are shared, by default
+pub const DEFAULT_SHARED_EVENT_THRESHOLD: usize = 1024 * 1024;
+
+/// The most segments a sender keeps alive while they are pending, by default
+pub const DEFAULT_MAX_SHARED_EVENTS: usize = 64;
+
+/// The header of a segment: the count of pending receivers, padded for the alignment of the event
+const HEADER_LEN: usize = 8;
+
+/// The count of receivers which did not read the segment yet
+fn pending<SHM>(shmem: &mut SHM) -> &AtomicU32
+where
+    SHM: ShMem,
+{
+    // # Safety
+    // Segments are page aligned and at least `HEADER_LEN` bytes long, and the first four bytes are only ever
+    // accessed atomically.
+    unsafe { &*shmem.as_mut_ptr().cast::<AtomicU32>() }
+}
+
+/// Releases one pending receiver of the segment, and returns how many are left
+fn release<SHM>(shmem: &mut SHM) -> u32
+where
+    SHM: ShMem,
+{
+    // Saturating: a client attaching later may see a reference it was not counted for
+    let previous = pending(shmem)
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+            Some(count.saturating_sub(1))
+        })
+        .unwrap();
+    previous.saturating_sub(1)
+}
+
+/// The LLMP message standing in for an event that was written to shared memory
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SharedEventRef {
+    /// The segment the event was written to
+    pub description: ShMemDescription,
+    /// The length of the serialized event
+    pub len: usize,
+}
+
+impl SharedEventRef {
+    /// Parses the reference from an LLMP message
+    pub fn from_msg(msg: &[u8]) -> Result<Self, Error> {
+        Ok(postcard::from_bytes(msg)?)
+    }
+
+    /// Maps the segment of the event
+    fn map<SP>(&self, shmem_provider: &mut SP) -> Result<SP::ShMem, Error>
+    where
+        SP: ShMemProvider,
+    {
+        if HEADER_LEN + self.len > self.description.size {
+            return Err(Error::illegal_state(format!(
+                "Shared event of {} bytes does not fit its segment of {} bytes",
+                self.len, self.description.size
+            )));
+        }
+        shmem_provider
+            .shmem_from_description(self.description)
+            .map_err(|err| {
+                Error::illegal_state(format!(
+                    "Could not map the shared event {:?}, it may have been freed already: {err}",
+                    self.description.id
+                ))
+            })
+    }
+
+    /// Calls `f` with the serialized event, without copying it
+    fn with_event<SHM, F, R>(&self, shmem: &mut SHM, f: F) -> Result<R, Error>
+    where
+        SHM: ShMem,
+        F: FnOnce(&[u8]) -> Result<R, Error>,
+    {
+        // # Safety
+        // The event is behind the header, in bounds as checked in `map`, and not written after it was published.
+        let event_bytes =
+            unsafe { slice::from_raw_parts(shmem.as_mut_ptr().add(HEADER_LEN), self.len) };
+        f(event_bytes)
+    }
+
+    /// Maps the segment and calls `f` with the serialized event, without copying it, then releases the segment.
+    ///
+    /// For clients, each of which was counted as pending receiver by the broker.
+    pub fn read<SP, F, R>(&self, shmem_provider: &mut SP, f: F) -> Result<R, Error>
+    where
+        SP: ShMemProvider,
+        F: FnOnce(&[u8]) -> Result<R, Error>,
+    {
+        let mut shmem = self.map(shmem_provider)?;
+        let res = self.with_event(&mut shmem, f);
+        release(&mut shmem);
+        res
+    }
+
+    /// Maps the segment and calls `f` with the serialized event, without releasing the segment.
+    ///
+    /// For broker hooks, which read the event before the broker [`Self::forward`]s it.
+    pub fn peek<SP, F, R>(&self, shmem_provider: &mut SP, f: F) -> Result<R, Error>
+    where
+        SP: ShMemProvider,
+        F: FnOnce(&[u8]) -> Result<R, Error>,
+    {
+        let mut shmem = self.map(shmem_provider)?;
+        self.with_event(&mut shmem, f)
+    }
+
+    /// Hands the segment over from the broker to the `receivers` it forwards the reference to, `0` if it does not
+    /// forward it. Must be called exactly once by the broker, after its hooks [`Self::peek`]ed at the event.
+    pub fn forward<SP>(&self, shmem_provider: &mut SP, receivers: usize) -> Result<(), Error>
+    where
+        SP: ShMemProvider,
+    {
+        let mut shmem = self.map(shmem_provider)?;
+        // Add the receivers before releasing the broker, so the count does not drop to zero in between
+        pending(&mut shmem).fetch_add(
+            u32::try_from(receivers).unwrap_or(u32::MAX),
+            Ordering::AcqRel,
+        );
+        release(&mut shmem);
+        Ok(())
+    }
+}
+
+/// The segments a sender published, kept alive until the receivers are done with them.
+///
+/// Enable it with [`crate::events::LlmpEventManagerBuilder::shared_events`].
+#[derive(Debug)]
+pub struct SharedEventPool<SHM> {
+    threshold: usize,
+    max_segments: usize,
+    /// The published segments, oldest first
+    segments: VecDeque<SHM>,
+}
+
+impl<SHM> SharedEventPool<SHM>
+where
+    SHM: ShMem,
+{
+    /// Create a new [`SharedEventPool`], sharing the serialized events of at least `threshold` bytes
+    #[must_use]
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            max_segments: DEFAULT_MAX_SHARED_EVENTS,
+            segments: VecDeque::new(),
+        }
+    }
+
+    /// Keep at most `max_segments` segments alive while they are pending; defaults to [`DEFAULT_MAX_SHARED_EVENTS`]
+    #[must_use]
+    pub fn with_max_segments(mut self, max_segments: usize) -> Self {
+        self.max_segments = max_segments;
+        self
+    }
+
+    /// If a serialized event of this length should be shared, frees the segments which were read by all receivers
+    pub fn should_share(&mut self, len: usize) -> bool {
+        self.collect();
+        len >= self.threshold && self.segments.len() < self.max_segments
+    }
+
+    /// The number of segments kept alive
+    #[must_use]
+    pub fn live_segments(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Writes the serialized event to a new segment, returning the reference to send instead
+    pub fn publish<SP>(
+        &mut self,
+        shmem_provider: &mut SP,
+        event_bytes: &[u8],
+    ) -> Result<SharedEventRef, Error>
+    where
+        SP: ShMemProvider<ShMem = SHM>,
+    {
+        let mut shmem = shmem_provider.new_shmem(HEADER_LEN + event_bytes.len())?;
+        shmem[..HEADER_LEN].fill(0);
+        shmem[HEADER_LEN..HEADER_LEN + event_bytes.len()].copy_from_slice(event_bytes);
+        // The broker, which releases it once it forwarded the reference
+        pending(&mut shmem).store(1, Ordering::Release);
+        let shared = SharedEventRef {
+            description: shmem.description(),
+            len: event_bytes.len(),
+        };
+        self.segments.push_back(shmem);
+        Ok(shared)
+    }
+
+    /// Frees the segments no receiver has to read anymore
+    fn collect(&mut self) {
+        self.segments
+            .retain_mut(|shmem| pending(shmem).load(Ordering::Acquire) != 0);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use libafl_bolts::shmem::{ShMemProvider, StdShMemProvider};
+
+    use super::{SharedEventPool, SharedEventRef};
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_shared_event_roundtrip() {
+        let mut provider = StdShMemProvider::new().unwrap();
+        let mut pool = SharedEventPool::new(16).with_max_segments(2);
+        assert!(!pool.should_share(8));
+        assert!(pool.should_share(256));
+
+        let event_bytes = (0..=255).collect::<Vec<u8>>();
+        let shared = pool.publish(&mut provider, &event_bytes).unwrap();
+        let msg = postcard::to_allocvec(&shared).unwrap();
+        let shared = SharedEventRef::from_msg(&msg).unwrap();
+
+        // The broker peeks, and forwards the reference to two clients
+        let peeked = shared
+            .peek(&mut provider, |bytes| Ok(bytes.to_vec()))
+            .unwrap();
+        assert_eq!(peeked, event_bytes);
+        shared.forward(&mut provider, 2).unwrap();
+        assert!(pool.should_share(256));
+        assert_eq!(pool.live_segments(), 1);
+
+        for _ in 0..2 {
+            let read = shared
+                .read(&mut provider, |bytes| Ok(bytes.to_vec()))
+                .unwrap();
+            assert_eq!(read, event_bytes);
+        }
+        assert!(pool.should_share(256));
+        assert_eq!(pool.live_segments(), 0);
+
+        // Pending segments are never freed, once too many are alive the events are not shared anymore
+        pool.publish(&mut provider, &event_bytes).unwrap();
+        pool.publish(&mut provider, &event_bytes).unwrap();
+        assert!(!pool.should_share(256));
+    }
+}
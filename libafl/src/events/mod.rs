@@ -21,6 +21,8 @@ pub use llmp::*;
 pub mod tcp;
 
 pub mod broker_hooks;
+#[cfg(feature = "control_server")]
+pub mod control;
 use alloc::{borrow::Cow, boxed::Box, string::String, vec::Vec};
 use core::{
     fmt,
@@ -39,6 +41,7 @@ use libafl_bolts::os::unix_signals::{siginfo_t, ucontext_t, Signal, SignalHandle
 use libafl_bolts::os::CTRL_C_EXIT;
 use libafl_bolts::{
     current_time,
+    os::ProcessMetrics,
     tuples::{Handle, MatchNameRef},
     ClientId,
 };
@@ -49,11 +52,12 @@ use uuid::Uuid;
 #[cfg(feature = "introspection")]
 use crate::state::HasClientPerfMonitor;
 use crate::{
+    corpus::GlobalTestcaseId,
     executors::ExitKind,
     inputs::Input,
     monitors::UserStats,
     observers::ObserversTuple,
-    state::{HasExecutions, HasLastReportTime, State},
+    state::{HasExecutions, HasLastReportTime, RuntimeConfig, State, Stoppable},
     Error, HasMetadata,
 };
 #[cfg(feature = "scalability_introspection")]
@@ -166,6 +170,118 @@ pub enum CustomBufEventResult {
     Next,
 }
 
+/// A command injected by an operator, usually via the broker, see [`Event::Command`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventCommand {
+    /// Stop fuzzing (but keep processing events) until [`EventCommand::Resume`] is received
+    Pause,
+    /// Continue fuzzing after an [`EventCommand::Pause`]
+    Resume,
+    /// Stop the fuzzer gracefully
+    Shutdown,
+    /// Reload the configuration, see [`CommandMetadata::take_reload_request`]
+    ReloadConfig,
+}
+
+impl fmt::Display for EventCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventCommand::Pause => write!(f, "Pause"),
+            EventCommand::Resume => write!(f, "Resume"),
+            EventCommand::Shutdown => write!(f, "Shutdown"),
+            EventCommand::ReloadConfig => write!(f, "ReloadConfig"),
+        }
+    }
+}
+
+impl EventCommand {
+    /// Apply this command to the state of a client
+    pub fn apply<S>(self, state: &mut S)
+    where
+        S: HasMetadata + Stoppable,
+    {
+        if self == EventCommand::Shutdown {
+            state.request_stop();
+        }
+        state
+            .metadata_or_insert_with(CommandMetadata::default)
+            .apply(self);
+    }
+}
+
+/// The [`EventCommand`]s a client received so far
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct CommandMetadata {
+    paused: bool,
+    reload_requested: bool,
+}
+
+libafl_bolts::impl_serdeany!(CommandMetadata);
+
+impl CommandMetadata {
+    /// Update this metadata according to the command
+    pub fn apply(&mut self, command: EventCommand) {
+        match command {
+            EventCommand::Pause => self.paused = true,
+            EventCommand::Resume | EventCommand::Shutdown => self.paused = false,
+            EventCommand::ReloadConfig => self.reload_requested = true,
+        }
+    }
+
+    /// If the client is currently paused
+    #[must_use]
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns `true` once, if a configuration reload was requested since the last call
+    pub fn take_reload_request(&mut self) -> bool {
+        core::mem::take(&mut self.reload_requested)
+    }
+}
+
+/// The default interval to poll for new events while a client is paused, see [`wait_while_paused`]
+pub const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Keep processing events, but don't fuzz, while this client is paused by an [`EventCommand::Pause`].
+///
+/// `poll` runs before each round, e.g. to pick up commands from elsewhere.
+/// Progress is still reported every `monitor_timeout`, so the broker and the restarter know the client is alive.
+pub fn wait_while_paused<E, EM, Z, F>(
+    fuzzer: &mut Z,
+    executor: &mut E,
+    state: &mut EM::State,
+    manager: &mut EM,
+    poll_interval: Duration,
+    monitor_timeout: Duration,
+    mut poll: F,
+) -> Result<(), Error>
+where
+    EM: EventProcessor<E, Z> + ProgressReporter,
+    EM::State: HasMetadata + HasExecutions + HasLastReportTime + Stoppable,
+    F: FnMut(&mut EM::State, &mut EM) -> Result<(), Error>,
+{
+    poll(state, manager)?;
+    while !state.stop_requested()
+        && state
+            .metadata::<CommandMetadata>()
+            .is_ok_and(CommandMetadata::paused)
+    {
+        #[cfg(feature = "std")]
+        std::thread::sleep(poll_interval);
+        manager.maybe_report_progress(state, monitor_timeout)?;
+        manager.process(fuzzer, state, executor)?;
+        poll(state, manager)?;
+    }
+    #[cfg(not(feature = "std"))]
+    let _ = poll_interval;
+    Ok(())
+}
+
 /// Indicate if an event worked or not
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub enum BrokerEventResult {
@@ -289,6 +405,10 @@ where
         time: Duration,
         /// The original sender if, if forwarded
         forward_id: Option<ClientId>,
+        /// The identity of the testcase, the same on all nodes
+        global_id: GlobalTestcaseId,
+        /// The identity of the testcase it was mutated from, if known
+        parent_global_id: Option<GlobalTestcaseId>,
         /// The (multi-machine) node from which the tc is from, if any
         #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
         node_id: Option<NodeId>,
@@ -299,6 +419,8 @@ where
         time: Duration,
         /// The executions of this client
         executions: u64,
+        /// The resource usage of this client, with the `host_metrics` feature
+        host_metrics: Option<ProcessMetrics>,
         /// [`PhantomData`]
         phantom: PhantomData<I>,
     },
@@ -318,6 +440,8 @@ where
         time: Duration,
         /// The executions of this client
         executions: u64,
+        /// The resource usage of this client, with the `host_metrics` feature
+        host_metrics: Option<ProcessMetrics>,
         /// Current performance statistics
         introspection_monitor: Box<ClientPerfMonitor>,
 
@@ -349,6 +473,36 @@ where
     },
     /// Exit gracefully
     Stop,
+    /// A command for all clients, injected by an operator or the broker
+    Command {
+        /// The id of this command, echoed back in the [`Event::CommandAck`]
+        id: u64,
+        /// The command
+        command: EventCommand,
+    },
+    /// A client applied the [`Event::Command`] with the given `id`
+    CommandAck {
+        /// The id of the acknowledged command
+        id: u64,
+        /// The acknowledged command
+        command: EventCommand,
+    },
+    /// New tokens for the dictionary, found by a client, see [`crate::stages::TokenSyncStage`]
+    NewTokens {
+        /// The tokens
+        tokens: Vec<Vec<u8>>,
+    },
+    /// Change parameters of all clients while they are running, see [`crate::state::HasRuntimeConfig`]
+    ConfigUpdate {
+        /// The parameters to change
+        config: RuntimeConfig,
+    },
+    /// A client stopped sending messages and is considered lost by the broker.
+    /// Only handled in the broker, see [`libafl_bolts::llmp::LlmpBrokerInner::set_client_timeout`].
+    ClientLost {
+        /// The lost client
+        client_id: ClientId,
+    },
     /*/// A custom type
     Custom {
         // TODO: Allow custom events
@@ -375,6 +529,11 @@ where
                 sender_id: _, /*custom_event} => custom_event.name()*/
             } => "todo",*/
             Event::Stop => "Stop",
+            Event::Command { .. } => "Command",
+            Event::CommandAck { .. } => "CommandAck",
+            Event::NewTokens { .. } => "NewTokens",
+            Event::ConfigUpdate { .. } => "ConfigUpdate",
+            Event::ClientLost { .. } => "ClientLost",
         }
     }
 
@@ -392,6 +551,11 @@ where
             Event::Log { .. } => Cow::Borrowed("Log"),
             Event::CustomBuf { .. } => Cow::Borrowed("CustomBuf"),
             Event::Stop => Cow::Borrowed("Stop"),
+            Event::Command { command, .. } => Cow::Owned(format!("Command {command}")),
+            Event::CommandAck { command, .. } => Cow::Owned(format!("CommandAck {command}")),
+            Event::NewTokens { tokens } => Cow::Owned(format!("NewTokens ({})", tokens.len())),
+            Event::ConfigUpdate { config } => Cow::Owned(format!("ConfigUpdate {config:?}")),
+            Event::ClientLost { client_id } => Cow::Owned(format!("ClientLost {client_id:?}")),
             /*Event::Custom {
                 sender_id: _, /*custom_event} => custom_event.name()*/
             } => "todo",*/
@@ -455,6 +619,20 @@ pub trait EventFirer: UsesState {
     fn should_send(&self) -> bool;
 }
 
+/// The resource usage of this process, sent along with the heartbeats with the `host_metrics` feature
+fn host_metrics() -> Option<ProcessMetrics> {
+    #[cfg(all(unix, feature = "host_metrics"))]
+    {
+        ProcessMetrics::current()
+            .map_err(|err| log::debug!("Failed to collect the host metrics: {err}"))
+            .ok()
+    }
+    #[cfg(not(all(unix, feature = "host_metrics")))]
+    {
+        None
+    }
+}
+
 /// [`ProgressReporter`] report progress to the broker.
 pub trait ProgressReporter: EventFirer
 where
@@ -487,6 +665,7 @@ where
     fn report_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
         let executions = *state.executions();
         let cur = current_time();
+        let host_metrics = host_metrics();
 
         // Default no introspection implmentation
         #[cfg(not(feature = "introspection"))]
@@ -495,6 +674,7 @@ where
             Event::UpdateExecStats {
                 executions,
                 time: cur,
+                host_metrics,
                 phantom: PhantomData,
             },
         )?;
@@ -513,6 +693,7 @@ where
                 Event::UpdatePerfMonitor {
                     executions,
                     time: cur,
+                    host_metrics,
                     introspection_monitor: Box::new(state.introspection_monitor().clone()),
                     phantom: PhantomData,
                 },
@@ -948,6 +1129,7 @@ mod tests {
     use tuple_list::tuple_list_type;
 
     use crate::{
+        corpus::GlobalTestcaseId,
         events::{Event, EventConfig},
         executors::ExitKind,
         inputs::bytes::BytesInput,
@@ -966,6 +1148,7 @@ mod tests {
         let observers_buf = postcard::to_allocvec(&map).unwrap();
 
         let i = BytesInput::new(vec![0]);
+        let global_id = GlobalTestcaseId::of(&i).unwrap();
         let e = Event::NewTestcase {
             input: i,
             observers_buf: Some(observers_buf),
@@ -974,6 +1157,8 @@ mod tests {
             client_config: EventConfig::AlwaysUnique,
             time: current_time(),
             forward_id: None,
+            global_id,
+            parent_global_id: None,
             #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
             node_id: None,
         };
@@ -18,7 +18,12 @@ use libafl_bolts::os::unix_signals::setup_signal_handler;
 use libafl_bolts::os::{fork, ForkResult};
 use libafl_bolts::ClientId;
 #[cfg(feature = "std")]
-use libafl_bolts::{os::CTRL_C_EXIT, shmem::ShMemProvider, staterestore::StateRestorer};
+use libafl_bolts::{
+    codec::{Codec, PostcardCodec},
+    os::CTRL_C_EXIT,
+    shmem::ShMemProvider,
+    staterestore::{DeltaSerialize, StateRestorer},
+};
 #[cfg(feature = "std")]
 use serde::{de::DeserializeOwned, Serialize};
 
@@ -32,7 +37,8 @@ use crate::{
     },
     inputs::UsesInput,
     monitors::Monitor,
-    state::{HasExecutions, HasLastReportTime, State, Stoppable, UsesState},
+    stages::import_tokens,
+    state::{HasExecutions, HasLastReportTime, HasRuntimeConfig, State, Stoppable, UsesState},
     Error, HasMetadata,
 };
 #[cfg(feature = "std")]
@@ -114,7 +120,7 @@ where
 impl<E, MT, S, Z> EventProcessor<E, Z> for SimpleEventManager<MT, S>
 where
     MT: Monitor,
-    S: State,
+    S: State + HasMetadata,
 {
     fn process(
         &mut self,
@@ -217,13 +223,19 @@ where
                 Ok(BrokerEventResult::Handled)
             }
             Event::UpdateExecStats {
-                time, executions, ..
+                time,
+                executions,
+                host_metrics,
+                ..
             } => {
                 // TODO: The monitor buffer should be added on client add.
                 monitor.client_stats_insert(ClientId(0));
                 let client = monitor.client_stats_mut_for(ClientId(0));
 
                 client.update_executions(*executions, *time);
+                if let Some(host_metrics) = host_metrics {
+                    client.update_host_metrics(*host_metrics, *time);
+                }
 
                 monitor.display(event.name(), ClientId(0));
                 Ok(BrokerEventResult::Handled)
@@ -242,12 +254,16 @@ where
                 time,
                 executions,
                 introspection_monitor,
+                host_metrics,
                 ..
             } => {
                 // TODO: The monitor buffer should be added on client add.
                 monitor.client_stats_insert(ClientId(0));
                 let client = monitor.client_stats_mut_for(ClientId(0));
                 client.update_executions(*executions, *time);
+                if let Some(host_metrics) = host_metrics {
+                    client.update_host_metrics(*host_metrics, *time);
+                }
                 client.update_introspection_monitor((**introspection_monitor).clone());
                 monitor.display(event.name(), ClientId(0));
                 Ok(BrokerEventResult::Handled)
@@ -270,13 +286,24 @@ where
                 Ok(BrokerEventResult::Handled)
             }
             Event::CustomBuf { .. } => Ok(BrokerEventResult::Forward),
-            Event::Stop => Ok(BrokerEventResult::Forward),
+            Event::Stop
+            | Event::Command { .. }
+            | Event::NewTokens { .. }
+            | Event::ConfigUpdate { .. } => Ok(BrokerEventResult::Forward),
+            Event::CommandAck { id, command } => {
+                log::info!("Client acknowledged command {command} (id {id})");
+                Ok(BrokerEventResult::Handled)
+            }
+            Event::ClientLost { .. } => Ok(BrokerEventResult::Handled),
         }
     }
 
     // Handle arriving events in the client
     #[allow(clippy::needless_pass_by_value, clippy::unused_self)]
-    fn handle_in_client(&mut self, state: &mut S, event: Event<S::Input>) -> Result<(), Error> {
+    fn handle_in_client(&mut self, state: &mut S, event: Event<S::Input>) -> Result<(), Error>
+    where
+        S: HasMetadata,
+    {
         match event {
             Event::CustomBuf { buf, tag } => {
                 for handler in &mut self.custom_buf_handlers {
@@ -288,6 +315,18 @@ where
                 state.request_stop();
                 Ok(())
             }
+            Event::Command { command, .. } => {
+                command.apply(state);
+                Ok(())
+            }
+            Event::NewTokens { tokens } => {
+                import_tokens(state, &tokens);
+                Ok(())
+            }
+            Event::ConfigUpdate { config } => {
+                state.apply_runtime_config(&config);
+                Ok(())
+            }
             _ => Err(Error::unknown(format!(
                 "Received illegal message that message should not have arrived: {event:?}."
             ))),
@@ -300,10 +339,12 @@ where
 /// The [`SimpleRestartingEventManager`] is a combination of a
 /// `restarter` and `runner`, that can be used on systems both with and without `fork` support. The
 /// `restarter` will start a new process each time the child crashes or times out.
+///
+/// The state is serialized with the [`Codec`] `C`, see [`SimpleRestartingEventManager::launch_with_codec`].
 #[cfg(feature = "std")]
 #[allow(clippy::default_trait_access)]
 #[derive(Debug)]
-pub struct SimpleRestartingEventManager<MT, S, SP>
+pub struct SimpleRestartingEventManager<MT, S, SP, C = PostcardCodec>
 where
     S: UsesInput + Stoppable,
     SP: ShMemProvider, //CE: CustomEvent<I, OT>,
@@ -311,12 +352,15 @@ where
     /// The actual simple event mgr
     simple_event_mgr: SimpleEventManager<MT, S>,
     /// [`StateRestorer`] for restarts
-    staterestorer: StateRestorer<SP>,
+    staterestorer: StateRestorer<SP, C>,
+    /// Save the state as a delta to a base snapshot, see [`StateRestorer::save_delta`]
+    delta_snapshots: bool,
 }
 
 #[cfg(feature = "std")]
-impl<MT, S, SP> UsesState for SimpleRestartingEventManager<MT, S, SP>
+impl<MT, S, SP, C> UsesState for SimpleRestartingEventManager<MT, S, SP, C>
 where
+    C: Codec,
     S: State,
     SP: ShMemProvider,
 {
@@ -324,8 +368,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<MT, S, SP> EventFirer for SimpleRestartingEventManager<MT, S, SP>
+impl<MT, S, SP, C> EventFirer for SimpleRestartingEventManager<MT, S, SP, C>
 where
+    C: Codec,
     MT: Monitor,
     S: State,
     SP: ShMemProvider,
@@ -344,8 +389,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<MT, S, SP> EventRestarter for SimpleRestartingEventManager<MT, S, SP>
+impl<MT, S, SP, C> EventRestarter for SimpleRestartingEventManager<MT, S, SP, C>
 where
+    C: Codec,
     MT: Monitor,
     S: State,
     SP: ShMemProvider,
@@ -356,11 +402,14 @@ where
 
         // First, reset the page to 0 so the next iteration can read read from the beginning of this page
         self.staterestorer.reset();
-        self.staterestorer.save(&(
-            state,
-            self.simple_event_mgr.monitor.start_time(),
-            self.simple_event_mgr.monitor.client_stats(),
-        ))
+        let monitor = &self.simple_event_mgr.monitor;
+        if self.delta_snapshots {
+            self.staterestorer
+                .save_delta(Some(state), &(monitor.start_time(), monitor.client_stats()))
+        } else {
+            self.staterestorer
+                .save(&(state, monitor.start_time(), monitor.client_stats()))
+        }
     }
 
     fn send_exiting(&mut self) -> Result<(), Error> {
@@ -370,10 +419,11 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<E, MT, S, SP, Z> EventProcessor<E, Z> for SimpleRestartingEventManager<MT, S, SP>
+impl<E, MT, S, SP, Z, C> EventProcessor<E, Z> for SimpleRestartingEventManager<MT, S, SP, C>
 where
+    C: Codec,
     MT: Monitor,
-    S: State + HasExecutions,
+    S: State + HasExecutions + HasMetadata,
     SP: ShMemProvider,
 {
     fn process(
@@ -390,8 +440,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<E, MT, S, SP, Z> EventManager<E, Z> for SimpleRestartingEventManager<MT, S, SP>
+impl<E, MT, S, SP, Z, C> EventManager<E, Z> for SimpleRestartingEventManager<MT, S, SP, C>
 where
+    C: Codec,
     MT: Monitor,
     S: State + HasExecutions + HasMetadata + HasLastReportTime + Serialize,
     SP: ShMemProvider,
@@ -399,8 +450,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<MT, S, SP> HasCustomBufHandlers for SimpleRestartingEventManager<MT, S, SP>
+impl<MT, S, SP, C> HasCustomBufHandlers for SimpleRestartingEventManager<MT, S, SP, C>
 where
+    C: Codec,
     MT: Monitor,
     S: State,
     SP: ShMemProvider,
@@ -414,8 +466,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<MT, S, SP> ProgressReporter for SimpleRestartingEventManager<MT, S, SP>
+impl<MT, S, SP, C> ProgressReporter for SimpleRestartingEventManager<MT, S, SP, C>
 where
+    C: Codec,
     MT: Monitor,
     S: State + HasExecutions + HasMetadata + HasLastReportTime,
     SP: ShMemProvider,
@@ -423,8 +476,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<MT, S, SP> HasEventManagerId for SimpleRestartingEventManager<MT, S, SP>
+impl<MT, S, SP, C> HasEventManagerId for SimpleRestartingEventManager<MT, S, SP, C>
 where
+    C: Codec,
     MT: Monitor,
     S: UsesInput + Stoppable,
     SP: ShMemProvider,
@@ -435,39 +489,91 @@ where
 }
 
 #[cfg(feature = "std")]
-#[allow(clippy::type_complexity, clippy::too_many_lines)]
-impl<MT, S, SP> SimpleRestartingEventManager<MT, S, SP>
+impl<MT, S, SP, C> SimpleRestartingEventManager<MT, S, SP, C>
 where
+    C: Codec,
     S: UsesInput + Stoppable,
     SP: ShMemProvider,
     MT: Monitor, //TODO CE: CustomEvent,
 {
     /// Creates a new [`SimpleEventManager`].
-    fn launched(monitor: MT, staterestorer: StateRestorer<SP>) -> Self {
+    fn launched(monitor: MT, staterestorer: StateRestorer<SP, C>, delta_snapshots: bool) -> Self {
         Self {
             staterestorer,
             simple_event_mgr: SimpleEventManager::new(monitor),
+            delta_snapshots,
         }
     }
+}
 
+#[cfg(feature = "std")]
+#[allow(clippy::type_complexity, clippy::too_many_lines)]
+impl<MT, S, SP> SimpleRestartingEventManager<MT, S, SP>
+where
+    S: UsesInput + Stoppable,
+    SP: ShMemProvider,
+    MT: Monitor,
+{
     /// Launch the simple restarting manager.
     /// This [`EventManager`] is simple and single threaded,
     /// but can still used shared maps to recover from crashes and timeouts.
+    pub fn launch(monitor: MT, shmem_provider: &mut SP) -> Result<(Option<S>, Self), Error>
+    where
+        S: DeserializeOwned + Serialize + DeltaSerialize + HasCorpus + HasSolutions,
+        MT: Debug,
+    {
+        Self::launch_with_codec(monitor, shmem_provider)
+    }
+
+    /// Launch the simple restarting manager, serializing the state for the next run with the [`Codec`] `C`
+    pub fn launch_with_codec<C>(
+        monitor: MT,
+        shmem_provider: &mut SP,
+    ) -> Result<(Option<S>, SimpleRestartingEventManager<MT, S, SP, C>), Error>
+    where
+        C: Codec,
+        S: DeserializeOwned + Serialize + DeltaSerialize + HasCorpus + HasSolutions,
+        MT: Debug,
+    {
+        Self::launch_inner(monitor, shmem_provider, false)
+    }
+
+    /// Launch the simple restarting manager, serializing the state for the next run with the [`Codec`] `C`
+    /// as a delta to a base snapshot kept in a tmpfile, see [`StateRestorer::save_delta`].
+    ///
+    /// With a large corpus, restarts then only write the inputs added since the base snapshot.
+    pub fn launch_with_delta_snapshots<C>(
+        monitor: MT,
+        shmem_provider: &mut SP,
+    ) -> Result<(Option<S>, SimpleRestartingEventManager<MT, S, SP, C>), Error>
+    where
+        C: Codec,
+        S: DeserializeOwned + Serialize + DeltaSerialize + HasCorpus + HasSolutions,
+        MT: Debug,
+    {
+        Self::launch_inner(monitor, shmem_provider, true)
+    }
+
     #[allow(clippy::similar_names)]
-    pub fn launch(mut monitor: MT, shmem_provider: &mut SP) -> Result<(Option<S>, Self), Error>
+    fn launch_inner<C>(
+        mut monitor: MT,
+        shmem_provider: &mut SP,
+        delta_snapshots: bool,
+    ) -> Result<(Option<S>, SimpleRestartingEventManager<MT, S, SP, C>), Error>
     where
-        S: DeserializeOwned + Serialize + HasCorpus + HasSolutions,
+        C: Codec,
+        S: DeserializeOwned + Serialize + DeltaSerialize + HasCorpus + HasSolutions,
         MT: Debug,
     {
         // We start ourself as child process to actually fuzz
         let mut staterestorer = if std::env::var(_ENV_FUZZER_SENDER).is_err() {
             // First, create a place to store state in, for restarts.
             #[cfg(unix)]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP, C> =
+                StateRestorer::new(shmem_provider.new_shmem(256 * 1024 * 1024)?).with_codec();
             #[cfg(not(unix))]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP, C> =
+                StateRestorer::new(shmem_provider.new_shmem(256 * 1024 * 1024)?).with_codec();
 
             //let staterestorer = { LlmpSender::new(shmem_provider.clone(), 0, false)? };
             staterestorer.write_to_env(_ENV_FUZZER_SENDER)?;
@@ -537,7 +643,7 @@ where
             // We are the newly started fuzzing instance (i.e. on Windows), first, connect to our own restore map.
             // We get here *only on Windows*, if we were started by a restarting fuzzer.
             // A staterestorer and a receiver for single communication
-            StateRestorer::from_env(shmem_provider, _ENV_FUZZER_SENDER)?
+            StateRestorer::from_env(shmem_provider, _ENV_FUZZER_SENDER)?.with_codec()
         };
 
         // At this point we are the fuzzer *NOT* the restarter.
@@ -549,13 +655,22 @@ where
         }
 
         // If we're restarting, deserialize the old state.
-        let (state, mgr) = match staterestorer.restore::<(S, Duration, Vec<ClientStats>)>()? {
+        let restored = if delta_snapshots {
+            staterestorer
+                .restore_delta::<S, (Duration, Vec<ClientStats>)>()?
+                .and_then(|(state, (start_time, clients_stats))| {
+                    state.map(|state| (state, start_time, clients_stats))
+                })
+        } else {
+            staterestorer.restore::<(S, Duration, Vec<ClientStats>)>()?
+        };
+        let (state, mgr) = match restored {
             None => {
                 log::info!("First run. Let's set it all up");
                 // Mgr to send and receive msgs from/to all other fuzzer instances
                 (
                     None,
-                    SimpleRestartingEventManager::launched(monitor, staterestorer),
+                    SimpleRestartingEventManager::launched(monitor, staterestorer, delta_snapshots),
                 )
             }
             // Restoring from a previous run, deserialize state and corpus.
@@ -570,7 +685,7 @@ where
 
                 (
                     Some(state),
-                    SimpleRestartingEventManager::launched(monitor, staterestorer),
+                    SimpleRestartingEventManager::launched(monitor, staterestorer, delta_snapshots),
                 )
             }
         };
@@ -584,3 +699,49 @@ where
         Ok((state, mgr))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{
+        events::{Event, EventFirer, EventProcessor, SimpleEventManager},
+        inputs::BytesInput,
+        monitors::NopMonitor,
+        schedulers::powersched::{BaseSchedule, PowerSchedule, SchedulerMetadata},
+        state::{HasRuntimeConfig, RuntimeConfig, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_config_update() {
+        let mut state = StdState::nop::<BytesInput>().unwrap();
+        state.add_metadata(SchedulerMetadata::new(Some(PowerSchedule::fast())));
+        let mut mgr = SimpleEventManager::new(NopMonitor::new());
+
+        mgr.fire(
+            &mut state,
+            Event::ConfigUpdate {
+                config: RuntimeConfig {
+                    timeout: Some(Duration::from_millis(200)),
+                    power_schedule: Some(Some(BaseSchedule::EXPLORE)),
+                    ..RuntimeConfig::default()
+                },
+            },
+        )
+        .unwrap();
+        // The update is only applied once the event is processed
+        assert_eq!(state.runtime_config().timeout, None);
+        assert_eq!(mgr.process(&mut (), &mut state, &mut ()).unwrap(), 1);
+
+        assert_eq!(
+            state.runtime_config().timeout,
+            Some(Duration::from_millis(200))
+        );
+        let strat = state.metadata::<SchedulerMetadata>().unwrap().strat();
+        assert_eq!(
+            strat.map(|strat| *strat.base()),
+            Some(BaseSchedule::EXPLORE)
+        );
+    }
+}
@@ -28,9 +28,13 @@ use libafl_bolts::os::unix_signals::setup_signal_handler;
 use libafl_bolts::os::CTRL_C_EXIT;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 use libafl_bolts::os::{fork, ForkResult};
-use libafl_bolts::{shmem::ShMemProvider, tuples::tuple_list, ClientId};
 #[cfg(feature = "std")]
-use libafl_bolts::{shmem::StdShMemProvider, staterestore::StateRestorer};
+use libafl_bolts::{
+    codec::{Codec, PostcardCodec},
+    shmem::StdShMemProvider,
+    staterestore::StateRestorer,
+};
+use libafl_bolts::{shmem::ShMemProvider, tuples::tuple_list, ClientId};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -44,6 +48,10 @@ use super::{CustomBufEventResult, CustomBufHandlerFn};
 #[cfg(all(unix, feature = "std", not(miri)))]
 use crate::events::EVENTMGR_SIGHANDLER_STATE;
 use crate::{
+    corpus::{
+        global_id::{clear_remote_testcase, expect_remote_testcase, is_known_global_id},
+        GlobalIdMetadata,
+    },
     events::{
         BrokerEventResult, Event, EventConfig, EventFirer, EventManager, EventManagerHooksTuple,
         EventManagerId, EventProcessor, EventRestarter, HasCustomBufHandlers, HasEventManagerId,
@@ -54,7 +62,8 @@ use crate::{
     inputs::{Input, UsesInput},
     monitors::Monitor,
     observers::ObserversTuple,
-    state::{HasExecutions, HasImported, HasLastReportTime, State, UsesState},
+    stages::import_tokens,
+    state::{HasExecutions, HasImported, HasLastReportTime, HasRuntimeConfig, State, UsesState},
     Error, HasMetadata,
 };
 
@@ -338,12 +347,16 @@ where
             Event::UpdateExecStats {
                 time,
                 executions,
+                host_metrics,
                 phantom: _,
             } => {
                 // TODO: The monitor buffer should be added on client add.
                 monitor.client_stats_insert(client_id);
                 let client = monitor.client_stats_mut_for(client_id);
                 client.update_executions(*executions, *time);
+                if let Some(host_metrics) = host_metrics {
+                    client.update_host_metrics(*host_metrics, *time);
+                }
                 monitor.display(event.name(), client_id);
                 Ok(BrokerEventResult::Handled)
             }
@@ -364,6 +377,7 @@ where
                 time,
                 executions,
                 introspection_monitor,
+                host_metrics,
                 phantom: _,
             } => {
                 // TODO: The monitor buffer should be added on client add.
@@ -374,6 +388,9 @@ where
 
                 // Update the normal monitor for this client
                 client.update_executions(*executions, *time);
+                if let Some(host_metrics) = host_metrics {
+                    client.update_host_metrics(*host_metrics, *time);
+                }
 
                 // Update the performance monitor for this client
                 client.update_introspection_monitor((**introspection_monitor).clone());
@@ -401,8 +418,22 @@ where
                 log::log!((*severity_level).into(), "{message}");
                 Ok(BrokerEventResult::Handled)
             }
-            Event::CustomBuf { .. } | Event::Stop => Ok(BrokerEventResult::Forward),
-            //_ => Ok(BrokerEventResult::Forward),
+            Event::CustomBuf { .. }
+            | Event::Stop
+            | Event::Command { .. }
+            | Event::NewTokens { .. }
+            | Event::ConfigUpdate { .. } => Ok(BrokerEventResult::Forward),
+            Event::CommandAck { id, command } => {
+                log::info!("Client {client_id:?} acknowledged command {command} (id {id})");
+                Ok(BrokerEventResult::Handled)
+            }
+            Event::ClientLost { client_id } => {
+                if let Some(client) = monitor.client_stats_mut().get_mut(client_id.0 as usize) {
+                    client.enabled = false;
+                }
+                monitor.display(event.name(), *client_id);
+                Ok(BrokerEventResult::Handled)
+            }
         }
     }
 }
@@ -613,16 +644,30 @@ where
             return Ok(());
         }
         match event {
+            Event::NewTestcase { global_id, .. } if is_known_global_id(state, global_id) => {
+                log::info!("Received Testcase {global_id} is in the corpus already, discarding");
+            }
             Event::NewTestcase {
                 input,
                 client_config,
                 exit_kind,
                 observers_buf,
                 forward_id,
+                global_id,
+                parent_global_id,
                 ..
             } => {
                 log::info!("Received new Testcase from {client_id:?} ({client_config:?}, forward {forward_id:?})");
 
+                expect_remote_testcase(
+                    state,
+                    GlobalIdMetadata {
+                        id: global_id,
+                        origin: Some(forward_id.unwrap_or(client_id)),
+                        parent: parent_global_id,
+                    },
+                );
+
                 let _res = if client_config.match_with(&self.configuration)
                     && observers_buf.is_some()
                 {
@@ -641,6 +686,7 @@ where
                     fuzzer
                         .evaluate_input_with_observers::<E>(state, executor, self, input, false)?
                 };
+                clear_remote_testcase(state);
                 if let Some(item) = _res.1 {
                     *state.imported_mut() += 1;
                     log::info!("Added received Testcase as item #{item}");
@@ -656,6 +702,22 @@ where
             Event::Stop => {
                 state.request_stop();
             }
+            Event::Command { id, command } => {
+                log::info!("Received command {command} (id {id}) from {client_id:?}");
+                command.apply(state);
+                self.fire(state, Event::CommandAck { id, command })?;
+            }
+            Event::NewTokens { tokens } => {
+                let added = import_tokens(state, &tokens);
+                log::debug!(
+                    "Received {} tokens from {client_id:?}, {added} new",
+                    tokens.len()
+                );
+            }
+            Event::ConfigUpdate { config } => {
+                log::info!("Received config update {config:?} from {client_id:?}");
+                state.apply_runtime_config(&config);
+            }
             _ => {
                 return Err(Error::unknown(format!(
                     "Received illegal message that message should not have arrived: {:?}.",
@@ -860,9 +922,11 @@ where
 }
 
 /// A manager that can restart on the fly, storing states in-between (in `on_restart`)
+///
+/// The state is serialized with the [`Codec`] `C`, see [`TcpRestartingMgr::launch_with_codec`].
 #[cfg(feature = "std")]
 #[derive(Debug)]
-pub struct TcpRestartingEventManager<EMH, S, SP>
+pub struct TcpRestartingEventManager<EMH, S, SP, C = PostcardCodec>
 where
     EMH: EventManagerHooksTuple<S>,
     S: State,
@@ -872,14 +936,17 @@ where
     /// The embedded TCP event manager
     tcp_mgr: TcpEventManager<EMH, S>,
     /// The staterestorer to serialize the state for the next runner
-    staterestorer: StateRestorer<SP>,
+    staterestorer: StateRestorer<SP, C>,
     /// Decide if the state restorer must save the serialized state
     save_state: bool,
+    /// Save the state as a delta to a base snapshot, see [`StateRestorer::save_delta`]
+    delta_snapshots: bool,
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> UsesState for TcpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> UsesState for TcpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     EMH: EventManagerHooksTuple<S>,
     S: State,
     SP: ShMemProvider + 'static,
@@ -888,8 +955,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> ProgressReporter for TcpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> ProgressReporter for TcpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     EMH: EventManagerHooksTuple<S>,
     S: State + HasExecutions + HasMetadata + HasLastReportTime,
     SP: ShMemProvider,
@@ -897,8 +965,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> EventFirer for TcpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> EventFirer for TcpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     EMH: EventManagerHooksTuple<S>,
     SP: ShMemProvider,
     S: State,
@@ -923,8 +992,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> EventRestarter for TcpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> EventRestarter for TcpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     EMH: EventManagerHooksTuple<S>,
     S: State + HasExecutions,
     SP: ShMemProvider,
@@ -943,11 +1013,18 @@ where
 
         // First, reset the page to 0 so the next iteration can read read from the beginning of this page
         self.staterestorer.reset();
-        self.staterestorer.save(&if self.save_state {
-            Some((state, self.tcp_mgr.client_id))
+        if self.delta_snapshots {
+            self.staterestorer.save_delta(
+                if self.save_state { Some(state) } else { None },
+                &self.tcp_mgr.client_id,
+            )?;
         } else {
-            None
-        })?;
+            self.staterestorer.save(&if self.save_state {
+                Some((state, self.tcp_mgr.client_id))
+            } else {
+                None
+            })?;
+        }
 
         self.await_restart_safe();
         Ok(())
@@ -962,8 +1039,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<E, EMH, S, SP, Z> EventProcessor<E, Z> for TcpRestartingEventManager<EMH, S, SP>
+impl<E, EMH, S, SP, Z, C> EventProcessor<E, Z> for TcpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     E: HasObservers + Executor<TcpEventManager<EMH, S>, Z, State = S>,
     for<'a> E::Observers: Deserialize<'a>,
     E::Observers: ObserversTuple<S::Input, S> + Serialize,
@@ -983,8 +1061,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<E, EMH, S, SP, Z> EventManager<E, Z> for TcpRestartingEventManager<EMH, S, SP>
+impl<E, EMH, S, SP, Z, C> EventManager<E, Z> for TcpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     E: HasObservers + Executor<TcpEventManager<EMH, S>, Z, State = S>,
     E::Observers: ObserversTuple<S::Input, S> + Serialize,
     for<'a> E::Observers: Deserialize<'a>,
@@ -997,8 +1076,9 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> HasEventManagerId for TcpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> HasEventManagerId for TcpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     EMH: EventManagerHooksTuple<S>,
     S: State,
     SP: ShMemProvider + 'static,
@@ -1015,42 +1095,54 @@ const _ENV_FUZZER_RECEIVER: &str = "_AFL_ENV_FUZZER_RECEIVER";
 const _ENV_FUZZER_BROKER_CLIENT_INITIAL: &str = "_AFL_ENV_FUZZER_BROKER_CLIENT";
 
 #[cfg(feature = "std")]
-impl<EMH, S, SP> TcpRestartingEventManager<EMH, S, SP>
+impl<EMH, S, SP, C> TcpRestartingEventManager<EMH, S, SP, C>
 where
+    C: Codec,
     EMH: EventManagerHooksTuple<S>,
     S: State,
     SP: ShMemProvider + 'static,
     //CE: CustomEvent<I>,
 {
     /// Create a new runner, the executed child doing the actual fuzzing.
-    pub fn new(tcp_mgr: TcpEventManager<EMH, S>, staterestorer: StateRestorer<SP>) -> Self {
+    pub fn new(tcp_mgr: TcpEventManager<EMH, S>, staterestorer: StateRestorer<SP, C>) -> Self {
         Self {
             tcp_mgr,
             staterestorer,
             save_state: true,
+            delta_snapshots: false,
         }
     }
 
     /// Create a new runner specifying if it must save the serialized state on restart.
     pub fn with_save_state(
         tcp_mgr: TcpEventManager<EMH, S>,
-        staterestorer: StateRestorer<SP>,
+        staterestorer: StateRestorer<SP, C>,
         save_state: bool,
     ) -> Self {
         Self {
             tcp_mgr,
             staterestorer,
             save_state,
+            delta_snapshots: false,
         }
     }
 
+    /// Save the state as a delta to a base snapshot kept in a tmpfile on restart, see [`StateRestorer::save_delta`].
+    ///
+    /// The next runner has to restore it with [`StateRestorer::restore_delta`], see [`TcpRestartingMgr`].
+    #[must_use]
+    pub fn with_delta_snapshots(mut self, delta_snapshots: bool) -> Self {
+        self.delta_snapshots = delta_snapshots;
+        self
+    }
+
     /// Get the staterestorer
-    pub fn staterestorer(&self) -> &StateRestorer<SP> {
+    pub fn staterestorer(&self) -> &StateRestorer<SP, C> {
         &self.staterestorer
     }
 
     /// Get the staterestorer (mutable)
-    pub fn staterestorer_mut(&mut self) -> &mut StateRestorer<SP> {
+    pub fn staterestorer_mut(&mut self) -> &mut StateRestorer<SP, C> {
         &mut self.staterestorer
     }
 }
@@ -1144,6 +1236,9 @@ where
     /// Tell the manager to serialize or not the state on restart
     #[builder(default = true)]
     serialize_state: bool,
+    /// Serialize the state as a delta to a base snapshot, see [`TcpRestartingEventManager::with_delta_snapshots`]
+    #[builder(default = false)]
+    delta_snapshots: bool,
     /// The hooks for `handle_in_client`
     hooks: EMH,
     #[builder(setter(skip), default = PhantomData)]
@@ -1161,8 +1256,19 @@ where
 {
     /// Launch the restarting manager
     pub fn launch(&mut self) -> Result<(Option<S>, TcpRestartingEventManager<EMH, S, SP>), Error> {
+        self.launch_with_codec()
+    }
+
+    /// Launch the restarting manager, serializing the state for the next run with the [`Codec`] `C`
+    pub fn launch_with_codec<C>(
+        &mut self,
+    ) -> Result<(Option<S>, TcpRestartingEventManager<EMH, S, SP, C>), Error>
+    where
+        C: Codec,
+    {
         // We start ourself as child process to actually fuzz
-        let (staterestorer, _new_shmem_provider, core_id) = if env::var(_ENV_FUZZER_SENDER).is_err()
+        let (mut staterestorer, _new_shmem_provider, core_id) = if env::var(_ENV_FUZZER_SENDER)
+            .is_err()
         {
             let broker_things = |mut broker: TcpEventBroker<S::Input, MT>, _remote_broker_addr| {
                 if let Some(exit_cleanly_after) = self.exit_cleanly_after {
@@ -1238,12 +1344,12 @@ where
 
             // First, create a channel from the current fuzzer to the next to store state between restarts.
             #[cfg(unix)]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP, C> =
+                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?).with_codec();
 
             #[cfg(not(unix))]
-            let staterestorer: StateRestorer<SP> =
-                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?);
+            let staterestorer: StateRestorer<SP, C> =
+                StateRestorer::new(self.shmem_provider.new_shmem(256 * 1024 * 1024)?).with_codec();
             // Store the information to a map.
             staterestorer.write_to_env(_ENV_FUZZER_SENDER)?;
 
@@ -1317,7 +1423,7 @@ where
             // We get here *only on Windows*, if we were started by a restarting fuzzer.
             // A staterestorer and a receiver for single communication
             (
-                StateRestorer::from_env(&mut self.shmem_provider, _ENV_FUZZER_SENDER)?,
+                StateRestorer::from_env(&mut self.shmem_provider, _ENV_FUZZER_SENDER)?.with_codec(),
                 self.shmem_provider.clone(),
                 None,
             )
@@ -1337,7 +1443,12 @@ where
         }
 
         // If we're restarting, deserialize the old state.
-        let (state, mut mgr) = if let Some((state_opt, this_id)) = staterestorer.restore()? {
+        let restored = if self.delta_snapshots {
+            staterestorer.restore_delta()?
+        } else {
+            staterestorer.restore()?
+        };
+        let (state, mut mgr) = if let Some((state_opt, this_id)) = restored {
             (
                 state_opt,
                 TcpRestartingEventManager::with_save_state(
@@ -1346,7 +1457,8 @@ where
                         .build_on_port(self.broker_port, this_id, self.configuration)?,
                     staterestorer,
                     self.serialize_state,
-                ),
+                )
+                .with_delta_snapshots(self.delta_snapshots),
             )
         } else {
             log::info!("First run. Let's set it all up");
@@ -1365,7 +1477,8 @@ where
                     mgr,
                     staterestorer,
                     self.serialize_state,
-                ),
+                )
+                .with_delta_snapshots(self.delta_snapshots),
             )
         };
         // We reset the staterestorer, the next staterestorer and receiver (after crash) will reuse the page from the initial message.
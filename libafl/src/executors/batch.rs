@@ -0,0 +1,181 @@
+//! Executors running a batch of inputs at once, see [`BatchExecutor`].
+//!
+//! Targets with an expensive setup, like emulators, interpreters with snapshot pools or remote execution services,
+//! amortize it over many inputs. The [`crate::stages::BatchMutationalStage`] mutates a batch of inputs, submits it
+//! and evaluates the observer snapshots of each input afterwards.
+
+use alloc::vec::Vec;
+
+use libafl_bolts::tuples::RefIndexable;
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::UsesInput,
+    observers::ObserversTuple,
+    state::UsesState,
+    Error,
+};
+
+/// The result of a single input of a batch: its [`ExitKind`], and a snapshot of the observers after its execution
+pub type BatchResult<OT> = (ExitKind, OT);
+
+/// An [`Executor`] running a batch of inputs at once.
+///
+/// For each input, the executor calls `pre_exec_all` and `post_exec_all` on the observers and returns a snapshot of
+/// them, so that feedbacks can evaluate every input on its own. Like [`Executor::run_target`], it counts the
+/// executions of the inputs in the state.
+pub trait BatchExecutor<EM, Z>: Executor<EM, Z> + HasObservers
+where
+    EM: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    /// The maximum amount of inputs of a batch, larger batches get split by the caller
+    fn max_batch_size(&self) -> usize;
+
+    /// Runs all `inputs`, returning a [`BatchResult`] per input, in the same order
+    fn run_batch(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        inputs: &[Self::Input],
+    ) -> Result<Vec<BatchResult<Self::Observers>>, Error>;
+}
+
+/// Runs the batches of any [`Executor`] one input after the other, cloning the observers after each execution.
+///
+/// Useful to compare a native [`BatchExecutor`] with a regular one, or to fuzz with a [`BatchExecutor`] stage while
+/// the target has no batched interface yet.
+#[derive(Debug)]
+pub struct SequentialBatchExecutor<E> {
+    executor: E,
+    max_batch_size: usize,
+}
+
+impl<E> SequentialBatchExecutor<E> {
+    /// Wraps the given [`Executor`], running batches of up to `max_batch_size` inputs
+    pub fn new(executor: E, max_batch_size: usize) -> Self {
+        Self {
+            executor,
+            max_batch_size,
+        }
+    }
+
+    /// The wrapped [`Executor`]
+    pub fn inner(&mut self) -> &mut E {
+        &mut self.executor
+    }
+}
+
+impl<E, EM, Z> Executor<EM, Z> for SequentialBatchExecutor<E>
+where
+    E: Executor<EM, Z>,
+    EM: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        self.executor.run_target(fuzzer, state, mgr, input)
+    }
+}
+
+impl<E> UsesState for SequentialBatchExecutor<E>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E> HasObservers for SequentialBatchExecutor<E>
+where
+    E: HasObservers,
+{
+    type Observers = E::Observers;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        self.executor.observers()
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        self.executor.observers_mut()
+    }
+}
+
+impl<E, EM, Z> BatchExecutor<EM, Z> for SequentialBatchExecutor<E>
+where
+    E: Executor<EM, Z> + HasObservers,
+    E::Observers: ObserversTuple<<Self as UsesInput>::Input, <Self as UsesState>::State> + Clone,
+    EM: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    fn run_batch(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        inputs: &[Self::Input],
+    ) -> Result<Vec<BatchResult<Self::Observers>>, Error> {
+        let mut results = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            self.executor.observers_mut().pre_exec_all(state, input)?;
+            let exit_kind = self.executor.run_target(fuzzer, state, mgr, input)?;
+            self.executor
+                .observers_mut()
+                .post_exec_all(state, input, &exit_kind)?;
+            results.push((exit_kind, (*self.executor.observers()).clone()));
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::tuples::tuple_list;
+
+    use super::{BatchExecutor, SequentialBatchExecutor};
+    use crate::{
+        events::NopEventManager,
+        executors::{test::NopExecutor, ExitKind, WithObservers},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        state::{HasExecutions, NopState},
+    };
+
+    #[test]
+    fn test_sequential_batch_executor() {
+        let executor = WithObservers::new(NopExecutor::new(), tuple_list!());
+        let mut executor = SequentialBatchExecutor::new(executor, 2);
+        let mut fuzzer = NopFuzzer::new();
+        let mut state = NopState::new();
+        let mut mgr = NopEventManager::new();
+
+        let inputs = [BytesInput::new(vec![1]), BytesInput::new(vec![2, 3])];
+        let results = executor
+            .run_batch(&mut fuzzer, &mut state, &mut mgr, &inputs)
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|(exit_kind, _)| *exit_kind == ExitKind::Ok));
+        assert_eq!(*state.executions(), 2);
+
+        // The inner executor fails on empty inputs
+        assert!(executor
+            .run_batch(
+                &mut fuzzer,
+                &mut state,
+                &mut mgr,
+                &[BytesInput::new(vec![])]
+            )
+            .is_err());
+    }
+}
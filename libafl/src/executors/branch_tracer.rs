@@ -0,0 +1,119 @@
+//! The [`BranchTracerHook`] approximates the edge coverage of uninstrumented binaries on machines without Intel PT,
+//! from the last branch record or the branch trace store of the CPU.
+//!
+//! Like the `IntelPTHook`, it is a [`PtraceHook`] of the [`crate::executors::PtraceExecutor`],
+//! starting the recording right after the `execve` of the target. The branches are counted as edges in a map,
+//! observed with the observer returned by [`BranchTracerHook::observer`], for a [`crate::feedbacks::MapFeedback`].
+//!
+//! See [`libafl_bolts::perf`] for the precision of each [`BranchSource`]: with [`BranchSource::Lbr`], the map only
+//! holds a sample of the edges, which varies between executions of the same input.
+//!
+//! ```rust,ignore
+//! let hook = BranchTracerHook::new(1 << 16, BranchSource::detect()?);
+//! let edges_observer = HitcountsMapObserver::new(hook.observer("edges"));
+//! let mut executor = PtraceExecutor::builder()
+//!     .program("/path/to/target")
+//!     .arg("@@")
+//!     .build_with_hooks(tuple_list!(edges_observer), tuple_list!(hook))?;
+//! ```
+
+use alloc::vec::Vec;
+use core::slice;
+
+use libafl_bolts::perf::{self, record_edges, BranchSource, BranchTracer};
+
+use crate::{
+    executors::{
+        ptrace::{PtraceHook, Tracee},
+        ExitKind,
+    },
+    observers::StdMapObserver,
+    Error,
+};
+
+/// The default size of the buffer of the [`BranchTracerHook`], 1 MiB
+pub const DEFAULT_BUFFER_SIZE: usize = 1 << 20;
+
+/// A [`PtraceHook`] recording the branches of the target with `perf`, and counting them in an edge coverage map.
+///
+/// Only the target itself is traced, not its children.
+#[derive(Debug)]
+pub struct BranchTracerHook {
+    tracer: Option<BranchTracer>,
+    checked: bool,
+    source: BranchSource,
+    buffer_size: usize,
+    branches: Vec<(u64, u64)>,
+    map_ptr: *mut u8,
+    map_size: usize,
+}
+
+impl BranchTracerHook {
+    /// Creates a new [`BranchTracerHook`], with a coverage map of `map_size` entries, e.g. from [`BranchSource::detect`].
+    ///
+    /// The map is allocated once and never freed, so that the observer of the map stays valid.
+    #[must_use]
+    pub fn new(map_size: usize, source: BranchSource) -> Self {
+        let map = Vec::leak(vec![0_u8; map_size]);
+        Self {
+            tracer: None,
+            checked: false,
+            source,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            branches: Vec::new(),
+            map_ptr: map.as_mut_ptr(),
+            map_size,
+        }
+    }
+
+    /// The size of the buffer for the branches of each execution; defaults to [`DEFAULT_BUFFER_SIZE`].
+    ///
+    /// Branches that do not fit are dropped: with [`BranchSource::Bts`], this cuts off longer executions.
+    #[must_use]
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// The source of the branches
+    #[must_use]
+    pub fn source(&self) -> BranchSource {
+        self.source
+    }
+
+    /// A map observer of the edge coverage collected by this hook
+    #[must_use]
+    pub fn observer(&self, name: &'static str) -> StdMapObserver<'static, u8, false> {
+        // # Safety
+        // The map is leaked, it is valid for the rest of the program.
+        // The hook only writes to it after the execution, while the observers do not run.
+        unsafe { StdMapObserver::from_mut_ptr(name, self.map_ptr, self.map_size) }
+    }
+}
+
+impl<I> PtraceHook<I> for BranchTracerHook {
+    fn on_exec(&mut self, tracee: &Tracee, _input: &I) -> Result<(), Error> {
+        if !self.checked {
+            // Fail early, with a readable error
+            perf::check_permissions()?;
+            self.checked = true;
+        }
+        let mut tracer = BranchTracer::new(tracee.pid(), self.source, self.buffer_size)?;
+        tracer.enable()?;
+        self.tracer = Some(tracer);
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _input: &I, _exit_kind: &ExitKind) -> Result<(), Error> {
+        let Some(mut tracer) = self.tracer.take() else {
+            return Ok(());
+        };
+        self.branches.clear();
+        tracer.read_branches(&mut self.branches)?;
+        // # Safety
+        // The map is leaked, and the observers do not run during the execution.
+        let map = unsafe { slice::from_raw_parts_mut(self.map_ptr, self.map_size) };
+        record_edges(&self.branches, map);
+        Ok(())
+    }
+}
@@ -1,4 +1,6 @@
 //! The command executor executes a sub program for each run
+#[cfg(feature = "multipart_inputs")]
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::{
     fmt::{self, Debug, Formatter},
@@ -25,10 +27,12 @@ use libafl_bolts::{
 
 #[cfg(all(feature = "std", unix))]
 use crate::executors::{Executor, ExitKind};
+#[cfg(feature = "multipart_inputs")]
+use crate::inputs::MultipartInput;
 use crate::{
     executors::HasObservers,
     inputs::{HasTargetBytes, UsesInput},
-    observers::{ObserversTuple, StdErrObserver, StdOutObserver},
+    observers::{ExitStatusObserver, ObserversTuple, StdErrObserver, StdOutObserver},
     state::{HasExecutions, State, UsesState},
     std::borrow::ToOwned,
 };
@@ -45,6 +49,11 @@ pub enum InputLocation {
         /// The offset of the argument to mutate
         argnum: usize,
     },
+    /// Deliver the input as the value of an environment variable
+    Env {
+        /// The name of the environment variable
+        key: OsString,
+    },
     /// Deliver input via `StdIn`
     StdIn,
     /// Deliver the input via the specified [`InputFile`]
@@ -66,6 +75,7 @@ pub struct StdCommandConfigurator {
     debug_child: bool,
     stdout_observer: Option<Handle<StdOutObserver>>,
     stderr_observer: Option<Handle<StdErrObserver>>,
+    exit_status_observer: Option<Handle<ExitStatusObserver>>,
     timeout: Duration,
     /// true: input gets delivered via stdink
     input_location: InputLocation,
@@ -73,6 +83,39 @@ pub struct StdCommandConfigurator {
     command: Command,
 }
 
+/// Converts the bytes of an input to an argument or environment variable value.
+///
+/// These can not contain NUL bytes, so the bytes are cut at the first one, like the target would see them
+/// as a C string. Otherwise, spawning the target fails for an ordinary mutant.
+fn os_string_from_bytes(bytes: &[u8]) -> OsString {
+    let bytes = bytes.split(|byte| *byte == 0).next().unwrap_or_default();
+    #[cfg(unix)]
+    {
+        OsStr::from_bytes(bytes).to_owned()
+    }
+    // There is an issue here that the chars on Windows are 16 bit wide.
+    #[cfg(not(unix))]
+    {
+        OsString::from(alloc::string::String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Writes the input to the stdin of the spawned child, ignoring a target that does not read it
+fn write_to_stdin(child: &mut Child, bytes: &[u8]) -> Result<(), Error> {
+    let mut stdin = child.stdin.take().unwrap();
+    if let Err(err) = stdin.write_all(bytes) {
+        if err.kind() != std::io::ErrorKind::BrokenPipe {
+            return Err(err.into());
+        }
+    } else if let Err(err) = stdin.flush() {
+        if err.kind() != std::io::ErrorKind::BrokenPipe {
+            return Err(err.into());
+        }
+    }
+    drop(stdin);
+    Ok(())
+}
+
 impl<I> CommandConfigurator<I> for StdCommandConfigurator
 where
     I: HasTargetBytes,
@@ -85,6 +128,10 @@ where
         self.stderr_observer.clone()
     }
 
+    fn exit_status_observer(&self) -> Option<Handle<ExitStatusObserver>> {
+        self.exit_status_observer.clone()
+    }
+
     fn spawn_child(&mut self, input: &I) -> Result<Child, Error> {
         match &mut self.input_location {
             InputLocation::Arg { argnum } => {
@@ -126,19 +173,14 @@ where
                 }
                 Ok(cmd.spawn()?)
             }
+            InputLocation::Env { key } => {
+                self.command
+                    .env(key, os_string_from_bytes(input.target_bytes().as_slice()));
+                Ok(self.command.spawn()?)
+            }
             InputLocation::StdIn => {
                 let mut handle = self.command.stdin(Stdio::piped()).spawn()?;
-                let mut stdin = handle.stdin.take().unwrap();
-                if let Err(err) = stdin.write_all(input.target_bytes().as_slice()) {
-                    if err.kind() != std::io::ErrorKind::BrokenPipe {
-                        return Err(err.into());
-                    }
-                } else if let Err(err) = stdin.flush() {
-                    if err.kind() != std::io::ErrorKind::BrokenPipe {
-                        return Err(err.into());
-                    }
-                }
-                drop(stdin);
+                write_to_stdin(&mut handle, input.target_bytes().as_slice())?;
                 Ok(handle)
             }
             InputLocation::File { out_file } => {
@@ -233,11 +275,16 @@ where
 
         let mut child = self.configurer.spawn_child(input)?;
 
-        let res = match child
+        let status = child
             .wait_timeout(self.configurer.exec_timeout())
-            .expect("waiting on child failed")
-            .map(|status| status.signal())
-        {
+            .expect("waiting on child failed");
+        if let (Some(h), Some(status)) = (self.configurer.exit_status_observer(), &status) {
+            let mut observers = self.observers_mut();
+            let obs = observers.index_mut(&h);
+            obs.observe_exit_status(status);
+        }
+
+        let res = match status.map(|status| status.signal()) {
             // for reference: https://www.man7.org/linux/man-pages/man7/signal.7.html
             Some(Some(9)) => Ok(ExitKind::Oom),
             Some(Some(_)) => Ok(ExitKind::Crash),
@@ -312,6 +359,7 @@ where
 pub struct CommandExecutorBuilder {
     stdout: Option<Handle<StdOutObserver>>,
     stderr: Option<Handle<StdErrObserver>>,
+    exit_status: Option<Handle<ExitStatusObserver>>,
     debug_child: bool,
     program: Option<OsString>,
     args: Vec<OsString>,
@@ -334,6 +382,7 @@ impl CommandExecutorBuilder {
         CommandExecutorBuilder {
             stdout: None,
             stderr: None,
+            exit_status: None,
             program: None,
             args: vec![],
             input_location: InputLocation::StdIn,
@@ -378,6 +427,15 @@ impl CommandExecutorBuilder {
         self
     }
 
+    /// Sets the input mode to [`InputLocation::Env`].
+    /// During execution, the input will be provided as the value of the environment variable `key`.
+    pub fn arg_input_env<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Self {
+        self.input(InputLocation::Env {
+            key: key.as_ref().to_owned(),
+        });
+        self
+    }
+
     /// Sets the stdout observer
     pub fn stdout_observer(&mut self, stdout: Handle<StdOutObserver>) -> &mut Self {
         self.stdout = Some(stdout);
@@ -390,6 +448,12 @@ impl CommandExecutorBuilder {
         self
     }
 
+    /// Sets the exit status observer
+    pub fn exit_status_observer(&mut self, exit_status: Handle<ExitStatusObserver>) -> &mut Self {
+        self.exit_status = Some(exit_status);
+        self
+    }
+
     /// Sets the input mode to [`InputLocation::File`]
     /// and adds the filename as arg to at the current position.
     /// Uses a default filename.
@@ -492,7 +556,7 @@ impl CommandExecutorBuilder {
             InputLocation::StdIn => {
                 command.stdin(Stdio::piped());
             }
-            InputLocation::File { .. } | InputLocation::Arg { .. } => {
+            InputLocation::File { .. } | InputLocation::Arg { .. } | InputLocation::Env { .. } => {
                 command.stdin(Stdio::null());
             }
         }
@@ -522,6 +586,7 @@ impl CommandExecutorBuilder {
             debug_child: self.debug_child,
             stdout_observer: self.stdout.clone(),
             stderr_observer: self.stderr.clone(),
+            exit_status_observer: self.exit_status.clone(),
             input_location: self.input_location.clone(),
             timeout: self.timeout,
             command,
@@ -535,6 +600,259 @@ impl CommandExecutorBuilder {
     }
 }
 
+/// A [`CommandConfigurator`] for [`MultipartInput`]s, routing each named part to its own [`InputLocation`].
+///
+/// This way, a single structured input can fill several commandline arguments, environment variables,
+/// files, and stdin at the same time, e.g., to fuzz the argument parsing of a cli tool together with
+/// the file it reads.
+/// If the input has no part of a routed name, an empty value is delivered, and environment variables stay unset.
+/// If it has several, only the first one is used.
+#[cfg(feature = "multipart_inputs")]
+#[derive(Debug)]
+pub struct MultipartCommandConfigurator {
+    debug_child: bool,
+    stdout_observer: Option<Handle<StdOutObserver>>,
+    stderr_observer: Option<Handle<StdErrObserver>>,
+    exit_status_observer: Option<Handle<ExitStatusObserver>>,
+    timeout: Duration,
+    program: OsString,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    cwd: Option<PathBuf>,
+    /// The name of a part, and where it goes
+    routes: Vec<(String, InputLocation)>,
+}
+
+#[cfg(feature = "multipart_inputs")]
+impl MultipartCommandConfigurator {
+    /// Create a new [`MultipartCommandConfigurator`], executing `program`
+    #[must_use]
+    pub fn new<O: AsRef<OsStr>>(program: O) -> Self {
+        Self {
+            debug_child: false,
+            stdout_observer: None,
+            stderr_observer: None,
+            exit_status_observer: None,
+            timeout: Duration::from_secs(5),
+            program: program.as_ref().to_owned(),
+            args: vec![],
+            envs: vec![],
+            cwd: None,
+            routes: vec![],
+        }
+    }
+
+    /// Adds a fixed argument to the program's commandline.
+    #[must_use]
+    pub fn arg<O: AsRef<OsStr>>(mut self, arg: O) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds a range of fixed arguments to the program's commandline.
+    #[must_use]
+    pub fn args<IT, O>(mut self, args: IT) -> Self
+    where
+        IT: IntoIterator<Item = O>,
+        O: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    /// Adds a fixed environment variable to the executed command.
+    #[must_use]
+    pub fn env<K, V>(mut self, key: K, val: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs
+            .push((key.as_ref().to_owned(), val.as_ref().to_owned()));
+        self
+    }
+
+    /// Sets the working directory for the child process.
+    #[must_use]
+    pub fn current_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.cwd = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Routes the part `name` to the given location.
+    ///
+    /// An [`InputLocation::Arg`] replaces the argument at `argnum`, which must already exist.
+    /// Prefer the `*_part` methods, which take care of this.
+    #[must_use]
+    pub fn route<N: Into<String>>(mut self, name: N, location: InputLocation) -> Self {
+        if let InputLocation::Arg { argnum } = location {
+            assert!(
+                argnum < self.args.len(),
+                "argument {argnum} of part does not exist"
+            );
+        }
+        if location == InputLocation::StdIn {
+            assert!(
+                !self
+                    .routes
+                    .iter()
+                    .any(|(_, other)| *other == InputLocation::StdIn),
+                "only one part can be delivered via stdin"
+            );
+        }
+        self.routes.push((name.into(), location));
+        self
+    }
+
+    /// Delivers the part `name` as the next argument on the commandline.
+    #[must_use]
+    pub fn arg_part<N: Into<String>>(self, name: N) -> Self {
+        let argnum = self.args.len();
+        // Placeholder arg that gets replaced with the part later.
+        self.arg("PLACEHOLDER")
+            .route(name, InputLocation::Arg { argnum })
+    }
+
+    /// Delivers the part `name` in a file at `path`, and adds `path` as the next argument on the commandline.
+    #[must_use]
+    pub fn arg_file_part<N: Into<String>, P: AsRef<Path>>(self, name: N, path: P) -> Self {
+        let out_file = InputFile::create(path.as_ref()).unwrap();
+        self.arg(path.as_ref())
+            .route(name, InputLocation::File { out_file })
+    }
+
+    /// Delivers the part `name` as the value of the environment variable `key`.
+    #[must_use]
+    pub fn env_part<N: Into<String>, K: AsRef<OsStr>>(self, name: N, key: K) -> Self {
+        self.route(
+            name,
+            InputLocation::Env {
+                key: key.as_ref().to_owned(),
+            },
+        )
+    }
+
+    /// Delivers the part `name` via stdin.
+    #[must_use]
+    pub fn stdin_part<N: Into<String>>(self, name: N) -> Self {
+        self.route(name, InputLocation::StdIn)
+    }
+
+    /// Sets the stdout observer
+    #[must_use]
+    pub fn stdout_observer(mut self, stdout: Handle<StdOutObserver>) -> Self {
+        self.stdout_observer = Some(stdout);
+        self
+    }
+
+    /// Sets the stderr observer
+    #[must_use]
+    pub fn stderr_observer(mut self, stderr: Handle<StdErrObserver>) -> Self {
+        self.stderr_observer = Some(stderr);
+        self
+    }
+
+    /// Sets the exit status observer
+    #[must_use]
+    pub fn exit_status_observer(mut self, exit_status: Handle<ExitStatusObserver>) -> Self {
+        self.exit_status_observer = Some(exit_status);
+        self
+    }
+
+    /// If set to true, the child's output won't be redirecited to `/dev/null`.
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn debug_child(mut self, debug_child: bool) -> Self {
+        self.debug_child = debug_child;
+        self
+    }
+
+    /// Sets the execution timeout duration.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[cfg(feature = "multipart_inputs")]
+impl<I> CommandConfigurator<MultipartInput<I>> for MultipartCommandConfigurator
+where
+    I: HasTargetBytes,
+{
+    fn stdout_observer(&self) -> Option<Handle<StdOutObserver>> {
+        self.stdout_observer.clone()
+    }
+
+    fn stderr_observer(&self) -> Option<Handle<StdErrObserver>> {
+        self.stderr_observer.clone()
+    }
+
+    fn exit_status_observer(&self) -> Option<Handle<ExitStatusObserver>> {
+        self.exit_status_observer.clone()
+    }
+
+    fn spawn_child(&mut self, input: &MultipartInput<I>) -> Result<Child, Error> {
+        let mut cmd = Command::new(&self.program);
+        let mut args = self.args.clone();
+        cmd.envs(
+            self.envs
+                .iter()
+                .map(|(k, v)| (k.as_os_str(), v.as_os_str())),
+        );
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+
+        let mut stdin_bytes = None;
+        for (name, location) in &mut self.routes {
+            let part_bytes = input
+                .parts_by_name(name)
+                .next()
+                .map(|(_, part)| part.target_bytes());
+            let bytes = part_bytes.as_ref().map_or(&[][..], |b| b.as_slice());
+            match location {
+                InputLocation::Arg { argnum } => args[*argnum] = os_string_from_bytes(bytes),
+                InputLocation::Env { key } => {
+                    if part_bytes.is_some() {
+                        cmd.env(key, os_string_from_bytes(bytes));
+                    }
+                }
+                InputLocation::StdIn => stdin_bytes = Some(bytes.to_vec()),
+                InputLocation::File { out_file } => out_file.write_buf(bytes)?,
+            }
+        }
+        cmd.args(args);
+
+        if stdin_bytes.is_some() {
+            cmd.stdin(Stdio::piped());
+        } else {
+            cmd.stdin(Stdio::null());
+        }
+        if !self.debug_child {
+            cmd.stdout(Stdio::null());
+            cmd.stderr(Stdio::null());
+        }
+        if self.stdout_observer.is_some() {
+            cmd.stdout(Stdio::piped());
+        }
+        if self.stderr_observer.is_some() {
+            cmd.stderr(Stdio::piped());
+        }
+
+        let mut child = cmd.spawn()?;
+        if let Some(bytes) = stdin_bytes {
+            write_to_stdin(&mut child, &bytes)?;
+        }
+        Ok(child)
+    }
+
+    fn exec_timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
 /// A `CommandConfigurator` takes care of creating and spawning a [`std::process::Command`] for the [`CommandExecutor`].
 /// # Example
 #[cfg_attr(all(feature = "std", unix), doc = " ```")]
@@ -586,6 +904,10 @@ pub trait CommandConfigurator<I>: Sized {
     fn stderr_observer(&self) -> Option<Handle<StdErrObserver>> {
         None
     }
+    /// Get the exit status
+    fn exit_status_observer(&self) -> Option<Handle<ExitStatusObserver>> {
+        None
+    }
 
     /// Spawns a new process with the given configuration.
     fn spawn_child(&mut self, input: &I) -> Result<Child, Error>;
@@ -644,4 +966,64 @@ mod tests {
             )
             .unwrap();
     }
+
+    #[test]
+    #[cfg(all(unix, feature = "multipart_inputs"))]
+    #[cfg_attr(miri, ignore)]
+    fn test_multipart_routes() {
+        use crate::{
+            executors::{
+                command::{CommandConfigurator, MultipartCommandConfigurator},
+                ExitKind,
+            },
+            inputs::MultipartInput,
+        };
+
+        let mut mgr = SimpleEventManager::new(SimpleMonitor::new(|status| {
+            log::info!("{status}");
+        }));
+
+        // Crashes unless each part arrived where it was routed to
+        let configurator = MultipartCommandConfigurator::new("sh")
+            .arg("-c")
+            .arg(r#"[ "$1" = arg ] && [ "$PART" = env ] && [ "$(cat)" = stdin ] || kill -SEGV $$"#)
+            .arg("sh")
+            .arg_part("arg")
+            .env_part("env", "PART")
+            .stdin_part("stdin");
+        let mut executor = <MultipartCommandConfigurator as CommandConfigurator<
+            MultipartInput<BytesInput>,
+        >>::into_executor(configurator, ());
+
+        let input = MultipartInput::from([
+            ("arg", BytesInput::new(b"arg".to_vec())),
+            ("env", BytesInput::new(b"env".to_vec())),
+            ("stdin", BytesInput::new(b"stdin".to_vec())),
+        ]);
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut NopState::new(),
+                &mut mgr,
+                &input,
+            )
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+
+        // Parts with NUL bytes are cut at the first one, instead of failing to spawn the target
+        let input = MultipartInput::from([
+            ("arg", BytesInput::new(b"arg\0tail".to_vec())),
+            ("env", BytesInput::new(b"env\0".to_vec())),
+            ("stdin", BytesInput::new(b"stdin".to_vec())),
+        ]);
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut NopState::new(),
+                &mut mgr,
+                &input,
+            )
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+    }
 }
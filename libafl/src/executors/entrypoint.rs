@@ -0,0 +1,127 @@
+//! Run the harness of the entrypoint an [`EntrypointInput`] is meant for.
+//!
+//! See [`crate::inputs::entrypoint`] for fuzzing several entrypoints in one campaign.
+
+use alloc::{borrow::Cow, boxed::Box, vec::Vec};
+use core::fmt::{self, Debug, Formatter};
+
+use crate::{
+    executors::ExitKind,
+    inputs::entrypoint::{EntrypointInput, HasEntrypoint},
+};
+
+/// The harnesses of several entrypoints of a target, picked by the entrypoint of each input.
+///
+/// Call [`MultiEntrypoint::run`] from the harness of any executor:
+///
+/// ```rust,ignore
+/// let mut entrypoints = MultiEntrypoint::new()
+///     .with_entrypoint("parse", |input: &BytesInput| parse(input.bytes()))
+///     .with_entrypoint("decode", |input: &BytesInput| decode(input.bytes()));
+/// let mut harness = |input: &EntrypointInput<BytesInput>| entrypoints.run(input);
+/// ```
+pub struct MultiEntrypoint<'a, I> {
+    names: Vec<Cow<'static, str>>,
+    harnesses: Vec<Box<dyn FnMut(&I) -> ExitKind + 'a>>,
+}
+
+impl<I> Debug for MultiEntrypoint<'_, I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiEntrypoint")
+            .field("names", &self.names)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I> Default for MultiEntrypoint<'_, I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, I> MultiEntrypoint<'a, I> {
+    /// Creates a new [`MultiEntrypoint`], without any entrypoint yet
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            names: Vec::new(),
+            harnesses: Vec::new(),
+        }
+    }
+
+    /// Adds an entrypoint, with the next index
+    #[must_use]
+    pub fn with_entrypoint<N, H>(mut self, name: N, harness: H) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+        H: FnMut(&I) -> ExitKind + 'a,
+    {
+        self.names.push(name.into());
+        self.harnesses.push(Box::new(harness));
+        self
+    }
+
+    /// The number of entrypoints
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.harnesses.len()
+    }
+
+    /// If there are no entrypoints
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.harnesses.is_empty()
+    }
+
+    /// The names of the entrypoints, by index
+    #[must_use]
+    pub fn names(&self) -> &[Cow<'static, str>] {
+        &self.names
+    }
+
+    /// The index of the harness running inputs for `entrypoint`, out of range entrypoints wrap around
+    #[must_use]
+    pub fn index_of(&self, entrypoint: usize) -> usize {
+        entrypoint % self.len()
+    }
+
+    /// Runs the harness of the entrypoint the input is meant for
+    ///
+    /// # Panics
+    /// Panics if there are no entrypoints
+    pub fn run(&mut self, input: &EntrypointInput<I>) -> ExitKind {
+        assert!(!self.is_empty(), "MultiEntrypoint has no entrypoints");
+        let idx = self.index_of(input.entrypoint());
+        (self.harnesses[idx])(input.input())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MultiEntrypoint;
+    use crate::{executors::ExitKind, inputs::entrypoint::EntrypointInput};
+
+    #[test]
+    fn test_multi_entrypoint() {
+        let mut entrypoints = MultiEntrypoint::new()
+            .with_entrypoint("ok", |_: &u8| ExitKind::Ok)
+            .with_entrypoint("crash", |input: &u8| {
+                if *input == 0 {
+                    ExitKind::Crash
+                } else {
+                    ExitKind::Ok
+                }
+            });
+        assert_eq!(entrypoints.len(), 2);
+        assert_eq!(entrypoints.run(&EntrypointInput::new(0, 0)), ExitKind::Ok);
+        assert_eq!(
+            entrypoints.run(&EntrypointInput::new(1, 0)),
+            ExitKind::Crash
+        );
+        assert_eq!(
+            entrypoints.run(&EntrypointInput::new(3, 0)),
+            ExitKind::Crash
+        );
+        assert_eq!(entrypoints.run(&EntrypointInput::new(3, 1)), ExitKind::Ok);
+    }
+}
@@ -1,6 +1,10 @@
 //! Expose an `Executor` based on a `Forkserver` in order to execute AFL/AFL++ binaries
 
-use alloc::{borrow::ToOwned, string::ToString, vec::Vec};
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::{
     fmt::{self, Debug, Formatter},
     marker::PhantomData,
@@ -603,6 +607,7 @@ where
     asan_obs: Handle<AsanBacktraceObserver>,
     timeout: TimeSpec,
     crash_exitcode: Option<i8>,
+    fork_aware_coverage: bool,
 }
 
 impl<OT, S, SP> Debug for ForkserverExecutor<OT, S, SP>
@@ -691,6 +696,7 @@ pub struct ForkserverExecutorBuilder<'a, SP> {
     #[cfg(feature = "regex")]
     asan_obs: Option<Handle<AsanBacktraceObserver>>,
     crash_exitcode: Option<i8>,
+    fork_aware_coverage: bool,
 }
 
 impl<'a, SP> ForkserverExecutorBuilder<'a, SP>
@@ -758,6 +764,7 @@ where
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
             crash_exitcode: self.crash_exitcode,
+            fork_aware_coverage: self.fork_aware_coverage,
         })
     }
 
@@ -822,6 +829,7 @@ where
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
             crash_exitcode: self.crash_exitcode,
+            fork_aware_coverage: self.fork_aware_coverage,
         })
     }
 
@@ -853,11 +861,16 @@ where
             }
         };
 
+        let mut envs = self.envs.clone();
+        if self.fork_aware_coverage {
+            envs.push(("LIBAFL_FORK_AWARE_COVERAGE".into(), "1".into()));
+        }
+
         let mut forkserver = match &self.program {
             Some(t) => Forkserver::with_kill_signal(
                 t.clone(),
                 self.arguments.clone(),
-                self.envs.clone(),
+                envs,
                 input_file.as_raw_fd(),
                 self.use_stdin,
                 0,
@@ -1296,6 +1309,18 @@ where
         self
     }
 
+    /// Call this if the target forks, and the coverage of its children should be merged into its own; default is false
+    ///
+    /// The forkserver of `libafl_targets` then puts each run into its own process group, and only reports the
+    /// exit status once all processes of the group are gone. On timeout, the whole group is killed.
+    /// Compile the target with the `sancov_pcguard_atomic` feature of `libafl_targets`, so that concurrent
+    /// processes do not lose hitcounts.
+    #[must_use]
+    pub fn fork_aware_coverage(mut self, fork_aware_coverage: bool) -> Self {
+        self.fork_aware_coverage = fork_aware_coverage;
+        self
+    }
+
     /// Call this if the harness uses deferred forkserver mode; default is false
     #[must_use]
     pub fn is_deferred_frksrv(mut self, is_deferred_frksrv: bool) -> Self {
@@ -1310,6 +1335,42 @@ where
         self
     }
 
+    /// Query the coverage map size of the target and use it for the coverage map.
+    ///
+    /// Like `afl-fuzz`, this runs the [`Self::program`] once with `AFL_DUMP_MAP_SIZE=1`,
+    /// so that the shared map is allocated with exactly the number of edges the target was instrumented with.
+    /// Set the program and its environment before calling this.
+    /// If the target does not report its map size, e.g. because it was not instrumented by AFL++,
+    /// the map size set by [`Self::coverage_map_size`] is kept.
+    pub fn coverage_map_size_from_target(mut self) -> Result<Self, Error> {
+        let Some(program) = &self.program else {
+            return Err(Error::illegal_argument(
+                "Set the program before querying its coverage map size",
+            ));
+        };
+        let output = Command::new(program)
+            .envs(self.envs.iter().map(|(key, val)| (key, val)))
+            .env("AFL_DUMP_MAP_SIZE", "1")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+
+        match String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<usize>()
+        {
+            Ok(map_size) if map_size > 0 => {
+                // The forkserver rounds the map size reported in the handshake up to a multiple of 64
+                self.map_size = Some(((map_size + 63) >> 6) << 6);
+            }
+            _ => log::warn!(
+                "Target {program:?} did not report its coverage map size, keeping {:?}",
+                self.map_size
+            ),
+        }
+        Ok(self)
+    }
+
     /// Call this to set a signal to be used to kill child processes after executions
     #[must_use]
     pub fn kill_signal(mut self, kill_signal: Signal) -> Self {
@@ -1346,6 +1407,7 @@ impl<'a> ForkserverExecutorBuilder<'a, UnixShMemProvider> {
             timeout: None,
             asan_obs: None,
             crash_exitcode: None,
+            fork_aware_coverage: false,
         }
     }
 
@@ -1375,6 +1437,7 @@ impl<'a> ForkserverExecutorBuilder<'a, UnixShMemProvider> {
             timeout: self.timeout,
             asan_obs: self.asan_obs,
             crash_exitcode: self.crash_exitcode,
+            fork_aware_coverage: self.fork_aware_coverage,
         }
     }
 }
@@ -1480,7 +1543,13 @@ where
             self.forkserver.set_last_run_timed_out(true);
 
             // We need to kill the child in case he has timed out, or we can't get the correct pid in the next call to self.executor.forkserver_mut().read_st()?
-            let _ = kill(self.forkserver().child_pid(), self.forkserver.kill_signal);
+            let child_pid = if self.fork_aware_coverage {
+                // Also kill the processes it forked, the forkserver waits for them
+                Pid::from_raw(-self.forkserver().child_pid().as_raw())
+            } else {
+                self.forkserver().child_pid()
+            };
+            let _ = kill(child_pid, self.forkserver.kill_signal);
             if let Err(err) = self.forkserver.read_st() {
                 return Err(Error::unknown(format!(
                     "Could not kill timed-out child: {err:?}"
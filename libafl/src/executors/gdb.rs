@@ -0,0 +1,815 @@
+//! The [`GdbExecutor`] runs each input on a real device, driven over the GDB remote serial protocol.
+//!
+//! It connects to the GDB server of a debug probe, e.g. `OpenOCD`, `pyOCD` or a J-Link GDB server, and, for
+//! each input, resets the board with monitor commands, runs it to a breakpoint where the harness is ready,
+//! writes the input into its RAM, and runs it until it reaches the exit or a crash breakpoint, e.g. of the
+//! `HardFault` handler. A coverage buffer maintained by the firmware can be read back into the coverage map
+//! after each run, so the rest of `LibAFL` works unchanged.
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter, Write as _},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Instant,
+};
+
+use libafl_bolts::{tuples::RefIndexable, AsSlice};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::HasTargetBytes,
+    observers::ObserversTuple,
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// The maximum number of bytes read or written with a single memory packet
+const MAX_MEMORY_CHUNK: usize = 0x200;
+/// The byte interrupting the running target
+const INTERRUPT: u8 = 0x03;
+/// How long to wait for the target to stop after an interrupt, and for replies in general
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Append the hex encoding of `bytes` to `out`
+fn push_hex(out: &mut String, bytes: &[u8]) {
+    for byte in bytes {
+        write!(out, "{byte:02x}").unwrap();
+    }
+}
+
+/// Decode the hex string `hex`, e.g. a memory or register reply
+fn decode_hex(hex: &str) -> Result<Vec<u8>, Error> {
+    if hex.len() % 2 != 0 {
+        return Err(Error::illegal_argument(format!(
+            "Odd length of the hex string {hex}"
+        )));
+    }
+    if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(Error::illegal_argument(format!("Invalid hex string {hex}")));
+    }
+    Ok(hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| (hex_digit(pair[0]) << 4) | hex_digit(pair[1]))
+        .collect())
+}
+
+/// The value of the ASCII hex digit `digit`
+fn hex_digit(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+/// An I/O error, so the [`GdbExecutor`] treats it like a lost connection
+fn connection_error(kind: ErrorKind, msg: &str) -> Error {
+    Error::os_error(io::Error::from(kind), msg)
+}
+
+/// Frame `payload` as packet of the GDB remote serial protocol, `$payload#checksum`
+fn encode_packet(payload: &str) -> Vec<u8> {
+    let checksum = payload.bytes().fold(0_u8, u8::wrapping_add);
+    format!("${payload}#{checksum:02x}").into_bytes()
+}
+
+/// Expand the run-length encoding of a reply: `x*n` repeats `x` another `n - 29` times
+fn decode_rle(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len());
+    let mut idx = 0;
+    while idx < payload.len() {
+        if payload[idx] == b'*' && idx + 1 < payload.len() {
+            if let Some(&last) = out.last() {
+                let count = payload[idx + 1].saturating_sub(29);
+                out.extend(core::iter::repeat(last).take(count as usize));
+            }
+            idx += 2;
+        } else {
+            out.push(payload[idx]);
+            idx += 1;
+        }
+    }
+    out
+}
+
+/// Why the target stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The target stopped with the given signal, e.g. `5` (`SIGTRAP`) at a breakpoint
+    Signal(u8),
+    /// The target process exited with the given status
+    Exited(u8),
+    /// The target process was terminated by the given signal
+    Terminated(u8),
+}
+
+/// A client of the GDB remote serial protocol
+pub struct GdbRemote {
+    reader: BufReader<TcpStream>,
+}
+
+impl Debug for GdbRemote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GdbRemote")
+            .field("peer", &self.reader.get_ref().peer_addr().ok())
+            .finish()
+    }
+}
+
+impl GdbRemote {
+    /// Connect to the GDB server at `address`
+    pub fn connect(address: SocketAddr) -> Result<Self, Error> {
+        let stream = TcpStream::connect_timeout(&address, REPLY_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        let mut remote = Self {
+            reader: BufReader::new(stream),
+        };
+        remote.reader.get_mut().write_all(b"+")?;
+        Ok(remote)
+    }
+
+    /// Send the packet with `payload`, and wait for the acknowledgement
+    fn send(&mut self, payload: &str) -> Result<(), Error> {
+        self.reader
+            .get_mut()
+            .set_read_timeout(Some(REPLY_TIMEOUT))?;
+        for _ in 0..3 {
+            self.reader.get_mut().write_all(&encode_packet(payload))?;
+            let mut ack = [0_u8];
+            self.reader.read_exact(&mut ack)?;
+            match ack[0] {
+                b'+' => return Ok(()),
+                b'-' => log::debug!("GDB server asked to resend {payload}"),
+                other => {
+                    return Err(Error::illegal_state(format!(
+                        "Unexpected acknowledgement {other:#x} from the GDB server"
+                    )))
+                }
+            }
+        }
+        Err(Error::illegal_state(format!(
+            "GDB server did not accept {payload}"
+        )))
+    }
+
+    /// Receive the next packet, waiting until `deadline`, if any, or [`REPLY_TIMEOUT`].
+    ///
+    /// Returns `None` if no packet starts before the deadline.
+    fn receive(&mut self, deadline: Option<Instant>) -> Result<Option<String>, Error> {
+        loop {
+            let timeout = match deadline {
+                Some(deadline) => match deadline
+                    .checked_duration_since(Instant::now())
+                    .filter(|timeout| !timeout.is_zero())
+                {
+                    Some(timeout) => timeout,
+                    None => return Ok(None),
+                },
+                None => REPLY_TIMEOUT,
+            };
+            self.reader.get_mut().set_read_timeout(Some(timeout))?;
+
+            let mut skipped = Vec::new();
+            match self.reader.read_until(b'$', &mut skipped) {
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(None)
+                }
+                res => res?,
+            };
+            self.reader
+                .get_mut()
+                .set_read_timeout(Some(REPLY_TIMEOUT))?;
+            if skipped.last() != Some(&b'$') {
+                return Err(connection_error(
+                    ErrorKind::UnexpectedEof,
+                    "GDB server closed the connection",
+                ));
+            }
+            let mut payload = Vec::new();
+            self.reader.read_until(b'#', &mut payload)?;
+            if payload.pop() != Some(b'#') {
+                return Err(connection_error(
+                    ErrorKind::UnexpectedEof,
+                    "GDB server closed the connection",
+                ));
+            }
+            let mut checksum = [0_u8; 2];
+            self.reader.read_exact(&mut checksum)?;
+
+            let expected = payload.iter().copied().fold(0_u8, u8::wrapping_add);
+            let valid = core::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                == Some(expected);
+            if valid {
+                self.reader.get_mut().write_all(b"+")?;
+                return String::from_utf8(decode_rle(&payload))
+                    .map(Some)
+                    .map_err(|_| {
+                        Error::illegal_state("GDB server sent a reply that is not ASCII")
+                    });
+            }
+            self.reader.get_mut().write_all(b"-")?;
+        }
+    }
+
+    /// Receive the next reply, skipping console output. Returns `None` on timeout.
+    fn reply(&mut self, deadline: Option<Instant>) -> Result<Option<String>, Error> {
+        loop {
+            let Some(reply) = self.receive(deadline)? else {
+                return Ok(None);
+            };
+            let is_output = reply.len() > 1
+                && reply.starts_with('O')
+                && reply[1..].bytes().all(|b| b.is_ascii_hexdigit());
+            if is_output {
+                if let Ok(output) = decode_hex(&reply[1..]) {
+                    log::debug!(
+                        "GDB server: {}",
+                        String::from_utf8_lossy(&output).trim_end()
+                    );
+                }
+            } else {
+                return Ok(Some(reply));
+            }
+        }
+    }
+
+    /// Send the command `payload`, and return the reply, failing on `E` error replies
+    pub fn command(&mut self, payload: &str) -> Result<String, Error> {
+        self.send(payload)?;
+        let reply = self.reply(None)?.ok_or_else(|| {
+            connection_error(
+                ErrorKind::TimedOut,
+                &format!("GDB server did not reply to {payload}"),
+            )
+        })?;
+        if reply.starts_with('E') && reply.len() == 3 {
+            return Err(Error::illegal_state(format!(
+                "GDB server failed {payload}: {reply}"
+            )));
+        }
+        Ok(reply)
+    }
+
+    /// Send a command expecting `OK`
+    fn command_ok(&mut self, payload: &str) -> Result<(), Error> {
+        let reply = self.command(payload)?;
+        if reply == "OK" {
+            Ok(())
+        } else {
+            Err(Error::illegal_state(format!(
+                "Unexpected reply {reply} of the GDB server to {payload}"
+            )))
+        }
+    }
+
+    /// Run the monitor command `cmd` of the GDB server, e.g. `reset halt` for `OpenOCD`
+    pub fn monitor(&mut self, cmd: &str) -> Result<(), Error> {
+        let mut payload = String::from("qRcmd,");
+        push_hex(&mut payload, cmd.as_bytes());
+        let reply = self.command(&payload)?;
+        if reply != "OK" && !reply.is_empty() {
+            log::debug!("Monitor command {cmd} returned {reply}");
+        }
+        Ok(())
+    }
+
+    /// Read `len` bytes of memory at `address`
+    pub fn read_memory(&mut self, address: u64, len: usize) -> Result<Vec<u8>, Error> {
+        let mut memory = Vec::with_capacity(len);
+        while memory.len() < len {
+            let chunk = (len - memory.len()).min(MAX_MEMORY_CHUNK);
+            let reply = self.command(&format!("m{:x},{chunk:x}", address + memory.len() as u64))?;
+            let bytes = decode_hex(&reply)?;
+            if bytes.is_empty() {
+                return Err(Error::illegal_state(format!(
+                    "GDB server returned no memory at {address:#x}"
+                )));
+            }
+            memory.extend(bytes);
+        }
+        memory.truncate(len);
+        Ok(memory)
+    }
+
+    /// Write `data` to the memory at `address`
+    pub fn write_memory(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
+        for (idx, chunk) in data.chunks(MAX_MEMORY_CHUNK).enumerate() {
+            let mut payload = format!(
+                "M{:x},{:x}:",
+                address + (idx * MAX_MEMORY_CHUNK) as u64,
+                chunk.len()
+            );
+            push_hex(&mut payload, chunk);
+            self.command_ok(&payload)?;
+        }
+        Ok(())
+    }
+
+    /// Read the register with the number `reg` of the target description, as little-endian value
+    pub fn read_register(&mut self, reg: usize) -> Result<u64, Error> {
+        let bytes = decode_hex(&self.command(&format!("p{reg:x}"))?)?;
+        Ok(bytes
+            .iter()
+            .take(8)
+            .rev()
+            .fold(0, |value, byte| (value << 8) | u64::from(*byte)))
+    }
+
+    /// Set a breakpoint at `address`, a hardware breakpoint if `hardware` is set, e.g. for code in flash
+    pub fn set_breakpoint(&mut self, address: u64, hardware: bool) -> Result<(), Error> {
+        let kind = u8::from(hardware);
+        self.command_ok(&format!("Z{kind},{address:x},2"))
+    }
+
+    /// Continue the target until it stops, or interrupt it after `timeout`.
+    ///
+    /// Returns `None` on timeout.
+    pub fn cont(&mut self, timeout: Duration) -> Result<Option<StopReason>, Error> {
+        self.send("c")?;
+        if let Some(reply) = self.reply(Some(Instant::now() + timeout))? {
+            return Self::parse_stop_reply(&reply).map(Some);
+        }
+        log::debug!("Interrupting the target after {timeout:?}");
+        self.reader.get_mut().write_all(&[INTERRUPT])?;
+        let reply = self.reply(None)?.ok_or_else(|| {
+            connection_error(
+                ErrorKind::TimedOut,
+                "The target did not stop after the interrupt",
+            )
+        })?;
+        Self::parse_stop_reply(&reply)?;
+        Ok(None)
+    }
+
+    /// Parse the stop reply `reply`, e.g. `T05thread:01;`
+    fn parse_stop_reply(reply: &str) -> Result<StopReason, Error> {
+        let code = reply
+            .get(1..3)
+            .and_then(|code| u8::from_str_radix(code, 16).ok());
+        match (reply.chars().next(), code) {
+            (Some('S' | 'T'), Some(signal)) => Ok(StopReason::Signal(signal)),
+            (Some('W'), Some(status)) => Ok(StopReason::Exited(status)),
+            (Some('X'), Some(signal)) => Ok(StopReason::Terminated(signal)),
+            _ => Err(Error::illegal_state(format!(
+                "Unexpected stop reply {reply} of the GDB server"
+            ))),
+        }
+    }
+}
+
+/// The signal reported at breakpoints and after interrupts
+const SIGTRAP: u8 = 5;
+/// The signal reported after interrupts by some servers
+const SIGINT: u8 = 2;
+
+/// Runs each input on a real device, driven over the GDB remote serial protocol, see the [module docs](self).
+///
+/// If the connection to the GDB server breaks or times out while an input runs, the input is reported as
+/// [`ExitKind::Crash`] or [`ExitKind::Timeout`], and the executor connects again.
+pub struct GdbExecutor<OT, S> {
+    remote: GdbRemote,
+    address: SocketAddr,
+    hardware_breakpoints: bool,
+    reset_commands: Vec<String>,
+    ready_breakpoint: Option<u64>,
+    exit_breakpoint: u64,
+    crash_breakpoints: Vec<u64>,
+    pc_register: usize,
+    input_address: u64,
+    input_max_len: usize,
+    input_len_address: Option<u64>,
+    coverage_address: u64,
+    map: *mut u8,
+    map_size: usize,
+    timeout: Duration,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl GdbExecutor<(), ()> {
+    /// Create a builder for [`GdbExecutor`]
+    #[must_use]
+    pub fn builder() -> GdbExecutorBuilder {
+        GdbExecutorBuilder::new()
+    }
+}
+
+impl<OT, S> Debug for GdbExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GdbExecutor")
+            .field("remote", &self.remote)
+            .field("reset_commands", &self.reset_commands)
+            .field("exit_breakpoint", &self.exit_breakpoint)
+            .field("crash_breakpoints", &self.crash_breakpoints)
+            .field("timeout", &self.timeout)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> GdbExecutor<OT, S> {
+    /// The connection to the GDB server, e.g. to inspect the target after a crash
+    pub fn remote_mut(&mut self) -> &mut GdbRemote {
+        &mut self.remote
+    }
+
+    /// Connect to the GDB server again, e.g. after the probe dropped the connection or a run wedged it
+    fn reconnect(&mut self) -> Result<(), Error> {
+        self.remote = connect_remote(
+            self.address,
+            &self.reset_commands,
+            self.ready_breakpoint
+                .iter()
+                .chain([self.exit_breakpoint].iter())
+                .chain(self.crash_breakpoints.iter()),
+            self.hardware_breakpoints,
+        )?;
+        Ok(())
+    }
+
+    /// Run the loaded input, until the target stops or the timeout, and read back its coverage
+    fn run(&mut self) -> Result<ExitKind, Error> {
+        let exit_kind = match self.remote.cont(self.timeout)? {
+            None => ExitKind::Timeout,
+            Some(StopReason::Signal(SIGTRAP | SIGINT)) => {
+                let pc = self.remote.read_register(self.pc_register)?;
+                if pc == self.exit_breakpoint {
+                    ExitKind::Ok
+                } else if self.crash_breakpoints.contains(&pc) {
+                    ExitKind::Crash
+                } else {
+                    log::warn!("The target stopped at the unexpected address {pc:#x}");
+                    ExitKind::Crash
+                }
+            }
+            Some(stop) => {
+                log::debug!("The target stopped: {stop:?}");
+                ExitKind::Crash
+            }
+        };
+
+        self.read_coverage()?;
+        Ok(exit_kind)
+    }
+
+    /// Reset the target, and run it until the harness is ready for the input
+    fn reset(&mut self) -> Result<(), Error> {
+        for cmd in &self.reset_commands {
+            self.remote.monitor(cmd)?;
+        }
+        if let Some(ready) = self.ready_breakpoint {
+            let stop = self.remote.cont(self.timeout)?;
+            let pc = self.remote.read_register(self.pc_register)?;
+            if stop != Some(StopReason::Signal(SIGTRAP)) || pc != ready {
+                return Err(Error::illegal_state(format!(
+                    "The target did not reach the ready breakpoint at {ready:#x} after the reset, but stopped at {pc:#x} ({stop:?})"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the input into the RAM of the target, and clear its coverage buffer
+    fn load(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let bytes = &bytes[..bytes.len().min(self.input_max_len)];
+        self.remote.write_memory(self.input_address, bytes)?;
+        if let Some(len_address) = self.input_len_address {
+            #[allow(clippy::cast_possible_truncation)]
+            let len = bytes.len() as u32;
+            self.remote.write_memory(len_address, &len.to_le_bytes())?;
+        }
+        if self.map_size > 0 {
+            self.remote
+                .write_memory(self.coverage_address, &vec![0; self.map_size])?;
+        }
+        Ok(())
+    }
+
+    /// Read the coverage buffer of the target into the coverage map
+    fn read_coverage(&mut self) -> Result<(), Error> {
+        if self.map_size == 0 {
+            return Ok(());
+        }
+        let coverage = self
+            .remote
+            .read_memory(self.coverage_address, self.map_size)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(coverage.as_ptr(), self.map, self.map_size);
+        }
+        Ok(())
+    }
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for GdbExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        let bytes = input.target_bytes();
+        // Losing the connection before the input ran is not its fault, try again on a fresh one
+        if let Err(err) = self.reset().and_then(|()| self.load(bytes.as_slice())) {
+            if !matches!(err, Error::OsError(..)) {
+                return Err(err);
+            }
+            log::warn!("Lost the GDB server before the run ({err}), reconnecting");
+            self.reconnect()?;
+            self.reset()?;
+            self.load(bytes.as_slice())?;
+        }
+
+        match self.run() {
+            Err(Error::OsError(err, msg, _)) => {
+                // The target hung the probe, or took it down with it
+                let exit_kind = if matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock)
+                {
+                    ExitKind::Timeout
+                } else {
+                    ExitKind::Crash
+                };
+                log::warn!(
+                    "Lost the GDB server during the run ({msg}: {err}), reporting {exit_kind:?}"
+                );
+                self.reconnect()?;
+                Ok(exit_kind)
+            }
+            res => res,
+        }
+    }
+}
+
+impl<OT, S> UsesState for GdbExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<OT, S> HasObservers for GdbExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+/// Connect to the GDB server at `address`, halt the target with the reset commands, and set the breakpoints
+fn connect_remote<'a>(
+    address: SocketAddr,
+    reset_commands: &[String],
+    breakpoints: impl Iterator<Item = &'a u64>,
+    hardware_breakpoints: bool,
+) -> Result<GdbRemote, Error> {
+    let mut remote = GdbRemote::connect(address)?;
+    // Many servers halt the target on connection; make sure it is, before setting breakpoints
+    for cmd in reset_commands {
+        remote.monitor(cmd)?;
+    }
+    for breakpoint in breakpoints {
+        remote.set_breakpoint(*breakpoint, hardware_breakpoints)?;
+    }
+    Ok(remote)
+}
+
+/// Builder for [`GdbExecutor`]
+#[derive(Debug)]
+pub struct GdbExecutorBuilder {
+    address: Option<SocketAddr>,
+    reset_commands: Vec<String>,
+    ready_breakpoint: Option<u64>,
+    exit_breakpoint: Option<u64>,
+    crash_breakpoints: Vec<u64>,
+    hardware_breakpoints: bool,
+    pc_register: usize,
+    input: Option<(u64, usize)>,
+    input_len_address: Option<u64>,
+    coverage_address: u64,
+    map: *mut u8,
+    map_size: usize,
+    timeout: Duration,
+}
+
+impl Default for GdbExecutorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GdbExecutorBuilder {
+    /// Constructor
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            address: None,
+            reset_commands: vec![],
+            ready_breakpoint: None,
+            exit_breakpoint: None,
+            crash_breakpoints: vec![],
+            hardware_breakpoints: true,
+            pc_register: 15,
+            input: None,
+            input_len_address: None,
+            coverage_address: 0,
+            map: core::ptr::null_mut(),
+            map_size: 0,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// The address of the GDB server, e.g. `127.0.0.1:3333` for `OpenOCD`. This option is required.
+    #[must_use]
+    pub fn tcp(mut self, address: SocketAddr) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// A monitor command resetting and halting the target before each input, e.g. `reset halt`.
+    /// Can be given multiple times, the commands run in order.
+    #[must_use]
+    pub fn reset_command<C: Into<String>>(mut self, cmd: C) -> Self {
+        self.reset_commands.push(cmd.into());
+        self
+    }
+
+    /// The address the target runs to after the reset, before the input is written,
+    /// e.g. the start of the harness, once the startup code initialized the RAM
+    #[must_use]
+    pub fn ready_breakpoint(mut self, address: u64) -> Self {
+        self.ready_breakpoint = Some(address);
+        self
+    }
+
+    /// The address the target reaches after handling the input. This option is required.
+    #[must_use]
+    pub fn exit_breakpoint(mut self, address: u64) -> Self {
+        self.exit_breakpoint = Some(address);
+        self
+    }
+
+    /// An address the target only reaches on a crash, e.g. of the `HardFault` handler or an assert handler
+    #[must_use]
+    pub fn crash_breakpoint(mut self, address: u64) -> Self {
+        self.crash_breakpoints.push(address);
+        self
+    }
+
+    /// Use hardware breakpoints, needed for code in flash. Enabled by default.
+    #[must_use]
+    pub fn hardware_breakpoints(mut self, hardware_breakpoints: bool) -> Self {
+        self.hardware_breakpoints = hardware_breakpoints;
+        self
+    }
+
+    /// The number of the program counter in the register list of the target.
+    /// Defaults to `15`, the `pc` of ARM targets.
+    #[must_use]
+    pub fn pc_register(mut self, pc_register: usize) -> Self {
+        self.pc_register = pc_register;
+        self
+    }
+
+    /// Write each input to the RAM of the target at `address`, truncated to `max_len` bytes.
+    /// This option is required.
+    #[must_use]
+    pub fn input_at(mut self, address: u64, max_len: usize) -> Self {
+        self.input = Some((address, max_len));
+        self
+    }
+
+    /// Write the length of each input to the RAM of the target at `address`, as little-endian `u32`
+    #[must_use]
+    pub fn input_len_at(mut self, address: u64) -> Self {
+        self.input_len_address = Some(address);
+        self
+    }
+
+    /// Report inputs running longer than `timeout` as [`ExitKind::Timeout`]. Defaults to 5 seconds.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Read the coverage buffer of `map_size` bytes, which the firmware maintains at `address` in its RAM,
+    /// into `map` after each run. Observe it with a map observer,
+    /// e.g. [`crate::observers::StdMapObserver::from_mut_ptr`].
+    ///
+    /// # Safety
+    /// The map must be valid for writes of `map_size` bytes, and outlive the [`GdbExecutor`].
+    #[must_use]
+    pub unsafe fn coverage_buffer(mut self, address: u64, map: *mut u8, map_size: usize) -> Self {
+        self.coverage_address = address;
+        self.map = map;
+        self.map_size = map_size;
+        self
+    }
+
+    /// Connect to the GDB server, set the breakpoints, and build the [`GdbExecutor`]
+    pub fn build<OT, S>(self, observers: OT) -> Result<GdbExecutor<OT, S>, Error> {
+        let Some(address) = self.address else {
+            return Err(Error::illegal_argument(
+                "GdbExecutor::builder: no GDB server address set!",
+            ));
+        };
+        let Some(exit_breakpoint) = self.exit_breakpoint else {
+            return Err(Error::illegal_argument(
+                "GdbExecutor::builder: no exit breakpoint set!",
+            ));
+        };
+        let Some((input_address, input_max_len)) = self.input else {
+            return Err(Error::illegal_argument(
+                "GdbExecutor::builder: no input address set!",
+            ));
+        };
+        if self.map_size > 0 && self.map.is_null() {
+            return Err(Error::illegal_argument(
+                "GdbExecutor::builder: the coverage map is null!",
+            ));
+        }
+
+        let remote = connect_remote(
+            address,
+            &self.reset_commands,
+            self.ready_breakpoint
+                .iter()
+                .chain([exit_breakpoint].iter())
+                .chain(self.crash_breakpoints.iter()),
+            self.hardware_breakpoints,
+        )?;
+
+        Ok(GdbExecutor {
+            remote,
+            address,
+            hardware_breakpoints: self.hardware_breakpoints,
+            reset_commands: self.reset_commands,
+            ready_breakpoint: self.ready_breakpoint,
+            exit_breakpoint,
+            crash_breakpoints: self.crash_breakpoints,
+            pc_register: self.pc_register,
+            input_address,
+            input_max_len,
+            input_len_address: self.input_len_address,
+            coverage_address: self.coverage_address,
+            map: self.map,
+            map_size: self.map_size,
+            timeout: self.timeout,
+            observers,
+            phantom: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_hex, decode_rle, encode_packet, GdbRemote, StopReason};
+
+    #[test]
+    fn test_packets() {
+        assert_eq!(encode_packet("m1000,4"), b"$m1000,4#8e".to_vec());
+        assert_eq!(decode_rle(b"0* "), b"0000".to_vec());
+        assert_eq!(decode_hex("00ff41").unwrap(), vec![0, 0xff, 0x41]);
+        assert_eq!(decode_hex("AbCd").unwrap(), vec![0xab, 0xcd]);
+        assert!(decode_hex("0").is_err());
+        assert!(decode_hex("+f").is_err());
+        assert!(decode_hex("\u{e9}").is_err());
+        assert!(decode_hex("OK").is_err());
+        assert_eq!(
+            GdbRemote::parse_stop_reply("T05thread:01;").unwrap(),
+            StopReason::Signal(5)
+        );
+        assert_eq!(
+            GdbRemote::parse_stop_reply("W00").unwrap(),
+            StopReason::Exited(0)
+        );
+        assert!(GdbRemote::parse_stop_reply("OK").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! The [`ExecStatsHook`] measures the resource usage of each run of the harness with `getrusage`, for the
+//! [`crate::observers::ExecStatsObserver`].
+
+use core::{mem::MaybeUninit, time::Duration};
+use std::time::Instant;
+
+use crate::{
+    executors::{hooks::ExecutorHook, HasObservers},
+    inputs::UsesInput,
+    observers::{record_exec_stats, ExecStats},
+};
+
+/// On Linux, only the thread running the harness is measured, elsewhere the whole process
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const RUSAGE_WHO: libc::c_int = libc::RUSAGE_THREAD;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const RUSAGE_WHO: libc::c_int = libc::RUSAGE_SELF;
+
+/// The total resource usage so far, or `None` if it could not be read
+fn current_usage() -> Option<ExecStats> {
+    let mut usage = MaybeUninit::<libc::rusage>::uninit();
+    // # Safety
+    // `getrusage` only writes to the given struct, which is fully initialized on success.
+    let usage = unsafe {
+        if libc::getrusage(RUSAGE_WHO, usage.as_mut_ptr()) != 0 {
+            return None;
+        }
+        usage.assume_init()
+    };
+    let duration = |time: libc::timeval| {
+        Duration::from_secs(u64::try_from(time.tv_sec).unwrap_or(0))
+            + Duration::from_micros(u64::try_from(time.tv_usec).unwrap_or(0))
+    };
+    let counter = |count: libc::c_long| u64::try_from(count).unwrap_or(0);
+    Some(ExecStats {
+        wall_time: Duration::ZERO,
+        user_time: duration(usage.ru_utime),
+        system_time: duration(usage.ru_stime),
+        minor_faults: counter(usage.ru_minflt),
+        major_faults: counter(usage.ru_majflt),
+        voluntary_switches: counter(usage.ru_nvcsw),
+        involuntary_switches: counter(usage.ru_nivcsw),
+    })
+}
+
+/// An executor hook measuring the wall time, CPU time, page faults and context switches of each run.
+///
+/// Add an [`crate::observers::ExecStatsObserver`] to the observers to read them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecStatsHook {
+    start: Option<(Instant, ExecStats)>,
+}
+
+impl ExecStatsHook {
+    /// Create a new [`ExecStatsHook`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S> ExecutorHook<S> for ExecStatsHook
+where
+    S: UsesInput,
+{
+    fn init<E: HasObservers>(&mut self, _state: &mut S) {}
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) {
+        self.start = current_usage().map(|usage| (Instant::now(), usage));
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &S::Input) {
+        let Some((start_time, before)) = self.start.take() else {
+            return;
+        };
+        let wall_time = start_time.elapsed();
+        let Some(after) = current_usage() else {
+            return;
+        };
+        record_exec_stats(ExecStats {
+            wall_time,
+            user_time: after.user_time.saturating_sub(before.user_time),
+            system_time: after.system_time.saturating_sub(before.system_time),
+            minor_faults: after.minor_faults.saturating_sub(before.minor_faults),
+            major_faults: after.major_faults.saturating_sub(before.major_faults),
+            voluntary_switches: after
+                .voluntary_switches
+                .saturating_sub(before.voluntary_switches),
+            involuntary_switches: after
+                .involuntary_switches
+                .saturating_sub(before.involuntary_switches),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExecStatsHook;
+    use crate::{
+        executors::{hooks::ExecutorHook, ExitKind},
+        inputs::BytesInput,
+        observers::{ExecStatsObserver, Observer},
+        state::NopState,
+    };
+
+    #[test]
+    fn test_exec_stats_hook() {
+        let mut hook = ExecStatsHook::new();
+        let mut observer = ExecStatsObserver::new("exec_stats");
+        let mut state = NopState::<BytesInput>::new();
+        let input = BytesInput::new(vec![]);
+
+        observer.pre_exec(&mut state, &input).unwrap();
+        hook.pre_exec(&mut state, &input);
+        hook.post_exec(&mut state, &input);
+        observer
+            .post_exec(&mut state, &input, &ExitKind::Ok)
+            .unwrap();
+        assert!(observer.last_stats().is_some());
+    }
+}
@@ -15,9 +15,16 @@ pub mod unix;
 /// The hook for inprocess fork executor
 pub mod inprocess_fork;
 
+/// Resource usage measurement of each run
+#[cfg(all(unix, feature = "std"))]
+pub mod exec_stats;
+
 /// The hook for inprocess executor
 pub mod inprocess;
 
+/// Reset hooks to clean up target state between runs
+pub mod reset;
+
 /// Timer-related stuff
 #[cfg(feature = "std")]
 pub mod timer;
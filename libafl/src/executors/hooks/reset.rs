@@ -0,0 +1,148 @@
+//! Reset hooks: user closures that clean up target state before and after each run.
+//!
+//! Register them on a [`crate::executors::inprocess::HookableInProcessExecutor`] (or any other executor
+//! taking an [`super::ExecutorHooksTuple`]) to reset globals, clear caches, or drain logs between runs,
+//! instead of wrapping the harness closure manually.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt::{self, Debug, Formatter};
+
+use crate::{
+    executors::{hooks::ExecutorHook, HasObservers},
+    inputs::UsesInput,
+};
+
+/// A reset hook closure, getting access to the state and the current input
+pub type ResetHookFn<S> = Box<dyn FnMut(&mut S, &<S as UsesInput>::Input)>;
+
+/// An ordered list of closures that run before and after each execution of the harness.
+///
+/// Pre-exec hooks run in the order they were added, post-exec hooks as well.
+pub struct ResetHooks<S>
+where
+    S: UsesInput,
+{
+    pre: Vec<ResetHookFn<S>>,
+    post: Vec<ResetHookFn<S>>,
+}
+
+impl<S> Debug for ResetHooks<S>
+where
+    S: UsesInput,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResetHooks")
+            .field("pre", &self.pre.len())
+            .field("post", &self.post.len())
+            .finish()
+    }
+}
+
+impl<S> Default for ResetHooks<S>
+where
+    S: UsesInput,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> ResetHooks<S>
+where
+    S: UsesInput,
+{
+    /// Create an empty list of reset hooks
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            pre: Vec::new(),
+            post: Vec::new(),
+        }
+    }
+
+    /// Add a hook that runs right before the harness
+    #[must_use]
+    pub fn pre<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&mut S, &S::Input) + 'static,
+    {
+        self.add_pre(hook);
+        self
+    }
+
+    /// Add a hook that runs right after the harness returned
+    #[must_use]
+    pub fn post<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&mut S, &S::Input) + 'static,
+    {
+        self.add_post(hook);
+        self
+    }
+
+    /// Add a hook that runs right before the harness
+    pub fn add_pre<F>(&mut self, hook: F)
+    where
+        F: FnMut(&mut S, &S::Input) + 'static,
+    {
+        self.pre.push(Box::new(hook));
+    }
+
+    /// Add a hook that runs right after the harness returned
+    pub fn add_post<F>(&mut self, hook: F)
+    where
+        F: FnMut(&mut S, &S::Input) + 'static,
+    {
+        self.post.push(Box::new(hook));
+    }
+
+    /// Remove all registered hooks
+    pub fn clear(&mut self) {
+        self.pre.clear();
+        self.post.clear();
+    }
+}
+
+impl<S> ExecutorHook<S> for ResetHooks<S>
+where
+    S: UsesInput,
+{
+    fn init<E: HasObservers>(&mut self, _state: &mut S) {}
+
+    fn pre_exec(&mut self, state: &mut S, input: &S::Input) {
+        for hook in &mut self.pre {
+            hook(state, input);
+        }
+    }
+
+    fn post_exec(&mut self, state: &mut S, input: &S::Input) {
+        for hook in &mut self.post {
+            hook(state, input);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    use super::ResetHooks;
+    use crate::{executors::hooks::ExecutorHook, inputs::BytesInput, state::NopState};
+
+    #[test]
+    fn test_reset_hooks_order() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let (l1, l2, l3) = (log.clone(), log.clone(), log.clone());
+        let mut hooks = ResetHooks::<NopState<BytesInput>>::new()
+            .pre(move |_, _| l1.borrow_mut().push(1))
+            .pre(move |_, _| l2.borrow_mut().push(2))
+            .post(move |_, _| l3.borrow_mut().push(3));
+
+        let mut state = NopState::new();
+        let input = BytesInput::new(vec![]);
+        hooks.pre_exec(&mut state, &input);
+        hooks.post_exec(&mut state, &input);
+        assert_eq!(*log.borrow(), vec![1, 2, 3]);
+    }
+}
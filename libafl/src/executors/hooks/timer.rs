@@ -253,6 +253,33 @@ impl TimerStruct {
         me
     }
 
+    /// Change the timeout, taking effect the next time the timer is set
+    #[cfg(all(unix, not(target_os = "linux")))]
+    pub fn set_exec_timeout(&mut self, exec_tmout: Duration) {
+        let milli_sec = exec_tmout.as_millis();
+        self.itimerval.it_value = Timeval {
+            tv_sec: (milli_sec / 1000) as i64,
+            tv_usec: (milli_sec % 1000) as i64,
+        };
+    }
+
+    /// Change the timeout, taking effect the next time the timer is set
+    #[cfg(windows)]
+    pub fn set_exec_timeout(&mut self, exec_tmout: Duration) {
+        self.milli_sec = exec_tmout.as_millis() as i64;
+    }
+
+    /// Change the timeout, taking effect the next time the timer is set
+    #[cfg(target_os = "linux")]
+    pub fn set_exec_timeout(&mut self, exec_tmout: Duration) {
+        let milli_sec = exec_tmout.as_millis();
+        self.itimerspec.it_value = libc::timespec {
+            tv_sec: (milli_sec / 1000) as _,
+            tv_nsec: ((milli_sec % 1000) * 1000 * 1000) as _,
+        };
+        self.exec_tmout = exec_tmout;
+    }
+
     #[cfg(all(unix, not(target_os = "linux")))]
     /// Set up timer
     pub fn set_timer(&mut self) {
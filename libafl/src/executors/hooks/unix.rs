@@ -213,6 +213,13 @@ pub mod unix_signal_handler {
 
             log::error!("Child crashed!");
 
+            #[cfg(feature = "std")]
+            crate::observers::crash_context::record_crash_context(
+                signal,
+                _info,
+                _context.as_deref(),
+            );
+
             {
                 let mut bsod = Vec::new();
                 {
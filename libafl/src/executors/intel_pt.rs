@@ -0,0 +1,138 @@
+//! The [`IntelPTHook`] collects the edge coverage of uninstrumented `x86_64` binaries with Intel PT.
+//!
+//! The hook is a [`PtraceHook`] of the [`crate::executors::PtraceExecutor`]: it starts tracing the target while it
+//! is stopped right after its `execve`, and decodes the trace into its map once the target is gone, see
+//! [`libafl_bolts::intel_pt`]. Observe the map with the observer returned by [`IntelPTHook::observer`].
+//!
+//! ```rust,ignore
+//! let hook = IntelPTHook::new(1 << 16).filter("filter 0x1000/0x4000@/path/to/target");
+//! // The executor owns both the hook and the observer of its map
+//! let edges_observer = HitcountsMapObserver::new(unsafe { hook.observer("edges") });
+//! let mut executor = PtraceExecutor::builder()
+//!     .program("/path/to/target")
+//!     .arg("@@")
+//!     .build_with_hooks(tuple_list!(edges_observer), tuple_list!(hook))?;
+//! ```
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{ptr, slice};
+
+use libafl_bolts::intel_pt::{self, IntelPT, PtDecoder, DEFAULT_AUX_SIZE};
+
+use crate::{
+    executors::{
+        ptrace::{PtraceHook, Tracee},
+        ExitKind,
+    },
+    observers::StdMapObserver,
+    Error,
+};
+
+/// A [`PtraceHook`] tracing the target with Intel PT, and decoding the trace into an edge coverage map.
+///
+/// Only the target itself is traced, not its children.
+#[derive(Debug)]
+pub struct IntelPTHook {
+    pt: Option<IntelPT>,
+    checked: bool,
+    aux_size: usize,
+    filter: Option<String>,
+    decoder: PtDecoder,
+    trace: Vec<u8>,
+    map_ptr: *mut u8,
+    map_size: usize,
+}
+
+impl IntelPTHook {
+    /// Creates a new [`IntelPTHook`], with a coverage map of `map_size` entries.
+    ///
+    /// The map is owned by the hook, and freed with it.
+    #[must_use]
+    pub fn new(map_size: usize) -> Self {
+        let map = Box::leak(vec![0_u8; map_size].into_boxed_slice());
+        Self {
+            pt: None,
+            checked: false,
+            aux_size: DEFAULT_AUX_SIZE,
+            filter: None,
+            decoder: PtDecoder::new(map_size),
+            trace: Vec::new(),
+            map_ptr: map.as_mut_ptr(),
+            map_size,
+        }
+    }
+
+    /// The size of the buffer for the trace of each execution; defaults to [`DEFAULT_AUX_SIZE`].
+    ///
+    /// The trace of longer executions is cut off.
+    #[must_use]
+    pub fn aux_size(mut self, aux_size: usize) -> Self {
+        self.aux_size = aux_size;
+        self
+    }
+
+    /// Only trace the address ranges of the given `perf` filter, see [`IntelPT::set_filter`].
+    ///
+    /// Restricting the trace to the code of the target, without its libraries, saves trace space and decoding time.
+    #[must_use]
+    pub fn filter<S: Into<String>>(mut self, filter: S) -> Self {
+        self.filter = Some(filter.into());
+        self
+    }
+
+    /// The decoder of the traces, e.g. for the statistics of its cache
+    #[must_use]
+    pub fn decoder(&self) -> &PtDecoder {
+        &self.decoder
+    }
+
+    /// A map observer of the edge coverage collected by this hook
+    ///
+    /// # Safety
+    /// The map is freed with this hook, so the observer must not be used once the hook is dropped, e.g. by passing
+    /// both to the same executor.
+    #[must_use]
+    pub unsafe fn observer(&self, name: &'static str) -> StdMapObserver<'static, u8, false> {
+        // # Safety
+        // The hook only writes to the map after the execution, while the observers do not run.
+        unsafe { StdMapObserver::from_mut_ptr(name, self.map_ptr, self.map_size) }
+    }
+}
+
+impl Drop for IntelPTHook {
+    fn drop(&mut self) {
+        // # Safety
+        // The map was allocated as a boxed slice of this size in `new`.
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(self.map_ptr, self.map_size)) });
+    }
+}
+
+impl<I> PtraceHook<I> for IntelPTHook {
+    fn on_exec(&mut self, tracee: &Tracee, _input: &I) -> Result<(), Error> {
+        if !self.checked {
+            // Fail early, with a readable error
+            intel_pt::check_availability()?;
+            self.checked = true;
+        }
+        let mut pt = IntelPT::new(tracee.pid(), self.aux_size)?;
+        if let Some(filter) = &self.filter {
+            pt.set_filter(filter)?;
+        }
+        pt.enable()?;
+        self.pt = Some(pt);
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _input: &I, _exit_kind: &ExitKind) -> Result<(), Error> {
+        let Some(mut pt) = self.pt.take() else {
+            return Ok(());
+        };
+        self.trace.clear();
+        pt.read_trace(&mut self.trace)?;
+        // # Safety
+        // The map lives as long as the hook, and the observers do not run during the execution.
+        let map = unsafe { slice::from_raw_parts_mut(self.map_ptr, self.map_size) };
+        self.decoder.decode(&self.trace, map);
+        Ok(())
+    }
+}
@@ -4,38 +4,90 @@
 use alloc::vec::Vec;
 use core::fmt::Debug;
 
+pub use batch::{BatchExecutor, BatchResult, SequentialBatchExecutor};
+#[cfg(all(feature = "perf", target_os = "linux"))]
+pub use branch_tracer::BranchTracerHook;
 pub use combined::CombinedExecutor;
 #[cfg(all(feature = "std", any(unix, doc)))]
 pub use command::CommandExecutor;
 pub use differential::DiffExecutor;
+pub use entrypoint::MultiEntrypoint;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub use forkserver::{Forkserver, ForkserverExecutor};
+#[cfg(feature = "std")]
+pub use gdb::GdbExecutor;
 pub use inprocess::InProcessExecutor;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub use inprocess_fork::InProcessForkExecutor;
+#[cfg(all(feature = "intel_pt", target_os = "linux", target_arch = "x86_64"))]
+pub use intel_pt::IntelPTHook;
 #[cfg(unix)]
 use libafl_bolts::os::unix_signals::Signal;
 use libafl_bolts::tuples::RefIndexable;
+#[cfg(all(feature = "std", unix))]
+pub use network::NetworkExecutor;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use ptrace::{PtraceAction, PtraceExecutor, PtraceHook, PtraceHooksTuple, Syscall, Tracee};
 use serde::{Deserialize, Serialize};
 pub use shadow::ShadowExecutor;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use snapshot::SnapshotExecutor;
+#[cfg(feature = "std")]
+pub use threaded::ThreadPoolExecutor;
+#[cfg(all(feature = "std", windows))]
+pub use ttd::TtdExecutor;
 pub use with_observers::WithObservers;
 
 use crate::{observers::ObserversTuple, state::UsesState, Error};
 
+/// The module for executors running a batch of inputs at once
+pub mod batch;
+/// The module for the coverage of uninstrumented binaries from the branch records of the CPU
+#[cfg(all(feature = "perf", target_os = "linux"))]
+pub mod branch_tracer;
 pub mod combined;
 #[cfg(all(feature = "std", any(unix, doc)))]
 pub mod command;
 pub mod differential;
+/// The module for running the harness of one of several entrypoints
+pub mod entrypoint;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub mod forkserver;
+/// The module for the executor of embedded targets behind a GDB server
+#[cfg(feature = "std")]
+pub mod gdb;
 pub mod inprocess;
 
+/// The module for the coverage of uninstrumented binaries with Intel PT
+#[cfg(all(feature = "intel_pt", target_os = "linux", target_arch = "x86_64"))]
+pub mod intel_pt;
+
 /// The module for inproc fork executor
 #[cfg(all(feature = "std", unix))]
 pub mod inprocess_fork;
 
+/// The module for the executor of network services
+#[cfg(all(feature = "std", unix))]
+pub mod network;
+
+/// The module for the executor tracing the target with `ptrace`
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod ptrace;
+
 pub mod shadow;
 
+/// The module for the snapshot-restoring executor wrapper
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod snapshot;
+
+/// The module for the in-process executor running a reentrant harness on multiple threads
+#[cfg(feature = "std")]
+pub mod threaded;
+
+/// The module for the coverage of closed-source Windows binaries with Time Travel Debugging
+#[cfg(all(feature = "std", windows))]
+pub mod ttd;
+
 pub mod with_observers;
 
 /// The module for all the hooks
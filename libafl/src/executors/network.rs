@@ -0,0 +1,844 @@
+//! The [`NetworkExecutor`] sends each input to a server process over a TCP or unix domain socket.
+//!
+//! The server is either started (and restarted after each crash) by the executor, or already running.
+//! Crashes are detected by watching the started process, or, optionally, by probing whether the server
+//! still accepts connections after each execution.
+//!
+//! Stateful protocols can be fuzzed with [`SequenceInput`]s, of which each message is sent on its own,
+//! and the server's responses to them are kept in [`NetworkExecutor::last_responses`]. A
+//! [`ResponseCodeObserver`] set with [`NetworkExecutorBuilder::response_observer`] turns the
+//! transitions between their response codes into coverage.
+
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    ops::IndexMut,
+    time::Duration,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpStream},
+    os::unix::{net::UnixStream, process::ExitStatusExt},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::Instant,
+};
+
+use libafl_bolts::{
+    tuples::{Handle, MatchName, RefIndexable},
+    AsSlice,
+};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::{HasTargetBytes, SequenceInput},
+    observers::{ObserversTuple, ResponseCodeObserver},
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// Where the server listens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerAddress {
+    /// A TCP socket
+    Tcp(SocketAddr),
+    /// A unix domain socket
+    Unix(PathBuf),
+}
+
+/// How the input is split into the packets sent to the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacketFraming {
+    /// Send the whole input at once
+    Raw,
+    /// Split the input after each occurrence of the delimiter, e.g. `\r\n` for line-based protocols
+    Delimited(Vec<u8>),
+    /// The input is a sequence of packets, each prefixed with its length, as a `width`-byte unsigned integer.
+    ///
+    /// Lengths exceeding the rest of the input are clamped, and the prefix is rewritten accordingly,
+    /// so the server always receives well-formed frames.
+    LengthPrefixed {
+        /// The size of the length prefix in bytes, between 1 and 8
+        width: usize,
+        /// Whether the length prefix is big endian
+        big_endian: bool,
+    },
+}
+
+impl PacketFraming {
+    /// Split the input into the packets to send
+    #[must_use]
+    pub fn packets(&self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        match self {
+            PacketFraming::Raw => vec![bytes.to_vec()],
+            PacketFraming::Delimited(delimiter) => {
+                let mut packets = Vec::new();
+                let mut start = 0;
+                let mut idx = 0;
+                while !delimiter.is_empty() && idx + delimiter.len() <= bytes.len() {
+                    if bytes[idx..].starts_with(delimiter) {
+                        idx += delimiter.len();
+                        packets.push(bytes[start..idx].to_vec());
+                        start = idx;
+                    } else {
+                        idx += 1;
+                    }
+                }
+                if start < bytes.len() {
+                    packets.push(bytes[start..].to_vec());
+                }
+                packets
+            }
+            PacketFraming::LengthPrefixed { width, big_endian } => {
+                let width = (*width).clamp(1, 8);
+                let mut packets = Vec::new();
+                let mut rest = bytes;
+                while rest.len() >= width {
+                    let (prefix, body) = rest.split_at(width);
+                    let mut len_bytes = [0_u8; 8];
+                    if *big_endian {
+                        len_bytes[8 - width..].copy_from_slice(prefix);
+                    } else {
+                        len_bytes[..width].copy_from_slice(prefix);
+                    }
+                    let len = if *big_endian {
+                        u64::from_be_bytes(len_bytes)
+                    } else {
+                        u64::from_le_bytes(len_bytes)
+                    };
+                    let len = usize::try_from(len).unwrap_or(usize::MAX).min(body.len());
+
+                    let len_bytes = if *big_endian {
+                        (len as u64).to_be_bytes()
+                    } else {
+                        (len as u64).to_le_bytes()
+                    };
+                    let mut packet = Vec::with_capacity(width + len);
+                    if *big_endian {
+                        packet.extend_from_slice(&len_bytes[8 - width..]);
+                    } else {
+                        packet.extend_from_slice(&len_bytes[..width]);
+                    }
+                    packet.extend_from_slice(&body[..len]);
+                    packets.push(packet);
+                    rest = &body[len..];
+                }
+                packets
+            }
+        }
+    }
+}
+
+/// A connection to the server
+#[derive(Debug)]
+enum Connection {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Connection {
+    fn connect(address: &ServerAddress, timeout: Duration) -> std::io::Result<Self> {
+        let connection = match address {
+            ServerAddress::Tcp(addr) => {
+                let stream = TcpStream::connect_timeout(addr, timeout)?;
+                stream.set_nodelay(true)?;
+                Connection::Tcp(stream)
+            }
+            ServerAddress::Unix(path) => Connection::Unix(UnixStream::connect(path)?),
+        };
+        connection.set_read_timeout(timeout)?;
+        Ok(connection)
+    }
+
+    fn set_read_timeout(&self, timeout: Duration) -> std::io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.set_read_timeout(Some(timeout)),
+            Connection::Unix(stream) => stream.set_read_timeout(Some(timeout)),
+        }
+    }
+
+    fn stream(&mut self) -> &mut dyn ReadWrite {
+        match self {
+            Connection::Tcp(stream) => stream,
+            Connection::Unix(stream) => stream,
+        }
+    }
+
+    /// Send a packet, then read the response until the server closes the connection, stays idle for
+    /// `idle_timeout` after answering, or `timeout` passed.
+    ///
+    /// Returns whether the connection is still open.
+    fn exchange(
+        &mut self,
+        packet: &[u8],
+        response: &mut Vec<u8>,
+        timeout: Duration,
+        idle_timeout: Duration,
+    ) -> std::io::Result<bool> {
+        let stream = self.stream();
+        stream.write_all(packet)?;
+        stream.flush()?;
+
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0_u8; 4096];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(true);
+            }
+            // Wait for the full timeout until the server starts to answer
+            let wait = if response.is_empty() {
+                remaining
+            } else {
+                remaining.min(idle_timeout)
+            };
+            // A zero read timeout is rejected
+            self.set_read_timeout(wait.max(Duration::from_millis(1)))?;
+            match self.stream().read(&mut buf) {
+                // The server closed the connection
+                Ok(0) => return Ok(false),
+                Ok(len) => response.extend_from_slice(&buf[..len]),
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(true)
+                }
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    return Ok(false)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+trait ReadWrite: Read + Write {}
+impl<T> ReadWrite for T where T: Read + Write {}
+
+/// How often to try connecting to the server, before it counts as gone
+const CONNECT_ATTEMPTS: usize = 3;
+
+/// Inputs the [`NetworkExecutor`] can send to a server
+pub trait NetworkInput {
+    /// The packets to send, in order
+    fn packets(&self, framing: &PacketFraming) -> Vec<Vec<u8>>;
+}
+
+impl<I> NetworkInput for I
+where
+    I: HasTargetBytes,
+{
+    fn packets(&self, framing: &PacketFraming) -> Vec<Vec<u8>> {
+        framing.packets(self.target_bytes().as_slice())
+    }
+}
+
+/// Each message is sent on its own, and framed separately
+impl<I> NetworkInput for SequenceInput<I>
+where
+    I: HasTargetBytes,
+{
+    fn packets(&self, framing: &PacketFraming) -> Vec<Vec<u8>> {
+        self.messages()
+            .iter()
+            .flat_map(|message| framing.packets(message.payload.target_bytes().as_slice()))
+            .collect()
+    }
+}
+
+/// An executor for network services, sending each input to a server over a TCP or unix domain socket.
+///
+/// Use [`NetworkExecutor::builder()`] to construct it.
+/// The coverage of the server can be collected with shared memory observers, as for the [`crate::executors::CommandExecutor`];
+/// put the shared memory ids into the environment of the server with [`NetworkExecutorBuilder::env`].
+pub struct NetworkExecutor<OT, S> {
+    address: ServerAddress,
+    command: Option<Command>,
+    server: Option<Child>,
+    connection: Option<Connection>,
+    framing: PacketFraming,
+    reuse_connection: bool,
+    liveness_probe: bool,
+    timeout: Duration,
+    idle_timeout: Duration,
+    startup_timeout: Duration,
+    /// The responses of the server to the packets of the last execution
+    responses: Vec<Vec<u8>>,
+    response_observer: Option<Handle<ResponseCodeObserver>>,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl NetworkExecutor<(), ()> {
+    /// Creates a builder for a new [`NetworkExecutor`]
+    #[must_use]
+    pub fn builder() -> NetworkExecutorBuilder {
+        NetworkExecutorBuilder::new()
+    }
+}
+
+impl<OT, S> Debug for NetworkExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetworkExecutor")
+            .field("address", &self.address)
+            .field("command", &self.command)
+            .field("server", &self.server)
+            .field("framing", &self.framing)
+            .field("reuse_connection", &self.reuse_connection)
+            .field("liveness_probe", &self.liveness_probe)
+            .field("timeout", &self.timeout)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> NetworkExecutor<OT, S> {
+    /// The address of the server
+    #[must_use]
+    pub fn address(&self) -> &ServerAddress {
+        &self.address
+    }
+
+    /// The responses of the server to each packet sent in the last execution, e.g. to extract the
+    /// state of a stateful protocol from status codes.
+    ///
+    /// Packets that could not be sent, because the server dropped the connection, have no response.
+    #[must_use]
+    pub fn last_responses(&self) -> &[Vec<u8>] {
+        &self.responses
+    }
+
+    /// The pid of the server process, if it was started by this executor
+    #[must_use]
+    pub fn server_pid(&self) -> Option<u32> {
+        self.server.as_ref().map(Child::id)
+    }
+
+    /// Start the server, if it is ours and not running, and wait until it accepts connections
+    fn ensure_server(&mut self) -> Result<(), Error> {
+        let Some(command) = &mut self.command else {
+            return Ok(());
+        };
+        if self.server.is_some() {
+            return Ok(());
+        }
+        self.server = Some(command.spawn()?);
+
+        let start = Instant::now();
+        loop {
+            match Connection::connect(&self.address, self.timeout) {
+                Ok(connection) => {
+                    if self.reuse_connection {
+                        self.connection = Some(connection);
+                    }
+                    return Ok(());
+                }
+                Err(err) => {
+                    if start.elapsed() > self.startup_timeout {
+                        self.kill_server();
+                        return Err(Error::illegal_state(format!(
+                            "Server did not accept connections on {:?} within {:?}: {err}",
+                            self.address, self.startup_timeout
+                        )));
+                    }
+                    if let Some(status) = self.server.as_mut().unwrap().try_wait()? {
+                        self.server = None;
+                        return Err(Error::illegal_state(format!(
+                            "Server exited during startup with {status}"
+                        )));
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+        }
+    }
+
+    /// Connect to the running server, retrying briefly if it refuses, e.g. while it is busy
+    fn connect(&self) -> std::io::Result<Connection> {
+        let mut attempt = 1;
+        loop {
+            match Connection::connect(&self.address, self.timeout) {
+                Err(err) if attempt < CONNECT_ATTEMPTS => {
+                    log::debug!("Connecting to the server failed, retrying: {err}");
+                    attempt += 1;
+                    thread::sleep(Duration::from_millis(10));
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The result of an execution after which the server does not accept connections anymore
+    fn unreachable_exit_kind(&mut self) -> Result<ExitKind, Error> {
+        if let Some(exit_kind) = self.server_exit_kind()? {
+            return Ok(exit_kind);
+        }
+        if self.server.is_some() {
+            // Still running, but not accepting connections anymore: it hangs
+            self.kill_server();
+            return Ok(ExitKind::Timeout);
+        }
+        Ok(ExitKind::Crash)
+    }
+
+    fn kill_server(&mut self) {
+        self.connection = None;
+        if let Some(mut server) = self.server.take() {
+            drop(server.kill());
+            drop(server.wait());
+        }
+    }
+
+    /// Whether the server process (if started by us) exited, and how
+    fn server_exit_kind(&mut self) -> Result<Option<ExitKind>, Error> {
+        let Some(server) = &mut self.server else {
+            return Ok(None);
+        };
+        let Some(status) = server.try_wait()? else {
+            return Ok(None);
+        };
+        self.server = None;
+        self.connection = None;
+        Ok(Some(match status.signal() {
+            Some(9) => ExitKind::Oom,
+            Some(_) => ExitKind::Crash,
+            None => ExitKind::Ok,
+        }))
+    }
+}
+
+impl<OT, S> Drop for NetworkExecutor<OT, S> {
+    fn drop(&mut self) {
+        self.kill_server();
+    }
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for NetworkExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: NetworkInput,
+    OT: Debug + MatchName,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+        self.ensure_server()?;
+
+        let packets = input.packets(&self.framing);
+        self.responses.clear();
+
+        let connection = match self.connection.take() {
+            Some(connection) => Ok(connection),
+            None => self.connect(),
+        };
+        let mut connection = match connection {
+            Ok(connection) => connection,
+            Err(err) => {
+                log::debug!("Connecting to the server failed: {err}");
+                return self.unreachable_exit_kind();
+            }
+        };
+        let mut open = true;
+        for packet in &packets {
+            if !open {
+                // The server dropped the connection after the previous packet
+                break;
+            }
+            let mut response = Vec::new();
+            match connection.exchange(packet, &mut response, self.timeout, self.idle_timeout) {
+                Ok(still_open) => open = still_open,
+                Err(err) => {
+                    // The server may have crashed on an earlier packet, or just dropped the connection
+                    log::debug!("Exchange with server failed: {err}");
+                    open = false;
+                    break;
+                }
+            }
+            self.responses.push(response);
+        }
+        if open && self.reuse_connection {
+            self.connection = Some(connection);
+        } else {
+            drop(connection);
+        }
+
+        if let Some(h) = &self.response_observer {
+            let mut observers = RefIndexable::from(&mut self.observers);
+            observers.index_mut(h).observe_responses(&self.responses);
+        }
+
+        if let Some(exit_kind) = self.server_exit_kind()? {
+            return Ok(exit_kind);
+        }
+
+        if self.liveness_probe && self.connect().is_err() {
+            return self.unreachable_exit_kind();
+        }
+
+        Ok(ExitKind::Ok)
+    }
+}
+
+impl<OT, S> UsesState for NetworkExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<OT, S> HasObservers for NetworkExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+/// The builder for a [`NetworkExecutor`]
+#[derive(Debug, Clone)]
+pub struct NetworkExecutorBuilder {
+    address: Option<ServerAddress>,
+    program: Option<OsString>,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    cwd: Option<PathBuf>,
+    debug_child: bool,
+    framing: PacketFraming,
+    reuse_connection: bool,
+    liveness_probe: bool,
+    timeout: Duration,
+    idle_timeout: Duration,
+    startup_timeout: Duration,
+    response_observer: Option<Handle<ResponseCodeObserver>>,
+}
+
+impl Default for NetworkExecutorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetworkExecutorBuilder {
+    /// Create a new [`NetworkExecutorBuilder`]
+    #[must_use]
+    fn new() -> NetworkExecutorBuilder {
+        NetworkExecutorBuilder {
+            address: None,
+            program: None,
+            args: vec![],
+            envs: vec![],
+            cwd: None,
+            debug_child: false,
+            framing: PacketFraming::Raw,
+            reuse_connection: false,
+            liveness_probe: false,
+            timeout: Duration::from_millis(100),
+            idle_timeout: Duration::from_millis(10),
+            startup_timeout: Duration::from_secs(5),
+            response_observer: None,
+        }
+    }
+
+    /// Connect to the server on this TCP address.
+    /// This option (or [`Self::unix`]) is required.
+    #[must_use]
+    pub fn tcp(mut self, address: SocketAddr) -> Self {
+        self.address = Some(ServerAddress::Tcp(address));
+        self
+    }
+
+    /// Connect to the server on this unix domain socket.
+    /// This option (or [`Self::tcp`]) is required.
+    #[must_use]
+    pub fn unix<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.address = Some(ServerAddress::Unix(path.as_ref().to_owned()));
+        self
+    }
+
+    /// Start the server from this binary, and restart it after each crash.
+    /// If unset, the server must already be running.
+    #[must_use]
+    pub fn program<O: AsRef<OsStr>>(mut self, program: O) -> Self {
+        self.program = Some(program.as_ref().to_owned());
+        self
+    }
+
+    /// Adds an argument to the server's commandline.
+    #[must_use]
+    pub fn arg<O: AsRef<OsStr>>(mut self, arg: O) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds a range of arguments to the server's commandline.
+    #[must_use]
+    pub fn args<IT, O>(mut self, args: IT) -> Self
+    where
+        IT: IntoIterator<Item = O>,
+        O: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    /// Adds an environment variable to the server.
+    #[must_use]
+    pub fn env<K, V>(mut self, key: K, val: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs
+            .push((key.as_ref().to_owned(), val.as_ref().to_owned()));
+        self
+    }
+
+    /// Adds a range of environment variables to the server.
+    #[must_use]
+    pub fn envs<IT, K, V>(mut self, vars: IT) -> Self
+    where
+        IT: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        for (key, val) in vars {
+            self = self.env(key, val);
+        }
+        self
+    }
+
+    /// Sets the working directory of the server.
+    #[must_use]
+    pub fn current_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.cwd = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// If set to true, the server's output won't be redirecited to `/dev/null`.
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn debug_child(mut self, debug_child: bool) -> Self {
+        self.debug_child = debug_child;
+        self
+    }
+
+    /// How to split the input into packets; defaults to [`PacketFraming::Raw`]
+    #[must_use]
+    pub fn framing(mut self, framing: PacketFraming) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Keep the connection open between executions, instead of connecting for each one; default is false
+    #[must_use]
+    pub fn reuse_connection(mut self, reuse_connection: bool) -> Self {
+        self.reuse_connection = reuse_connection;
+        self
+    }
+
+    /// After each execution, check that the server still accepts connections; default is false.
+    ///
+    /// This is the only way to detect crashes of a server not started by this executor.
+    #[must_use]
+    pub fn liveness_probe(mut self, liveness_probe: bool) -> Self {
+        self.liveness_probe = liveness_probe;
+        self
+    }
+
+    /// How long to wait for connections and for the server's response to each packet; defaults to 100ms
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long to wait for more of the server's response, once it started answering a packet; defaults to 10ms
+    #[must_use]
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// How long to wait for a started server to accept connections; defaults to 5s
+    #[must_use]
+    pub fn startup_timeout(mut self, startup_timeout: Duration) -> Self {
+        self.startup_timeout = startup_timeout;
+        self
+    }
+
+    /// Pass the responses of each execution to this [`ResponseCodeObserver`], which must be part of the observers
+    #[must_use]
+    pub fn response_observer(mut self, response_observer: Handle<ResponseCodeObserver>) -> Self {
+        self.response_observer = Some(response_observer);
+        self
+    }
+
+    /// Builds the [`NetworkExecutor`]
+    pub fn build<OT, S>(self, observers: OT) -> Result<NetworkExecutor<OT, S>, Error>
+    where
+        OT: ObserversTuple<S::Input, S>,
+        S: State,
+    {
+        let Some(address) = self.address else {
+            return Err(Error::illegal_argument(
+                "NetworkExecutor::builder: no server address set!",
+            ));
+        };
+
+        let command = self.program.map(|program| {
+            let mut command = Command::new(program);
+            command
+                .args(&self.args)
+                .envs(
+                    self.envs
+                        .iter()
+                        .map(|(k, v)| (k.as_os_str(), v.as_os_str())),
+                )
+                .stdin(Stdio::null());
+            if let Some(cwd) = &self.cwd {
+                command.current_dir(cwd);
+            }
+            if !self.debug_child {
+                command.stdout(Stdio::null());
+                command.stderr(Stdio::null());
+            }
+            command
+        });
+
+        Ok(NetworkExecutor {
+            address,
+            command,
+            server: None,
+            connection: None,
+            framing: self.framing,
+            reuse_connection: self.reuse_connection,
+            liveness_probe: self.liveness_probe,
+            timeout: self.timeout,
+            idle_timeout: self.idle_timeout,
+            startup_timeout: self.startup_timeout,
+            responses: Vec::new(),
+            response_observer: self.response_observer,
+            observers,
+            phantom: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+        time::Instant,
+    };
+
+    use super::{NetworkExecutor, PacketFraming};
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        state::NopState,
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_local_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        // Echo one packet per connection, then first keep the connection open but idle, then close it
+        let server = thread::spawn(move || {
+            let mut idle_streams = Vec::new();
+            for idle in [true, false] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0_u8; 16];
+                let len = stream.read(&mut buf).unwrap();
+                stream.write_all(&buf[..len]).unwrap();
+                if idle {
+                    idle_streams.push(stream);
+                }
+            }
+        });
+
+        let mut executor = NetworkExecutor::builder()
+            .tcp(address)
+            .timeout(Duration::from_secs(2))
+            .build(())
+            .unwrap();
+        let mut state = NopState::<BytesInput>::new();
+        let mut fuzzer = NopFuzzer::new();
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(b"ping".to_vec());
+
+        for _ in 0..2 {
+            let start = Instant::now();
+            let exit_kind = executor
+                .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+                .unwrap();
+            assert_eq!(exit_kind, ExitKind::Ok);
+            assert_eq!(executor.last_responses(), &[b"ping".to_vec()]);
+            // Neither waiting for the idle server, nor for the timeout
+            assert!(start.elapsed() < Duration::from_millis(400));
+        }
+        server.join().unwrap();
+
+        // The listener is gone, so the server counts as crashed
+        let exit_kind = executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Crash);
+    }
+
+    #[test]
+    fn test_packet_framing() {
+        assert_eq!(PacketFraming::Raw.packets(b"abc"), vec![b"abc".to_vec()]);
+
+        let delimited = PacketFraming::Delimited(b"\r\n".to_vec());
+        assert_eq!(
+            delimited.packets(b"USER a\r\nPASS b\r\nQUIT"),
+            vec![
+                b"USER a\r\n".to_vec(),
+                b"PASS b\r\n".to_vec(),
+                b"QUIT".to_vec()
+            ]
+        );
+
+        // The second length exceeds the input and is clamped
+        let length_prefixed = PacketFraming::LengthPrefixed {
+            width: 2,
+            big_endian: true,
+        };
+        assert_eq!(
+            length_prefixed.packets(b"\x00\x02ab\x00\x09cd"),
+            vec![b"\x00\x02ab".to_vec(), b"\x00\x02cd".to_vec()]
+        );
+    }
+}
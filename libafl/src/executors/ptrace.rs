@@ -0,0 +1,1125 @@
+//! The [`PtraceExecutor`] runs the target in a child process under `ptrace`, for platforms and targets
+//! where a forkserver is not feasible.
+//!
+//! Unlike the [`crate::executors::CommandExecutor`], which only sees the exit status, the executor stops the
+//! target at each signal and, optionally, at selected syscalls, and passes these stops to [`PtraceHook`]s.
+//! Hooks can inspect and modify the memory of the [`Tracee`], e.g. to inject the input, suppress signals
+//! and syscalls, or end the execution with an [`ExitKind`] of their choice.
+//!
+//! Crashing signals of the target and of all its descendants are captured as a [`CrashContext`], which is
+//! picked up by a [`crate::observers::CrashContextObserver`]. The executor can also stop fork bombs,
+//! see [`PtraceExecutorBuilder::max_forks`].
+
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    mem::{size_of, MaybeUninit},
+    ptr,
+    time::Duration,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::File,
+    io,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::Instant,
+};
+
+use libafl_bolts::{
+    fs::{get_unique_std_input_file, InputFile},
+    tuples::RefIndexable,
+    AsSlice,
+};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::HasTargetBytes,
+    observers::{
+        crash_context::{fault_addr, store_first_crash_context},
+        AccessType, CrashContext, ObserversTuple,
+    },
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// The placeholder in the arguments of the target for the file containing the input
+pub const INPUT_PLACEHOLDER: &str = "@@";
+
+/// The signals considered crashes of the target
+const CRASH_SIGNALS: [i32; 5] = [
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGILL,
+    libc::SIGFPE,
+    libc::SIGABRT,
+];
+
+/// The longest pause between two polls for the next stop of a tracee
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The regset of the syscall number on `aarch64`, see `linux/elf.h`
+#[cfg(target_arch = "aarch64")]
+const NT_ARM_SYSTEM_CALL: usize = 0x404;
+
+/// The program counter, stack pointer and named registers of a tracee
+pub(crate) type TraceeRegisters = (Option<usize>, Option<usize>, Vec<(String, u64)>);
+
+/// A process stopped under `ptrace`, passed to the [`PtraceHook`]s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tracee {
+    pid: libc::pid_t,
+}
+
+impl Tracee {
+    /// A stopped tracee of the calling process
+    #[must_use]
+    pub fn new(pid: libc::pid_t) -> Self {
+        Self { pid }
+    }
+
+    /// The pid of the tracee
+    #[must_use]
+    pub fn pid(&self) -> libc::pid_t {
+        self.pid
+    }
+
+    /// Read `len` bytes at `addr` from the memory of the tracee
+    #[allow(clippy::cast_sign_loss)]
+    pub fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, Error> {
+        let mut buf = vec![0; len];
+        let local = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: len,
+        };
+        let remote = libc::iovec {
+            iov_base: addr as *mut libc::c_void,
+            iov_len: len,
+        };
+        // # Safety
+        // The local buffer is `len` bytes long, the kernel checks the remote range.
+        let read = unsafe { libc::process_vm_readv(self.pid, &local, 1, &remote, 1, 0) };
+        if read == -1 {
+            return Err(Error::last_os_error(format!(
+                "Failed to read {len} bytes at {addr:#x} from the tracee {}",
+                self.pid
+            )));
+        }
+        buf.truncate(read as usize);
+        Ok(buf)
+    }
+
+    /// Write `data` at `addr` into the memory of the tracee.
+    ///
+    /// Like a debugger, this also writes to read-only mappings, e.g. to patch code.
+    pub fn write_memory(&self, addr: usize, data: &[u8]) -> Result<(), Error> {
+        const WORD: usize = size_of::<libc::c_long>();
+        let start = addr - addr % WORD;
+        let end = addr + data.len();
+        for word_addr in (start..end).step_by(WORD) {
+            let mut word = self.peek(word_addr)?.to_ne_bytes();
+            for (idx, byte) in word.iter_mut().enumerate() {
+                let byte_addr = word_addr + idx;
+                if (addr..end).contains(&byte_addr) {
+                    *byte = data[byte_addr - addr];
+                }
+            }
+            // # Safety
+            // The tracee is stopped, the kernel checks the address.
+            let ret = unsafe {
+                libc::ptrace(
+                    libc::PTRACE_POKEDATA,
+                    self.pid,
+                    word_addr as *mut libc::c_void,
+                    libc::c_long::from_ne_bytes(word) as *mut libc::c_void,
+                )
+            };
+            if ret == -1 {
+                return Err(Error::last_os_error(format!(
+                    "Failed to write to {word_addr:#x} in the tracee {}",
+                    self.pid
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn peek(&self, addr: usize) -> Result<libc::c_long, Error> {
+        // # Safety
+        // `PTRACE_PEEKDATA` returns the word, so `-1` is only an error if `errno` is set.
+        unsafe {
+            *libc::__errno_location() = 0;
+            let word = libc::ptrace(
+                libc::PTRACE_PEEKDATA,
+                self.pid,
+                addr as *mut libc::c_void,
+                ptr::null_mut::<libc::c_void>(),
+            );
+            if word == -1 && *libc::__errno_location() != 0 {
+                return Err(Error::last_os_error(format!(
+                    "Failed to read {addr:#x} from the tracee {}",
+                    self.pid
+                )));
+            }
+            Ok(word)
+        }
+    }
+
+    /// Read the signal and registers of the tracee, stopped at the crashing `signal`
+    pub fn crash_context(&self, signal: i32) -> Result<CrashContext, Error> {
+        // # Safety
+        // The tracee is stopped at the signal, the kernel fills the `siginfo_t`.
+        let info = unsafe {
+            let mut info = MaybeUninit::<libc::siginfo_t>::uninit();
+            if libc::ptrace(
+                libc::PTRACE_GETSIGINFO,
+                self.pid,
+                ptr::null_mut::<libc::c_void>(),
+                info.as_mut_ptr(),
+            ) == -1
+            {
+                return Err(Error::last_os_error(
+                    "Failed to read the siginfo of the target",
+                ));
+            }
+            info.assume_init()
+        };
+
+        let (pc, sp, registers) = self.registers()?;
+        let fault_addr = fault_addr(signal, &info);
+        let access = if fault_addr.is_some() && fault_addr == pc {
+            AccessType::Execute
+        } else {
+            AccessType::Unknown
+        };
+        Ok(CrashContext {
+            signal,
+            fault_addr,
+            access,
+            pc,
+            sp,
+            registers,
+            memory: Vec::new(),
+        })
+    }
+
+    /// The syscall the tracee is stopped at, see [`PtraceExecutorBuilder::trace_syscalls`]
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    fn syscall(&self, exiting: bool) -> Result<Syscall, Error> {
+        let regs = self.raw_registers()?;
+        #[cfg(target_arch = "x86_64")]
+        let (nr, args, ret) = (
+            regs.orig_rax,
+            [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9],
+            regs.rax,
+        );
+        #[cfg(target_arch = "aarch64")]
+        let (nr, args, ret) = (
+            regs.regs[8],
+            [
+                regs.regs[0],
+                regs.regs[1],
+                regs.regs[2],
+                regs.regs[3],
+                regs.regs[4],
+                regs.regs[5],
+            ],
+            regs.regs[0],
+        );
+        #[allow(clippy::cast_possible_wrap)]
+        Ok(Syscall {
+            nr: nr as libc::c_long,
+            args,
+            ret: exiting.then_some(ret as i64),
+        })
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[allow(clippy::unused_self)]
+    fn syscall(&self, _exiting: bool) -> Result<Syscall, Error> {
+        Err(Error::unsupported(
+            "Tracing syscalls is not supported on this architecture",
+        ))
+    }
+
+    /// Skip the syscall the tracee is stopped at the entry of; it returns `-ENOSYS`
+    #[cfg(target_arch = "x86_64")]
+    fn skip_syscall(&self) -> Result<(), Error> {
+        let mut regs = self.raw_registers()?;
+        regs.orig_rax = u64::MAX;
+        // # Safety
+        // The tracee is stopped, the registers were read from it.
+        if unsafe {
+            libc::ptrace(
+                libc::PTRACE_SETREGS,
+                self.pid,
+                ptr::null_mut::<libc::c_void>(),
+                ptr::addr_of_mut!(regs),
+            )
+        } == -1
+        {
+            return Err(Error::last_os_error("Failed to skip the syscall"));
+        }
+        Ok(())
+    }
+
+    /// Skip the syscall the tracee is stopped at the entry of; it returns `-ENOSYS`
+    #[cfg(target_arch = "aarch64")]
+    fn skip_syscall(&self) -> Result<(), Error> {
+        let mut nr: libc::c_int = -1;
+        let mut iov = libc::iovec {
+            iov_base: ptr::addr_of_mut!(nr).cast(),
+            iov_len: size_of::<libc::c_int>(),
+        };
+        // # Safety
+        // The tracee is stopped at a syscall entry, the `iovec` points to the new syscall number.
+        if unsafe {
+            libc::ptrace(
+                libc::PTRACE_SETREGSET,
+                self.pid,
+                NT_ARM_SYSTEM_CALL as *mut libc::c_void,
+                ptr::addr_of_mut!(iov),
+            )
+        } == -1
+        {
+            return Err(Error::last_os_error("Failed to skip the syscall"));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[allow(clippy::unused_self)]
+    fn skip_syscall(&self) -> Result<(), Error> {
+        Err(Error::unsupported(
+            "Tracing syscalls is not supported on this architecture",
+        ))
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn raw_registers(&self) -> Result<libc::user_regs_struct, Error> {
+        // # Safety
+        // The tracee is stopped, the kernel fills the `user_regs_struct`.
+        unsafe {
+            let mut regs = MaybeUninit::<libc::user_regs_struct>::uninit();
+            if libc::ptrace(
+                libc::PTRACE_GETREGS,
+                self.pid,
+                ptr::null_mut::<libc::c_void>(),
+                regs.as_mut_ptr(),
+            ) == -1
+            {
+                return Err(Error::last_os_error(
+                    "Failed to read the registers of the target",
+                ));
+            }
+            Ok(regs.assume_init())
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    fn raw_registers(&self) -> Result<libc::user_regs_struct, Error> {
+        // # Safety
+        // The tracee is stopped, the kernel fills the `user_regs_struct` described by the `iovec`.
+        unsafe {
+            let mut regs = MaybeUninit::<libc::user_regs_struct>::uninit();
+            let mut iov = libc::iovec {
+                iov_base: regs.as_mut_ptr().cast(),
+                iov_len: size_of::<libc::user_regs_struct>(),
+            };
+            if libc::ptrace(
+                libc::PTRACE_GETREGSET,
+                self.pid,
+                libc::NT_PRSTATUS as usize as *mut libc::c_void,
+                ptr::addr_of_mut!(iov),
+            ) == -1
+            {
+                return Err(Error::last_os_error(
+                    "Failed to read the registers of the target",
+                ));
+            }
+            Ok(regs.assume_init())
+        }
+    }
+
+    /// The program counter, stack pointer and named general purpose registers of the tracee
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) fn registers(&self) -> Result<TraceeRegisters, Error> {
+        let regs = self.raw_registers()?;
+        let registers = [
+            ("rax", regs.rax),
+            ("rbx", regs.rbx),
+            ("rcx", regs.rcx),
+            ("rdx", regs.rdx),
+            ("rsi", regs.rsi),
+            ("rdi", regs.rdi),
+            ("rbp", regs.rbp),
+            ("rsp", regs.rsp),
+            ("r8", regs.r8),
+            ("r9", regs.r9),
+            ("r10", regs.r10),
+            ("r11", regs.r11),
+            ("r12", regs.r12),
+            ("r13", regs.r13),
+            ("r14", regs.r14),
+            ("r15", regs.r15),
+            ("rip", regs.rip),
+            ("eflags", regs.eflags),
+        ]
+        .iter()
+        .map(|(name, value)| (String::from(*name), *value))
+        .collect();
+        Ok((Some(regs.rip as usize), Some(regs.rsp as usize), registers))
+    }
+
+    /// The program counter, stack pointer and named general purpose registers of the tracee
+    #[cfg(target_arch = "aarch64")]
+    pub(crate) fn registers(&self) -> Result<TraceeRegisters, Error> {
+        use crate::observers::crash_context::aarch64_registers;
+
+        let regs = self.raw_registers()?;
+        let registers = aarch64_registers(&regs.regs, regs.sp, regs.pc);
+        Ok((Some(regs.pc as usize), Some(regs.sp as usize), registers))
+    }
+
+    /// The registers are not supported on this architecture
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    pub(crate) fn registers(&self) -> Result<TraceeRegisters, Error> {
+        Ok((None, None, Vec::new()))
+    }
+
+    /// Resume the stopped tracee with `request`, delivering `signal` if it is not 0
+    #[allow(clippy::cast_sign_loss)]
+    fn resume(&self, request: libc::c_uint, signal: i32) -> Result<(), Error> {
+        // # Safety
+        // The tracee is stopped.
+        if unsafe {
+            libc::ptrace(
+                request,
+                self.pid,
+                ptr::null_mut::<libc::c_void>(),
+                signal as usize as *mut libc::c_void,
+            )
+        } == -1
+        {
+            // The tracee may have been killed in the meantime, e.g. by `PTRACE_O_EXITKILL`
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ESRCH) {
+                return Err(err.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A syscall of the tracee, see [`PtraceHook::on_syscall`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Syscall {
+    /// The number of the syscall, e.g. [`libc::SYS_open`]
+    pub nr: libc::c_long,
+    /// The arguments of the syscall
+    pub args: [u64; 6],
+    /// The return value, if the tracee is stopped at the exit of the syscall, `None` at its entry
+    pub ret: Option<i64>,
+}
+
+/// What the [`PtraceExecutor`] does after a [`PtraceHook`] handled a stop of the tracee
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtraceAction {
+    /// Resume the tracee as if there was no hook
+    Continue,
+    /// Resume the tracee, but do not deliver the signal, or skip the syscall at its entry
+    Suppress,
+    /// Kill the target and end the execution with this [`ExitKind`]
+    Stop(ExitKind),
+}
+
+/// A hook of the [`PtraceExecutor`], called at the stops of the target.
+///
+/// The first hook returning anything else than [`PtraceAction::Continue`] decides what happens to the tracee.
+pub trait PtraceHook<I> {
+    /// Called when the target is stopped right after its `execve`, before it ran any instruction,
+    /// e.g. to inject the input into its memory
+    fn on_exec(&mut self, _tracee: &Tracee, _input: &I) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called at the entry and exit of each syscall passed to [`PtraceExecutorBuilder::trace_syscalls`]
+    fn on_syscall(
+        &mut self,
+        _tracee: &Tracee,
+        _syscall: &Syscall,
+        _input: &I,
+    ) -> Result<PtraceAction, Error> {
+        Ok(PtraceAction::Continue)
+    }
+
+    /// Called before a signal is delivered to the target
+    fn on_signal(&mut self, _tracee: &Tracee, _signal: i32) -> Result<PtraceAction, Error> {
+        Ok(PtraceAction::Continue)
+    }
+
+    /// Called after the target and all its descendants are gone, before the observers
+    fn post_exec(&mut self, _input: &I, _exit_kind: &ExitKind) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// A tuple of [`PtraceHook`]s
+pub trait PtraceHooksTuple<I> {
+    /// Calls [`PtraceHook::on_exec`] of all hooks
+    fn on_exec_all(&mut self, tracee: &Tracee, input: &I) -> Result<(), Error>;
+
+    /// Calls [`PtraceHook::on_syscall`] until a hook does not continue
+    fn on_syscall_all(
+        &mut self,
+        tracee: &Tracee,
+        syscall: &Syscall,
+        input: &I,
+    ) -> Result<PtraceAction, Error>;
+
+    /// Calls [`PtraceHook::on_signal`] until a hook does not continue
+    fn on_signal_all(&mut self, tracee: &Tracee, signal: i32) -> Result<PtraceAction, Error>;
+
+    /// Calls [`PtraceHook::post_exec`] of all hooks
+    fn post_exec_all(&mut self, input: &I, exit_kind: &ExitKind) -> Result<(), Error>;
+}
+
+impl<I> PtraceHooksTuple<I> for () {
+    fn on_exec_all(&mut self, _tracee: &Tracee, _input: &I) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_syscall_all(
+        &mut self,
+        _tracee: &Tracee,
+        _syscall: &Syscall,
+        _input: &I,
+    ) -> Result<PtraceAction, Error> {
+        Ok(PtraceAction::Continue)
+    }
+
+    fn on_signal_all(&mut self, _tracee: &Tracee, _signal: i32) -> Result<PtraceAction, Error> {
+        Ok(PtraceAction::Continue)
+    }
+
+    fn post_exec_all(&mut self, _input: &I, _exit_kind: &ExitKind) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<Head, Tail, I> PtraceHooksTuple<I> for (Head, Tail)
+where
+    Head: PtraceHook<I>,
+    Tail: PtraceHooksTuple<I>,
+{
+    fn on_exec_all(&mut self, tracee: &Tracee, input: &I) -> Result<(), Error> {
+        self.0.on_exec(tracee, input)?;
+        self.1.on_exec_all(tracee, input)
+    }
+
+    fn on_syscall_all(
+        &mut self,
+        tracee: &Tracee,
+        syscall: &Syscall,
+        input: &I,
+    ) -> Result<PtraceAction, Error> {
+        match self.0.on_syscall(tracee, syscall, input)? {
+            PtraceAction::Continue => self.1.on_syscall_all(tracee, syscall, input),
+            action => Ok(action),
+        }
+    }
+
+    fn on_signal_all(&mut self, tracee: &Tracee, signal: i32) -> Result<PtraceAction, Error> {
+        match self.0.on_signal(tracee, signal)? {
+            PtraceAction::Continue => self.1.on_signal_all(tracee, signal),
+            action => Ok(action),
+        }
+    }
+
+    fn post_exec_all(&mut self, input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        self.0.post_exec(input, exit_kind)?;
+        self.1.post_exec_all(input, exit_kind)
+    }
+}
+
+/// An executor running the target under `ptrace`, see the [module-level documentation](self).
+///
+/// The input is written to a file, passed on `stdin`, and in place of each [`INPUT_PLACEHOLDER`] argument.
+/// The target runs in its own process group, together with all its descendants, which are traced as well.
+/// Use [`PtraceExecutor::builder()`] to construct it.
+pub struct PtraceExecutor<H, OT, S> {
+    command: Command,
+    input_file: InputFile,
+    timeout: Duration,
+    syscalls: BTreeSet<libc::c_long>,
+    max_forks: Option<usize>,
+    hooks: H,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl PtraceExecutor<(), (), ()> {
+    /// Creates a builder for a new [`PtraceExecutor`]
+    #[must_use]
+    pub fn builder() -> PtraceExecutorBuilder {
+        PtraceExecutorBuilder::new()
+    }
+}
+
+impl<H, OT, S> Debug for PtraceExecutor<H, OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PtraceExecutor")
+            .field("command", &self.command)
+            .field("input_file", &self.input_file)
+            .field("timeout", &self.timeout)
+            .field("syscalls", &self.syscalls)
+            .field("max_forks", &self.max_forks)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H, OT, S> PtraceExecutor<H, OT, S> {
+    /// The hooks of this executor
+    #[must_use]
+    pub fn hooks(&self) -> &H {
+        &self.hooks
+    }
+
+    /// The hooks of this executor, mutable
+    pub fn hooks_mut(&mut self) -> &mut H {
+        &mut self.hooks
+    }
+
+    /// Trace the target spawned with `pid` until it exits, the timeout, or a hook stopping it
+    #[allow(clippy::too_many_lines)]
+    fn trace<I>(&mut self, pid: libc::pid_t, input: &I) -> Result<ExitKind, Error>
+    where
+        H: PtraceHooksTuple<I>,
+    {
+        // The target called `setpgid(0, 0)`, its descendants inherit the process group
+        let pgid = pid;
+        let request = if self.syscalls.is_empty() {
+            libc::PTRACE_CONT
+        } else {
+            libc::PTRACE_SYSCALL
+        };
+
+        let start = Instant::now();
+        let mut started = BTreeSet::new();
+        let mut in_syscall = BTreeSet::new();
+        let mut forks = 0;
+        let mut crashed = false;
+        let mut main_status = None;
+        // Grows while the tracees are running, so that a long execution does not keep a core busy
+        let mut poll_interval = Duration::from_micros(1);
+        loop {
+            if main_status.is_none() && start.elapsed() > self.timeout {
+                kill_group(pgid);
+                return Ok(ExitKind::Timeout);
+            }
+
+            let mut status = 0;
+            // # Safety
+            // We only wait for the process group of the target.
+            let ret = unsafe { libc::waitpid(-pgid, &mut status, libc::WNOHANG | libc::__WALL) };
+            if ret == -1 {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() == Some(libc::ECHILD) {
+                    // All tracees are gone
+                    break;
+                }
+                kill_group(pgid);
+                return Err(err.into());
+            }
+            if ret == 0 {
+                thread::sleep(poll_interval);
+                poll_interval = (poll_interval * 2).min(MAX_POLL_INTERVAL);
+                continue;
+            }
+            poll_interval = Duration::from_micros(1);
+
+            if !libc::WIFSTOPPED(status) {
+                in_syscall.remove(&ret);
+                if ret == pid {
+                    main_status = Some(status);
+                    // Do not wait for leftover descendants of the target
+                    // # Safety
+                    // Only signals the process group of the target.
+                    unsafe {
+                        libc::kill(-pgid, libc::SIGKILL);
+                    }
+                }
+                continue;
+            }
+
+            let tracee = Tracee::new(ret);
+            let signal = libc::WSTOPSIG(status);
+            let event = status >> 16;
+            let mut deliver = 0;
+            if !started.contains(&ret) {
+                // The stop after `execve` for the target, the initial `SIGSTOP` for its descendants
+                started.insert(ret);
+                if ret == pid {
+                    set_options(pid)?;
+                    self.hooks.on_exec_all(&tracee, input)?;
+                }
+            } else if event == libc::PTRACE_EVENT_FORK || event == libc::PTRACE_EVENT_VFORK {
+                forks += 1;
+                if self.max_forks.is_some_and(|max_forks| forks > max_forks) {
+                    log::debug!("The target forked more than {forks} times, stopping it");
+                    kill_group(pgid);
+                    return Ok(ExitKind::Oom);
+                }
+            } else if event != 0 {
+                // Other events, e.g. `clone` and `execve`
+            } else if signal == libc::SIGTRAP | 0x80 {
+                let exiting = !in_syscall.insert(ret);
+                if exiting {
+                    in_syscall.remove(&ret);
+                }
+                let syscall = tracee.syscall(exiting)?;
+                if self.syscalls.contains(&syscall.nr) {
+                    match self.hooks.on_syscall_all(&tracee, &syscall, input)? {
+                        PtraceAction::Continue => {}
+                        PtraceAction::Suppress => {
+                            if !exiting {
+                                tracee.skip_syscall()?;
+                            }
+                        }
+                        PtraceAction::Stop(exit_kind) => {
+                            kill_group(pgid);
+                            return Ok(exit_kind);
+                        }
+                    }
+                }
+            } else {
+                match self.hooks.on_signal_all(&tracee, signal)? {
+                    PtraceAction::Continue => {
+                        if CRASH_SIGNALS.contains(&signal) {
+                            crashed = true;
+                            match tracee.crash_context(signal) {
+                                // # Safety
+                                // The observers do not run during the execution.
+                                Ok(context) => unsafe { store_first_crash_context(context) },
+                                Err(err) => log::warn!("Failed to read the crash context: {err}"),
+                            }
+                        }
+                        deliver = signal;
+                    }
+                    PtraceAction::Suppress => {}
+                    PtraceAction::Stop(exit_kind) => {
+                        kill_group(pgid);
+                        return Ok(exit_kind);
+                    }
+                }
+            }
+            tracee.resume(request, deliver)?;
+        }
+
+        let Some(status) = main_status else {
+            return Err(Error::illegal_state("The target vanished while tracing it"));
+        };
+        Ok(if crashed {
+            ExitKind::Crash
+        } else if libc::WIFSIGNALED(status) {
+            match libc::WTERMSIG(status) {
+                libc::SIGKILL => ExitKind::Oom,
+                _ => ExitKind::Crash,
+            }
+        } else {
+            ExitKind::Ok
+        })
+    }
+}
+
+/// Set the tracing options of the target, stopped after its `execve`
+#[allow(clippy::cast_sign_loss)]
+fn set_options(pid: libc::pid_t) -> Result<(), Error> {
+    let options = libc::PTRACE_O_TRACESYSGOOD
+        | libc::PTRACE_O_TRACEFORK
+        | libc::PTRACE_O_TRACEVFORK
+        | libc::PTRACE_O_TRACECLONE
+        | libc::PTRACE_O_TRACEEXEC
+        | libc::PTRACE_O_EXITKILL;
+    // # Safety
+    // The target is stopped.
+    if unsafe {
+        libc::ptrace(
+            libc::PTRACE_SETOPTIONS,
+            pid,
+            ptr::null_mut::<libc::c_void>(),
+            options as usize as *mut libc::c_void,
+        )
+    } == -1
+    {
+        return Err(Error::last_os_error("Failed to set the ptrace options"));
+    }
+    Ok(())
+}
+
+/// Kill the process group of the target, and reap all its tracees
+fn kill_group(pgid: libc::pid_t) {
+    // # Safety
+    // Only signals and waits for the process group of the target.
+    unsafe {
+        libc::kill(-pgid, libc::SIGKILL);
+        let mut status = 0;
+        while libc::waitpid(-pgid, &mut status, libc::__WALL) > 0 {}
+    }
+}
+
+impl<EM, H, OT, S, Z> Executor<EM, Z> for PtraceExecutor<H, OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    H: PtraceHooksTuple<S::Input>,
+    OT: ObserversTuple<S::Input, S>,
+    Z: UsesState<State = S>,
+{
+    #[allow(clippy::cast_possible_wrap)]
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+        self.observers.pre_exec_child_all(state, input)?;
+
+        self.input_file.write_buf(input.target_bytes().as_slice())?;
+        self.command.stdin(File::open(&self.input_file.path)?);
+        let child = self.command.spawn()?;
+        let exit_kind = self.trace(child.id() as libc::pid_t, input)?;
+        self.hooks.post_exec_all(input, &exit_kind)?;
+
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<H, OT, S> UsesState for PtraceExecutor<H, OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<H, OT, S> HasObservers for PtraceExecutor<H, OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+/// The builder for a [`PtraceExecutor`]
+#[derive(Debug, Clone)]
+pub struct PtraceExecutorBuilder {
+    program: Option<OsString>,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    cwd: Option<PathBuf>,
+    input_file: Option<PathBuf>,
+    debug_child: bool,
+    timeout: Duration,
+    syscalls: BTreeSet<libc::c_long>,
+    max_forks: Option<usize>,
+}
+
+impl Default for PtraceExecutorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PtraceExecutorBuilder {
+    /// Create a new [`PtraceExecutorBuilder`]
+    #[must_use]
+    fn new() -> PtraceExecutorBuilder {
+        PtraceExecutorBuilder {
+            program: None,
+            args: vec![],
+            envs: vec![],
+            cwd: None,
+            input_file: None,
+            debug_child: false,
+            timeout: Duration::from_secs(5),
+            syscalls: BTreeSet::new(),
+            max_forks: None,
+        }
+    }
+
+    /// The binary of the target. This option is required.
+    #[must_use]
+    pub fn program<O: AsRef<OsStr>>(mut self, program: O) -> Self {
+        self.program = Some(program.as_ref().to_owned());
+        self
+    }
+
+    /// Adds an argument to the target's commandline, [`INPUT_PLACEHOLDER`] is replaced by the input file.
+    #[must_use]
+    pub fn arg<O: AsRef<OsStr>>(mut self, arg: O) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds a range of arguments to the target's commandline.
+    #[must_use]
+    pub fn args<IT, O>(mut self, args: IT) -> Self
+    where
+        IT: IntoIterator<Item = O>,
+        O: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    /// Adds an environment variable to the target.
+    #[must_use]
+    pub fn env<K, V>(mut self, key: K, val: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs
+            .push((key.as_ref().to_owned(), val.as_ref().to_owned()));
+        self
+    }
+
+    /// Adds a range of environment variables to the target.
+    #[must_use]
+    pub fn envs<IT, K, V>(mut self, vars: IT) -> Self
+    where
+        IT: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        for (key, val) in vars {
+            self = self.env(key, val);
+        }
+        self
+    }
+
+    /// Sets the working directory of the target.
+    #[must_use]
+    pub fn current_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.cwd = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// The file the input is written to; defaults to a unique file in the current directory
+    #[must_use]
+    pub fn input_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.input_file = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// If set to true, the target's output won't be redirecited to `/dev/null`.
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn debug_child(mut self, debug_child: bool) -> Self {
+        self.debug_child = debug_child;
+        self
+    }
+
+    /// The timeout of each execution; defaults to 5s
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Stop the target at the entry and exit of these syscalls, e.g. [`libc::SYS_read`], and pass them to
+    /// [`PtraceHook::on_syscall`].
+    ///
+    /// Tracing syscalls stops the target at every syscall, which is slow. Only supported on `x86_64` and `aarch64`.
+    #[must_use]
+    pub fn trace_syscalls<IT>(mut self, syscalls: IT) -> Self
+    where
+        IT: IntoIterator<Item = libc::c_long>,
+    {
+        self.syscalls.extend(syscalls);
+        self
+    }
+
+    /// Kill the target once it and its descendants called `fork` more than `max_forks` times in one execution,
+    /// and report the execution as [`ExitKind::Oom`], as it exhausts the resources of the host; default is unlimited
+    #[must_use]
+    pub fn max_forks(mut self, max_forks: usize) -> Self {
+        self.max_forks = Some(max_forks);
+        self
+    }
+
+    /// Builds the [`PtraceExecutor`], without hooks
+    pub fn build<OT, S>(self, observers: OT) -> Result<PtraceExecutor<(), OT, S>, Error>
+    where
+        OT: ObserversTuple<S::Input, S>,
+        S: State,
+    {
+        self.build_with_hooks(observers, ())
+    }
+
+    /// Builds the [`PtraceExecutor`] with the given tuple of [`PtraceHook`]s
+    pub fn build_with_hooks<H, OT, S>(
+        self,
+        observers: OT,
+        hooks: H,
+    ) -> Result<PtraceExecutor<H, OT, S>, Error>
+    where
+        H: PtraceHooksTuple<S::Input>,
+        OT: ObserversTuple<S::Input, S>,
+        S: State,
+    {
+        let Some(program) = self.program else {
+            return Err(Error::illegal_argument(
+                "PtraceExecutor::builder: no program set!",
+            ));
+        };
+        let input_file = InputFile::create(
+            self.input_file
+                .unwrap_or_else(|| get_unique_std_input_file().into()),
+        )?;
+
+        let input_path = input_file.path.to_string_lossy().into_owned();
+        let mut command = Command::new(program);
+        command
+            .args(self.args.iter().map(|arg| match arg.to_str() {
+                Some(arg) => arg.replace(INPUT_PLACEHOLDER, &input_path).into(),
+                None => arg.clone(),
+            }))
+            .envs(
+                self.envs
+                    .iter()
+                    .map(|(k, v)| (k.as_os_str(), v.as_os_str())),
+            );
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        if !self.debug_child {
+            command.stdout(Stdio::null());
+            command.stderr(Stdio::null());
+        }
+        // # Safety
+        // `setpgid` and `ptrace(PTRACE_TRACEME)` are async-signal-safe.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setpgid(0, 0) == -1
+                    || libc::ptrace(
+                        libc::PTRACE_TRACEME,
+                        0,
+                        ptr::null_mut::<libc::c_void>(),
+                        ptr::null_mut::<libc::c_void>(),
+                    ) == -1
+                {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        Ok(PtraceExecutor {
+            command,
+            input_file,
+            timeout: self.timeout,
+            syscalls: self.syscalls,
+            max_forks: self.max_forks,
+            hooks,
+            observers,
+            phantom: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::tuples::tuple_list;
+
+    use super::{PtraceAction, PtraceExecutor, PtraceHook, PtraceHooksTuple, Tracee};
+    use crate::{
+        events::SimpleEventManager,
+        executors::{Executor, ExitKind, HasObservers},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        monitors::SimpleMonitor,
+        observers::CrashContextObserver,
+        state::NopState,
+        Error,
+    };
+
+    struct SignalHook(PtraceAction);
+
+    impl PtraceHook<()> for SignalHook {
+        fn on_signal(&mut self, _tracee: &Tracee, _signal: i32) -> Result<PtraceAction, Error> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn test_ptrace_hooks_tuple() {
+        let tracee = Tracee::new(0);
+        let mut hooks = tuple_list!(
+            SignalHook(PtraceAction::Continue),
+            SignalHook(PtraceAction::Stop(ExitKind::Crash)),
+            SignalHook(PtraceAction::Suppress)
+        );
+        assert_eq!(
+            hooks.on_signal_all(&tracee, libc::SIGSEGV).unwrap(),
+            PtraceAction::Stop(ExitKind::Crash)
+        );
+        assert_eq!(
+            tuple_list!(SignalHook(PtraceAction::Continue))
+                .on_signal_all(&tracee, libc::SIGSEGV)
+                .unwrap(),
+            PtraceAction::Continue
+        );
+        assert_eq!(
+            PtraceHooksTuple::<()>::on_signal_all(&mut (), &tracee, libc::SIGSEGV).unwrap(),
+            PtraceAction::Continue
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_trace_crashing_child() {
+        let mut mgr = SimpleEventManager::new(SimpleMonitor::new(|status| {
+            log::info!("{status}");
+        }));
+        let mut executor = PtraceExecutor::builder()
+            .program("sh")
+            .args(["-c", "kill -SEGV $$"])
+            .build(tuple_list!(CrashContextObserver::new("crash_context")))
+            .unwrap();
+
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut NopState::new(),
+                &mut mgr,
+                &BytesInput::new(b"test".to_vec()),
+            )
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Crash);
+        let context = executor.observers().0.context().unwrap();
+        assert_eq!(context.signal, libc::SIGSEGV);
+    }
+}
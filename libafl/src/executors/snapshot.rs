@@ -0,0 +1,426 @@
+//! The [`SnapshotExecutor`] wraps an in-process executor and resets the writable memory of the target
+//! to a snapshot before each run.
+//!
+//! The snapshot is taken lazily, right before the first execution, so any initialization the harness
+//! did until then is part of it. Writes are tracked with the kernel's soft-dirty page bits
+//! (see `Documentation/admin-guide/mm/soft-dirty.rst`), so only pages that were actually touched by
+//! the target get copied back. This gives stateful in-process targets the semantics of a fresh
+//! `fork()` per execution, without paying for it.
+//!
+//! Only global data is restored, heap allocations done by the target are *not* rolled back.
+//!
+//! The memory is restored right before the next run, not right after the last one: the observers, e.g. a
+//! coverage map in the globals of the snapshot, are only read by the fuzzer once the run returned. The
+//! fuzzer runs in the same process, so prefer [`SnapshotExecutor::with_modules`] with the modules of the
+//! target, otherwise the globals of the fuzzer itself are rolled back as well.
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Debug, Formatter};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    os::unix::fs::FileExt,
+};
+
+use libafl_bolts::tuples::RefIndexable;
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    state::UsesState,
+    Error,
+};
+
+/// Bit 55 of a `/proc/self/pagemap` entry: the page was written since the last soft-dirty clear
+const PAGEMAP_SOFT_DIRTY: u64 = 1 << 55;
+
+/// A single writable memory region, together with the contents it had when the snapshot was taken
+struct SnapshotRegion {
+    start: usize,
+    data: Vec<u8>,
+}
+
+/// A snapshot of (parts of) the writable memory of the current process.
+pub struct MemorySnapshot {
+    regions: Vec<SnapshotRegion>,
+    page_size: usize,
+    pagemap: File,
+}
+
+impl Debug for MemorySnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemorySnapshot")
+            .field("regions", &self.regions.len())
+            .field("bytes", &self.size())
+            .field("page_size", &self.page_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MemorySnapshot {
+    /// Take a snapshot of all private, writable mappings whose path contains one of the given `modules`.
+    ///
+    /// Anonymous mappings directly following a matched mapping (usually the `.bss`) are included as well.
+    /// If `modules` is empty, the main executable is used.
+    pub fn take(modules: &[String]) -> Result<Self, Error> {
+        let modules = if modules.is_empty() {
+            vec![std::env::current_exe()?.to_string_lossy().into_owned()]
+        } else {
+            modules.to_vec()
+        };
+
+        // # Safety
+        // `sysconf` has no preconditions
+        #[allow(clippy::cast_sign_loss)]
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+
+        let maps = fs::read_to_string("/proc/self/maps")?;
+        let mut regions = Vec::new();
+        let mut prev_end: Option<usize> = None;
+        for line in maps.lines() {
+            let mut fields = line.split_whitespace();
+            let (Some(range), Some(perms)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            // offset, dev, inode
+            let path = fields.nth(3).unwrap_or("");
+
+            let Some((start, end)) = range.split_once('-') else {
+                continue;
+            };
+            let start = usize::from_str_radix(start, 16)
+                .map_err(|_| Error::illegal_state(format!("Invalid mapping {line}")))?;
+            let end = usize::from_str_radix(end, 16)
+                .map_err(|_| Error::illegal_state(format!("Invalid mapping {line}")))?;
+
+            let matches = modules.iter().any(|m| path.contains(m.as_str()));
+            let follows_module = path.is_empty() && prev_end == Some(start);
+            prev_end = if matches || follows_module {
+                Some(end)
+            } else {
+                None
+            };
+
+            if !perms.starts_with("rw") || !perms.ends_with('p') || !(matches || follows_module) {
+                continue;
+            }
+
+            // # Safety
+            // The mapping is readable, as reported by the kernel.
+            let data = unsafe { core::slice::from_raw_parts(start as *const u8, end - start) };
+            regions.push(SnapshotRegion {
+                start,
+                data: data.to_vec(),
+            });
+        }
+
+        if regions.is_empty() {
+            return Err(Error::illegal_argument(format!(
+                "No writable mappings found for modules {modules:?}"
+            )));
+        }
+
+        let snapshot = Self {
+            regions,
+            page_size,
+            pagemap: File::open("/proc/self/pagemap")?,
+        };
+        Self::clear_soft_dirty()?;
+        Ok(snapshot)
+    }
+
+    /// The amount of bytes held by this snapshot
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.regions.iter().map(|r| r.data.len()).sum()
+    }
+
+    /// Restore all pages written to since the snapshot (or the last restore), and reset write tracking.
+    ///
+    /// Returns the number of restored pages.
+    pub fn restore(&mut self) -> Result<usize, Error> {
+        let mut restored = 0;
+        let mut entries = Vec::new();
+        for region in &self.regions {
+            let pages = region.data.len() / self.page_size;
+            entries.resize(pages * 8, 0);
+            self.pagemap
+                .read_exact_at(&mut entries, (region.start / self.page_size * 8) as u64)?;
+
+            for (i, entry) in entries.chunks_exact(8).enumerate() {
+                let entry = u64::from_ne_bytes(entry.try_into().unwrap());
+                if entry & PAGEMAP_SOFT_DIRTY == 0 {
+                    continue;
+                }
+                let offset = i * self.page_size;
+                // # Safety
+                // The region was mapped writable when we took the snapshot and the target does not unmap globals.
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        region.data.as_ptr().add(offset),
+                        (region.start + offset) as *mut u8,
+                        self.page_size,
+                    );
+                }
+                restored += 1;
+            }
+        }
+        Self::clear_soft_dirty()?;
+        Ok(restored)
+    }
+
+    /// Reset the soft-dirty bits of all pages of this process
+    fn clear_soft_dirty() -> Result<(), Error> {
+        OpenOptions::new()
+            .write(true)
+            .open("/proc/self/clear_refs")?
+            .write_all(b"4")?;
+        Ok(())
+    }
+}
+
+/// An executor wrapper that restores the writable global data of the target before each run.
+///
+/// Wrap an [`crate::executors::InProcessExecutor`] with it to fuzz targets that keep state in globals.
+pub struct SnapshotExecutor<E> {
+    executor: E,
+    modules: Vec<String>,
+    snapshot: Option<MemorySnapshot>,
+}
+
+impl<E> Debug for SnapshotExecutor<E>
+where
+    E: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotExecutor")
+            .field("executor", &self.executor)
+            .field("modules", &self.modules)
+            .field("snapshot", &self.snapshot)
+            .finish()
+    }
+}
+
+impl<E> SnapshotExecutor<E> {
+    /// Create a new [`SnapshotExecutor`] that snapshots the writable data of the main executable
+    #[must_use]
+    pub fn new(executor: E) -> Self {
+        Self::with_modules(executor, Vec::new())
+    }
+
+    /// Create a new [`SnapshotExecutor`] that snapshots the writable data of the given modules.
+    ///
+    /// A mapping is part of the snapshot if its path contains any of the `modules`.
+    #[must_use]
+    pub fn with_modules(executor: E, modules: Vec<String>) -> Self {
+        Self {
+            executor,
+            modules,
+            snapshot: None,
+        }
+    }
+
+    /// The snapshot, if it was taken already
+    #[must_use]
+    pub fn snapshot(&self) -> Option<&MemorySnapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// Drop the current snapshot, without restoring it. A new one will be taken before the next execution.
+    pub fn reset_snapshot(&mut self) {
+        self.snapshot = None;
+    }
+
+    /// The wrapped executor
+    pub fn inner(&mut self) -> &mut E {
+        &mut self.executor
+    }
+}
+
+impl<E> UsesState for SnapshotExecutor<E>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Executor<EM, Z> for SnapshotExecutor<E>
+where
+    E: Executor<EM, Z>,
+    EM: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        match self.snapshot.as_mut() {
+            // The observers of the last run were processed by now
+            Some(snapshot) => {
+                snapshot.restore()?;
+            }
+            None => self.snapshot = Some(MemorySnapshot::take(&self.modules)?),
+        }
+        self.executor.run_target(fuzzer, state, mgr, input)
+    }
+}
+
+impl<E> HasObservers for SnapshotExecutor<E>
+where
+    E: HasObservers,
+{
+    type Observers = E::Observers;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        self.executor.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        self.executor.observers_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use core::{ptr, slice};
+    use std::{env, fs, os::fd::AsRawFd, process};
+
+    use super::{MemorySnapshot, SnapshotExecutor};
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        state::{NopState, UsesState},
+        Error,
+    };
+
+    /// A private, writable file mapping, standing in for the globals of a target module
+    struct Globals {
+        name: String,
+        ptr: *mut u8,
+        len: usize,
+    }
+
+    impl Globals {
+        fn new(test: &str, pages: usize) -> Self {
+            let name = format!("libafl_snapshot_{test}_{}", process::id());
+            let path = env::temp_dir().join(&name);
+            // # Safety
+            // `sysconf` has no preconditions
+            #[allow(clippy::cast_sign_loss)]
+            let len = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize * pages;
+            fs::write(&path, vec![0; len]).unwrap();
+            let file = fs::File::open(&path).unwrap();
+            // # Safety
+            // Maps a new region, backed by the file we just wrote.
+            let ptr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            assert_ne!(ptr, libc::MAP_FAILED);
+            fs::remove_file(path).unwrap();
+            Self {
+                name,
+                ptr: ptr.cast(),
+                len,
+            }
+        }
+
+        fn bytes(&mut self) -> &mut [u8] {
+            // # Safety
+            // The mapping lives as long as `self`.
+            unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+        }
+    }
+
+    impl Drop for Globals {
+        fn drop(&mut self) {
+            // # Safety
+            // Nothing refers to the mapping anymore.
+            unsafe {
+                libc::munmap(self.ptr.cast(), self.len);
+            }
+        }
+    }
+
+    /// Counts its runs in the first byte of the globals
+    struct CountingExecutor {
+        ptr: *mut u8,
+    }
+
+    impl UsesState for CountingExecutor {
+        type State = NopState<BytesInput>;
+    }
+
+    impl<EM, Z> Executor<EM, Z> for CountingExecutor
+    where
+        EM: UsesState<State = Self::State>,
+        Z: UsesState<State = Self::State>,
+    {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut Self::State,
+            _mgr: &mut EM,
+            _input: &Self::Input,
+        ) -> Result<ExitKind, Error> {
+            // # Safety
+            // The mapping outlives the executor.
+            unsafe {
+                *self.ptr += 1;
+            }
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_memory_snapshot() {
+        let mut globals = Globals::new("memory", 4);
+        globals.bytes()[1] = 7;
+        let mut snapshot = MemorySnapshot::take(&[globals.name.clone()]).unwrap();
+        assert_eq!(snapshot.size(), globals.len);
+
+        let page_size = globals.len / 4;
+        globals.bytes()[1] = 1;
+        globals.bytes()[2 * page_size] = 2;
+        assert_eq!(snapshot.restore().unwrap(), 2);
+        assert_eq!(globals.bytes()[1], 7);
+        assert_eq!(globals.bytes()[2 * page_size], 0);
+        assert_eq!(snapshot.restore().unwrap(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_snapshot_executor() {
+        let mut globals = Globals::new("executor", 1);
+        let mut executor = SnapshotExecutor::with_modules(
+            CountingExecutor { ptr: globals.ptr },
+            vec![globals.name.clone()],
+        );
+        let input = BytesInput::new(b"test".to_vec());
+        for _ in 0..3 {
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut NopState::new(),
+                    &mut NopEventManager::new(),
+                    &input,
+                )
+                .unwrap();
+            // Still visible after the run, for the observers
+            assert_eq!(globals.bytes()[0], 1);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! An in-process executor running a pure, reentrant harness on multiple threads, see [`ThreadPoolExecutor`].
+
+use alloc::vec::Vec;
+use core::{fmt::Debug, marker::PhantomData, num::NonZeroUsize};
+use std::thread;
+
+use libafl_bolts::tuples::RefIndexable;
+
+use crate::{
+    executors::{BatchExecutor, BatchResult, Executor, ExitKind, HasObservers},
+    inputs::UsesInput,
+    observers::ObserversTuple,
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// The default amount of inputs each worker thread runs per batch
+pub const DEFAULT_INPUTS_PER_THREAD: usize = 16;
+
+/// An in-process executor for pure, reentrant harness functions, running the inputs of a batch concurrently.
+///
+/// Instead of a global coverage map, the harness gets its own copy of the observers for each input, and writes its
+/// observations into them. The observers of all inputs are handed back in order, so a single client uses all cores
+/// without the overhead of a [`crate::events::launcher::Launcher`] running multiple processes.
+/// Use it with the [`crate::stages::BatchMutationalStage`] to run mutants concurrently, a single
+/// [`Executor::run_target`] runs on the calling thread.
+///
+/// The `pre_exec` and `post_exec` hooks of the observers run on the calling thread, only the harness runs on the
+/// worker threads. The workers are scoped to each batch. There is no timeout, and a panic of the harness is not
+/// caught, it takes down the fuzzer like in the [`crate::executors::InProcessExecutor`]; a harness touching global
+/// state needs a forking executor.
+///
+/// ```rust
+/// # use core::num::NonZeroUsize;
+/// # use libafl::{executors::{ExitKind, ThreadPoolExecutor}, inputs::{BytesInput, HasTargetBytes}, observers::{MapObserver, StdMapObserver}, state::NopState};
+/// # use libafl_bolts::{tuples::tuple_list, AsSlice};
+/// let observers = tuple_list!(StdMapObserver::owned("edges", vec![0_u8; 256]));
+/// let executor = ThreadPoolExecutor::<_, _, NopState<BytesInput>>::new(
+///     |input: &BytesInput, (edges, ()): &mut (StdMapObserver<u8, false>, ())| {
+///         for byte in input.target_bytes().as_slice() {
+///             edges.set(usize::from(*byte), 1);
+///         }
+///         ExitKind::Ok
+///     },
+///     observers,
+///     NonZeroUsize::new(4).unwrap(),
+/// );
+/// ```
+pub struct ThreadPoolExecutor<H, OT, S> {
+    harness: H,
+    observers: OT,
+    threads: NonZeroUsize,
+    max_batch_size: usize,
+    phantom: PhantomData<S>,
+}
+
+impl<H, OT, S> Debug for ThreadPoolExecutor<H, OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ThreadPoolExecutor")
+            .field("observers", &self.observers)
+            .field("threads", &self.threads)
+            .field("max_batch_size", &self.max_batch_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H, OT, S> ThreadPoolExecutor<H, OT, S> {
+    /// Creates a new [`ThreadPoolExecutor`], running batches of [`DEFAULT_INPUTS_PER_THREAD`] inputs per thread
+    pub fn new(harness: H, observers: OT, threads: NonZeroUsize) -> Self {
+        Self {
+            harness,
+            observers,
+            threads,
+            max_batch_size: threads.get() * DEFAULT_INPUTS_PER_THREAD,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the maximum amount of inputs of a batch
+    #[must_use]
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size;
+        self
+    }
+
+    /// The amount of worker threads
+    #[must_use]
+    pub fn threads(&self) -> NonZeroUsize {
+        self.threads
+    }
+
+    /// The harness
+    #[must_use]
+    pub fn harness(&self) -> &H {
+        &self.harness
+    }
+}
+
+impl<H, OT, S> UsesState for ThreadPoolExecutor<H, OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<H, OT, S> HasObservers for ThreadPoolExecutor<H, OT, S>
+where
+    OT: ObserversTuple<S::Input, S>,
+    S: State,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+impl<EM, H, OT, S, Z> Executor<EM, Z> for ThreadPoolExecutor<H, OT, S>
+where
+    EM: UsesState<State = S>,
+    H: Fn(&S::Input, &mut OT) -> ExitKind,
+    S: State + HasExecutions,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+        Ok((self.harness)(input, &mut self.observers))
+    }
+}
+
+impl<EM, H, OT, S, Z> BatchExecutor<EM, Z> for ThreadPoolExecutor<H, OT, S>
+where
+    EM: UsesState<State = S>,
+    H: Fn(&S::Input, &mut OT) -> ExitKind + Sync,
+    OT: ObserversTuple<S::Input, S> + Clone + Send,
+    S: State + HasExecutions,
+    S::Input: Sync,
+    Z: UsesState<State = S>,
+{
+    fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    fn run_batch(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        inputs: &[Self::Input],
+    ) -> Result<Vec<BatchResult<Self::Observers>>, Error> {
+        // Each input gets its own observers, reset on this thread
+        let mut results = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let mut observers = self.observers.clone();
+            observers.pre_exec_all(state, input)?;
+            results.push((ExitKind::Ok, observers));
+        }
+
+        let chunk_size = inputs.len().div_ceil(self.threads.get()).max(1);
+        let harness = &self.harness;
+        thread::scope(|scope| {
+            for (inputs, results) in inputs
+                .chunks(chunk_size)
+                .zip(results.chunks_mut(chunk_size))
+            {
+                scope.spawn(move || {
+                    for (input, (exit_kind, observers)) in inputs.iter().zip(results) {
+                        *exit_kind = harness(input, observers);
+                    }
+                });
+            }
+        });
+        *state.executions_mut() += inputs.len() as u64;
+
+        for (input, (exit_kind, observers)) in inputs.iter().zip(&mut results) {
+            observers.post_exec_all(state, input, exit_kind)?;
+        }
+        // Leave the observers of the executor as after running the batch sequentially
+        if let Some((_, observers)) = results.last() {
+            self.observers = observers.clone();
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+    use core::num::NonZeroUsize;
+
+    use libafl_bolts::{tuples::tuple_list, AsSlice};
+
+    use crate::{
+        events::NopEventManager,
+        executors::{BatchExecutor, ExitKind, ThreadPoolExecutor},
+        fuzzer::NopFuzzer,
+        inputs::{BytesInput, HasTargetBytes},
+        observers::{MapObserver, StdMapObserver},
+        state::{HasExecutions, NopState},
+    };
+
+    #[test]
+    fn test_thread_pool_executor() {
+        let mut executor = ThreadPoolExecutor::<_, _, NopState<BytesInput>>::new(
+            |input: &BytesInput, (map, ()): &mut (StdMapObserver<u8, false>, ())| {
+                map.set(input.target_bytes().as_slice().len(), 1);
+                ExitKind::Ok
+            },
+            tuple_list!(StdMapObserver::owned("map", vec![0_u8; 16])),
+            NonZeroUsize::new(3).unwrap(),
+        );
+        let inputs = (0..8)
+            .map(|len| BytesInput::new(vec![b'a'; len]))
+            .collect::<Vec<_>>();
+
+        let mut state = NopState::new();
+        let results = executor
+            .run_batch(
+                &mut NopFuzzer::new(),
+                &mut state,
+                &mut NopEventManager::new(),
+                &inputs,
+            )
+            .unwrap();
+        assert_eq!(results.len(), inputs.len());
+        for (len, (exit_kind, (map, ()))) in results.iter().enumerate() {
+            assert_eq!(*exit_kind, ExitKind::Ok);
+            assert_eq!(map.count_bytes(), 1);
+            assert_eq!(map.get(len), 1);
+        }
+        assert_eq!(*state.executions(), 8);
+    }
+}
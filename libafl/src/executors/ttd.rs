@@ -0,0 +1,471 @@
+//! The [`TtdExecutor`] collects the coverage of closed-source Windows binaries with
+//! [Time Travel Debugging](https://learn.microsoft.com/en-us/windows-hardware/drivers/debuggercmds/time-travel-debugging-overview) (TTD),
+//! for targets that neither `TinyInst` nor `Frida` can instrument.
+//!
+//! Each input is run under the TTD recorder (`TTD.exe`), and the recording is replayed by `cdb.exe`, which lists the
+//! calls of the target with the TTD data model. The coverage is made of *call edges*: the pair of the return address
+//! and the called function, relative to their modules, so that it is stable under ASLR. This is coarser than
+//! basic block coverage, and recording and replaying take a lot longer than the execution itself: expect a few
+//! executions per second at best.
+//!
+//! The execution is a crash if the recording contains a fatal exception, such as an access violation or a
+//! fail-fast; first-chance exceptions handled by the target count as well.
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    slice,
+    time::Duration,
+};
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use libafl_bolts::{hash_std, tuples::RefIndexable, AsSlice};
+use wait_timeout::ChildExt;
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::HasTargetBytes,
+    observers::{ObserversTuple, StdMapObserver},
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// The placeholder in the arguments of the target for the file containing the input
+pub const INPUT_PLACEHOLDER: &str = "@@";
+
+/// The exception codes considered crashes
+const FATAL_EXCEPTIONS: [u32; 7] = [
+    // STATUS_ACCESS_VIOLATION
+    0xC000_0005,
+    // STATUS_ILLEGAL_INSTRUCTION
+    0xC000_001D,
+    // STATUS_INTEGER_DIVIDE_BY_ZERO
+    0xC000_0094,
+    // STATUS_PRIVILEGED_INSTRUCTION
+    0xC000_0096,
+    // STATUS_STACK_OVERFLOW
+    0xC000_00FD,
+    // STATUS_HEAP_CORRUPTION
+    0xC000_0374,
+    // STATUS_STACK_BUFFER_OVERRUN, raised by `__fastfail`
+    0xC000_0409,
+];
+
+/// The `cdb` script printing the calls and exceptions of the loaded recording
+const COVERAGE_SCRIPT: &str = r#""use strict";
+
+function moduleOffset(modules, address) {
+    for (const module of modules) {
+        const end = module.BaseAddress.add(module.Size);
+        if (address.compareTo(module.BaseAddress) >= 0 && address.compareTo(end) < 0) {
+            return module.Name + "+" + address.subtract(module.BaseAddress).toString(16);
+        }
+    }
+    return "?+" + address.toString(16);
+}
+
+function libaflCoverage(pattern) {
+    const session = host.currentSession;
+    const modules = Array.from(host.currentProcess.Modules);
+    for (const call of session.TTD.Calls(pattern)) {
+        host.diagnostics.debugLog(
+            "CALL " + moduleOffset(modules, call.ReturnAddress) + " " + moduleOffset(modules, call.FunctionAddress) + "\n"
+        );
+    }
+    for (const event of session.TTD.Events) {
+        if (event.Type == "Exception") {
+            host.diagnostics.debugLog("EXCEPTION " + event.Exception.Code.toString(16) + "\n");
+        }
+    }
+}
+"#;
+
+/// The coverage and exceptions of a replayed recording
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Replay {
+    edges: Vec<u64>,
+    exceptions: Vec<u32>,
+}
+
+/// Parse the output of [`COVERAGE_SCRIPT`]
+fn parse_replay(output: &str) -> Replay {
+    let mut replay = Replay::default();
+    for line in output.lines() {
+        if let Some(edge) = line.trim().strip_prefix("CALL ") {
+            replay.edges.push(hash_std(edge.as_bytes()));
+        } else if let Some(code) = line.trim().strip_prefix("EXCEPTION ") {
+            if let Ok(code) = u32::from_str_radix(code.trim_start_matches("0x"), 16) {
+                replay.exceptions.push(code);
+            }
+        }
+    }
+    replay
+}
+
+/// An executor recording each run of the target with TTD, see the [module-level documentation](self).
+///
+/// Use [`TtdExecutor::builder()`] to construct it, and [`TtdExecutor::observer`] to observe its coverage map.
+pub struct TtdExecutor<OT, S> {
+    program: OsString,
+    args: Vec<OsString>,
+    ttd: PathBuf,
+    cdb: PathBuf,
+    pattern: String,
+    work_dir: PathBuf,
+    input_file: PathBuf,
+    script_file: PathBuf,
+    timeout: Duration,
+    map_ptr: *mut u8,
+    map_size: usize,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl TtdExecutor<(), ()> {
+    /// Creates a builder for a new [`TtdExecutor`]
+    #[must_use]
+    pub fn builder() -> TtdExecutorBuilder {
+        TtdExecutorBuilder::new()
+    }
+}
+
+impl<OT, S> Debug for TtdExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TtdExecutor")
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("ttd", &self.ttd)
+            .field("cdb", &self.cdb)
+            .field("pattern", &self.pattern)
+            .field("work_dir", &self.work_dir)
+            .field("timeout", &self.timeout)
+            .field("map_size", &self.map_size)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> TtdExecutor<OT, S> {
+    /// A map observer of the call edge coverage collected by this executor
+    #[must_use]
+    pub fn observer(&self, name: &'static str) -> StdMapObserver<'static, u8, false> {
+        // # Safety
+        // The map is leaked, it is valid for the rest of the program.
+        // The executor only writes to it during the execution, while the observers do not run.
+        unsafe { StdMapObserver::from_mut_ptr(name, self.map_ptr, self.map_size) }
+    }
+
+    /// Record the target on the current input, returns `None` on timeout
+    fn record(&self, trace_dir: &Path) -> Result<Option<()>, Error> {
+        let input_path = self.input_file.to_string_lossy();
+        let mut command = Command::new(&self.ttd);
+        command
+            .arg("-accepteula")
+            .arg("-out")
+            .arg(trace_dir)
+            .arg("-launch")
+            .arg(&self.program)
+            .args(self.args.iter().map(|arg| match arg.to_str() {
+                Some(arg) => arg.replace(INPUT_PLACEHOLDER, &input_path).into(),
+                None => arg.clone(),
+            }))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        let mut child = command.spawn()?;
+        if child.wait_timeout(self.timeout)?.is_none() {
+            drop(child.kill());
+            drop(child.wait());
+            return Ok(None);
+        }
+        Ok(Some(()))
+    }
+
+    /// Replay the recording in `trace_dir`
+    fn replay(&self, trace_dir: &Path) -> Result<Replay, Error> {
+        let Some(trace) = fs::read_dir(trace_dir)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .find(|path| path.extension().is_some_and(|ext| ext == "run"))
+        else {
+            return Err(Error::illegal_state(format!(
+                "TTD did not write a recording to {}",
+                trace_dir.display()
+            )));
+        };
+        let commands = format!(
+            ".scriptload {}; dx @$scriptContents.libaflCoverage(\"{}\"); q",
+            self.script_file.display(),
+            self.pattern
+        );
+        let output = Command::new(&self.cdb)
+            .arg("-z")
+            .arg(&trace)
+            .arg("-c")
+            .arg(commands)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        Ok(parse_replay(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for TtdExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    OT: ObserversTuple<S::Input, S>,
+    Z: UsesState<State = S>,
+{
+    #[allow(clippy::cast_possible_truncation)]
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+        self.observers.pre_exec_child_all(state, input)?;
+
+        fs::write(&self.input_file, input.target_bytes().as_slice())?;
+        let trace_dir = self.work_dir.join("trace");
+        if trace_dir.exists() {
+            fs::remove_dir_all(&trace_dir)?;
+        }
+        fs::create_dir_all(&trace_dir)?;
+
+        let exit_kind = if self.record(&trace_dir)?.is_none() {
+            ExitKind::Timeout
+        } else {
+            let replay = self.replay(&trace_dir)?;
+            // # Safety
+            // The map is leaked, and the observers do not run during the execution.
+            let map = unsafe { slice::from_raw_parts_mut(self.map_ptr, self.map_size) };
+            for edge in &replay.edges {
+                let entry = &mut map[(*edge % self.map_size as u64) as usize];
+                *entry = entry.saturating_add(1);
+            }
+            if replay
+                .exceptions
+                .iter()
+                .any(|code| FATAL_EXCEPTIONS.contains(code))
+            {
+                ExitKind::Crash
+            } else {
+                ExitKind::Ok
+            }
+        };
+
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<OT, S> UsesState for TtdExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<OT, S> HasObservers for TtdExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+/// The builder for a [`TtdExecutor`]
+#[derive(Debug, Clone)]
+pub struct TtdExecutorBuilder {
+    program: Option<OsString>,
+    args: Vec<OsString>,
+    ttd: PathBuf,
+    cdb: PathBuf,
+    pattern: Option<String>,
+    work_dir: Option<PathBuf>,
+    timeout: Duration,
+    map_size: usize,
+}
+
+impl Default for TtdExecutorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TtdExecutorBuilder {
+    /// Create a new [`TtdExecutorBuilder`]
+    #[must_use]
+    fn new() -> TtdExecutorBuilder {
+        TtdExecutorBuilder {
+            program: None,
+            args: vec![],
+            ttd: PathBuf::from("TTD.exe"),
+            cdb: PathBuf::from("cdb.exe"),
+            pattern: None,
+            work_dir: None,
+            timeout: Duration::from_secs(30),
+            map_size: 1 << 16,
+        }
+    }
+
+    /// The binary of the target. This option is required.
+    #[must_use]
+    pub fn program<O: AsRef<OsStr>>(mut self, program: O) -> Self {
+        self.program = Some(program.as_ref().to_owned());
+        self
+    }
+
+    /// Adds an argument to the target's commandline, [`INPUT_PLACEHOLDER`] is replaced by the input file.
+    #[must_use]
+    pub fn arg<O: AsRef<OsStr>>(mut self, arg: O) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds a range of arguments to the target's commandline.
+    #[must_use]
+    pub fn args<IT, O>(mut self, args: IT) -> Self
+    where
+        IT: IntoIterator<Item = O>,
+        O: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    /// The TTD recorder; defaults to `TTD.exe` in the `PATH`. Recording needs administrator rights.
+    #[must_use]
+    pub fn ttd<P: AsRef<Path>>(mut self, ttd: P) -> Self {
+        self.ttd = ttd.as_ref().to_owned();
+        self
+    }
+
+    /// The console debugger replaying the recordings; defaults to `cdb.exe` in the `PATH`
+    #[must_use]
+    pub fn cdb<P: AsRef<Path>>(mut self, cdb: P) -> Self {
+        self.cdb = cdb.as_ref().to_owned();
+        self
+    }
+
+    /// The functions whose calls are covered, in the syntax of `TTD.Calls`, e.g. `target!*`;
+    /// defaults to all functions of the module of the target.
+    ///
+    /// Functions are found by their symbols, so without a `.pdb`, only exported functions are covered.
+    #[must_use]
+    pub fn pattern<P: Into<String>>(mut self, pattern: P) -> Self {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    /// The directory for the input, the recording and the replay script; defaults to a directory in the temp dir
+    #[must_use]
+    pub fn work_dir<P: AsRef<Path>>(mut self, work_dir: P) -> Self {
+        self.work_dir = Some(work_dir.as_ref().to_owned());
+        self
+    }
+
+    /// The timeout of recording an execution; defaults to 30s
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The number of entries of the coverage map; defaults to `65536`
+    #[must_use]
+    pub fn map_size(mut self, map_size: usize) -> Self {
+        self.map_size = map_size;
+        self
+    }
+
+    /// Builds the [`TtdExecutor`]
+    pub fn build<OT, S>(self, observers: OT) -> Result<TtdExecutor<OT, S>, Error>
+    where
+        OT: ObserversTuple<S::Input, S>,
+        S: State,
+    {
+        let Some(program) = self.program else {
+            return Err(Error::illegal_argument(
+                "TtdExecutor::builder: no program set!",
+            ));
+        };
+        if self.map_size == 0 {
+            return Err(Error::illegal_argument(
+                "TtdExecutor::builder: the map size must not be 0!",
+            ));
+        }
+        let pattern = self.pattern.unwrap_or_else(|| {
+            let module = Path::new(&program)
+                .file_stem()
+                .map_or_else(|| "*".into(), |stem| stem.to_string_lossy().into_owned());
+            format!("{module}!*")
+        });
+        let work_dir = self
+            .work_dir
+            .unwrap_or_else(|| env::temp_dir().join(format!("libafl_ttd_{}", std::process::id())));
+        fs::create_dir_all(&work_dir)?;
+        let script_file = work_dir.join("libafl_coverage.js");
+        fs::write(&script_file, COVERAGE_SCRIPT)?;
+
+        let map = Vec::leak(vec![0_u8; self.map_size]);
+        Ok(TtdExecutor {
+            program,
+            args: self.args,
+            ttd: self.ttd,
+            cdb: self.cdb,
+            pattern,
+            input_file: work_dir.join(".cur_input"),
+            script_file,
+            work_dir,
+            timeout: self.timeout,
+            map_ptr: map.as_mut_ptr(),
+            map_size: self.map_size,
+            observers,
+            phantom: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_replay;
+
+    #[test]
+    fn test_parse_replay() {
+        let replay = parse_replay(
+            "Microsoft (R) Windows Debugger\n\
+             CALL target+1a2b target+3000\n\
+             CALL ?+7ff00000 target+3000\n\
+             EXCEPTION c0000005\n\
+             EXCEPTION 80000003\n",
+        );
+        assert_eq!(replay.edges.len(), 2);
+        assert_ne!(replay.edges[0], replay.edges[1]);
+        assert_eq!(replay.exceptions, [0xC000_0005, 0x8000_0003]);
+    }
+}
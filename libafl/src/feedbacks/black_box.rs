@@ -0,0 +1,261 @@
+//! The [`BlackBoxFeedback`] gives minimal guidance to fuzzing a target without any instrumentation.
+//!
+//! Without coverage, the only observable behavior of a target is how it exits, what it prints, and how long it takes.
+//! The feedback keeps the inputs leading to a new combination of these, ignoring the numbers in the output,
+//! such as addresses, pids, or timestamps, which would make nearly every run look new.
+
+use alloc::{borrow::Cow, string::ToString, vec::Vec};
+use core::time::Duration;
+
+use hashbrown::HashSet;
+use libafl_bolts::{
+    hash_std, impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::{ExitStatusObserver, StdErrObserver, StdOutObserver, TimeObserver},
+    Error, HasNamedMetadata,
+};
+
+/// The prefix of the metadata names
+pub const BLACKBOXFEEDBACK_PREFIX: &str = "blackboxfeedback_metadata_";
+
+/// The state of a [`BlackBoxFeedback`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct BlackBoxFeedbackMetadata {
+    /// The hashes of the behaviors seen so far
+    pub signatures: HashSet<u64>,
+}
+
+impl_serdeany!(BlackBoxFeedbackMetadata);
+
+/// Normalizes the output of a target, replacing each run of digits with a single `0`,
+/// and each hex number starting with `0x` with `0x0`.
+#[must_use]
+pub fn normalize_output(output: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(output.len());
+    let mut i = 0;
+    while i < output.len() {
+        let hex = output[i] == b'0'
+            && matches!(output.get(i + 1), Some(b'x' | b'X'))
+            && output.get(i + 2).is_some_and(u8::is_ascii_hexdigit);
+        if hex {
+            normalized.extend_from_slice(b"0x0");
+            i += 2;
+            while output.get(i).is_some_and(u8::is_ascii_hexdigit) {
+                i += 1;
+            }
+        } else if output[i].is_ascii_digit() {
+            normalized.push(b'0');
+            while output.get(i).is_some_and(u8::is_ascii_digit) {
+                i += 1;
+            }
+        } else {
+            normalized.push(output[i]);
+            i += 1;
+        }
+    }
+    normalized
+}
+
+/// The logarithmic bucket of an execution time; `0` below a millisecond, the bit length of the milliseconds otherwise
+#[must_use]
+pub fn time_bucket(time: Duration) -> u32 {
+    128 - time.as_millis().leading_zeros()
+}
+
+/// A [`BlackBoxFeedback`] considers a run interesting if it shows a behavior not seen before: a new combination
+/// of the [`ExitKind`], the exit code and signal, the normalized output, and the bucket of the execution time.
+///
+/// Each part is optional, set the observers to take into account with the builder methods.
+/// Since runs are only told apart by these few observations, this is a last resort for targets without any kind of
+/// coverage, see [`normalize_output`] and [`time_bucket`]. The time bucket in particular can make a noisy target
+/// flood the corpus, leave it out if it does.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlackBoxFeedback {
+    name: Cow<'static, str>,
+    exit_status: Option<Handle<ExitStatusObserver>>,
+    stdout: Option<Handle<StdOutObserver>>,
+    stderr: Option<Handle<StdErrObserver>>,
+    time: Option<Handle<TimeObserver>>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl BlackBoxFeedback {
+    /// Create a new [`BlackBoxFeedback`], only telling runs apart by their [`ExitKind`] so far
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(BLACKBOXFEEDBACK_PREFIX.to_string() + name),
+            exit_status: None,
+            stdout: None,
+            stderr: None,
+            time: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+
+    /// Take the exit code and the terminating signal into account
+    #[must_use]
+    pub fn exit_status(mut self, observer: &ExitStatusObserver) -> Self {
+        self.exit_status = Some(observer.handle());
+        self
+    }
+
+    /// Take the normalized stdout into account
+    #[must_use]
+    pub fn stdout(mut self, observer: &StdOutObserver) -> Self {
+        self.stdout = Some(observer.handle());
+        self
+    }
+
+    /// Take the normalized stderr into account
+    #[must_use]
+    pub fn stderr(mut self, observer: &StdErrObserver) -> Self {
+        self.stderr = Some(observer.handle());
+        self
+    }
+
+    /// Take the bucket of the execution time into account
+    #[must_use]
+    pub fn time(mut self, observer: &TimeObserver) -> Self {
+        self.time = Some(observer.handle());
+        self
+    }
+
+    /// The hash of the behavior of the last run
+    #[allow(clippy::cast_sign_loss)]
+    fn signature<OT>(&self, observers: &OT, exit_kind: &ExitKind) -> Result<u64, Error>
+    where
+        OT: MatchName,
+    {
+        let mut parts: Vec<u64> = Vec::with_capacity(6);
+        parts.push(match exit_kind {
+            ExitKind::Ok => 0,
+            ExitKind::Crash => 1,
+            ExitKind::Oom => 2,
+            ExitKind::Timeout => 3,
+            ExitKind::Diff { .. } => 4,
+        });
+        if let Some(h) = &self.exit_status {
+            let observer = observers
+                .get(h)
+                .ok_or_else(|| Error::illegal_state("ExitStatusObserver is missing"))?;
+            parts.push(observer.code.map_or(u64::MAX, |code| code as u64));
+            parts.push(observer.signal.map_or(u64::MAX, |signal| signal as u64));
+        }
+        if let Some(h) = &self.stdout {
+            let observer = observers
+                .get(h)
+                .ok_or_else(|| Error::illegal_state("StdOutObserver is missing"))?;
+            let stdout = observer.stdout.as_deref().unwrap_or_default();
+            parts.push(hash_std(&normalize_output(stdout)));
+        }
+        if let Some(h) = &self.stderr {
+            let observer = observers
+                .get(h)
+                .ok_or_else(|| Error::illegal_state("StdErrObserver is missing"))?;
+            let stderr = observer.stderr.as_deref().unwrap_or_default();
+            parts.push(hash_std(&normalize_output(stderr)));
+        }
+        if let Some(h) = &self.time {
+            let observer = observers
+                .get(h)
+                .ok_or_else(|| Error::illegal_state("TimeObserver is missing"))?;
+            let time = *observer.last_runtime();
+            parts.push(time.map_or(u64::MAX, |time| u64::from(time_bucket(time))));
+        }
+        let bytes: Vec<u8> = parts.iter().flat_map(|part| part.to_le_bytes()).collect();
+        Ok(hash_std(&bytes))
+    }
+}
+
+impl<S> StateInitializer<S> for BlackBoxFeedback
+where
+    S: HasNamedMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_named_metadata(&self.name, BlackBoxFeedbackMetadata::default());
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for BlackBoxFeedback
+where
+    OT: MatchName,
+    S: HasNamedMetadata,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let signature = self.signature(observers, exit_kind)?;
+        let res = state
+            .named_metadata_mut::<BlackBoxFeedbackMetadata>(&self.name)?
+            .signatures
+            .insert(signature);
+
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+}
+
+impl Named for BlackBoxFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{normalize_output, time_bucket};
+
+    #[test]
+    fn test_normalize_output() {
+        assert_eq!(
+            normalize_output(b"pid 1234 at 0x7ffdeadbeef: error 42"),
+            b"pid 0 at 0x0: error 0"
+        );
+        assert_eq!(normalize_output(b"0x"), b"0x");
+        assert_eq!(normalize_output(b"v1.2"), b"v0.0");
+        assert_eq!(normalize_output(b""), b"");
+    }
+
+    #[test]
+    fn test_time_bucket() {
+        assert_eq!(time_bucket(Duration::from_micros(500)), 0);
+        assert_eq!(time_bucket(Duration::from_millis(1)), 1);
+        assert_eq!(time_bucket(Duration::from_millis(3)), 2);
+        assert_eq!(time_bucket(Duration::from_millis(1000)), 10);
+    }
+}
@@ -0,0 +1,171 @@
+//! Compact per-testcase coverage summaries, e.g. used by the [`crate::mutators::CoverageSpliceMutator`].
+
+use alloc::{borrow::Cow, vec, vec::Vec};
+use core::marker::PhantomData;
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    AsIter, Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    feedbacks::{Feedback, StateInitializer},
+    observers::MapObserver,
+    Error, HasMetadata,
+};
+
+/// The default maximum number of 64 bit words of a [`CoverageSummaryMetadata`],
+/// see [`CoverageSummaryFeedback::with_max_words`]
+pub const DEFAULT_COVERAGE_SUMMARY_MAX_WORDS: usize = 64;
+
+/// A compact fingerprint of the map entries covered by a testcase.
+///
+/// With `n` words, map index `i` sets bit `i % (64 * n)`, so two testcases with disjoint summaries cover disjoint
+/// entries. Summaries of maps with at most `64 * n` entries are exact.
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CoverageSummaryMetadata {
+    /// The bits of the summary
+    pub bits: Vec<u64>,
+}
+
+impl_serdeany!(CoverageSummaryMetadata);
+
+impl CoverageSummaryMetadata {
+    /// Summarize the given covered map indexes in (at least one) `words` 64 bit words
+    #[must_use]
+    pub fn from_indexes<I>(indexes: I, words: usize) -> Self
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let mut bits = vec![0; words.max(1)];
+        for idx in indexes {
+            let bit = idx % (64 * bits.len());
+            bits[bit / 64] |= 1 << (bit % 64);
+        }
+        Self { bits }
+    }
+
+    /// The number of bits set in `other`, but not in `self`, i.e. roughly what `other` adds to `self`.
+    ///
+    /// Both summaries should have the same size, words missing in `self` count as empty.
+    #[must_use]
+    pub fn complement_count(&self, other: &Self) -> u32 {
+        other
+            .bits
+            .iter()
+            .enumerate()
+            .map(|(idx, other)| (other & !self.bits.get(idx).copied().unwrap_or(0)).count_ones())
+            .sum()
+    }
+}
+
+/// Adds a [`CoverageSummaryMetadata`] of the given map observer to each new testcase.
+///
+/// The summaries have one bit per map entry, up to [`DEFAULT_COVERAGE_SUMMARY_MAX_WORDS`] words, larger maps
+/// are folded. Is never interesting (use with an Eager OR).
+#[derive(Debug, Clone)]
+pub struct CoverageSummaryFeedback<C, O> {
+    map_ref: Handle<C>,
+    max_words: usize,
+    phantom: PhantomData<O>,
+}
+
+impl<C, O> CoverageSummaryFeedback<C, O>
+where
+    C: Named,
+{
+    /// Create a new [`CoverageSummaryFeedback`] summarizing the given map observer
+    #[must_use]
+    pub fn new(map_observer: &C) -> Self {
+        Self {
+            map_ref: map_observer.handle(),
+            max_words: DEFAULT_COVERAGE_SUMMARY_MAX_WORDS,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the maximum number of 64 bit words of the summaries, trading memory per testcase for precision
+    #[must_use]
+    pub fn with_max_words(mut self, max_words: usize) -> Self {
+        self.max_words = max_words.max(1);
+        self
+    }
+}
+
+impl<C, O> Named for CoverageSummaryFeedback<C, O> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CoverageSummaryFeedback");
+        &NAME
+    }
+}
+
+impl<C, O, S> StateInitializer<S> for CoverageSummaryFeedback<C, O> {}
+
+impl<C, EM, I, O, OT, S> Feedback<EM, I, OT, S> for CoverageSummaryFeedback<C, O>
+where
+    C: AsRef<O>,
+    O: MapObserver + for<'it> AsIter<'it, Item = O::Entry>,
+    OT: MatchName,
+{
+    #[cfg(feature = "track_hit_feedbacks")]
+    #[inline]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.map_ref)
+            .ok_or_else(|| {
+                Error::key_not_found("Map observer for CoverageSummaryFeedback missing")
+            })?
+            .as_ref();
+        let initial = observer.initial();
+        let words = observer.usable_count().div_ceil(64).min(self.max_words);
+        let summary = CoverageSummaryMetadata::from_indexes(
+            observer
+                .as_iter()
+                .enumerate()
+                .filter(|(_, entry)| **entry != initial)
+                .map(|(idx, _)| idx),
+            words,
+        );
+        testcase.add_metadata(summary);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoverageSummaryMetadata;
+
+    #[test]
+    fn test_coverage_summary() {
+        let a = CoverageSummaryMetadata::from_indexes([1, 2, 300], 4);
+        let b = CoverageSummaryMetadata::from_indexes([2, 44, 256 + 44], 4);
+        // 300 is folded onto bit 44
+        assert_eq!(a.complement_count(&b), 0);
+        assert_eq!(b.complement_count(&a), 1);
+
+        // With enough words, the summaries are exact
+        let a = CoverageSummaryMetadata::from_indexes([1, 2, 300], 5);
+        let b = CoverageSummaryMetadata::from_indexes([2, 44, 256 + 44], 5);
+        assert_eq!(a.complement_count(&b), 1);
+        assert_eq!(b.complement_count(&a), 1);
+        // A testcase without a summary gains everything
+        assert_eq!(CoverageSummaryMetadata::default().complement_count(&b), 3);
+    }
+}
@@ -0,0 +1,262 @@
+//! The [`CrashBundleFeedback`] writes a self-contained triage bundle for each objective to disk.
+//!
+//! Each bundle is a directory containing the input, the captured output of the target (which includes
+//! sanitizer reports), a dump of the testcase metadata, the exit kind, and a `reproduce.sh` script.
+//! Handing a crash over to somebody else is then a single directory copy.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use libafl_bolts::{
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+
+use crate::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    inputs::Input,
+    observers::{StdErrObserver, StdOutObserver},
+    Error, HasMetadata,
+};
+
+/// The placeholder in the harness command line that is replaced with the path to the input
+pub const CRASH_BUNDLE_INPUT_PLACEHOLDER: &str = "@@";
+
+/// Nop feedback writing a triage bundle for each new testcase.
+/// The testcase is never interesting, add it to the objective with an OR.
+///
+/// Put it last in the objective chain, so that the metadata of the other feedbacks
+/// (e.g. backtrace hashes) is part of the bundle.
+#[derive(Clone, Debug)]
+pub struct CrashBundleFeedback {
+    name: Cow<'static, str>,
+    out_dir: PathBuf,
+    command: Vec<String>,
+    stdout_ref: Option<Handle<StdOutObserver>>,
+    stderr_ref: Option<Handle<StdErrObserver>>,
+    last_exit_kind: Option<ExitKind>,
+}
+
+impl CrashBundleFeedback {
+    /// Creates a new [`CrashBundleFeedback`], writing one bundle directory per objective into `out_dir`.
+    #[must_use]
+    pub fn new<P>(out_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            name: Cow::Borrowed("CrashBundleFeedback"),
+            out_dir: out_dir.into(),
+            command: Vec::new(),
+            stdout_ref: None,
+            stderr_ref: None,
+            last_exit_kind: None,
+        }
+    }
+
+    /// Set the harness command line used in `reproduce.sh`.
+    ///
+    /// Each `@@` is replaced with the path to the input. Without `@@`, the input is passed on stdin.
+    /// If no command is set, `reproduce.sh` runs the binary given in the `TARGET` environment variable.
+    #[must_use]
+    pub fn with_command<IT, A>(mut self, command: IT) -> Self
+    where
+        IT: IntoIterator<Item = A>,
+        A: ToString,
+    {
+        self.command = command.into_iter().map(|arg| arg.to_string()).collect();
+        self
+    }
+
+    /// Include the stdout captured by the given observer in the bundle
+    #[must_use]
+    pub fn with_stdout(mut self, observer: &StdOutObserver) -> Self {
+        self.stdout_ref = Some(observer.handle());
+        self
+    }
+
+    /// Include the stderr captured by the given observer, e.g. an `ASan` report, in the bundle
+    #[must_use]
+    pub fn with_stderr(mut self, observer: &StdErrObserver) -> Self {
+        self.stderr_ref = Some(observer.handle());
+        self
+    }
+
+    /// The generated `reproduce.sh`, for an input stored as `input_file` next to it
+    #[must_use]
+    pub fn reproduce_script(&self, input_file: &str) -> String {
+        let mut script =
+            String::from("#!/bin/sh\n# Reproduces the objective stored in this bundle\n");
+        script.push_str("cd \"$(dirname \"$0\")\" || exit 1\n");
+
+        let input = format!("./{input_file}");
+        if self.command.is_empty() {
+            let _ = writeln!(
+                script,
+                "exec \"${{TARGET:?set TARGET to the harness binary}}\" {}",
+                shell_quote(&input)
+            );
+            return script;
+        }
+
+        let mut uses_file = false;
+        let args: Vec<String> = self
+            .command
+            .iter()
+            .map(|arg| {
+                if arg.contains(CRASH_BUNDLE_INPUT_PLACEHOLDER) {
+                    uses_file = true;
+                    shell_quote(&arg.replace(CRASH_BUNDLE_INPUT_PLACEHOLDER, &input))
+                } else {
+                    shell_quote(arg)
+                }
+            })
+            .collect();
+        script.push_str("exec ");
+        script.push_str(&args.join(" "));
+        if !uses_file {
+            script.push_str(" < ");
+            script.push_str(&shell_quote(&input));
+        }
+        script.push('\n');
+        script
+    }
+
+    fn write_bundle<I, OT>(
+        &self,
+        dir: &Path,
+        input: &I,
+        observers: &OT,
+        testcase: &Testcase<I>,
+    ) -> Result<(), Error>
+    where
+        I: Input,
+        OT: MatchName,
+    {
+        fs::create_dir_all(dir)?;
+        input.to_file(dir.join("input"))?;
+
+        if let Some(stdout_ref) = &self.stdout_ref {
+            if let Some(stdout) = observers.get(stdout_ref).and_then(|o| o.stdout.as_ref()) {
+                fs::write(dir.join("stdout.txt"), stdout)?;
+            }
+        }
+        if let Some(stderr_ref) = &self.stderr_ref {
+            if let Some(stderr) = observers.get(stderr_ref).and_then(|o| o.stderr.as_ref()) {
+                fs::write(dir.join("stderr.txt"), stderr)?;
+            }
+        }
+
+        let mut info = String::new();
+        if let Some(exit_kind) = &self.last_exit_kind {
+            let _ = writeln!(info, "exit_kind: {exit_kind:?}");
+        }
+        if let Some(exec_time) = testcase.exec_time() {
+            let _ = writeln!(info, "exec_time: {exec_time:?}");
+        }
+        let _ = writeln!(info, "command: {:?}", self.command);
+        let _ = writeln!(info, "metadata: {:#?}", testcase.metadata_map());
+        fs::write(dir.join("info.txt"), info)?;
+
+        let script_path = dir.join("reproduce.sh");
+        fs::write(&script_path, self.reproduce_script("input"))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&script_path, fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote an argument for a POSIX shell
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_./=:,+@%".contains(&b))
+    {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\"'\"'"))
+}
+
+impl<S> StateInitializer<S> for CrashBundleFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for CrashBundleFeedback
+where
+    I: Input,
+    OT: MatchName,
+{
+    #[inline]
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        self.last_exit_kind = Some(*exit_kind);
+        Ok(false)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Write the bundle for the new testcase.
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Some(input) = testcase.input() else {
+            return Err(Error::empty("The objective has no input to bundle"));
+        };
+        let dir = self.out_dir.join(input.generate_name(None));
+        if let Err(err) = self.write_bundle(&dir, input, observers, testcase) {
+            // Failing to write the bundle must not lose the objective itself
+            log::error!("Failed to write crash bundle to {}: {err}", dir.display());
+        }
+        Ok(())
+    }
+}
+
+impl Named for CrashBundleFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CrashBundleFeedback;
+
+    #[test]
+    fn test_reproduce_script() {
+        let feedback = CrashBundleFeedback::new("bundles").with_command(["./target", "-x", "@@"]);
+        assert!(feedback
+            .reproduce_script("input")
+            .ends_with("exec ./target -x ./input\n"));
+
+        let feedback = CrashBundleFeedback::new("bundles").with_command(["./target", "it's"]);
+        assert!(feedback
+            .reproduce_script("input")
+            .ends_with("exec ./target 'it'\"'\"'s' < ./input\n"));
+    }
+}
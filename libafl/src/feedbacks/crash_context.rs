@@ -0,0 +1,94 @@
+//! The [`CrashContextFeedback`] stores the [`CrashContext`] of in-process crashes in the solutions.
+
+use alloc::borrow::Cow;
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    feedbacks::{Feedback, StateInitializer},
+    observers::{CrashContext, CrashContextObserver, CrashKind},
+    Error, HasMetadata,
+};
+
+/// The machine state of a crashing testcase, and its classification
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashContextMetadata {
+    /// The context of the crash
+    pub context: CrashContext,
+    /// The classification of the crash
+    pub kind: CrashKind,
+}
+
+impl_serdeany!(CrashContextMetadata);
+
+impl CrashContextMetadata {
+    /// Classify the given context
+    #[must_use]
+    pub fn new(context: CrashContext) -> Self {
+        let kind = context.classify();
+        Self { context, kind }
+    }
+}
+
+/// Nop feedback adding the [`CrashContextMetadata`] to new testcases, from a [`CrashContextObserver`].
+/// The testcase is never interesting, add it to the objective with an OR.
+#[derive(Clone, Debug)]
+pub struct CrashContextFeedback {
+    observer_handle: Handle<CrashContextObserver>,
+}
+
+impl CrashContextFeedback {
+    /// Creates a new [`CrashContextFeedback`], reading the context from the given observer
+    #[must_use]
+    pub fn new(observer: &CrashContextObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+        }
+    }
+}
+
+impl<S> StateInitializer<S> for CrashContextFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for CrashContextFeedback
+where
+    OT: MatchName,
+{
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let context = observers
+            .get(&self.observer_handle)
+            .and_then(|observer| observer.context());
+        if let Some(context) = context {
+            testcase.add_metadata(CrashContextMetadata::new(context.clone()));
+        }
+        Ok(())
+    }
+}
+
+impl Named for CrashContextFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CrashContextFeedback");
+        &NAME
+    }
+}
@@ -0,0 +1,268 @@
+//! The [`CrashReportFeedback`] writes objectives in formats understood by existing triage pipelines.
+//!
+//! - [`CrashReportFormat::Casr`] writes a `.casrep` JSON report per objective, as produced by `casr-san`,
+//!   so that `casr-cluster` and `casr-cli` can deduplicate and display LibAFL crashes.
+//! - [`CrashReportFormat::ClusterFuzz`] uses the `libFuzzer` reproducer layout `ClusterFuzz` ingests:
+//!   a `crash-<name>` testcase, next to a `crash-<name>.log` holding the sanitizer output.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use std::{fs, path::PathBuf};
+
+use libafl_bolts::{
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    inputs::Input,
+    observers::StdErrObserver,
+    Error,
+};
+
+/// The format written by a [`CrashReportFeedback`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrashReportFormat {
+    /// CASR JSON reports (`.casrep`)
+    Casr,
+    /// The `libFuzzer`-style reproducer layout used by `ClusterFuzz`
+    ClusterFuzz,
+}
+
+/// The subset of a CASR crash report that can be filled from a LibAFL run.
+///
+/// Field names follow the `.casrep` format, so the report can be read by the CASR tools.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CasrReport {
+    /// The path of the fuzzed binary
+    pub executable_path: String,
+    /// The command line of the harness
+    pub proc_cmdline: String,
+    /// The operating system
+    #[serde(rename = "OS")]
+    pub os: String,
+    /// The CPU architecture
+    pub architecture: String,
+    /// The frames of the crashing stack trace
+    pub stacktrace: Vec<String>,
+    /// The complete sanitizer report
+    pub asan_report: Vec<String>,
+    /// The source location of the first frame with debug info
+    pub crash_line: String,
+}
+
+impl CasrReport {
+    /// Create a report from the sanitizer output of a crashing run
+    #[must_use]
+    pub fn from_sanitizer_output(output: &str) -> Self {
+        let lines: Vec<&str> = output.lines().collect();
+        let start = lines
+            .iter()
+            .position(|line| line.starts_with("==") && line.contains("ERROR:"))
+            .unwrap_or(0);
+        let asan_report: Vec<String> = lines[start..]
+            .iter()
+            .map(|line| (*line).to_string())
+            .collect();
+
+        // The first contiguous run of `#N` frames is the crashing stack
+        let stacktrace: Vec<String> = asan_report
+            .iter()
+            .map(|line| line.trim_start())
+            .skip_while(|line| !is_stack_frame(line))
+            .take_while(|line| is_stack_frame(line))
+            .map(ToString::to_string)
+            .collect();
+
+        let crash_line = stacktrace
+            .iter()
+            .filter_map(|frame| frame.split_whitespace().last())
+            .find(|location| location.starts_with('/') && location.contains(':'))
+            .unwrap_or_default()
+            .to_string();
+
+        Self {
+            os: std::env::consts::OS.to_string(),
+            architecture: std::env::consts::ARCH.to_string(),
+            stacktrace,
+            asan_report,
+            crash_line,
+            ..Self::default()
+        }
+    }
+}
+
+/// If this (trimmed) line is a sanitizer stack frame, like `#0 0x4a1b2c in main /src/main.c:3:5`
+fn is_stack_frame(line: &str) -> bool {
+    line.strip_prefix('#')
+        .and_then(|rest| rest.split_whitespace().next())
+        .is_some_and(|idx| idx.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Nop feedback writing a crash report for each new testcase in the given [`CrashReportFormat`].
+/// The testcase is never interesting, add it to the objective with an OR.
+#[derive(Clone, Debug)]
+pub struct CrashReportFeedback {
+    name: Cow<'static, str>,
+    out_dir: PathBuf,
+    format: CrashReportFormat,
+    command: Vec<String>,
+    stderr_ref: Option<Handle<StdErrObserver>>,
+    last_exit_kind: Option<ExitKind>,
+}
+
+impl CrashReportFeedback {
+    /// Creates a new [`CrashReportFeedback`] writing reports in the given `format` into `out_dir`.
+    #[must_use]
+    pub fn new<P>(out_dir: P, format: CrashReportFormat) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            name: Cow::Borrowed("CrashReportFeedback"),
+            out_dir: out_dir.into(),
+            format,
+            command: Vec::new(),
+            stderr_ref: None,
+            last_exit_kind: None,
+        }
+    }
+
+    /// Set the command line of the harness, the first argument being the binary
+    #[must_use]
+    pub fn with_command<IT, A>(mut self, command: IT) -> Self
+    where
+        IT: IntoIterator<Item = A>,
+        A: ToString,
+    {
+        self.command = command.into_iter().map(|arg| arg.to_string()).collect();
+        self
+    }
+
+    /// Read the sanitizer output from the stderr captured by the given observer
+    #[must_use]
+    pub fn with_stderr(mut self, observer: &StdErrObserver) -> Self {
+        self.stderr_ref = Some(observer.handle());
+        self
+    }
+
+    fn write_report<I, OT>(&self, input: &I, observers: &OT) -> Result<(), Error>
+    where
+        I: Input,
+        OT: MatchName,
+    {
+        let stderr = self
+            .stderr_ref
+            .as_ref()
+            .and_then(|stderr_ref| observers.get(stderr_ref))
+            .and_then(|observer| observer.stderr.as_ref())
+            .map(|stderr| String::from_utf8_lossy(stderr).into_owned())
+            .unwrap_or_default();
+        let name = input.generate_name(None);
+        fs::create_dir_all(&self.out_dir)?;
+
+        match self.format {
+            CrashReportFormat::Casr => {
+                let mut report = CasrReport::from_sanitizer_output(&stderr);
+                if report.stacktrace.is_empty() {
+                    if let Some(exit_kind) = &self.last_exit_kind {
+                        report
+                            .asan_report
+                            .push(format!("LibAFL exit kind: {exit_kind:?}"));
+                    }
+                }
+                report.executable_path = self.command.first().cloned().unwrap_or_default();
+                report.proc_cmdline = self.command.join(" ");
+
+                let json = serde_json::to_string_pretty(&report)?;
+                fs::write(self.out_dir.join(format!("{name}.casrep")), json)?;
+                input.to_file(self.out_dir.join(name))?;
+            }
+            CrashReportFormat::ClusterFuzz => {
+                let testcase = format!("crash-{name}");
+                fs::write(self.out_dir.join(format!("{testcase}.log")), stderr)?;
+                input.to_file(self.out_dir.join(testcase))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S> StateInitializer<S> for CrashReportFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for CrashReportFeedback
+where
+    I: Input,
+    OT: MatchName,
+{
+    #[inline]
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        self.last_exit_kind = Some(*exit_kind);
+        Ok(false)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Write the report for the new testcase.
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Some(input) = testcase.input() else {
+            return Err(Error::empty("The objective has no input to report"));
+        };
+        if let Err(err) = self.write_report(input, observers) {
+            // Failing to write the report must not lose the objective itself
+            log::error!("Failed to write crash report: {err}");
+        }
+        Ok(())
+    }
+}
+
+impl Named for CrashReportFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CasrReport;
+
+    #[test]
+    fn test_casr_report_from_asan() {
+        let output = "INFO: Seed: 1\n\
+            ==42==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011\n\
+            READ of size 1 at 0x602000000011 thread T0\n    \
+            #0 0x4f2a1b in parse /src/parse.c:12:5\n    \
+            #1 0x4f2c3d in main /src/main.c:3:1\n\
+            \n\
+            SUMMARY: AddressSanitizer: heap-buffer-overflow /src/parse.c:12:5 in parse\n";
+        let report = CasrReport::from_sanitizer_output(output);
+        assert_eq!(report.stacktrace.len(), 2);
+        assert_eq!(report.crash_line, "/src/parse.c:12:5");
+        assert!(report.asan_report[0].starts_with("==42==ERROR"));
+    }
+}
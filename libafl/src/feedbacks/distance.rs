@@ -0,0 +1,282 @@
+//! Directed fuzzing toward target sites, minimizing the distance of the executions to them, as in `AFLGo`.
+//!
+//! The [`DistanceFeedback`] keeps inputs that came closer to the target sites than any testcase before,
+//! as reported by a [`DistanceObserver`], and records the distance of each new testcase in its
+//! [`TestcaseDistanceMetadata`]. The [`crate::schedulers::testcase_score::DistanceTestcaseScore`] then gives
+//! the closest testcases more weight, the longer the campaign runs: it anneals from exploration to exploitation
+//! over the exploitation time of the [`DistanceMetadata`].
+
+use alloc::borrow::Cow;
+use core::{marker::PhantomData, time::Duration};
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::DistanceObserver,
+    Error, HasMetadata,
+};
+
+/// The default time after which the schedule fully exploits the closest testcases, 45 minutes as in `AFLGo`
+pub const DEFAULT_EXPLOITATION_TIME: Duration = Duration::from_secs(45 * 60);
+
+/// The maximum factor the weight of a testcase is multiplied or divided with, depending on its distance
+const MAX_POWER_FACTOR: f64 = 32.0;
+
+/// The range of the distances of the testcases, and the annealing schedule
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct DistanceMetadata {
+    /// The minimum and maximum distance of the testcases so far
+    range: Option<(f64, f64)>,
+    /// The time after which the schedule fully exploits the closest testcases
+    exploitation_time: Duration,
+}
+
+impl_serdeany!(DistanceMetadata);
+
+impl Default for DistanceMetadata {
+    fn default() -> Self {
+        Self::new(DEFAULT_EXPLOITATION_TIME)
+    }
+}
+
+impl DistanceMetadata {
+    /// Create a new [`DistanceMetadata`] with the given exploitation time
+    #[must_use]
+    pub fn new(exploitation_time: Duration) -> Self {
+        Self {
+            range: None,
+            exploitation_time,
+        }
+    }
+
+    /// The minimum distance of the testcases so far
+    #[must_use]
+    pub fn min(&self) -> Option<f64> {
+        self.range.map(|(min, _)| min)
+    }
+
+    /// The maximum distance of the testcases so far
+    #[must_use]
+    pub fn max(&self) -> Option<f64> {
+        self.range.map(|(_, max)| max)
+    }
+
+    /// The time after which the schedule fully exploits the closest testcases
+    #[must_use]
+    pub fn exploitation_time(&self) -> Duration {
+        self.exploitation_time
+    }
+
+    /// Add the distance of a new testcase to the range
+    pub fn update(&mut self, distance: f64) {
+        self.range = Some(match self.range {
+            Some((min, max)) => (min.min(distance), max.max(distance)),
+            None => (distance, distance),
+        });
+    }
+
+    /// The distance, normalized to the range of the testcases: `0.0` is the closest, `1.0` the farthest
+    #[must_use]
+    pub fn normalize(&self, distance: f64) -> f64 {
+        match self.range {
+            Some((min, max)) if max > min => ((distance - min) / (max - min)).clamp(0.0, 1.0),
+            _ => 0.0,
+        }
+    }
+
+    /// The factor for the weight of a testcase at the given distance, after `elapsed` time of the campaign.
+    ///
+    /// The temperature of the simulated annealing drops from `1.0` to `0.05` over the exploitation time.
+    /// While it is hot, all testcases get about the same weight; once it cooled down, the closest testcases get up to
+    /// 32 times the weight, and the farthest down to a 32th.
+    #[must_use]
+    pub fn power_factor(&self, distance: f64, elapsed: Duration) -> f64 {
+        let exploitation = self.exploitation_time.as_secs_f64().max(1.0);
+        let temperature = libm::pow(20.0, -elapsed.as_secs_f64() / exploitation);
+        let power = (1.0 - self.normalize(distance)) * (1.0 - temperature) + 0.5 * temperature;
+        libm::pow(2.0, 2.0 * libm::log2(MAX_POWER_FACTOR) * (power - 0.5))
+    }
+}
+
+/// The distance of a testcase to the target sites
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TestcaseDistanceMetadata {
+    /// The mean distance of the executed basic blocks to the target sites
+    pub distance: f64,
+}
+
+impl_serdeany!(TestcaseDistanceMetadata);
+
+/// A feedback for directed fuzzing, keeping the inputs that came closer to the target sites than all testcases before.
+///
+/// Each new testcase gets a [`TestcaseDistanceMetadata`], and the minimum distance is reported as a user stat.
+/// Use it in an OR with the map feedback.
+#[derive(Debug, Clone)]
+pub struct DistanceFeedback {
+    observer_ref: Handle<DistanceObserver>,
+    exploitation_time: Duration,
+    last_distance: Option<f64>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl DistanceFeedback {
+    /// Create a new [`DistanceFeedback`], with the [`DEFAULT_EXPLOITATION_TIME`]
+    #[must_use]
+    pub fn new(observer: &DistanceObserver) -> Self {
+        Self::with_exploitation_time(observer, DEFAULT_EXPLOITATION_TIME)
+    }
+
+    /// Create a new [`DistanceFeedback`], which fully exploits the closest testcases after `exploitation_time`
+    #[must_use]
+    pub fn with_exploitation_time(
+        observer: &DistanceObserver,
+        exploitation_time: Duration,
+    ) -> Self {
+        Self {
+            observer_ref: observer.handle(),
+            exploitation_time,
+            last_distance: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+impl Named for DistanceFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("DistanceFeedback");
+        &NAME
+    }
+}
+
+impl<S> StateInitializer<S> for DistanceFeedback
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        let exploitation_time = self.exploitation_time;
+        state.metadata_or_insert_with(|| DistanceMetadata::new(exploitation_time));
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for DistanceFeedback
+where
+    EM: EventFirer<State = S>,
+    OT: MatchName,
+    S: HasMetadata,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.observer_ref)
+            .ok_or_else(|| Error::key_not_found("DistanceObserver for DistanceFeedback missing"))?;
+        self.last_distance = observer.last_distance();
+        let min = state.metadata::<DistanceMetadata>()?.min();
+        let res = match (self.last_distance, min) {
+            (Some(distance), Some(min)) => distance < min,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Some(distance) = self.last_distance.take() else {
+            return Ok(());
+        };
+        testcase.add_metadata(TestcaseDistanceMetadata { distance });
+        let meta = state.metadata_mut::<DistanceMetadata>()?;
+        let old_min = meta.min();
+        meta.update(distance);
+        if old_min.map_or(true, |min| distance < min) {
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::Borrowed("min distance"),
+                    value: UserStats::new(UserStatsValue::Float(distance), AggregatorOps::Min),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last_distance = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::DistanceMetadata;
+
+    #[test]
+    fn test_distance_power_factor() {
+        let mut meta = DistanceMetadata::new(Duration::from_secs(60));
+        assert!(meta.min().is_none());
+        meta.update(4.0);
+        meta.update(12.0);
+        meta.update(8.0);
+        assert_eq!(meta.range, Some((4.0, 12.0)));
+        assert!((meta.normalize(8.0) - 0.5).abs() < f64::EPSILON);
+
+        // Hot: all testcases are about equal
+        let start = Duration::ZERO;
+        assert!((meta.power_factor(4.0, start) - 1.0).abs() < 1e-9);
+        assert!((meta.power_factor(12.0, start) - 1.0).abs() < 1e-9);
+
+        // Cold: the closest testcase gets almost 32 times the weight, the farthest almost a 32th
+        let late = Duration::from_secs(600);
+        assert!(meta.power_factor(4.0, late) > 31.0);
+        assert!(meta.power_factor(12.0, late) < 1.0 / 31.0);
+        assert!((meta.power_factor(8.0, late) - 1.0).abs() < 1e-9);
+    }
+}
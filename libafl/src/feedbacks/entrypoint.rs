@@ -0,0 +1,289 @@
+//! Coverage and stats per entrypoint, when fuzzing several entrypoints of a target in one campaign.
+//!
+//! See [`crate::inputs::entrypoint`] for details.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::marker::PhantomData;
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    inputs::entrypoint::HasEntrypoint,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::MapObserver,
+    Error, HasMetadata,
+};
+
+/// The coverage and stats of one entrypoint
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EntrypointStats {
+    /// The name of the entrypoint
+    pub name: String,
+    /// The number of runs of the entrypoint
+    pub executions: u64,
+    /// The number of testcases added to the corpus for the entrypoint
+    pub testcases: u64,
+    /// If each map index was covered by a testcase of the entrypoint
+    covered: Vec<bool>,
+    /// The number of covered map indices
+    covered_count: usize,
+}
+
+impl EntrypointStats {
+    /// Creates new, empty [`EntrypointStats`]
+    #[must_use]
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            executions: 0,
+            testcases: 0,
+            covered: Vec::new(),
+            covered_count: 0,
+        }
+    }
+
+    /// The number of map indices covered by the testcases of the entrypoint
+    #[must_use]
+    pub fn covered_count(&self) -> usize {
+        self.covered_count
+    }
+
+    /// If the map index `idx` was covered by a testcase of the entrypoint
+    #[must_use]
+    pub fn is_covered(&self, idx: usize) -> bool {
+        self.covered.get(idx).copied().unwrap_or(false)
+    }
+
+    /// The map indices the observer hit in the last run, that no testcase of the entrypoint covered before
+    pub fn new_indices<O>(&self, observer: &O) -> Vec<usize>
+    where
+        O: MapObserver,
+    {
+        let initial = observer.initial();
+        (0..observer.usable_count())
+            .filter(|idx| observer.get(*idx) != initial && !self.is_covered(*idx))
+            .collect()
+    }
+
+    /// Mark the map indices as covered, returns how many were not covered before
+    pub fn mark_covered(&mut self, indices: &[usize]) -> usize {
+        let mut new = 0;
+        for idx in indices {
+            if *idx >= self.covered.len() {
+                self.covered.resize(*idx + 1, false);
+            }
+            if !self.covered[*idx] {
+                self.covered[*idx] = true;
+                new += 1;
+            }
+        }
+        self.covered_count += new;
+        new
+    }
+}
+
+/// The coverage and stats of each entrypoint, by index
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EntrypointStatsMetadata {
+    /// The stats of each entrypoint, by index
+    pub entrypoints: Vec<EntrypointStats>,
+}
+
+impl_serdeany!(EntrypointStatsMetadata);
+
+/// Keeps a coverage map and stats per entrypoint, in the [`EntrypointStatsMetadata`] of the state.
+///
+/// A run is interesting if it covers a map index no testcase of its entrypoint covered before, even if the
+/// testcases of other entrypoints did, so each entrypoint keeps the testcases exploring it. The coverage and
+/// corpus size of each entrypoint are reported as user stats. Use it in an eager OR with the map feedback of
+/// the same observer, or instead of it.
+#[derive(Debug, Clone)]
+pub struct EntrypointCoverageFeedback<C, O> {
+    map_ref: Handle<C>,
+    names: Vec<Cow<'static, str>>,
+    /// The entrypoint of the last run, and the map indices it newly covered
+    last: Option<(usize, Vec<usize>)>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+    phantom: PhantomData<O>,
+}
+
+impl<C, O> EntrypointCoverageFeedback<C, O>
+where
+    C: Named,
+{
+    /// Create a new [`EntrypointCoverageFeedback`] for the entrypoints with the given names, by index, e.g.
+    /// from [`crate::executors::entrypoint::MultiEntrypoint::names`]
+    #[must_use]
+    pub fn new(map_observer: &C, names: &[Cow<'static, str>]) -> Self {
+        Self {
+            map_ref: map_observer.handle(),
+            names: names.to_vec(),
+            last: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, O> Named for EntrypointCoverageFeedback<C, O> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("EntrypointCoverageFeedback");
+        &NAME
+    }
+}
+
+impl<C, O, S> StateInitializer<S> for EntrypointCoverageFeedback<C, O>
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        if self.names.is_empty() {
+            return Err(Error::illegal_argument(
+                "EntrypointCoverageFeedback needs at least one entrypoint",
+            ));
+        }
+        let meta = state.metadata_or_insert_with(EntrypointStatsMetadata::default);
+        for name in self.names.iter().skip(meta.entrypoints.len()) {
+            meta.entrypoints
+                .push(EntrypointStats::new(name.to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl<C, EM, I, O, OT, S> Feedback<EM, I, OT, S> for EntrypointCoverageFeedback<C, O>
+where
+    C: AsRef<O>,
+    EM: EventFirer<State = S>,
+    I: HasEntrypoint,
+    O: MapObserver,
+    OT: MatchName,
+    S: HasMetadata,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.map_ref)
+            .ok_or_else(|| {
+                Error::key_not_found("Map observer for EntrypointCoverageFeedback missing")
+            })?
+            .as_ref();
+        let idx = input.entrypoint() % self.names.len();
+        let stats = state
+            .metadata_mut::<EntrypointStatsMetadata>()?
+            .entrypoints
+            .get_mut(idx)
+            .ok_or_else(|| Error::key_not_found(format!("No stats for entrypoint {idx}")))?;
+        stats.executions += 1;
+        let new = stats.new_indices(observer);
+        let res = !new.is_empty();
+        self.last = Some((idx, new));
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        _testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Some((idx, new)) = self.last.take() else {
+            return Ok(());
+        };
+        let stats = &mut state.metadata_mut::<EntrypointStatsMetadata>()?.entrypoints[idx];
+        stats.mark_covered(&new);
+        stats.testcases += 1;
+        let (covered, testcases) = (stats.covered_count() as u64, stats.testcases);
+
+        let name = &self.names[idx];
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: Cow::Owned(format!("{name} edges")),
+                value: UserStats::new(UserStatsValue::Number(covered), AggregatorOps::Max),
+                phantom: PhantomData,
+            },
+        )?;
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: Cow::Owned(format!("{name} corpus")),
+                value: UserStats::new(UserStatsValue::Number(testcases), AggregatorOps::Sum),
+                phantom: PhantomData,
+            },
+        )?;
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::EntrypointStats;
+    use crate::observers::StdMapObserver;
+
+    #[test]
+    fn test_entrypoint_stats() {
+        let mut parse = EntrypointStats::new("parse".to_string());
+        let mut decode = EntrypointStats::new("decode".to_string());
+
+        let mut map = [0u8, 1, 0, 2];
+        let observer = unsafe { StdMapObserver::new("edges", &mut map) };
+        let new = parse.new_indices(&observer);
+        assert_eq!(new, [1, 3]);
+        assert_eq!(parse.mark_covered(&new), 2);
+        assert!(parse.new_indices(&observer).is_empty());
+
+        // The coverage of other entrypoints does not count
+        assert_eq!(decode.new_indices(&observer), [1, 3]);
+        assert_eq!(decode.mark_covered(&[3]), 1);
+        assert_eq!(decode.new_indices(&observer), [1]);
+        assert_eq!(parse.covered_count(), 2);
+        assert!(!decode.is_covered(1));
+    }
+}
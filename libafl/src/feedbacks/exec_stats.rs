@@ -0,0 +1,241 @@
+//! The [`ExecStatsFeedback`] sums up the [`ExecStats`] of all executions, and reports their averages to the
+//! monitors.
+
+use alloc::borrow::Cow;
+use core::{marker::PhantomData, time::Duration};
+
+use libafl_bolts::{
+    current_time, impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::{ExecStats, ExecStatsObserver},
+    Error, HasMetadata,
+};
+
+/// The default interval between two reports of the [`ExecStatsFeedback`]
+pub const DEFAULT_EXEC_STATS_REPORT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The sums of the [`ExecStats`] of all measured executions
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExecStatsMetadata {
+    /// The number of measured executions
+    pub execs: u64,
+    /// The sum of the wall times
+    pub total_wall_time: Duration,
+    /// The sum of the CPU times
+    pub total_cpu_time: Duration,
+    /// The sum of the page faults
+    pub total_page_faults: u64,
+    /// The sum of the context switches
+    pub total_context_switches: u64,
+}
+
+impl_serdeany!(ExecStatsMetadata);
+
+impl ExecStatsMetadata {
+    /// Add the stats of an execution
+    pub fn add(&mut self, stats: &ExecStats) {
+        self.execs += 1;
+        self.total_wall_time += stats.wall_time;
+        self.total_cpu_time += stats.cpu_time();
+        self.total_page_faults += stats.page_faults();
+        self.total_context_switches += stats.context_switches();
+    }
+
+    /// The average wall time of an execution
+    #[must_use]
+    pub fn avg_wall_time(&self) -> Duration {
+        self.avg_duration(self.total_wall_time)
+    }
+
+    /// The average CPU time of an execution
+    #[must_use]
+    pub fn avg_cpu_time(&self) -> Duration {
+        self.avg_duration(self.total_cpu_time)
+    }
+
+    /// The average page faults of an execution
+    #[must_use]
+    pub fn avg_page_faults(&self) -> f64 {
+        self.avg_count(self.total_page_faults)
+    }
+
+    /// The average context switches of an execution
+    #[must_use]
+    pub fn avg_context_switches(&self) -> f64 {
+        self.avg_count(self.total_context_switches)
+    }
+
+    fn avg_duration(&self, total: Duration) -> Duration {
+        if self.execs == 0 {
+            Duration::ZERO
+        } else {
+            // Divide the nanoseconds, more than `u32::MAX` executions do not fit the divisor of a `Duration`
+            let nanos = total.as_nanos() / u128::from(self.execs);
+            Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn avg_count(&self, total: u64) -> f64 {
+        if self.execs == 0 {
+            0.0
+        } else {
+            total as f64 / self.execs as f64
+        }
+    }
+}
+
+/// Sums up the [`ExecStats`] measured by an [`ExecStatsObserver`] in the [`ExecStatsMetadata`], and
+/// periodically reports the averages as user stats.
+///
+/// Is never interesting (use with an Eager OR).
+#[derive(Debug, Clone)]
+pub struct ExecStatsFeedback {
+    observer_handle: Handle<ExecStatsObserver>,
+    report_interval: Duration,
+    last_report: Duration,
+}
+
+impl ExecStatsFeedback {
+    /// Create a new [`ExecStatsFeedback`], reporting every [`DEFAULT_EXEC_STATS_REPORT_INTERVAL`]
+    #[must_use]
+    pub fn new(observer: &ExecStatsObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            report_interval: DEFAULT_EXEC_STATS_REPORT_INTERVAL,
+            last_report: Duration::ZERO,
+        }
+    }
+
+    /// Report the averages every `report_interval` instead
+    #[must_use]
+    pub fn with_report_interval(mut self, report_interval: Duration) -> Self {
+        self.report_interval = report_interval;
+        self
+    }
+}
+
+impl Named for ExecStatsFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ExecStatsFeedback");
+        &NAME
+    }
+}
+
+impl<S> StateInitializer<S> for ExecStatsFeedback
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.metadata_or_insert_with(ExecStatsMetadata::default);
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for ExecStatsFeedback
+where
+    EM: EventFirer<State = S>,
+    OT: MatchName,
+    S: HasMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers.get(&self.observer_handle).ok_or_else(|| {
+            Error::key_not_found("ExecStatsObserver for ExecStatsFeedback missing")
+        })?;
+        let Some(stats) = observer.last_stats() else {
+            return Ok(false);
+        };
+        let totals = state.metadata_or_insert_with(ExecStatsMetadata::default);
+        totals.add(stats);
+        let totals = *totals;
+
+        let now = current_time();
+        if now.saturating_sub(self.last_report) < self.report_interval {
+            return Ok(false);
+        }
+        self.last_report = now;
+        for (name, value) in [
+            (
+                "avg wall time (us)",
+                totals.avg_wall_time().as_secs_f64() * 1_000_000.0,
+            ),
+            (
+                "avg cpu time (us)",
+                totals.avg_cpu_time().as_secs_f64() * 1_000_000.0,
+            ),
+            ("avg page faults", totals.avg_page_faults()),
+            ("avg context switches", totals.avg_context_switches()),
+        ] {
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::Borrowed(name),
+                    value: UserStats::new(UserStatsValue::Float(value), AggregatorOps::Avg),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(false)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    #[inline]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::ExecStatsMetadata;
+    use crate::observers::ExecStats;
+
+    #[test]
+    fn test_exec_stats_metadata() {
+        let mut totals = ExecStatsMetadata::default();
+        assert_eq!(totals.avg_cpu_time(), Duration::ZERO);
+
+        for (micros, faults) in [(100, 2), (300, 5)] {
+            totals.add(&ExecStats {
+                wall_time: Duration::from_micros(micros * 2),
+                user_time: Duration::from_micros(micros),
+                minor_faults: faults,
+                voluntary_switches: 1,
+                ..ExecStats::default()
+            });
+        }
+        assert_eq!(totals.avg_cpu_time(), Duration::from_micros(200));
+        assert_eq!(totals.avg_wall_time(), Duration::from_micros(400));
+        assert!((totals.avg_page_faults() - 3.5).abs() < f64::EPSILON);
+        assert!((totals.avg_context_switches() - 1.0).abs() < f64::EPSILON);
+
+        let many = ExecStatsMetadata {
+            execs: u64::from(u32::MAX) * 4,
+            total_cpu_time: Duration::from_micros(u64::from(u32::MAX) * 4),
+            ..ExecStatsMetadata::default()
+        };
+        assert_eq!(many.avg_cpu_time(), Duration::from_micros(1));
+    }
+}
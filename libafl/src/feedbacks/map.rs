@@ -24,7 +24,7 @@ use crate::{
     corpus::Testcase,
     events::{Event, EventFirer},
     executors::ExitKind,
-    feedbacks::{Feedback, HasObserverHandle, StateInitializer},
+    feedbacks::{Feedback, HasObserverHandle, StateInitializer, TestcaseStatsMetadata},
     inputs::UsesInput,
     monitors::{AggregatorOps, UserStats, UserStatsValue},
     observers::{CanTrack, MapObserver},
@@ -302,6 +302,10 @@ pub struct MapFeedbackMetadata<T> {
     pub history_map: Vec<T>,
     /// Tells us how many non-initial entries there are in `history_map`
     pub num_covered_map_indexes: usize,
+    /// Entries found to be unstable by the [`crate::stages::CalibrationStage`], ignored by a [`MapFeedback`]
+    /// created with [`MapFeedback::with_unstable_masking`].
+    #[serde(default)]
+    pub unstable_mask: Vec<bool>,
 }
 
 libafl_bolts::impl_serdeany!(
@@ -319,6 +323,7 @@ where
         Self {
             history_map: vec![T::default(); map_size],
             num_covered_map_indexes: 0,
+            unstable_mask: Vec::new(),
         }
     }
 
@@ -333,6 +338,39 @@ where
         Self {
             history_map,
             num_covered_map_indexes,
+            unstable_mask: Vec::new(),
+        }
+    }
+
+    /// Mark the entry at `idx` as unstable
+    pub fn mask_unstable(&mut self, idx: usize) {
+        if self.unstable_mask.len() <= idx {
+            self.unstable_mask.resize(idx + 1, false);
+        }
+        self.unstable_mask[idx] = true;
+    }
+
+    /// If the entry at `idx` was marked as unstable
+    #[must_use]
+    pub fn is_unstable(&self, idx: usize) -> bool {
+        self.unstable_mask.get(idx).copied().unwrap_or(false)
+    }
+
+    /// Merge a baseline map into the history with the [`Reducer`] `R`, so that coverage already in the baseline is
+    /// not novel anymore. Entries equal to `initial` are not covered.
+    pub fn merge_baseline<R>(&mut self, baseline: &[T], initial: T)
+    where
+        R: Reducer<T>,
+    {
+        if self.history_map.len() < baseline.len() {
+            self.history_map.resize(baseline.len(), initial);
+        }
+        for (history, entry) in self.history_map.iter_mut().zip(baseline) {
+            let reduced = R::reduce(*history, *entry);
+            if *history == initial && reduced != initial {
+                self.num_covered_map_indexes += 1;
+            }
+            *history = reduced;
         }
     }
 
@@ -370,6 +408,8 @@ pub struct MapFeedback<C, N, O, R> {
     map_ref: Handle<C>,
     /// Name of the feedback as shown in the `UserStats`
     stats_name: Cow<'static, str>,
+    /// Ignore the entries marked as unstable in the [`MapFeedbackMetadata`]
+    mask_unstable: bool,
     // The previous run's result of [`Self::is_interesting`]
     #[cfg(feature = "track_hit_feedbacks")]
     last_result: Option<bool>,
@@ -465,6 +505,7 @@ where
             map_state.history_map.resize(len, observer.initial());
         }
 
+        let covered_before = map_state.num_covered_map_indexes;
         let history_map = &mut map_state.history_map;
         if C::INDICES {
             let mut indices = Vec::new();
@@ -514,6 +555,9 @@ where
         // at this point you are executing this code, the testcase is always interesting
         let covered = map_state.num_covered_map_indexes;
         let len = history_map.len();
+        testcase
+            .metadata_or_insert_with(TestcaseStatsMetadata::default)
+            .novel_entries += covered - covered_before;
         // opt: if not tracking optimisations, we technically don't show the *current* history
         // map but the *last* history map; this is better than walking over and allocating
         // unnecessarily
@@ -548,14 +592,24 @@ where
     fn is_interesting(
         &mut self,
         state: &mut S,
-        _manager: &mut EM,
-        _input: &I,
+        manager: &mut EM,
+        input: &I,
         observers: &OT,
-        _exit_kind: &ExitKind,
+        exit_kind: &ExitKind,
     ) -> Result<bool, Error> {
         // 128 bits vectors
         type VectorType = core::simd::u8x16;
 
+        if self.mask_unstable {
+            // The vectorized comparison cannot skip single entries
+            let res = self.is_interesting_default(state, manager, input, observers, exit_kind);
+            #[cfg(feature = "track_hit_feedbacks")]
+            {
+                self.last_result = Some(res);
+            }
+            return Ok(res);
+        }
+
         let mut interesting = false;
         // TODO Replace with match_name_type when stable
         let observer = observers.get(&self.map_ref).unwrap().as_ref();
@@ -668,6 +722,17 @@ impl<C, N, O, R> HasObserverHandle for MapFeedback<C, N, O, R> {
     }
 }
 
+/// Converts the virgin bits of AFL++, the `fuzz_bitmap` in its output directory, into a baseline for
+/// [`MapFeedback::preload_baseline`].
+///
+/// AFL++ clears the bit of each hitcount bucket it has seen, so the baseline is the inverted map. It matches the
+/// history of an [`AflMapFeedback`] exactly; for a [`MaxMapFeedback`], seeing a bucket also marks all lower buckets
+/// as seen.
+#[must_use]
+pub fn baseline_from_afl_virgin_bits(virgin_bits: &[u8]) -> Vec<u8> {
+    virgin_bits.iter().map(|bits| !bits).collect()
+}
+
 #[allow(clippy::ptr_arg)]
 fn create_stats_name(name: &Cow<'static, str>) -> Cow<'static, str> {
     if name.chars().all(char::is_lowercase) {
@@ -689,6 +754,7 @@ where
             name: map_observer.name().clone(),
             map_ref: map_observer.handle(),
             stats_name: create_stats_name(map_observer.name()),
+            mask_unstable: false,
             #[cfg(feature = "track_hit_feedbacks")]
             last_result: None,
             phantom: PhantomData,
@@ -706,11 +772,47 @@ where
             map_ref: map_observer.handle(),
             stats_name: create_stats_name(&name),
             name,
+            mask_unstable: false,
             #[cfg(feature = "track_hit_feedbacks")]
             last_result: None,
             phantom: PhantomData,
         }
     }
+
+    /// Preload a baseline coverage map into the history of this feedback in the `state`, e.g. the coverage of a
+    /// previous campaign, or one converted from AFL++ with [`baseline_from_afl_virgin_bits`].
+    ///
+    /// Only coverage beyond the baseline is novel afterwards, so the fuzzer focuses on new code. Call it after the
+    /// state was created with this feedback, as its initialization resets the history. The baseline is part of the
+    /// state from then on, and survives restarts.
+    pub fn preload_baseline<S>(&self, state: &mut S, baseline: &[O::Entry]) -> Result<(), Error>
+    where
+        O: MapObserver,
+        O::Entry: 'static + Default + Debug + Serialize + DeserializeOwned,
+        R: Reducer<O::Entry>,
+        S: HasNamedMetadata,
+    {
+        let map_state = state
+            .named_metadata_map_mut()
+            .get_mut::<MapFeedbackMetadata<O::Entry>>(&self.name)
+            .ok_or_else(|| {
+                Error::key_not_found(format!(
+                    "No MapFeedbackMetadata for {}, the state was not initialized with this feedback",
+                    self.name
+                ))
+            })?;
+        map_state.merge_baseline::<R>(baseline, O::Entry::default());
+        Ok(())
+    }
+
+    /// Ignore entries the [`crate::stages::CalibrationStage`] found to be unstable, like AFL++ does.
+    ///
+    /// Changes in flaky entries then never make an input interesting.
+    #[must_use]
+    pub fn with_unstable_masking(mut self) -> Self {
+        self.mask_unstable = true;
+        self
+    }
 }
 
 impl<C, N, O, R> MapFeedback<C, N, O, R>
@@ -752,6 +854,7 @@ where
         let history_map = map_state.history_map.as_slice();
 
         let initial = observer.initial();
+        let mask = self.mask_unstable;
 
         if let Some(novelties) = self.novelties.as_mut() {
             novelties.clear();
@@ -759,7 +862,7 @@ where
                 .as_iter()
                 .map(|x| *x)
                 .enumerate()
-                .filter(|(_, item)| *item != initial)
+                .filter(|(i, item)| *item != initial && !(mask && map_state.is_unstable(*i)))
             {
                 let existing = unsafe { *history_map.get_unchecked(i) };
                 let reduced = R::reduce(existing, item);
@@ -773,7 +876,7 @@ where
                 .as_iter()
                 .map(|x| *x)
                 .enumerate()
-                .filter(|(_, item)| *item != initial)
+                .filter(|(i, item)| *item != initial && !(mask && map_state.is_unstable(*i)))
             {
                 let existing = unsafe { *history_map.get_unchecked(i) };
                 let reduced = R::reduce(existing, item);
@@ -790,7 +893,35 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::feedbacks::{AllIsNovel, IsNovel, NextPow2IsNovel};
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list, Named};
+
+    use crate::{
+        corpus::InMemoryCorpus,
+        events::NopEventManager,
+        executors::ExitKind,
+        feedbacks::{
+            baseline_from_afl_virgin_bits, AllIsNovel, ConstFeedback, Feedback, IsNovel,
+            MapFeedbackMetadata, MaxMapFeedback, MaxReducer, NextPow2IsNovel, OrReducer,
+            StateInitializer,
+        },
+        inputs::BytesInput,
+        observers::StdMapObserver,
+        state::StdState,
+        HasNamedMetadata,
+    };
+
+    #[test]
+    fn test_merge_baseline() {
+        let mut meta = MapFeedbackMetadata::with_history_map(vec![0_u8, 4, 0, 0], 0);
+        meta.merge_baseline::<MaxReducer>(&[2, 1, 0, 0, 8], 0);
+        assert_eq!(meta.history_map, [2, 4, 0, 0, 8]);
+        assert_eq!(meta.num_covered_map_indexes, 3);
+
+        let mut meta = MapFeedbackMetadata::<u8>::new(2);
+        meta.merge_baseline::<OrReducer>(&baseline_from_afl_virgin_bits(&[0xff, 0xfa]), 0);
+        assert_eq!(meta.history_map, [0, 0x05]);
+        assert_eq!(meta.num_covered_map_indexes, 1);
+    }
 
     #[test]
     fn test_map_is_novel() {
@@ -811,4 +942,39 @@ mod tests {
         assert!(NextPow2IsNovel::is_novel(254_u8, 255));
         assert!(!NextPow2IsNovel::is_novel(255_u8, 255));
     }
+
+    #[test]
+    fn test_unstable_masking() {
+        let observer = StdMapObserver::owned("map", vec![0_u8; 4]);
+        let mut feedback = MaxMapFeedback::new(&observer).with_unstable_masking();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        feedback.init_state(&mut state).unwrap();
+        state
+            .named_metadata_mut::<MapFeedbackMetadata<u8>>(feedback.name())
+            .unwrap()
+            .mask_unstable(1);
+
+        let mut is_interesting = |map: Vec<u8>| {
+            let observers = tuple_list!(StdMapObserver::owned("map", map));
+            feedback
+                .is_interesting(
+                    &mut state,
+                    &mut NopEventManager::new(),
+                    &BytesInput::new(vec![]),
+                    &observers,
+                    &ExitKind::Ok,
+                )
+                .unwrap()
+        };
+        // A change only in the unstable entry is ignored, changes in other entries are not
+        assert!(!is_interesting(vec![0, 1, 0, 0]));
+        assert!(is_interesting(vec![0, 0, 1, 0]));
+    }
 }
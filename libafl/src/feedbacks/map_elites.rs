@@ -0,0 +1,260 @@
+//! A MAP-Elites like archive over user-defined behavior descriptors.
+//!
+//! A [`BehaviorDescriptor`] maps the observers of a run to a [`Behavior`], i.e. a cell in the archive and a fitness.
+//! The [`MapElitesFeedback`] considers an input interesting if its cell is empty, or if it is fitter than the elite
+//! of its cell. Use it together with the [`crate::schedulers::MapElitesScheduler`], which keeps the archive up to
+//! date and schedules the elites of all cells uniformly, to drive fuzzing towards diverse behaviors.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::fmt::{self, Debug, Formatter};
+
+use hashbrown::HashMap;
+use libafl_bolts::{impl_serdeany, Named};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    corpus::{CorpusId, Testcase},
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    Error, HasMetadata,
+};
+
+/// The behavior of a run: the cell of the archive it falls into, and how fit it is.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Behavior {
+    /// The cell, e.g. the coordinates of the descriptors in a discretized grid, combined into one number
+    pub cell: u64,
+    /// The fitness, larger is better
+    pub fitness: f64,
+}
+
+/// Computes the [`Behavior`] of a run from its observers
+pub trait BehaviorDescriptor<OT> {
+    /// The behavior of the last run, or `None` if it does not belong into the archive
+    fn describe(&mut self, observers: &OT) -> Result<Option<Behavior>, Error>;
+}
+
+impl<F, OT> BehaviorDescriptor<OT> for F
+where
+    F: FnMut(&OT) -> Result<Option<Behavior>, Error>,
+{
+    fn describe(&mut self, observers: &OT) -> Result<Option<Behavior>, Error> {
+        self(observers)
+    }
+}
+
+/// The elite of a cell of the [`MapElitesMetadata`] archive
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Elite {
+    /// The behavior of the elite
+    pub behavior: Behavior,
+    /// The elite in the corpus
+    pub id: CorpusId,
+}
+
+/// The archive of a [`MapElitesFeedback`], holding the fittest testcase of each cell
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct MapElitesMetadata {
+    elites: Vec<Elite>,
+    cells: HashMap<u64, usize>,
+}
+
+impl_serdeany!(MapElitesMetadata);
+
+impl MapElitesMetadata {
+    /// The elites of all occupied cells
+    #[must_use]
+    pub fn elites(&self) -> &[Elite] {
+        &self.elites
+    }
+
+    /// The elite of the given cell
+    #[must_use]
+    pub fn elite(&self, cell: u64) -> Option<&Elite> {
+        self.cells.get(&cell).map(|idx| &self.elites[*idx])
+    }
+
+    /// Returns `true` if a testcase with the given behavior would become the elite of its cell
+    #[must_use]
+    pub fn improves(&self, behavior: &Behavior) -> bool {
+        !self
+            .elite(behavior.cell)
+            .is_some_and(|elite| behavior.fitness <= elite.behavior.fitness)
+    }
+
+    /// Make the testcase the elite of its cell, if it [`Self::improves`] the archive.
+    ///
+    /// Returns the id of the replaced elite, if any.
+    pub fn insert(&mut self, behavior: Behavior, id: CorpusId) -> Option<CorpusId> {
+        if !self.improves(&behavior) {
+            return None;
+        }
+        let elite = Elite { behavior, id };
+        if let Some(idx) = self.cells.get(&behavior.cell) {
+            let old = core::mem::replace(&mut self.elites[*idx], elite);
+            Some(old.id)
+        } else {
+            self.cells.insert(behavior.cell, self.elites.len());
+            self.elites.push(elite);
+            None
+        }
+    }
+
+    /// Remove the testcase from the archive, leaving its cell empty
+    pub fn remove(&mut self, id: CorpusId) {
+        let Some(idx) = self.elites.iter().position(|elite| elite.id == id) else {
+            return;
+        };
+        let removed = self.elites.swap_remove(idx);
+        self.cells.remove(&removed.behavior.cell);
+        if let Some(moved) = self.elites.get(idx) {
+            self.cells.insert(moved.behavior.cell, idx);
+        }
+    }
+}
+
+/// The [`Behavior`] of a testcase, added by the [`MapElitesFeedback`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct MapElitesTestcaseMetadata {
+    /// The behavior of the testcase
+    pub behavior: Behavior,
+}
+
+impl_serdeany!(MapElitesTestcaseMetadata);
+
+/// A feedback considering inputs interesting that are the fittest in their cell of the archive, see the
+/// [module docs](self).
+pub struct MapElitesFeedback<D> {
+    descriptor: D,
+    behavior: Option<Behavior>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl<D> MapElitesFeedback<D> {
+    /// Create a new [`MapElitesFeedback`] using the given [`BehaviorDescriptor`]
+    #[must_use]
+    pub fn new(descriptor: D) -> Self {
+        Self {
+            descriptor,
+            behavior: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+impl<D> Debug for MapElitesFeedback<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapElitesFeedback")
+            .field("behavior", &self.behavior)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D> Named for MapElitesFeedback<D> {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MapElitesFeedback");
+        &NAME
+    }
+}
+
+impl<D, S> StateInitializer<S> for MapElitesFeedback<D>
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.metadata_or_insert_with(MapElitesMetadata::default);
+        Ok(())
+    }
+}
+
+impl<D, EM, I, OT, S> Feedback<EM, I, OT, S> for MapElitesFeedback<D>
+where
+    D: BehaviorDescriptor<OT>,
+    S: HasMetadata,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        self.behavior = self.descriptor.describe(observers)?;
+        let res = match self.behavior {
+            Some(behavior) => state.metadata::<MapElitesMetadata>()?.improves(&behavior),
+            None => false,
+        };
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        // The input may have been added because of another feedback
+        if let Some(behavior) = self.behavior.take() {
+            if state.metadata::<MapElitesMetadata>()?.improves(&behavior) {
+                testcase.add_metadata(MapElitesTestcaseMetadata { behavior });
+            }
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.behavior = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Behavior, MapElitesMetadata};
+    use crate::corpus::CorpusId;
+
+    #[test]
+    fn test_map_elites_archive() {
+        let mut archive = MapElitesMetadata::default();
+        let behavior = |cell, fitness| Behavior { cell, fitness };
+
+        assert_eq!(archive.insert(behavior(1, 1.0), CorpusId(0)), None);
+        assert_eq!(archive.insert(behavior(2, 1.0), CorpusId(1)), None);
+        assert!(!archive.improves(&behavior(1, 0.5)));
+        assert_eq!(
+            archive.insert(behavior(1, 2.0), CorpusId(2)),
+            Some(CorpusId(0))
+        );
+        assert_eq!(archive.elites().len(), 2);
+
+        archive.remove(CorpusId(2));
+        assert!(archive.elite(1).is_none());
+        assert_eq!(archive.elite(2).unwrap().id, CorpusId(1));
+    }
+}
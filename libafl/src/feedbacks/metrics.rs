@@ -0,0 +1,298 @@
+//! The [`MetricFeedback`] turns a metric reported through a [`MetricsObserver`] into guidance.
+
+use alloc::{borrow::Cow, string::ToString};
+
+use hashbrown::HashSet;
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverHandle, StateInitializer},
+    observers::MetricsObserver,
+    Error, HasNamedMetadata,
+};
+
+/// The prefix of the metadata names
+pub const METRICFEEDBACK_PREFIX: &str = "metricfeedback_metadata_";
+
+/// When a [`MetricFeedback`] considers a value of its metric interesting
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricGoal {
+    /// Values larger than any value seen before
+    Maximize,
+    /// Values smaller than any value seen before
+    Minimize,
+    /// Values falling in a logarithmic bucket (like AFL hitcounts) not seen before
+    NewBucket,
+}
+
+/// The state of a [`MetricFeedback`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct MetricFeedbackMetadata {
+    /// The largest value seen so far
+    pub max: Option<i64>,
+    /// The smallest value seen so far
+    pub min: Option<i64>,
+    /// The buckets seen so far
+    pub buckets: HashSet<i32>,
+}
+
+impl_serdeany!(MetricFeedbackMetadata);
+
+/// The logarithmic bucket of a value; `0` for `0`, `±(bit length)` otherwise
+#[must_use]
+#[allow(clippy::cast_possible_wrap)]
+pub fn metric_bucket(value: i64) -> i32 {
+    let bits = (64 - value.unsigned_abs().leading_zeros()) as i32;
+    if value < 0 {
+        -bits
+    } else {
+        bits
+    }
+}
+
+/// A [`MetricFeedback`] considers a run interesting if a named metric reported by the harness
+/// reached a new maximum, a new minimum, or a new bucket, depending on its [`MetricGoal`].
+///
+/// Runs that did not report the metric are never interesting. The new value is only recorded once the run is
+/// added to the corpus (or the solutions), so a run rejected by another feedback does not raise the bar.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MetricFeedback {
+    name: Cow<'static, str>,
+    metric: Cow<'static, str>,
+    goal: MetricGoal,
+    o_ref: Handle<MetricsObserver>,
+    /// The value of the last interesting run, recorded in [`Feedback::append_metadata`]
+    last_value: Option<i64>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl MetricFeedback {
+    /// Create a new [`MetricFeedback`] for the given `metric` of the observer
+    #[must_use]
+    pub fn new(observer: &MetricsObserver, metric: &'static str, goal: MetricGoal) -> Self {
+        Self {
+            name: Cow::from(METRICFEEDBACK_PREFIX.to_string() + observer.name() + "_" + metric),
+            metric: Cow::Borrowed(metric),
+            goal,
+            o_ref: observer.handle(),
+            last_value: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+
+    /// Create a new [`MetricFeedback`] maximizing the given `metric`
+    #[must_use]
+    pub fn maximize(observer: &MetricsObserver, metric: &'static str) -> Self {
+        Self::new(observer, metric, MetricGoal::Maximize)
+    }
+
+    /// Create a new [`MetricFeedback`] minimizing the given `metric`
+    #[must_use]
+    pub fn minimize(observer: &MetricsObserver, metric: &'static str) -> Self {
+        Self::new(observer, metric, MetricGoal::Minimize)
+    }
+}
+
+impl<S> StateInitializer<S> for MetricFeedback
+where
+    S: HasNamedMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_named_metadata(&self.name, MetricFeedbackMetadata::default());
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for MetricFeedback
+where
+    OT: MatchName,
+    S: HasNamedMetadata,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or_else(|| Error::illegal_state("MetricsObserver is missing"))?;
+
+        self.last_value = None;
+        let res = if let Some(value) = observer.get(&self.metric) {
+            let meta = state.named_metadata::<MetricFeedbackMetadata>(&self.name)?;
+            let res = match self.goal {
+                MetricGoal::Maximize => !meta.max.is_some_and(|max| value <= max),
+                MetricGoal::Minimize => !meta.min.is_some_and(|min| value >= min),
+                MetricGoal::NewBucket => !meta.buckets.contains(&metric_bucket(value)),
+            };
+            if res {
+                self.last_value = Some(value);
+            }
+            res
+        } else {
+            false
+        };
+
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        _testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Some(value) = self.last_value.take() else {
+            return Ok(());
+        };
+        let meta = state.named_metadata_mut::<MetricFeedbackMetadata>(&self.name)?;
+        match self.goal {
+            MetricGoal::Maximize => meta.max = Some(meta.max.map_or(value, |max| max.max(value))),
+            MetricGoal::Minimize => meta.min = Some(meta.min.map_or(value, |min| min.min(value))),
+            MetricGoal::NewBucket => {
+                meta.buckets.insert(metric_bucket(value));
+            }
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last_value = None;
+        Ok(())
+    }
+}
+
+impl Named for MetricFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl HasObserverHandle for MetricFeedback {
+    type Observer = MetricsObserver;
+
+    #[inline]
+    fn observer_handle(&self) -> &Handle<MetricsObserver> {
+        &self.o_ref
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use super::{metric_bucket, MetricFeedback, MetricFeedbackMetadata};
+    use crate::{
+        corpus::{InMemoryCorpus, Testcase},
+        executors::ExitKind,
+        feedbacks::{ConstFeedback, Feedback, StateInitializer},
+        inputs::BytesInput,
+        observers::{report_metric, MetricsObserver, Observer},
+        state::StdState,
+        HasNamedMetadata,
+    };
+
+    #[test]
+    fn test_metric_bucket() {
+        assert_eq!(metric_bucket(0), 0);
+        assert_eq!(metric_bucket(1), 1);
+        assert_eq!(metric_bucket(3), 2);
+        assert_eq!(metric_bucket(4), 3);
+        assert_eq!(metric_bucket(-4), -3);
+        assert_eq!(metric_bucket(i64::MIN), -64);
+    }
+
+    #[test]
+    fn test_metric_feedback_commits_on_append() {
+        let mut observer = MetricsObserver::new("metrics");
+        let mut metric_feedback = MetricFeedback::maximize(&observer, "depth");
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        metric_feedback.init_state(&mut state).unwrap();
+        let input = BytesInput::new(vec![0]);
+
+        let mut run = |observer: &mut MetricsObserver, value: i64| {
+            Observer::<(), ()>::pre_exec(observer, &mut (), &()).unwrap();
+            report_metric("depth", value);
+            Observer::<(), ()>::post_exec(observer, &mut (), &(), &ExitKind::Ok).unwrap();
+        };
+
+        // An interesting run rejected by another feedback does not raise the maximum
+        run(&mut observer, 5);
+        let observers = tuple_list!(observer.clone());
+        assert!(metric_feedback
+            .is_interesting(&mut state, &mut (), &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        Feedback::<(), _, (MetricsObserver, ()), _>::discard_metadata(
+            &mut metric_feedback,
+            &mut state,
+            &input,
+        )
+        .unwrap();
+        let meta = state
+            .named_metadata::<MetricFeedbackMetadata>("metricfeedback_metadata_metrics_depth")
+            .unwrap();
+        assert_eq!(meta.max, None);
+
+        // Once added, the same value is no longer interesting
+        assert!(metric_feedback
+            .is_interesting(&mut state, &mut (), &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        metric_feedback
+            .append_metadata(
+                &mut state,
+                &mut (),
+                &observers,
+                &mut Testcase::new(input.clone()),
+            )
+            .unwrap();
+        assert!(!metric_feedback
+            .is_interesting(&mut state, &mut (), &input, &observers, &ExitKind::Ok)
+            .unwrap());
+
+        run(&mut observer, 6);
+        let observers = tuple_list!(observer);
+        assert!(metric_feedback
+            .is_interesting(&mut state, &mut (), &input, &observers, &ExitKind::Ok)
+            .unwrap());
+    }
+}
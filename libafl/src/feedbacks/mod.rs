@@ -4,20 +4,41 @@
 
 // TODO: make S of Feedback<S> an associated type when specialisation + AT is stable
 
-use alloc::borrow::Cow;
 #[cfg(feature = "track_hit_feedbacks")]
 use alloc::vec::Vec;
+use alloc::{borrow::Cow, boxed::Box};
 use core::{fmt::Debug, marker::PhantomData};
 
+#[cfg(feature = "std")]
+pub use black_box::{BlackBoxFeedback, BlackBoxFeedbackMetadata};
 #[cfg(feature = "std")]
 pub use concolic::ConcolicFeedback;
+pub use coverage_summary::{CoverageSummaryFeedback, CoverageSummaryMetadata};
+#[cfg(feature = "std")]
+pub use crash_bundle::CrashBundleFeedback;
+#[cfg(all(unix, feature = "std"))]
+pub use crash_context::{CrashContextFeedback, CrashContextMetadata};
+#[cfg(feature = "std")]
+pub use crash_report::{CrashReportFeedback, CrashReportFormat};
 pub use differential::DiffFeedback;
+pub use distance::{
+    DistanceFeedback, DistanceMetadata, TestcaseDistanceMetadata, DEFAULT_EXPLOITATION_TIME,
+};
+pub use entrypoint::{EntrypointCoverageFeedback, EntrypointStats, EntrypointStatsMetadata};
+#[cfg(feature = "std")]
+pub use exec_stats::{ExecStatsFeedback, ExecStatsMetadata};
 use libafl_bolts::{
     tuples::{Handle, Handled, MatchName, MatchNameRef},
     Named,
 };
 pub use list::*;
 pub use map::*;
+pub use map_elites::{
+    Behavior, BehaviorDescriptor, Elite, MapElitesFeedback, MapElitesMetadata,
+    MapElitesTestcaseMetadata,
+};
+#[cfg(feature = "std")]
+pub use metrics::{MetricFeedback, MetricFeedbackMetadata, MetricGoal};
 #[cfg(feature = "nautilus")]
 pub use nautilus::*;
 #[cfg(feature = "std")]
@@ -25,24 +46,53 @@ pub use new_hash_feedback::NewHashFeedback;
 #[cfg(feature = "std")]
 pub use new_hash_feedback::NewHashFeedbackMetadata;
 use serde::{Deserialize, Serialize};
+pub use slow_input::{SlowInputFeedback, SlowInputMetadata, SlowInputsMetadata};
+pub use slow_unit::{RuntimeBaselineMetadata, SlowUnitFeedback};
+pub use target_edges::{
+    TargetEdgesFeedback, TargetEdgesMetadata, TargetEdgesTestcaseMetadata,
+    DEFAULT_TARGET_EDGES_BOOST,
+};
+pub use testcase_stats::{CorpusStatsMetadata, TestcaseStatsFeedback, TestcaseStatsMetadata};
+pub use weighted_maps::{WeightedMap, WeightedMapsFeedback, WeightedMapsTuple};
 
 use crate::{corpus::Testcase, executors::ExitKind, observers::TimeObserver, Error};
 #[cfg(feature = "std")]
+pub mod black_box;
+#[cfg(feature = "std")]
 pub mod concolic;
+pub mod coverage_summary;
+#[cfg(feature = "std")]
+pub mod crash_bundle;
+#[cfg(all(unix, feature = "std"))]
+pub mod crash_context;
+#[cfg(feature = "std")]
+pub mod crash_report;
 #[cfg(feature = "std")]
 /// The module for `CustomFilenameToTestcaseFeedback`
 pub mod custom_filename;
 pub mod differential;
+pub mod distance;
+pub mod entrypoint;
+#[cfg(feature = "std")]
+pub mod exec_stats;
 /// The module for list feedback
 pub mod list;
 pub mod map;
+pub mod map_elites;
+#[cfg(feature = "std")]
+pub mod metrics;
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "std")]
 pub mod new_hash_feedback;
+pub mod slow_input;
+pub mod slow_unit;
 #[cfg(feature = "std")]
 pub mod stdio;
+pub mod target_edges;
+pub mod testcase_stats;
 pub mod transferred;
+pub mod weighted_maps;
 
 #[cfg(feature = "introspection")]
 use crate::state::HasClientPerfMonitor;
@@ -146,6 +196,109 @@ pub trait Feedback<EM, I, OT, S>: StateInitializer<S> + Named {
     }
 }
 
+/// A type-erased [`Feedback`], to assemble feedbacks at runtime without spelling out their types.
+///
+/// Each call goes through one virtual call, the [`Feedback`] trait is object-safe.
+pub struct BoxedFeedback<EM, I, OT, S> {
+    inner: Box<dyn Feedback<EM, I, OT, S>>,
+}
+
+impl<EM, I, OT, S> BoxedFeedback<EM, I, OT, S> {
+    /// Type-erases the given [`Feedback`]
+    pub fn new<F>(feedback: F) -> Self
+    where
+        F: Feedback<EM, I, OT, S> + 'static,
+    {
+        Self {
+            inner: Box::new(feedback),
+        }
+    }
+
+    /// The boxed [`Feedback`]
+    #[must_use]
+    pub fn into_inner(self) -> Box<dyn Feedback<EM, I, OT, S>> {
+        self.inner
+    }
+}
+
+impl<EM, I, OT, S> Debug for BoxedFeedback<EM, I, OT, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("BoxedFeedback")
+            .field(self.inner.name())
+            .finish()
+    }
+}
+
+impl<EM, I, OT, S> Named for BoxedFeedback<EM, I, OT, S> {
+    fn name(&self) -> &Cow<'static, str> {
+        self.inner.name()
+    }
+}
+
+impl<EM, I, OT, S> StateInitializer<S> for BoxedFeedback<EM, I, OT, S> {
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        self.inner.init_state(state)
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for BoxedFeedback<EM, I, OT, S> {
+    #[inline]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        self.inner
+            .is_interesting(state, manager, input, observers, exit_kind)
+    }
+
+    #[cfg(feature = "introspection")]
+    fn is_interesting_introspection(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        S: HasClientPerfMonitor,
+    {
+        self.inner
+            .is_interesting_introspection(state, manager, input, observers, exit_kind)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.inner.last_result()
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn append_hit_feedbacks(&self, list: &mut Vec<Cow<'static, str>>) -> Result<(), Error> {
+        self.inner.append_hit_feedbacks(list)
+    }
+
+    #[inline]
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        self.inner
+            .append_metadata(state, manager, observers, testcase)
+    }
+
+    #[inline]
+    fn discard_metadata(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.inner.discard_metadata(state, input)
+    }
+}
+
 /// Has an associated observer name (mostly used to retrieve the observer with `MatchName` from an `ObserverTuple`)
 pub trait HasObserverHandle {
     /// The observer for which we hold a reference
@@ -97,6 +97,11 @@ where
     ) -> Result<(), Error> {
         state.corpus().load_input_into(testcase)?;
         let input = testcase.input().as_ref().unwrap().clone();
+        if !self.ctx.is_valid_tree(&input.tree) {
+            // A tree of another grammar, e.g. loaded from the disk of a misconfigured node
+            log::warn!("Not adding the chunks of a tree that is not valid in this grammar");
+            return Ok(());
+        }
         let meta = state
             .metadata_map_mut()
             .get_mut::<NautilusChunksMetadata>()
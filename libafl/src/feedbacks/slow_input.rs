@@ -0,0 +1,259 @@
+//! A soft timeout, collecting slow inputs as performance bugs instead of treating them as hangs.
+//!
+//! The executor keeps its hard timeout, which kills runs that hang. Runs finishing normally, but slower than the
+//! soft timeout of the [`SlowInputFeedback`], point at algorithmic complexity issues. The feedback keeps the
+//! slowest of them, marks them with a [`SlowInputMetadata`], and optionally writes them to a directory of their
+//! own, apart from the crashes and the hangs.
+
+use alloc::borrow::Cow;
+use core::{marker::PhantomData, time::Duration};
+#[cfg(feature = "std")]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    inputs::Input,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::TimeObserver,
+    Error, HasMetadata,
+};
+
+/// The slow inputs found so far
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlowInputsMetadata {
+    /// The runtime of the slowest input so far
+    pub slowest: Option<Duration>,
+    /// The number of slow inputs kept
+    pub count: u64,
+}
+
+impl_serdeany!(SlowInputsMetadata);
+
+impl SlowInputsMetadata {
+    /// If a run taking `runtime` is slower than the soft timeout, and than all slow inputs so far
+    #[must_use]
+    pub fn is_new_slowest(&self, runtime: Duration, soft_timeout: Duration) -> bool {
+        runtime >= soft_timeout && self.slowest.map_or(true, |slowest| runtime > slowest)
+    }
+
+    /// Record a slow input taking `runtime`
+    pub fn record(&mut self, runtime: Duration) {
+        self.slowest = Some(self.slowest.map_or(runtime, |slowest| slowest.max(runtime)));
+        self.count += 1;
+    }
+}
+
+/// The runtime of a slow testcase
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlowInputMetadata {
+    /// The runtime of the testcase
+    pub runtime: Duration,
+}
+
+impl_serdeany!(SlowInputMetadata);
+
+/// A feedback for a soft timeout: a run finishing normally is interesting if it takes longer than the soft
+/// timeout, and longer than all slow inputs before.
+///
+/// Runs which crashed or hit the hard timeout of the executor are never interesting to it. Use it in the
+/// feedback, or, to keep the slow inputs apart from the corpus, in the objective, and combine it with the
+/// map feedback in an AND to keep one slow input per new path.
+#[derive(Debug, Clone)]
+pub struct SlowInputFeedback {
+    observer_handle: Handle<TimeObserver>,
+    soft_timeout: Duration,
+    #[cfg(feature = "std")]
+    out_dir: Option<PathBuf>,
+    last_runtime: Option<Duration>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl SlowInputFeedback {
+    /// Creates a new [`SlowInputFeedback`], for runs of the given [`TimeObserver`] slower than `soft_timeout`
+    #[must_use]
+    pub fn new(observer: &TimeObserver, soft_timeout: Duration) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            soft_timeout,
+            #[cfg(feature = "std")]
+            out_dir: None,
+            last_runtime: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+
+    /// Also writes each slow input to `out_dir`, named after its runtime in milliseconds
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn with_out_dir<P>(mut self, out_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.out_dir = Some(out_dir.into());
+        self
+    }
+
+    /// The soft timeout
+    #[must_use]
+    pub fn soft_timeout(&self) -> Duration {
+        self.soft_timeout
+    }
+}
+
+impl Named for SlowInputFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SlowInputFeedback");
+        &NAME
+    }
+}
+
+impl<S> StateInitializer<S> for SlowInputFeedback
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.metadata_or_insert_with(SlowInputsMetadata::default);
+        #[cfg(feature = "std")]
+        if let Some(out_dir) = &self.out_dir {
+            fs::create_dir_all(out_dir)?;
+        }
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for SlowInputFeedback
+where
+    EM: EventFirer<State = S>,
+    OT: MatchName,
+    S: HasMetadata,
+    I: Input,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .ok_or_else(|| Error::key_not_found("TimeObserver for SlowInputFeedback missing"))?;
+        self.last_runtime = None;
+        let res = match (exit_kind, *observer.last_runtime()) {
+            (ExitKind::Ok, Some(runtime))
+                if state
+                    .metadata::<SlowInputsMetadata>()?
+                    .is_new_slowest(runtime, self.soft_timeout) =>
+            {
+                self.last_runtime = Some(runtime);
+                true
+            }
+            _ => false,
+        };
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Some(runtime) = self.last_runtime.take() else {
+            return Ok(());
+        };
+        let slow_inputs = state.metadata_mut::<SlowInputsMetadata>()?;
+        slow_inputs.record(runtime);
+        let count = slow_inputs.count;
+        testcase.add_metadata(SlowInputMetadata { runtime });
+
+        #[cfg(feature = "std")]
+        if let (Some(out_dir), Some(input)) = (&self.out_dir, testcase.input()) {
+            write_slow_input(out_dir, &format!("{}ms", runtime.as_millis()), input)?;
+        }
+
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: Cow::Borrowed("slow inputs"),
+                value: UserStats::new(UserStatsValue::Number(count), AggregatorOps::Sum),
+                phantom: PhantomData,
+            },
+        )?;
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last_runtime = None;
+        Ok(())
+    }
+}
+
+/// Write a slow input to `out_dir`, as `<prefix>-<name of the input>`, and return its path
+#[cfg(feature = "std")]
+pub(crate) fn write_slow_input<I>(out_dir: &Path, prefix: &str, input: &I) -> Result<PathBuf, Error>
+where
+    I: Input,
+{
+    let path = out_dir.join(format!("{prefix}-{}", input.generate_name(None)));
+    input.to_file(&path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::SlowInputsMetadata;
+
+    #[test]
+    fn test_slow_inputs() {
+        let soft_timeout = Duration::from_millis(100);
+        let mut slow_inputs = SlowInputsMetadata::default();
+        assert!(!slow_inputs.is_new_slowest(Duration::from_millis(50), soft_timeout));
+        assert!(slow_inputs.is_new_slowest(Duration::from_millis(150), soft_timeout));
+
+        slow_inputs.record(Duration::from_millis(150));
+        assert!(!slow_inputs.is_new_slowest(Duration::from_millis(120), soft_timeout));
+        assert!(slow_inputs.is_new_slowest(Duration::from_millis(300), soft_timeout));
+        assert_eq!(slow_inputs.count, 1);
+    }
+}
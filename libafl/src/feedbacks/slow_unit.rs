@@ -0,0 +1,412 @@
+//! Slow-unit detection against a statistical baseline of the runtimes, as `-report_slow_units` of `libFuzzer`.
+//!
+//! The [`SlowUnitFeedback`] keeps the runtimes of recent runs in a [`RuntimeBaselineMetadata`], and estimates
+//! their median and median absolute deviation (MAD). A run is a slow unit if it takes a large multiple of the
+//! median, far outside of the usual deviation, and longer than the slowest unit before. Unlike a fixed
+//! threshold, this adapts to the speed of the target and of the machine. A minimum runtime keeps the jitter of
+//! very fast targets from counting as slow units.
+
+use alloc::{borrow::Cow, collections::VecDeque, vec::Vec};
+use core::{marker::PhantomData, time::Duration};
+#[cfg(feature = "std")]
+use std::{fs, path::PathBuf};
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+#[cfg(feature = "std")]
+use crate::feedbacks::slow_input::write_slow_input;
+use crate::{
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, SlowInputMetadata, StateInitializer},
+    inputs::Input,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::TimeObserver,
+    Error, HasMetadata,
+};
+
+/// The default number of recent runtimes the baseline is estimated from
+pub const DEFAULT_BASELINE_SAMPLES: usize = 1024;
+
+/// The minimum number of runtimes before any run is considered a slow unit
+pub const MIN_BASELINE_SAMPLES: usize = 64;
+
+/// The default multiple of the median runtime a slow unit takes at least
+pub const DEFAULT_SLOW_UNIT_MULTIPLE: f64 = 10.0;
+
+/// The default number of MADs above the median a slow unit takes at least
+pub const DEFAULT_SLOW_UNIT_MADS: f64 = 8.0;
+
+/// The default minimum runtime of a slow unit, whatever the baseline
+pub const DEFAULT_SLOW_UNIT_MIN_RUNTIME: Duration = Duration::from_millis(10);
+
+/// The number of new runtimes after which the median and MAD are estimated again
+const BASELINE_UPDATE_INTERVAL: usize = 64;
+
+/// A new slow unit must be this much slower than the slowest unit before, as in `libFuzzer`
+const SLOWEST_UNIT_MARGIN: f64 = 1.1;
+
+/// The runtimes of recent runs, and their median and median absolute deviation
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RuntimeBaselineMetadata {
+    samples: VecDeque<Duration>,
+    capacity: usize,
+    since_update: usize,
+    median: Option<Duration>,
+    mad: Duration,
+    /// The runtime of the slowest unit so far
+    pub slowest_unit: Option<Duration>,
+    /// The number of slow units found
+    pub slow_units: u64,
+}
+
+impl_serdeany!(RuntimeBaselineMetadata);
+
+impl Default for RuntimeBaselineMetadata {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASELINE_SAMPLES)
+    }
+}
+
+impl RuntimeBaselineMetadata {
+    /// Creates a new [`RuntimeBaselineMetadata`], estimating the baseline from the last `capacity` runtimes
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            since_update: 0,
+            median: None,
+            mad: Duration::ZERO,
+            slowest_unit: None,
+            slow_units: 0,
+        }
+    }
+
+    /// Adds the runtime of a run, updating the estimates from time to time
+    pub fn add(&mut self, runtime: Duration) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(runtime);
+        self.since_update += 1;
+        if self.median.is_none() && self.samples.len() >= MIN_BASELINE_SAMPLES
+            || self.since_update >= BASELINE_UPDATE_INTERVAL
+        {
+            self.update();
+        }
+    }
+
+    /// Estimates the median and MAD of the runtimes again
+    pub fn update(&mut self) {
+        self.since_update = 0;
+        if self.samples.len() < MIN_BASELINE_SAMPLES {
+            return;
+        }
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        let median = median_duration(&mut sorted);
+        let mut deviations: Vec<Duration> = sorted
+            .iter()
+            .map(|runtime| runtime.abs_diff(median))
+            .collect();
+        self.mad = median_duration(&mut deviations);
+        self.median = Some(median);
+    }
+
+    /// The median runtime, once enough runs were seen
+    #[must_use]
+    pub fn median(&self) -> Option<Duration> {
+        self.median
+    }
+
+    /// The median absolute deviation of the runtimes
+    #[must_use]
+    pub fn mad(&self) -> Duration {
+        self.mad
+    }
+
+    /// If a run taking `runtime` is a slow unit: at least `min_runtime`, at least `multiple` times the median, at
+    /// least `mads` MADs above it, and slower than the slowest unit before
+    #[must_use]
+    pub fn is_slow_unit(
+        &self,
+        runtime: Duration,
+        min_runtime: Duration,
+        multiple: f64,
+        mads: f64,
+    ) -> bool {
+        let Some(median) = self.median else {
+            return false;
+        };
+        // Compare as floats, scaling a `Duration` panics for negative or huge factors
+        let secs = runtime.as_secs_f64();
+        runtime >= min_runtime
+            && secs >= median.as_secs_f64() * multiple
+            && secs >= median.as_secs_f64() + self.mad.as_secs_f64() * mads
+            && self.slowest_unit.map_or(true, |slowest| {
+                secs > slowest.as_secs_f64() * SLOWEST_UNIT_MARGIN
+            })
+    }
+
+    /// Record a slow unit taking `runtime`
+    pub fn record_slow_unit(&mut self, runtime: Duration) {
+        self.slowest_unit = Some(
+            self.slowest_unit
+                .map_or(runtime, |slowest| slowest.max(runtime)),
+        );
+        self.slow_units += 1;
+    }
+}
+
+/// The median of the durations, sorting them
+fn median_duration(durations: &mut [Duration]) -> Duration {
+    durations.sort_unstable();
+    let mid = durations.len() / 2;
+    if durations.len() % 2 == 0 {
+        (durations[mid - 1] + durations[mid]) / 2
+    } else {
+        durations[mid]
+    }
+}
+
+/// Flags the runs taking a large multiple of the baseline runtime as slow units, see [`RuntimeBaselineMetadata`].
+///
+/// The slow units get a [`SlowInputMetadata`] with their runtime. Use it in the objective, and write the slow
+/// units to a directory of their own with [`SlowUnitFeedback::with_out_dir`]. Runs that crashed or timed out
+/// are not part of the baseline, and are never slow units.
+#[derive(Debug, Clone)]
+pub struct SlowUnitFeedback {
+    observer_handle: Handle<TimeObserver>,
+    min_runtime: Duration,
+    multiple: f64,
+    mads: f64,
+    samples: usize,
+    #[cfg(feature = "std")]
+    out_dir: Option<PathBuf>,
+    last_runtime: Option<Duration>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl SlowUnitFeedback {
+    /// Creates a new [`SlowUnitFeedback`], for the runtimes of the given [`TimeObserver`]
+    #[must_use]
+    pub fn new(observer: &TimeObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            min_runtime: DEFAULT_SLOW_UNIT_MIN_RUNTIME,
+            multiple: DEFAULT_SLOW_UNIT_MULTIPLE,
+            mads: DEFAULT_SLOW_UNIT_MADS,
+            samples: DEFAULT_BASELINE_SAMPLES,
+            #[cfg(feature = "std")]
+            out_dir: None,
+            last_runtime: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+
+    /// Sets the multiple of the median runtime, and the number of MADs above it, a slow unit takes at least
+    ///
+    /// # Panics
+    /// Panics if `multiple` or `mads` is negative or not finite.
+    #[must_use]
+    pub fn with_thresholds(mut self, multiple: f64, mads: f64) -> Self {
+        assert!(
+            multiple.is_finite() && multiple >= 0.0 && mads.is_finite() && mads >= 0.0,
+            "The slow unit thresholds must be finite and not negative, got multiple {multiple} and mads {mads}"
+        );
+        self.multiple = multiple;
+        self.mads = mads;
+        self
+    }
+
+    /// Sets the minimum runtime of a slow unit, [`DEFAULT_SLOW_UNIT_MIN_RUNTIME`] by default
+    #[must_use]
+    pub fn with_min_runtime(mut self, min_runtime: Duration) -> Self {
+        self.min_runtime = min_runtime;
+        self
+    }
+
+    /// Sets the number of recent runtimes the baseline is estimated from
+    #[must_use]
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Also writes each slow unit to `out_dir`, next to a `.time` file with its runtime and the baseline
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn with_out_dir<P>(mut self, out_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.out_dir = Some(out_dir.into());
+        self
+    }
+}
+
+impl Named for SlowUnitFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SlowUnitFeedback");
+        &NAME
+    }
+}
+
+impl<S> StateInitializer<S> for SlowUnitFeedback
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        let samples = self.samples;
+        state.metadata_or_insert_with(|| RuntimeBaselineMetadata::new(samples));
+        #[cfg(feature = "std")]
+        if let Some(out_dir) = &self.out_dir {
+            fs::create_dir_all(out_dir)?;
+        }
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for SlowUnitFeedback
+where
+    EM: EventFirer<State = S>,
+    OT: MatchName,
+    S: HasMetadata,
+    I: Input,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .ok_or_else(|| Error::key_not_found("TimeObserver for SlowUnitFeedback missing"))?;
+        self.last_runtime = None;
+        let mut res = false;
+        if let (ExitKind::Ok, Some(runtime)) = (exit_kind, *observer.last_runtime()) {
+            let baseline = state.metadata_mut::<RuntimeBaselineMetadata>()?;
+            // Check before adding, so a run of slow units does not shift the baseline it is compared to
+            res = baseline.is_slow_unit(runtime, self.min_runtime, self.multiple, self.mads);
+            baseline.add(runtime);
+            if res {
+                self.last_runtime = Some(runtime);
+            }
+        }
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Some(runtime) = self.last_runtime.take() else {
+            return Ok(());
+        };
+        let baseline = state.metadata_mut::<RuntimeBaselineMetadata>()?;
+        baseline.record_slow_unit(runtime);
+        let slow_units = baseline.slow_units;
+        #[cfg(feature = "std")]
+        let (median, mad) = (baseline.median().unwrap_or_default(), baseline.mad());
+        testcase.add_metadata(SlowInputMetadata { runtime });
+
+        #[cfg(feature = "std")]
+        if let (Some(out_dir), Some(input)) = (&self.out_dir, testcase.input()) {
+            let mut time_path = write_slow_input(out_dir, "slow-unit", input)?.into_os_string();
+            time_path.push(".time");
+            fs::write(
+                time_path,
+                format!(
+                    "runtime_us: {}\nmedian_us: {}\nmad_us: {}\n",
+                    runtime.as_micros(),
+                    median.as_micros(),
+                    mad.as_micros()
+                ),
+            )?;
+        }
+
+        log::info!("Slow unit: {runtime:?}");
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: Cow::Borrowed("slow units"),
+                value: UserStats::new(UserStatsValue::Number(slow_units), AggregatorOps::Sum),
+                phantom: PhantomData,
+            },
+        )?;
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last_runtime = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{RuntimeBaselineMetadata, MIN_BASELINE_SAMPLES};
+
+    #[test]
+    fn test_runtime_baseline() {
+        let min = Duration::from_millis(1);
+        let mut baseline = RuntimeBaselineMetadata::new(256);
+        for i in 0..MIN_BASELINE_SAMPLES as u64 - 1 {
+            baseline.add(Duration::from_micros(100 + i % 10));
+        }
+        // Not enough runs yet
+        assert!(!baseline.is_slow_unit(Duration::from_secs(1), min, 10.0, 8.0));
+
+        baseline.add(Duration::from_micros(105));
+        let median = baseline.median().unwrap();
+        assert!(median >= Duration::from_micros(100) && median <= Duration::from_micros(110));
+        assert!(!baseline.is_slow_unit(Duration::from_micros(200), min, 10.0, 8.0));
+        assert!(baseline.is_slow_unit(Duration::from_millis(5), min, 10.0, 8.0));
+        // Below the minimum runtime
+        assert!(!baseline.is_slow_unit(
+            Duration::from_millis(5),
+            Duration::from_millis(10),
+            10.0,
+            8.0
+        ));
+        // Negative thresholds do not panic
+        assert!(baseline.is_slow_unit(Duration::from_millis(5), min, -1.0, -1.0));
+
+        baseline.record_slow_unit(Duration::from_millis(5));
+        assert!(!baseline.is_slow_unit(Duration::from_micros(5200), min, 10.0, 8.0));
+        assert!(baseline.is_slow_unit(Duration::from_millis(6), min, 10.0, 8.0));
+    }
+}
@@ -0,0 +1,316 @@
+//! Directed fuzzing toward a set of target edges, e.g. the edges of the source lines changed by a patch.
+//!
+//! The [`TargetEdgesMetadata`] of the state holds the indices of the target edges in the coverage map.
+//! For `SanitizerCoverage` targets, `libafl_targets::patch` computes them from a unified diff.
+//! The [`TargetEdgesFeedback`] considers a run interesting if it reaches a target edge no testcase reached
+//! before, and records the target edges each new testcase reaches in its [`TargetEdgesTestcaseMetadata`].
+//! A weighted scheduler with the [`crate::schedulers::testcase_score::TargetEdgesTestcaseScore`] then picks
+//! the testcases reaching the changed code more often.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::marker::PhantomData;
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::MapObserver,
+    Error, HasMetadata,
+};
+
+/// The default factor the weight of a testcase reaching target edges is multiplied with
+pub const DEFAULT_TARGET_EDGES_BOOST: f64 = 8.0;
+
+/// The target edges of a directed campaign, and which of them were reached so far
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TargetEdgesMetadata {
+    /// The indices of the target edges in the coverage map, sorted
+    edges: Vec<usize>,
+    /// If each target edge was reached, in the order of `edges`
+    reached: Vec<bool>,
+    /// The factor the weight of a testcase reaching target edges is multiplied with
+    boost: f64,
+}
+
+impl_serdeany!(TargetEdgesMetadata);
+
+impl Default for TargetEdgesMetadata {
+    fn default() -> Self {
+        Self::new([])
+    }
+}
+
+impl TargetEdgesMetadata {
+    /// Create a new [`TargetEdgesMetadata`] for the given indices of the coverage map
+    #[must_use]
+    pub fn new<T>(edges: T) -> Self
+    where
+        T: IntoIterator<Item = usize>,
+    {
+        let mut edges: Vec<usize> = edges.into_iter().collect();
+        edges.sort_unstable();
+        edges.dedup();
+        let reached = vec![false; edges.len()];
+        Self {
+            edges,
+            reached,
+            boost: DEFAULT_TARGET_EDGES_BOOST,
+        }
+    }
+
+    /// Set the factor the weight of a testcase reaching target edges is multiplied with
+    #[must_use]
+    pub fn with_boost(mut self, boost: f64) -> Self {
+        self.boost = boost;
+        self
+    }
+
+    /// The indices of the target edges in the coverage map, sorted
+    #[must_use]
+    pub fn edges(&self) -> &[usize] {
+        &self.edges
+    }
+
+    /// The factor the weight of a testcase reaching target edges is multiplied with
+    #[must_use]
+    pub fn boost(&self) -> f64 {
+        self.boost
+    }
+
+    /// The number of target edges
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// If there are no target edges
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    /// If the edge at `idx` of the coverage map is a target edge
+    #[must_use]
+    pub fn is_target(&self, idx: usize) -> bool {
+        self.edges.binary_search(&idx).is_ok()
+    }
+
+    /// If the target edge at `idx` of the coverage map was reached
+    #[must_use]
+    pub fn is_reached(&self, idx: usize) -> bool {
+        self.edges
+            .binary_search(&idx)
+            .is_ok_and(|pos| self.reached[pos])
+    }
+
+    /// The number of target edges reached so far
+    #[must_use]
+    pub fn reached_count(&self) -> usize {
+        self.reached.iter().filter(|reached| **reached).count()
+    }
+
+    /// Mark the target edges at the given indices as reached, returns how many were not reached before
+    pub fn mark_reached(&mut self, edges: &[usize]) -> usize {
+        let mut new = 0;
+        for idx in edges {
+            if let Ok(pos) = self.edges.binary_search(idx) {
+                if !self.reached[pos] {
+                    self.reached[pos] = true;
+                    new += 1;
+                }
+            }
+        }
+        new
+    }
+
+    /// The target edges the map observer hit in the last run
+    pub fn hits<O>(&self, observer: &O) -> Vec<usize>
+    where
+        O: MapObserver,
+    {
+        let initial = observer.initial();
+        let len = observer.usable_count();
+        self.edges
+            .iter()
+            .copied()
+            .take_while(|idx| *idx < len)
+            .filter(|idx| observer.get(*idx) != initial)
+            .collect()
+    }
+}
+
+/// The target edges a testcase reaches
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TargetEdgesTestcaseMetadata {
+    /// The indices of the target edges in the coverage map, sorted
+    pub edges: Vec<usize>,
+}
+
+impl_serdeany!(TargetEdgesTestcaseMetadata);
+
+/// A feedback for directed fuzzing, finding the target edges of the [`TargetEdgesMetadata`] each run reaches.
+///
+/// A run reaching a target edge for the first time is interesting. Each new testcase gets a
+/// [`TargetEdgesTestcaseMetadata`], and the number of reached target edges is reported as a user stat.
+/// Use it in an OR with the map feedback of the same observer.
+#[derive(Debug, Clone)]
+pub struct TargetEdgesFeedback<C, O> {
+    map_ref: Handle<C>,
+    last_hits: Vec<usize>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+    phantom: PhantomData<O>,
+}
+
+impl<C, O> TargetEdgesFeedback<C, O>
+where
+    C: Named,
+{
+    /// Create a new [`TargetEdgesFeedback`], looking for the target edges in the given map observer
+    #[must_use]
+    pub fn new(map_observer: &C) -> Self {
+        Self {
+            map_ref: map_observer.handle(),
+            last_hits: Vec::new(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, O> Named for TargetEdgesFeedback<C, O> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TargetEdgesFeedback");
+        &NAME
+    }
+}
+
+impl<C, O, S> StateInitializer<S> for TargetEdgesFeedback<C, O>
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.metadata_or_insert_with(TargetEdgesMetadata::default);
+        Ok(())
+    }
+}
+
+impl<C, EM, I, O, OT, S> Feedback<EM, I, OT, S> for TargetEdgesFeedback<C, O>
+where
+    C: AsRef<O>,
+    EM: EventFirer<State = S>,
+    O: MapObserver,
+    OT: MatchName,
+    S: HasMetadata,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.map_ref)
+            .ok_or_else(|| Error::key_not_found("Map observer for TargetEdgesFeedback missing"))?
+            .as_ref();
+        let targets = state.metadata::<TargetEdgesMetadata>()?;
+        self.last_hits = targets.hits(observer);
+        let res = self.last_hits.iter().any(|idx| !targets.is_reached(*idx));
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        if self.last_hits.is_empty() {
+            return Ok(());
+        }
+        let edges = core::mem::take(&mut self.last_hits);
+        let targets = state.metadata_mut::<TargetEdgesMetadata>()?;
+        let new = targets.mark_reached(&edges);
+        let (reached, total) = (targets.reached_count() as u64, targets.len() as u64);
+        testcase.add_metadata(TargetEdgesTestcaseMetadata { edges });
+
+        if new > 0 {
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::Borrowed("target edges"),
+                    value: UserStats::new(
+                        UserStatsValue::Ratio(reached, total),
+                        AggregatorOps::Max,
+                    ),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last_hits.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TargetEdgesMetadata;
+    use crate::observers::StdMapObserver;
+
+    #[test]
+    fn test_target_edges() {
+        let mut targets = TargetEdgesMetadata::new([7, 2, 5, 2, 64]).with_boost(4.0);
+        assert_eq!(targets.edges(), &[2, 5, 7, 64]);
+        assert!(targets.is_target(5));
+        assert!(!targets.is_target(3));
+
+        let mut map = [0u8, 0, 1, 0, 0, 0, 0, 3];
+        let observer = unsafe { StdMapObserver::new("edges", &mut map) };
+        let hits = targets.hits(&observer);
+        assert_eq!(hits, [2, 7]);
+
+        assert_eq!(targets.mark_reached(&hits), 2);
+        assert_eq!(targets.mark_reached(&[2, 3]), 0);
+        assert!(targets.is_reached(7));
+        assert!(!targets.is_reached(5));
+        assert_eq!(targets.reached_count(), 2);
+    }
+}
@@ -0,0 +1,244 @@
+//! Standard execution statistics of each testcase, and their aggregates over the corpus.
+//!
+//! The [`TestcaseStatsFeedback`] records the execution time and the input length of each new testcase
+//! in its [`TestcaseStatsMetadata`], while map feedbacks, such as [`crate::feedbacks::MaxMapFeedback`], add
+//! the number of map entries the testcase covered first. Schedulers and corpus pruners can read them from
+//! there, instead of measuring them again. The [`crate::schedulers::CorpusStatsScheduler`] sums them up over
+//! the testcases in the corpus, in the [`CorpusStatsMetadata`].
+
+use alloc::borrow::Cow;
+use core::{marker::PhantomData, time::Duration};
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    HasLen, Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    feedbacks::{Feedback, StateInitializer},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::TimeObserver,
+    Error, HasMetadata,
+};
+
+/// Execution statistics of a testcase, measured when it was added to the corpus
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TestcaseStatsMetadata {
+    /// The execution time of the run that added the testcase
+    pub exec_time: Option<Duration>,
+    /// The length of the input
+    pub input_len: Option<usize>,
+    /// The number of map entries this testcase covered first, over all map feedbacks
+    pub novel_entries: usize,
+}
+
+impl_serdeany!(TestcaseStatsMetadata);
+
+/// The sums of the [`TestcaseStatsMetadata`] of the testcases in the corpus, kept by the
+/// [`crate::schedulers::CorpusStatsScheduler`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CorpusStatsMetadata {
+    /// The number of testcases
+    pub count: u64,
+    /// The sum of the execution times
+    pub total_exec_time: Duration,
+    /// The sum of the input lengths
+    pub total_input_len: u64,
+    /// The sum of the novel map entries
+    pub total_novel_entries: u64,
+}
+
+impl_serdeany!(CorpusStatsMetadata);
+
+impl CorpusStatsMetadata {
+    /// Add the stats of a new testcase
+    pub fn add(&mut self, stats: &TestcaseStatsMetadata) {
+        self.count += 1;
+        self.total_exec_time += stats.exec_time.unwrap_or_default();
+        self.total_input_len += stats.input_len.unwrap_or_default() as u64;
+        self.total_novel_entries += stats.novel_entries as u64;
+    }
+
+    /// Remove the stats of a testcase which left the corpus
+    pub fn remove(&mut self, stats: &TestcaseStatsMetadata) {
+        self.count = self.count.saturating_sub(1);
+        self.total_exec_time = self
+            .total_exec_time
+            .saturating_sub(stats.exec_time.unwrap_or_default());
+        self.total_input_len = self
+            .total_input_len
+            .saturating_sub(stats.input_len.unwrap_or_default() as u64);
+        self.total_novel_entries = self
+            .total_novel_entries
+            .saturating_sub(stats.novel_entries as u64);
+    }
+
+    /// The average execution time of the testcases
+    #[must_use]
+    pub fn avg_exec_time(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.total_exec_time / u32::try_from(self.count).unwrap_or(u32::MAX)
+        }
+    }
+
+    /// The average input length of the testcases
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn avg_input_len(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total_input_len as f64 / self.count as f64
+        }
+    }
+
+    /// The average number of novel map entries of the testcases
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn avg_novel_entries(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total_novel_entries as f64 / self.count as f64
+        }
+    }
+}
+
+/// Fills the [`TestcaseStatsMetadata`] of each new testcase, and reports the corpus averages as user stats.
+///
+/// Is never interesting (use with an Eager OR). Place it after the map feedbacks, so the novel entries they
+/// recorded are part of the testcase stats. The averages are taken from the [`CorpusStatsMetadata`], which the
+/// [`crate::schedulers::CorpusStatsScheduler`] updates once the testcase is in the corpus, so they do not
+/// include the testcase being added yet. Solutions never reach the scheduler, so they are not counted.
+#[derive(Debug, Clone)]
+pub struct TestcaseStatsFeedback {
+    time_ref: Handle<TimeObserver>,
+}
+
+impl TestcaseStatsFeedback {
+    /// Create a new [`TestcaseStatsFeedback`], taking the execution times from the given [`TimeObserver`]
+    #[must_use]
+    pub fn new(time_observer: &TimeObserver) -> Self {
+        Self {
+            time_ref: time_observer.handle(),
+        }
+    }
+}
+
+impl Named for TestcaseStatsFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TestcaseStatsFeedback");
+        &NAME
+    }
+}
+
+impl<S> StateInitializer<S> for TestcaseStatsFeedback
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.metadata_or_insert_with(CorpusStatsMetadata::default);
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for TestcaseStatsFeedback
+where
+    EM: EventFirer<State = S>,
+    I: HasLen,
+    OT: MatchName,
+    S: HasMetadata,
+{
+    #[cfg(feature = "track_hit_feedbacks")]
+    #[inline]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let exec_time = *observers
+            .get(&self.time_ref)
+            .ok_or_else(|| Error::key_not_found("TimeObserver for TestcaseStatsFeedback missing"))?
+            .last_runtime();
+        let input_len = testcase.input().as_ref().map(HasLen::len);
+
+        let stats = testcase.metadata_or_insert_with(TestcaseStatsMetadata::default);
+        stats.exec_time = exec_time;
+        stats.input_len = input_len;
+
+        let corpus_stats = *state.metadata_or_insert_with(CorpusStatsMetadata::default);
+
+        let avg_exec_time = corpus_stats.avg_exec_time().as_secs_f64() * 1_000_000.0;
+        for (name, value) in [
+            ("avg exec time (us)", avg_exec_time),
+            ("avg input len", corpus_stats.avg_input_len()),
+            ("avg novel entries", corpus_stats.avg_novel_entries()),
+        ] {
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::Borrowed(name),
+                    value: UserStats::new(UserStatsValue::Float(value), AggregatorOps::Avg),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{CorpusStatsMetadata, TestcaseStatsMetadata};
+
+    #[test]
+    fn test_corpus_stats() {
+        let mut corpus_stats = CorpusStatsMetadata::default();
+        assert_eq!(corpus_stats.avg_exec_time(), Duration::ZERO);
+
+        corpus_stats.add(&TestcaseStatsMetadata {
+            exec_time: Some(Duration::from_millis(10)),
+            input_len: Some(4),
+            novel_entries: 3,
+        });
+        corpus_stats.add(&TestcaseStatsMetadata {
+            exec_time: Some(Duration::from_millis(30)),
+            input_len: Some(8),
+            novel_entries: 0,
+        });
+        assert_eq!(corpus_stats.avg_exec_time(), Duration::from_millis(20));
+        assert!((corpus_stats.avg_input_len() - 6.0).abs() < f64::EPSILON);
+        assert!((corpus_stats.avg_novel_entries() - 1.5).abs() < f64::EPSILON);
+
+        corpus_stats.remove(&TestcaseStatsMetadata {
+            exec_time: Some(Duration::from_millis(10)),
+            input_len: Some(4),
+            novel_entries: 3,
+        });
+        assert_eq!(corpus_stats.count, 1);
+        assert_eq!(corpus_stats.avg_exec_time(), Duration::from_millis(30));
+        assert_eq!(corpus_stats.total_novel_entries, 0);
+    }
+}
@@ -0,0 +1,308 @@
+//! The [`WeightedMapsFeedback`] aggregates several map observers into a single novelty decision.
+//!
+//! Instead of chaining one [`crate::feedbacks::MaxMapFeedback`] per map with `feedback_or!`, where a single new
+//! entry in any map (e.g. in a noisy value profile map) makes an input interesting, each map gets a weight.
+//! An input is interesting if the weighted number of new entries over all maps reaches a threshold.
+//! The feedback also reports a single, weighted coverage percentage to the monitor.
+
+use alloc::{borrow::Cow, string::ToString};
+use core::{fmt::Debug, marker::PhantomData};
+
+use libafl_bolts::{
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    AsIter, Named,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{Feedback, MapFeedbackMetadata, StateInitializer},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::MapObserver,
+    Error, HasNamedMetadata,
+};
+
+/// The prefix of the metadata names of the maps of a [`WeightedMapsFeedback`]
+pub const WEIGHTEDMAPS_PREFIX: &str = "weightedmaps_";
+
+/// A map observer and its weight, part of the tuple list of a [`WeightedMapsFeedback`]
+#[derive(Debug, Clone)]
+pub struct WeightedMap<C, O> {
+    map_ref: Handle<C>,
+    name: Cow<'static, str>,
+    weight: f64,
+    phantom: PhantomData<O>,
+}
+
+impl<C, O> WeightedMap<C, O>
+where
+    C: Named,
+{
+    /// Use the given map observer with the given weight, i.e. each new entry in this map adds `weight` to the score
+    #[must_use]
+    pub fn new(map_observer: &C, weight: f64) -> Self {
+        Self {
+            map_ref: map_observer.handle(),
+            name: Cow::from(WEIGHTEDMAPS_PREFIX.to_string() + map_observer.name()),
+            weight,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The weight of this map
+    #[must_use]
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+}
+
+/// A tuple list of [`WeightedMap`]s
+pub trait WeightedMapsTuple<OT, S> {
+    /// Add the history of each map to the state
+    fn init_all(&self, state: &mut S) -> Result<(), Error>;
+
+    /// The weighted number of map entries that reached a new maximum in this run
+    fn score_all(&self, state: &S, observers: &OT) -> Result<f64, Error>;
+
+    /// Merge the maps into their histories.
+    ///
+    /// Returns the sum of the weighted coverage of each map, and the sum of the weights.
+    fn update_all(&self, state: &mut S, observers: &OT) -> Result<(f64, f64), Error>;
+}
+
+impl<OT, S> WeightedMapsTuple<OT, S> for () {
+    fn init_all(&self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn score_all(&self, _state: &S, _observers: &OT) -> Result<f64, Error> {
+        Ok(0.0)
+    }
+
+    fn update_all(&self, _state: &mut S, _observers: &OT) -> Result<(f64, f64), Error> {
+        Ok((0.0, 0.0))
+    }
+}
+
+impl<C, O, OT, S, Tail> WeightedMapsTuple<OT, S> for (WeightedMap<C, O>, Tail)
+where
+    C: AsRef<O>,
+    O: MapObserver + for<'it> AsIter<'it, Item = O::Entry>,
+    O::Entry: 'static + Default + Debug + DeserializeOwned + Serialize + PartialOrd,
+    OT: MatchName,
+    S: HasNamedMetadata,
+    Tail: WeightedMapsTuple<OT, S>,
+{
+    fn init_all(&self, state: &mut S) -> Result<(), Error> {
+        state.add_named_metadata(&self.0.name, MapFeedbackMetadata::<O::Entry>::default());
+        self.1.init_all(state)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn score_all(&self, state: &S, observers: &OT) -> Result<f64, Error> {
+        let map = &self.0;
+        let observer = observers
+            .get(&map.map_ref)
+            .ok_or_else(|| Error::key_not_found(format!("Map observer for {} missing", map.name)))?
+            .as_ref();
+        let history = &state
+            .named_metadata::<MapFeedbackMetadata<O::Entry>>(&map.name)?
+            .history_map;
+
+        let initial = observer.initial();
+        let novel = observer
+            .as_iter()
+            .enumerate()
+            .filter(|(i, item)| {
+                **item != initial && !history.get(*i).is_some_and(|existing| **item <= *existing)
+            })
+            .count();
+        Ok(map.weight * novel as f64 + self.1.score_all(state, observers)?)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn update_all(&self, state: &mut S, observers: &OT) -> Result<(f64, f64), Error> {
+        let map = &self.0;
+        let observer = observers
+            .get(&map.map_ref)
+            .ok_or_else(|| Error::key_not_found(format!("Map observer for {} missing", map.name)))?
+            .as_ref();
+        let map_state = state.named_metadata_mut::<MapFeedbackMetadata<O::Entry>>(&map.name)?;
+
+        let initial = observer.initial();
+        if map_state.history_map.len() < observer.len() {
+            map_state.history_map.resize(observer.len(), initial);
+        }
+        for (i, item) in observer.as_iter().enumerate() {
+            let existing = &mut map_state.history_map[i];
+            if *item > *existing {
+                if *existing == initial {
+                    map_state.num_covered_map_indexes += 1;
+                }
+                *existing = *item;
+            }
+        }
+
+        let len = map_state.history_map.len();
+        let coverage = if len == 0 {
+            0.0
+        } else {
+            map_state.num_covered_map_indexes as f64 / len as f64
+        };
+        let (tail_coverage, tail_weights) = self.1.update_all(state, observers)?;
+        Ok((
+            map.weight * coverage + tail_coverage,
+            map.weight + tail_weights,
+        ))
+    }
+}
+
+/// A feedback combining several map observers with per-map weights, see the [module docs](self).
+///
+/// Only use it for maps where larger values are better, like hitcounts, as for a [`crate::feedbacks::MaxMapFeedback`].
+#[derive(Debug, Clone)]
+pub struct WeightedMapsFeedback<MT> {
+    name: Cow<'static, str>,
+    maps: MT,
+    threshold: f64,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl<MT> WeightedMapsFeedback<MT> {
+    /// Create a new [`WeightedMapsFeedback`] for a tuple list of [`WeightedMap`]s.
+    ///
+    /// By default, a weighted score of `1.0` is interesting,
+    /// e.g. one new entry in a map with weight `1.0`, or four in a map with weight `0.25`.
+    #[must_use]
+    pub fn new(name: &'static str, maps: MT) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            maps,
+            threshold: 1.0,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+
+    /// Set the weighted score from which an input is interesting
+    #[must_use]
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+impl<MT> Named for WeightedMapsFeedback<MT> {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<MT, S> StateInitializer<S> for WeightedMapsFeedback<MT>
+where
+    MT: WeightedMapsTuple<(), S>,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        self.maps.init_all(state)
+    }
+}
+
+impl<EM, I, MT, OT, S> Feedback<EM, I, OT, S> for WeightedMapsFeedback<MT>
+where
+    EM: EventFirer<State = S>,
+    MT: WeightedMapsTuple<OT, S>,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let res = self.maps.score_all(state, observers)? >= self.threshold;
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        observers: &OT,
+        _testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let (coverage, weights) = self.maps.update_all(state, observers)?;
+        let percent = if weights > 0.0 {
+            coverage / weights
+        } else {
+            0.0
+        };
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: self.name.clone(),
+                value: UserStats::new(UserStatsValue::Percent(percent), AggregatorOps::Avg),
+                phantom: PhantomData,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use super::{WeightedMap, WeightedMapsFeedback, WeightedMapsTuple};
+    use crate::{
+        corpus::InMemoryCorpus, inputs::BytesInput, observers::StdMapObserver, state::StdState,
+    };
+
+    #[test]
+    fn test_weighted_maps_score() {
+        let mut edges = [0u8, 1, 0, 1];
+        let mut values = [1u8, 1, 1, 0];
+        let edges_observer = unsafe { StdMapObserver::new("edges", &mut edges) };
+        let values_observer = unsafe { StdMapObserver::new("values", &mut values) };
+
+        let maps = tuple_list!(
+            WeightedMap::new(&edges_observer, 1.0),
+            WeightedMap::new(&values_observer, 0.25)
+        );
+        let observers = tuple_list!(edges_observer, values_observer);
+
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        WeightedMapsTuple::<(), _>::init_all(&maps, &mut state).unwrap();
+        assert!((maps.score_all(&state, &observers).unwrap() - 2.75).abs() < f64::EPSILON);
+
+        let (coverage, weights) = maps.update_all(&mut state, &observers).unwrap();
+        // (1.0 * 2/4 + 0.25 * 3/4) / 1.25
+        assert!((coverage / weights - 0.55).abs() < 1e-9);
+        assert!(maps.score_all(&state, &observers).unwrap() < f64::EPSILON);
+
+        let _feedback = WeightedMapsFeedback::new("weighted", maps).with_threshold(0.5);
+    }
+}
@@ -1,20 +1,28 @@
 //! The `Fuzzer` is the main struct for a fuzz campaign.
 
-use alloc::{string::ToString, vec::Vec};
+use alloc::{borrow::Cow, collections::VecDeque, string::ToString, vec::Vec};
 use core::{fmt::Debug, marker::PhantomData, time::Duration};
 
-use libafl_bolts::current_time;
+use hashbrown::HashMap;
+use libafl_bolts::{current_time, hash_std};
 use serde::{de::DeserializeOwned, Serialize};
 
 #[cfg(feature = "introspection")]
 use crate::monitors::PerfFeature;
 use crate::{
-    corpus::{Corpus, CorpusId, HasCurrentCorpusId, HasTestcase, Testcase},
-    events::{Event, EventConfig, EventFirer, EventProcessor, ProgressReporter},
+    corpus::{
+        global_id::GlobalIdsMetadata, Corpus, CorpusId, GlobalIdMetadata, HasCurrentCorpusId,
+        HasTestcase, Testcase,
+    },
+    events::{
+        wait_while_paused, Event, EventConfig, EventFirer, EventProcessor, ProgressReporter,
+        PAUSE_POLL_INTERVAL,
+    },
     executors::{Executor, ExitKind, HasObservers},
     feedbacks::Feedback,
     inputs::UsesInput,
     mark_feature_time,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
     observers::ObserversTuple,
     schedulers::Scheduler,
     stages::{HasCurrentStageId, StagesTuple},
@@ -27,7 +35,10 @@ use crate::{
 };
 
 /// Send a monitor update all 15 (or more) seconds
-const STATS_TIMEOUT_DEFAULT: Duration = Duration::from_secs(15);
+pub(crate) const STATS_TIMEOUT_DEFAULT: Duration = Duration::from_secs(15);
+
+/// Report the hit rate of the [`EvaluationCache`] every this many lookups
+const EVAL_CACHE_STATS_INTERVAL: u64 = 4096;
 
 /// Holds a scheduler
 pub trait HasScheduler: UsesState
@@ -183,6 +194,21 @@ pub trait Evaluator<E, EM>: UsesState {
         send_events: bool,
     ) -> Result<(ExecuteInputResult, Option<CorpusId>), Error>;
 
+    /// Runs an input created by a mutator, like [`Evaluator::evaluate_input`].
+    ///
+    /// Fuzzers may skip mutants repeating a recently evaluated input, see [`StdFuzzer::with_evaluation_cache`].
+    /// Used by the mutational stages, while inputs from other sources, e.g. imported from other clients or
+    /// loaded from the initial corpus, go through [`Evaluator::evaluate_input`] and are always evaluated.
+    fn evaluate_mutant(
+        &mut self,
+        state: &mut Self::State,
+        executor: &mut E,
+        manager: &mut EM,
+        input: <Self::State as UsesInput>::Input,
+    ) -> Result<(ExecuteInputResult, Option<CorpusId>), Error> {
+        self.evaluate_input(state, executor, manager, input)
+    }
+
     /// Runs the input and triggers observers and feedback.
     /// Adds an input, to the corpus even if it's not considered `interesting` by the `feedback`.
     /// Returns the `index` of the new testcase in the corpus.
@@ -301,12 +327,100 @@ pub enum ExecuteInputResult {
     Solution,
 }
 
+/// A bounded LRU cache of the hashes of recently evaluated mutants.
+///
+/// Mutators frequently regenerate inputs that were just evaluated.
+/// For a deterministic target, an exact repeat cannot be interesting again, so the [`StdFuzzer`] skips its
+/// execution in [`Evaluator::evaluate_mutant`]. Do not use it for targets whose coverage or outcome for the same
+/// input varies between runs, as the repeats could find something new there.
+/// Imported and initial inputs are always evaluated.
+#[derive(Debug, Clone)]
+pub struct EvaluationCache {
+    capacity: usize,
+    /// The input hashes, mapped to the time of their last use
+    entries: HashMap<u64, u64>,
+    /// The input hashes in order of use, may contain outdated entries
+    order: VecDeque<(u64, u64)>,
+    clock: u64,
+    lookups: u64,
+    hits: u64,
+}
+
+impl EvaluationCache {
+    /// Create a new [`EvaluationCache`] holding up to `capacity` input hashes
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            clock: 0,
+            lookups: 0,
+            hits: 0,
+        }
+    }
+
+    /// Look up the hash of an input, inserting it if it was not evaluated recently.
+    ///
+    /// Returns `true` if the hash was in the cache.
+    pub fn check_and_insert(&mut self, hash: u64) -> bool {
+        self.lookups += 1;
+        self.clock += 1;
+        let hit = self.entries.insert(hash, self.clock).is_some();
+        if hit {
+            self.hits += 1;
+        }
+        self.order.push_back((hash, self.clock));
+
+        while self.entries.len() > self.capacity {
+            let Some((old_hash, used)) = self.order.pop_front() else {
+                break;
+            };
+            if self.entries.get(&old_hash) == Some(&used) {
+                self.entries.remove(&old_hash);
+            }
+        }
+        // Drop outdated entries left behind by hits
+        if self.order.len() > 2 * self.capacity.max(1) {
+            let entries = &self.entries;
+            self.order
+                .retain(|(hash, used)| entries.get(hash) == Some(used));
+        }
+        hit
+    }
+
+    /// The number of lookups so far
+    #[must_use]
+    pub fn lookups(&self) -> u64 {
+        self.lookups
+    }
+
+    /// The number of lookups that found the input in the cache
+    #[must_use]
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// The number of input hashes in the cache
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the cache is empty
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
 /// Your default fuzzer instance, for everyday use.
 #[derive(Debug)]
 pub struct StdFuzzer<CS, F, OF, S> {
     scheduler: CS,
     feedback: F,
     objective: OF,
+    eval_cache: Option<EvaluationCache>,
     phantom: PhantomData<S>,
 }
 
@@ -368,7 +482,13 @@ where
     CS: Scheduler<S::Input, S>,
     F: Feedback<EM, S::Input, OT, S>,
     OF: Feedback<EM, S::Input, OT, S>,
-    S: HasCorpus + HasSolutions + HasExecutions + HasCorpus + HasCurrentCorpusId + State,
+    S: HasCorpus
+        + HasSolutions
+        + HasExecutions
+        + HasCorpus
+        + HasCurrentCorpusId
+        + HasMetadata
+        + State,
     S::Corpus: Corpus<Input = S::Input>,    //delete me
     S::Solutions: Corpus<Input = S::Input>, //delete me
 {
@@ -487,6 +607,7 @@ where
         match exec_res {
             ExecuteInputResult::Corpus => {
                 if manager.should_send() {
+                    let global_id = GlobalIdMetadata::local(state, &input)?;
                     manager.fire(
                         state,
                         Event::NewTestcase {
@@ -497,6 +618,8 @@ where
                             client_config: manager.configuration(),
                             time: current_time(),
                             forward_id: None,
+                            global_id: global_id.id,
+                            parent_global_id: global_id.parent,
                             #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
                             node_id: None,
                         },
@@ -543,13 +666,18 @@ where
                 self.objective_mut().discard_metadata(state, input)?;
 
                 // Add the input to the main corpus
+                let global_id = GlobalIdMetadata::local(state, input)?;
                 let mut testcase = Testcase::from(input.clone());
                 #[cfg(feature = "track_hit_feedbacks")]
                 self.feedback_mut()
                     .append_hit_feedbacks(testcase.hit_feedbacks_mut())?;
                 self.feedback_mut()
                     .append_metadata(state, manager, observers, &mut testcase)?;
+                testcase.add_metadata(global_id);
                 let id = state.corpus_mut().add(testcase)?;
+                state
+                    .metadata_or_insert_with(GlobalIdsMetadata::default)
+                    .insert(global_id.id);
                 self.scheduler_mut().on_add(state, id)?;
 
                 Ok(Some(id))
@@ -583,7 +711,7 @@ where
     OT: ObserversTuple<S::Input, S> + Serialize + DeserializeOwned,
     F: Feedback<EM, S::Input, OT, S>,
     OF: Feedback<EM, S::Input, OT, S>,
-    S: HasCorpus + HasSolutions + HasExecutions + State,
+    S: HasCorpus + HasSolutions + HasExecutions + HasMetadata + State,
     S::Corpus: Corpus<Input = S::Input>,    //delete me
     S::Solutions: Corpus<Input = S::Input>, //delete me
 {
@@ -618,7 +746,7 @@ where
     EM: EventFirer<State = S>,
     F: Feedback<EM, S::Input, E::Observers, S>,
     OF: Feedback<EM, S::Input, E::Observers, S>,
-    S: HasCorpus + HasSolutions + HasExecutions + HasLastFoundTime + State,
+    S: HasCorpus + HasSolutions + HasExecutions + HasLastFoundTime + HasMetadata + State,
     S::Corpus: Corpus<Input = S::Input>,    //delete me
     S::Solutions: Corpus<Input = S::Input>, //delete me
 {
@@ -634,6 +762,37 @@ where
     ) -> Result<(ExecuteInputResult, Option<CorpusId>), Error> {
         self.evaluate_input_with_observers(state, executor, manager, input, send_events)
     }
+
+    /// Skips mutants repeating one of the recently evaluated mutants, if the [`EvaluationCache`] is enabled
+    fn evaluate_mutant(
+        &mut self,
+        state: &mut Self::State,
+        executor: &mut E,
+        manager: &mut EM,
+        input: <Self::State as UsesInput>::Input,
+    ) -> Result<(ExecuteInputResult, Option<CorpusId>), Error> {
+        if let Some(cache) = &mut self.eval_cache {
+            let hit = cache.check_and_insert(hash_std(&postcard::to_allocvec(&input)?));
+            if cache.lookups() % EVAL_CACHE_STATS_INTERVAL == 0 {
+                let (hits, lookups) = (cache.hits(), cache.lookups());
+                manager.fire(
+                    state,
+                    Event::UpdateUserStats {
+                        name: Cow::from("eval cache hits"),
+                        value: UserStats::new(
+                            UserStatsValue::Ratio(hits, lookups),
+                            AggregatorOps::Avg,
+                        ),
+                        phantom: PhantomData,
+                    },
+                )?;
+            }
+            if hit {
+                return Ok((ExecuteInputResult::None, None));
+            }
+        }
+        self.evaluate_input_events(state, executor, manager, input, true)
+    }
     fn add_disabled_input(
         &mut self,
         state: &mut Self::State,
@@ -718,7 +877,12 @@ where
         // Add the input to the main corpus
         self.feedback_mut()
             .append_metadata(state, manager, &*observers, &mut testcase)?;
+        let global_id = GlobalIdMetadata::local(state, &input)?;
+        testcase.add_metadata(global_id);
         let id = state.corpus_mut().add(testcase)?;
+        state
+            .metadata_or_insert_with(GlobalIdsMetadata::default)
+            .insert(global_id.id);
         self.scheduler_mut().on_add(state, id)?;
 
         let observers_buf = if manager.configuration() == EventConfig::AlwaysUnique {
@@ -736,6 +900,8 @@ where
                 client_config: manager.configuration(),
                 time: current_time(),
                 forward_id: None,
+                global_id: global_id.id,
+                parent_global_id: global_id.parent,
                 #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
                 node_id: None,
             },
@@ -775,6 +941,8 @@ where
             id // we are resuming
         } else {
             let id = self.scheduler.next(state)?;
+            // Whatever the scheduler, let the corpus load the input while the stages start
+            state.corpus().prefetch(id)?;
             state.set_corpus_id(id)?; // set up for resume
             id
         };
@@ -797,6 +965,17 @@ where
         // Execute the manager
         manager.process(self, state, executor)?;
 
+        // Keep processing events, but don't fuzz, while an operator paused this client
+        wait_while_paused(
+            self,
+            executor,
+            state,
+            manager,
+            PAUSE_POLL_INTERVAL,
+            STATS_TIMEOUT_DEFAULT,
+            |_, _| Ok(()),
+        )?;
+
         // Mark the elapsed time for the manager
         #[cfg(feature = "introspection")]
         state.introspection_monitor_mut().mark_manager_time();
@@ -832,10 +1011,27 @@ where
             scheduler,
             feedback,
             objective,
+            eval_cache: None,
             phantom: PhantomData,
         }
     }
 
+    /// Skip the execution of mutants that are exact repeats of one of the last `capacity` evaluated mutants.
+    ///
+    /// Assumes a deterministic target, see [`EvaluationCache`].
+    /// The hit rate is reported to the monitor as `eval cache hits`.
+    #[must_use]
+    pub fn with_evaluation_cache(mut self, capacity: usize) -> Self {
+        self.eval_cache = Some(EvaluationCache::new(capacity));
+        self
+    }
+
+    /// The [`EvaluationCache`], if enabled
+    #[must_use]
+    pub fn evaluation_cache(&self) -> Option<&EvaluationCache> {
+        self.eval_cache.as_ref()
+    }
+
     /// Runs the input and triggers observers
     pub fn execute_input<E, EM>(
         &mut self,
@@ -963,3 +1159,81 @@ where
         unimplemented!("NopFuzzer cannot fuzz");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use super::{EvaluationCache, Evaluator, StdFuzzer};
+    use crate::{
+        corpus::InMemoryCorpus,
+        events::NopEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        schedulers::RandScheduler,
+        state::StdState,
+    };
+
+    #[test]
+    fn test_evaluation_cache() {
+        let mut cache = EvaluationCache::new(2);
+        assert!(!cache.check_and_insert(1));
+        assert!(!cache.check_and_insert(2));
+        assert!(cache.check_and_insert(1));
+        // 2 is the least recently used entry
+        assert!(!cache.check_and_insert(3));
+        assert!(cache.check_and_insert(1));
+        assert!(!cache.check_and_insert(2));
+        assert_eq!(cache.len(), 2);
+        assert_eq!((cache.hits(), cache.lookups()), (2, 6));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_evaluation_cache_mutants_only() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut mgr = NopEventManager::new();
+        let mut fuzzer =
+            StdFuzzer::new(RandScheduler::new(), feedback, objective).with_evaluation_cache(16);
+
+        let mut runs = 0_u64;
+        let mut harness = |_input: &BytesInput| {
+            runs += 1;
+            ExitKind::Ok
+        };
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+
+        let input = BytesInput::new(b"dup".to_vec());
+        for _ in 0..3 {
+            fuzzer
+                .evaluate_mutant(&mut state, &mut executor, &mut mgr, input.clone())
+                .unwrap();
+        }
+        // The same input, imported from another client, is evaluated anyway
+        fuzzer
+            .evaluate_input(&mut state, &mut executor, &mut mgr, input)
+            .unwrap();
+        drop(executor);
+
+        assert_eq!(runs, 2);
+        let cache = fuzzer.evaluation_cache().unwrap();
+        assert_eq!((cache.hits(), cache.lookups()), (2, 3));
+    }
+}
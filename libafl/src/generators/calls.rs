@@ -0,0 +1,50 @@
+//! Generates sequences of API calls, see [`crate::inputs::calls`].
+
+use core::num::NonZero;
+
+use libafl_bolts::rands::Rand;
+
+use crate::{
+    generators::Generator,
+    inputs::calls::{ApiSpec, CallSequenceInput},
+    state::HasRand,
+    Error,
+};
+
+/// Generates random [`CallSequenceInput`]s the [`ApiSpec`] accepts, e.g. for the initial corpus
+#[derive(Debug, Clone)]
+pub struct CallSequenceGenerator {
+    spec: ApiSpec,
+    max_calls: NonZero<usize>,
+}
+
+impl CallSequenceGenerator {
+    /// Creates a new [`CallSequenceGenerator`], generating sequences of 1 to `max_calls` calls
+    #[must_use]
+    pub fn new(spec: ApiSpec, max_calls: NonZero<usize>) -> Self {
+        Self { spec, max_calls }
+    }
+}
+
+impl<S> Generator<CallSequenceInput, S> for CallSequenceGenerator
+where
+    S: HasRand,
+{
+    fn generate(&mut self, state: &mut S) -> Result<CallSequenceInput, Error> {
+        let rand = state.rand_mut();
+        let len = rand.below(self.max_calls) + 1;
+        let mut input = CallSequenceInput::default();
+        for _ in 0..len {
+            let Some(call) = self.spec.random_call(rand, input.calls()) else {
+                break;
+            };
+            input.push(call);
+        }
+        if input.calls().is_empty() {
+            return Err(Error::illegal_argument(
+                "The API spec has no function callable without handles",
+            ));
+        }
+        Ok(input)
+    }
+}
@@ -7,11 +7,17 @@ use libafl_bolts::rands::Rand;
 
 use crate::{inputs::bytes::BytesInput, nonzero, state::HasRand, Error};
 
+pub mod calls;
+pub use calls::CallSequenceGenerator;
+
 pub mod gramatron;
 use core::cmp::max;
 
 pub use gramatron::*;
 
+pub mod structured;
+pub use structured::{ElfFormat, FormatDescriptor, PngFormat, StructuredGenerator, ZipFormat};
+
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "nautilus")]
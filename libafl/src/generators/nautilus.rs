@@ -113,6 +113,7 @@ impl<S: HasRand> Generator<NautilusInput, S> for NautilusGenerator<'_> {
         let len = self.ctx.get_random_len_for_nt(&nonterm);
         let mut input = NautilusInput::empty();
         self.generate_from_nonterminal(state.rand_mut(), &mut input, nonterm, len);
+        input.set_context_hash(Some(self.ctx.fingerprint()));
         Ok(input)
     }
 }
@@ -0,0 +1,283 @@
+//! Generators for minimally valid binary container formats, to bootstrap campaigns without a seed corpus.
+//!
+//! A [`FormatDescriptor`] knows how to lay out a format, with correct magic values, header sizes and checksums,
+//! around a random payload. The [`StructuredGenerator`] turns any descriptor into a [`Generator`].
+
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+
+use libafl_bolts::rands::Rand;
+
+use crate::{generators::Generator, inputs::BytesInput, nonzero, state::HasRand, Error};
+
+/// The CRC-32 (ISO-HDLC) checksum, as used by PNG and ZIP
+#[must_use]
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// The Adler-32 checksum, as used by zlib
+#[must_use]
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for byte in data {
+        a = (a + u32::from(*byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// `size` random bytes
+fn rand_bytes<R>(rand: &mut R, size: usize) -> Vec<u8>
+where
+    R: Rand,
+{
+    (0..size).map(|_| rand.below(nonzero!(256)) as u8).collect()
+}
+
+/// Describes how to generate a minimally valid file of a format
+pub trait FormatDescriptor {
+    /// Generate a file whose payload is at most `max_payload` bytes
+    fn generate<R>(&self, rand: &mut R, max_payload: NonZeroUsize) -> Vec<u8>
+    where
+        R: Rand;
+}
+
+/// Generates inputs with the layout of a [`FormatDescriptor`]
+#[derive(Clone, Debug)]
+pub struct StructuredGenerator<F> {
+    format: F,
+    max_payload: NonZeroUsize,
+}
+
+impl<F> StructuredGenerator<F> {
+    /// Returns a new [`StructuredGenerator`] for the given format, with payloads of up to `max_payload` bytes
+    #[must_use]
+    pub fn new(format: F, max_payload: NonZeroUsize) -> Self {
+        Self {
+            format,
+            max_payload,
+        }
+    }
+}
+
+impl<F, S> Generator<BytesInput, S> for StructuredGenerator<F>
+where
+    F: FormatDescriptor,
+    S: HasRand,
+{
+    fn generate(&mut self, state: &mut S) -> Result<BytesInput, Error> {
+        Ok(BytesInput::new(
+            self.format.generate(state.rand_mut(), self.max_payload),
+        ))
+    }
+}
+
+/// A grayscale PNG image with random pixels and correct chunk CRCs
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PngFormat;
+
+impl PngFormat {
+    fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let crc = crc32(&out[start..]);
+        out.extend_from_slice(&crc.to_be_bytes());
+    }
+}
+
+impl FormatDescriptor for PngFormat {
+    fn generate<R>(&self, rand: &mut R, max_payload: NonZeroUsize) -> Vec<u8>
+    where
+        R: Rand,
+    {
+        // Each row is a filter type byte, followed by one byte per pixel
+        let width = 1 + rand.below(NonZeroUsize::new(max_payload.get().min(64)).unwrap());
+        let max_rows = (max_payload.get() / (width + 1)).clamp(1, 64);
+        let height = 1 + rand.below(NonZeroUsize::new(max_rows).unwrap());
+
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+        // Bit depth 8, grayscale, deflate, adaptive filtering, no interlace
+        ihdr.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+        let mut raw = Vec::with_capacity(height * (width + 1));
+        for _ in 0..height {
+            raw.push(0);
+            raw.extend(rand_bytes(rand, width));
+        }
+
+        // A zlib stream of stored deflate blocks
+        let mut idat = vec![0x78, 0x01];
+        let blocks = raw.chunks(0xffff);
+        let count = blocks.len();
+        for (idx, block) in blocks.enumerate() {
+            idat.push(u8::from(idx + 1 == count));
+            let len = block.len() as u16;
+            idat.extend_from_slice(&len.to_le_bytes());
+            idat.extend_from_slice(&(!len).to_le_bytes());
+            idat.extend_from_slice(block);
+        }
+        idat.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+        let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+        Self::chunk(&mut out, b"IHDR", &ihdr);
+        Self::chunk(&mut out, b"IDAT", &idat);
+        Self::chunk(&mut out, b"IEND", &[]);
+        out
+    }
+}
+
+/// A little-endian x86-64 ELF executable with a single loadable segment containing random code
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ElfFormat;
+
+impl ElfFormat {
+    const BASE_ADDR: u64 = 0x40_0000;
+    const EHDR_SIZE: u16 = 64;
+    const PHDR_SIZE: u16 = 56;
+}
+
+impl FormatDescriptor for ElfFormat {
+    fn generate<R>(&self, rand: &mut R, max_payload: NonZeroUsize) -> Vec<u8>
+    where
+        R: Rand,
+    {
+        let code_size = 1 + rand.below(max_payload);
+        let code = rand_bytes(rand, code_size);
+        let headers = u64::from(Self::EHDR_SIZE + Self::PHDR_SIZE);
+        let file_size = headers + code.len() as u64;
+
+        let mut out = Vec::with_capacity(file_size as usize);
+        // e_ident: magic, 64 bit, little endian, version 1, System V ABI
+        out.extend_from_slice(b"\x7fELF\x02\x01\x01\x00");
+        out.extend_from_slice(&[0; 8]);
+        out.extend_from_slice(&2_u16.to_le_bytes()); // e_type: executable
+        out.extend_from_slice(&0x3e_u16.to_le_bytes()); // e_machine: x86-64
+        out.extend_from_slice(&1_u32.to_le_bytes()); // e_version
+        out.extend_from_slice(&(Self::BASE_ADDR + headers).to_le_bytes()); // e_entry
+        out.extend_from_slice(&u64::from(Self::EHDR_SIZE).to_le_bytes()); // e_phoff
+        out.extend_from_slice(&0_u64.to_le_bytes()); // e_shoff
+        out.extend_from_slice(&0_u32.to_le_bytes()); // e_flags
+        out.extend_from_slice(&Self::EHDR_SIZE.to_le_bytes()); // e_ehsize
+        out.extend_from_slice(&Self::PHDR_SIZE.to_le_bytes()); // e_phentsize
+        out.extend_from_slice(&1_u16.to_le_bytes()); // e_phnum
+        out.extend_from_slice(&64_u16.to_le_bytes()); // e_shentsize
+        out.extend_from_slice(&0_u16.to_le_bytes()); // e_shnum
+        out.extend_from_slice(&0_u16.to_le_bytes()); // e_shstrndx
+
+        out.extend_from_slice(&1_u32.to_le_bytes()); // p_type: PT_LOAD
+        out.extend_from_slice(&5_u32.to_le_bytes()); // p_flags: R + X
+        out.extend_from_slice(&0_u64.to_le_bytes()); // p_offset
+        out.extend_from_slice(&Self::BASE_ADDR.to_le_bytes()); // p_vaddr
+        out.extend_from_slice(&Self::BASE_ADDR.to_le_bytes()); // p_paddr
+        out.extend_from_slice(&file_size.to_le_bytes()); // p_filesz
+        out.extend_from_slice(&file_size.to_le_bytes()); // p_memsz
+        out.extend_from_slice(&0x1000_u64.to_le_bytes()); // p_align
+
+        out.extend_from_slice(&code);
+        out
+    }
+}
+
+/// A ZIP archive holding a single stored (uncompressed) file with random contents
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ZipFormat;
+
+impl FormatDescriptor for ZipFormat {
+    fn generate<R>(&self, rand: &mut R, max_payload: NonZeroUsize) -> Vec<u8>
+    where
+        R: Rand,
+    {
+        let data_size = rand.below(max_payload);
+        let data = rand_bytes(rand, data_size);
+        let name_len = 1 + rand.below(nonzero!(8));
+        let name: Vec<u8> = (0..name_len)
+            .map(|_| b'a' + rand.below(nonzero!(26)) as u8)
+            .collect();
+        let crc = crc32(&data);
+        let size = (data.len() as u32).to_le_bytes();
+
+        // The fields shared by the local file header and the central directory entry:
+        // version needed, flags, stored, time, date (1980-01-01), crc, compressed and uncompressed size, name length
+        let mut common = Vec::with_capacity(26);
+        common.extend_from_slice(&20_u16.to_le_bytes());
+        common.extend_from_slice(&0_u16.to_le_bytes());
+        common.extend_from_slice(&0_u16.to_le_bytes());
+        common.extend_from_slice(&0_u16.to_le_bytes());
+        common.extend_from_slice(&0x21_u16.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&size);
+        common.extend_from_slice(&size);
+        common.extend_from_slice(&(name_len as u16).to_le_bytes());
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&0x0403_4b50_u32.to_le_bytes());
+        out.extend_from_slice(&common);
+        out.extend_from_slice(&0_u16.to_le_bytes()); // extra field length
+        out.extend_from_slice(&name);
+        out.extend_from_slice(&data);
+
+        let cd_offset = out.len() as u32;
+        out.extend_from_slice(&0x0201_4b50_u32.to_le_bytes());
+        out.extend_from_slice(&20_u16.to_le_bytes()); // version made by
+        out.extend_from_slice(&common);
+        // extra field and comment length, disk number, internal and external attributes, local header offset
+        out.extend_from_slice(&[0; 16]);
+        out.extend_from_slice(&name);
+        let cd_size = out.len() as u32 - cd_offset;
+
+        out.extend_from_slice(&0x0605_4b50_u32.to_le_bytes());
+        out.extend_from_slice(&0_u16.to_le_bytes()); // this disk
+        out.extend_from_slice(&0_u16.to_le_bytes()); // central directory disk
+        out.extend_from_slice(&1_u16.to_le_bytes()); // entries on this disk
+        out.extend_from_slice(&1_u16.to_le_bytes()); // entries in total
+        out.extend_from_slice(&cd_size.to_le_bytes());
+        out.extend_from_slice(&cd_offset.to_le_bytes());
+        out.extend_from_slice(&0_u16.to_le_bytes()); // comment length
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{adler32, crc32, ElfFormat, FormatDescriptor, PngFormat, ZipFormat};
+    use crate::nonzero;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_structured_formats() {
+        let mut rand = StdRand::with_seed(1337);
+
+        let png = PngFormat.generate(&mut rand, nonzero!(512));
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+
+        let elf = ElfFormat.generate(&mut rand, nonzero!(512));
+        assert!(elf.starts_with(b"\x7fELF"));
+        let filesz = u64::from_le_bytes(elf[64 + 32..64 + 40].try_into().unwrap());
+        assert_eq!(filesz, elf.len() as u64);
+
+        let zip = ZipFormat.generate(&mut rand, nonzero!(512));
+        assert!(zip.starts_with(b"PK\x03\x04"));
+        assert_eq!(&zip[zip.len() - 22..zip.len() - 18], b"PK\x05\x06");
+    }
+}
@@ -0,0 +1,460 @@
+//! An input made of a sequence of API calls, for stateful libraries such as allocators or database engines.
+//!
+//! Each [`Call`] names a function of the [`ApiSpec`] by index, and passes it typed arguments: integers, byte
+//! buffers, or handles returned by earlier calls of the sequence. The mutators in [`crate::mutators::calls`]
+//! only build sequences the spec accepts, e.g. they never pass a handle before a call created it. On the
+//! target side, `libafl_targets::api_calls` interprets the sequence.
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::hash::{BuildHasher, Hash, Hasher};
+
+use ahash::RandomState;
+use libafl_bolts::{rands::Rand, HasLen};
+use serde::{Deserialize, Serialize};
+
+use crate::{corpus::CorpusId, inputs::Input, Error};
+
+/// The default maximum length of a byte buffer argument
+pub const DEFAULT_MAX_BYTES_LEN: usize = 256;
+
+/// The type of an argument of a function
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ArgType {
+    /// An integer between `min` and `max`, inclusive
+    Int {
+        /// The smallest value
+        min: u64,
+        /// The largest value
+        max: u64,
+    },
+    /// A byte buffer, at most [`ApiSpec::max_bytes_len`] long
+    Bytes,
+    /// A handle of the given kind, returned by an earlier call
+    Handle(u32),
+}
+
+impl ArgType {
+    /// Any integer
+    #[must_use]
+    pub fn int() -> Self {
+        Self::Int {
+            min: 0,
+            max: u64::MAX,
+        }
+    }
+
+    /// An integer between `min` and `max`, inclusive
+    #[must_use]
+    pub fn int_between(min: u64, max: u64) -> Self {
+        Self::Int { min, max }
+    }
+}
+
+/// The argument of a call
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CallArg {
+    /// An integer
+    Int(u64),
+    /// A byte buffer
+    Bytes(Vec<u8>),
+    /// The handle returned by the call at this index of the sequence
+    Result(usize),
+}
+
+/// The signature of a function of the API
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CallSignature {
+    /// The name of the function, for reports
+    pub name: Cow<'static, str>,
+    /// The types of the arguments
+    pub args: Vec<ArgType>,
+    /// The kind of the handle the function returns, if any
+    pub returns: Option<u32>,
+}
+
+impl CallSignature {
+    /// Creates a new [`CallSignature`] without arguments, returning nothing
+    #[must_use]
+    pub fn new<N>(name: N) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            args: Vec::new(),
+            returns: None,
+        }
+    }
+
+    /// Adds an argument
+    #[must_use]
+    pub fn with_arg(mut self, arg: ArgType) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    /// Sets the kind of the handle the function returns
+    #[must_use]
+    pub fn returning(mut self, kind: u32) -> Self {
+        self.returns = Some(kind);
+        self
+    }
+}
+
+/// A call of a function of the API
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Call {
+    /// The index of the function in the [`ApiSpec`]
+    pub function: usize,
+    /// The arguments
+    pub args: Vec<CallArg>,
+}
+
+impl Call {
+    /// Creates a new [`Call`]
+    #[must_use]
+    pub fn new(function: usize, args: Vec<CallArg>) -> Self {
+        Self { function, args }
+    }
+
+    /// The indices of the earlier calls whose handles this call uses
+    pub fn dependencies(&self) -> impl Iterator<Item = usize> + '_ {
+        self.args.iter().filter_map(|arg| match arg {
+            CallArg::Result(idx) => Some(*idx),
+            _ => None,
+        })
+    }
+}
+
+/// The functions of an API, and the constraints on their arguments
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ApiSpec {
+    functions: Vec<CallSignature>,
+    max_bytes_len: usize,
+}
+
+impl Default for ApiSpec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApiSpec {
+    /// Creates a new [`ApiSpec`], without any function yet
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            functions: Vec::new(),
+            max_bytes_len: DEFAULT_MAX_BYTES_LEN,
+        }
+    }
+
+    /// Adds a function, with the next index
+    #[must_use]
+    pub fn with_function(mut self, signature: CallSignature) -> Self {
+        self.functions.push(signature);
+        self
+    }
+
+    /// Sets the maximum length of a byte buffer argument
+    #[must_use]
+    pub fn with_max_bytes_len(mut self, max_bytes_len: usize) -> Self {
+        self.max_bytes_len = max_bytes_len;
+        self
+    }
+
+    /// The functions, by index
+    #[must_use]
+    pub fn functions(&self) -> &[CallSignature] {
+        &self.functions
+    }
+
+    /// The maximum length of a byte buffer argument
+    #[must_use]
+    pub fn max_bytes_len(&self) -> usize {
+        self.max_bytes_len
+    }
+
+    /// The indices of the calls returning a handle of the given kind
+    pub fn providers<'a>(
+        &'a self,
+        calls: &'a [Call],
+        kind: u32,
+    ) -> impl Iterator<Item = usize> + 'a {
+        calls.iter().enumerate().filter_map(move |(idx, call)| {
+            (self
+                .functions
+                .get(call.function)
+                .and_then(|signature| signature.returns)
+                == Some(kind))
+            .then_some(idx)
+        })
+    }
+
+    /// If the function can be called after the given calls, i.e. they return all the handles it needs
+    #[must_use]
+    pub fn is_callable(&self, function: usize, calls_before: &[Call]) -> bool {
+        self.functions.get(function).is_some_and(|signature| {
+            signature.args.iter().all(|arg| match arg {
+                ArgType::Handle(kind) => self.providers(calls_before, *kind).next().is_some(),
+                _ => true,
+            })
+        })
+    }
+
+    /// A random argument of the given type, after the given calls, `None` if no earlier call returns the handle
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn random_arg<R>(
+        &self,
+        rand: &mut R,
+        ty: &ArgType,
+        calls_before: &[Call],
+    ) -> Option<CallArg>
+    where
+        R: Rand,
+    {
+        Some(match ty {
+            ArgType::Int { min, max } => CallArg::Int(random_int(rand, *min, *max)),
+            ArgType::Bytes => {
+                let len = rand.between(0, self.max_bytes_len);
+                CallArg::Bytes((0..len).map(|_| rand.next() as u8).collect())
+            }
+            ArgType::Handle(kind) => {
+                CallArg::Result(rand.choose(self.providers(calls_before, *kind))?)
+            }
+        })
+    }
+
+    /// A random call with random arguments, after the given calls, `None` if no function is callable
+    pub fn random_call<R>(&self, rand: &mut R, calls_before: &[Call]) -> Option<Call>
+    where
+        R: Rand,
+    {
+        let function = rand.choose(
+            (0..self.functions.len()).filter(|function| self.is_callable(*function, calls_before)),
+        )?;
+        let args = self.functions[function]
+            .args
+            .iter()
+            .map(|ty| self.random_arg(rand, ty, calls_before))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Call::new(function, args))
+    }
+
+    /// Checks that the sequence only calls functions of this spec, with arguments of the right types, and only
+    /// uses the handles earlier calls returned
+    pub fn validate(&self, input: &CallSequenceInput) -> Result<(), Error> {
+        for (idx, call) in input.calls().iter().enumerate() {
+            let signature = self.functions.get(call.function).ok_or_else(|| {
+                Error::illegal_argument(format!(
+                    "Call {idx} calls unknown function {}",
+                    call.function
+                ))
+            })?;
+            if signature.args.len() != call.args.len() {
+                return Err(Error::illegal_argument(format!(
+                    "Call {idx} passes {} arguments to {}, which takes {}",
+                    call.args.len(),
+                    signature.name,
+                    signature.args.len()
+                )));
+            }
+            for (pos, (ty, arg)) in signature.args.iter().zip(&call.args).enumerate() {
+                let valid = match (ty, arg) {
+                    (ArgType::Int { min, max }, CallArg::Int(value)) => {
+                        (*min..=*max).contains(value)
+                    }
+                    (ArgType::Bytes, CallArg::Bytes(bytes)) => bytes.len() <= self.max_bytes_len,
+                    (ArgType::Handle(kind), CallArg::Result(provider)) => {
+                        *provider < idx
+                            && self
+                                .functions
+                                .get(input.calls()[*provider].function)
+                                .is_some_and(|provider| provider.returns == Some(*kind))
+                    }
+                    _ => false,
+                };
+                if !valid {
+                    return Err(Error::illegal_argument(format!(
+                        "Argument {pos} of call {idx} to {} does not match its type {ty:?}",
+                        signature.name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A random integer between `min` and `max`, inclusive
+pub(crate) fn random_int<R>(rand: &mut R, min: u64, max: u64) -> u64
+where
+    R: Rand,
+{
+    match max.checked_sub(min).and_then(|span| span.checked_add(1)) {
+        Some(span) => min + rand.next() % span,
+        None if min <= max => rand.next(),
+        None => min,
+    }
+}
+
+/// An input made of a sequence of API calls
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CallSequenceInput {
+    calls: Vec<Call>,
+}
+
+impl CallSequenceInput {
+    /// Creates a new [`CallSequenceInput`] from the given calls
+    #[must_use]
+    pub fn new(calls: Vec<Call>) -> Self {
+        Self { calls }
+    }
+
+    /// The calls of this input
+    #[must_use]
+    pub fn calls(&self) -> &[Call] {
+        &self.calls
+    }
+
+    /// The calls of this input, mutable. Keep the handle references pointing to earlier calls.
+    #[must_use]
+    pub fn calls_mut(&mut self) -> &mut Vec<Call> {
+        &mut self.calls
+    }
+
+    /// Appends a call to the sequence
+    pub fn push(&mut self, call: Call) {
+        self.calls.push(call);
+    }
+
+    /// Inserts a call at `idx`, updating the handle references of the later calls
+    pub fn insert(&mut self, idx: usize, call: Call) {
+        for later in &mut self.calls[idx..] {
+            for arg in &mut later.args {
+                if let CallArg::Result(provider) = arg {
+                    if *provider >= idx {
+                        *provider += 1;
+                    }
+                }
+            }
+        }
+        self.calls.insert(idx, call);
+    }
+
+    /// Removes the call at `idx`, and the later calls using its handle, directly or not.
+    /// Returns the number of removed calls.
+    pub fn remove_with_dependents(&mut self, idx: usize) -> usize {
+        let mut removed = vec![false; self.calls.len()];
+        removed[idx] = true;
+        for later in idx + 1..self.calls.len() {
+            let dependent = self.calls[later]
+                .dependencies()
+                .any(|provider| removed[provider]);
+            removed[later] = dependent;
+        }
+
+        // The new index of each kept call
+        let mut new_idx = Vec::with_capacity(self.calls.len());
+        let mut kept = 0;
+        for is_removed in &removed {
+            new_idx.push(kept);
+            if !is_removed {
+                kept += 1;
+            }
+        }
+
+        let mut pos = 0;
+        self.calls.retain(|_| {
+            pos += 1;
+            !removed[pos - 1]
+        });
+        for call in &mut self.calls {
+            for arg in &mut call.args {
+                if let CallArg::Result(provider) = arg {
+                    *provider = new_idx[*provider];
+                }
+            }
+        }
+        removed.len() - kept
+    }
+}
+
+impl HasLen for CallSequenceInput {
+    /// The number of calls
+    #[inline]
+    fn len(&self) -> usize {
+        self.calls.len()
+    }
+}
+
+impl Input for CallSequenceInput {
+    fn generate_name(&self, _id: Option<CorpusId>) -> String {
+        let mut hasher = RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+        self.calls.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+impl From<Vec<Call>> for CallSequenceInput {
+    fn from(calls: Vec<Call>) -> Self {
+        Self::new(calls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{ApiSpec, ArgType, Call, CallArg, CallSequenceInput, CallSignature};
+
+    fn allocator_spec() -> ApiSpec {
+        ApiSpec::new()
+            .with_function(
+                CallSignature::new("malloc")
+                    .with_arg(ArgType::int_between(0, 4096))
+                    .returning(0),
+            )
+            .with_function(CallSignature::new("free").with_arg(ArgType::Handle(0)))
+            .with_function(
+                CallSignature::new("write")
+                    .with_arg(ArgType::Handle(0))
+                    .with_arg(ArgType::Bytes),
+            )
+    }
+
+    #[test]
+    fn test_call_sequence() {
+        let spec = allocator_spec();
+        let mut input = CallSequenceInput::new(vec![
+            Call::new(0, vec![CallArg::Int(16)]),
+            Call::new(0, vec![CallArg::Int(32)]),
+            Call::new(2, vec![CallArg::Result(1), CallArg::Bytes(vec![1, 2])]),
+            Call::new(1, vec![CallArg::Result(0)]),
+        ]);
+        spec.validate(&input).unwrap();
+
+        input.insert(1, Call::new(0, vec![CallArg::Int(8)]));
+        spec.validate(&input).unwrap();
+        assert_eq!(input.calls()[3].args[0], CallArg::Result(2));
+        assert_eq!(input.calls()[4].args[0], CallArg::Result(0));
+
+        // Removing the second allocation also removes the write to it
+        assert_eq!(input.remove_with_dependents(2), 2);
+        spec.validate(&input).unwrap();
+        assert_eq!(input.calls().len(), 3);
+        assert_eq!(input.calls()[2], Call::new(1, vec![CallArg::Result(0)]));
+
+        let invalid = CallSequenceInput::new(vec![Call::new(1, vec![CallArg::Result(0)])]);
+        assert!(spec.validate(&invalid).is_err());
+
+        let mut rand = StdRand::with_seed(0);
+        assert!(!spec.is_callable(1, &[]));
+        let mut generated = CallSequenceInput::default();
+        for _ in 0..16 {
+            let call = spec.random_call(&mut rand, generated.calls()).unwrap();
+            generated.push(call);
+        }
+        spec.validate(&generated).unwrap();
+    }
+}
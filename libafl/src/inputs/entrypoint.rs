@@ -0,0 +1,109 @@
+//! An input for one of several entrypoints of a target, to fuzz the API of a library in a single campaign.
+//!
+//! The [`EntrypointInput`] carries the index of the entrypoint it is meant for, next to its payload. The
+//! [`crate::executors::entrypoint::MultiEntrypoint`] runs the matching harness, the
+//! [`crate::feedbacks::entrypoint::EntrypointCoverageFeedback`] keeps a coverage map and stats per entrypoint,
+//! and the [`crate::mutators::entrypoint::EntrypointSwitchMutator`] moves testcases to other entrypoints, for
+//! entrypoints that can share a corpus. Mutate the payload with the mutators of the inner input, mapped with
+//! [`crate::mutators::ToFunctionMappingMutatorMapper`] and [`EntrypointInput::input_mut`].
+
+use alloc::{string::String, vec::Vec};
+
+use libafl_bolts::{ownedref::OwnedSlice, HasLen};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::CorpusId,
+    inputs::{HasTargetBytes, Input},
+};
+
+/// An input meant for one of several entrypoints of the target
+pub trait HasEntrypoint {
+    /// The index of the entrypoint
+    fn entrypoint(&self) -> usize;
+}
+
+/// An input for the entrypoint at a given index
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EntrypointInput<I> {
+    entrypoint: usize,
+    input: I,
+}
+
+impl<I> EntrypointInput<I> {
+    /// Creates a new [`EntrypointInput`] for the entrypoint at index `entrypoint`
+    #[must_use]
+    pub fn new(entrypoint: usize, input: I) -> Self {
+        Self { entrypoint, input }
+    }
+
+    /// Creates an [`EntrypointInput`] for each of the first `entrypoints` entrypoints, e.g. to seed all of them
+    /// with the same initial inputs
+    #[must_use]
+    pub fn for_each_entrypoint(entrypoints: usize, input: &I) -> Vec<Self>
+    where
+        I: Clone,
+    {
+        (0..entrypoints)
+            .map(|entrypoint| Self::new(entrypoint, input.clone()))
+            .collect()
+    }
+
+    /// Sets the index of the entrypoint
+    pub fn set_entrypoint(&mut self, entrypoint: usize) {
+        self.entrypoint = entrypoint;
+    }
+
+    /// The payload for the entrypoint
+    #[must_use]
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    /// The payload for the entrypoint, mutable
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Consumes this input, returning the payload
+    #[must_use]
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+}
+
+impl<I> HasEntrypoint for EntrypointInput<I> {
+    fn entrypoint(&self) -> usize {
+        self.entrypoint
+    }
+}
+
+impl<I> HasLen for EntrypointInput<I>
+where
+    I: HasLen,
+{
+    /// The length of the payload
+    #[inline]
+    fn len(&self) -> usize {
+        self.input.len()
+    }
+}
+
+impl<I> HasTargetBytes for EntrypointInput<I>
+where
+    I: HasTargetBytes,
+{
+    /// The target bytes of the payload, the entrypoint is not part of them
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        self.input.target_bytes()
+    }
+}
+
+impl<I> Input for EntrypointInput<I>
+where
+    I: Input,
+{
+    fn generate_name(&self, id: Option<CorpusId>) -> String {
+        format!("{}-{}", self.entrypoint, self.input.generate_name(id))
+    }
+}
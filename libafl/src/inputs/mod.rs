@@ -3,9 +3,15 @@
 pub mod bytes;
 pub use bytes::BytesInput;
 
+pub mod calls;
+pub use calls::{ApiSpec, ArgType, Call, CallArg, CallSequenceInput, CallSignature};
+
 pub mod encoded;
 pub use encoded::*;
 
+pub mod entrypoint;
+pub use entrypoint::{EntrypointInput, HasEntrypoint};
+
 pub mod gramatron;
 pub use gramatron::*;
 
@@ -15,6 +21,15 @@ pub use generalized::*;
 pub mod bytessub;
 pub use bytessub::BytesSubInput;
 
+pub mod numeric;
+pub use numeric::{HasMutableNumbers, NumberMut};
+
+pub mod sequence;
+pub use sequence::{SequenceInput, SequenceMessage};
+
+pub mod utf8;
+pub use utf8::Utf8Input;
+
 #[cfg(feature = "multipart_inputs")]
 pub mod multi;
 #[cfg(feature = "multipart_inputs")]
@@ -1,6 +1,8 @@
 //! Input for the [`Nautilus`](https://github.com/RUB-SysSec/nautilus) grammar fuzzer methods
 //!
-//!
+//! A [`NautilusInput`] is a derivation tree, made of the rule ids of a grammar. It only makes sense with the same
+//! grammar, so inputs remember the fingerprint of the context they were generated from. Across nodes, add a
+//! [`crate::events::NautilusContextHook`] to the event manager to drop the inputs that are not valid in this grammar.
 use alloc::{rc::Rc, string::String, vec::Vec};
 use core::cell::RefCell;
 use std::hash::{Hash, Hasher};
@@ -25,6 +27,9 @@ use crate::{
 pub struct NautilusInput {
     /// The input representation as Tree
     pub tree: Tree,
+    /// The fingerprint of the context the tree was derived from, if known
+    #[serde(default)]
+    context_hash: Option<u64>,
 }
 
 impl Input for NautilusInput {
@@ -63,7 +68,19 @@ impl NautilusInput {
     /// Creates a new codes input using the given terminals
     #[must_use]
     pub fn new(tree: Tree) -> Self {
-        Self { tree }
+        Self {
+            tree,
+            context_hash: None,
+        }
+    }
+
+    /// Creates a new input from a tree derived from the given context
+    #[must_use]
+    pub fn with_context(tree: Tree, context: &NautilusContext) -> Self {
+        Self {
+            tree,
+            context_hash: Some(context.ctx.fingerprint()),
+        }
     }
 
     /// Create an empty [`Input`]
@@ -75,6 +92,7 @@ impl NautilusInput {
                 sizes: vec![],
                 paren: vec![],
             },
+            context_hash: None,
         }
     }
 
@@ -95,6 +113,31 @@ impl NautilusInput {
     pub fn tree_mut(&mut self) -> &mut Tree {
         &mut self.tree
     }
+
+    /// The fingerprint of the context the tree was derived from, if known
+    #[must_use]
+    pub fn context_hash(&self) -> Option<u64> {
+        self.context_hash
+    }
+
+    /// Set the fingerprint of the context the tree was derived from
+    pub fn set_context_hash(&mut self, context_hash: Option<u64>) {
+        self.context_hash = context_hash;
+    }
+
+    /// If this input can be unparsed and mutated with the given context.
+    ///
+    /// Inputs of an unknown or another context are accepted, as long as the tree is a derivation of this context.
+    #[must_use]
+    pub fn is_valid_for(&self, context: &NautilusContext) -> bool {
+        context.ctx.is_valid_tree(&self.tree)
+    }
+
+    /// If this input was derived from the given context, i.e. its fingerprint is known and matches
+    #[must_use]
+    pub fn is_derived_from(&self, context: &NautilusContext) -> bool {
+        self.context_hash == Some(context.ctx.fingerprint())
+    }
 }
 
 impl Hash for NautilusInput {
@@ -137,3 +180,33 @@ impl InputConverter for NautilusToBytesInputConverter<'_> {
         Ok(BytesInput::new(bytes))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::NautilusInput;
+    use crate::{
+        generators::{Generator, NautilusContext, NautilusGenerator},
+        state::NopState,
+    };
+
+    #[test]
+    fn test_nautilus_input_context_hash() {
+        let context = NautilusContext::with_rules(10, &[("E", b"({E}+{E})"), ("E", b"1")]).unwrap();
+        let other = NautilusContext::with_rules(10, &[("E", b"-{E}"), ("E", b"2")]).unwrap();
+
+        let mut generator = NautilusGenerator::new(&context);
+        let mut state = NopState::<NautilusInput>::new();
+        let input = generator.generate(&mut state).unwrap();
+        assert!(input.is_derived_from(&context));
+        assert!(input.is_valid_for(&context));
+        assert!(!input.is_derived_from(&other));
+
+        // Inputs of older corpora have no fingerprint
+        let mut json = serde_json::to_value(&input).unwrap();
+        json.as_object_mut().unwrap().remove("context_hash");
+        let old: NautilusInput = serde_json::from_value(json).unwrap();
+        assert_eq!(old.context_hash(), None);
+        assert!(!old.is_derived_from(&context));
+        assert!(old.is_valid_for(&context));
+    }
+}
@@ -0,0 +1,189 @@
+//! Numeric fields of structured inputs, for the [`crate::mutators::numeric`] mutators.
+//!
+//! A custom input implements [`HasMutableNumbers`] to expose its integers, e.g. the lengths, counters and flags of
+//! a protocol message, and gets bit flips, interesting values, boundary arithmetic and endianness swaps for free.
+
+use alloc::vec::Vec;
+
+/// A mutable reference to a numeric field of an input
+#[derive(Debug)]
+pub enum NumberMut<'a> {
+    /// An unsigned 8-bit field
+    U8(&'a mut u8),
+    /// A signed 8-bit field
+    I8(&'a mut i8),
+    /// An unsigned 16-bit field
+    U16(&'a mut u16),
+    /// A signed 16-bit field
+    I16(&'a mut i16),
+    /// An unsigned 32-bit field
+    U32(&'a mut u32),
+    /// A signed 32-bit field
+    I32(&'a mut i32),
+    /// An unsigned 64-bit field
+    U64(&'a mut u64),
+    /// A signed 64-bit field
+    I64(&'a mut i64),
+}
+
+impl NumberMut<'_> {
+    /// The width of the field, in bits
+    #[must_use]
+    pub fn bits(&self) -> u32 {
+        match self {
+            NumberMut::U8(_) | NumberMut::I8(_) => 8,
+            NumberMut::U16(_) | NumberMut::I16(_) => 16,
+            NumberMut::U32(_) | NumberMut::I32(_) => 32,
+            NumberMut::U64(_) | NumberMut::I64(_) => 64,
+        }
+    }
+
+    /// If the field is signed
+    #[must_use]
+    pub fn signed(&self) -> bool {
+        matches!(
+            self,
+            NumberMut::I8(_) | NumberMut::I16(_) | NumberMut::I32(_) | NumberMut::I64(_)
+        )
+    }
+
+    /// The bits of the field, zero-extended
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn get(&self) -> u64 {
+        match self {
+            NumberMut::U8(v) => u64::from(**v),
+            NumberMut::I8(v) => u64::from(**v as u8),
+            NumberMut::U16(v) => u64::from(**v),
+            NumberMut::I16(v) => u64::from(**v as u16),
+            NumberMut::U32(v) => u64::from(**v),
+            NumberMut::I32(v) => u64::from(**v as u32),
+            NumberMut::U64(v) => **v,
+            NumberMut::I64(v) => **v as u64,
+        }
+    }
+
+    /// Sets the bits of the field, truncating `bits` to its width
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub fn set(&mut self, bits: u64) {
+        match self {
+            NumberMut::U8(v) => **v = bits as u8,
+            NumberMut::I8(v) => **v = bits as i8,
+            NumberMut::U16(v) => **v = bits as u16,
+            NumberMut::I16(v) => **v = bits as i16,
+            NumberMut::U32(v) => **v = bits as u32,
+            NumberMut::I32(v) => **v = bits as i32,
+            NumberMut::U64(v) => **v = bits,
+            NumberMut::I64(v) => **v = bits as i64,
+        }
+    }
+}
+
+macro_rules! number_mut_from_impl {
+    ($variant: ident, $size: ty) => {
+        impl<'a> From<&'a mut $size> for NumberMut<'a> {
+            fn from(value: &'a mut $size) -> Self {
+                NumberMut::$variant(value)
+            }
+        }
+    };
+}
+
+number_mut_from_impl!(U8, u8);
+number_mut_from_impl!(I8, i8);
+number_mut_from_impl!(U16, u16);
+number_mut_from_impl!(I16, i16);
+number_mut_from_impl!(U32, u32);
+number_mut_from_impl!(I32, i32);
+number_mut_from_impl!(U64, u64);
+number_mut_from_impl!(I64, i64);
+
+/// Exposes the numeric fields of a structured input, to mutate them with the [`crate::mutators::numeric`] mutators
+///
+/// ```rust
+/// use libafl::inputs::{HasMutableNumbers, NumberMut};
+///
+/// struct Header {
+///     version: u8,
+///     length: u32,
+///     offset: i64,
+/// }
+///
+/// impl HasMutableNumbers for Header {
+///     fn numbers_len(&self) -> usize {
+///         3
+///     }
+///
+///     fn number_mut(&mut self, idx: usize) -> Option<NumberMut<'_>> {
+///         match idx {
+///             0 => Some((&mut self.version).into()),
+///             1 => Some((&mut self.length).into()),
+///             2 => Some((&mut self.offset).into()),
+///             _ => None,
+///         }
+///     }
+/// }
+/// ```
+pub trait HasMutableNumbers {
+    /// The amount of numeric fields
+    fn numbers_len(&self) -> usize;
+
+    /// The numeric field at `idx`, or `None` if there are fewer fields
+    fn number_mut(&mut self, idx: usize) -> Option<NumberMut<'_>>;
+}
+
+impl<T> HasMutableNumbers for [T]
+where
+    T: HasMutableNumbers,
+{
+    fn numbers_len(&self) -> usize {
+        self.iter().map(HasMutableNumbers::numbers_len).sum()
+    }
+
+    fn number_mut(&mut self, mut idx: usize) -> Option<NumberMut<'_>> {
+        for item in self {
+            let len = item.numbers_len();
+            if idx < len {
+                return item.number_mut(idx);
+            }
+            idx -= len;
+        }
+        None
+    }
+}
+
+impl<T> HasMutableNumbers for Vec<T>
+where
+    T: HasMutableNumbers,
+{
+    fn numbers_len(&self) -> usize {
+        self.as_slice().numbers_len()
+    }
+
+    fn number_mut(&mut self, idx: usize) -> Option<NumberMut<'_>> {
+        self.as_mut_slice().number_mut(idx)
+    }
+}
+
+macro_rules! has_mutable_numbers_impl {
+    ($size: ty) => {
+        impl HasMutableNumbers for $size {
+            fn numbers_len(&self) -> usize {
+                1
+            }
+
+            fn number_mut(&mut self, idx: usize) -> Option<NumberMut<'_>> {
+                (idx == 0).then(|| self.into())
+            }
+        }
+    };
+}
+
+has_mutable_numbers_impl!(u8);
+has_mutable_numbers_impl!(i8);
+has_mutable_numbers_impl!(u16);
+has_mutable_numbers_impl!(i16);
+has_mutable_numbers_impl!(u32);
+has_mutable_numbers_impl!(i32);
+has_mutable_numbers_impl!(u64);
+has_mutable_numbers_impl!(i64);
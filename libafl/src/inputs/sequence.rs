@@ -0,0 +1,101 @@
+//! An input made of a sequence of typed messages, for stateful protocols.
+//!
+//! Each message is sent on its own, in order, e.g. by the [`crate::executors::network::NetworkExecutor`],
+//! so the target walks through the states of its protocol. The mutators in [`crate::mutators::sequence`]
+//! change the order and number of messages, and mutate their payloads.
+
+use alloc::{string::String, vec::Vec};
+use core::hash::{BuildHasher, Hasher};
+
+use ahash::RandomState;
+use libafl_bolts::HasLen;
+use serde::{Deserialize, Serialize};
+
+use crate::{corpus::CorpusId, inputs::Input};
+
+/// A message of a [`SequenceInput`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SequenceMessage<I> {
+    /// The type of the message, as defined by the protocol, e.g. the command of a text protocol
+    pub kind: u32,
+    /// The content of the message
+    pub payload: I,
+}
+
+impl<I> SequenceMessage<I> {
+    /// Creates a new [`SequenceMessage`]
+    #[must_use]
+    pub fn new(kind: u32, payload: I) -> Self {
+        Self { kind, payload }
+    }
+}
+
+/// An input made of a sequence of typed messages
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SequenceInput<I> {
+    messages: Vec<SequenceMessage<I>>,
+}
+
+impl<I> Default for SequenceInput<I> {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl<I> SequenceInput<I> {
+    /// Creates a new [`SequenceInput`] from the given messages
+    #[must_use]
+    pub fn new(messages: Vec<SequenceMessage<I>>) -> Self {
+        Self { messages }
+    }
+
+    /// The messages of this input
+    #[must_use]
+    pub fn messages(&self) -> &[SequenceMessage<I>] {
+        &self.messages
+    }
+
+    /// The messages of this input, mutable
+    #[must_use]
+    pub fn messages_mut(&mut self) -> &mut Vec<SequenceMessage<I>> {
+        &mut self.messages
+    }
+
+    /// Appends a message to the sequence
+    pub fn push(&mut self, kind: u32, payload: I) {
+        self.messages.push(SequenceMessage::new(kind, payload));
+    }
+}
+
+impl<I> HasLen for SequenceInput<I> {
+    /// The number of messages
+    #[inline]
+    fn len(&self) -> usize {
+        self.messages.len()
+    }
+}
+
+impl<I> Input for SequenceInput<I>
+where
+    I: Input,
+{
+    fn generate_name(&self, id: Option<CorpusId>) -> String {
+        let mut hasher = RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+        for message in &self.messages {
+            hasher.write_u32(message.kind);
+            hasher.write(message.payload.generate_name(id).as_bytes());
+        }
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+impl<I> From<Vec<(u32, I)>> for SequenceInput<I> {
+    fn from(messages: Vec<(u32, I)>) -> Self {
+        Self::new(
+            messages
+                .into_iter()
+                .map(|(kind, payload)| SequenceMessage::new(kind, payload))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,134 @@
+//! The [`Utf8Input`] is a string input, which is valid UTF-8 before and after every mutation.
+//!
+//! Text processors often reject invalid UTF-8 at their first check, so byte-level mutations are mostly wasted on
+//! them. With the `unicode` feature, the `Utf8*Mutator`s in [`crate::mutators`] mutate chars and grapheme clusters.
+
+use alloc::{
+    borrow::ToOwned,
+    rc::Rc,
+    string::{String, ToString},
+};
+use core::{
+    cell::RefCell,
+    hash::{BuildHasher, Hasher},
+};
+#[cfg(feature = "std")]
+use std::{fs::read, path::Path};
+
+use ahash::RandomState;
+#[cfg(feature = "std")]
+use libafl_bolts::fs::write_file_atomic;
+use libafl_bolts::{ownedref::OwnedSlice, Error, HasLen};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::CorpusId,
+    inputs::{HasTargetBytes, Input},
+};
+
+/// A string input, always valid UTF-8
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Utf8Input {
+    string: String,
+}
+
+impl Input for Utf8Input {
+    /// Write this input to the file
+    #[cfg(feature = "std")]
+    fn to_file<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        write_file_atomic(path, self.string.as_bytes())
+    }
+
+    /// Load the content of this input from a file, which has to be valid UTF-8
+    #[cfg(feature = "std")]
+    fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let string = String::from_utf8(read(path)?).map_err(|err| {
+            Error::illegal_argument(format!("{} is not valid UTF-8: {err}", path.display()))
+        })?;
+        Ok(Self::new(string))
+    }
+
+    /// Generate a name for this input
+    fn generate_name(&self, _id: Option<CorpusId>) -> String {
+        let mut hasher = RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+        hasher.write(self.string.as_bytes());
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Rc Ref-cell from Input
+impl From<Utf8Input> for Rc<RefCell<Utf8Input>> {
+    fn from(input: Utf8Input) -> Self {
+        Rc::new(RefCell::new(input))
+    }
+}
+
+impl HasTargetBytes for Utf8Input {
+    #[inline]
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        OwnedSlice::from(self.string.as_bytes())
+    }
+}
+
+impl HasLen for Utf8Input {
+    /// The length of the string, in bytes
+    #[inline]
+    fn len(&self) -> usize {
+        self.string.len()
+    }
+}
+
+impl From<String> for Utf8Input {
+    fn from(string: String) -> Self {
+        Self::new(string)
+    }
+}
+
+impl From<&str> for Utf8Input {
+    fn from(string: &str) -> Self {
+        Self::new(string.to_owned())
+    }
+}
+
+impl From<Utf8Input> for String {
+    fn from(value: Utf8Input) -> String {
+        value.string
+    }
+}
+
+impl TryFrom<&[u8]> for Utf8Input {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        core::str::from_utf8(bytes)
+            .map(Self::from)
+            .map_err(|err| Error::illegal_argument(err.to_string()))
+    }
+}
+
+impl Utf8Input {
+    /// Creates a new string input
+    #[must_use]
+    pub const fn new(string: String) -> Self {
+        Self { string }
+    }
+
+    /// The string
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+
+    /// The string to mutate. Being a [`String`], it stays valid UTF-8.
+    #[must_use]
+    pub fn string_mut(&mut self) -> &mut String {
+        &mut self.string
+    }
+}
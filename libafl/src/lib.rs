@@ -66,6 +66,8 @@ extern crate libafl_derive;
 #[doc(hidden)]
 pub use libafl_derive::*;
 
+#[cfg(feature = "builder")]
+pub mod builder;
 pub mod common;
 pub use common::*;
 pub mod corpus;
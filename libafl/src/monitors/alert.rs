@@ -0,0 +1,459 @@
+//! The [`AlertMonitor`] wraps a base monitor and fires alerts on conditions of the campaign, so long
+//! campaigns can notify their operators, e.g. when the first objective is found, a client stopped
+//! executing, or the executions per second dropped.
+//!
+//! Alerts go to [`AlertHandler`]`s`: any `FnMut(&Alert)` closure, or, with the `alert_webhook` feature,
+//! a [`WebhookAlertHandler`] posting them to an HTTP endpoint.
+
+use alloc::{boxed::Box, collections::VecDeque, string::String, vec::Vec};
+use core::{fmt, time::Duration};
+
+use hashbrown::HashMap;
+use libafl_bolts::{current_time, format_duration_hms, ClientId};
+use serde::{Deserialize, Serialize};
+
+use crate::monitors::{ClientStats, Monitor};
+
+/// The default minimum time between two checks of the alert rules
+pub const DEFAULT_ALERT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A condition the [`AlertMonitor`] fires an alert on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum AlertRule {
+    /// The first objective of the campaign was found
+    FirstObjective,
+    /// A new objective was found
+    NewObjective,
+    /// A client did not report new executions for the given time.
+    ///
+    /// Fires once per stall, and again only after the client reported executions in between.
+    ClientStalled(Duration),
+    /// The executions per second of the campaign dropped below `1 - ratio` of the highest rate in the last `window`.
+    ///
+    /// Fires once per drop, and again only after the rate recovered in between.
+    ExecsDropped {
+        /// The relative drop, between `0.0` and `1.0`, e.g. `0.8` to alert on a drop of 80%
+        ratio: f64,
+        /// The time window of the reference rate
+        window: Duration,
+    },
+}
+
+/// An alert fired by the [`AlertMonitor`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// The rule that fired
+    pub rule: AlertRule,
+    /// The client the alert is about, if any
+    pub client: Option<ClientId>,
+    /// The run time of the campaign when the alert fired
+    pub run_time: Duration,
+    /// A human-readable description of the alert
+    pub message: String,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[LibAFL alert, run time {}] {}",
+            format_duration_hms(&self.run_time),
+            self.message
+        )
+    }
+}
+
+/// Receives the alerts of an [`AlertMonitor`]
+pub trait AlertHandler {
+    /// Handle an alert
+    fn on_alert(&mut self, alert: &Alert);
+}
+
+impl<F> AlertHandler for F
+where
+    F: FnMut(&Alert),
+{
+    fn on_alert(&mut self, alert: &Alert) {
+        self(alert);
+    }
+}
+
+/// Posts the alerts as Json to an HTTP endpoint, e.g. a chat or paging webhook.
+///
+/// The body has a `text` field with the formatted alert, which most chat webhooks display as is, and an `alert`
+/// field with the [`Alert`]. Each request is sent from its own thread, so a slow endpoint does not stall the
+/// broker; failures are logged.
+#[cfg(all(feature = "alert_webhook", feature = "std"))]
+#[derive(Debug, Clone)]
+pub struct WebhookAlertHandler {
+    url: String,
+}
+
+#[cfg(all(feature = "alert_webhook", feature = "std"))]
+impl WebhookAlertHandler {
+    /// Create a new [`WebhookAlertHandler`] posting to `url`
+    #[must_use]
+    pub fn new<U>(url: U) -> Self
+    where
+        U: Into<String>,
+    {
+        Self { url: url.into() }
+    }
+}
+
+#[cfg(all(feature = "alert_webhook", feature = "std"))]
+impl AlertHandler for WebhookAlertHandler {
+    fn on_alert(&mut self, alert: &Alert) {
+        use alloc::string::ToString;
+
+        let url = self.url.clone();
+        let body = serde_json::json!({
+            "text": alert.to_string(),
+            "alert": alert,
+        });
+        std::thread::spawn(move || {
+            if let Err(err) = ureq::post(&url).send_json(body) {
+                log::error!("Failed to post alert to {url}: {err}");
+            }
+        });
+    }
+}
+
+/// The execution activity of a client, to detect stalls
+#[derive(Debug, Clone, Copy)]
+struct ClientActivity {
+    executions: u64,
+    since: Duration,
+    stalled: bool,
+}
+
+/// Wraps a base monitor and fires [`Alert`]`s` on the given [`AlertRule`]`s`.
+///
+/// The rules are checked on each display and [`Monitor::tick`] of the monitor, at most every
+/// [`DEFAULT_ALERT_CHECK_INTERVAL`]. The ticks of the broker check them while no events arrive, e.g. when all
+/// clients stalled.
+pub struct AlertMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    rules: Vec<AlertRule>,
+    handlers: Vec<Box<dyn AlertHandler>>,
+    check_interval: Duration,
+    last_check: Option<(Duration, u64)>,
+    objectives: u64,
+    clients: HashMap<ClientId, ClientActivity>,
+    rates: VecDeque<(Duration, f64)>,
+    rate_dropped: bool,
+}
+
+impl<M> fmt::Debug for AlertMonitor<M>
+where
+    M: Monitor + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlertMonitor")
+            .field("base", &self.base)
+            .field("rules", &self.rules)
+            .field("handlers", &self.handlers.len())
+            .field("check_interval", &self.check_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M> AlertMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create a new [`AlertMonitor`], without rules or handlers
+    #[must_use]
+    pub fn new(base: M) -> Self {
+        Self {
+            base,
+            rules: Vec::new(),
+            handlers: Vec::new(),
+            check_interval: DEFAULT_ALERT_CHECK_INTERVAL,
+            last_check: None,
+            objectives: 0,
+            clients: HashMap::new(),
+            rates: VecDeque::new(),
+            rate_dropped: false,
+        }
+    }
+
+    /// Add a rule to alert on
+    #[must_use]
+    pub fn with_rule(mut self, rule: AlertRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Add a handler, receiving all alerts
+    #[must_use]
+    pub fn with_handler<H>(mut self, handler: H) -> Self
+    where
+        H: AlertHandler + 'static,
+    {
+        self.handlers.push(Box::new(handler));
+        self
+    }
+
+    /// Set the minimum time between two checks of the rules
+    #[must_use]
+    pub fn with_check_interval(mut self, check_interval: Duration) -> Self {
+        self.check_interval = check_interval;
+        self
+    }
+
+    /// The rules of this monitor
+    #[must_use]
+    pub fn rules(&self) -> &[AlertRule] {
+        &self.rules
+    }
+
+    fn fire(
+        &mut self,
+        rule: AlertRule,
+        client: Option<ClientId>,
+        run_time: Duration,
+        message: String,
+    ) {
+        let alert = Alert {
+            rule,
+            client,
+            run_time,
+            message,
+        };
+        log::warn!("{alert}");
+        for handler in &mut self.handlers {
+            handler.on_alert(&alert);
+        }
+    }
+
+    /// Check the rules at the time `now`, if the check interval passed since the last check
+    fn maybe_check(&mut self, now: Duration) {
+        if self.last_check.map_or(true, |(time, _)| {
+            now.saturating_sub(time) >= self.check_interval
+        }) {
+            self.check(now);
+        }
+    }
+
+    /// Check the rules at the time `now`
+    #[allow(clippy::cast_precision_loss)]
+    fn check(&mut self, now: Duration) {
+        let run_time = now.saturating_sub(self.base.start_time());
+        let objectives = self.base.objective_size();
+        let total_execs = self.base.total_execs();
+        let rate = self.last_check.and_then(|(time, execs)| {
+            let elapsed = now.checked_sub(time)?.as_secs_f64();
+            (elapsed > 0.0).then(|| total_execs.saturating_sub(execs) as f64 / elapsed)
+        });
+        self.last_check = Some((now, total_execs));
+
+        // Collect the alerts first, the rules borrow `self`
+        let mut alerts: Vec<(AlertRule, Option<ClientId>, String)> = Vec::new();
+        for rule in self.rules.clone() {
+            match rule {
+                AlertRule::FirstObjective => {
+                    if self.objectives == 0 && objectives > 0 {
+                        alerts.push((rule, None, "Found the first objective".into()));
+                    }
+                }
+                AlertRule::NewObjective => {
+                    if objectives > self.objectives {
+                        alerts.push((
+                            rule,
+                            None,
+                            format!(
+                                "Found {} new objective(s), {objectives} in total",
+                                objectives - self.objectives
+                            ),
+                        ));
+                    }
+                }
+                AlertRule::ClientStalled(timeout) => {
+                    for (id, stats) in active_clients(self.base.client_stats()) {
+                        let activity = self.clients.entry(id).or_insert(ClientActivity {
+                            executions: stats.executions,
+                            since: now,
+                            stalled: false,
+                        });
+                        if activity.executions != stats.executions {
+                            *activity = ClientActivity {
+                                executions: stats.executions,
+                                since: now,
+                                stalled: false,
+                            };
+                        } else if !activity.stalled && now.saturating_sub(activity.since) >= timeout
+                        {
+                            activity.stalled = true;
+                            alerts.push((
+                                rule,
+                                Some(id),
+                                format!(
+                                    "Client {} reported no executions for {}",
+                                    id.0,
+                                    format_duration_hms(&now.saturating_sub(activity.since))
+                                ),
+                            ));
+                        }
+                    }
+                }
+                AlertRule::ExecsDropped { ratio, window } => {
+                    let Some(rate) = rate else {
+                        continue;
+                    };
+                    while self
+                        .rates
+                        .front()
+                        .is_some_and(|(time, _)| now.saturating_sub(*time) > window)
+                    {
+                        self.rates.pop_front();
+                    }
+                    let peak = self.rates.iter().map(|(_, rate)| *rate).fold(0.0, f64::max);
+                    let dropped = peak > 0.0 && rate < peak * (1.0 - ratio);
+                    if dropped && !self.rate_dropped {
+                        alerts.push((
+                            rule,
+                            None,
+                            format!(
+                                "Executions per second dropped from {peak:.1} to {rate:.1} in the last {}",
+                                format_duration_hms(&window)
+                            ),
+                        ));
+                    }
+                    self.rate_dropped = dropped;
+                    self.rates.push_back((now, rate));
+                }
+            }
+        }
+        self.objectives = objectives;
+
+        for (rule, client, message) in alerts {
+            self.fire(rule, client, run_time, message);
+        }
+    }
+}
+
+/// The clients that executed anything, with their id
+#[allow(clippy::cast_possible_truncation)]
+fn active_clients(client_stats: &[ClientStats]) -> impl Iterator<Item = (ClientId, &ClientStats)> {
+    client_stats
+        .iter()
+        .enumerate()
+        .filter(|(_, stats)| stats.enabled && stats.executions > 0)
+        .map(|(idx, stats)| (ClientId(idx as u32), stats))
+}
+
+impl<M> Monitor for AlertMonitor<M>
+where
+    M: Monitor,
+{
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    fn start_time(&self) -> Duration {
+        self.base.start_time()
+    }
+
+    fn set_start_time(&mut self, time: Duration) {
+        self.base.set_start_time(time);
+    }
+
+    fn aggregate(&mut self, name: &str) {
+        self.base.aggregate(name);
+    }
+
+    fn display(&mut self, event_msg: &str, sender_id: ClientId) {
+        self.maybe_check(current_time());
+        self.base.display(event_msg, sender_id);
+    }
+
+    fn tick(&mut self) {
+        self.maybe_check(current_time());
+        self.base.tick();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{rc::Rc, vec::Vec};
+    use core::{cell::RefCell, time::Duration};
+
+    use libafl_bolts::ClientId;
+
+    use super::{Alert, AlertMonitor, AlertRule};
+    use crate::monitors::{Monitor, NopMonitor};
+
+    #[test]
+    fn test_alert_rules() {
+        let alerts: Rc<RefCell<Vec<Alert>>> = Rc::default();
+        let sink = alerts.clone();
+        let mut monitor = AlertMonitor::new(NopMonitor::new())
+            .with_rule(AlertRule::FirstObjective)
+            .with_rule(AlertRule::ClientStalled(Duration::from_secs(60)))
+            .with_rule(AlertRule::ExecsDropped {
+                ratio: 0.8,
+                window: Duration::from_secs(300),
+            })
+            .with_handler(move |alert: &Alert| sink.borrow_mut().push(alert.clone()));
+        let start = monitor.start_time();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // 1000 execs/sec for a minute
+        monitor.client_stats_insert(ClientId(1));
+        for sec in 0..=60 {
+            monitor
+                .client_stats_mut_for(ClientId(1))
+                .update_executions(sec * 1000, at(sec));
+            monitor.check(at(sec));
+        }
+        assert!(alerts.borrow().is_empty());
+
+        // Down to 100 execs/sec, and the first objective
+        monitor
+            .client_stats_mut_for(ClientId(1))
+            .update_executions(60_100, at(61));
+        monitor.client_stats_mut_for(ClientId(1)).objective_size = 1;
+        monitor.check(at(61));
+        assert_eq!(alerts.borrow().len(), 2);
+        assert!(alerts
+            .borrow()
+            .iter()
+            .any(|alert| alert.rule == AlertRule::FirstObjective));
+
+        // The client stalls, alert once
+        monitor.maybe_check(at(121));
+        monitor.maybe_check(at(200));
+        let alerts = alerts.borrow();
+        assert_eq!(alerts.len(), 3);
+        assert_eq!(alerts[2].client, Some(ClientId(1)));
+        assert_eq!(alerts[2].run_time, Duration::from_secs(121));
+    }
+
+    #[test]
+    fn test_alert_tick() {
+        let alerts: Rc<RefCell<Vec<Alert>>> = Rc::default();
+        let sink = alerts.clone();
+        let mut monitor = AlertMonitor::new(NopMonitor::new())
+            .with_rule(AlertRule::ClientStalled(Duration::ZERO))
+            .with_check_interval(Duration::ZERO)
+            .with_handler(move |alert: &Alert| sink.borrow_mut().push(alert.clone()));
+        monitor.client_stats_insert(ClientId(1));
+        let now = monitor.start_time();
+        monitor
+            .client_stats_mut_for(ClientId(1))
+            .update_executions(10, now);
+
+        // No events arrive, the tick of the broker checks the rules
+        monitor.tick();
+        assert_eq!(alerts.borrow().len(), 1);
+        assert_eq!(alerts.borrow()[0].client, Some(ClientId(1)));
+    }
+}
@@ -3,15 +3,19 @@
 use alloc::{string::String, vec::Vec};
 use core::time::Duration;
 use std::{
-    fs::{File, OpenOptions},
+    fs::{self, File, OpenOptions},
     io::Write,
     path::PathBuf,
 };
 
 use libafl_bolts::{current_time, format_duration_hms, ClientId};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::monitors::{ClientStats, Monitor, NopMonitor};
+use crate::{
+    monitors::{ClientStats, Monitor, NopMonitor},
+    Error,
+};
 
 /// Wrap a monitor and log the current state of the monitor into a Toml file.
 #[derive(Debug, Clone)]
@@ -49,6 +53,10 @@ where
         self.base.set_start_time(time);
     }
 
+    fn tick(&mut self) {
+        self.base.tick();
+    }
+
     fn aggregate(&mut self, name: &str) {
         self.base.aggregate(name);
     }
@@ -106,6 +114,10 @@ exec_sec = {}
                     writeln!(&mut file, "{k} = \"{val}\"")
                         .expect("Failed to write to the Toml file");
                 }
+                if let Some(host_metrics) = client.host_metrics_pretty() {
+                    writeln!(&mut file, "host_metrics = \"{host_metrics}\"")
+                        .expect("Failed to write to the Toml file");
+                }
             }
 
             drop(file);
@@ -208,6 +220,10 @@ where
         self.base.set_start_time(time);
     }
 
+    fn tick(&mut self) {
+        self.base.tick();
+    }
+
     fn display(&mut self, event_msg: &str, sender_id: ClientId) {
         if (self.log_record)(&mut self.base) {
             let file = OpenOptions::new()
@@ -230,3 +246,315 @@ where
         self.base.display(event_msg, sender_id);
     }
 }
+
+/// The cumulative statistics of a single client, as persisted by the [`PersistentMonitor`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClientCampaignStats {
+    /// The executions of the client, over all runs
+    pub executions: u64,
+    /// The objectives found by the client, over all runs
+    pub objective_size: u64,
+}
+
+/// The cumulative statistics of a campaign, as persisted by the [`PersistentMonitor`]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CampaignStats {
+    /// When the campaign was started first
+    pub start_time: Duration,
+    /// The executions of all clients, over all runs
+    pub executions: u64,
+    /// The largest corpus size seen
+    pub corpus_size: u64,
+    /// The objectives found, over all runs
+    pub objective_size: u64,
+    /// How often the campaign was resumed
+    pub resumes: u64,
+    /// The statistics of each client, indexed by the client id
+    #[serde(default)]
+    pub clients: Vec<ClientCampaignStats>,
+}
+
+/// Wraps a base monitor, periodically persisting the cumulative [`CampaignStats`] of the broker to a Json file.
+///
+/// If the file exists when the monitor is created, the campaign is resumed: the start time is restored, and the
+/// executions and objectives of each client in earlier runs become its baseline, so long campaigns keep accurate
+/// numbers across broker crashes and redeploys.
+/// Clients often survive a restart of the broker, and keep counting from where they were. So the baseline of a
+/// client only counts if its first report after the resume is below it, i.e. if the client was restarted as well.
+/// The corpus size is the largest one seen, as clients usually reload their corpus from disk.
+///
+/// The totals of [`Monitor::total_execs`], [`Monitor::objective_size`] and [`Monitor::corpus_size`] of this
+/// monitor are cumulative, while the base monitor displays the numbers of the current run.
+#[derive(Debug, Clone)]
+pub struct PersistentMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    path: PathBuf,
+    previous: CampaignStats,
+    /// The baseline added to the numbers of each client, once it reported after the resume
+    offsets: Vec<Option<ClientCampaignStats>>,
+    last_update: Duration,
+    update_interval: Duration,
+}
+
+impl<M> PersistentMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create a new [`PersistentMonitor`], resuming the campaign persisted at `filename`, if any
+    pub fn new<P>(filename: P, base: M) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        Self::with_update_interval(filename, base, Duration::from_secs(60))
+    }
+
+    /// Create a new [`PersistentMonitor`] with a custom update interval
+    pub fn with_update_interval<P>(
+        filename: P,
+        mut base: M,
+        update_interval: Duration,
+    ) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = filename.into();
+        let previous = if path.exists() {
+            let mut previous: CampaignStats = serde_json::from_slice(&fs::read(&path)?)?;
+            previous.resumes += 1;
+            base.set_start_time(previous.start_time);
+            log::info!(
+                "Resuming campaign started {} ago (resume #{}): {} executions, {} objectives, corpus of {}",
+                format_duration_hms(&current_time().saturating_sub(previous.start_time)),
+                previous.resumes,
+                previous.executions,
+                previous.objective_size,
+                previous.corpus_size
+            );
+            previous
+        } else {
+            CampaignStats {
+                start_time: base.start_time(),
+                ..CampaignStats::default()
+            }
+        };
+
+        Ok(Self {
+            base,
+            path,
+            previous,
+            offsets: Vec::new(),
+            last_update: current_time(),
+            update_interval,
+        })
+    }
+
+    /// The statistics of the campaign before it was resumed
+    #[must_use]
+    pub fn previous_stats(&self) -> &CampaignStats {
+        &self.previous
+    }
+
+    /// The cumulative statistics of the campaign
+    #[must_use]
+    pub fn campaign_stats(&self) -> CampaignStats {
+        CampaignStats {
+            start_time: self.base.start_time(),
+            executions: self.total_execs(),
+            corpus_size: self.corpus_size(),
+            objective_size: self.objective_size(),
+            resumes: self.previous.resumes,
+            clients: self.client_campaign_stats(),
+        }
+    }
+
+    /// The cumulative statistics of each client
+    fn client_campaign_stats(&self) -> Vec<ClientCampaignStats> {
+        let clients = self
+            .base
+            .client_stats()
+            .len()
+            .max(self.previous.clients.len());
+        (0..clients)
+            .map(|id| {
+                let current = self.base.client_stats().get(id);
+                match self.offsets.get(id).copied().flatten() {
+                    Some(offset) => ClientCampaignStats {
+                        executions: offset.executions
+                            + current.map_or(0, |client| client.executions),
+                        objective_size: offset.objective_size
+                            + current.map_or(0, |client| client.objective_size),
+                    },
+                    // Not reported since the resume, keep the numbers of the earlier runs
+                    None => self.previous.clients.get(id).copied().unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+
+    /// The numbers of earlier runs which are not attributed to a client, e.g. from files without clients
+    fn unattributed(&self) -> ClientCampaignStats {
+        let clients = &self.previous.clients;
+        ClientCampaignStats {
+            executions: self
+                .previous
+                .executions
+                .saturating_sub(clients.iter().map(|client| client.executions).sum()),
+            objective_size: self
+                .previous
+                .objective_size
+                .saturating_sub(clients.iter().map(|client| client.objective_size).sum()),
+        }
+    }
+
+    /// Decide on the baseline of a client on its first report after the resume
+    fn update_offset(&mut self, sender_id: ClientId) {
+        let id = sender_id.0 as usize;
+        if self.offsets.get(id).copied().flatten().is_some() {
+            return;
+        }
+        let Some(current) = self.base.client_stats().get(id) else {
+            return;
+        };
+        if current.executions == 0 {
+            return;
+        }
+        let baseline = self.previous.clients.get(id).copied().unwrap_or_default();
+        let offset = if current.executions >= baseline.executions {
+            // The client survived the restart of the broker, and keeps counting
+            ClientCampaignStats::default()
+        } else {
+            baseline
+        };
+        if self.offsets.len() <= id {
+            self.offsets.resize(id + 1, None);
+        }
+        self.offsets[id] = Some(offset);
+    }
+
+    /// Write the cumulative statistics to disk, replacing the file atomically
+    pub fn persist(&self) -> Result<(), Error> {
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(
+            &tmp_path,
+            serde_json::to_vec_pretty(&self.campaign_stats())?,
+        )?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+impl<M> Monitor for PersistentMonitor<M>
+where
+    M: Monitor,
+{
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    fn start_time(&self) -> Duration {
+        self.base.start_time()
+    }
+
+    fn set_start_time(&mut self, time: Duration) {
+        self.base.set_start_time(time);
+    }
+
+    fn tick(&mut self) {
+        self.base.tick();
+    }
+
+    fn corpus_size(&self) -> u64 {
+        self.base.corpus_size().max(self.previous.corpus_size)
+    }
+
+    fn objective_size(&self) -> u64 {
+        self.unattributed().objective_size
+            + self
+                .client_campaign_stats()
+                .iter()
+                .map(|client| client.objective_size)
+                .sum::<u64>()
+    }
+
+    fn total_execs(&self) -> u64 {
+        self.unattributed().executions
+            + self
+                .client_campaign_stats()
+                .iter()
+                .map(|client| client.executions)
+                .sum::<u64>()
+    }
+
+    fn aggregate(&mut self, name: &str) {
+        self.base.aggregate(name);
+    }
+
+    fn display(&mut self, event_msg: &str, sender_id: ClientId) {
+        self.update_offset(sender_id);
+        let cur_time = current_time();
+        if cur_time - self.last_update >= self.update_interval {
+            self.last_update = cur_time;
+            if let Err(err) = self.persist() {
+                log::error!(
+                    "Failed to persist the campaign stats to {:?}: {err}",
+                    self.path
+                );
+            }
+        }
+        self.base.display(event_msg, sender_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use libafl_bolts::ClientId;
+
+    use crate::monitors::{disk::PersistentMonitor, Monitor, NopMonitor};
+
+    #[test]
+    fn test_persistent_monitor_resume() {
+        let path = env::temp_dir().join(format!("libafl_campaign_{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut monitor = PersistentMonitor::new(&path, NopMonitor::new()).unwrap();
+        let start_time = monitor.start_time();
+        for (id, executions, objective_size) in [(1, 100, 2), (2, 300, 1)] {
+            monitor.client_stats_insert(ClientId(id));
+            monitor.client_stats_mut_for(ClientId(id)).executions = executions;
+            monitor.client_stats_mut_for(ClientId(id)).objective_size = objective_size;
+            monitor.display("Client Heartbeat", ClientId(id));
+        }
+        monitor.persist().unwrap();
+
+        let mut monitor = PersistentMonitor::new(&path, NopMonitor::new()).unwrap();
+        assert_eq!(monitor.start_time(), start_time);
+        assert_eq!(monitor.previous_stats().resumes, 1);
+        // Before the clients report, the numbers of the earlier run count
+        assert_eq!(monitor.total_execs(), 400);
+
+        // Client 1 was restarted and counts from zero, client 2 survived the restart of the broker
+        for (id, executions) in [(1, 50), (2, 350)] {
+            monitor.client_stats_insert(ClientId(id));
+            monitor.client_stats_mut_for(ClientId(id)).executions = executions;
+            monitor.client_stats_mut_for(ClientId(id)).objective_size = 1;
+            monitor.display("Client Heartbeat", ClientId(id));
+        }
+        assert_eq!(monitor.total_execs(), 150 + 350);
+        assert_eq!(monitor.objective_size(), 3 + 1);
+
+        // The baseline stays once decided
+        monitor.client_stats_mut_for(ClientId(1)).executions = 200;
+        assert_eq!(monitor.total_execs(), 300 + 350);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
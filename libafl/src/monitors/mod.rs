@@ -3,6 +3,11 @@
 pub mod multi;
 pub use multi::MultiMonitor;
 
+pub mod alert;
+#[cfg(all(feature = "alert_webhook", feature = "std"))]
+pub use alert::WebhookAlertHandler;
+pub use alert::{Alert, AlertHandler, AlertMonitor, AlertRule};
+
 #[cfg(all(feature = "tui_monitor", feature = "std"))]
 pub mod tui;
 
@@ -14,18 +19,55 @@ use alloc::string::ToString;
 pub use prometheus::PrometheusMonitor;
 #[cfg(feature = "std")]
 pub mod disk;
-use alloc::{borrow::Cow, fmt::Debug, string::String, vec::Vec};
+use alloc::{borrow::Cow, collections::VecDeque, fmt::Debug, string::String, vec::Vec};
 use core::{fmt, fmt::Write, time::Duration};
 
 #[cfg(feature = "std")]
-pub use disk::{OnDiskJsonMonitor, OnDiskTomlMonitor};
+pub use disk::{
+    CampaignStats, ClientCampaignStats, OnDiskJsonMonitor, OnDiskTomlMonitor, PersistentMonitor,
+};
 use hashbrown::HashMap;
-use libafl_bolts::{current_time, format_duration_hms, ClientId};
+use libafl_bolts::{current_time, format_duration_hms, os::ProcessMetrics, ClientId};
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "afl_exec_sec")]
 const CLIENT_STATS_TIME_WINDOW_SECS: u64 = 5; // 5 seconds
 
+/// The maximum number of execution samples kept per client, see [`ClientStats::exec_samples`]
+const CLIENT_STATS_MAX_SAMPLES: usize = 512;
+
+/// The minimum time between two execution samples of a client
+const CLIENT_STATS_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How the executions per second of the clients are aggregated, see [`Monitor::execs_per_sec_aggregated`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ExecsAggregation {
+    /// The sum of the clients' executions per second, as reported by [`ClientStats::execs_per_sec`]
+    Cumulative,
+    /// The sum of the clients' executions per second over the given, most recent, time window
+    SlidingWindow(Duration),
+    /// The sum of the clients' exponentially weighted moving averages with the given half-life
+    Ewma {
+        /// The time after which a sample only counts half as much
+        half_life: Duration,
+    },
+    /// The given percentile (between `0.0` and `1.0`) of the per-client executions per second.
+    ///
+    /// Unlike the other modes, this is the rate of a single (typical) client, not of the whole campaign.
+    Percentile(f64),
+}
+
+impl fmt::Display for ExecsAggregation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cumulative => write!(f, "total"),
+            Self::SlidingWindow(window) => write!(f, "last {}s", window.as_secs()),
+            Self::Ewma { half_life } => write!(f, "ewma {}s", half_life.as_secs()),
+            Self::Percentile(percentile) => write!(f, "p{:.0}", percentile * 100.0),
+        }
+    }
+}
+
 /// Definition of how we aggreate this across multiple clients
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum AggregatorOps {
@@ -364,14 +406,54 @@ pub struct ClientStats {
     pub last_window_time: Duration,
     /// the start time of the client
     pub start_time: Duration,
+    /// Recent `(time, executions)` samples, used for the [`ExecsAggregation`] modes
+    pub exec_samples: VecDeque<(Duration, u64)>,
     /// User-defined monitor
     pub user_monitor: HashMap<Cow<'static, str>, UserStats>,
+    /// The last process and host metrics reported by this client, see the `host_metrics` feature
+    pub host_metrics: Option<ProcessMetrics>,
+    /// The time of the last host metrics update
+    pub host_metrics_time: Duration,
+    /// The CPU usage of this client, in percent of one core, between the last two host metrics updates
+    pub cpu_usage: Option<f64>,
     /// Client performance statistics
     #[cfg(feature = "introspection")]
     pub introspection_monitor: ClientPerfMonitor,
 }
 
 impl ClientStats {
+    /// Update the process and host metrics of this client, and its CPU usage since the last update
+    #[allow(clippy::cast_precision_loss)]
+    pub fn update_host_metrics(&mut self, metrics: ProcessMetrics, cur_time: Duration) {
+        if let Some(prev) = self.host_metrics {
+            let wall = cur_time.saturating_sub(self.host_metrics_time);
+            if !wall.is_zero() && metrics.cpu_time >= prev.cpu_time {
+                let cpu = metrics.cpu_time - prev.cpu_time;
+                self.cpu_usage = Some(cpu.as_secs_f64() * 100.0 / wall.as_secs_f64());
+            }
+        }
+        self.host_metrics = Some(metrics);
+        self.host_metrics_time = cur_time;
+    }
+
+    /// The host metrics of this client, formatted for a monitor line
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn host_metrics_pretty(&self) -> Option<String> {
+        let metrics = self.host_metrics?;
+        let mut out = format!("rss: {:.1} MiB", metrics.rss_bytes as f64 / 1_048_576.0);
+        if let Some(cpu) = self.cpu_usage {
+            write!(out, ", cpu: {cpu:.1}%").unwrap();
+        }
+        if let Some(fds) = metrics.open_fds {
+            write!(out, ", fds: {fds}").unwrap();
+        }
+        if let Some(load) = metrics.load_average {
+            write!(out, ", load: {load:.2}").unwrap();
+        }
+        Some(out)
+    }
+
     /// We got new information about executions for this client, insert them.
     #[cfg(feature = "afl_exec_sec")]
     pub fn update_executions(&mut self, executions: u64, cur_time: Duration) {
@@ -388,16 +470,18 @@ impl ClientStats {
             self.prev_state_executions = self.executions;
         }
         self.executions = self.prev_state_executions + executions;
+        self.record_exec_sample(cur_time);
     }
 
     /// We got a new information about executions for this client, insert them.
     #[cfg(not(feature = "afl_exec_sec"))]
-    pub fn update_executions(&mut self, executions: u64, _cur_time: Duration) {
+    pub fn update_executions(&mut self, executions: u64, cur_time: Duration) {
         if self.executions > self.prev_state_executions + executions {
             // Something is strange here, sum the executions
             self.prev_state_executions = self.executions;
         }
         self.executions = self.prev_state_executions + executions;
+        self.record_exec_sample(cur_time);
     }
 
     /// We got new information about corpus size for this client, insert them.
@@ -465,6 +549,79 @@ impl ClientStats {
         prettify_float(self.execs_per_sec(cur_time))
     }
 
+    /// Remember the current executions, keeping at most one sample per [`CLIENT_STATS_SAMPLE_INTERVAL`]
+    fn record_exec_sample(&mut self, cur_time: Duration) {
+        let previous = self
+            .exec_samples
+            .len()
+            .checked_sub(2)
+            .map(|idx| self.exec_samples[idx].0);
+        if previous.is_some_and(|time| cur_time.saturating_sub(time) < CLIENT_STATS_SAMPLE_INTERVAL)
+        {
+            // Too close to the previous one, just move the latest sample forward
+            self.exec_samples.pop_back();
+        }
+        self.exec_samples.push_back((cur_time, self.executions));
+        if self.exec_samples.len() > CLIENT_STATS_MAX_SAMPLES {
+            self.exec_samples.pop_front();
+        }
+    }
+
+    /// Executions per second during the last `window`.
+    ///
+    /// A client that stopped reporting slows down over time, since the window extends up to `cur_time`.
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn execs_per_sec_window(&self, cur_time: Duration, window: Duration) -> f64 {
+        let Some(&(_, last_executions)) = self.exec_samples.back() else {
+            return 0.0;
+        };
+        let start = cur_time.saturating_sub(window);
+        // The last sample before the window is the baseline, if we have samples reaching back that far
+        let Some(&(first_time, first_executions)) = self
+            .exec_samples
+            .iter()
+            .rev()
+            .find(|(time, _)| *time <= start)
+            .or_else(|| self.exec_samples.front())
+        else {
+            return 0.0;
+        };
+        let elapsed = cur_time.saturating_sub(first_time).as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        (last_executions - first_executions) as f64 / elapsed
+    }
+
+    /// Exponentially weighted moving average of the executions per second, with the given `half_life`
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn execs_per_sec_ewma(&self, half_life: Duration) -> f64 {
+        let half_life = half_life.as_secs_f64();
+        let mut average = None;
+        for ((prev_time, prev_executions), (time, executions)) in self
+            .exec_samples
+            .iter()
+            .zip(self.exec_samples.iter().skip(1))
+        {
+            let elapsed = time.saturating_sub(*prev_time).as_secs_f64();
+            if elapsed <= 0.0 {
+                continue;
+            }
+            let rate = (executions - prev_executions) as f64 / elapsed;
+            average = Some(match average {
+                None => rate,
+                Some(_) if half_life <= 0.0 => rate,
+                Some(average) => {
+                    let weight = 1.0 - libm::exp2(-elapsed / half_life);
+                    average + weight * (rate - average)
+                }
+            });
+        }
+        average.unwrap_or(0.0)
+    }
+
     /// Update the user-defined stat with name and value
     pub fn update_user_stats(
         &mut self,
@@ -504,6 +661,9 @@ pub trait Monitor {
     /// Show the monitor to the user
     fn display(&mut self, event_msg: &str, sender_id: ClientId);
 
+    /// Called regularly by the broker, also while no events arrive, e.g. to act on timers
+    fn tick(&mut self) {}
+
     /// Amount of elements in the corpus (combined for all children)
     fn corpus_size(&self) -> u64 {
         self.client_stats()
@@ -549,6 +709,37 @@ pub trait Monitor {
         prettify_float(self.execs_per_sec())
     }
 
+    /// Executions per second, aggregated over the enabled clients as given by `aggregation`.
+    ///
+    /// Unlike [`Self::execs_per_sec`], the windowed modes make recent slowdowns visible.
+    fn execs_per_sec_aggregated(&mut self, aggregation: ExecsAggregation) -> f64 {
+        let cur_time = current_time();
+        match aggregation {
+            ExecsAggregation::Cumulative => self.execs_per_sec(),
+            ExecsAggregation::SlidingWindow(window) => self
+                .client_stats()
+                .iter()
+                .filter(|client| client.enabled)
+                .map(|client| client.execs_per_sec_window(cur_time, window))
+                .sum(),
+            ExecsAggregation::Ewma { half_life } => self
+                .client_stats()
+                .iter()
+                .filter(|client| client.enabled)
+                .map(|client| client.execs_per_sec_ewma(half_life))
+                .sum(),
+            ExecsAggregation::Percentile(percentile) => {
+                let mut rates: Vec<f64> = self
+                    .client_stats_mut()
+                    .iter_mut()
+                    .filter(|client| client.enabled)
+                    .map(|client| client.execs_per_sec(cur_time))
+                    .collect();
+                percentile_of(&mut rates, percentile)
+            }
+        }
+    }
+
     /// The client monitor for a specific id, creating new if it doesn't exist
     fn client_stats_insert(&mut self, client_id: ClientId) {
         let total_client_stat_count = self.client_stats().len();
@@ -562,10 +753,13 @@ pub trait Monitor {
         }
         let new_stat = self.client_stats_mut_for(client_id);
         if !new_stat.enabled {
-            let timestamp = current_time();
-            // I have never seen this man in my life
-            new_stat.start_time = timestamp;
-            new_stat.last_window_time = timestamp;
+            // Clients that were lost for a while keep their start time
+            if new_stat.start_time == Duration::ZERO {
+                let timestamp = current_time();
+                // I have never seen this man in my life
+                new_stat.start_time = timestamp;
+                new_stat.last_window_time = timestamp;
+            }
             new_stat.enabled = true;
         }
     }
@@ -584,6 +778,21 @@ pub trait Monitor {
     fn aggregate(&mut self, _name: &str) {}
 }
 
+/// The nearest-rank `percentile` (between `0.0` and `1.0`) of `values`, or `0.0` if there are none
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn percentile_of(values: &mut [f64], percentile: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_unstable_by(f64::total_cmp);
+    let rank = libm::ceil(percentile.clamp(0.0, 1.0) * values.len() as f64) as usize;
+    values[rank.saturating_sub(1)]
+}
+
 /// Monitor that print exactly nothing.
 /// Not good for debugging, very good for speed.
 #[derive(Debug, Clone)]
@@ -690,6 +899,10 @@ impl Monitor for SimplePrintingMonitor {
             .map(|(key, value)| format!("{key}: {value}"))
             .collect::<Vec<_>>();
         userstats.sort();
+        if let Some(host_metrics) = self.client_stats()[sender_id.0 as usize].host_metrics_pretty()
+        {
+            userstats.push(host_metrics);
+        }
         println!(
             "[{} #{}] run time: {}, clients: {}, corpus: {}, objectives: {}, executions: {}, exec/sec: {}, {}",
             event_msg,
@@ -726,6 +939,7 @@ where
     print_fn: F,
     start_time: Duration,
     print_user_monitor: bool,
+    execs_aggregation: Option<ExecsAggregation>,
     client_stats: Vec<ClientStats>,
 }
 
@@ -778,6 +992,11 @@ where
             self.execs_per_sec_pretty()
         );
 
+        if let Some(aggregation) = self.execs_aggregation {
+            let execs_per_sec = self.execs_per_sec_aggregated(aggregation);
+            write!(fmt, " ({aggregation}: {})", prettify_float(execs_per_sec)).unwrap();
+        }
+
         if self.print_user_monitor {
             self.client_stats_insert(sender_id);
             let client = self.client_stats_mut_for(sender_id);
@@ -786,6 +1005,14 @@ where
             }
         }
 
+        if let Some(host_metrics) = self
+            .client_stats()
+            .get(sender_id.0 as usize)
+            .and_then(ClientStats::host_metrics_pretty)
+        {
+            write!(fmt, ", {host_metrics}").unwrap();
+        }
+
         (self.print_fn)(&fmt);
 
         // Only print perf monitor if the feature is enabled
@@ -814,6 +1041,7 @@ where
             print_fn,
             start_time: current_time(),
             print_user_monitor: false,
+            execs_aggregation: None,
             client_stats: vec![],
         }
     }
@@ -824,6 +1052,7 @@ where
             print_fn,
             start_time,
             print_user_monitor: false,
+            execs_aggregation: None,
             client_stats: vec![],
         }
     }
@@ -834,9 +1063,18 @@ where
             print_fn,
             start_time: current_time(),
             print_user_monitor: true,
+            execs_aggregation: None,
             client_stats: vec![],
         }
     }
+
+    /// Also print the executions per second aggregated with the given [`ExecsAggregation`],
+    /// e.g. over a sliding window, to spot recent slowdowns
+    #[must_use]
+    pub fn with_execs_aggregation(mut self, aggregation: ExecsAggregation) -> Self {
+        self.execs_aggregation = Some(aggregation);
+        self
+    }
 }
 
 /// Start the timer
@@ -1311,3 +1549,85 @@ impl Default for ClientPerfMonitor {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{rc::Rc, string::String, vec::Vec};
+    use core::{cell::RefCell, time::Duration};
+
+    use libafl_bolts::{os::ProcessMetrics, ClientId};
+
+    use super::{percentile_of, ClientStats, Monitor, NopMonitor, SimpleMonitor};
+
+    #[test]
+    fn test_execs_aggregation() {
+        let mut client = ClientStats::default();
+        // 100 execs/sec for 10 seconds, then 10 execs/sec for 10 seconds
+        for sec in 0..=20 {
+            let executions = if sec <= 10 {
+                sec * 100
+            } else {
+                1000 + (sec - 10) * 10
+            };
+            client.update_executions(executions, Duration::from_secs(sec));
+        }
+        let now = Duration::from_secs(20);
+        let recent = client.execs_per_sec_window(now, Duration::from_secs(5));
+        assert!((recent - 10.0).abs() < f64::EPSILON);
+        let ewma = client.execs_per_sec_ewma(Duration::from_secs(2));
+        assert!(ewma < 15.0);
+
+        let mut rates = [5.0, 1.0, 3.0, 2.0, 4.0];
+        assert!((percentile_of(&mut rates, 0.5) - 3.0).abs() < f64::EPSILON);
+        assert!((percentile_of(&mut rates, 0.95) - 5.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_client_reenabled() {
+        let mut monitor = NopMonitor::new();
+        monitor.client_stats_insert(ClientId(1));
+        let start_time = monitor.client_stats()[1].start_time;
+        assert!(!monitor.client_stats()[0].enabled);
+
+        // A lost client comes back
+        monitor.client_stats_mut()[1].enabled = false;
+        monitor.client_stats_insert(ClientId(1));
+        assert!(monitor.client_stats()[1].enabled);
+        assert_eq!(monitor.client_stats()[1].start_time, start_time);
+    }
+
+    #[test]
+    fn test_host_metrics() {
+        let mut client = ClientStats::default();
+        assert_eq!(client.host_metrics_pretty(), None);
+
+        let metrics = ProcessMetrics {
+            rss_bytes: 64 << 20,
+            cpu_time: Duration::from_secs(1),
+            open_fds: Some(12),
+            load_average: None,
+        };
+        client.update_host_metrics(metrics, Duration::from_secs(10));
+        assert_eq!(client.cpu_usage, None);
+        // Half a core over the next 4 seconds
+        let metrics = ProcessMetrics {
+            cpu_time: Duration::from_secs(3),
+            ..metrics
+        };
+        client.update_host_metrics(metrics, Duration::from_secs(14));
+        assert_eq!(
+            client.host_metrics_pretty().as_deref(),
+            Some("rss: 64.0 MiB, cpu: 50.0%, fds: 12")
+        );
+
+        let lines = Rc::new(RefCell::new(Vec::<String>::new()));
+        let printed = lines.clone();
+        let mut monitor = SimpleMonitor::new(move |line: &str| {
+            printed.borrow_mut().push(line.into());
+        });
+        monitor.client_stats_insert(ClientId(0));
+        monitor.client_stats_mut()[0] = client;
+        monitor.display("Client Heartbeat", ClientId(0));
+        assert!(lines.borrow()[0].ends_with(", rss: 64.0 MiB, cpu: 50.0%, fds: 12"));
+    }
+}
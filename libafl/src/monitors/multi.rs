@@ -100,6 +100,9 @@ where
         for (key, val) in &client.user_monitor {
             write!(fmt, ", {key}: {val}").unwrap();
         }
+        if let Some(host_metrics) = client.host_metrics_pretty() {
+            write!(fmt, ", {host_metrics}").unwrap();
+        }
         (self.print_fn)(&fmt);
 
         // Only print perf monitor if the feature is enabled
@@ -471,6 +471,9 @@ impl Monitor for TuiMonitor {
         for (key, val) in &client.user_monitor {
             write!(fmt, ", {key}: {val}").unwrap();
         }
+        if let Some(host_metrics) = client.host_metrics_pretty() {
+            write!(fmt, ", {host_metrics}").unwrap();
+        }
         for (key, val) in &self.aggregator.aggregated {
             write!(fmt, ", {key}: {val}").unwrap();
         }
@@ -0,0 +1,296 @@
+//! Mutators for [`CallSequenceInput`]s, inserting, removing and changing calls while respecting the [`ApiSpec`].
+//!
+//! See [`crate::inputs::calls`] for details.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::num::NonZero;
+
+use libafl_bolts::{rands::Rand, Named};
+use tuple_list::{tuple_list, tuple_list_type};
+
+use crate::{
+    inputs::calls::{random_int, ApiSpec, ArgType, CallArg, CallSequenceInput},
+    mutators::{MutationResult, Mutator},
+    state::HasRand,
+    Error,
+};
+
+/// The default maximum number of calls the [`CallInsertMutator`] grows a sequence to
+pub const DEFAULT_MAX_CALLS: usize = 64;
+
+/// Inserts a random call the spec allows at a random position
+#[derive(Debug, Clone)]
+pub struct CallInsertMutator {
+    spec: ApiSpec,
+    max_calls: usize,
+}
+
+impl CallInsertMutator {
+    /// Creates a new [`CallInsertMutator`] for the given spec
+    #[must_use]
+    pub fn new(spec: ApiSpec) -> Self {
+        Self {
+            spec,
+            max_calls: DEFAULT_MAX_CALLS,
+        }
+    }
+
+    /// Creates a new [`CallInsertMutator`], growing sequences to at most `max_calls` calls
+    #[must_use]
+    pub fn with_max_calls(spec: ApiSpec, max_calls: usize) -> Self {
+        Self { spec, max_calls }
+    }
+}
+
+impl<S> Mutator<CallSequenceInput, S> for CallInsertMutator
+where
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut CallSequenceInput,
+    ) -> Result<MutationResult, Error> {
+        let len = input.calls().len();
+        if len >= self.max_calls {
+            return Ok(MutationResult::Skipped);
+        }
+        let rand = state.rand_mut();
+        let idx = rand.between(0, len);
+        let Some(call) = self.spec.random_call(rand, &input.calls()[..idx]) else {
+            return Ok(MutationResult::Skipped);
+        };
+        input.insert(idx, call);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for CallInsertMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CallInsertMutator");
+        &NAME
+    }
+}
+
+/// Removes a random call, and the later calls using its handle
+#[derive(Debug, Default)]
+pub struct CallRemoveMutator;
+
+impl<S> Mutator<CallSequenceInput, S> for CallRemoveMutator
+where
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut CallSequenceInput,
+    ) -> Result<MutationResult, Error> {
+        let len = input.calls().len();
+        if len < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = state.rand_mut().below(NonZero::new(len).unwrap());
+        let mut removed = input.clone();
+        if removed.remove_with_dependents(idx) == len {
+            // Never leave an empty sequence
+            return Ok(MutationResult::Skipped);
+        }
+        *input = removed;
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for CallRemoveMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CallRemoveMutator");
+        &NAME
+    }
+}
+
+impl CallRemoveMutator {
+    /// Creates a new [`CallRemoveMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Changes a random argument of a random call, within the constraints of its type: integers stay in their
+/// range, byte buffers within the maximum length, and handles point to another earlier call of the same kind
+#[derive(Debug, Clone)]
+pub struct CallArgMutator {
+    spec: ApiSpec,
+}
+
+impl CallArgMutator {
+    /// Creates a new [`CallArgMutator`] for the given spec
+    #[must_use]
+    pub fn new(spec: ApiSpec) -> Self {
+        Self { spec }
+    }
+
+    /// Mutate the integer `value` between `min` and `max`
+    fn mutate_int<R>(rand: &mut R, value: u64, min: u64, max: u64) -> u64
+    where
+        R: Rand,
+    {
+        match rand.below(NonZero::new(4).unwrap()) {
+            0 => value.saturating_add(1 + rand.below(NonZero::new(16).unwrap()) as u64),
+            1 => value.saturating_sub(1 + rand.below(NonZero::new(16).unwrap()) as u64),
+            2 => *rand.choose(&[min, max]).unwrap(),
+            _ => random_int(rand, min, max),
+        }
+        .clamp(min, max)
+    }
+
+    /// Mutate the byte buffer, keeping it at most `max_len` long
+    #[allow(clippy::cast_possible_truncation)]
+    fn mutate_bytes<R>(rand: &mut R, bytes: &mut Vec<u8>, max_len: usize)
+    where
+        R: Rand,
+    {
+        let len = bytes.len();
+        match (NonZero::new(len), rand.below(NonZero::new(3).unwrap())) {
+            (Some(nz_len), 0) => {
+                let idx = rand.below(nz_len);
+                bytes[idx] ^= 1 << rand.below(NonZero::new(8).unwrap());
+            }
+            (Some(nz_len), 1) => {
+                bytes.remove(rand.below(nz_len));
+            }
+            _ if len < max_len => {
+                let idx = rand.between(0, len);
+                bytes.insert(idx, rand.next() as u8);
+            }
+            _ => bytes.truncate(len / 2),
+        }
+    }
+}
+
+impl<S> Mutator<CallSequenceInput, S> for CallArgMutator
+where
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut CallSequenceInput,
+    ) -> Result<MutationResult, Error> {
+        let rand = state.rand_mut();
+        let Some(idx) = rand.choose(
+            input
+                .calls()
+                .iter()
+                .enumerate()
+                .filter(|(_, call)| !call.args.is_empty())
+                .map(|(idx, _)| idx),
+        ) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let (before, rest) = input.calls_mut().split_at_mut(idx);
+        let call = &mut rest[0];
+        let Some(signature) = self.spec.functions().get(call.function) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let pos = rand.below(NonZero::new(call.args.len()).unwrap());
+        match (&signature.args[pos], &mut call.args[pos]) {
+            (ArgType::Int { min, max }, CallArg::Int(value)) => {
+                *value = Self::mutate_int(rand, *value, *min, *max);
+            }
+            (ArgType::Bytes, CallArg::Bytes(bytes)) => {
+                Self::mutate_bytes(rand, bytes, self.spec.max_bytes_len());
+            }
+            (ArgType::Handle(kind), CallArg::Result(provider)) => {
+                let current = *provider;
+                let Some(other) = rand.choose(
+                    self.spec
+                        .providers(before, *kind)
+                        .filter(|other| *other != current),
+                ) else {
+                    return Ok(MutationResult::Skipped);
+                };
+                *provider = other;
+            }
+            _ => return Ok(MutationResult::Skipped),
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for CallArgMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CallArgMutator");
+        &NAME
+    }
+}
+
+/// The type of [`api_call_mutations`]
+pub type ApiCallMutationsType =
+    tuple_list_type!(CallInsertMutator, CallRemoveMutator, CallArgMutator);
+
+/// The mutations for [`CallSequenceInput`]s of the given spec
+#[must_use]
+pub fn api_call_mutations(spec: &ApiSpec) -> ApiCallMutationsType {
+    tuple_list!(
+        CallInsertMutator::new(spec.clone()),
+        CallRemoveMutator::new(),
+        CallArgMutator::new(spec.clone()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{CallArgMutator, CallInsertMutator, CallRemoveMutator};
+    use crate::{
+        corpus::InMemoryCorpus,
+        feedbacks::ConstFeedback,
+        inputs::calls::{ApiSpec, ArgType, Call, CallArg, CallSequenceInput, CallSignature},
+        mutators::Mutator,
+        state::StdState,
+    };
+
+    #[test]
+    fn test_api_call_mutations() {
+        let spec = ApiSpec::new()
+            .with_max_bytes_len(8)
+            .with_function(
+                CallSignature::new("open")
+                    .with_arg(ArgType::int_between(1, 3))
+                    .returning(0),
+            )
+            .with_function(
+                CallSignature::new("put")
+                    .with_arg(ArgType::Handle(0))
+                    .with_arg(ArgType::Bytes),
+            )
+            .with_function(CallSignature::new("close").with_arg(ArgType::Handle(0)));
+
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<CallSequenceInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+
+        let mut input = CallSequenceInput::new(vec![Call::new(0, vec![CallArg::Int(1)])]);
+        let mut insert = CallInsertMutator::new(spec.clone());
+        let mut remove = CallRemoveMutator::new();
+        let mut arg = CallArgMutator::new(spec.clone());
+        for round in 0..256 {
+            match round % 3 {
+                0 => insert.mutate(&mut state, &mut input).unwrap(),
+                1 => arg.mutate(&mut state, &mut input).unwrap(),
+                _ if round % 9 == 8 => remove.mutate(&mut state, &mut input).unwrap(),
+                _ => insert.mutate(&mut state, &mut input).unwrap(),
+            };
+            spec.validate(&input).unwrap();
+            assert!(!input.calls().is_empty());
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Mutators for [`EntrypointInput`]s, moving testcases between entrypoints sharing a corpus.
+//!
+//! See [`crate::inputs::entrypoint`] for details.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::num::NonZero;
+
+use libafl_bolts::{rands::Rand, Named};
+
+use crate::{
+    inputs::entrypoint::{EntrypointInput, HasEntrypoint},
+    mutators::{MutationResult, Mutator},
+    state::HasRand,
+    Error,
+};
+
+/// Moves the input to another random entrypoint of the same group.
+///
+/// Only entrypoints of the same group accept similar inputs and can share their testcases, e.g. the
+/// decoders of the same format. By default, all entrypoints form a single group.
+#[derive(Debug, Clone)]
+pub struct EntrypointSwitchMutator {
+    /// The group of each entrypoint, by index
+    groups: Vec<usize>,
+}
+
+impl EntrypointSwitchMutator {
+    /// Creates a new [`EntrypointSwitchMutator`] for `entrypoints` entrypoints sharing their testcases
+    #[must_use]
+    pub fn new(entrypoints: usize) -> Self {
+        Self {
+            groups: vec![0; entrypoints],
+        }
+    }
+
+    /// Creates a new [`EntrypointSwitchMutator`] only moving inputs between the entrypoints of the same group,
+    /// given the group of each entrypoint, by index
+    #[must_use]
+    pub fn with_groups(groups: Vec<usize>) -> Self {
+        Self { groups }
+    }
+}
+
+impl<I, S> Mutator<EntrypointInput<I>, S> for EntrypointSwitchMutator
+where
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut EntrypointInput<I>,
+    ) -> Result<MutationResult, Error> {
+        let current = input.entrypoint();
+        let Some(group) = self.groups.get(current).copied() else {
+            return Ok(MutationResult::Skipped);
+        };
+        let others = self
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|(entrypoint, other)| *entrypoint != current && **other == group)
+            .count();
+        let Some(others) = NonZero::new(others) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let pick = state.rand_mut().below(others);
+        let (entrypoint, _) = self
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|(entrypoint, other)| *entrypoint != current && **other == group)
+            .nth(pick)
+            .unwrap();
+        input.set_entrypoint(entrypoint);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for EntrypointSwitchMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("EntrypointSwitchMutator");
+        &NAME
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::EntrypointSwitchMutator;
+    use crate::{
+        corpus::InMemoryCorpus,
+        feedbacks::ConstFeedback,
+        inputs::{
+            entrypoint::{EntrypointInput, HasEntrypoint},
+            BytesInput,
+        },
+        mutators::{MutationResult, Mutator},
+        state::StdState,
+    };
+
+    #[test]
+    fn test_entrypoint_switch() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<EntrypointInput<BytesInput>>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+
+        let mut mutator = EntrypointSwitchMutator::with_groups(vec![0, 1, 0, 1]);
+        let mut input = EntrypointInput::new(1, BytesInput::new(vec![1, 2]));
+        for _ in 0..8 {
+            let res = mutator.mutate(&mut state, &mut input).unwrap();
+            assert_eq!(res, MutationResult::Mutated);
+            assert!(input.entrypoint() == 1 || input.entrypoint() == 3);
+        }
+
+        let mut single = EntrypointSwitchMutator::new(1);
+        let mut input = EntrypointInput::new(0, BytesInput::new(vec![1]));
+        let res = single.mutate(&mut state, &mut input).unwrap();
+        assert_eq!(res, MutationResult::Skipped);
+    }
+}
@@ -17,6 +17,7 @@ use crate::mutators::{
         MappedCrossoverInsertMutator, MappedCrossoverReplaceMutator, QwordAddMutator,
         WordAddMutator, WordInterestingMutator,
     },
+    string_mutations::TextNumberMutator,
 };
 
 /// Tuple type of the mutations that compose the Havoc mutator without crossover mutations
@@ -231,6 +232,18 @@ pub fn havoc_mutations() -> HavocMutationsType {
     havoc_mutations_no_crossover().merge(havoc_crossover())
 }
 
+/// Tuple type of the mutations that compose the Havoc mutator with additional mutations for text-based targets
+pub type HavocMutationsTextType =
+    <HavocMutationsType as Merge<tuple_list_type!(TextNumberMutator)>>::MergeResult;
+
+/// Get the mutations that compose the Havoc mutator, plus the [`TextNumberMutator`] for text-based targets.
+///
+/// Opt in to these instead of [`havoc_mutations`] if the inputs are textual formats like JSON or config files.
+#[must_use]
+pub fn havoc_mutations_text() -> HavocMutationsTextType {
+    havoc_mutations().merge(tuple_list!(TextNumberMutator::new()))
+}
+
 /// Get the mutations that compose the Havoc mutator for mapped input types
 ///
 /// Check the example fuzzer for details on how to use this.
@@ -8,6 +8,8 @@ use core::fmt;
 pub use scheduled::*;
 pub mod mutations;
 pub use mutations::*;
+pub mod calls;
+pub use calls::*;
 pub mod token_mutations;
 use serde::{Deserialize, Serialize};
 pub use token_mutations::*;
@@ -15,6 +17,8 @@ pub mod havoc_mutations;
 pub use havoc_mutations::*;
 pub mod encoded_mutations;
 pub use encoded_mutations::*;
+pub mod entrypoint;
+pub use entrypoint::*;
 pub mod mopt_mutator;
 pub use mopt_mutator::*;
 pub mod gramatron;
@@ -25,6 +29,16 @@ pub mod mapping;
 pub use mapping::*;
 pub mod tuneable;
 pub use tuneable::*;
+pub mod string_mutations;
+pub use string_mutations::*;
+pub mod sequence;
+pub use sequence::*;
+pub mod numeric;
+pub use numeric::*;
+#[cfg(feature = "std")]
+pub mod structured;
+#[cfg(feature = "std")]
+pub use structured::*;
 
 #[cfg(feature = "unicode")]
 pub mod unicode;
@@ -366,6 +380,64 @@ impl<I, S> IntoVec<Box<dyn Mutator<I, S>>> for Vec<Box<dyn Mutator<I, S>>> {
     }
 }
 
+/// A type-erased [`Mutator`], to choose mutators at runtime without spelling out their types.
+///
+/// Each call goes through one virtual call. Collect several of them with [`BoxedMutator::into_inner`] into a
+/// `Vec<Box<dyn Mutator<I, S>>>`, which is a [`MutatorsTuple`] itself.
+pub struct BoxedMutator<I, S> {
+    inner: Box<dyn Mutator<I, S>>,
+}
+
+impl<I, S> BoxedMutator<I, S> {
+    /// Type-erases the given [`Mutator`]
+    pub fn new<M>(mutator: M) -> Self
+    where
+        M: Mutator<I, S> + 'static,
+    {
+        Self {
+            inner: Box::new(mutator),
+        }
+    }
+
+    /// The boxed [`Mutator`]
+    #[must_use]
+    pub fn into_inner(self) -> Box<dyn Mutator<I, S>> {
+        self.inner
+    }
+}
+
+impl<I, S> From<Box<dyn Mutator<I, S>>> for BoxedMutator<I, S> {
+    fn from(inner: Box<dyn Mutator<I, S>>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<I, S> fmt::Debug for BoxedMutator<I, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BoxedMutator")
+            .field(self.inner.name())
+            .finish()
+    }
+}
+
+impl<I, S> Named for BoxedMutator<I, S> {
+    fn name(&self) -> &Cow<'static, str> {
+        self.inner.name()
+    }
+}
+
+impl<I, S> Mutator<I, S> for BoxedMutator<I, S> {
+    #[inline]
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        self.inner.mutate(state, input)
+    }
+
+    #[inline]
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
+
 /// [`Mutator`] that does nothing, used for testing.
 ///
 /// Example:
@@ -15,12 +15,14 @@ use core::{
 use libafl_bolts::{rands::Rand, Named};
 
 use crate::{
-    corpus::Corpus,
+    corpus::{Corpus, CorpusId},
+    feedbacks::CoverageSummaryMetadata,
     inputs::HasMutatorBytes,
     mutators::{MutationResult, Mutator},
     nonzero, random_corpus_id_with_disabled,
+    stages::TaintMetadata,
     state::{HasCorpus, HasMaxSize, HasRand},
-    Error,
+    Error, HasMetadata,
 };
 
 /// Mem move in the own vec
@@ -332,6 +334,61 @@ impl ByteRandMutator {
     }
 }
 
+/// Byte random mutation restricted to the ranges the [`crate::stages::ColorizationStage`] found to leave the
+/// coverage unchanged: it keeps the execution path, but changes values that may flow into comparisons.
+///
+/// Skips if there is no [`TaintMetadata`], or if the input length changed since the colorization.
+#[derive(Default, Debug)]
+pub struct TaintedBytesRandMutator;
+
+impl<I, S> Mutator<I, S> for TaintedBytesRandMutator
+where
+    S: HasRand + HasMetadata,
+    I: HasMutatorBytes,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Some(meta) = state.metadata_map().get::<TaintMetadata>() else {
+            return Ok(MutationResult::Skipped);
+        };
+        if meta.input_vec().len() != input.bytes().len() {
+            return Ok(MutationResult::Skipped);
+        }
+        let tainted_len: usize = meta.ranges().iter().map(Range::len).sum();
+        let Some(tainted_len) = NonZero::new(tainted_len) else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        // Pick a tainted byte uniformly, over all ranges
+        let mut idx = state.rand_mut().below(tainted_len);
+        let meta = state.metadata_map().get::<TaintMetadata>().unwrap();
+        for range in meta.ranges() {
+            if idx < range.len() {
+                idx += range.start;
+                break;
+            }
+            idx -= range.len();
+        }
+
+        input.bytes_mut()[idx] ^= 1 + state.rand_mut().below(nonzero!(254)) as u8;
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for TaintedBytesRandMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TaintedBytesRandMutator");
+        &NAME
+    }
+}
+
+impl TaintedBytesRandMutator {
+    /// Creates a new [`TaintedBytesRandMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
 // Helper macro that defines the arithmetic addition/subtraction mutations where random slices
 // within the input are treated as u8, u16, u32, or u64, then mutated in place.
 macro_rules! add_mutator_impl {
@@ -1279,6 +1336,127 @@ impl CrossoverReplaceMutator {
     }
 }
 
+/// The default number of donor candidates of a [`CoverageSpliceMutator`]
+pub const COVERAGE_SPLICE_CANDIDATES: usize = 8;
+
+/// Crossover replace mutation preferring donors with complementary coverage.
+///
+/// Samples a few testcases and splices from the one covering most map entries the current testcase does not,
+/// according to their [`CoverageSummaryMetadata`] (add a [`crate::feedbacks::CoverageSummaryFeedback`] to
+/// the feedbacks). Testcases without a summary are only used if no better donor is found.
+#[derive(Debug)]
+pub struct CoverageSpliceMutator {
+    candidates: NonZeroUsize,
+}
+
+impl CoverageSpliceMutator {
+    /// Creates a new [`CoverageSpliceMutator`], sampling [`COVERAGE_SPLICE_CANDIDATES`] donors.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_candidates(nonzero!(COVERAGE_SPLICE_CANDIDATES))
+    }
+
+    /// Creates a new [`CoverageSpliceMutator`], sampling the given number of donors
+    #[must_use]
+    pub fn with_candidates(candidates: NonZeroUsize) -> Self {
+        Self { candidates }
+    }
+}
+
+impl Default for CoverageSpliceMutator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, S> Mutator<I, S> for CoverageSpliceMutator
+where
+    S: HasCorpus + HasRand,
+    <S::Corpus as Corpus>::Input: HasMutatorBytes,
+    I: HasMutatorBytes,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let size = input.bytes().len();
+        if size == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let current = *state.corpus().current();
+        let own_summary = match current {
+            Some(cur) => state
+                .corpus()
+                .get_from_all(cur)?
+                .borrow()
+                .metadata_map()
+                .get::<CoverageSummaryMetadata>()
+                .cloned()
+                .unwrap_or_default(),
+            None => CoverageSummaryMetadata::default(),
+        };
+
+        let mut best: Option<(CorpusId, u32)> = None;
+        for _ in 0..self.candidates.get() {
+            let id = random_corpus_id_with_disabled!(state.corpus(), state.rand_mut());
+            // We don't want to use the testcase we're already using for splicing
+            if Some(id) == current {
+                continue;
+            }
+            let score = state
+                .corpus()
+                .get_from_all(id)?
+                .borrow()
+                .metadata_map()
+                .get::<CoverageSummaryMetadata>()
+                .map_or(0, |summary| own_summary.complement_count(summary));
+            if !best.is_some_and(|(_, best_score)| score <= best_score) {
+                best = Some((id, score));
+            }
+        }
+        let Some((id, _)) = best else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        let other_size = {
+            let mut testcase = state.corpus().get_from_all(id)?.borrow_mut();
+            testcase.load_input(state.corpus())?.bytes().len()
+        };
+
+        if other_size < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        // # Safety
+        // Size is > 0 here (checked above)
+        let target = state
+            .rand_mut()
+            .below(unsafe { NonZero::new(size).unwrap_unchecked() });
+        // # Safety
+        // other_size is checked above.
+        // target is smaller than size (since below is exclusive) -> the subtraction result is larger than 0.
+        let range = rand_range(state, other_size, unsafe {
+            NonZero::new(min(other_size, size - target)).unwrap_unchecked()
+        });
+
+        let other_testcase = state.corpus().get_from_all(id)?.borrow_mut();
+        // No need to load the input again, it'll still be cached.
+        let other = other_testcase.input().as_ref().unwrap();
+
+        Ok(CrossoverReplaceMutator::crossover_replace(
+            input,
+            target,
+            range,
+            other.bytes(),
+        ))
+    }
+}
+
+impl Named for CoverageSpliceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CoverageSpliceMutator");
+        &NAME
+    }
+}
+
 trait IntoOptionBytes {
     type Type<'b>;
 
@@ -1614,8 +1792,12 @@ mod tests {
 
     use super::*;
     use crate::{
-        corpus::InMemoryCorpus, feedbacks::ConstFeedback, inputs::BytesInput,
-        mutators::MutatorsTuple, state::StdState, HasMetadata,
+        corpus::{InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        mutators::MutatorsTuple,
+        state::StdState,
+        HasMetadata,
     };
 
     type TestMutatorsTupleType = tuple_list_type!(
@@ -1933,4 +2115,57 @@ mod tests {
             < 500));
         Ok(())
     }
+
+    #[test]
+    fn test_coverage_splice() -> Result<(), Error> {
+        let summary = |idx| CoverageSummaryMetadata::from_indexes([idx], 1);
+        let mut corpus = InMemoryCorpus::new();
+        let mut add = |bytes: u8, idx| {
+            let mut testcase = Testcase::new(BytesInput::new(vec![bytes; 16]));
+            testcase.add_metadata(summary(idx));
+            corpus.add(testcase).unwrap()
+        };
+        let current = add(0, 0);
+        // The same region as the current testcase, and a different one
+        add(1, 0);
+        add(2, 1);
+
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )?;
+        *state.corpus_mut().current_mut() = Some(current);
+
+        let mut mutator = CoverageSpliceMutator::with_candidates(nonzero!(16));
+        for _ in 0..16 {
+            let mut input = BytesInput::new(vec![0; 16]);
+            assert_eq!(
+                mutator.mutate(&mut state, &mut input)?,
+                MutationResult::Mutated
+            );
+            assert!(input.bytes().contains(&2));
+            assert!(!input.bytes().contains(&1));
+        }
+
+        // Without any other testcase there is no donor
+        let mut corpus = InMemoryCorpus::new();
+        let current = corpus.add(Testcase::new(BytesInput::new(vec![0; 16])))?;
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )?;
+        *state.corpus_mut().current_mut() = Some(current);
+        let mut input = BytesInput::new(vec![0; 16]);
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input)?,
+            MutationResult::Skipped
+        );
+        Ok(())
+    }
 }
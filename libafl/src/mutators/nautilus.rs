@@ -40,6 +40,10 @@ impl<S: HasRand> Mutator<NautilusInput, S> for NautilusRandomMutator<'_> {
         state: &mut S,
         input: &mut NautilusInput,
     ) -> Result<MutationResult, Error> {
+        if !self.ctx.is_valid_tree(&input.tree) {
+            // e.g. a tree of another grammar, synced from the disk
+            return Ok(MutationResult::Skipped);
+        }
         // TODO get rid of tmp
         let mut tmp = vec![];
         self.mutator
@@ -102,6 +106,9 @@ impl<S: HasRand> Mutator<NautilusInput, S> for NautilusRecursionMutator<'_> {
         state: &mut S,
         input: &mut NautilusInput,
     ) -> Result<MutationResult, Error> {
+        if !self.ctx.is_valid_tree(&input.tree) {
+            return Ok(MutationResult::Skipped);
+        }
         // TODO don't calc recursions here
         if let Some(ref mut recursions) = input.tree.calc_recursions(self.ctx) {
             // TODO get rid of tmp
@@ -169,6 +176,9 @@ where
         state: &mut S,
         input: &mut NautilusInput,
     ) -> Result<MutationResult, Error> {
+        if !self.ctx.is_valid_tree(&input.tree) {
+            return Ok(MutationResult::Skipped);
+        }
         // TODO get rid of tmp
         let mut tmp = vec![];
         // Create a fast temp mutator to get around borrowing..
@@ -0,0 +1,244 @@
+//! Mutators for the numeric fields of structured inputs, see [`HasMutableNumbers`].
+//!
+//! Each mutation picks a random field, and mutates it at its own width and signedness,
+//! like the byte-level mutators of AFL do for random slices of a [`crate::inputs::BytesInput`].
+
+use alloc::borrow::Cow;
+use core::num::NonZero;
+
+use libafl_bolts::{nonzero, rands::Rand, Named};
+use tuple_list::{tuple_list, tuple_list_type};
+
+use crate::{
+    inputs::{HasMutableNumbers, NumberMut},
+    mutators::{
+        mutations::{ARITH_MAX, INTERESTING_16, INTERESTING_32, INTERESTING_8},
+        MutationResult, Mutator,
+    },
+    state::HasRand,
+    Error,
+};
+
+/// The mask of a field of `bits` bits
+fn mask(bits: u32) -> u64 {
+    u64::MAX >> (64 - bits)
+}
+
+/// The boundaries of a field of `bits` bits: its minimum and maximum and their neighbours,
+/// and the sign boundary, i.e. zero if signed and the signed maximum if not
+fn boundaries(bits: u32, signed: bool) -> [u64; 5] {
+    let (min, max, sign) = if signed {
+        (1 << (bits - 1), mask(bits) >> 1, 0)
+    } else {
+        (0, mask(bits), mask(bits) >> 1)
+    };
+    [min, min.wrapping_add(1), max, max.wrapping_sub(1), sign]
+}
+
+/// Swaps the byte order of a field of `bits` bits
+fn swap_bytes(value: u64, bits: u32) -> u64 {
+    (value & mask(bits)).swap_bytes() >> (64 - bits)
+}
+
+/// Picks a random numeric field of the input
+fn choose_number<'a, I, S>(state: &mut S, input: &'a mut I) -> Option<NumberMut<'a>>
+where
+    S: HasRand,
+    I: HasMutableNumbers + ?Sized,
+{
+    let len = NonZero::new(input.numbers_len())?;
+    let idx = state.rand_mut().below(len);
+    input.number_mut(idx)
+}
+
+macro_rules! number_mutator_impl {
+    ($(#[$meta: meta])* $name: ident, |$state: ident, $number: ident| $body: expr) => {
+        $(#[$meta])*
+        #[derive(Default, Debug)]
+        pub struct $name;
+
+        impl<I, S> Mutator<I, S> for $name
+        where
+            S: HasRand,
+            I: HasMutableNumbers,
+        {
+            fn mutate(&mut self, $state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+                let Some(mut $number) = choose_number($state, input) else {
+                    return Ok(MutationResult::Skipped);
+                };
+                let old = $number.get();
+                let new: Option<u64> = $body;
+                match new {
+                    Some(new) if new & mask($number.bits()) != old => {
+                        $number.set(new);
+                        Ok(MutationResult::Mutated)
+                    }
+                    _ => Ok(MutationResult::Skipped),
+                }
+            }
+        }
+
+        impl Named for $name {
+            fn name(&self) -> &Cow<'static, str> {
+                static NAME: Cow<'static, str> = Cow::Borrowed(stringify!($name));
+                &NAME
+            }
+        }
+
+        impl $name {
+            #[doc = concat!("Creates a new [`", stringify!($name), "`].")]
+            #[must_use]
+            pub fn new() -> Self {
+                Self
+            }
+        }
+    };
+}
+
+number_mutator_impl!(
+    /// Flips a random bit of a random numeric field
+    NumberBitFlipMutator,
+    |state, number| {
+        let bits = number.bits() as usize;
+        let bit = state.rand_mut().below(NonZero::new(bits).unwrap());
+        Some(number.get() ^ (1 << bit))
+    }
+);
+
+number_mutator_impl!(
+    /// Adds or subtracts a random value up to [`ARITH_MAX`] to a random numeric field, wrapping at its width
+    NumberArithMutator,
+    |state, number| {
+        let num = 1 + state.rand_mut().below(nonzero!(ARITH_MAX)) as u64;
+        if state.rand_mut().coinflip(0.5) {
+            Some(number.get().wrapping_add(num))
+        } else {
+            Some(number.get().wrapping_sub(num))
+        }
+    }
+);
+
+number_mutator_impl!(
+    /// Sets a random numeric field to one of the interesting values from AFL fitting its width
+    NumberInterestingMutator,
+    |state, number| {
+        #[allow(clippy::cast_sign_loss)]
+        let value = match number.bits() {
+            8 => *state.rand_mut().choose(&INTERESTING_8).unwrap() as u64,
+            16 => *state.rand_mut().choose(&INTERESTING_16).unwrap() as u64,
+            _ => *state.rand_mut().choose(&INTERESTING_32).unwrap() as u64,
+        };
+        Some(value)
+    }
+);
+
+number_mutator_impl!(
+    /// Sets a random numeric field to a boundary of its type, e.g. `i16::MIN` or `u32::MAX - 1`
+    NumberBoundaryMutator,
+    |state, number| {
+        state
+            .rand_mut()
+            .choose(boundaries(number.bits(), number.signed()))
+    }
+);
+
+number_mutator_impl!(
+    /// Swaps the byte order of a random numeric field, for fields the target reads in the other endianness
+    NumberByteSwapMutator,
+    |_state, number| {
+        (number.bits() > 8).then(|| swap_bytes(number.get(), number.bits()))
+    }
+);
+
+/// Tuple type of the mutations for inputs with [`HasMutableNumbers`]
+pub type NumericMutationsType = tuple_list_type!(
+    NumberBitFlipMutator,
+    NumberArithMutator,
+    NumberInterestingMutator,
+    NumberBoundaryMutator,
+    NumberByteSwapMutator,
+);
+
+/// Get the mutations for the numeric fields of inputs with [`HasMutableNumbers`]
+#[must_use]
+pub fn numeric_mutations() -> NumericMutationsType {
+    tuple_list!(
+        NumberBitFlipMutator::new(),
+        NumberArithMutator::new(),
+        NumberInterestingMutator::new(),
+        NumberBoundaryMutator::new(),
+        NumberByteSwapMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{
+        boundaries, swap_bytes, NumberBoundaryMutator, NumberByteSwapMutator,
+        NumberInterestingMutator,
+    };
+    use crate::{
+        corpus::InMemoryCorpus,
+        inputs::BytesInput,
+        mutators::{mutations::INTERESTING_8, MutationResult, Mutator},
+        state::StdState,
+    };
+
+    #[test]
+    fn test_number_helpers() {
+        assert_eq!(boundaries(8, true), [0x80, 0x81, 0x7f, 0x7e, 0]);
+        assert_eq!(boundaries(16, false), [0, 1, 0xffff, 0xfffe, 0x7fff]);
+        assert_eq!(swap_bytes(0x1234, 16), 0x3412);
+        assert_eq!(swap_bytes(0x1122_3344, 32), 0x4433_2211);
+    }
+
+    #[test]
+    fn test_numeric_mutators() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        let mut fields: Vec<i16> = vec![0, i16::MAX];
+        for _ in 0..32 {
+            NumberBoundaryMutator::new()
+                .mutate(&mut state, &mut fields)
+                .unwrap();
+            assert!(fields
+                .iter()
+                .all(|f| [i16::MIN, i16::MIN + 1, i16::MAX, i16::MAX - 1, 0].contains(f)));
+        }
+
+        let mut byte = 0_u8;
+        assert_eq!(
+            NumberByteSwapMutator::new()
+                .mutate(&mut state, &mut byte)
+                .unwrap(),
+            MutationResult::Skipped
+        );
+        for _ in 0..32 {
+            NumberInterestingMutator::new()
+                .mutate(&mut state, &mut byte)
+                .unwrap();
+        }
+        #[allow(clippy::cast_sign_loss)]
+        let interesting = INTERESTING_8.map(|v| v as u8);
+        assert!(interesting.contains(&byte));
+
+        let mut empty: Vec<u32> = vec![];
+        assert_eq!(
+            NumberInterestingMutator::new()
+                .mutate(&mut state, &mut empty)
+                .unwrap(),
+            MutationResult::Skipped
+        );
+    }
+}
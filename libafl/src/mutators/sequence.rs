@@ -0,0 +1,285 @@
+//! Mutators for [`SequenceInput`]s, changing the order and number of messages, and their payloads.
+//!
+//! See [`crate::inputs::sequence`] for details.
+
+use alloc::borrow::Cow;
+use core::num::NonZero;
+
+use libafl_bolts::{rands::Rand, Named};
+use tuple_list::{tuple_list, tuple_list_type};
+
+use crate::{
+    corpus::CorpusId,
+    inputs::sequence::SequenceInput,
+    mutators::{MutationResult, Mutator},
+    state::HasRand,
+    Error,
+};
+
+/// The default maximum number of messages the [`SequenceDuplicateMutator`] grows a sequence to
+pub const DEFAULT_MAX_MESSAGES: usize = 64;
+
+/// Moves a random message to another random position of the sequence
+#[derive(Debug, Default)]
+pub struct SequenceReorderMutator;
+
+impl<I, S> Mutator<SequenceInput<I>, S> for SequenceReorderMutator
+where
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut SequenceInput<I>,
+    ) -> Result<MutationResult, Error> {
+        let len = input.messages().len();
+        let Some(nz_len) = NonZero::new(len) else {
+            return Ok(MutationResult::Skipped);
+        };
+        if len < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+        let from = state.rand_mut().below(nz_len);
+        let to = state.rand_mut().below(nz_len);
+        if from == to {
+            return Ok(MutationResult::Skipped);
+        }
+        let message = input.messages_mut().remove(from);
+        input.messages_mut().insert(to, message);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for SequenceReorderMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SequenceReorderMutator");
+        &NAME
+    }
+}
+
+impl SequenceReorderMutator {
+    /// Creates a new [`SequenceReorderMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Inserts a copy of a random message right after it, e.g., to repeat a state transition
+#[derive(Debug)]
+pub struct SequenceDuplicateMutator {
+    max_messages: usize,
+}
+
+impl<I, S> Mutator<SequenceInput<I>, S> for SequenceDuplicateMutator
+where
+    I: Clone,
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut SequenceInput<I>,
+    ) -> Result<MutationResult, Error> {
+        let len = input.messages().len();
+        let Some(nz_len) = NonZero::new(len) else {
+            return Ok(MutationResult::Skipped);
+        };
+        if len >= self.max_messages {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = state.rand_mut().below(nz_len);
+        let message = input.messages()[idx].clone();
+        input.messages_mut().insert(idx + 1, message);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for SequenceDuplicateMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SequenceDuplicateMutator");
+        &NAME
+    }
+}
+
+impl Default for SequenceDuplicateMutator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SequenceDuplicateMutator {
+    /// Creates a new [`SequenceDuplicateMutator`], growing sequences up to [`DEFAULT_MAX_MESSAGES`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_messages(DEFAULT_MAX_MESSAGES)
+    }
+
+    /// Creates a new [`SequenceDuplicateMutator`], growing sequences up to `max_messages`.
+    #[must_use]
+    pub fn with_max_messages(max_messages: usize) -> Self {
+        Self { max_messages }
+    }
+}
+
+/// Removes a random message, keeping at least one
+#[derive(Debug, Default)]
+pub struct SequenceDropMutator;
+
+impl<I, S> Mutator<SequenceInput<I>, S> for SequenceDropMutator
+where
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut SequenceInput<I>,
+    ) -> Result<MutationResult, Error> {
+        let len = input.messages().len();
+        let Some(nz_len) = NonZero::new(len) else {
+            return Ok(MutationResult::Skipped);
+        };
+        if len < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = state.rand_mut().below(nz_len);
+        input.messages_mut().remove(idx);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for SequenceDropMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SequenceDropMutator");
+        &NAME
+    }
+}
+
+impl SequenceDropMutator {
+    /// Creates a new [`SequenceDropMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Mutates the payload of a random message with the inner mutator, keeping its kind
+#[derive(Debug)]
+pub struct SequencePayloadMutator<M> {
+    mutator: M,
+}
+
+impl<I, M, S> Mutator<SequenceInput<I>, S> for SequencePayloadMutator<M>
+where
+    M: Mutator<I, S>,
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut SequenceInput<I>,
+    ) -> Result<MutationResult, Error> {
+        let Some(len) = NonZero::new(input.messages().len()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let idx = state.rand_mut().below(len);
+        self.mutator
+            .mutate(state, &mut input.messages_mut()[idx].payload)
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        self.mutator.post_exec(state, new_corpus_id)
+    }
+}
+
+impl<M> Named for SequencePayloadMutator<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SequencePayloadMutator");
+        &NAME
+    }
+}
+
+impl<M> SequencePayloadMutator<M> {
+    /// Creates a new [`SequencePayloadMutator`], mutating payloads with `mutator`,
+    /// e.g. a [`crate::mutators::StdScheduledMutator`] of the havoc mutations.
+    #[must_use]
+    pub fn new(mutator: M) -> Self {
+        Self { mutator }
+    }
+}
+
+/// Tuple type of the mutations for [`SequenceInput`]s
+pub type SequenceMutationsType<M> = tuple_list_type!(
+    SequenceReorderMutator,
+    SequenceDuplicateMutator,
+    SequenceDropMutator,
+    SequencePayloadMutator<M>,
+);
+
+/// Get the mutations for [`SequenceInput`]s, mutating the payloads with `payload_mutator`
+#[must_use]
+pub fn sequence_mutations<M>(payload_mutator: M) -> SequenceMutationsType<M> {
+    tuple_list!(
+        SequenceReorderMutator::new(),
+        SequenceDuplicateMutator::new(),
+        SequenceDropMutator::new(),
+        SequencePayloadMutator::new(payload_mutator),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{SequenceDropMutator, SequenceDuplicateMutator, SequenceReorderMutator};
+    use crate::{
+        corpus::InMemoryCorpus,
+        inputs::{sequence::SequenceInput, BytesInput},
+        mutators::{MutationResult, Mutator},
+        state::StdState,
+    };
+
+    #[test]
+    fn test_sequence_mutators() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<SequenceInput<BytesInput>>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        let mut input = SequenceInput::from(vec![
+            (0, BytesInput::new(b"USER".to_vec())),
+            (1, BytesInput::new(b"PASS".to_vec())),
+        ]);
+
+        let mut duplicate = SequenceDuplicateMutator::with_max_messages(3);
+        assert_eq!(
+            duplicate.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Mutated
+        );
+        assert_eq!(input.messages().len(), 3);
+        assert_eq!(
+            duplicate.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Skipped
+        );
+
+        for _ in 0..10 {
+            SequenceReorderMutator::new()
+                .mutate(&mut state, &mut input)
+                .unwrap();
+        }
+        assert_eq!(input.messages().len(), 3);
+
+        let mut drop = SequenceDropMutator::new();
+        while input.messages().len() > 1 {
+            drop.mutate(&mut state, &mut input).unwrap();
+        }
+        assert_eq!(
+            drop.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Skipped
+        );
+    }
+}
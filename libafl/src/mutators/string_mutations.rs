@@ -0,0 +1,532 @@
+//! Category-preserving mutations for textual data in byte inputs, similar to `AFL++`'s string replacement mutations.
+//!
+//! Unlike the mutators in [`crate::mutators::unicode`], these do not need a [`crate::inputs::UnicodeInput`] or any
+//! preprocessing, but find ASCII and UTF-8 substrings in any [`HasMutatorBytes`] input on the fly.
+//! Digits are replaced with digits, letters with letters, and multibyte UTF-8 characters with characters of the same
+//! length, so that the input stays valid text.
+
+use alloc::{borrow::Cow, string::String};
+use core::{num::NonZero, ops::Range};
+
+use libafl_bolts::{rands::Rand, Named};
+use tuple_list::{tuple_list, tuple_list_type};
+
+use crate::{
+    inputs::HasMutatorBytes,
+    mutators::{MutationResult, Mutator},
+    nonzero,
+    state::{HasMaxSize, HasRand},
+    Error,
+};
+
+/// Boundary values of integers, as used by [`StrNumberBoundaryMutator`]
+pub const NUMBER_BOUNDARIES: [&str; 16] = [
+    "0",
+    "1",
+    "-1",
+    "127",
+    "128",
+    "255",
+    "256",
+    "32767",
+    "65535",
+    "65536",
+    "2147483647",
+    "-2147483648",
+    "4294967295",
+    "4294967296",
+    "9223372036854775807",
+    "18446744073709551615",
+];
+
+const ASCII_PUNCTUATION: &[u8] = b"!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// Find the first byte matching `pred`, starting at a random position and wrapping around
+fn find_random<S>(state: &mut S, bytes: &[u8], pred: impl Fn(u8) -> bool) -> Option<usize>
+where
+    S: HasRand,
+{
+    let start = state.rand_mut().below(NonZero::new(bytes.len())?);
+    (start..bytes.len())
+        .chain(0..start)
+        .find(|&idx| pred(bytes[idx]))
+}
+
+/// The range of the run of bytes matching `pred` around `pos`
+fn run_around(bytes: &[u8], pos: usize, pred: impl Fn(u8) -> bool) -> Range<usize> {
+    let start = bytes[..pos]
+        .iter()
+        .rposition(|b| !pred(*b))
+        .map_or(0, |idx| idx + 1);
+    let end = bytes[pos..]
+        .iter()
+        .position(|b| !pred(*b))
+        .map_or(bytes.len(), |idx| pos + idx);
+    start..end
+}
+
+/// Replaces the digits of a number in the input with random digits, keeping its length
+#[derive(Default, Debug)]
+pub struct StrDigitsMutator;
+
+impl<I, S> Mutator<I, S> for StrDigitsMutator
+where
+    S: HasRand,
+    I: HasMutatorBytes,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Some(pos) = find_random(state, input.bytes(), |b| b.is_ascii_digit()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let range = run_around(input.bytes(), pos, |b| b.is_ascii_digit());
+
+        // Either change a single digit, or the whole number
+        let range = if state.rand_mut().coinflip(0.5) {
+            pos..=pos
+        } else {
+            range.start..=range.end - 1
+        };
+        for idx in range {
+            input.bytes_mut()[idx] = b'0' + state.rand_mut().below(nonzero!(10)) as u8;
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for StrDigitsMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("StrDigitsMutator");
+        &NAME
+    }
+}
+
+impl StrDigitsMutator {
+    /// Creates a new [`StrDigitsMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a number in the input with one of the [`NUMBER_BOUNDARIES`]
+#[derive(Default, Debug)]
+pub struct StrNumberBoundaryMutator;
+
+impl<I, S> Mutator<I, S> for StrNumberBoundaryMutator
+where
+    S: HasRand + HasMaxSize,
+    I: HasMutatorBytes,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Some(pos) = find_random(state, input.bytes(), |b| b.is_ascii_digit()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let mut range = run_around(input.bytes(), pos, |b| b.is_ascii_digit());
+        if range.start > 0 && input.bytes()[range.start - 1] == b'-' {
+            range.start -= 1;
+        }
+
+        let replacement = state.rand_mut().choose(NUMBER_BOUNDARIES).unwrap();
+        let size = input.bytes().len();
+        if size - range.len() + replacement.len() > state.max_size()
+            || input.bytes()[range.clone()] == *replacement.as_bytes()
+        {
+            return Ok(MutationResult::Skipped);
+        }
+        input.splice(range, replacement.bytes());
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for StrNumberBoundaryMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("StrNumberBoundaryMutator");
+        &NAME
+    }
+}
+
+impl StrNumberBoundaryMutator {
+    /// Creates a new [`StrNumberBoundaryMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Flips the case of a letter, or of a whole word, in the input
+#[derive(Default, Debug)]
+pub struct StrCaseFlipMutator;
+
+impl<I, S> Mutator<I, S> for StrCaseFlipMutator
+where
+    S: HasRand,
+    I: HasMutatorBytes,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Some(pos) = find_random(state, input.bytes(), |b| b.is_ascii_alphabetic()) else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        let range = if state.rand_mut().coinflip(0.5) {
+            pos..pos + 1
+        } else {
+            run_around(input.bytes(), pos, |b| b.is_ascii_alphabetic())
+        };
+        // The case of the chosen letter decides the case of the whole word
+        let to_upper = input.bytes()[pos].is_ascii_lowercase();
+        let word = &mut input.bytes_mut()[range];
+        if to_upper {
+            word.make_ascii_uppercase();
+        } else {
+            word.make_ascii_lowercase();
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for StrCaseFlipMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("StrCaseFlipMutator");
+        &NAME
+    }
+}
+
+impl StrCaseFlipMutator {
+    /// Creates a new [`StrCaseFlipMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a character in the input with another one of the same category, keeping UTF-8 validity.
+///
+/// ASCII letters, digits, whitespace and punctuation are replaced within their class.
+/// Multibyte characters are replaced with a character of the same encoded length from the same 128 codepoint block,
+/// which usually belongs to the same script.
+#[derive(Default, Debug)]
+pub struct StrCharReplaceMutator;
+
+impl StrCharReplaceMutator {
+    /// Creates a new [`StrCharReplaceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// The replacement for an ASCII character, or `None` if it has no category
+    fn replace_ascii<R>(rand: &mut R, byte: u8) -> Option<u8>
+    where
+        R: Rand,
+    {
+        match byte {
+            b'a'..=b'z' => Some(b'a' + rand.below(nonzero!(26)) as u8),
+            b'A'..=b'Z' => Some(b'A' + rand.below(nonzero!(26)) as u8),
+            b'0'..=b'9' => Some(b'0' + rand.below(nonzero!(10)) as u8),
+            b' ' | b'\t' => rand.choose(*b" \t"),
+            _ if byte.is_ascii_punctuation() => rand.choose(ASCII_PUNCTUATION).copied(),
+            _ => None,
+        }
+    }
+}
+
+impl<I, S> Mutator<I, S> for StrCharReplaceMutator
+where
+    S: HasRand,
+    I: HasMutatorBytes,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        // Start at any byte that is not a UTF-8 continuation byte
+        let Some(pos) = find_random(state, input.bytes(), |b| b & 0xc0 != 0x80) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let bytes = input.bytes();
+        let char_len = match bytes[pos] {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            _ => 4,
+        };
+        let Some(encoded) = bytes.get(pos..pos + char_len) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let Some(c) = core::str::from_utf8(encoded)
+            .ok()
+            .and_then(|s| s.chars().next())
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        if c.is_ascii() {
+            let Some(replacement) = Self::replace_ascii(state.rand_mut(), c as u8) else {
+                return Ok(MutationResult::Skipped);
+            };
+            input.bytes_mut()[pos] = replacement;
+            return Ok(MutationResult::Mutated);
+        }
+
+        let block = u32::from(c) & !0x7f;
+        for _ in 0..8 {
+            let candidate = block | state.rand_mut().below(nonzero!(0x80)) as u32;
+            let Some(replacement) = char::from_u32(candidate) else {
+                continue;
+            };
+            if replacement.len_utf8() == char_len && replacement != c {
+                let mut buf = [0; 4];
+                replacement.encode_utf8(&mut buf);
+                input.bytes_mut()[pos..pos + char_len].copy_from_slice(&buf[..char_len]);
+                return Ok(MutationResult::Mutated);
+            }
+        }
+        Ok(MutationResult::Skipped)
+    }
+}
+
+impl Named for StrCharReplaceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("StrCharReplaceMutator");
+        &NAME
+    }
+}
+
+/// Interesting values for [`TextNumberMutator`]
+#[allow(clippy::cast_lossless)]
+pub const INTERESTING_NUMBERS: [i128; 20] = [
+    0,
+    1,
+    -1,
+    127,
+    128,
+    255,
+    256,
+    32767,
+    32768,
+    65535,
+    65536,
+    i32::MAX as i128,
+    i32::MIN as i128,
+    u32::MAX as i128,
+    u32::MAX as i128 + 1,
+    i64::MAX as i128,
+    i64::MIN as i128,
+    u64::MAX as i128,
+    u64::MAX as i128 + 1,
+    i128::MAX,
+];
+
+/// A decimal or hexadecimal number literal in a text input
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NumberLiteral {
+    /// The digits of the literal, including a leading `-` for decimals, excluding the `0x` for hexadecimals
+    range: Range<usize>,
+    value: i128,
+    hex: bool,
+}
+
+impl NumberLiteral {
+    /// Find the literal containing the digit at `pos`
+    fn find(bytes: &[u8], pos: usize) -> Option<Self> {
+        let hex_range = run_around(bytes, pos, |b| b.is_ascii_hexdigit());
+        let hex_start = if hex_range.start >= 2
+            && bytes[hex_range.start - 2] == b'0'
+            && matches!(bytes[hex_range.start - 1], b'x' | b'X')
+        {
+            // Within the digits after the `0x`
+            Some(hex_range.start)
+        } else if bytes[pos] == b'0'
+            && matches!(bytes.get(pos + 1), Some(b'x' | b'X'))
+            && bytes.get(pos + 2).is_some_and(u8::is_ascii_hexdigit)
+        {
+            // At the `0` of the `0x`
+            Some(pos + 2)
+        } else {
+            None
+        };
+
+        if let Some(start) = hex_start {
+            let range = run_around(bytes, start, |b| b.is_ascii_hexdigit());
+            let digits = core::str::from_utf8(&bytes[range.clone()]).ok()?;
+            let value = u128::from_str_radix(digits, 16).ok()?;
+            return Some(Self {
+                range,
+                value: i128::try_from(value).ok()?,
+                hex: true,
+            });
+        }
+
+        let mut range = run_around(bytes, pos, |b| b.is_ascii_digit());
+        if range.start > 0 && bytes[range.start - 1] == b'-' {
+            range.start -= 1;
+        }
+        let value = core::str::from_utf8(&bytes[range.clone()])
+            .ok()?
+            .parse()
+            .ok()?;
+        Some(Self {
+            range,
+            value,
+            hex: false,
+        })
+    }
+
+    /// Format a new value like this literal: hexadecimals keep their width and letter case
+    fn format(&self, bytes: &[u8], value: i128) -> String {
+        if self.hex {
+            let width = self.range.len();
+            let value = value.unsigned_abs();
+            if bytes[self.range.clone()].iter().any(u8::is_ascii_uppercase) {
+                format!("{value:0width$X}")
+            } else {
+                format!("{value:0width$x}")
+            }
+        } else {
+            format!("{value}")
+        }
+    }
+}
+
+/// Replaces a decimal or hexadecimal number literal in a text input with a boundary value, an off-by-one,
+/// or a large magnitude, keeping the base and the surrounding text.
+///
+/// Use it through [`crate::mutators::havoc_mutations_text`] for text-based targets.
+#[derive(Default, Debug)]
+pub struct TextNumberMutator;
+
+impl<I, S> Mutator<I, S> for TextNumberMutator
+where
+    S: HasRand + HasMaxSize,
+    I: HasMutatorBytes,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Some(pos) = find_random(state, input.bytes(), |b| b.is_ascii_digit()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let Some(literal) = NumberLiteral::find(input.bytes(), pos) else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        let value = literal.value;
+        let new_value = match state.rand_mut().below(nonzero!(4)) {
+            0 => *state.rand_mut().choose(&INTERESTING_NUMBERS).unwrap(),
+            1 => value.wrapping_add(1),
+            2 => value.wrapping_sub(1),
+            _ => {
+                // A large magnitude, e.g. to trigger overflows in the parser of the target
+                let shift = 8 * (1 + state.rand_mut().below(nonzero!(8)) as u32);
+                value.wrapping_shl(shift) | value
+            }
+        };
+        // Hexadecimal literals cannot be negative
+        let new_value = if literal.hex {
+            new_value.saturating_abs()
+        } else {
+            new_value
+        };
+        if new_value == value {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let replacement = literal.format(input.bytes(), new_value);
+        if input.bytes().len() - literal.range.len() + replacement.len() > state.max_size() {
+            return Ok(MutationResult::Skipped);
+        }
+        input.splice(literal.range, replacement.bytes());
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl Named for TextNumberMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TextNumberMutator");
+        &NAME
+    }
+}
+
+impl TextNumberMutator {
+    /// Creates a new [`TextNumberMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Tuple type of the string mutations
+pub type StringMutationsType = tuple_list_type!(
+    StrDigitsMutator,
+    StrNumberBoundaryMutator,
+    StrCaseFlipMutator,
+    StrCharReplaceMutator,
+);
+
+/// Get the string mutations, to be used alongside the havoc mutations for text-based targets
+#[must_use]
+pub fn string_mutations() -> StringMutationsType {
+    tuple_list!(
+        StrDigitsMutator::new(),
+        StrNumberBoundaryMutator::new(),
+        StrCaseFlipMutator::new(),
+        StrCharReplaceMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::*;
+    use crate::{
+        corpus::InMemoryCorpus, feedbacks::ConstFeedback, inputs::BytesInput, state::StdState,
+    };
+
+    #[test]
+    fn test_string_mutations_keep_utf8() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+
+        let mut input = BytesInput::new("Größe: 42 Äpfel, ÿ ☃ 𝄞!".as_bytes().to_vec());
+        let mut mutator = StrCharReplaceMutator::new();
+        for _ in 0..100 {
+            mutator.mutate(&mut state, &mut input).unwrap();
+            assert!(core::str::from_utf8(input.bytes()).is_ok());
+        }
+
+        let mut input = BytesInput::new(b"len=1234;".to_vec());
+        for _ in 0..20 {
+            StrDigitsMutator::new()
+                .mutate(&mut state, &mut input)
+                .unwrap();
+            let bytes = input.bytes();
+            assert_eq!(bytes.len(), 9);
+            assert!(bytes[4..8].iter().all(u8::is_ascii_digit));
+        }
+
+        let mut input = BytesInput::new(b"a=-5".to_vec());
+        StrNumberBoundaryMutator::new()
+            .mutate(&mut state, &mut input)
+            .unwrap();
+        let text = core::str::from_utf8(input.bytes()).unwrap();
+        assert!(NUMBER_BOUNDARIES.contains(&&text[2..]));
+    }
+
+    #[test]
+    fn test_number_literal() {
+        let find = |text: &str, pos| {
+            NumberLiteral::find(text.as_bytes(), pos).map(|lit| (lit.range, lit.value, lit.hex))
+        };
+        assert_eq!(find("x=-42;", 4), Some((2..5, -42, false)));
+        assert_eq!(find("c=0x1F;", 2), Some((4..6, 0x1f, true)));
+        assert_eq!(find("c=0x1F;", 4), Some((4..6, 0x1f, true)));
+        assert_eq!(find("v1.20", 3), Some((3..5, 20, false)));
+
+        let literal = NumberLiteral::find(b"0x00fF", 3).unwrap();
+        assert_eq!(literal.format(b"0x00fF", 0x1ab), "01AB");
+    }
+}
@@ -0,0 +1,405 @@
+//! A structured mutator for any input implementing [`Serialize`] and [`Deserialize`].
+//!
+//! The [`SerdeMutator`] serializes the input into the serde data model, as a [`serde_json::Value`], mutates a random
+//! node of it, and deserializes it back. Numbers, strings, bools, sequences, maps and optional fields are mutated
+//! structurally, so Rust-typed inputs can be fuzzed without a single line of custom mutator code:
+//!
+//! ```rust
+//! # use libafl::mutators::{SerdeMutator, StdScheduledMutator};
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Serialize, Deserialize, Clone, Debug)]
+//! struct Request {
+//!     method: String,
+//!     port: u16,
+//!     headers: Vec<(String, String)>,
+//!     body: Option<Vec<u8>>,
+//! }
+//!
+//! let mutator = StdScheduledMutator::new(libafl_bolts::tuples::tuple_list!(
+//!     SerdeMutator::<Request>::new()
+//! ));
+//! ```
+//!
+//! A mutation that does not fit the type, e.g. a number too large for a `u8` field, fails to deserialize
+//! and is retried with another node.
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{marker::PhantomData, num::NonZero};
+
+use libafl_bolts::{nonzero, rands::Rand, Named};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Number, Value};
+
+#[cfg(doc)]
+use serde::Deserialize;
+
+use crate::{
+    mutators::{mutations::ARITH_MAX, MutationResult, Mutator},
+    state::HasRand,
+    Error,
+};
+
+/// The default amount of mutations tried, until one fits the type of the input
+pub const DEFAULT_SERDE_MUTATION_TRIES: usize = 8;
+
+/// Interesting integers, at the boundaries of the integer types
+const INTERESTING_INTEGERS: [i128; 16] = [
+    0,
+    1,
+    -1,
+    i8::MAX as i128,
+    u8::MAX as i128,
+    i16::MIN as i128,
+    i16::MAX as i128,
+    u16::MAX as i128,
+    i32::MIN as i128,
+    i32::MAX as i128,
+    u32::MAX as i128,
+    i64::MIN as i128,
+    i64::MAX as i128,
+    u64::MAX as i128,
+    i8::MIN as i128,
+    (u32::MAX as i128) + 1,
+];
+
+/// Interesting floats
+const INTERESTING_FLOATS: [f64; 8] = [
+    0.0,
+    -0.0,
+    1.0,
+    -1.0,
+    0.5,
+    f64::EPSILON,
+    f64::MIN_POSITIVE,
+    f64::MAX,
+];
+
+/// Interesting strings
+const INTERESTING_STRINGS: [&str; 8] = ["", " ", "0", "-1", "%s%n", "\0", "\u{feff}", "null"];
+
+/// The amount of nodes of the value, in pre-order
+fn count_nodes(value: &Value) -> usize {
+    1 + match value {
+        Value::Array(items) => items.iter().map(count_nodes).sum(),
+        Value::Object(map) => map.values().map(count_nodes).sum(),
+        _ => 0,
+    }
+}
+
+/// The `n`-th node of the value, in pre-order
+fn nth_node(value: &mut Value, n: &mut usize) -> Option<&mut Value> {
+    if *n == 0 {
+        return Some(value);
+    }
+    *n -= 1;
+    let children: Vec<&mut Value> = match value {
+        Value::Array(items) => items.iter_mut().collect(),
+        Value::Object(map) => map.values_mut().collect(),
+        _ => return None,
+    };
+    for child in children {
+        let size = count_nodes(child);
+        if *n < size {
+            return nth_node(child, n);
+        }
+        *n -= size;
+    }
+    None
+}
+
+/// A mutated integer
+fn mutate_integer<R>(rand: &mut R, int: i128) -> i128
+where
+    R: Rand,
+{
+    match rand.below(nonzero!(4)) {
+        0 => int + 1 + rand.below(nonzero!(ARITH_MAX)) as i128,
+        1 => int - 1 - rand.below(nonzero!(ARITH_MAX)) as i128,
+        2 => int ^ (1 << rand.below(nonzero!(64))),
+        _ => *rand.choose(&INTERESTING_INTEGERS).unwrap(),
+    }
+}
+
+/// A mutated number, which may not fit the type of the field
+fn mutate_number<R>(rand: &mut R, number: &Number) -> Option<Number>
+where
+    R: Rand,
+{
+    let int = number
+        .as_i64()
+        .map(i128::from)
+        .or_else(|| number.as_u64().map(i128::from));
+    if let Some(int) = int {
+        let int = mutate_integer(rand, int);
+        return i64::try_from(int)
+            .map(Number::from)
+            .or_else(|_| u64::try_from(int).map(Number::from))
+            .ok();
+    }
+    let float = number.as_f64()?;
+    let float = match rand.below(nonzero!(3)) {
+        0 => -float,
+        1 => float * 2.0,
+        _ => *rand.choose(&INTERESTING_FLOATS).unwrap(),
+    };
+    Number::from_f64(float)
+}
+
+/// A mutated string
+#[allow(clippy::cast_possible_truncation)]
+fn mutate_string<R>(rand: &mut R, string: &mut String)
+where
+    R: Rand,
+{
+    let boundaries = string
+        .char_indices()
+        .map(|(idx, _)| idx)
+        .chain(core::iter::once(string.len()))
+        .collect::<Vec<_>>();
+    let pos = |rand: &mut R| *rand.choose(&boundaries).unwrap();
+    match rand.below(nonzero!(4)) {
+        0 => {
+            let (a, b) = (pos(rand), pos(rand));
+            string.replace_range(a.min(b)..a.max(b), "");
+        }
+        1 => {
+            let (a, b) = (pos(rand), pos(rand));
+            let copy = String::from(&string[a.min(b)..a.max(b)]);
+            string.insert_str(pos(rand), &copy);
+        }
+        2 => {
+            let c = char::from(0x20 + rand.below(nonzero!(0x5f)) as u8);
+            string.insert(pos(rand), c);
+        }
+        _ => *string = String::from(*rand.choose(&INTERESTING_STRINGS).unwrap()),
+    }
+}
+
+/// Some value of a random kind, to set absent optional fields
+#[allow(clippy::cast_possible_truncation)]
+fn random_value<R>(rand: &mut R) -> Value
+where
+    R: Rand,
+{
+    match rand.below(nonzero!(6)) {
+        0 => Value::Bool(rand.coinflip(0.5)),
+        1 => Value::from(mutate_integer(rand, 0) as i64),
+        2 => Value::String(String::from(*rand.choose(&INTERESTING_STRINGS).unwrap())),
+        3 => Value::Array(Vec::new()),
+        4 => Value::Object(Map::new()),
+        _ => Value::from(0.0),
+    }
+}
+
+/// Mutates the node in place, returns if it changed
+fn mutate_node<R>(rand: &mut R, node: &mut Value) -> bool
+where
+    R: Rand,
+{
+    match node {
+        // An absent optional field, or a unit
+        Value::Null => *node = random_value(rand),
+        Value::Bool(b) => *b = !*b,
+        Value::Number(number) => match mutate_number(rand, number) {
+            Some(mutated) => *number = mutated,
+            None => return false,
+        },
+        Value::String(string) => mutate_string(rand, string),
+        Value::Array(items) => {
+            let Some(len) = NonZero::new(items.len()) else {
+                return false;
+            };
+            let idx = rand.below(len);
+            match rand.below(nonzero!(4)) {
+                0 => {
+                    let other = rand.below(len);
+                    items.swap(idx, other);
+                }
+                1 => {
+                    items.remove(idx);
+                }
+                2 => {
+                    let item = items[idx].clone();
+                    items.insert(idx, item);
+                }
+                _ => items.reverse(),
+            }
+        }
+        Value::Object(map) => {
+            let Some(len) = NonZero::new(map.len()) else {
+                return false;
+            };
+            let key = map.keys().nth(rand.below(len)).unwrap().clone();
+            if rand.coinflip(0.5) {
+                // Drop an optional field
+                map.insert(key, Value::Null);
+            } else {
+                // Drop a field with a default
+                map.remove(&key);
+            }
+        }
+    }
+    true
+}
+
+/// Mutates any input implementing [`Serialize`] and [`Deserialize`], by walking its serde data model.
+///
+/// Tweaks numbers and strings, flips bools, reorders, drops and duplicates sequence items, and drops or sets
+/// optional fields. A mutation is tried up to `max_tries` times, until one deserializes to the input type.
+/// Inputs without a JSON data model, e.g. maps with non-string keys, are skipped.
+#[derive(Debug)]
+pub struct SerdeMutator<I> {
+    max_tries: usize,
+    phantom: PhantomData<I>,
+}
+
+impl<I, S> Mutator<I, S> for SerdeMutator<I>
+where
+    I: Serialize + DeserializeOwned,
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Ok(value) = serde_json::to_value(&*input) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let nodes = NonZero::new(count_nodes(&value)).unwrap();
+        for _ in 0..self.max_tries {
+            let mut mutated = value.clone();
+            let mut n = state.rand_mut().below(nodes);
+            let node = nth_node(&mut mutated, &mut n).unwrap();
+            if !mutate_node(state.rand_mut(), node) || mutated == value {
+                continue;
+            }
+            if let Ok(new) = serde_json::from_value(mutated) {
+                *input = new;
+                return Ok(MutationResult::Mutated);
+            }
+        }
+        Ok(MutationResult::Skipped)
+    }
+}
+
+impl<I> Named for SerdeMutator<I> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SerdeMutator");
+        &NAME
+    }
+}
+
+impl<I> Default for SerdeMutator<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> SerdeMutator<I> {
+    /// Creates a new [`SerdeMutator`], trying up to [`DEFAULT_SERDE_MUTATION_TRIES`] mutations.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_tries(DEFAULT_SERDE_MUTATION_TRIES)
+    }
+
+    /// Creates a new [`SerdeMutator`], trying up to `max_tries` mutations until one fits the type of the input.
+    #[must_use]
+    pub fn with_max_tries(max_tries: usize) -> Self {
+        Self {
+            max_tries,
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+    use libafl_bolts::rands::StdRand;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use super::{count_nodes, nth_node, SerdeMutator};
+    use crate::{
+        corpus::NopCorpus,
+        inputs::BytesInput,
+        mutators::{MutationResult, Mutator},
+        state::StdState,
+    };
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    enum Method {
+        Get,
+        Post { length: u32 },
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Request {
+        method: Method,
+        port: u8,
+        path: String,
+        headers: Vec<(String, String)>,
+        body: Option<Vec<u8>>,
+        #[serde(default)]
+        keep_alive: bool,
+    }
+
+    #[test]
+    fn test_nth_node() {
+        let mut value = json!({"a": [1, {"b": 2}], "c": "d"});
+        assert_eq!(count_nodes(&value), 6);
+        assert_eq!(nth_node(&mut value, &mut 3).cloned(), Some(json!({"b": 2})));
+        assert_eq!(nth_node(&mut value, &mut 4).cloned(), Some(json!(2)));
+        assert_eq!(nth_node(&mut value, &mut 5).cloned(), Some(json!("d")));
+        assert_eq!(nth_node(&mut value, &mut 6), None);
+    }
+
+    #[test]
+    fn test_serde_mutator() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            NopCorpus::<BytesInput>::new(),
+            NopCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        let original = Request {
+            method: Method::Post { length: 4 },
+            port: 80,
+            path: String::from("/index.html"),
+            headers: vec![(String::from("Host"), String::from("localhost"))],
+            body: None,
+            keep_alive: true,
+        };
+        let mut input = original.clone();
+        let mut mutator = SerdeMutator::new();
+        let mut mutated = 0;
+        for _ in 0..256 {
+            if mutator.mutate(&mut state, &mut input).unwrap() == MutationResult::Mutated {
+                mutated += 1;
+            }
+        }
+        assert!(mutated > 64);
+        assert_ne!(input, original);
+    }
+
+    #[test]
+    fn test_serde_mutator_skips_non_json() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            NopCorpus::<BytesInput>::new(),
+            NopCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+
+        // JSON objects only have string keys
+        let mut input = BTreeMap::from([(vec![1_u8], 2_u8)]);
+        let mut mutator = SerdeMutator::new();
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Skipped
+        );
+    }
+}
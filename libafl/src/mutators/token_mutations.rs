@@ -29,6 +29,7 @@ use crate::{
     mutators::{
         buffer_self_copy, mutations::buffer_copy, MultiMutator, MutationResult, Mutator, Named,
     },
+    nonzero,
     observers::cmp::{AFLppCmpValuesMetadata, CmpValues, CmpValuesMetadata},
     stages::TaintMetadata,
     state::{HasCorpus, HasMaxSize, HasRand},
@@ -614,6 +615,327 @@ impl I2SRandReplace {
     }
 }
 
+/// A transform the target applies to (a part of) the input before comparing it, see [`I2STransformReplace`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2STransform {
+    /// The input holds the operand as lowercase hex, e.g. the target decodes hex before comparing
+    Hex,
+    /// The input holds the operand as uppercase hex
+    HexUpper,
+    /// The input holds the operand base64-encoded
+    Base64,
+    /// The target compares the input value plus one
+    Increment,
+    /// The target compares the input value minus one
+    Decrement,
+    /// The target compares the CRC-32 of a slice of the input
+    Crc32,
+}
+
+impl I2STransform {
+    /// All transforms
+    pub const ALL: [Self; 6] = [
+        Self::Hex,
+        Self::HexUpper,
+        Self::Base64,
+        Self::Increment,
+        Self::Decrement,
+        Self::Crc32,
+    ];
+
+    /// Patch `bytes`, so that the target sees the other operand of `cmp_values` after applying this transform.
+    /// Returns `false` if no part of `bytes` at or after `off` matches an operand.
+    #[allow(clippy::cast_possible_truncation)] // CRC-32 operands are 4 bytes wide
+    fn apply(self, cmp_values: &CmpValues, bytes: &mut [u8], off: usize) -> bool {
+        match self {
+            Self::Hex | Self::HexUpper => {
+                let upper = self == Self::HexUpper;
+                byte_operand_pairs(cmp_values).iter().any(|(from, to)| {
+                    replace_first(bytes, off, &hex_encode(from, upper), &hex_encode(to, upper))
+                })
+            }
+            Self::Base64 => byte_operand_pairs(cmp_values)
+                .iter()
+                .any(|(from, to)| replace_first_base64(bytes, off, from, to)),
+            Self::Increment | Self::Decrement => {
+                // The input holds `operand - 1` for an incremented value, and vice versa
+                let delta = if self == Self::Increment {
+                    1_u64.wrapping_neg()
+                } else {
+                    1
+                };
+                int_operand_pairs(cmp_values)
+                    .iter()
+                    .any(|&(from, to, width)| {
+                        let (from, to) = (from.wrapping_add(delta), to.wrapping_add(delta));
+                        [false, true].into_iter().any(|big_endian| {
+                            replace_first(
+                                bytes,
+                                off,
+                                &int_bytes(from, width, big_endian),
+                                &int_bytes(to, width, big_endian),
+                            )
+                        })
+                    })
+            }
+            Self::Crc32 => int_operand_pairs(cmp_values)
+                .iter()
+                .filter(|(_, _, width)| *width == size_of::<u32>())
+                .any(|&(from, to, _)| forge_crc32_slice(bytes, off, from as u32, to as u32)),
+        }
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The `(input side, other side, width)` pairs of a numeric comparison
+fn int_operand_pairs(cmp_values: &CmpValues) -> Vec<(u64, u64, usize)> {
+    let width = match cmp_values {
+        CmpValues::U8(_) => size_of::<u8>(),
+        CmpValues::U16(_) => size_of::<u16>(),
+        CmpValues::U32(_) => size_of::<u32>(),
+        CmpValues::U64(_) => size_of::<u64>(),
+        CmpValues::Bytes(_) => return Vec::new(),
+    };
+    let (v1, v2, v1_is_const) = cmp_values.to_u64_tuple().unwrap();
+    let mut pairs = vec![(v2, v1, width)];
+    if !v1_is_const {
+        pairs.push((v1, v2, width));
+    }
+    pairs
+}
+
+/// The `(input side, other side)` pairs of a comparison as bytes, in both byte orders for numeric comparisons
+fn byte_operand_pairs(cmp_values: &CmpValues) -> Vec<(Vec<u8>, Vec<u8>)> {
+    if let CmpValues::Bytes((v1, v2)) = cmp_values {
+        let len = v1.len().min(v2.len());
+        if len == 0 {
+            return Vec::new();
+        }
+        let (v1, v2) = (&v1.as_slice()[..len], &v2.as_slice()[..len]);
+        return vec![(v1.to_vec(), v2.to_vec()), (v2.to_vec(), v1.to_vec())];
+    }
+    int_operand_pairs(cmp_values)
+        .into_iter()
+        .flat_map(|(from, to, width)| {
+            [false, true].map(|big_endian| {
+                (
+                    int_bytes(from, width, big_endian),
+                    int_bytes(to, width, big_endian),
+                )
+            })
+        })
+        .collect()
+}
+
+/// The lowest `width` bytes of `value`
+fn int_bytes(value: u64, width: usize, big_endian: bool) -> Vec<u8> {
+    if big_endian {
+        value.to_be_bytes()[size_of::<u64>() - width..].to_vec()
+    } else {
+        value.to_le_bytes()[..width].to_vec()
+    }
+}
+
+/// Overwrite the first occurrence of `from` at or after `off` with `to`, which has the same length
+fn replace_first(bytes: &mut [u8], off: usize, from: &[u8], to: &[u8]) -> bool {
+    debug_assert_eq!(from.len(), to.len());
+    if from.is_empty() || bytes.len() < from.len() {
+        return false;
+    }
+    for i in off..=bytes.len() - from.len() {
+        if bytes[i..i + from.len()] == *from {
+            bytes[i..i + to.len()].copy_from_slice(to);
+            return true;
+        }
+    }
+    false
+}
+
+fn hex_encode(bytes: &[u8], upper: bool) -> Vec<u8> {
+    let digits: &[u8; 16] = if upper {
+        b"0123456789ABCDEF"
+    } else {
+        b"0123456789abcdef"
+    };
+    bytes
+        .iter()
+        .flat_map(|byte| {
+            [
+                digits[usize::from(byte >> 4)],
+                digits[usize::from(byte & 0xf)],
+            ]
+        })
+        .collect()
+}
+
+/// The base64 sextets encoding `bytes`, without padding.
+///
+/// Also returns the mask of the bits of the last sextet that are determined by `bytes`,
+/// the remaining bits depend on whatever follows in the encoded stream.
+fn base64_sextets(bytes: &[u8]) -> (Vec<u8>, u8) {
+    let bits = bytes.len() * 8;
+    let sextets = (0..bits.div_ceil(6))
+        .map(|idx| {
+            (0..6).fold(0_u8, |sextet, bit| {
+                let pos = idx * 6 + bit;
+                let set = pos < bits && bytes[pos / 8] & (0x80 >> (pos % 8)) != 0;
+                (sextet << 1) | u8::from(set)
+            })
+        })
+        .collect();
+    let mask = match bits % 6 {
+        0 => 0x3f,
+        rem => (0x3f << (6 - rem)) & 0x3f,
+    };
+    (sextets, mask)
+}
+
+/// Like [`replace_first`], but for the base64 encodings of `from` and `to`, which have the same length
+#[allow(clippy::cast_possible_truncation)]
+fn replace_first_base64(bytes: &mut [u8], off: usize, from: &[u8], to: &[u8]) -> bool {
+    let (from, mask) = base64_sextets(from);
+    let (to, _) = base64_sextets(to);
+    let count = from.len();
+    if count == 0 || bytes.len() < count {
+        return false;
+    }
+    let decode = |c: u8| {
+        BASE64_ALPHABET
+            .iter()
+            .position(|a| *a == c)
+            .map(|v| v as u8)
+    };
+    let mask_of = |idx: usize| if idx + 1 == count { mask } else { 0x3f };
+
+    for i in off..=bytes.len() - count {
+        let matches = from.iter().enumerate().all(|(idx, sextet)| {
+            decode(bytes[i + idx]).is_some_and(|v| v & mask_of(idx) == sextet & mask_of(idx))
+        });
+        if matches {
+            for (idx, sextet) in to.iter().enumerate() {
+                let old = decode(bytes[i + idx]).unwrap();
+                let new = (sextet & mask_of(idx)) | (old & !mask_of(idx) & 0x3f);
+                bytes[i + idx] = BASE64_ALPHABET[usize::from(new)];
+            }
+            return true;
+        }
+    }
+    false
+}
+
+/// One byte of the (reflected) CRC-32, without the final inversion
+fn crc32_update(mut crc: u32, byte: u8) -> u32 {
+    crc ^= u32::from(byte);
+    for _ in 0..8 {
+        let mask = (crc & 1).wrapping_neg();
+        crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+    }
+    crc
+}
+
+/// Overwrite the last four bytes of `data`, so that its CRC-32 becomes `wanted`
+fn crc32_forge(data: &mut [u8], wanted: u32) {
+    // Runs the CRC backwards over one byte
+    fn unwind(crc: u32, byte: u8) -> u32 {
+        let mut rev = (crc >> 24) << 24;
+        for _ in 0..8 {
+            rev = if rev & 0x8000_0000 == 0 {
+                rev << 1
+            } else {
+                ((rev ^ 0xedb8_8320) << 1) | 1
+            };
+        }
+        (crc << 8) ^ rev ^ u32::from(byte)
+    }
+
+    let pos = data.len() - size_of::<u32>();
+    let forward = data[..pos]
+        .iter()
+        .fold(0xffff_ffff, |crc, byte| crc32_update(crc, *byte));
+    let patch = forward
+        .to_le_bytes()
+        .iter()
+        .rev()
+        .fold(!wanted, |crc, byte| unwind(crc, *byte));
+    data[pos..].copy_from_slice(&patch.to_le_bytes());
+}
+
+/// Find a slice starting at the beginning of the input or at `off` with the CRC-32 `from`, and forge it to `to`
+fn forge_crc32_slice(bytes: &mut [u8], off: usize, from: u32, to: u32) -> bool {
+    for start in [0, off] {
+        let mut crc = 0xffff_ffff;
+        for end in start..bytes.len() {
+            crc = crc32_update(crc, bytes[end]);
+            if end + 1 - start >= size_of::<u32>() && !crc == from {
+                crc32_forge(&mut bytes[start..=end], to);
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// A `I2STransformReplace` [`Mutator`] solves comparisons against encoded or transformed parts of the input,
+/// similar to the transform mode of AFL++'s cmplog (`-l AT`).
+///
+/// If a comparison operand matches the hex or base64 encoding, the value plus or minus one, or the CRC-32
+/// of a part of the input, that part is patched with the inverse [`I2STransform`] of the other operand.
+/// Byte-swapped values are covered as well, integers are searched for in both byte orders.
+/// It needs a valid [`CmpValuesMetadata`] in the state.
+#[derive(Debug, Default)]
+pub struct I2STransformReplace;
+
+impl<I, S> Mutator<I, S> for I2STransformReplace
+where
+    S: HasMetadata + HasRand,
+    I: HasMutatorBytes,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Some(size) = NonZero::new(input.bytes().len()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let cmps_len = state
+            .metadata_map()
+            .get::<CmpValuesMetadata>()
+            .map_or(0, |meta| meta.list.len());
+        let Some(cmps_len) = NonZero::new(cmps_len) else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        let idx = state.rand_mut().below(cmps_len);
+        let off = state.rand_mut().below(size);
+        let first = state.rand_mut().below(nonzero!(I2STransform::ALL.len()));
+
+        let meta = state.metadata_map().get::<CmpValuesMetadata>().unwrap();
+        let cmp_values = &meta.list[idx];
+        let bytes = input.bytes_mut();
+        for i in 0..I2STransform::ALL.len() {
+            let transform = I2STransform::ALL[(first + i) % I2STransform::ALL.len()];
+            if transform.apply(cmp_values, bytes, off) {
+                return Ok(MutationResult::Mutated);
+            }
+        }
+        Ok(MutationResult::Skipped)
+    }
+}
+
+impl Named for I2STransformReplace {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("I2STransformReplace");
+        &NAME
+    }
+}
+
+impl I2STransformReplace {
+    /// Creates a new `I2STransformReplace` struct.
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
 // A `I2SRandReplaceBinonly` [`Mutator`] replaces a random matching input-2-state comparison operand with the other.
 /// It needs a valid [`CmpValuesMetadata`] in the state.
 /// This version has been designed for binary-only fuzzing, for which cmp sized can be larger than necessary.
@@ -2092,8 +2414,49 @@ mod tests {
     #[cfg(feature = "std")]
     use std::fs;
 
+    use super::{crc32_forge, crc32_update, I2STransform};
     #[cfg(feature = "std")]
     use super::{AFLppRedQueen, Tokens};
+    use crate::observers::cmp::CmpValues;
+
+    #[test]
+    fn test_i2s_transforms() {
+        let magic = CmpValues::U32((0x1234_5678, 0xdead_beef, false));
+
+        let mut hex = b"xx78563412yy".to_vec();
+        assert!(I2STransform::Hex.apply(&magic, &mut hex, 0));
+        assert_eq!(hex, b"xxefbeaddeyy");
+
+        // base64 of [0x78, 0x56, 0x34, 0x12] is "eFY0Eg=="
+        let mut base64 = b"eFY0Eg==".to_vec();
+        assert!(I2STransform::Base64.apply(&magic, &mut base64, 0));
+        // base64 of [0xef, 0xbe, 0xad, 0xde] is "776t3g=="
+        assert_eq!(base64, b"776t3g==");
+
+        let mut inc = 0x1234_5677_u32.to_be_bytes().to_vec();
+        assert!(I2STransform::Increment.apply(&magic, &mut inc, 0));
+        assert_eq!(inc, 0xdead_beee_u32.to_be_bytes());
+
+        let mut crc_input = b"some checksummed data".to_vec();
+        let crc = !crc_input
+            .iter()
+            .fold(0xffff_ffff, |crc, b| crc32_update(crc, *b));
+        let checksum = CmpValues::U32((0xcafe_babe, crc, true));
+        assert!(I2STransform::Crc32.apply(&checksum, &mut crc_input, 0));
+        let forged = !crc_input
+            .iter()
+            .fold(0xffff_ffff, |crc, b| crc32_update(crc, *b));
+        assert_eq!(forged, 0xcafe_babe);
+
+        let mut data = [1, 2, 3, 4, 5, 6];
+        crc32_forge(&mut data, 0);
+        assert_eq!(
+            !data
+                .iter()
+                .fold(0xffff_ffff, |crc, b| crc32_update(crc, *b)),
+            0
+        );
+    }
 
     #[cfg(feature = "std")]
     #[test]
@@ -29,6 +29,9 @@ use crate::{
 #[allow(clippy::redundant_static_lifetimes)]
 pub mod unicode_categories;
 
+pub mod utf8;
+pub use utf8::*;
+
 /// Input which contains the context necessary to perform unicode mutations
 pub type UnicodeInput = (BytesInput, UnicodeIdentificationMetadata);
 
@@ -0,0 +1,534 @@
+//! Mutators for [`Utf8Input`]s, inserting, deleting and replacing chars and grapheme clusters.
+//!
+//! All mutations keep the input valid UTF-8. The grapheme mutators never split a user-perceived character, e.g. a
+//! letter and its combining accents, or an emoji and its skin tone modifier, which a parser would otherwise see as
+//! dangling marks. [`grapheme_boundaries`] approximates the extended grapheme clusters of Unicode, without tables
+//! beyond the mark categories.
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{iter, num::NonZero, ops::Range};
+
+use libafl_bolts::{rands::Rand, Error, Named};
+use tuple_list::{tuple_list, tuple_list_type};
+
+use crate::{
+    inputs::Utf8Input,
+    mutators::{unicode::unicode_categories::MARK, MutationResult, Mutator},
+    nonzero,
+    state::{HasMaxSize, HasRand},
+};
+
+/// Chars that often trip text processors: controls, invisibles, bidi overrides, case mappings changing the length,
+/// noncharacters and astral planes
+pub const INTERESTING_CHARS: [char; 16] = [
+    '\0',
+    '\u{7f}',
+    '\u{a0}',
+    '\u{130}',
+    '\u{301}',
+    '\u{200b}',
+    '\u{200d}',
+    '\u{2028}',
+    '\u{202e}',
+    '\u{fb03}',
+    '\u{feff}',
+    '\u{fffd}',
+    '\u{ffff}',
+    '\u{1f1e9}',
+    '\u{1f600}',
+    '\u{10ffff}',
+];
+
+/// Pairs of confusable chars, i.e. Latin letters and punctuation and their Cyrillic, Greek or typographic lookalikes
+pub const CONFUSABLES: [(char, char); 30] = [
+    ('a', 'а'),
+    ('c', 'с'),
+    ('e', 'е'),
+    ('i', 'і'),
+    ('j', 'ј'),
+    ('o', 'о'),
+    ('o', 'ο'),
+    ('p', 'р'),
+    ('s', 'ѕ'),
+    ('x', 'х'),
+    ('y', 'у'),
+    ('A', 'А'),
+    ('A', 'Α'),
+    ('B', 'В'),
+    ('E', 'Е'),
+    ('H', 'Н'),
+    ('I', 'І'),
+    ('K', 'К'),
+    ('M', 'М'),
+    ('O', 'О'),
+    ('P', 'Р'),
+    ('T', 'Т'),
+    ('X', 'Х'),
+    (' ', '\u{a0}'),
+    ('-', '\u{2010}'),
+    ('-', '\u{2212}'),
+    ('/', '\u{2215}'),
+    ('.', '\u{2024}'),
+    ('\'', '\u{2019}'),
+    ('"', '\u{201c}'),
+];
+
+/// The offset of the fullwidth forms of the printable ASCII chars
+const FULLWIDTH_OFFSET: u32 = 0xfee0;
+
+/// If `c` is in one of the sorted `ranges`
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    let c = c as u32;
+    ranges
+        .binary_search_by(|&(min, max)| {
+            if max < c {
+                core::cmp::Ordering::Less
+            } else if min > c {
+                core::cmp::Ordering::Greater
+            } else {
+                core::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// If `c` extends the grapheme cluster before it
+fn extends_grapheme(c: char) -> bool {
+    // ZWJ, variation selectors, emoji modifiers, and tags
+    matches!(
+        c as u32,
+        0x200d | 0xfe00..=0xfe0f | 0x1f3fb..=0x1f3ff | 0xe0020..=0xe007f | 0xe0100..=0xe01ef
+    ) || in_ranges(c, MARK)
+}
+
+/// If `c` is a regional indicator, two of which make a flag
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1f1e6}'..='\u{1f1ff}').contains(&c)
+}
+
+/// The byte offsets of the grapheme cluster boundaries of `s`, including `0` and `s.len()`.
+///
+/// Marks, joiners, modifiers and the second regional indicator of a flag do not start a new cluster,
+/// neither does a char after a zero width joiner, nor `\n` after `\r`.
+#[must_use]
+pub fn grapheme_boundaries(s: &str) -> Vec<usize> {
+    let mut boundaries = vec![0];
+    let mut prev: Option<char> = None;
+    let mut regional_indicators = 0;
+    for (idx, c) in s.char_indices() {
+        if let Some(prev) = prev {
+            let flag = is_regional_indicator(c) && regional_indicators % 2 == 1;
+            let joined =
+                prev == '\u{200d}' || (prev == '\r' && c == '\n') || flag || extends_grapheme(c);
+            if !joined {
+                boundaries.push(idx);
+            }
+        }
+        if is_regional_indicator(c) {
+            regional_indicators += 1;
+        } else {
+            regional_indicators = 0;
+        }
+        prev = Some(c);
+    }
+    if !s.is_empty() {
+        boundaries.push(s.len());
+    }
+    boundaries
+}
+
+/// The byte offsets of the char boundaries of `s`, including `0` and `s.len()`
+fn char_boundaries(s: &str) -> Vec<usize> {
+    s.char_indices()
+        .map(|(idx, _)| idx)
+        .chain(iter::once(s.len()))
+        .collect()
+}
+
+/// The confusables of `c`, both ways, and its fullwidth form
+fn confusables(c: char) -> impl Iterator<Item = char> {
+    let fullwidth = match c {
+        '!'..='~' => char::from_u32(c as u32 + FULLWIDTH_OFFSET),
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - FULLWIDTH_OFFSET),
+        _ => None,
+    };
+    CONFUSABLES
+        .iter()
+        .filter_map(move |&(a, b)| {
+            if a == c {
+                Some(b)
+            } else if b == c {
+                Some(a)
+            } else {
+                None
+            }
+        })
+        .chain(fullwidth)
+}
+
+/// A random char: printable ASCII, one of the [`INTERESTING_CHARS`], or any char of the BMP or all planes
+#[allow(clippy::cast_possible_truncation)]
+fn random_char<R>(rand: &mut R) -> char
+where
+    R: Rand,
+{
+    let planes = match rand.below(nonzero!(4)) {
+        0 => return char::from(0x20 + rand.below(nonzero!(0x5f)) as u8),
+        1 => return *rand.choose(&INTERESTING_CHARS).unwrap(),
+        2 => nonzero!(0x1_0000),
+        _ => nonzero!(0x11_0000),
+    };
+    loop {
+        // Surrogates are not chars
+        if let Some(c) = char::from_u32(rand.below(planes) as u32) {
+            return c;
+        }
+    }
+}
+
+/// A random range between two consecutive `boundaries`
+fn choose_unit<R>(rand: &mut R, boundaries: &[usize]) -> Option<Range<usize>>
+where
+    R: Rand,
+{
+    let units = NonZero::new(boundaries.len().checked_sub(1)?)?;
+    let idx = rand.below(units);
+    Some(boundaries[idx]..boundaries[idx + 1])
+}
+
+/// Replaces `range` of the input with `replacement`, if the input stays within the max size
+fn replace<S>(
+    state: &S,
+    input: &mut Utf8Input,
+    range: Range<usize>,
+    replacement: &str,
+) -> MutationResult
+where
+    S: HasMaxSize,
+{
+    if input.as_str().len() - range.len() + replacement.len() > state.max_size()
+        || input.as_str()[range.clone()] == *replacement
+    {
+        return MutationResult::Skipped;
+    }
+    input.string_mut().replace_range(range, replacement);
+    MutationResult::Mutated
+}
+
+/// Inserts a random char at a random char boundary
+#[derive(Debug, Default)]
+pub struct Utf8CharInsertMutator;
+
+impl<S> Mutator<Utf8Input, S> for Utf8CharInsertMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut Utf8Input) -> Result<MutationResult, Error> {
+        let boundaries = char_boundaries(input.as_str());
+        let pos = *state.rand_mut().choose(&boundaries).unwrap();
+        let c = random_char(state.rand_mut());
+        Ok(replace(state, input, pos..pos, c.encode_utf8(&mut [0; 4])))
+    }
+}
+
+impl Named for Utf8CharInsertMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("Utf8CharInsertMutator");
+        &NAME
+    }
+}
+
+impl Utf8CharInsertMutator {
+    /// Creates a new [`Utf8CharInsertMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Deletes a random char, which may split a grapheme cluster
+#[derive(Debug, Default)]
+pub struct Utf8CharDeleteMutator;
+
+impl<S> Mutator<Utf8Input, S> for Utf8CharDeleteMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut Utf8Input) -> Result<MutationResult, Error> {
+        let boundaries = char_boundaries(input.as_str());
+        let Some(range) = choose_unit(state.rand_mut(), &boundaries) else {
+            return Ok(MutationResult::Skipped);
+        };
+        Ok(replace(state, input, range, ""))
+    }
+}
+
+impl Named for Utf8CharDeleteMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("Utf8CharDeleteMutator");
+        &NAME
+    }
+}
+
+impl Utf8CharDeleteMutator {
+    /// Creates a new [`Utf8CharDeleteMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a random char with a random char
+#[derive(Debug, Default)]
+pub struct Utf8CharReplaceMutator;
+
+impl<S> Mutator<Utf8Input, S> for Utf8CharReplaceMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut Utf8Input) -> Result<MutationResult, Error> {
+        let boundaries = char_boundaries(input.as_str());
+        let Some(range) = choose_unit(state.rand_mut(), &boundaries) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let c = random_char(state.rand_mut());
+        Ok(replace(state, input, range, c.encode_utf8(&mut [0; 4])))
+    }
+}
+
+impl Named for Utf8CharReplaceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("Utf8CharReplaceMutator");
+        &NAME
+    }
+}
+
+impl Utf8CharReplaceMutator {
+    /// Creates a new [`Utf8CharReplaceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Inserts a copy of a random grapheme cluster of the input at a random grapheme boundary
+#[derive(Debug, Default)]
+pub struct Utf8GraphemeInsertMutator;
+
+impl<S> Mutator<Utf8Input, S> for Utf8GraphemeInsertMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut Utf8Input) -> Result<MutationResult, Error> {
+        let boundaries = grapheme_boundaries(input.as_str());
+        let Some(range) = choose_unit(state.rand_mut(), &boundaries) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let pos = *state.rand_mut().choose(&boundaries).unwrap();
+        let grapheme = String::from(&input.as_str()[range]);
+        Ok(replace(state, input, pos..pos, &grapheme))
+    }
+}
+
+impl Named for Utf8GraphemeInsertMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("Utf8GraphemeInsertMutator");
+        &NAME
+    }
+}
+
+impl Utf8GraphemeInsertMutator {
+    /// Creates a new [`Utf8GraphemeInsertMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Deletes a random grapheme cluster, with all its marks and modifiers
+#[derive(Debug, Default)]
+pub struct Utf8GraphemeDeleteMutator;
+
+impl<S> Mutator<Utf8Input, S> for Utf8GraphemeDeleteMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut Utf8Input) -> Result<MutationResult, Error> {
+        let boundaries = grapheme_boundaries(input.as_str());
+        let Some(range) = choose_unit(state.rand_mut(), &boundaries) else {
+            return Ok(MutationResult::Skipped);
+        };
+        Ok(replace(state, input, range, ""))
+    }
+}
+
+impl Named for Utf8GraphemeDeleteMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("Utf8GraphemeDeleteMutator");
+        &NAME
+    }
+}
+
+impl Utf8GraphemeDeleteMutator {
+    /// Creates a new [`Utf8GraphemeDeleteMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a random grapheme cluster with another one of the input, or with a random char
+#[derive(Debug, Default)]
+pub struct Utf8GraphemeReplaceMutator;
+
+impl<S> Mutator<Utf8Input, S> for Utf8GraphemeReplaceMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut Utf8Input) -> Result<MutationResult, Error> {
+        let boundaries = grapheme_boundaries(input.as_str());
+        let Some(range) = choose_unit(state.rand_mut(), &boundaries) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let replacement = if state.rand_mut().coinflip(0.5) {
+            let other = choose_unit(state.rand_mut(), &boundaries).unwrap();
+            String::from(&input.as_str()[other])
+        } else {
+            String::from(random_char(state.rand_mut()))
+        };
+        Ok(replace(state, input, range, &replacement))
+    }
+}
+
+impl Named for Utf8GraphemeReplaceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("Utf8GraphemeReplaceMutator");
+        &NAME
+    }
+}
+
+impl Utf8GraphemeReplaceMutator {
+    /// Creates a new [`Utf8GraphemeReplaceMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Replaces a random char with a lookalike, see [`CONFUSABLES`], or its fullwidth form,
+/// e.g. to bypass or to test blocklists and normalization
+#[derive(Debug, Default)]
+pub struct Utf8ConfusableMutator;
+
+impl<S> Mutator<Utf8Input, S> for Utf8ConfusableMutator
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut Utf8Input) -> Result<MutationResult, Error> {
+        let candidates = input
+            .as_str()
+            .char_indices()
+            .filter(|&(_, c)| confusables(c).next().is_some())
+            .collect::<Vec<_>>();
+        let Some(&(idx, c)) = state.rand_mut().choose(&candidates) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let confusable = state.rand_mut().choose(confusables(c)).unwrap();
+        Ok(replace(
+            state,
+            input,
+            idx..idx + c.len_utf8(),
+            confusable.encode_utf8(&mut [0; 4]),
+        ))
+    }
+}
+
+impl Named for Utf8ConfusableMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("Utf8ConfusableMutator");
+        &NAME
+    }
+}
+
+impl Utf8ConfusableMutator {
+    /// Creates a new [`Utf8ConfusableMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Tuple type of the mutations for [`Utf8Input`]s
+pub type Utf8MutationsType = tuple_list_type!(
+    Utf8CharInsertMutator,
+    Utf8CharDeleteMutator,
+    Utf8CharReplaceMutator,
+    Utf8GraphemeInsertMutator,
+    Utf8GraphemeDeleteMutator,
+    Utf8GraphemeReplaceMutator,
+    Utf8ConfusableMutator,
+);
+
+/// Get the mutations for [`Utf8Input`]s
+#[must_use]
+pub fn utf8_mutations() -> Utf8MutationsType {
+    tuple_list!(
+        Utf8CharInsertMutator::new(),
+        Utf8CharDeleteMutator::new(),
+        Utf8CharReplaceMutator::new(),
+        Utf8GraphemeInsertMutator::new(),
+        Utf8GraphemeDeleteMutator::new(),
+        Utf8GraphemeReplaceMutator::new(),
+        Utf8ConfusableMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{grapheme_boundaries, utf8_mutations, Utf8ConfusableMutator};
+    use crate::{
+        corpus::NopCorpus,
+        inputs::Utf8Input,
+        mutators::{MutationResult, Mutator, StdScheduledMutator},
+        state::{HasMaxSize, StdState},
+    };
+
+    #[test]
+    fn test_grapheme_boundaries() {
+        // e + combining acute, thumbs up + skin tone, CRLF, two flags
+        let s = "e\u{301}x\u{1f44d}\u{1f3fd}\r\n\u{1f1e9}\u{1f1ea}\u{1f1eb}\u{1f1f7}";
+        assert_eq!(grapheme_boundaries(s), [0, 3, 4, 12, 14, 22, 30]);
+        assert_eq!(grapheme_boundaries(""), [0]);
+    }
+
+    #[test]
+    fn test_utf8_mutations() {
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            NopCorpus::<Utf8Input>::new(),
+            NopCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        state.set_max_size(64);
+
+        let mut confusable = Utf8Input::from("ok");
+        assert_eq!(
+            Utf8ConfusableMutator::new()
+                .mutate(&mut state, &mut confusable)
+                .unwrap(),
+            MutationResult::Mutated
+        );
+        assert_ne!(confusable.as_str(), "ok");
+
+        let mut mutator = StdScheduledMutator::new(utf8_mutations());
+        let mut input = Utf8Input::from("na\u{ef}ve caf\u{e9} \u{1f44d}\u{1f3fd}");
+        for _ in 0..1024 {
+            mutator.mutate(&mut state, &mut input).unwrap();
+            assert!(input.as_str().len() <= 64);
+        }
+    }
+}
@@ -0,0 +1,427 @@
+//! The [`CrashContextObserver`] captures the machine state of an in-process crash, and [`CrashContext::classify`]
+//! estimates how exploitable it is.
+//!
+//! The context holds the signal, the faulting address and access type, and the registers at the crash.
+//! For targets running in a child process, see [`crate::stages::CrashAnalysisStage`], which collects the same
+//! context with `ptrace`, or run them with the `PtraceExecutor` on Linux, which feeds this observer directly.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, ptr::addr_of_mut};
+
+use libafl_bolts::{
+    os::unix_signals::{ucontext_t, Signal},
+    Named,
+};
+use libc::siginfo_t;
+use serde::{Deserialize, Serialize};
+
+use crate::{executors::ExitKind, observers::Observer, Error};
+
+/// Faults below this address are considered `NULL` pointer dereferences, as with a field offset from `NULL`
+pub const NULL_DEREF_THRESHOLD: usize = 0x10000;
+
+/// Faults this close to the stack pointer are considered stack overflows, hitting the guard page
+const STACK_OVERFLOW_DISTANCE: usize = 0x10000;
+
+/// The crash context of the last crash, set by the crash handler or the `PtraceExecutor`
+static mut LAST_CRASH_CONTEXT: Option<CrashContext> = None;
+
+/// The kind of memory access that faulted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AccessType {
+    /// A read of data
+    Read,
+    /// A write of data
+    Write,
+    /// An instruction fetch
+    Execute,
+    /// Not known on this platform, or not a memory fault
+    Unknown,
+}
+
+/// The classification of a crash, from the most to the least likely exploitable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CrashKind {
+    /// The program counter itself faulted, e.g. after a stack smash overwrote a return address,
+    /// or a corrupted function pointer was called
+    ControlFlowHijack,
+    /// A write to an invalid, non-`NULL` address
+    WildWrite,
+    /// A read from an invalid, non-`NULL` address
+    WildRead,
+    /// A fault at an invalid, non-`NULL` address, with an unknown access type
+    WildAccess,
+    /// An access close to `NULL`, see [`NULL_DEREF_THRESHOLD`]
+    NullDereference,
+    /// A fault next to the stack pointer, usually unbounded recursion
+    StackOverflow,
+    /// An illegal instruction, e.g. a trap inserted by the compiler
+    IllegalInstruction,
+    /// An arithmetic error, such as a division by zero
+    ArithmeticError,
+    /// An abort, e.g. a failed assertion, a sanitizer report, or a stack smash detected by the stack protector
+    Abort,
+    /// Any other signal
+    Other,
+}
+
+impl CrashKind {
+    /// A short name, for reports and user stats
+    #[must_use]
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            CrashKind::ControlFlowHijack => "control-flow-hijack",
+            CrashKind::WildWrite => "wild-write",
+            CrashKind::WildRead => "wild-read",
+            CrashKind::WildAccess => "wild-access",
+            CrashKind::NullDereference => "null-deref",
+            CrashKind::StackOverflow => "stack-overflow",
+            CrashKind::IllegalInstruction => "illegal-instruction",
+            CrashKind::ArithmeticError => "arithmetic",
+            CrashKind::Abort => "abort",
+            CrashKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for CrashKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.short_name())
+    }
+}
+
+/// The machine state at a crash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashContext {
+    /// The number of the signal
+    pub signal: i32,
+    /// The faulting address, for memory faults
+    pub fault_addr: Option<usize>,
+    /// The kind of the faulting access
+    pub access: AccessType,
+    /// The program counter of the faulting instruction
+    pub pc: Option<usize>,
+    /// The stack pointer at the crash
+    pub sp: Option<usize>,
+    /// The general purpose registers, by name, if supported on this platform
+    pub registers: Vec<(String, u64)>,
+    /// Memory around the crash, e.g. around the program counter and the stack pointer, if captured
+    #[serde(default)]
+    pub memory: Vec<MemoryWindow>,
+}
+
+/// A window of the memory of the target at a crash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryWindow {
+    /// What the window is around, e.g. `pc`
+    pub label: String,
+    /// The address of the first byte
+    pub addr: usize,
+    /// The content of the memory
+    pub bytes: Vec<u8>,
+}
+
+impl CrashContext {
+    /// Collect the context of a crash from the arguments of a signal handler
+    #[must_use]
+    pub fn from_signal(signal: Signal, info: &siginfo_t, context: Option<&ucontext_t>) -> Self {
+        let signal = signal as i32;
+        let mut crash = Self {
+            signal,
+            fault_addr: fault_addr(signal, info),
+            access: AccessType::Unknown,
+            pc: None,
+            sp: None,
+            registers: Vec::new(),
+            memory: Vec::new(),
+        };
+        if let Some(context) = context {
+            crash.read_ucontext(context);
+        }
+        if crash.access == AccessType::Unknown
+            && crash.fault_addr.is_some()
+            && crash.pc == crash.fault_addr
+        {
+            crash.access = AccessType::Execute;
+        }
+        crash
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[allow(clippy::cast_sign_loss)]
+    fn read_ucontext(&mut self, context: &ucontext_t) {
+        let gregs = &context.uc_mcontext.gregs;
+        let reg = |idx: libc::c_int| gregs[idx as usize] as u64;
+        self.pc = Some(reg(libc::REG_RIP) as usize);
+        self.sp = Some(reg(libc::REG_RSP) as usize);
+        if self.signal == libc::SIGSEGV {
+            // The page fault error code: bit 1 is set for writes, bit 4 for instruction fetches
+            let err = reg(libc::REG_ERR);
+            self.access = if err & 0x10 != 0 {
+                AccessType::Execute
+            } else if err & 0x2 != 0 {
+                AccessType::Write
+            } else {
+                AccessType::Read
+            };
+        }
+        self.registers = [
+            ("rax", libc::REG_RAX),
+            ("rbx", libc::REG_RBX),
+            ("rcx", libc::REG_RCX),
+            ("rdx", libc::REG_RDX),
+            ("rsi", libc::REG_RSI),
+            ("rdi", libc::REG_RDI),
+            ("rbp", libc::REG_RBP),
+            ("rsp", libc::REG_RSP),
+            ("r8", libc::REG_R8),
+            ("r9", libc::REG_R9),
+            ("r10", libc::REG_R10),
+            ("r11", libc::REG_R11),
+            ("r12", libc::REG_R12),
+            ("r13", libc::REG_R13),
+            ("r14", libc::REG_R14),
+            ("r15", libc::REG_R15),
+            ("rip", libc::REG_RIP),
+            ("eflags", libc::REG_EFL),
+        ]
+        .iter()
+        .map(|(name, idx)| ((*name).to_string(), reg(*idx)))
+        .collect();
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+    fn read_ucontext(&mut self, context: &ucontext_t) {
+        let mcontext = &context.uc_mcontext;
+        self.pc = Some(mcontext.pc as usize);
+        self.sp = Some(mcontext.sp as usize);
+        self.registers = aarch64_registers(&mcontext.regs, mcontext.sp, mcontext.pc);
+    }
+
+    #[cfg(not(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )))]
+    #[allow(clippy::unused_self)]
+    fn read_ucontext(&mut self, _context: &ucontext_t) {}
+
+    /// Classify the crash, see [`CrashKind`]
+    #[must_use]
+    pub fn classify(&self) -> CrashKind {
+        match self.signal {
+            libc::SIGSEGV | libc::SIGBUS => {
+                let Some(addr) = self.fault_addr else {
+                    return CrashKind::WildAccess;
+                };
+                if addr < NULL_DEREF_THRESHOLD {
+                    CrashKind::NullDereference
+                } else if self.access == AccessType::Execute || self.pc == Some(addr) {
+                    CrashKind::ControlFlowHijack
+                } else if self
+                    .sp
+                    .is_some_and(|sp| sp.abs_diff(addr) < STACK_OVERFLOW_DISTANCE)
+                {
+                    CrashKind::StackOverflow
+                } else {
+                    match self.access {
+                        AccessType::Write => CrashKind::WildWrite,
+                        AccessType::Read => CrashKind::WildRead,
+                        _ => CrashKind::WildAccess,
+                    }
+                }
+            }
+            libc::SIGILL | libc::SIGTRAP => CrashKind::IllegalInstruction,
+            libc::SIGFPE => CrashKind::ArithmeticError,
+            libc::SIGABRT => CrashKind::Abort,
+            _ => CrashKind::Other,
+        }
+    }
+}
+
+/// The registers of an `aarch64` thread, by name
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+pub(crate) fn aarch64_registers(regs: &[u64; 31], sp: u64, pc: u64) -> Vec<(String, u64)> {
+    regs.iter()
+        .enumerate()
+        .map(|(idx, value)| (format!("x{idx}"), *value))
+        .chain([("sp".to_string(), sp), ("pc".to_string(), pc)])
+        .collect()
+}
+
+/// The faulting address of a memory fault
+pub(crate) fn fault_addr(signal: i32, info: &siginfo_t) -> Option<usize> {
+    if !matches!(
+        signal,
+        libc::SIGSEGV | libc::SIGBUS | libc::SIGILL | libc::SIGFPE
+    ) {
+        return None;
+    }
+    #[cfg(target_os = "android")]
+    let addr = ((info._pad[0] as i64) | ((info._pad[1] as i64) << 32)) as usize;
+    #[cfg(not(target_os = "android"))]
+    // # Safety
+    // The signal is a fault, so `si_addr` is set.
+    let addr = unsafe { info.si_addr() as usize };
+    Some(addr)
+}
+
+/// Store the context of an in-process crash for the [`CrashContextObserver`], called by the crash handler.
+///
+/// # Safety
+/// Must only be called from the crash handler, while no observer is running.
+pub unsafe fn record_crash_context(signal: Signal, info: &siginfo_t, context: Option<&ucontext_t>) {
+    let crash = CrashContext::from_signal(signal, info, context);
+    unsafe {
+        *addr_of_mut!(LAST_CRASH_CONTEXT) = Some(crash);
+    }
+}
+
+/// Store the context of a crash of a traced child, if none was stored in this execution yet.
+///
+/// # Safety
+/// Must only be called during an execution, while no observer is running.
+#[cfg(target_os = "linux")]
+pub(crate) unsafe fn store_first_crash_context(crash: CrashContext) {
+    unsafe {
+        let last = &mut *addr_of_mut!(LAST_CRASH_CONTEXT);
+        if last.is_none() {
+            *last = Some(crash);
+        }
+    }
+}
+
+/// An observer taking the [`CrashContext`] of in-process crashes, recorded by the crash handler,
+/// and of crashes of targets run by the `PtraceExecutor`.
+///
+/// Use a [`crate::feedbacks::CrashContextFeedback`] in the objective to store it in the solutions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashContextObserver {
+    name: Cow<'static, str>,
+    context: Option<CrashContext>,
+}
+
+impl CrashContextObserver {
+    /// Creates a new [`CrashContextObserver`] with the given name
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            context: None,
+        }
+    }
+
+    /// The context of the last crash, `None` if the last execution did not crash
+    #[must_use]
+    pub fn context(&self) -> Option<&CrashContext> {
+        self.context.as_ref()
+    }
+
+    /// Sets the context of the last crash, for executors collecting it themselves, e.g. from an emulator.
+    /// The `post_exec` of this observer keeps it, unless the crash handler recorded a context as well.
+    pub fn set_context(&mut self, context: Option<CrashContext>) {
+        self.context = context;
+    }
+}
+
+impl<I, S> Observer<I, S> for CrashContextObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.context = None;
+        // # Safety
+        // The crash handler only writes the context during an execution.
+        unsafe {
+            *addr_of_mut!(LAST_CRASH_CONTEXT) = None;
+        }
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        if *exit_kind == ExitKind::Crash {
+            // # Safety
+            // The execution ended, the crash handler does not write the context concurrently.
+            let recorded = unsafe { (*addr_of_mut!(LAST_CRASH_CONTEXT)).take() };
+            // Keep the context set by the executor, or taken by an earlier call for this execution
+            if recorded.is_some() {
+                self.context = recorded;
+            }
+        }
+        Ok(())
+    }
+
+    fn pre_exec_child(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.pre_exec(state, input)
+    }
+
+    fn post_exec_child(
+        &mut self,
+        state: &mut S,
+        input: &I,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.post_exec(state, input, exit_kind)
+    }
+}
+
+impl Named for CrashContextObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::ptr::addr_of_mut;
+
+    use super::{AccessType, CrashContext, CrashContextObserver, CrashKind, LAST_CRASH_CONTEXT};
+    use crate::{executors::ExitKind, observers::Observer};
+
+    fn segv(fault_addr: usize, access: AccessType, pc: usize) -> CrashContext {
+        CrashContext {
+            signal: libc::SIGSEGV,
+            fault_addr: Some(fault_addr),
+            access,
+            pc: Some(pc),
+            sp: Some(0x7fff_0000_0000),
+            registers: Vec::new(),
+            memory: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_post_exec_keeps_context() {
+        let mut observer = CrashContextObserver::new("crash_context");
+        Observer::<(), ()>::pre_exec(&mut observer, &mut (), &()).unwrap();
+        // # Safety
+        // No crash handler runs in this test.
+        unsafe {
+            *addr_of_mut!(LAST_CRASH_CONTEXT) = Some(segv(0x8, AccessType::Read, 0x40_1000));
+        }
+        Observer::<(), ()>::post_exec_child(&mut observer, &mut (), &(), &ExitKind::Crash).unwrap();
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Crash).unwrap();
+        assert_eq!(observer.context().unwrap().fault_addr, Some(0x8));
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            segv(0x8, AccessType::Read, 0x40_1000).classify(),
+            CrashKind::NullDereference
+        );
+        assert_eq!(
+            segv(0x4141_4141, AccessType::Write, 0x40_1000).classify(),
+            CrashKind::WildWrite
+        );
+        assert_eq!(
+            segv(0x4141_4141, AccessType::Unknown, 0x4141_4141).classify(),
+            CrashKind::ControlFlowHijack
+        );
+        assert_eq!(
+            segv(0x7fff_0000_0000 - 0x100, AccessType::Write, 0x40_1000).classify(),
+            CrashKind::StackOverflow
+        );
+    }
+}
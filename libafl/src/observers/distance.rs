@@ -0,0 +1,116 @@
+//! The [`DistanceObserver`] reports how close an execution came to the target sites of a directed campaign.
+//!
+//! With the `DirectedDistance` pass of `libafl_cc`, each basic block that can reach a target site adds its static
+//! distance to it to a global sum, and increments a global counter, as in `AFLGo`. The distance of an execution is
+//! the mean over the executed basic blocks. `libafl_targets` defines the two globals, with its `directed` feature.
+
+use alloc::{borrow::Cow, boxed::Box};
+
+use libafl_bolts::{ownedref::OwnedMutPtr, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::{executors::ExitKind, observers::Observer, Error};
+
+/// Observes the mean distance of the executed basic blocks to the target sites
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::unsafe_derive_deserialize)]
+pub struct DistanceObserver {
+    name: Cow<'static, str>,
+    sum: OwnedMutPtr<u64>,
+    count: OwnedMutPtr<u64>,
+    last_distance: Option<f64>,
+}
+
+impl DistanceObserver {
+    /// Creates a new [`DistanceObserver`], reading the sum of the distances and the number of basic blocks
+    /// from the given counters, which the target increments
+    #[must_use]
+    pub fn new<S>(name: S, sum: OwnedMutPtr<u64>, count: OwnedMutPtr<u64>) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            sum,
+            count,
+            last_distance: None,
+        }
+    }
+
+    /// Creates a new [`DistanceObserver`] with its own counters, for harnesses reporting the distances
+    /// themselves, with [`DistanceObserver::report`]
+    #[must_use]
+    pub fn owned<S>(name: S) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        Self::new(
+            name,
+            OwnedMutPtr::Owned(Box::new(0)),
+            OwnedMutPtr::Owned(Box::new(0)),
+        )
+    }
+
+    /// Report the distance of an executed basic block to the target sites
+    pub fn report(&mut self, distance: u64) {
+        *self.sum.as_mut() = self.sum.as_ref().saturating_add(distance);
+        *self.count.as_mut() += 1;
+    }
+
+    /// The mean distance of the basic blocks of the last execution, or `None` if it executed none that
+    /// can reach a target site
+    #[must_use]
+    pub fn last_distance(&self) -> Option<f64> {
+        self.last_distance
+    }
+}
+
+impl Named for DistanceObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for DistanceObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        *self.sum.as_mut() = 0;
+        *self.count.as_mut() = 0;
+        self.last_distance = None;
+        Ok(())
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        let count = *self.count.as_ref();
+        self.last_distance = (count > 0).then(|| *self.sum.as_ref() as f64 / count as f64);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DistanceObserver;
+    use crate::{executors::ExitKind, inputs::NopInput, observers::Observer};
+
+    #[test]
+    fn test_distance_observer() {
+        let mut observer = DistanceObserver::owned("distance");
+        let mut state = ();
+        Observer::<NopInput, ()>::pre_exec(&mut observer, &mut state, &NopInput {}).unwrap();
+        Observer::<NopInput, ()>::post_exec(&mut observer, &mut state, &NopInput {}, &ExitKind::Ok)
+            .unwrap();
+        assert_eq!(observer.last_distance(), None);
+
+        Observer::<NopInput, ()>::pre_exec(&mut observer, &mut state, &NopInput {}).unwrap();
+        observer.report(2);
+        observer.report(7);
+        Observer::<NopInput, ()>::post_exec(&mut observer, &mut state, &NopInput {}, &ExitKind::Ok)
+            .unwrap();
+        assert!((observer.last_distance().unwrap() - 4.5).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,144 @@
+//! The [`ExecStatsObserver`] exposes the resource usage of each execution: wall time, CPU time, page faults and
+//! context switches.
+//!
+//! The numbers are measured by the [`crate::executors::hooks::exec_stats::ExecStatsHook`], right around the
+//! harness, and handed to the observer through a thread-local. Feedbacks read them from the observer, and the
+//! [`crate::feedbacks::ExecStatsFeedback`] reports their averages to the monitors.
+
+use alloc::borrow::Cow;
+use core::{cell::Cell, time::Duration};
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{executors::ExitKind, observers::Observer, Error};
+
+/// The resource usage of one execution of the harness
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExecStats {
+    /// The elapsed wall clock time
+    pub wall_time: Duration,
+    /// The CPU time spent in user mode
+    pub user_time: Duration,
+    /// The CPU time spent in the kernel
+    pub system_time: Duration,
+    /// The page faults served without I/O
+    pub minor_faults: u64,
+    /// The page faults which required I/O
+    pub major_faults: u64,
+    /// The context switches because the target waited for a resource
+    pub voluntary_switches: u64,
+    /// The context switches because the time slice of the target ran out
+    pub involuntary_switches: u64,
+}
+
+impl ExecStats {
+    /// The CPU time spent in user mode and in the kernel
+    #[must_use]
+    pub fn cpu_time(&self) -> Duration {
+        self.user_time + self.system_time
+    }
+
+    /// The page faults, minor and major
+    #[must_use]
+    pub fn page_faults(&self) -> u64 {
+        self.minor_faults + self.major_faults
+    }
+
+    /// The context switches, voluntary and involuntary
+    #[must_use]
+    pub fn context_switches(&self) -> u64 {
+        self.voluntary_switches + self.involuntary_switches
+    }
+}
+
+std::thread_local! {
+    /// The stats of the current execution, measured by the executor hook
+    static CURRENT_EXEC_STATS: Cell<Option<ExecStats>> = const { Cell::new(None) };
+}
+
+/// Record the stats of the current execution, for the [`ExecStatsObserver`] to pick up
+pub fn record_exec_stats(stats: ExecStats) {
+    CURRENT_EXEC_STATS.with(|current| current.set(Some(stats)));
+}
+
+/// An observer for the [`ExecStats`] of each execution.
+///
+/// The stats are only available if an [`crate::executors::hooks::exec_stats::ExecStatsHook`] is registered on
+/// the executor, and it runs the harness on the thread of the fuzzer, like the in-process executors.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecStatsObserver {
+    name: Cow<'static, str>,
+    last_stats: Option<ExecStats>,
+}
+
+impl ExecStatsObserver {
+    /// Creates a new [`ExecStatsObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            last_stats: None,
+        }
+    }
+
+    /// The stats of the last execution, if they were measured
+    #[must_use]
+    pub fn last_stats(&self) -> Option<&ExecStats> {
+        self.last_stats.as_ref()
+    }
+}
+
+impl Named for ExecStatsObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for ExecStatsObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last_stats = None;
+        CURRENT_EXEC_STATS.with(|current| current.set(None));
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.last_stats = CURRENT_EXEC_STATS.with(Cell::take);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{record_exec_stats, ExecStats, ExecStatsObserver};
+    use crate::{executors::ExitKind, observers::Observer};
+
+    #[test]
+    fn test_exec_stats_observer() {
+        let mut observer = ExecStatsObserver::new("exec_stats");
+        let stats = ExecStats {
+            user_time: Duration::from_millis(2),
+            system_time: Duration::from_millis(1),
+            minor_faults: 5,
+            ..ExecStats::default()
+        };
+        record_exec_stats(stats);
+        Observer::<(), ()>::pre_exec(&mut observer, &mut (), &()).unwrap();
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Ok).unwrap();
+        assert_eq!(observer.last_stats(), None);
+
+        Observer::<(), ()>::pre_exec(&mut observer, &mut (), &()).unwrap();
+        record_exec_stats(stats);
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Ok).unwrap();
+        let last = observer.last_stats().unwrap();
+        assert_eq!(last.cpu_time(), Duration::from_millis(3));
+        assert_eq!(last.page_faults(), 5);
+    }
+}
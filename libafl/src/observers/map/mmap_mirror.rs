@@ -0,0 +1,487 @@
+//! A map observer mirroring its map into a memory-mapped file, for live and post-mortem coverage analysis.
+//!
+//! External tools can map the same file to watch the coverage of a client while it is fuzzing.
+//! The file is a shared mapping, so its pages are written back by the kernel even if the client crashes.
+//! To only mirror the maps of inputs added to the corpus, use [`MirrorMode::Interesting`] together with
+//! the [`MmapMirrorFeedback`].
+use alloc::{borrow::Cow, vec::Vec};
+use core::{
+    hash::{Hash, Hasher},
+    mem::size_of,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+};
+use std::{fs::OpenOptions, os::unix::io::AsRawFd, path::PathBuf};
+
+use libafl_bolts::{
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    AsSlice, AsSliceMut, HasLen, Named,
+};
+use libc::{mmap, munmap, MAP_FAILED, MAP_SHARED, PROT_READ, PROT_WRITE};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::{map::MapObserver, Observer},
+    Error,
+};
+
+/// When a [`MmapMirrorMapObserver`] writes the map to its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MirrorMode {
+    /// Copy the map after each execution, the file holds the map of the last execution
+    EveryExec,
+    /// Only write entries greater than the ones in the file, the file holds the coverage of all executions.
+    ///
+    /// Pages are only touched for inputs covering new entries, and the coverage of previous runs of the
+    /// client is kept if the file exists. This assumes the initial value of the map is its minimum.
+    Cumulative,
+    /// Copy the map of each input added to the corpus, the file holds the map of the last interesting input.
+    ///
+    /// The observer does not write the file itself, add a [`MmapMirrorFeedback`] for it after the feedbacks
+    /// deciding about the input.
+    Interesting,
+}
+
+/// A memory-mapped file, mapped lazily, so that it can be serialized and cloned with the observer
+#[derive(Debug, Serialize, Deserialize)]
+struct MirrorFile {
+    path: PathBuf,
+    #[serde(skip)]
+    mapping: Option<(NonNull<u8>, usize)>,
+}
+
+impl MirrorFile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            mapping: None,
+        }
+    }
+
+    /// The file as a slice of `len` entries, (re-)mapping it if needed
+    fn entries_mut<T>(&mut self, len: usize) -> Result<&mut [T], Error> {
+        let size = len * size_of::<T>();
+        if !matches!(self.mapping, Some((_, mapped)) if mapped == size) {
+            self.unmap();
+            if size == 0 {
+                return Ok(&mut []);
+            }
+
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&self.path)?;
+            file.set_len(size as u64)?;
+            // # Safety
+            // We map a file we just opened and resized, the mapping stays valid after closing the file.
+            let map = unsafe {
+                mmap(
+                    ptr::null_mut(),
+                    size,
+                    PROT_READ | PROT_WRITE,
+                    MAP_SHARED,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if map == MAP_FAILED || map.is_null() {
+                return Err(Error::last_os_error(format!(
+                    "Failed to mmap the map mirror {}",
+                    self.path.display()
+                )));
+            }
+            self.mapping = Some((NonNull::new(map.cast()).unwrap(), size));
+        }
+        let (map, _) = self.mapping.unwrap();
+        // # Safety
+        // The mapping is page-aligned and `size` bytes long.
+        Ok(unsafe { slice::from_raw_parts_mut(map.as_ptr().cast(), len) })
+    }
+
+    /// Write `map` to the file, merging it with the entries in the file for [`MirrorMode::Cumulative`]
+    fn write<T>(&mut self, map: &[T], mode: MirrorMode) -> Result<(), Error>
+    where
+        T: Copy + PartialOrd,
+    {
+        let mirror = self.entries_mut::<T>(map.len())?;
+        match mode {
+            MirrorMode::EveryExec | MirrorMode::Interesting => mirror.copy_from_slice(map),
+            MirrorMode::Cumulative => {
+                for (dst, src) in mirror.iter_mut().zip(map.iter()) {
+                    if *src > *dst {
+                        *dst = *src;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn unmap(&mut self) {
+        if let Some((map, size)) = self.mapping.take() {
+            // # Safety
+            // The mapping was created by us with this size, and is not borrowed anymore.
+            unsafe {
+                munmap(map.as_ptr().cast(), size);
+            }
+        }
+    }
+}
+
+impl Clone for MirrorFile {
+    fn clone(&self) -> Self {
+        Self::new(self.path.clone())
+    }
+}
+
+impl Hash for MirrorFile {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+    }
+}
+
+impl Drop for MirrorFile {
+    fn drop(&mut self) {
+        self.unmap();
+    }
+}
+
+/// Map observer mirroring the map of the wrapped observer into a memory-mapped file after each execution,
+/// or for each interesting input with a [`MmapMirrorFeedback`], see [`MirrorMode`].
+///
+/// Use one file per client, e.g. named after the core, since clients would overwrite each other's map.
+/// The file holds the raw entries of the map, in native endianness. Wrap the observer after postprocessing,
+/// e.g. after [`super::HitcountsMapObserver`], to mirror the postprocessed map.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash)]
+pub struct MmapMirrorMapObserver<M> {
+    base: M,
+    file: MirrorFile,
+    mode: MirrorMode,
+}
+
+impl<M> MmapMirrorMapObserver<M> {
+    /// Creates a new [`MmapMirrorMapObserver`], mirroring the map of `base` into the file at `path`.
+    ///
+    /// The file is created, or resized, on the first execution.
+    pub fn new<P>(base: M, path: P, mode: MirrorMode) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            base,
+            file: MirrorFile::new(path.into()),
+            mode,
+        }
+    }
+
+    /// The file the map is mirrored into
+    #[must_use]
+    pub fn path(&self) -> &PathBuf {
+        &self.file.path
+    }
+
+    /// When the map is written to the file
+    #[must_use]
+    pub fn mode(&self) -> MirrorMode {
+        self.mode
+    }
+}
+
+impl<M> MmapMirrorMapObserver<M>
+where
+    M: MapObserver + for<'a> AsSlice<'a, Entry = <M as MapObserver>::Entry>,
+    <M as MapObserver>::Entry: PartialOrd,
+{
+    /// Write the current map of the wrapped observer to the file
+    pub fn mirror(&mut self) -> Result<(), Error> {
+        let map = self.base.as_slice();
+        self.file
+            .write::<<M as MapObserver>::Entry>(&map, self.mode)
+    }
+}
+
+impl<M> Deref for MmapMirrorMapObserver<M> {
+    type Target = M;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<M> DerefMut for MmapMirrorMapObserver<M> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl<I, S, M> Observer<I, S> for MmapMirrorMapObserver<M>
+where
+    M: MapObserver + Observer<I, S> + for<'a> AsSlice<'a, Entry = <M as MapObserver>::Entry>,
+    <M as MapObserver>::Entry: PartialOrd,
+{
+    #[inline]
+    fn pre_exec(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.base.pre_exec(state, input)
+    }
+
+    #[inline]
+    fn post_exec(&mut self, state: &mut S, input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        self.base.post_exec(state, input, exit_kind)?;
+        if self.mode == MirrorMode::Interesting {
+            Ok(())
+        } else {
+            self.mirror()
+        }
+    }
+}
+
+impl<M> Named for MmapMirrorMapObserver<M>
+where
+    M: Named,
+{
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.base.name()
+    }
+}
+
+impl<M> HasLen for MmapMirrorMapObserver<M>
+where
+    M: HasLen,
+{
+    #[inline]
+    fn len(&self) -> usize {
+        self.base.len()
+    }
+}
+
+impl<M> AsRef<Self> for MmapMirrorMapObserver<M> {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl<M> AsMut<Self> for MmapMirrorMapObserver<M> {
+    fn as_mut(&mut self) -> &mut Self {
+        self
+    }
+}
+
+impl<M> MapObserver for MmapMirrorMapObserver<M>
+where
+    M: MapObserver,
+{
+    type Entry = M::Entry;
+
+    #[inline]
+    fn initial(&self) -> M::Entry {
+        self.base.initial()
+    }
+
+    #[inline]
+    fn usable_count(&self) -> usize {
+        self.base.usable_count()
+    }
+
+    #[inline]
+    fn get(&self, idx: usize) -> M::Entry {
+        self.base.get(idx)
+    }
+
+    #[inline]
+    fn set(&mut self, idx: usize, val: M::Entry) {
+        self.base.set(idx, val);
+    }
+
+    fn count_bytes(&self) -> u64 {
+        self.base.count_bytes()
+    }
+
+    #[inline]
+    fn reset_map(&mut self) -> Result<(), Error> {
+        self.base.reset_map()
+    }
+
+    #[inline]
+    fn hash_simple(&self) -> u64 {
+        self.base.hash_simple()
+    }
+
+    fn to_vec(&self) -> Vec<M::Entry> {
+        self.base.to_vec()
+    }
+
+    fn how_many_set(&self, indexes: &[usize]) -> usize {
+        self.base.how_many_set(indexes)
+    }
+}
+
+impl<'a, M> AsSlice<'a> for MmapMirrorMapObserver<M>
+where
+    M: AsSlice<'a>,
+{
+    type Entry = <M as AsSlice<'a>>::Entry;
+    type SliceRef = <M as AsSlice<'a>>::SliceRef;
+
+    #[inline]
+    fn as_slice(&'a self) -> Self::SliceRef {
+        self.base.as_slice()
+    }
+}
+
+impl<'a, M> AsSliceMut<'a> for MmapMirrorMapObserver<M>
+where
+    M: AsSliceMut<'a>,
+{
+    type SliceRefMut = <M as AsSliceMut<'a>>::SliceRefMut;
+
+    #[inline]
+    fn as_slice_mut(&'a mut self) -> Self::SliceRefMut {
+        self.base.as_slice_mut()
+    }
+}
+
+/// Mirrors the map of a [`MmapMirrorMapObserver`] in [`MirrorMode::Interesting`] into its file, for each input
+/// added to the corpus.
+///
+/// It is never interesting by itself. Combine it with the other feedbacks, e.g.
+/// `feedback_or!(map_feedback, MmapMirrorFeedback::new(&observer))`, so it sees the inputs they keep.
+#[derive(Debug, Clone)]
+pub struct MmapMirrorFeedback<M> {
+    observer_handle: Handle<MmapMirrorMapObserver<M>>,
+    file: Option<MirrorFile>,
+}
+
+impl<M> MmapMirrorFeedback<M>
+where
+    M: Named,
+{
+    /// Creates a new [`MmapMirrorFeedback`], writing the map of `observer` to its file
+    #[must_use]
+    pub fn new(observer: &MmapMirrorMapObserver<M>) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            file: None,
+        }
+    }
+}
+
+impl<M> Named for MmapMirrorFeedback<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        self.observer_handle.name()
+    }
+}
+
+impl<M, S> StateInitializer<S> for MmapMirrorFeedback<M> {}
+
+impl<EM, I, M, OT, S> Feedback<EM, I, OT, S> for MmapMirrorFeedback<M>
+where
+    M: MapObserver + for<'a> AsSlice<'a, Entry = <M as MapObserver>::Entry>,
+    <M as MapObserver>::Entry: PartialOrd,
+    OT: MatchName,
+{
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        _testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .ok_or_else(|| Error::key_not_found("MmapMirrorMapObserver not found"))?;
+        let map = observer.base.as_slice();
+        self.file
+            .get_or_insert_with(|| MirrorFile::new(observer.path().clone()))
+            .write::<<M as MapObserver>::Entry>(&map, MirrorMode::Interesting)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use libafl_bolts::tuples::tuple_list;
+
+    use crate::{
+        corpus::Testcase,
+        executors::ExitKind,
+        feedbacks::Feedback,
+        inputs::BytesInput,
+        observers::{
+            map::{
+                MapObserver, MirrorMode, MmapMirrorFeedback, MmapMirrorMapObserver,
+                OwnedMapObserver,
+            },
+            Observer,
+        },
+    };
+
+    #[test]
+    fn test_mmap_mirror() {
+        let path = env::temp_dir().join(format!("libafl_mirror_test_{}", process::id()));
+        let mut observer = MmapMirrorMapObserver::new(
+            OwnedMapObserver::new("map", vec![0_u8; 4]),
+            &path,
+            MirrorMode::Cumulative,
+        );
+        observer.set(1, 3);
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Ok).unwrap();
+        observer.reset_map().unwrap();
+        observer.set(2, 1);
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Ok).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), vec![0, 3, 1, 0]);
+
+        let mut observer = MmapMirrorMapObserver::new(
+            OwnedMapObserver::new("map", vec![0_u8; 4]),
+            &path,
+            MirrorMode::EveryExec,
+        );
+        observer.set(0, 2);
+        observer.mirror().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), vec![2, 0, 0, 0]);
+        drop(observer);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_mmap_mirror_interesting() {
+        let path = env::temp_dir().join(format!("libafl_mirror_interesting_{}", process::id()));
+        let observer = MmapMirrorMapObserver::new(
+            OwnedMapObserver::new("map", vec![0_u8; 4]),
+            &path,
+            MirrorMode::Interesting,
+        );
+        let mut feedback = MmapMirrorFeedback::new(&observer);
+        let mut observers = tuple_list!(observer);
+
+        // The observer leaves the file alone
+        observers.0.set(3, 1);
+        Observer::<(), ()>::post_exec(&mut observers.0, &mut (), &(), &ExitKind::Ok).unwrap();
+        assert!(!path.exists());
+
+        let mut testcase = Testcase::new(BytesInput::new(vec![]));
+        Feedback::<(), BytesInput, _, ()>::append_metadata(
+            &mut feedback,
+            &mut (),
+            &mut (),
+            &observers,
+            &mut testcase,
+        )
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), vec![0, 0, 0, 1]);
+        drop(feedback);
+        fs::remove_file(path).unwrap();
+    }
+}
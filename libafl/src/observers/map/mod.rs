@@ -32,6 +32,11 @@ pub use multi_map::*;
 pub mod owned_map;
 pub use owned_map::*;
 
+#[cfg(all(unix, feature = "std"))]
+pub mod mmap_mirror;
+#[cfg(all(unix, feature = "std"))]
+pub use mmap_mirror::*;
+
 /// A trait indicating tracking of observed map values after testcase execution
 ///
 /// Trait marker which indicates that this [`MapObserver`] is tracked for indices or novelties.
@@ -0,0 +1,132 @@
+//! The [`MetricsObserver`] collects named numeric values the harness reports during an execution.
+//!
+//! The harness calls [`report_metric`] (or [`add_to_metric`] / [`max_metric`]) from anywhere in the target,
+//! e.g. with the number of bytes parsed or the number of states visited.
+//! The values are buffered thread-locally and picked up by the observer after each run, so they can guide the
+//! fuzzer through a [`crate::feedbacks::MetricFeedback`] without writing a custom observer for each metric.
+
+use alloc::borrow::Cow;
+use core::cell::RefCell;
+
+use hashbrown::HashMap;
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{executors::ExitKind, observers::Observer, Error};
+
+std::thread_local! {
+    /// The metrics reported by the harness during the current execution
+    static CURRENT_METRICS: RefCell<HashMap<&'static str, i64>> = RefCell::new(HashMap::new());
+}
+
+/// Report the value of a metric for the current execution, overwriting earlier reports
+pub fn report_metric(name: &'static str, value: i64) {
+    CURRENT_METRICS.with(|metrics| {
+        metrics.borrow_mut().insert(name, value);
+    });
+}
+
+/// Add `delta` to a metric of the current execution, starting at `0`
+pub fn add_to_metric(name: &'static str, delta: i64) {
+    CURRENT_METRICS.with(|metrics| {
+        let mut metrics = metrics.borrow_mut();
+        let value = metrics.entry(name).or_insert(0);
+        *value = value.saturating_add(delta);
+    });
+}
+
+/// Report a value for a metric of the current execution, keeping the largest value reported
+pub fn max_metric(name: &'static str, value: i64) {
+    CURRENT_METRICS.with(|metrics| {
+        let mut metrics = metrics.borrow_mut();
+        let current = metrics.entry(name).or_insert(value);
+        *current = (*current).max(value);
+    });
+}
+
+/// An observer collecting the metrics reported by the harness via [`report_metric`] and friends.
+///
+/// Only metrics reported on the thread executing the target are seen, so this works with in-process executors.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetricsObserver {
+    name: Cow<'static, str>,
+    values: HashMap<Cow<'static, str>, i64>,
+}
+
+impl MetricsObserver {
+    /// Creates a new [`MetricsObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            values: HashMap::new(),
+        }
+    }
+
+    /// The value of the metric with the given name in the last run, if it was reported
+    #[must_use]
+    pub fn get(&self, metric: &str) -> Option<i64> {
+        self.values.get(metric).copied()
+    }
+
+    /// All metrics reported in the last run
+    #[must_use]
+    pub fn values(&self) -> &HashMap<Cow<'static, str>, i64> {
+        &self.values
+    }
+}
+
+impl Named for MetricsObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for MetricsObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.values.clear();
+        CURRENT_METRICS.with(|metrics| metrics.borrow_mut().clear());
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        CURRENT_METRICS.with(|metrics| {
+            self.values.extend(
+                metrics
+                    .borrow_mut()
+                    .drain()
+                    .map(|(name, value)| (Cow::Borrowed(name), value)),
+            );
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_to_metric, max_metric, report_metric, MetricsObserver};
+    use crate::{executors::ExitKind, observers::Observer};
+
+    #[test]
+    fn test_metrics_observer() {
+        let mut observer = MetricsObserver::new("metrics");
+        Observer::<(), ()>::pre_exec(&mut observer, &mut (), &()).unwrap();
+        report_metric("parsed", 3);
+        report_metric("parsed", 7);
+        add_to_metric("states", 2);
+        add_to_metric("states", 2);
+        max_metric("depth", 5);
+        max_metric("depth", 1);
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Ok).unwrap();
+
+        assert_eq!(observer.get("parsed"), Some(7));
+        assert_eq!(observer.get("states"), Some(4));
+        assert_eq!(observer.get("depth"), Some(5));
+        assert_eq!(observer.get("missing"), None);
+    }
+}
@@ -7,7 +7,24 @@ pub use cmp::*;
 #[cfg(feature = "std")]
 pub mod stdio;
 #[cfg(feature = "std")]
-pub use stdio::{StdErrObserver, StdOutObserver};
+pub use stdio::{ExitStatusObserver, StdErrObserver, StdOutObserver};
+
+/// Harness-reported metrics observer
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub use metrics::{add_to_metric, max_metric, report_metric, MetricsObserver};
+
+/// Per-execution resource usage observer
+#[cfg(feature = "std")]
+pub mod exec_stats;
+#[cfg(feature = "std")]
+pub use exec_stats::{record_exec_stats, ExecStats, ExecStatsObserver};
+
+#[cfg(all(unix, feature = "std"))]
+pub mod crash_context;
+#[cfg(all(unix, feature = "std"))]
+pub use crash_context::{AccessType, CrashContext, CrashContextObserver, CrashKind, MemoryWindow};
 
 #[cfg(feature = "regex")]
 pub mod stacktrace;
@@ -21,11 +38,16 @@ pub mod profiling;
 pub use profiling::*;
 
 pub mod concolic;
+pub mod distance;
+pub use distance::DistanceObserver;
 pub mod map;
 pub use map::*;
 
 pub mod value;
 
+pub mod response;
+pub use response::{ResponseCodeObserver, ResponseCodeParser};
+
 /// List observer
 pub mod list;
 use core::{fmt::Debug, time::Duration};
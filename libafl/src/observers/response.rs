@@ -0,0 +1,252 @@
+//! The [`ResponseCodeObserver`] turns the response codes of a server into coverage.
+//!
+//! The code of each response is parsed by a [`ResponseCodeParser`], and each transition between two
+//! consecutive codes is counted in a map, like an edge between two basic blocks. Use it with a
+//! [`crate::feedbacks::MaxMapFeedback`], so inputs driving the server into new protocol states are
+//! kept, even if they do not cover new code.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{
+    hash::Hash,
+    ops::{Deref, DerefMut},
+};
+
+use libafl_bolts::{HasLen, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    executors::ExitKind,
+    observers::{MapObserver, Observer, StdMapObserver},
+    Error,
+};
+
+/// How to get the code out of a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResponseCodeParser {
+    /// The first run of exactly three ASCII digits in the first line, as in FTP, SMTP, HTTP, RTSP or SIP
+    StatusDigits,
+    /// A big endian integer of `len` bytes (up to 4) at `offset`, for binary protocols
+    BigEndian {
+        /// The offset of the code in the response
+        offset: usize,
+        /// The size of the code in bytes
+        len: usize,
+    },
+}
+
+impl ResponseCodeParser {
+    /// Parse the code of the `response`, if it has one
+    #[must_use]
+    pub fn parse(&self, response: &[u8]) -> Option<u32> {
+        match self {
+            ResponseCodeParser::StatusDigits => {
+                let line = response.split(|b| *b == b'\n').next()?;
+                line.split(|b| !b.is_ascii_digit())
+                    .find(|digits| digits.len() == 3)
+                    .map(|digits| {
+                        digits
+                            .iter()
+                            .fold(0, |code, digit| code * 10 + u32::from(digit - b'0'))
+                    })
+            }
+            ResponseCodeParser::BigEndian { offset, len } => {
+                let bytes = response.get(*offset..offset.checked_add((*len).min(4))?)?;
+                Some(
+                    bytes
+                        .iter()
+                        .fold(0, |code, byte| (code << 8) | u32::from(*byte)),
+                )
+            }
+        }
+    }
+}
+
+/// Maps the transitions between the response codes of a server into a coverage map.
+///
+/// The executor passes the responses to [`ResponseCodeObserver::observe_responses`], e.g. the
+/// [`crate::executors::network::NetworkExecutor`] if set up with
+/// [`crate::executors::network::NetworkExecutorBuilder::response_observer`].
+/// As it is a [`MapObserver`] itself, it works with all map feedbacks and stages.
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+pub struct ResponseCodeObserver {
+    map: StdMapObserver<'static, u8, false>,
+    parser: ResponseCodeParser,
+    codes: Vec<u32>,
+}
+
+impl ResponseCodeObserver {
+    /// Creates a new [`ResponseCodeObserver`] with a map of `map_size` transitions
+    #[must_use]
+    pub fn new<S>(name: S, parser: ResponseCodeParser, map_size: usize) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        Self {
+            map: StdMapObserver::owned(name, vec![0; map_size]),
+            parser,
+            codes: Vec::new(),
+        }
+    }
+
+    /// The codes of the responses of the last execution, in order
+    #[must_use]
+    pub fn codes(&self) -> &[u32] {
+        &self.codes
+    }
+
+    /// React to the responses of the server, counting each transition between two consecutive codes
+    pub fn observe_responses<R>(&mut self, responses: &[R])
+    where
+        R: AsRef<[u8]>,
+    {
+        self.codes = responses
+            .iter()
+            .filter_map(|response| self.parser.parse(response.as_ref()))
+            .collect();
+
+        let map_size = self.map.len();
+        if map_size == 0 {
+            return;
+        }
+        // Like AFL edges, the previous location is shifted, so A -> B and B -> A differ
+        let mut prev = 0;
+        for code in &self.codes {
+            let cur = code.wrapping_mul(0x9e37_79b1);
+            let idx = (cur ^ (prev >> 1)) as usize % map_size;
+            let hits = self.map.get(idx);
+            self.map.set(idx, hits.saturating_add(1));
+            prev = cur;
+        }
+    }
+}
+
+impl Named for ResponseCodeObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        self.map.name()
+    }
+}
+
+impl<I, S> Observer<I, S> for ResponseCodeObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.codes.clear();
+        self.map.reset_map()
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl HasLen for ResponseCodeObserver {
+    #[inline]
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+impl AsRef<Self> for ResponseCodeObserver {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl AsMut<Self> for ResponseCodeObserver {
+    fn as_mut(&mut self) -> &mut Self {
+        self
+    }
+}
+
+impl Deref for ResponseCodeObserver {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+impl DerefMut for ResponseCodeObserver {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.map
+    }
+}
+
+impl MapObserver for ResponseCodeObserver {
+    type Entry = u8;
+
+    #[inline]
+    fn initial(&self) -> u8 {
+        self.map.initial()
+    }
+
+    #[inline]
+    fn usable_count(&self) -> usize {
+        self.map.usable_count()
+    }
+
+    #[inline]
+    fn get(&self, idx: usize) -> u8 {
+        self.map.get(idx)
+    }
+
+    #[inline]
+    fn set(&mut self, idx: usize, val: u8) {
+        self.map.set(idx, val);
+    }
+
+    fn count_bytes(&self) -> u64 {
+        self.map.count_bytes()
+    }
+
+    #[inline]
+    fn reset_map(&mut self) -> Result<(), Error> {
+        self.map.reset_map()
+    }
+
+    #[inline]
+    fn hash_simple(&self) -> u64 {
+        self.map.hash_simple()
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        self.map.to_vec()
+    }
+
+    fn how_many_set(&self, indexes: &[usize]) -> usize {
+        self.map.how_many_set(indexes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ResponseCodeObserver, ResponseCodeParser};
+    use crate::observers::MapObserver;
+
+    #[test]
+    fn test_response_codes() {
+        let parser = ResponseCodeParser::StatusDigits;
+        assert_eq!(parser.parse(b"220 FTP server ready\r\n"), Some(220));
+        assert_eq!(parser.parse(b"HTTP/1.1 404 Not Found\r\n"), Some(404));
+        assert_eq!(parser.parse(b"hello\r\n200 later"), None);
+
+        let parser = ResponseCodeParser::BigEndian { offset: 2, len: 2 };
+        assert_eq!(parser.parse(&[0, 0, 0x01, 0x02]), Some(0x0102));
+        assert_eq!(parser.parse(&[0, 0, 0x01]), None);
+
+        let mut observer =
+            ResponseCodeObserver::new("responses", ResponseCodeParser::StatusDigits, 1024);
+        observer.observe_responses(&[b"220 ready".as_slice(), b"331 password", b"230 logged in"]);
+        assert_eq!(observer.codes(), &[220, 331, 230]);
+        assert_eq!(observer.count_bytes(), 3);
+
+        // The same codes in a different order are different transitions
+        let mut reordered =
+            ResponseCodeObserver::new("reordered", ResponseCodeParser::StatusDigits, 1024);
+        reordered.observe_responses(&[b"220 ready".as_slice(), b"230 logged in", b"331 password"]);
+        assert_ne!(observer.to_vec(), reordered.to_vec());
+    }
+}
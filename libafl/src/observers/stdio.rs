@@ -1,11 +1,12 @@
-//! Observers for `stdout` and `stderr`
+//! Observers for `stdout`, `stderr`, and the exit status of a program
 //!
-//! The [`StdOutObserver`] and [`StdErrObserver`] observers look at the stdout of a program
+//! The [`StdOutObserver`] and [`StdErrObserver`] observers look at the stdout of a program,
+//! the [`ExitStatusObserver`] at how it exited.
 //! The executor must explicitly support these observers.
 //! For example, they are supported on the [`crate::executors::CommandExecutor`].
 
 use alloc::borrow::Cow;
-use std::vec::Vec;
+use std::{process::ExitStatus, vec::Vec};
 
 use libafl_bolts::Named;
 use serde::{Deserialize, Serialize};
@@ -257,3 +258,59 @@ impl<I, S> Observer<I, S> for StdErrObserver {
         Ok(())
     }
 }
+
+/// An observer that captures the exit code or the terminating signal of a target.
+/// Only works for supported executors.
+///
+/// Both stay `None` if the target timed out, and was killed by the executor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExitStatusObserver {
+    /// The name of the observer.
+    pub name: Cow<'static, str>,
+    /// The exit code of the target during its last execution, if it exited.
+    pub code: Option<i32>,
+    /// The signal that terminated the target during its last execution, if any.
+    pub signal: Option<i32>,
+}
+
+impl ExitStatusObserver {
+    /// Create a new [`ExitStatusObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            code: None,
+            signal: None,
+        }
+    }
+
+    /// React to a new exit status
+    pub fn observe_exit_status(&mut self, status: &ExitStatus) {
+        self.code = status.code();
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            self.signal = status.signal();
+        }
+    }
+}
+
+impl Named for ExitStatusObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for ExitStatusObserver {
+    fn pre_exec_child(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.code = None;
+        self.signal = None;
+        Ok(())
+    }
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.code = None;
+        self.signal = None;
+        Ok(())
+    }
+}
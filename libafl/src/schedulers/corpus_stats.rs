@@ -0,0 +1,207 @@
+//! A scheduler keeping the [`CorpusStatsMetadata`] in sync with the testcases in the corpus, see
+//! [`CorpusStatsScheduler`].
+
+use libafl_bolts::tuples::MatchName;
+
+use crate::{
+    corpus::{Corpus, CorpusId, Testcase},
+    feedbacks::{CorpusStatsMetadata, TestcaseStatsMetadata},
+    schedulers::{HasQueueCycles, RemovableScheduler, Scheduler},
+    state::HasCorpus,
+    Error, HasMetadata,
+};
+
+/// Wraps a scheduler, summing up the [`TestcaseStatsMetadata`] of the testcases in the corpus in the
+/// [`CorpusStatsMetadata`] of the state.
+///
+/// The stats of a testcase are added when it is added to the corpus, and subtracted again when it is removed or
+/// replaced, so solutions and testcases pruned from the corpus are not part of the averages. Use it together
+/// with the [`crate::feedbacks::TestcaseStatsFeedback`], which fills the stats of each testcase.
+#[derive(Debug, Clone)]
+pub struct CorpusStatsScheduler<CS> {
+    inner: CS,
+}
+
+impl<CS> CorpusStatsScheduler<CS> {
+    /// Creates a new [`CorpusStatsScheduler`], wrapping the given scheduler
+    #[must_use]
+    pub fn new(inner: CS) -> Self {
+        Self { inner }
+    }
+
+    /// The inner scheduler
+    #[must_use]
+    pub fn inner(&self) -> &CS {
+        &self.inner
+    }
+
+    /// The inner scheduler (mutable)
+    pub fn inner_mut(&mut self) -> &mut CS {
+        &mut self.inner
+    }
+}
+
+/// The stats of a testcase, if the [`crate::feedbacks::TestcaseStatsFeedback`] recorded them
+fn testcase_stats<I>(testcase: &Testcase<I>) -> Option<TestcaseStatsMetadata> {
+    testcase
+        .metadata_map()
+        .get::<TestcaseStatsMetadata>()
+        .copied()
+}
+
+/// Add the stats of the testcase at `id` to the [`CorpusStatsMetadata`]
+fn add_stats<S>(state: &mut S, id: CorpusId) -> Result<(), Error>
+where
+    S: HasCorpus + HasMetadata,
+{
+    let stats = testcase_stats(&state.corpus().get_from_all(id)?.borrow());
+    if let Some(stats) = stats {
+        state
+            .metadata_or_insert_with(CorpusStatsMetadata::default)
+            .add(&stats);
+    }
+    Ok(())
+}
+
+/// Subtract the stats of a testcase which left the corpus from the [`CorpusStatsMetadata`]
+fn remove_stats<I, S>(state: &mut S, testcase: &Testcase<I>)
+where
+    S: HasMetadata,
+{
+    if let Some(stats) = testcase_stats(testcase) {
+        state
+            .metadata_or_insert_with(CorpusStatsMetadata::default)
+            .remove(&stats);
+    }
+}
+
+impl<CS, I, S> RemovableScheduler<I, S> for CorpusStatsScheduler<CS>
+where
+    CS: RemovableScheduler<I, S>,
+    S: HasCorpus + HasMetadata,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        self.inner.on_remove(state, id, testcase)?;
+        if let Some(testcase) = testcase {
+            remove_stats(state, testcase);
+        }
+        Ok(())
+    }
+
+    fn on_replace(&mut self, state: &mut S, id: CorpusId, prev: &Testcase<I>) -> Result<(), Error> {
+        self.inner.on_replace(state, id, prev)?;
+        remove_stats(state, prev);
+        add_stats(state, id)
+    }
+}
+
+impl<CS, I, S> Scheduler<I, S> for CorpusStatsScheduler<CS>
+where
+    CS: Scheduler<I, S>,
+    S: HasCorpus + HasMetadata,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        self.inner.on_add(state, id)?;
+        add_stats(state, id)
+    }
+
+    fn on_evaluation<OT>(&mut self, state: &mut S, input: &I, observers: &OT) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        self.inner.on_evaluation(state, input, observers)
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        self.inner.next(state)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        self.inner.set_current_scheduled(state, next_id)
+    }
+}
+
+impl<CS> HasQueueCycles for CorpusStatsScheduler<CS>
+where
+    CS: HasQueueCycles,
+{
+    fn queue_cycles(&self) -> u64 {
+        self.inner.queue_cycles()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::CorpusStatsScheduler;
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::{ConstFeedback, CorpusStatsMetadata, TestcaseStatsMetadata},
+        inputs::BytesInput,
+        schedulers::{QueueScheduler, RemovableScheduler, Scheduler},
+        state::{HasCorpus, HasSolutions, StdState},
+        HasMetadata,
+    };
+
+    fn testcase(exec_time_ms: u64) -> Testcase<BytesInput> {
+        let mut testcase = Testcase::new(BytesInput::new(vec![0; 4]));
+        testcase.add_metadata(TestcaseStatsMetadata {
+            exec_time: Some(Duration::from_millis(exec_time_ms)),
+            input_len: Some(4),
+            novel_entries: 1,
+        });
+        testcase
+    }
+
+    #[test]
+    fn test_corpus_stats_scheduler() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut scheduler = CorpusStatsScheduler::new(QueueScheduler::new());
+
+        let first = state.corpus_mut().add(testcase(10)).unwrap();
+        scheduler.on_add(&mut state, first).unwrap();
+        let second = state.corpus_mut().add(testcase(30)).unwrap();
+        scheduler.on_add(&mut state, second).unwrap();
+        // Solutions never reach the scheduler
+        state.solutions_mut().add(testcase(1000)).unwrap();
+        let stats = *state.metadata::<CorpusStatsMetadata>().unwrap();
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.avg_exec_time(), Duration::from_millis(20));
+
+        let prev = state.corpus_mut().replace(second, testcase(50)).unwrap();
+        scheduler.on_replace(&mut state, second, &prev).unwrap();
+        let stats = *state.metadata::<CorpusStatsMetadata>().unwrap();
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.avg_exec_time(), Duration::from_millis(30));
+
+        let removed = state.corpus_mut().remove(first).unwrap();
+        scheduler
+            .on_remove(&mut state, first, &Some(removed))
+            .unwrap();
+        let stats = *state.metadata::<CorpusStatsMetadata>().unwrap();
+        assert_eq!(stats.count, 1);
+        assert_eq!(stats.avg_exec_time(), Duration::from_millis(50));
+        assert_eq!(stats.total_novel_entries, 1);
+    }
+}
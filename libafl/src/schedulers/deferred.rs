@@ -0,0 +1,319 @@
+//! Deferred [`Testcase`]`s` are only scheduled once the rest of the queue is exhausted, or not before a given time.
+//!
+//! Defer a testcase with [`defer_testcase`] or [`defer_testcase_until`], and wrap the scheduler in a
+//! [`DeferringScheduler`], which skips the deferred testcases the inner scheduler picks. With
+//! [`DeferringScheduler::with_slow_threshold`], testcases slower than the threshold are deferred as well, so a
+//! single input taking seconds to run does not keep consuming scheduling slots.
+
+use core::time::Duration;
+
+use libafl_bolts::{current_time, tuples::MatchName};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId, Testcase},
+    schedulers::{HasQueueCycles, RemovableScheduler, Scheduler},
+    state::HasCorpus,
+    Error, HasMetadata,
+};
+
+/// The default maximum number of testcases the [`DeferringScheduler`] skips before falling back to a deferred one
+pub const DEFAULT_MAX_SKIPS: usize = 64;
+
+/// A testcase metadata deferring its scheduling
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeferredMetadata {
+    /// The time, as returned by [`current_time`], before which the testcase is not scheduled
+    not_before: Option<Duration>,
+    /// If the testcase is only scheduled when no other testcase is left
+    until_exhausted: bool,
+}
+
+libafl_bolts::impl_serdeany!(DeferredMetadata);
+
+impl DeferredMetadata {
+    /// Defers a testcase until the rest of the queue is exhausted
+    #[must_use]
+    pub fn until_exhausted() -> Self {
+        Self {
+            not_before: None,
+            until_exhausted: true,
+        }
+    }
+
+    /// Defers a testcase until the given time, as returned by [`current_time`]
+    #[must_use]
+    pub fn until(time: Duration) -> Self {
+        Self {
+            not_before: Some(time),
+            until_exhausted: false,
+        }
+    }
+
+    /// The time before which the testcase is not scheduled
+    #[must_use]
+    pub fn not_before(&self) -> Option<Duration> {
+        self.not_before
+    }
+
+    /// If the testcase is only scheduled when no other testcase is left
+    #[must_use]
+    pub fn is_until_exhausted(&self) -> bool {
+        self.until_exhausted
+    }
+
+    /// If the testcase is still deferred at the time `now`
+    #[must_use]
+    pub fn is_deferred(&self, now: Duration) -> bool {
+        self.until_exhausted || self.not_before.is_some_and(|time| now < time)
+    }
+}
+
+/// Defers the testcase `id` of the corpus until the rest of the queue is exhausted
+pub fn defer_testcase<S>(state: &mut S, id: CorpusId) -> Result<(), Error>
+where
+    S: HasCorpus,
+{
+    state
+        .corpus()
+        .get(id)?
+        .borrow_mut()
+        .add_metadata(DeferredMetadata::until_exhausted());
+    Ok(())
+}
+
+/// Defers the testcase `id` of the corpus for `delay` from now
+pub fn defer_testcase_until<S>(state: &mut S, id: CorpusId, delay: Duration) -> Result<(), Error>
+where
+    S: HasCorpus,
+{
+    state
+        .corpus()
+        .get(id)?
+        .borrow_mut()
+        .add_metadata(DeferredMetadata::until(current_time() + delay));
+    Ok(())
+}
+
+/// Stops deferring the testcase `id` of the corpus, returns if it was deferred
+pub fn undefer_testcase<S>(state: &mut S, id: CorpusId) -> Result<bool, Error>
+where
+    S: HasCorpus,
+{
+    Ok(state
+        .corpus()
+        .get(id)?
+        .borrow_mut()
+        .remove_metadata::<DeferredMetadata>()
+        .is_some())
+}
+
+/// A scheduler wrapper skipping the deferred testcases the inner scheduler picks.
+///
+/// After skipping every testcase of the corpus, or [`DEFAULT_MAX_SKIPS`] of them, the queue is considered
+/// exhausted, and it schedules a deferred testcase anyway: one deferred until exhaustion if it saw one, else the
+/// one with the earliest time.
+#[derive(Debug, Clone)]
+pub struct DeferringScheduler<CS> {
+    inner: CS,
+    slow_threshold: Option<Duration>,
+    max_skips: usize,
+}
+
+impl<CS> DeferringScheduler<CS> {
+    /// Creates a new [`DeferringScheduler`], skipping the testcases with a [`DeferredMetadata`]
+    #[must_use]
+    pub fn new(inner: CS) -> Self {
+        Self {
+            inner,
+            slow_threshold: None,
+            max_skips: DEFAULT_MAX_SKIPS,
+        }
+    }
+
+    /// Creates a new [`DeferringScheduler`], which also defers the testcases slower than `threshold` until the
+    /// rest of the queue is exhausted
+    #[must_use]
+    pub fn with_slow_threshold(inner: CS, threshold: Duration) -> Self {
+        Self {
+            slow_threshold: Some(threshold),
+            ..Self::new(inner)
+        }
+    }
+
+    /// Sets the maximum number of testcases skipped before falling back to a deferred one
+    pub fn set_max_skips(&mut self, max_skips: usize) {
+        self.max_skips = max_skips;
+    }
+
+    /// The inner scheduler
+    #[must_use]
+    pub fn inner(&self) -> &CS {
+        &self.inner
+    }
+
+    /// The inner scheduler (mutable)
+    pub fn inner_mut(&mut self) -> &mut CS {
+        &mut self.inner
+    }
+
+    /// The deferral of a testcase at the time `now`, if it is deferred
+    fn deferral<I>(&self, testcase: &Testcase<I>, now: Duration) -> Option<DeferredMetadata> {
+        if let Some(meta) = testcase.metadata_map().get::<DeferredMetadata>() {
+            if meta.is_deferred(now) {
+                return Some(*meta);
+            }
+        }
+        match (self.slow_threshold, testcase.exec_time()) {
+            (Some(threshold), Some(exec_time)) if *exec_time > threshold => {
+                Some(DeferredMetadata::until_exhausted())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<CS, I, S> RemovableScheduler<I, S> for DeferringScheduler<CS>
+where
+    CS: RemovableScheduler<I, S>,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        self.inner.on_remove(state, id, testcase)
+    }
+
+    fn on_replace(&mut self, state: &mut S, id: CorpusId, prev: &Testcase<I>) -> Result<(), Error> {
+        self.inner.on_replace(state, id, prev)
+    }
+}
+
+impl<CS, I, S> Scheduler<I, S> for DeferringScheduler<CS>
+where
+    CS: Scheduler<I, S>,
+    S: HasCorpus,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        self.inner.on_add(state, id)
+    }
+
+    fn on_evaluation<OT>(&mut self, state: &mut S, input: &I, observers: &OT) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        self.inner.on_evaluation(state, input, observers)
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        let now = current_time();
+        let attempts = state.corpus().count().min(self.max_skips).max(1);
+        // The deferred testcase to schedule if all attempts are deferred
+        let mut fallback: Option<(CorpusId, DeferredMetadata)> = None;
+        for _ in 0..attempts {
+            let id = self.inner.next(state)?;
+            let mut testcase = state.corpus().get(id)?.borrow_mut();
+            let Some(deferral) = self.deferral(&*testcase, now) else {
+                // Forget the deferrals that expired
+                drop(testcase.remove_metadata::<DeferredMetadata>());
+                return Ok(id);
+            };
+            let better = match fallback {
+                None => true,
+                Some((_, best)) => {
+                    !best.is_until_exhausted()
+                        && (deferral.is_until_exhausted()
+                            || deferral.not_before() < best.not_before())
+                }
+            };
+            if better {
+                fallback = Some((id, deferral));
+            }
+        }
+
+        // The queue is exhausted
+        let (id, _) = fallback.unwrap();
+        self.inner.set_current_scheduled(state, Some(id))?;
+        Ok(id)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        self.inner.set_current_scheduled(state, next_id)
+    }
+}
+
+impl<CS> HasQueueCycles for DeferringScheduler<CS>
+where
+    CS: HasQueueCycles,
+{
+    fn queue_cycles(&self) -> u64 {
+        self.inner.queue_cycles()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{defer_testcase, defer_testcase_until, undefer_testcase, DeferringScheduler};
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        schedulers::{QueueScheduler, Scheduler},
+        state::{HasCorpus, StdState},
+    };
+
+    #[test]
+    fn test_deferring_scheduler() {
+        let mut corpus = InMemoryCorpus::new();
+        let fast = corpus.add(Testcase::new(BytesInput::new(vec![0]))).unwrap();
+        let mut slow_testcase = Testcase::new(BytesInput::new(vec![1]));
+        *slow_testcase.exec_time_mut() = Some(Duration::from_secs(5));
+        let slow = corpus.add(slow_testcase).unwrap();
+        let later = corpus.add(Testcase::new(BytesInput::new(vec![2]))).unwrap();
+
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::<BytesInput>::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+
+        let mut scheduler =
+            DeferringScheduler::with_slow_threshold(QueueScheduler::new(), Duration::from_secs(1));
+        defer_testcase_until(&mut state, later, Duration::from_secs(3600)).unwrap();
+        for _ in 0..4 {
+            let id = Scheduler::<BytesInput, _>::next(&mut scheduler, &mut state).unwrap();
+            assert_eq!(id, fast);
+            assert_eq!(*state.corpus().current(), Some(fast));
+        }
+
+        // Once the fast testcase is deferred too, the slow one runs before the one deferred for an hour
+        defer_testcase(&mut state, fast).unwrap();
+        let id = Scheduler::<BytesInput, _>::next(&mut scheduler, &mut state).unwrap();
+        assert_eq!(id, slow);
+        assert_eq!(*state.corpus().current(), Some(slow));
+
+        assert!(undefer_testcase(&mut state, later).unwrap());
+        assert!(!undefer_testcase(&mut state, later).unwrap());
+        let id = Scheduler::<BytesInput, _>::next(&mut scheduler, &mut state).unwrap();
+        assert_eq!(id, later);
+    }
+}
@@ -0,0 +1,120 @@
+//! The [`MapElitesScheduler`] schedules the elites of a MAP-Elites archive, see [`crate::feedbacks::map_elites`].
+
+use alloc::borrow::ToOwned;
+use core::{marker::PhantomData, num::NonZero};
+
+use libafl_bolts::rands::Rand;
+
+use crate::{
+    corpus::{Corpus, CorpusId, Testcase},
+    feedbacks::{MapElitesMetadata, MapElitesTestcaseMetadata},
+    random_corpus_id,
+    schedulers::{RemovableScheduler, Scheduler},
+    state::{HasCorpus, HasRand},
+    Error, HasMetadata,
+};
+
+/// Picks a random cell of the [`MapElitesMetadata`] archive and schedules its elite.
+///
+/// Each cell is equally likely, no matter how many testcases fell into it, so rare behaviors get as much
+/// attention as common ones. Testcases added without a [`MapElitesTestcaseMetadata`] are never scheduled,
+/// unless the archive is still empty.
+#[derive(Debug, Clone)]
+pub struct MapElitesScheduler<S> {
+    phantom: PhantomData<S>,
+}
+
+impl<I, S> RemovableScheduler<I, S> for MapElitesScheduler<S>
+where
+    S: HasMetadata,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        _testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        if let Ok(archive) = state.metadata_mut::<MapElitesMetadata>() {
+            archive.remove(id);
+        }
+        Ok(())
+    }
+}
+
+impl<I, S> Scheduler<I, S> for MapElitesScheduler<S>
+where
+    S: HasCorpus + HasMetadata + HasRand,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        let current_id = *state.corpus().current();
+        let behavior = {
+            let mut testcase = state.corpus().get(id)?.borrow_mut();
+            testcase.set_parent_id_optional(current_id);
+            testcase
+                .metadata_map()
+                .get::<MapElitesTestcaseMetadata>()
+                .map(|meta| meta.behavior)
+        };
+
+        if let Some(behavior) = behavior {
+            let replaced = state
+                .metadata_or_insert_with(MapElitesMetadata::default)
+                .insert(behavior, id);
+            if let Some(replaced) = replaced {
+                log::debug!(
+                    "Testcase {id} replaced {replaced} as elite of cell {}",
+                    behavior.cell
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the elite of a random cell
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        if state.corpus().count() == 0 {
+            return Err(Error::empty(
+                "No entries in corpus. This often implies the target is not properly instrumented."
+                    .to_owned(),
+            ));
+        }
+
+        let cells = state
+            .metadata_map()
+            .get::<MapElitesMetadata>()
+            .map_or(0, |archive| archive.elites().len());
+        let id = if let Some(cells) = NonZero::new(cells) {
+            let idx = state.rand_mut().below(cells);
+            state.metadata::<MapElitesMetadata>()?.elites()[idx].id
+        } else {
+            random_corpus_id!(state.corpus(), state.rand_mut())
+        };
+        <Self as Scheduler<I, S>>::set_current_scheduled(self, state, Some(id))?;
+        Ok(id)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        *state.corpus_mut().current_mut() = next_id;
+        Ok(())
+    }
+}
+
+impl<S> MapElitesScheduler<S> {
+    /// Create a new [`MapElitesScheduler`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<S> Default for MapElitesScheduler<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -14,7 +14,9 @@ use crate::{
     feedbacks::MapIndexesMetadata,
     observers::CanTrack,
     require_index_tracking,
-    schedulers::{LenTimeMulTestcaseScore, RemovableScheduler, Scheduler, TestcaseScore},
+    schedulers::{
+        is_pinned, LenTimeMulTestcaseScore, RemovableScheduler, Scheduler, TestcaseScore,
+    },
     state::{HasCorpus, HasRand},
     Error, HasMetadata,
 };
@@ -214,7 +216,7 @@ where
         self.base.on_evaluation(state, input, observers)
     }
 
-    /// Gets the next entry
+    /// Gets the next entry, pinned entries count as favored
     fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
         self.cull(state)?;
         let mut id = self.base.next(state)?;
@@ -223,7 +225,8 @@ where
                 .corpus()
                 .get(id)?
                 .borrow()
-                .has_metadata::<IsFavoredMetadata>();
+                .has_metadata::<IsFavoredMetadata>()
+                && !is_pinned(state, id);
             has
         } && state.rand_mut().coinflip(self.skip_non_favored_prob)
         {
@@ -26,6 +26,20 @@ pub use accounting::CoverageAccountingScheduler;
 pub mod weighted;
 pub use weighted::{StdWeightedScheduler, WeightedScheduler};
 
+pub mod map_elites;
+pub use map_elites::MapElitesScheduler;
+
+pub mod pinned;
+pub use pinned::{is_pinned, pin_testcase, unpin_testcase, PinnedMetadata};
+
+pub mod deferred;
+pub use deferred::{
+    defer_testcase, defer_testcase_until, undefer_testcase, DeferredMetadata, DeferringScheduler,
+};
+
+pub mod corpus_stats;
+pub use corpus_stats::CorpusStatsScheduler;
+
 pub mod tuneable;
 use libafl_bolts::{
     rands::Rand,
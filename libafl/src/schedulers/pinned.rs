@@ -0,0 +1,143 @@
+//! Pinned [`Testcase`]`s` are seeds the user marked as important.
+//!
+//! They are never removed by the corpus minimizers, the [`super::MinimizerScheduler`] treats them as favored, and
+//! the [`super::WeightedScheduler`] with [`super::WeightedScheduler::favored_rotation`] schedules each of them at
+//! least once every few queue cycles, however low their weight.
+//!
+//! Pin a testcase with [`pin_testcase`], e.g. right after loading the initial inputs.
+
+use alloc::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId},
+    state::HasCorpus,
+    Error, HasMetadata,
+};
+
+#[cfg(doc)]
+use crate::corpus::Testcase;
+
+/// The state metadata holding the pinned [`Testcase`]`s`
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PinnedMetadata {
+    /// The pinned testcases, and the queue cycle they were last scheduled in, if ever
+    pinned: BTreeMap<CorpusId, Option<u64>>,
+}
+
+libafl_bolts::impl_serdeany!(PinnedMetadata);
+
+impl PinnedMetadata {
+    /// Creates a new [`struct@PinnedMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pins the testcase `id`
+    pub fn pin(&mut self, id: CorpusId) {
+        self.pinned.entry(id).or_insert(None);
+    }
+
+    /// Unpins the testcase `id`, returns if it was pinned
+    pub fn unpin(&mut self, id: CorpusId) -> bool {
+        self.pinned.remove(&id).is_some()
+    }
+
+    /// If the testcase `id` is pinned
+    #[must_use]
+    pub fn is_pinned(&self, id: CorpusId) -> bool {
+        self.pinned.contains_key(&id)
+    }
+
+    /// The pinned testcases
+    pub fn ids(&self) -> impl Iterator<Item = CorpusId> + '_ {
+        self.pinned.keys().copied()
+    }
+
+    /// Records that the testcase `id`, if pinned, was scheduled in the queue cycle `cycle`
+    pub fn scheduled(&mut self, id: CorpusId, cycle: u64) {
+        if let Some(last) = self.pinned.get_mut(&id) {
+            *last = Some(cycle);
+        }
+    }
+
+    /// The first pinned testcase not scheduled in the last `period` queue cycles before `cycle`
+    #[must_use]
+    pub fn overdue(&self, cycle: u64, period: u64) -> Option<CorpusId> {
+        self.pinned
+            .iter()
+            .find(|(_, last)| last.map_or(true, |last| cycle >= last + period))
+            .map(|(id, _)| *id)
+    }
+}
+
+/// Pins the testcase `id` of the corpus
+pub fn pin_testcase<S>(state: &mut S, id: CorpusId) -> Result<(), Error>
+where
+    S: HasCorpus + HasMetadata,
+{
+    // Fail early for a testcase not in the corpus
+    state.corpus().get(id)?;
+    state.metadata_or_insert_with(PinnedMetadata::new).pin(id);
+    Ok(())
+}
+
+/// Unpins the testcase `id` of the corpus, returns if it was pinned
+pub fn unpin_testcase<S>(state: &mut S, id: CorpusId) -> bool
+where
+    S: HasMetadata,
+{
+    state
+        .metadata_map_mut()
+        .get_mut::<PinnedMetadata>()
+        .is_some_and(|meta| meta.unpin(id))
+}
+
+/// If the testcase `id` of the corpus is pinned
+#[must_use]
+pub fn is_pinned<S>(state: &S, id: CorpusId) -> bool
+where
+    S: HasMetadata,
+{
+    state
+        .metadata_map()
+        .get::<PinnedMetadata>()
+        .is_some_and(|meta| meta.is_pinned(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PinnedMetadata;
+    use crate::corpus::CorpusId;
+
+    #[test]
+    fn test_pinned_rotation() {
+        let mut meta = PinnedMetadata::new();
+        meta.pin(CorpusId(3));
+        meta.pin(CorpusId(1));
+        assert!(meta.is_pinned(CorpusId(3)));
+        assert!(!meta.is_pinned(CorpusId(2)));
+
+        // Never scheduled testcases are overdue right away
+        assert_eq!(meta.overdue(0, 4), Some(CorpusId(1)));
+        meta.scheduled(CorpusId(1), 0);
+        assert_eq!(meta.overdue(0, 4), Some(CorpusId(3)));
+        meta.scheduled(CorpusId(3), 1);
+        assert_eq!(meta.overdue(3, 4), None);
+        assert_eq!(meta.overdue(4, 4), Some(CorpusId(1)));
+
+        // Unpinned testcases are not tracked anymore
+        meta.scheduled(CorpusId(2), 4);
+        assert!(!meta.is_pinned(CorpusId(2)));
+        assert!(meta.unpin(CorpusId(1)));
+        assert!(!meta.unpin(CorpusId(1)));
+        assert_eq!(meta.overdue(4, 4), None);
+        assert_eq!(meta.overdue(5, 4), Some(CorpusId(3)));
+    }
+}
@@ -78,6 +78,13 @@ impl SchedulerMetadata {
         self.strat = strat;
     }
 
+    /// Switch the base of the `PowerSchedule`, keeping its other settings
+    pub fn set_base_schedule(&mut self, base: BaseSchedule) {
+        let mut strat = self.strat.unwrap_or_else(|| PowerSchedule::new(base));
+        strat.set_base(base);
+        self.strat = Some(strat);
+    }
+
     /// The measured exec time during calibration
     #[must_use]
     pub fn exec_time(&self) -> Duration {
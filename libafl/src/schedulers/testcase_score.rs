@@ -1,16 +1,20 @@
 //! The `TestcaseScore` is an evaluator providing scores of corpus items.
 use alloc::string::{String, ToString};
+use core::marker::PhantomData;
 
-use libafl_bolts::{HasLen, HasRefCnt};
+use libafl_bolts::{current_time, HasLen, HasRefCnt};
 
 use crate::{
     corpus::{Corpus, SchedulerTestcaseMetadata, Testcase},
-    feedbacks::MapIndexesMetadata,
+    feedbacks::{
+        DistanceMetadata, MapIndexesMetadata, TargetEdgesMetadata, TargetEdgesTestcaseMetadata,
+        TestcaseDistanceMetadata,
+    },
     schedulers::{
         minimizer::{IsFavoredMetadata, TopRatedsMetadata},
         powersched::{BaseSchedule, SchedulerMetadata},
     },
-    state::HasCorpus,
+    state::{HasCorpus, HasStartTime},
     Error, HasMetadata,
 };
 
@@ -351,3 +355,75 @@ where
         Ok(weight)
     }
 }
+
+/// Boosts the score of another [`TestcaseScore`] for testcases reaching the target edges of a directed campaign.
+///
+/// The score of a testcase with a [`TargetEdgesTestcaseMetadata`] is multiplied with the boost of the
+/// [`TargetEdgesMetadata`], and grows logarithmically with the number of target edges it reaches.
+/// Use it in a weighted scheduler, e.g. `WeightedScheduler<C, TargetEdgesTestcaseScore<CorpusWeightTestcaseScore>, O>`.
+#[derive(Debug, Clone)]
+pub struct TargetEdgesTestcaseScore<F> {
+    phantom: PhantomData<F>,
+}
+
+impl<F, S> TestcaseScore<S> for TargetEdgesTestcaseScore<F>
+where
+    F: TestcaseScore<S>,
+    S: HasCorpus + HasMetadata,
+{
+    #[allow(clippy::cast_precision_loss)]
+    fn compute(
+        state: &S,
+        entry: &mut Testcase<<S::Corpus as Corpus>::Input>,
+    ) -> Result<f64, Error> {
+        let score = F::compute(state, entry)?;
+        let hits = entry
+            .metadata_map()
+            .get::<TargetEdgesTestcaseMetadata>()
+            .map_or(0, |meta| meta.edges.len());
+        if hits == 0 {
+            return Ok(score);
+        }
+        let boost = state
+            .metadata_map()
+            .get::<TargetEdgesMetadata>()
+            .map_or(1.0, TargetEdgesMetadata::boost);
+        Ok(score * boost * (1.0 + libm::log2(hits as f64)))
+    }
+}
+
+/// Weighs the score of another [`TestcaseScore`] by the distance of the testcase to the target sites of a directed
+/// campaign, annealing from exploration to exploitation, as in `AFLGo`.
+///
+/// Testcases with a [`TestcaseDistanceMetadata`] get the power factor of the [`DistanceMetadata`], see
+/// [`DistanceMetadata::power_factor`]. Use it in a weighted scheduler, together with a
+/// [`crate::feedbacks::DistanceFeedback`], e.g. `WeightedScheduler<C, DistanceTestcaseScore<CorpusWeightTestcaseScore>, O>`.
+#[derive(Debug, Clone)]
+pub struct DistanceTestcaseScore<F> {
+    phantom: PhantomData<F>,
+}
+
+impl<F, S> TestcaseScore<S> for DistanceTestcaseScore<F>
+where
+    F: TestcaseScore<S>,
+    S: HasCorpus + HasMetadata + HasStartTime,
+{
+    fn compute(
+        state: &S,
+        entry: &mut Testcase<<S::Corpus as Corpus>::Input>,
+    ) -> Result<f64, Error> {
+        let score = F::compute(state, entry)?;
+        let Some(distance) = entry
+            .metadata_map()
+            .get::<TestcaseDistanceMetadata>()
+            .map(|meta| meta.distance)
+        else {
+            return Ok(score);
+        };
+        let Some(meta) = state.metadata_map().get::<DistanceMetadata>() else {
+            return Ok(score);
+        };
+        let elapsed = current_time().saturating_sub(*state.start_time());
+        Ok(score * meta.power_factor(distance, elapsed))
+    }
+}
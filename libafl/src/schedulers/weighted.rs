@@ -3,6 +3,7 @@
 //! The queue corpus scheduler with weighted queue item selection [from AFL++](https://github.com/AFLplusplus/AFLplusplus/blob/1d4f1e48797c064ee71441ba555b29fc3f467983/src/afl-fuzz-queue.c#L32).
 //! This queue corpus scheduler needs calibration stage.
 
+use alloc::vec::Vec;
 use core::marker::PhantomData;
 
 use hashbrown::HashMap;
@@ -20,6 +21,7 @@ use crate::{
     random_corpus_id,
     schedulers::{
         on_add_metadata_default, on_evaluation_metadata_default, on_next_metadata_default,
+        pinned::PinnedMetadata,
         powersched::{BaseSchedule, SchedulerMetadata},
         testcase_score::{CorpusWeightTestcaseScore, TestcaseScore},
         AflScheduler, HasQueueCycles, RemovableScheduler, Scheduler,
@@ -107,6 +109,8 @@ pub struct WeightedScheduler<C, F, O> {
     phantom: PhantomData<(F, O)>,
     /// Cycle `PowerSchedule` on completion of every queue cycle.
     cycle_schedules: bool,
+    /// Schedule each pinned testcase at least once every this many queue cycles.
+    favored_rotation: Option<u64>,
 }
 
 impl<C, F, O> WeightedScheduler<C, F, O>
@@ -138,6 +142,7 @@ where
             queue_cycles: 0,
             table_invalidated: true,
             cycle_schedules: false,
+            favored_rotation: None,
             phantom: PhantomData,
         }
     }
@@ -149,6 +154,15 @@ where
         self
     }
 
+    /// Schedule each pinned testcase at least once every `cycles` queue cycles, whatever its weight.
+    ///
+    /// See [`crate::schedulers::pinned`] for how to pin testcases.
+    #[must_use]
+    pub fn favored_rotation(mut self, cycles: u64) -> Self {
+        self.favored_rotation = Some(cycles.max(1));
+        self
+    }
+
     #[must_use]
     /// Getter for `strat`
     pub fn strat(&self) -> &Option<PowerSchedule> {
@@ -243,6 +257,28 @@ where
         Ok(())
     }
 
+    /// Replaces the chosen `idx` by a pinned testcase not scheduled in the last `period` queue cycles, if any
+    fn rotate_pinned<S>(&self, state: &mut S, idx: CorpusId, period: u64) -> Result<CorpusId, Error>
+    where
+        S: HasCorpus + HasMetadata,
+    {
+        let Some(pinned) = state.metadata_map().get::<PinnedMetadata>() else {
+            return Ok(idx);
+        };
+        // Forget the pinned testcases removed from the corpus in the meantime
+        let removed: Vec<CorpusId> = pinned
+            .ids()
+            .filter(|id| state.corpus().get(*id).is_err())
+            .collect();
+        let pinned = state.metadata_mut::<PinnedMetadata>()?;
+        for id in removed {
+            pinned.unpin(id);
+        }
+        let idx = pinned.overdue(self.queue_cycles, period).unwrap_or(idx);
+        pinned.scheduled(idx, self.queue_cycles);
+        Ok(idx)
+    }
+
     /// Cycles the strategy of the scheduler; tries to mimic AFL++'s cycling formula
     fn cycle_schedule(&mut self, metadata: &mut SchedulerMetadata) -> Result<(), Error> {
         let mut ps = metadata.strat().ok_or(Error::illegal_argument(
@@ -364,7 +400,7 @@ where
                 wsmeta.set_runs_current_cycle(runs_in_current_cycle + 1);
             }
 
-            let idx = if probability < *wsmeta.alias_probability().get(&s).unwrap() {
+            let mut idx = if probability < *wsmeta.alias_probability().get(&s).unwrap() {
                 s
             } else {
                 *wsmeta.alias_table().get(&s).unwrap()
@@ -380,6 +416,10 @@ where
                 }
             }
 
+            if let Some(period) = self.favored_rotation {
+                idx = self.rotate_pinned(state, idx, period)?;
+            }
+
             self.set_current_scheduled(state, Some(idx))?;
             Ok(idx)
         }
@@ -7,7 +7,7 @@ use alloc::{
 };
 use core::{fmt::Debug, marker::PhantomData, time::Duration};
 
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
 use libafl_bolts::{current_time, impl_serdeany, tuples::Handle, AsIter, Named};
 use num_traits::Bounded;
 use serde::{Deserialize, Serialize};
@@ -29,6 +29,9 @@ use crate::{
 
 /// The metadata to keep unstable entries
 /// Formula is same as AFL++: number of unstable entries divided by the number of filled entries.
+///
+/// Additionally, it counts how often each unstable entry differed from the first calibration run,
+/// to give a per-entry stability.
 #[cfg_attr(
     any(not(feature = "serdeany_autoreg"), miri),
     allow(clippy::unsafe_derive_deserialize)
@@ -37,6 +40,10 @@ use crate::{
 pub struct UnstableEntriesMetadata {
     unstable_entries: HashSet<usize>,
     filled_entries_count: usize,
+    #[serde(default)]
+    unstable_hits: HashMap<usize, u64>,
+    #[serde(default)]
+    calibration_runs: u64,
 }
 impl_serdeany!(UnstableEntriesMetadata);
 
@@ -47,6 +54,8 @@ impl UnstableEntriesMetadata {
         Self {
             unstable_entries: HashSet::new(),
             filled_entries_count: 0,
+            unstable_hits: HashMap::new(),
+            calibration_runs: 0,
         }
     }
 
@@ -61,6 +70,38 @@ impl UnstableEntriesMetadata {
     pub fn filled_entries_count(&self) -> usize {
         self.filled_entries_count
     }
+
+    /// The number of calibration runs that were compared against their first run, over all testcases
+    #[must_use]
+    pub fn calibration_runs(&self) -> u64 {
+        self.calibration_runs
+    }
+
+    /// The stability of the entry at `idx`, between `0.0` and `1.0`:
+    /// the share of calibration runs in which it did not differ from the first run.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn entry_stability(&self, idx: usize) -> f64 {
+        match self.unstable_hits.get(&idx) {
+            Some(hits) if self.calibration_runs > 0 => {
+                1.0 - (*hits as f64 / self.calibration_runs as f64)
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// The overall stability, between `0.0` and `1.0`, as shown by AFL++
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn stability(&self) -> f64 {
+        if self.filled_entries_count == 0 {
+            return 1.0;
+        }
+        let stable = self
+            .filled_entries_count
+            .saturating_sub(self.unstable_entries.len());
+        stable as f64 / self.filled_entries_count as f64
+    }
 }
 
 impl Default for UnstableEntriesMetadata {
@@ -176,6 +217,9 @@ where
         let map_first_entries = map_first.to_vec();
         let map_first_len = map_first.to_vec().len();
         let mut unstable_entries: Vec<usize> = vec![];
+        // How often each entry differed from the first run
+        let mut unstable_hits: HashMap<usize, u64> = HashMap::new();
+        let mut compared_runs = 0;
         // Run CAL_STAGE_START - 1 times, increase by 2 for every time a new
         // run is found to be unstable or to crash with CAL_STAGE_MAX total runs.
         let mut i = 1;
@@ -230,6 +274,9 @@ where
                     .zip(map.iter().zip(history_map.iter_mut()))
                     .enumerate()
                 {
+                    if *first != *cur {
+                        *unstable_hits.entry(idx).or_insert(0) += 1;
+                    }
                     if *first != *cur && *history != O::Entry::max_value() {
                         // If we just hit a history map entry that was not covered before, but is now flagged as flaky,
                         // we need to make sure the `num_covered_map_indexes` is kept in sync.
@@ -240,6 +287,11 @@ where
                     };
                 }
 
+                for idx in unstable_hits.keys() {
+                    map_state.mask_unstable(*idx);
+                }
+                compared_runs += 1;
+
                 if !unstable_entries.is_empty() && iter < CAL_STAGE_MAX {
                     iter += 2;
                 }
@@ -261,6 +313,13 @@ where
             send_default_stability = true;
             state.add_metadata(UnstableEntriesMetadata::new());
         }
+        if compared_runs > 0 {
+            let metadata = state.metadata_mut::<UnstableEntriesMetadata>()?;
+            metadata.calibration_runs += compared_runs;
+            for (idx, hits) in unstable_hits {
+                *metadata.unstable_hits.entry(idx).or_insert(0) += hits;
+            }
+        }
 
         // If weighted scheduler or powerscheduler is used, update it
         if state.has_metadata::<SchedulerMetadata>() {
@@ -1,4 +1,10 @@
 //! The colorization stage from `colorization()` in afl++
+//!
+//! Colorization approximates taint tracking: it replaces the bytes of the current input with random values,
+//! range by range, and keeps those ranges that leave the coverage map unchanged. The resulting
+//! [`TaintMetadata`] is stored in the current testcase, so it is only computed once per testcase, and in the
+//! state, where the I2S mutators, such as [`crate::mutators::AFLppRedQueen`], and the
+//! [`crate::mutators::TaintedBytesRandMutator`] pick it up.
 use alloc::{
     borrow::{Cow, ToOwned},
     collections::binary_heap::BinaryHeap,
@@ -22,7 +28,7 @@ use crate::{
     nonzero,
     observers::{MapObserver, ObserversTuple},
     stages::{RetryCountRestartHelper, Stage},
-    state::{HasCorpus, HasCurrentTestcase, HasRand, UsesState},
+    state::{HasCorpus, HasCurrentCorpusId, HasCurrentTestcase, HasRand, UsesState},
     Error, HasMetadata, HasNamedMetadata,
 };
 
@@ -60,11 +66,38 @@ impl Ord for Earlier {
 
 /// Default name for `ColorizationStage`; derived from ALF++
 pub const COLORIZATION_STAGE_NAME: &str = "colorization";
-/// The mutational stage using power schedules
+
+/// The Shannon entropy of the given bytes, in bits per byte (between `0.0` and `8.0`)
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn byte_entropy(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0_usize; 256];
+    for b in bytes {
+        counts[*b as usize] += 1;
+    }
+    let len = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / len;
+            -p * libm::log2(p)
+        })
+        .sum()
+}
+
+/// The colorization pre-pass of afl++ redqueen, approximating which input bytes may be changed freely
 #[derive(Clone, Debug)]
 pub struct ColorizationStage<C, E, EM, O, Z> {
     map_observer_handle: Handle<C>,
     name: Cow<'static, str>,
+    max_input_len: usize,
+    min_entropy: f64,
+    max_entropy: f64,
+    min_range_len: usize,
     #[allow(clippy::type_complexity)]
     phantom: PhantomData<(E, EM, O, E, Z)>,
 }
@@ -89,7 +122,7 @@ impl<C, E, EM, O, Z> Stage<E, EM, Z> for ColorizationStage<C, E, EM, O, Z>
 where
     EM: UsesState<State = Self::State> + EventFirer,
     E: HasObservers + Executor<EM, Z>,
-    E::State: HasCorpus + HasMetadata + HasRand + HasNamedMetadata,
+    E::State: HasCorpus + HasCurrentCorpusId + HasMetadata + HasRand + HasNamedMetadata,
     E::Observers: ObserversTuple<<Self as UsesInput>::Input, <Self as UsesState>::State>,
     E::Input: HasMutatorBytes,
     O: MapObserver,
@@ -106,8 +139,23 @@ where
         state: &mut Self::State,
         manager: &mut EM,
     ) -> Result<(), Error> {
-        // Run with the mutated input
-        Self::colorize(fuzzer, executor, state, manager, &self.map_observer_handle)?;
+        // Colorize each testcase only once
+        let cached = state
+            .current_testcase()?
+            .metadata::<TaintMetadata>()
+            .ok()
+            .cloned();
+        let meta = match cached {
+            Some(meta) => meta,
+            None => {
+                let meta = self.colorize(fuzzer, executor, state, manager)?;
+                state.current_testcase_mut()?.add_metadata(meta.clone());
+                meta
+            }
+        };
+
+        // Share it with the mutators of the following stages
+        state.add_metadata(meta);
 
         Ok(())
     }
@@ -125,7 +173,7 @@ where
 }
 
 /// Store the taint and the input
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(
     any(not(feature = "serdeany_autoreg"), miri),
     allow(clippy::unsafe_derive_deserialize)
@@ -159,6 +207,14 @@ impl TaintMetadata {
     pub fn ranges(&self) -> &Vec<Range<usize>> {
         &self.ranges
     }
+
+    #[must_use]
+    /// Whether the byte at `idx` lies in one of the `ranges`
+    pub fn is_tainted(&self, idx: usize) -> bool {
+        // The ranges are sorted and disjoint
+        let pos = self.ranges.partition_point(|r| r.end <= idx);
+        self.ranges.get(pos).is_some_and(|r| r.contains(&idx))
+    }
 }
 
 libafl_bolts::impl_serdeany!(TaintMetadata);
@@ -170,7 +226,7 @@ where
     C: AsRef<O> + Named,
     E: HasObservers + Executor<EM, Z>,
     E::Observers: ObserversTuple<<Self as UsesInput>::Input, <Self as UsesState>::State>,
-    <E as UsesState>::State: HasCorpus + HasMetadata + HasRand,
+    <E as UsesState>::State: HasCorpus + HasCurrentCorpusId + HasMetadata + HasRand,
     E::Input: HasMutatorBytes,
     Z: UsesState<State = <Self as UsesState>::State>,
     <<Self as UsesState>::State as HasCorpus>::Corpus: Corpus<Input = E::Input>, //delete me
@@ -178,13 +234,25 @@ where
     #[inline]
     #[allow(clippy::let_and_return)]
     fn colorize(
+        &self,
         fuzzer: &mut Z,
         executor: &mut E,
         state: &mut <Self as UsesState>::State,
         manager: &mut EM,
-        observer_handle: &Handle<C>,
-    ) -> Result<E::Input, Error> {
+    ) -> Result<TaintMetadata, Error> {
+        let observer_handle = &self.map_observer_handle;
         let mut input = state.current_input_cloned()?;
+
+        // Too long or too random inputs are not worth the executions: keep them as they are, without taint
+        let input_len = input.bytes().len();
+        let entropy = byte_entropy(input.bytes());
+        if input_len > self.max_input_len
+            || entropy < self.min_entropy
+            || entropy > self.max_entropy
+        {
+            return Ok(TaintMetadata::new(input.bytes().to_vec(), Vec::new()));
+        }
+
         // The backup of the input
         let backup = input.clone();
         // This is the buffer we'll randomly mutate during type_replace
@@ -196,7 +264,6 @@ where
         let orig_hash =
             Self::get_raw_map_hash_run(fuzzer, executor, state, manager, &input, observer_handle)?;
         let changed_bytes = changed.bytes_mut();
-        let input_len = changed_bytes.len();
 
         // Binary heap, pop is logN, insert is logN
         // We will separate this range into smaller ranges.
@@ -295,16 +362,10 @@ where
             }
         }
 
-        if let Some(meta) = state.metadata_map_mut().get_mut::<TaintMetadata>() {
-            meta.update(input.bytes().to_vec(), res);
-
-            // println!("meta: {:#?}", meta);
-        } else {
-            let meta = TaintMetadata::new(input.bytes().to_vec(), res);
-            state.add_metadata::<TaintMetadata>(meta);
-        }
+        // Tiny ranges rarely hold a whole comparison operand
+        res.retain(|r| r.len() >= self.min_range_len);
 
-        Ok(input)
+        Ok(TaintMetadata::new(input.bytes().to_vec(), res))
     }
 
     #[must_use]
@@ -314,10 +375,39 @@ where
         Self {
             map_observer_handle: map_observer.handle(),
             name: Cow::Owned(COLORIZATION_STAGE_NAME.to_owned() + ":" + obs_name.as_str()),
+            max_input_len: usize::MAX,
+            min_entropy: 0.0,
+            max_entropy: 8.0,
+            min_range_len: 1,
             phantom: PhantomData,
         }
     }
 
+    /// Skip inputs longer than `max_input_len`, as colorization needs up to two executions per byte
+    #[must_use]
+    pub fn with_max_input_len(mut self, max_input_len: usize) -> Self {
+        self.max_input_len = max_input_len;
+        self
+    }
+
+    /// Only colorize inputs whose [`byte_entropy`] lies between `min_entropy` and `max_entropy` bits per byte.
+    ///
+    /// Compressed or encrypted inputs rarely contain comparison operands verbatim, so a `max_entropy` of
+    /// about `7.5` skips them.
+    #[must_use]
+    pub fn with_entropy_thresholds(mut self, min_entropy: f64, max_entropy: f64) -> Self {
+        self.min_entropy = min_entropy;
+        self.max_entropy = max_entropy;
+        self
+    }
+
+    /// Drop tainted ranges shorter than `min_range_len` bytes
+    #[must_use]
+    pub fn with_min_range_len(mut self, min_range_len: usize) -> Self {
+        self.min_range_len = min_range_len;
+        self
+    }
+
     // Run the target and get map hash but before hitcounts's post_exec is used
     fn get_raw_map_hash_run(
         fuzzer: &mut Z,
@@ -439,3 +529,24 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::{byte_entropy, TaintMetadata};
+
+    #[test]
+    fn test_taint_ranges() {
+        assert!(byte_entropy(&[]).abs() < f64::EPSILON);
+        assert!(byte_entropy(&[0x41; 16]).abs() < f64::EPSILON);
+        assert!((byte_entropy(&[0, 1, 2, 3]) - 2.0).abs() < f64::EPSILON);
+
+        let meta = TaintMetadata::new(vec![0; 16], vec![2..4, 8..12]);
+        assert!(!meta.is_tainted(1));
+        assert!(meta.is_tainted(2));
+        assert!(!meta.is_tainted(4));
+        assert!(meta.is_tainted(11));
+        assert!(!meta.is_tainted(12));
+    }
+}
@@ -0,0 +1,237 @@
+//! The [`ControlStage`] applies operator commands received by a [`ControlServer`], and pauses the fuzzer on request.
+//! The [`ControlMinimizeStage`] minimizes the corpus on request.
+
+#[cfg(all(feature = "cmin", unix))]
+use core::hash::Hash;
+use core::{marker::PhantomData, time::Duration};
+
+#[cfg(all(feature = "cmin", unix))]
+use libafl_bolts::AsIter;
+
+#[cfg(all(feature = "cmin", unix))]
+use crate::{
+    corpus::minimizer::MapCorpusMinimizer,
+    events::control::ControlMetadata,
+    executors::{Executor, HasObservers},
+    observers::{MapObserver, ObserversTuple},
+    schedulers::{RemovableScheduler, Scheduler, TestcaseScore},
+    HasScheduler,
+};
+use crate::{
+    corpus::Corpus,
+    events::{
+        control::{fire_control_command, ControlServer, ControlStats},
+        wait_while_paused, CommandMetadata, EventFirer, EventProcessor, ProgressReporter,
+        PAUSE_POLL_INTERVAL,
+    },
+    fuzzer::STATS_TIMEOUT_DEFAULT,
+    stages::Stage,
+    state::{
+        HasCorpus, HasExecutions, HasLastReportTime, HasRuntimeConfig, HasSolutions, Stoppable,
+        UsesState,
+    },
+    Error, HasMetadata,
+};
+
+/// A stage applying [`crate::events::control::ControlCommand`]s.
+///
+/// If created with a [`ControlServer`], commands received via HTTP are fired to all other clients.
+/// While paused, the stage keeps processing incoming events and commands, so a later `Resume` gets through,
+/// see [`wait_while_paused`].
+#[derive(Debug)]
+pub struct ControlStage<E, EM, Z> {
+    server: Option<ControlServer>,
+    poll_interval: Duration,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> UsesState for ControlStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for ControlStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: EventFirer<State = Self::State> + EventProcessor<E, Z> + ProgressReporter,
+    Z: UsesState<State = Self::State>,
+    Self::State:
+        HasMetadata + HasExecutions + HasLastReportTime + Stoppable + HasCorpus + HasSolutions,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let server = self.server.as_ref();
+        wait_while_paused(
+            fuzzer,
+            executor,
+            state,
+            manager,
+            self.poll_interval,
+            STATS_TIMEOUT_DEFAULT,
+            |state, manager| Self::handle_commands(server, state, manager),
+        )
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> ControlStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: EventFirer<State = E::State>,
+    E::State: HasMetadata + HasExecutions + HasCorpus + HasSolutions,
+{
+    /// Create a new [`ControlStage`] for a client that only applies commands fired by others
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            server: None,
+            poll_interval: PAUSE_POLL_INTERVAL,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Create a new [`ControlStage`] forwarding the commands of the given [`ControlServer`]
+    #[must_use]
+    pub fn with_server(server: ControlServer) -> Self {
+        Self {
+            server: Some(server),
+            poll_interval: PAUSE_POLL_INTERVAL,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Set the interval to poll for events while the fuzzer is paused
+    #[must_use]
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Fire all commands received by the server, and publish the current stats
+    fn handle_commands(
+        server: Option<&ControlServer>,
+        state: &mut E::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let Some(server) = server else {
+            return Ok(());
+        };
+        for command in server.take_commands() {
+            log::info!("Firing control command {command:?}");
+            fire_control_command(manager, state, &command)?;
+        }
+
+        server.update_stats(ControlStats {
+            executions: *state.executions(),
+            corpus_size: state.corpus().count(),
+            objective_size: state.solutions().count(),
+            paused: state
+                .metadata::<CommandMetadata>()
+                .is_ok_and(CommandMetadata::paused),
+            timeout_ms: state.runtime_config().timeout.map(|t| t.as_millis() as u64),
+        });
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> Default for ControlStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: EventFirer<State = E::State>,
+    E::State: HasMetadata + HasExecutions + HasCorpus + HasSolutions,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A stage minimizing the corpus with a [`MapCorpusMinimizer`],
+/// once a [`crate::events::control::ControlCommand::MinimizeCorpus`] was received.
+#[cfg(all(feature = "cmin", unix))]
+#[derive(Debug)]
+pub struct ControlMinimizeStage<C, E, EM, O, T, TS, Z> {
+    minimizer: MapCorpusMinimizer<C, E, O, T, TS>,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+#[cfg(all(feature = "cmin", unix))]
+impl<C, E, EM, O, T, TS, Z> UsesState for ControlMinimizeStage<C, E, EM, O, T, TS, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+#[cfg(all(feature = "cmin", unix))]
+impl<C, CS, E, EM, O, T, TS, Z> Stage<E, EM, Z> for ControlMinimizeStage<C, E, EM, O, T, TS, Z>
+where
+    E: Executor<EM, Z> + HasObservers,
+    E::Observers: ObserversTuple<E::Input, E::State>,
+    E::State: HasMetadata + HasCorpus + HasExecutions,
+    <E::State as HasCorpus>::Corpus: Corpus<Input = E::Input>,
+    for<'a> O: MapObserver<Entry = T> + AsIter<'a, Item = T>,
+    C: AsRef<O>,
+    CS: Scheduler<E::Input, E::State> + RemovableScheduler<E::Input, E::State>,
+    EM: EventFirer<State = E::State>,
+    T: Copy + Hash + Eq,
+    TS: TestcaseScore<E::State>,
+    Z: HasScheduler<Scheduler = CS, State = E::State>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        if state
+            .metadata_mut::<ControlMetadata>()
+            .is_ok_and(ControlMetadata::take_minimize_request)
+        {
+            log::info!("Minimizing the corpus on request");
+            self.minimizer.minimize(fuzzer, executor, manager, state)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // The request was taken before minimizing, so a minimization is not retried after a crash
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "cmin", unix))]
+impl<C, E, EM, O, T, TS, Z> ControlMinimizeStage<C, E, EM, O, T, TS, Z> {
+    /// Create a new [`ControlMinimizeStage`] running the given minimizer on request
+    #[must_use]
+    pub fn new(minimizer: MapCorpusMinimizer<C, E, O, T, TS>) -> Self {
+        Self {
+            minimizer,
+            phantom: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! The [`CrashAnalysisStage`] classifies new objectives by the machine state at the crash, see [`CrashKind`].
+//!
+//! In-process crashes are captured by the [`crate::observers::CrashContextObserver`] and stored by the
+//! [`crate::feedbacks::CrashContextFeedback`]. For targets running in a child process, the stage re-runs each new solution
+//! under `ptrace` on Linux, see [`CrashAnalysisStage::with_command`].
+
+use alloc::{borrow::Cow, collections::BTreeMap, string::String, vec::Vec};
+use core::{marker::PhantomData, time::Duration};
+use std::{env, ffi::OsString, fs, path::Path};
+
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId},
+    events::{Event, EventFirer},
+    feedbacks::CrashContextMetadata,
+    inputs::{Input, UsesInput},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::{CrashContext, CrashKind},
+    stages::{for_each_new_solution, SolutionProgress, Stage},
+    state::{HasSolutions, UsesState},
+    Error, HasMetadata,
+};
+
+/// The classified solutions of the [`CrashAnalysisStage`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct CrashAnalysisMetadata {
+    /// The number of solutions of each kind
+    pub kinds: BTreeMap<CrashKind, u64>,
+    last_solution: Option<CorpusId>,
+}
+
+impl_serdeany!(CrashAnalysisMetadata);
+
+impl SolutionProgress for CrashAnalysisMetadata {
+    fn last_solution(&self) -> Option<CorpusId> {
+        self.last_solution
+    }
+
+    fn set_last_solution(&mut self, id: CorpusId) {
+        self.last_solution = Some(id);
+    }
+}
+
+impl CrashAnalysisMetadata {
+    /// The number of solutions of each kind, formatted for the `crash kinds` user stat
+    #[must_use]
+    pub fn summary(&self) -> String {
+        self.kinds
+            .iter()
+            .map(|(kind, count)| format!("{kind}: {count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// A stage classifying each new solution by its [`CrashContext`], see the [module-level documentation](self).
+///
+/// The kind is stored in the [`CrashContextMetadata`] of the solution, and the number of solutions of each kind
+/// is reported as the `crash kinds` user stat. Solutions without a context are skipped.
+#[derive(Debug)]
+pub struct CrashAnalysisStage<EM, Z> {
+    command: Option<(OsString, Vec<OsString>)>,
+    timeout: Duration,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+impl<EM, Z> UsesState for CrashAnalysisStage<EM, Z>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for CrashAnalysisStage<EM, Z>
+where
+    EM: EventFirer,
+    E: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasSolutions + HasMetadata,
+    <Self::State as HasSolutions>::Solutions: Corpus<Input = <Self::State as UsesInput>::Input>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let analyzed = for_each_new_solution::<CrashAnalysisMetadata, _, _>(state, |state, id| {
+            if let Some(kind) = self.analyze(state, id)? {
+                let meta = state.metadata_or_insert_with(CrashAnalysisMetadata::default);
+                *meta.kinds.entry(kind).or_default() += 1;
+            }
+            Ok(())
+        })?;
+        if analyzed == 0 {
+            return Ok(());
+        }
+
+        let summary = state.metadata::<CrashAnalysisMetadata>()?.summary();
+        if !summary.is_empty() {
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::Borrowed("crash kinds"),
+                    value: UserStats::new(
+                        UserStatsValue::String(summary.into()),
+                        AggregatorOps::None,
+                    ),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // The target is only re-run in a separate process, and the progress is stored after each solution
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<EM, Z> CrashAnalysisStage<EM, Z>
+where
+    EM: UsesState,
+{
+    /// Create a new [`CrashAnalysisStage`], classifying the contexts stored by the
+    /// [`CrashContextFeedback`](crate::feedbacks::CrashContextFeedback)
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            command: None,
+            timeout: Duration::from_secs(10),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Re-run solutions without a stored context with the given command under `ptrace`, only supported on Linux.
+    ///
+    /// In `args`, `@@` is replaced by a file containing the input, which is also passed on `stdin`.
+    /// Runs taking longer than `timeout` are killed. The access type of a fault is not known under `ptrace`,
+    /// so reads and writes are both classified as [`CrashKind::WildAccess`].
+    #[must_use]
+    pub fn with_command<P, A, AS>(mut self, program: P, args: A, timeout: Duration) -> Self
+    where
+        P: Into<OsString>,
+        A: IntoIterator<Item = AS>,
+        AS: Into<OsString>,
+    {
+        self.command = Some((program.into(), args.into_iter().map(Into::into).collect()));
+        self.timeout = timeout;
+        self
+    }
+
+    /// The kind of the solution `id`, collecting its context if needed
+    fn analyze(&self, state: &EM::State, id: CorpusId) -> Result<Option<CrashKind>, Error>
+    where
+        EM::State: HasSolutions,
+        <EM::State as HasSolutions>::Solutions: Corpus<Input = <EM::State as UsesInput>::Input>,
+    {
+        if let Some(meta) = state
+            .solutions()
+            .get(id)?
+            .borrow()
+            .metadata_map()
+            .get::<CrashContextMetadata>()
+        {
+            return Ok(Some(meta.kind));
+        }
+        let Some((program, args)) = &self.command else {
+            return Ok(None);
+        };
+
+        let input = state.solutions().cloned_input_for_id(id)?;
+        let input_file =
+            env::temp_dir().join(format!("libafl_crash_analysis_{}_{id}", std::process::id()));
+        input.to_file(&input_file)?;
+        let context = trace_command(program, args, &input_file, self.timeout);
+        fs::remove_file(&input_file)?;
+
+        match context {
+            Ok(Some(context)) => {
+                let meta = CrashContextMetadata::new(context);
+                let kind = meta.kind;
+                log::info!("Solution {id} classified as {kind}");
+                state.solutions().get(id)?.borrow_mut().add_metadata(meta);
+                Ok(Some(kind))
+            }
+            Ok(None) => {
+                log::info!("Solution {id} did not crash again under ptrace");
+                Ok(None)
+            }
+            Err(err) => {
+                log::warn!("Failed to analyze solution {id}: {err}");
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl<EM, Z> Default for CrashAnalysisStage<EM, Z>
+where
+    EM: UsesState,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run the command under `ptrace`, and collect the context of its first crashing signal
+#[cfg(target_os = "linux")]
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
+fn trace_command(
+    program: &OsString,
+    args: &[OsString],
+    input_file: &Path,
+    timeout: Duration,
+) -> Result<Option<CrashContext>, Error> {
+    use core::ptr;
+    use std::{
+        io,
+        os::unix::process::CommandExt,
+        process::{Command, Stdio},
+        thread,
+        time::Instant,
+    };
+
+    use crate::executors::Tracee;
+
+    let input_arg = input_file.to_string_lossy();
+    let mut command = Command::new(program);
+    command
+        .args(args.iter().map(|arg| match arg.to_str() {
+            Some(arg) => arg.replace("@@", &input_arg).into(),
+            None => arg.clone(),
+        }))
+        .stdin(fs::File::open(input_file)?)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // # Safety
+    // `ptrace(PTRACE_TRACEME)` is async-signal-safe.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::ptrace(
+                libc::PTRACE_TRACEME,
+                0,
+                ptr::null_mut::<libc::c_void>(),
+                ptr::null_mut::<libc::c_void>(),
+            ) == -1
+            {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command.spawn()?;
+    let pid = child.id() as libc::pid_t;
+
+    let kill = |pid: libc::pid_t| unsafe {
+        libc::kill(pid, libc::SIGKILL);
+        let mut status = 0;
+        libc::waitpid(pid, &mut status, 0);
+    };
+
+    let start = Instant::now();
+    let mut exec_stopped = false;
+    loop {
+        let mut status = 0;
+        // # Safety
+        // We only wait for our own child.
+        let ret = unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) };
+        if ret == -1 {
+            return Err(Error::last_os_error("Failed to wait for the traced target"));
+        }
+        if ret == 0 {
+            if start.elapsed() > timeout {
+                kill(pid);
+                return Ok(None);
+            }
+            thread::sleep(Duration::from_millis(1));
+            continue;
+        }
+        if !libc::WIFSTOPPED(status) {
+            // Exited without a crashing signal
+            return Ok(None);
+        }
+
+        let signal = libc::WSTOPSIG(status);
+        let deliver = match signal {
+            // The stop after `execve`
+            libc::SIGTRAP if !exec_stopped => {
+                exec_stopped = true;
+                0
+            }
+            libc::SIGSEGV
+            | libc::SIGBUS
+            | libc::SIGILL
+            | libc::SIGFPE
+            | libc::SIGABRT
+            | libc::SIGTRAP => {
+                let context = Tracee::new(pid).crash_context(signal);
+                kill(pid);
+                return context.map(Some);
+            }
+            signal => signal,
+        };
+        // # Safety
+        // The tracee is stopped.
+        unsafe {
+            libc::ptrace(
+                libc::PTRACE_CONT,
+                pid,
+                ptr::null_mut::<libc::c_void>(),
+                deliver as usize as *mut libc::c_void,
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn trace_command(
+    _program: &OsString,
+    _args: &[OsString],
+    _input_file: &Path,
+    _timeout: Duration,
+) -> Result<Option<CrashContext>, Error> {
+    Err(Error::unsupported(
+        "Collecting crash contexts with ptrace is only supported on Linux",
+    ))
+}
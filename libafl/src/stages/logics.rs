@@ -1,10 +1,13 @@
 //! Stage wrappers that add logics to stage list
 
-use core::marker::PhantomData;
+use alloc::{boxed::Box, vec::Vec};
+use core::{fmt, marker::PhantomData, num::NonZeroUsize, time::Duration};
+
+use libafl_bolts::{current_time, rands::Rand};
 
 use crate::{
     stages::{HasCurrentStageId, HasNestedStageStatus, Stage, StageId, StagesTuple},
-    state::UsesState,
+    state::{HasRand, UsesState},
     Error,
 };
 
@@ -317,3 +320,254 @@ impl<E, EM, ST, Z> OptionalStage<E, EM, ST, Z> {
         }
     }
 }
+
+/// Perform the stages, then evaluate the closure, until it returns true (a do-while loop).
+#[derive(Debug)]
+pub struct LoopUntilStage<CB, E, EM, ST, Z> {
+    closure: CB,
+    stages: ST,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<CB, E, EM, ST, Z> UsesState for LoopUntilStage<CB, E, EM, ST, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<CB, E, EM, ST, Z> Stage<E, EM, Z> for LoopUntilStage<CB, E, EM, ST, Z>
+where
+    CB: FnMut(&mut Z, &mut E, &mut Self::State, &mut EM) -> Result<bool, Error>,
+    E: UsesState,
+    EM: UsesState<State = E::State>,
+    ST: StagesTuple<E, EM, Self::State, Z>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasNestedStageStatus,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        loop {
+            self.stages.perform_all(fuzzer, executor, state, manager)?;
+            if (self.closure)(fuzzer, executor, state, manager)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        NestedStageRetryCountRestartHelper::should_restart(state, self)
+    }
+
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        NestedStageRetryCountRestartHelper::clear_progress(state, self)
+    }
+}
+
+impl<CB, E, EM, ST, Z> LoopUntilStage<CB, E, EM, ST, Z>
+where
+    CB: FnMut(&mut Z, &mut E, &mut <Self as UsesState>::State, &mut EM) -> Result<bool, Error>,
+    E: UsesState,
+{
+    /// Constructor. The stages run at least once, and then until the closure returns true.
+    pub fn new(closure: CB, stages: ST) -> Self {
+        Self {
+            closure,
+            stages,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Perform the stages at most once per interval of wall-clock time, e.g., to sync every 10 minutes.
+///
+/// The stages run on the first invocation.
+#[derive(Debug)]
+pub struct IntervalStage<E, EM, ST, Z> {
+    interval: Duration,
+    last_run: Option<Duration>,
+    stages: ST,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, ST, Z> UsesState for IntervalStage<E, EM, ST, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, ST, Z> Stage<E, EM, Z> for IntervalStage<E, EM, ST, Z>
+where
+    E: UsesState,
+    EM: UsesState<State = Self::State>,
+    ST: StagesTuple<E, EM, Self::State, Z>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasNestedStageStatus,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let now = current_time();
+        let due = match self.last_run {
+            Some(last) => now.saturating_sub(last) >= self.interval,
+            None => true,
+        };
+        if state.current_stage_id()?.is_some() || due {
+            self.last_run = Some(now);
+            self.stages.perform_all(fuzzer, executor, state, manager)?;
+        }
+        Ok(())
+    }
+
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        NestedStageRetryCountRestartHelper::should_restart(state, self)
+    }
+
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        NestedStageRetryCountRestartHelper::clear_progress(state, self)
+    }
+}
+
+impl<E, EM, ST, Z> IntervalStage<E, EM, ST, Z> {
+    /// Constructor for a stage running `stages` at most once per `interval`
+    #[must_use]
+    pub fn new(interval: Duration, stages: ST) -> Self {
+        Self {
+            interval,
+            last_run: None,
+            stages,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// A boxed [`Stage`], as used by [`WeightedRandomStage`]
+pub type BoxedStage<E, EM, Z> = Box<
+    dyn Stage<
+        E,
+        EM,
+        Z,
+        State = <E as UsesState>::State,
+        Input = <<E as UsesState>::State as crate::inputs::UsesInput>::Input,
+    >,
+>;
+
+/// Perform one of the wrapped stages, picked at random according to its weight.
+pub struct WeightedRandomStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    stages: Vec<(usize, BoxedStage<E, EM, Z>)>,
+    total_weight: usize,
+}
+
+impl<E, EM, Z> fmt::Debug for WeightedRandomStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeightedRandomStage")
+            .field("weights", &self.weights())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<E, EM, Z> UsesState for WeightedRandomStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for WeightedRandomStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasNestedStageStatus + HasRand,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let Some(total_weight) = NonZeroUsize::new(self.total_weight) else {
+            return Ok(());
+        };
+
+        let idx = if let Some(StageId(idx)) = state.current_stage_id()? {
+            // we are resuming
+            idx
+        } else {
+            let mut pick = state.rand_mut().below(total_weight);
+            let idx = self
+                .stages
+                .iter()
+                .position(|(weight, _)| {
+                    if pick < *weight {
+                        true
+                    } else {
+                        pick -= *weight;
+                        false
+                    }
+                })
+                .unwrap();
+            state.set_current_stage_id(StageId(idx))?;
+            idx
+        };
+
+        state.enter_inner_stage()?;
+        self.stages[idx]
+            .1
+            .perform_restartable(fuzzer, executor, state, manager)?;
+        state.exit_inner_stage()?;
+        state.clear_stage_id()?;
+
+        Ok(())
+    }
+
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        NestedStageRetryCountRestartHelper::should_restart(state, self)
+    }
+
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        NestedStageRetryCountRestartHelper::clear_progress(state, self)
+    }
+}
+
+impl<E, EM, Z> WeightedRandomStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    /// Create a new [`WeightedRandomStage`] from stages and their relative weights.
+    ///
+    /// Stages with weight `0` are never picked.
+    #[must_use]
+    pub fn new(stages: Vec<(usize, BoxedStage<E, EM, Z>)>) -> Self {
+        let total_weight = stages.iter().map(|(weight, _)| weight).sum();
+        Self {
+            stages,
+            total_weight,
+        }
+    }
+
+    /// The weights of all stages, in order
+    #[must_use]
+    pub fn weights(&self) -> Vec<usize> {
+        self.stages.iter().map(|(weight, _)| *weight).collect()
+    }
+}
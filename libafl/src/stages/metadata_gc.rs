@@ -0,0 +1,229 @@
+//! The [`MetadataGcStage`] removes state metadata according to its retention, so that the metadata of analyses does
+//! not grow without bounds over long campaigns.
+//!
+//! Register each metadata type with its [`MetadataRetention`], and place the stage between the other stages:
+//!
+//! ```rust,ignore
+//! let gc = MetadataGcStage::new()
+//!     .retain::<CrashAnalysisMetadata>(MetadataRetention::PerCycle)
+//!     .retain::<MyScratchMetadata>(MetadataRetention::PerRun);
+//! let mut stages = tuple_list!(calibration, power, gc);
+//! ```
+
+use alloc::vec::Vec;
+use core::{any::type_name, marker::PhantomData};
+
+use libafl_bolts::{
+    impl_serdeany,
+    serdeany::{SerdeAny, SerdeAnyMap},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    schedulers::HasQueueCycles,
+    stages::Stage,
+    state::{HasCorpus, UsesState},
+    Error, HasMetadata, HasScheduler,
+};
+
+/// How long a metadata entry is kept in the state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MetadataRetention {
+    /// Removed each time the [`MetadataGcStage`] runs, i.e. after each testcase
+    PerRun,
+    /// Removed after each queue cycle of the scheduler, see [`HasQueueCycles`]
+    PerCycle,
+    /// Never removed
+    Persistent,
+}
+
+/// The progress of the [`MetadataGcStage`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MetadataGcMetadata {
+    /// The queue cycle of the last per-cycle collection
+    pub last_cycle: u64,
+    /// The number of metadata entries removed so far
+    pub collected: u64,
+}
+
+impl_serdeany!(MetadataGcMetadata);
+
+/// A metadata type registered with the [`MetadataGcStage`]
+#[derive(Debug, Clone, Copy)]
+struct RetainedMetadata {
+    name: &'static str,
+    retention: MetadataRetention,
+    remove: fn(&mut SerdeAnyMap) -> bool,
+}
+
+/// Removes metadata from the state according to its [`MetadataRetention`].
+///
+/// Metadata types which are not registered are kept, like the [`MetadataRetention::Persistent`] ones. The queue
+/// cycles for [`MetadataRetention::PerCycle`] metadata come from the scheduler of the fuzzer.
+#[derive(Debug, Clone)]
+pub struct MetadataGcStage<E, EM, Z> {
+    retained: Vec<RetainedMetadata>,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> MetadataGcStage<E, EM, Z> {
+    /// Create a new [`MetadataGcStage`], without any registered metadata
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            retained: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Registers the metadata type `T` with the given retention, replacing an earlier registration
+    #[must_use]
+    pub fn retain<T>(mut self, retention: MetadataRetention) -> Self
+    where
+        T: SerdeAny,
+    {
+        let name = type_name::<T>();
+        self.retained.retain(|retained| retained.name != name);
+        self.retained.push(RetainedMetadata {
+            name,
+            retention,
+            remove: |map| map.remove::<T>().is_some(),
+        });
+        self
+    }
+
+    /// The retention of the metadata type `T`, if it is registered
+    #[must_use]
+    pub fn retention<T>(&self) -> Option<MetadataRetention>
+    where
+        T: SerdeAny,
+    {
+        let name = type_name::<T>();
+        self.retained
+            .iter()
+            .find(|retained| retained.name == name)
+            .map(|retained| retained.retention)
+    }
+
+    /// Removes the metadata of the given retentions from the map, returning how many entries were removed
+    fn collect(&self, map: &mut SerdeAnyMap, per_cycle: bool) -> u64 {
+        let mut collected = 0;
+        for retained in &self.retained {
+            let due = match retained.retention {
+                MetadataRetention::PerRun => true,
+                MetadataRetention::PerCycle => per_cycle,
+                MetadataRetention::Persistent => false,
+            };
+            if due && (retained.remove)(map) {
+                collected += 1;
+            }
+        }
+        collected
+    }
+}
+
+impl<E, EM, Z> Default for MetadataGcStage<E, EM, Z> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E, EM, Z> UsesState for MetadataGcStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for MetadataGcStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: UsesState<State = Self::State>,
+    Z: HasScheduler<State = Self::State>,
+    Z::Scheduler: HasQueueCycles,
+    Self::State: HasCorpus + HasMetadata,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        let cycles_done = fuzzer.scheduler().queue_cycles();
+        let last_cycle = state
+            .metadata_or_insert_with(MetadataGcMetadata::default)
+            .last_cycle;
+        let per_cycle = cycles_done > last_cycle;
+
+        let collected = self.collect(state.metadata_map_mut(), per_cycle);
+        // The map may have been emptied by a per-run entry of this very type
+        let meta = state.metadata_or_insert_with(MetadataGcMetadata::default);
+        meta.collected += collected;
+        if per_cycle {
+            meta.last_cycle = cycles_done;
+            log::debug!(
+                "Metadata GC after queue cycle {cycles_done}, {} entries removed so far",
+                meta.collected
+            );
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not running the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not running the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{impl_serdeany, serdeany::SerdeAnyMap};
+    use serde::{Deserialize, Serialize};
+
+    use super::{MetadataGcStage, MetadataRetention};
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Scratch;
+    impl_serdeany!(Scratch);
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Analysis;
+    impl_serdeany!(Analysis);
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Kept;
+    impl_serdeany!(Kept);
+
+    #[test]
+    fn test_metadata_gc() {
+        let gc = MetadataGcStage::<(), (), ()>::new()
+            .retain::<Scratch>(MetadataRetention::PerRun)
+            .retain::<Analysis>(MetadataRetention::PerCycle)
+            .retain::<Kept>(MetadataRetention::PerRun)
+            .retain::<Kept>(MetadataRetention::Persistent);
+        assert_eq!(gc.retention::<Kept>(), Some(MetadataRetention::Persistent));
+
+        let mut map = SerdeAnyMap::new();
+        map.insert(Scratch);
+        map.insert(Analysis);
+        map.insert(Kept);
+        assert_eq!(gc.collect(&mut map, false), 1);
+        assert!(!map.contains::<Scratch>() && map.contains::<Analysis>());
+
+        map.insert(Scratch);
+        assert_eq!(gc.collect(&mut map, true), 2);
+        assert!(!map.contains::<Analysis>() && map.contains::<Kept>());
+    }
+}
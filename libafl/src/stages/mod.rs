@@ -18,25 +18,46 @@ pub use colorization::*;
 pub use concolic::ConcolicTracingStage;
 #[cfg(all(feature = "std", feature = "concolic_mutation", unix))]
 pub use concolic::SimpleConcolicMutationalStage;
+#[cfg(feature = "control_server")]
+pub use control::ControlStage;
+#[cfg(all(unix, feature = "std"))]
+pub use crash_analysis::{CrashAnalysisMetadata, CrashAnalysisStage};
 #[cfg(feature = "std")]
 pub use dump::*;
 pub use generalization::GeneralizationStage;
 use hashbrown::HashSet;
 use libafl_bolts::{
     impl_serdeany,
+    serdeany::SerdeAny,
     tuples::{HasConstLen, IntoVec},
     Named,
 };
 pub use logics::*;
-pub use mutational::{MutationalStage, StdMutationalStage};
+pub use metadata_gc::{MetadataGcMetadata, MetadataGcStage, MetadataRetention};
+pub use mutational::{BatchMutationalStage, MutationalStage, StdMutationalStage};
+pub use plateau::{
+    install_escalation_handler, Escalation, EscalationMetadata, PlateauMetadata,
+    PlateauMonitorStage,
+};
 pub use power::{PowerMutationalStage, StdPowerMutationalStage};
+pub use queue_cycles::{QueueCycleMetadata, QueueCycleStage};
+#[cfg(feature = "std")]
+pub use record::{RecordCommand, RecordTraceStage, RecordedTraceMetadata};
+#[cfg(feature = "std")]
+pub use runtime_config::RuntimeConfigStage;
 use serde::{Deserialize, Serialize};
+#[cfg(all(feature = "std", unix))]
+pub use state_dump::install_state_dump_handler;
+#[cfg(feature = "std")]
+pub use state_dump::{request_state_dump, StateDumpStage, STATE_DUMP_FILE};
 pub use stats::AflStatsStage;
 #[cfg(feature = "std")]
 pub use sync::*;
+pub use time_budget::{TimeBudgetMetadata, TimeBudgetStage};
 pub use tmin::{
     MapEqualityFactory, MapEqualityFeedback, StdTMinMutationalStage, TMinMutationalStage,
 };
+pub use token_sync::{import_tokens, TokenSyncMetadata, TokenSyncStage};
 pub use tracing::{ShadowTracingStage, TracingStage};
 pub use tuneable::*;
 use tuple_list::NonEmptyTuple;
@@ -44,14 +65,17 @@ use tuple_list::NonEmptyTuple;
 pub use unicode::*;
 
 use crate::{
-    corpus::{CorpusId, HasCurrentCorpusId},
+    corpus::{Corpus, CorpusId, HasCurrentCorpusId},
     events::{EventFirer, EventProcessor, EventRestarter, HasEventManagerId, ProgressReporter},
     executors::{Executor, HasObservers},
     inputs::UsesInput,
     observers::ObserversTuple,
     schedulers::Scheduler,
     stages::push::PushStage,
-    state::{HasCorpus, HasExecutions, HasLastReportTime, HasRand, State, Stoppable, UsesState},
+    state::{
+        HasCorpus, HasExecutions, HasLastReportTime, HasRand, HasSolutions, State, Stoppable,
+        UsesState,
+    },
     Error, EvaluatorObservers, ExecutesInput, ExecutionProcessor, HasMetadata, HasNamedMetadata,
     HasScheduler,
 };
@@ -65,15 +89,30 @@ pub mod calibrate;
 pub mod colorization;
 #[cfg(all(feature = "std", unix))]
 pub mod concolic;
+#[cfg(feature = "control_server")]
+pub mod control;
+#[cfg(all(unix, feature = "std"))]
+pub mod crash_analysis;
 #[cfg(feature = "std")]
 pub mod dump;
 pub mod generalization;
 pub mod generation;
 pub mod logics;
+pub mod metadata_gc;
+pub mod plateau;
 pub mod power;
+pub mod queue_cycles;
+#[cfg(feature = "std")]
+pub mod record;
+#[cfg(feature = "std")]
+pub mod runtime_config;
+#[cfg(feature = "std")]
+pub mod state_dump;
 pub mod stats;
 #[cfg(feature = "std")]
 pub mod sync;
+pub mod time_budget;
+pub mod token_sync;
 pub mod tracing;
 pub mod tuneable;
 #[cfg(feature = "unicode")]
@@ -277,6 +316,103 @@ where
     }
 }
 
+/// A type-erased [`Stage`], to assemble pipelines at runtime without spelling out the types of all stages.
+///
+/// Each call goes through one virtual call. Collect several of them with [`BoxedStageWrapper::into_inner`] into
+/// a `Vec<`[`BoxedStage`]`>`, which is a [`StagesTuple`] itself.
+pub struct BoxedStageWrapper<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+{
+    inner: Box<dyn Stage<E, EM, Z, State = Z::State, Input = Z::Input>>,
+}
+
+impl<E, EM, Z> BoxedStageWrapper<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+{
+    /// Type-erases the given [`Stage`]
+    pub fn new<ST>(stage: ST) -> Self
+    where
+        ST: Stage<E, EM, Z, State = Z::State, Input = Z::Input> + 'static,
+    {
+        Self {
+            inner: Box::new(stage),
+        }
+    }
+
+    /// The boxed [`Stage`]
+    #[must_use]
+    #[allow(clippy::type_complexity)]
+    pub fn into_inner(self) -> Box<dyn Stage<E, EM, Z, State = Z::State, Input = Z::Input>> {
+        self.inner
+    }
+}
+
+impl<E, EM, Z> fmt::Debug for BoxedStageWrapper<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxedStageWrapper").finish_non_exhaustive()
+    }
+}
+
+impl<E, EM, Z> UsesState for BoxedStageWrapper<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for BoxedStageWrapper<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+{
+    #[inline]
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        self.inner.should_restart(state)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        self.inner.clear_progress(state)
+    }
+
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        self.inner.perform(fuzzer, executor, state, manager)
+    }
+
+    #[inline]
+    fn perform_restartable(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        self.inner
+            .perform_restartable(fuzzer, executor, state, manager)
+    }
+}
+
 impl<E, EM, S, Z> StagesTuple<E, EM, S, Z>
     for Vec<Box<dyn Stage<E, EM, Z, State = S, Input = S::Input>>>
 where
@@ -695,6 +831,48 @@ impl ExecutionCountRestartHelper {
     }
 }
 
+/// The progress of a stage processing each solution once, kept in the metadata of the state,
+/// see [`for_each_new_solution`].
+pub trait SolutionProgress: SerdeAny + Default {
+    /// The last solution already processed
+    fn last_solution(&self) -> Option<CorpusId>;
+
+    /// Mark the solution `id` as processed
+    fn set_last_solution(&mut self, id: CorpusId);
+}
+
+/// Calls `process` for each solution added since the last call, in the order of the solutions corpus.
+///
+/// Used by stages post-processing new objectives, e.g. the `RecordTraceStage` and the `CrashAnalysisStage`.
+/// The progress is stored in the metadata `M` after each solution, so a stage restarted after a crash of the
+/// fuzzer continues with the next solution. Returns the number of solutions processed.
+pub fn for_each_new_solution<M, S, F>(state: &mut S, mut process: F) -> Result<usize, Error>
+where
+    M: SolutionProgress,
+    S: HasSolutions + HasMetadata,
+    F: FnMut(&mut S, CorpusId) -> Result<(), Error>,
+{
+    let last_solution = state
+        .metadata_map()
+        .get::<M>()
+        .and_then(SolutionProgress::last_solution);
+    let mut solution_id = match last_solution {
+        Some(id) => state.solutions().next(id),
+        None => state.solutions().first(),
+    };
+
+    let mut processed = 0;
+    while let Some(id) = solution_id {
+        process(state, id)?;
+        state
+            .metadata_or_insert_with(M::default)
+            .set_last_solution(id);
+        processed += 1;
+        solution_id = state.solutions().next(id);
+    }
+    Ok(processed)
+}
+
 #[cfg(test)]
 mod test {
     use alloc::borrow::Cow;
@@ -704,10 +882,15 @@ mod test {
     use serde::{Deserialize, Serialize};
 
     use crate::{
-        corpus::{Corpus, HasCurrentCorpusId, Testcase},
+        corpus::{Corpus, CorpusId, HasCurrentCorpusId, Testcase},
+        events::NopEventManager,
+        fuzzer::NopFuzzer,
         inputs::NopInput,
-        stages::{RetryCountRestartHelper, Stage},
-        state::{HasCorpus, State, StdState, UsesState},
+        stages::{
+            for_each_new_solution, BoxedStageWrapper, RetryCountRestartHelper, SolutionProgress,
+            Stage,
+        },
+        state::{HasCorpus, HasSolutions, State, StdState, UsesState},
         HasMetadata,
     };
 
@@ -789,6 +972,24 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_boxed_stage() -> Result<(), Error> {
+        let mut state = StdState::nop::<NopInput>()?;
+        let mut stage = BoxedStageWrapper::new(ResumeSucceededStage {
+            phantom: PhantomData,
+        });
+        // The stage does not run the target, any executor will do
+        let mut executor = NopFuzzer::new();
+        let mut fuzzer = NopFuzzer::new();
+        let mut mgr = NopEventManager::new();
+
+        // Progress is cleared through the box, so the second run does not look like a resume
+        for _ in 0..2 {
+            stage.perform_restartable(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        }
+        Ok(())
+    }
+
     /// Test to test retries in stages
     #[test]
     fn test_tries_progress() -> Result<(), Error> {
@@ -872,4 +1073,68 @@ mod test {
 
         Ok(())
     }
+
+    /// The progress of a stage processing solutions, for testing
+    #[derive(Serialize, Deserialize, Default, Debug)]
+    pub struct TestSolutionProgress {
+        last_solution: Option<CorpusId>,
+    }
+
+    impl_serdeany!(TestSolutionProgress);
+
+    impl SolutionProgress for TestSolutionProgress {
+        fn last_solution(&self) -> Option<CorpusId> {
+            self.last_solution
+        }
+
+        fn set_last_solution(&mut self, id: CorpusId) {
+            self.last_solution = Some(id);
+        }
+    }
+
+    #[test]
+    fn test_for_each_new_solution() -> Result<(), Error> {
+        let mut state = StdState::nop()?;
+        let first = state.solutions_mut().add(Testcase::new(NopInput {}))?;
+        let second = state.solutions_mut().add(Testcase::new(NopInput {}))?;
+
+        let mut seen = vec![];
+        let processed =
+            for_each_new_solution::<TestSolutionProgress, _, _>(&mut state, |_, id| {
+                seen.push(id);
+                Ok(())
+            })?;
+        assert_eq!(processed, 2);
+        assert_eq!(seen, [first, second]);
+
+        // Only the solutions added since are processed on the next run
+        let third = state.solutions_mut().add(Testcase::new(NopInput {}))?;
+        seen.clear();
+        for_each_new_solution::<TestSolutionProgress, _, _>(&mut state, |_, id| {
+            seen.push(id);
+            Ok(())
+        })?;
+        assert_eq!(seen, [third]);
+
+        // A failing solution is processed again, after a restart
+        let fourth = state.solutions_mut().add(Testcase::new(NopInput {}))?;
+        assert!(
+            for_each_new_solution::<TestSolutionProgress, _, _>(&mut state, |_, _| Err(
+                Error::unknown("interrupted")
+            ))
+            .is_err()
+        );
+        assert_eq!(
+            state.metadata::<TestSolutionProgress>()?.last_solution,
+            Some(third)
+        );
+        let processed =
+            for_each_new_solution::<TestSolutionProgress, _, _>(&mut state, |_, id| {
+                assert_eq!(id, fourth);
+                Ok(())
+            })?;
+        assert_eq!(processed, 1);
+
+        Ok(())
+    }
 }
@@ -4,21 +4,27 @@
 use alloc::{
     borrow::{Cow, ToOwned},
     string::ToString,
+    vec::Vec,
 };
 use core::{marker::PhantomData, num::NonZeroUsize};
 
 use libafl_bolts::{rands::Rand, Named};
+use serde::Serialize;
 
 use crate::{
     corpus::{Corpus, CorpusId, Testcase},
-    fuzzer::Evaluator,
+    events::EventFirer,
+    executors::BatchExecutor,
+    fuzzer::{Evaluator, ExecutionProcessor, HasScheduler},
     inputs::Input,
     mark_feature_time,
     mutators::{MultiMutator, MutationResult, Mutator},
     nonzero,
+    observers::ObserversTuple,
+    schedulers::Scheduler,
     stages::{RetryCountRestartHelper, Stage},
     start_timer,
-    state::{HasCorpus, HasCurrentTestcase, HasExecutions, HasRand, UsesState},
+    state::{HasCorpus, HasCurrentTestcase, HasExecutions, HasRand, HasRuntimeConfig, UsesState},
     Error, HasMetadata, HasNamedMetadata,
 };
 #[cfg(feature = "introspection")]
@@ -139,7 +145,7 @@ where
 
             // Time is measured directly the `evaluate_input` function
             let (untransformed, post) = input.try_transform_into(state)?;
-            let (_, corpus_id) = fuzzer.evaluate_input(state, executor, manager, untransformed)?;
+            let (_, corpus_id) = fuzzer.evaluate_mutant(state, executor, manager, untransformed)?;
 
             start_timer!(state);
             self.mutator_mut().post_exec(state, corpus_id)?;
@@ -190,9 +196,14 @@ where
         &mut self.mutator
     }
 
-    /// Gets the number of iterations as a random number
+    /// Gets the number of iterations as a random number,
+    /// bounded by the `max_iterations` of the [`crate::state::RuntimeConfig`], if set
     fn iterations(&self, state: &mut Self::State) -> Result<usize, Error> {
-        Ok(1 + state.rand_mut().below(self.max_iterations))
+        let max_iterations = state
+            .runtime_config()
+            .max_iterations
+            .unwrap_or(self.max_iterations);
+        Ok(1 + state.rand_mut().below(max_iterations))
     }
 }
 
@@ -376,7 +387,7 @@ where
         for new_input in generated {
             // Time is measured directly the `evaluate_input` function
             let (untransformed, post) = new_input.try_transform_into(state)?;
-            let (_, corpus_id) = fuzzer.evaluate_input(state, executor, manager, untransformed)?;
+            let (_, corpus_id) = fuzzer.evaluate_mutant(state, executor, manager, untransformed)?;
             self.mutator.multi_post_exec(state, corpus_id)?;
             post.post_exec(state, corpus_id)?;
         }
@@ -414,3 +425,261 @@ impl<E, EM, I, M, Z> MultiMutationalStage<E, EM, I, M, Z> {
         }
     }
 }
+
+/// A mutational stage submitting its mutated inputs in batches to a [`BatchExecutor`].
+///
+/// Each round, it mutates the current testcase a random amount of times, like the [`StdMutationalStage`], and runs
+/// the mutants in batches of up to [`BatchExecutor::max_batch_size`] inputs. Every input is then evaluated on its own,
+/// with the observer snapshot the executor returned for it.
+#[derive(Clone, Debug)]
+pub struct BatchMutationalStage<E, EM, I, M, Z> {
+    name: Cow<'static, str>,
+    mutator: M,
+    max_iterations: NonZeroUsize,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, I, Z)>,
+}
+
+/// The unique id for batch mutational stage
+static mut BATCH_MUTATIONAL_STAGE_ID: usize = 0;
+/// The name for batch mutational stage
+pub static BATCH_MUTATIONAL_STAGE_NAME: &str = "batchmutational";
+
+impl<E, EM, I, M, Z> UsesState for BatchMutationalStage<E, EM, I, M, Z>
+where
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, I, M, Z> Named for BatchMutationalStage<E, EM, I, M, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, I, M, Z> BatchMutationalStage<E, EM, I, M, Z>
+where
+    E: BatchExecutor<EM, Z, State = Self::State>,
+    E::Observers: ObserversTuple<Self::Input, Self::State> + Serialize,
+    EM: EventFirer<State = Self::State>,
+    M: Mutator<I, Self::State>,
+    Z: ExecutionProcessor<EM, E::Observers> + HasScheduler,
+    Z::State: HasCorpus + HasRand + HasExecutions + HasCurrentTestcase,
+    I: MutatedTransform<Self::Input, Self::State> + Clone,
+    <<Self as UsesState>::State as HasCorpus>::Corpus: Corpus<Input = Self::Input>,
+{
+    /// Runs a batch of mutants, and evaluates each of them
+    fn run_batch(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+        batch: &mut Vec<Self::Input>,
+        posts: &mut Vec<I::Post>,
+    ) -> Result<(), Error> {
+        start_timer!(state);
+        let results = executor.run_batch(fuzzer, state, manager, batch)?;
+        mark_feature_time!(state, PerfFeature::TargetExecution);
+
+        if results.len() != batch.len() {
+            return Err(Error::illegal_state(format!(
+                "The executor returned {} results for a batch of {} inputs",
+                results.len(),
+                batch.len()
+            )));
+        }
+
+        for ((input, post), (exit_kind, observers)) in
+            batch.drain(..).zip(posts.drain(..)).zip(results)
+        {
+            fuzzer
+                .scheduler_mut()
+                .on_evaluation(state, &input, &observers)?;
+            let (_, corpus_id) =
+                fuzzer.evaluate_execution(state, manager, input, &observers, &exit_kind, true)?;
+
+            start_timer!(state);
+            self.mutator.post_exec(state, corpus_id)?;
+            post.post_exec(state, corpus_id)?;
+            mark_feature_time!(state, PerfFeature::MutatePostExec);
+        }
+        Ok(())
+    }
+}
+
+impl<E, EM, I, M, Z> Stage<E, EM, Z> for BatchMutationalStage<E, EM, I, M, Z>
+where
+    E: BatchExecutor<EM, Z, State = Self::State>,
+    E::Observers: ObserversTuple<Self::Input, Self::State> + Serialize,
+    EM: EventFirer<State = Self::State>,
+    M: Mutator<I, Self::State>,
+    Z: ExecutionProcessor<EM, E::Observers> + HasScheduler,
+    Z::State: HasCorpus + HasRand + HasExecutions + HasNamedMetadata + HasCurrentTestcase,
+    I: MutatedTransform<Self::Input, Self::State> + Clone,
+    <<Self as UsesState>::State as HasCorpus>::Corpus: Corpus<Input = Self::Input>, //delete me
+{
+    #[inline]
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        // Make sure we don't get stuck crashing on a single testcase
+        RetryCountRestartHelper::should_restart(state, &self.name, 3)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let max_iterations = state
+            .runtime_config()
+            .max_iterations
+            .unwrap_or(self.max_iterations);
+        let num = 1 + state.rand_mut().below(max_iterations);
+        let batch_size = executor.max_batch_size().max(1);
+
+        let mut testcase = state.current_testcase_mut()?;
+        let Ok(input) = I::try_transform_from(&mut testcase, state) else {
+            return Ok(());
+        };
+        drop(testcase);
+
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut posts = Vec::with_capacity(batch_size);
+        for _ in 0..num {
+            let mut input = input.clone();
+
+            start_timer!(state);
+            let mutated = self.mutator.mutate(state, &mut input)?;
+            mark_feature_time!(state, PerfFeature::Mutate);
+
+            if mutated == MutationResult::Skipped {
+                continue;
+            }
+
+            let (untransformed, post) = input.try_transform_into(state)?;
+            batch.push(untransformed);
+            posts.push(post);
+            if batch.len() == batch_size {
+                self.run_batch(fuzzer, executor, state, manager, &mut batch, &mut posts)?;
+            }
+        }
+        if !batch.is_empty() {
+            self.run_batch(fuzzer, executor, state, manager, &mut batch, &mut posts)?;
+        }
+
+        #[cfg(feature = "introspection")]
+        state.introspection_monitor_mut().finish_stage();
+
+        Ok(())
+    }
+}
+
+impl<E, EM, M, Z> BatchMutationalStage<E, EM, Z::Input, M, Z>
+where
+    Z: UsesState,
+{
+    /// Creates a new [`BatchMutationalStage`]
+    pub fn new(mutator: M) -> Self {
+        Self::transforming_with_max_iterations(mutator, nonzero!(DEFAULT_MUTATIONAL_MAX_ITERATIONS))
+    }
+
+    /// Creates a new [`BatchMutationalStage`] with the given max iterations
+    pub fn with_max_iterations(mutator: M, max_iterations: NonZeroUsize) -> Self {
+        Self::transforming_with_max_iterations(mutator, max_iterations)
+    }
+}
+
+impl<E, EM, I, M, Z> BatchMutationalStage<E, EM, I, M, Z> {
+    /// Creates a new transforming [`BatchMutationalStage`] with the given max iterations
+    pub fn transforming_with_max_iterations(mutator: M, max_iterations: NonZeroUsize) -> Self {
+        // unsafe but impossible that you create two threads both instantiating this instance
+        let stage_id = unsafe {
+            let ret = BATCH_MUTATIONAL_STAGE_ID;
+            BATCH_MUTATIONAL_STAGE_ID += 1;
+            ret
+        };
+        Self {
+            name: Cow::Owned(
+                BATCH_MUTATIONAL_STAGE_NAME.to_owned() + ":" + stage_id.to_string().as_str(),
+            ),
+            mutator,
+            max_iterations,
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use super::BatchMutationalStage;
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{ExitKind, InProcessExecutor, SequentialBatchExecutor},
+        feedbacks::ConstFeedback,
+        fuzzer::Fuzzer,
+        inputs::BytesInput,
+        mutators::{mutations::BitFlipMutator, StdScheduledMutator},
+        schedulers::RandScheduler,
+        state::{HasExecutions, StdState},
+        StdFuzzer,
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_batch_mutational_stage() {
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        corpus.add(Testcase::new(vec![0; 4].into())).unwrap();
+
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::<BytesInput>::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut mgr = NopEventManager::new();
+        let mut fuzzer = StdFuzzer::new(RandScheduler::new(), feedback, objective);
+
+        let mut runs = 0u64;
+        let mut harness = |_input: &BytesInput| {
+            runs += 1;
+            ExitKind::Ok
+        };
+        let executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+        let mut executor = SequentialBatchExecutor::new(executor, 3);
+
+        let mutator = StdScheduledMutator::new(tuple_list!(BitFlipMutator::new()));
+        let mut stages = tuple_list!(BatchMutationalStage::new(mutator));
+        for _ in 0..10 {
+            fuzzer
+                .fuzz_one(&mut stages, &mut executor, &mut state, &mut mgr)
+                .unwrap();
+        }
+        drop(executor);
+
+        // Each mutant ran once, and was counted once
+        assert!(runs > 0);
+        assert_eq!(*state.executions(), runs);
+    }
+}
@@ -0,0 +1,382 @@
+//! The [`PlateauMonitorStage`] detects when a campaign stops finding new coverage, and escalates its strategy.
+//!
+//! A plateau is a stretch of time, or of executions, without a new corpus entry. On each plateau, the stage fires the
+//! next of its configured [`Escalation`]s as an [`Event::CustomBuf`] with the [`ESCALATION_TAG`], and applies it
+//! locally. Other clients apply it after calling [`install_escalation_handler`] on their event manager, so that
+//! e.g. the first client to stall enables cmplog for everyone, like an operator would.
+//!
+//! Escalations are recorded in the [`EscalationMetadata`]. Expensive stages that should only run once the campaign
+//! stalls go into an [`crate::stages::IfStage`]:
+//!
+//! ```rust,ignore
+//! install_escalation_handler(&mut mgr);
+//! let cmplog = IfStage::new(
+//!     |_, _, state: &mut StdState<_, _, _, _>, _| Ok(EscalationMetadata::cmplog_enabled(state)),
+//!     tuple_list!(tracing, i2s),
+//! );
+//! let plateau = PlateauMonitorStage::new(Duration::from_secs(30 * 60))
+//!     .escalate(Escalation::EnableCmpLog)
+//!     .escalate(Escalation::SetPowerSchedule(BaseSchedule::EXPLORE))
+//!     .escalate(Escalation::HavocStacking(9));
+//! let mut stages = tuple_list!(calibration, power, cmplog, plateau);
+//! ```
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{marker::PhantomData, time::Duration};
+
+use libafl_bolts::{current_time, impl_serdeany};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Corpus,
+    events::{CustomBufEventResult, Event, EventFirer, HasCustomBufHandlers},
+    inputs::UsesInput,
+    mutators::TuneableScheduledMutatorMetadata,
+    schedulers::powersched::{BaseSchedule, SchedulerMetadata},
+    stages::Stage,
+    state::{HasCorpus, HasExecutions, UsesState},
+    Error, HasMetadata,
+};
+
+/// The tag of [`Event::CustomBuf`]s carrying an [`Escalation`]
+pub const ESCALATION_TAG: &str = "libafl_escalation";
+
+/// A change of strategy, once the campaign reached a plateau
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Escalation {
+    /// Enable the cmplog stages, see [`EscalationMetadata::cmplog_enabled`]
+    EnableCmpLog,
+    /// Enable the concolic stages, see [`EscalationMetadata::concolic_enabled`]
+    EnableConcolic,
+    /// Switch the power schedule of the [`SchedulerMetadata`]
+    SetPowerSchedule(BaseSchedule),
+    /// Stack up to `2^n` havoc mutations, for a [`crate::mutators::TuneableScheduledMutator`]
+    HavocStacking(u32),
+}
+
+impl Escalation {
+    /// Serialize this escalation and wrap it into an [`Event::CustomBuf`]
+    pub fn to_event<I>(&self) -> Result<Event<I>, Error>
+    where
+        I: crate::inputs::Input,
+    {
+        Ok(Event::CustomBuf {
+            buf: postcard::to_allocvec(self)?,
+            tag: ESCALATION_TAG.into(),
+        })
+    }
+
+    /// Apply this escalation to the given state, unless it was applied before.
+    ///
+    /// This also advances the [`PlateauMetadata`], so that a [`PlateauMonitorStage`] won't fire this escalation
+    /// again on its next plateau. Returns `true` if the escalation was newly applied.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn apply<S>(&self, state: &mut S) -> bool
+    where
+        S: HasMetadata,
+    {
+        if state
+            .metadata::<EscalationMetadata>()
+            .is_ok_and(|meta| meta.applied.contains(self))
+        {
+            return false;
+        }
+        match self {
+            Escalation::SetPowerSchedule(base) => {
+                if let Ok(meta) = state.metadata_mut::<SchedulerMetadata>() {
+                    meta.set_base_schedule(*base);
+                }
+            }
+            Escalation::HavocStacking(pow) => {
+                if let Ok(meta) = state.metadata_mut::<TuneableScheduledMutatorMetadata>() {
+                    // Each stack depth from `2^1` to `2^pow` is equally likely
+                    let pow = (*pow).clamp(1, 31);
+                    meta.iters = None;
+                    meta.iter_probabilities_pow_cumulative =
+                        (1..=pow).map(|i| i as f32 / pow as f32).collect::<Vec<_>>();
+                }
+            }
+            Escalation::EnableCmpLog | Escalation::EnableConcolic => {}
+        }
+        state
+            .metadata_or_insert_with(EscalationMetadata::default)
+            .apply(self);
+        state
+            .metadata_or_insert_with(PlateauMetadata::default)
+            .escalations += 1;
+        true
+    }
+}
+
+/// The escalations applied to this client so far
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EscalationMetadata {
+    cmplog: bool,
+    concolic: bool,
+    applied: Vec<Escalation>,
+}
+
+impl_serdeany!(EscalationMetadata);
+
+impl EscalationMetadata {
+    /// Update this metadata according to the escalation, ignoring escalations applied before
+    pub fn apply(&mut self, escalation: &Escalation) {
+        if self.applied.contains(escalation) {
+            return;
+        }
+        match escalation {
+            Escalation::EnableCmpLog => self.cmplog = true,
+            Escalation::EnableConcolic => self.concolic = true,
+            Escalation::SetPowerSchedule(_) | Escalation::HavocStacking(_) => {}
+        }
+        self.applied.push(*escalation);
+    }
+
+    /// The escalations applied so far, in order
+    #[must_use]
+    pub fn applied(&self) -> &[Escalation] {
+        &self.applied
+    }
+
+    /// If the cmplog stages of the given state were enabled by an escalation
+    #[must_use]
+    pub fn cmplog_enabled<S>(state: &S) -> bool
+    where
+        S: HasMetadata,
+    {
+        state.metadata::<Self>().is_ok_and(|meta| meta.cmplog)
+    }
+
+    /// If the concolic stages of the given state were enabled by an escalation
+    #[must_use]
+    pub fn concolic_enabled<S>(state: &S) -> bool
+    where
+        S: HasMetadata,
+    {
+        state.metadata::<Self>().is_ok_and(|meta| meta.concolic)
+    }
+}
+
+/// Adds a custom buf handler to the event manager, applying all incoming [`Escalation`]s to the state
+pub fn install_escalation_handler<EM>(mgr: &mut EM)
+where
+    EM: HasCustomBufHandlers,
+    EM::State: HasMetadata,
+{
+    mgr.add_custom_buf_handler(Box::new(|state, tag, buf| {
+        if tag != ESCALATION_TAG {
+            return Ok(CustomBufEventResult::Next);
+        }
+        let escalation: Escalation = postcard::from_bytes(buf)?;
+        if escalation.apply(state) {
+            log::info!("Received escalation {escalation:?}");
+        }
+        Ok(CustomBufEventResult::Handled)
+    }));
+}
+
+/// The progress of the campaign, as seen by a [`PlateauMonitorStage`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct PlateauMetadata {
+    /// The corpus size at the last find
+    pub corpus_size: usize,
+    /// The time of the last find, or of the last escalation
+    pub since_time: Duration,
+    /// The executions at the last find, or at the last escalation
+    pub since_executions: u64,
+    /// The amount of escalations applied to this client, fired by itself or received from others
+    pub escalations: usize,
+}
+
+impl_serdeany!(PlateauMetadata);
+
+/// A stage detecting coverage plateaus, and firing the next configured [`Escalation`] on each of them.
+///
+/// New coverage is approximated by new corpus entries. After an escalation, the next one needs a new plateau.
+#[derive(Debug, Clone)]
+pub struct PlateauMonitorStage<E, EM, Z> {
+    time: Option<Duration>,
+    executions: Option<u64>,
+    escalations: Vec<Escalation>,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> UsesState for PlateauMonitorStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for PlateauMonitorStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: EventFirer<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasCorpus + HasExecutions + HasMetadata,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let corpus_size = state.corpus().count();
+        let executions = *state.executions();
+        let now = current_time();
+        let meta = state.metadata_or_insert_with(|| PlateauMetadata {
+            corpus_size,
+            since_time: now,
+            since_executions: executions,
+            escalations: 0,
+        });
+
+        if corpus_size > meta.corpus_size {
+            meta.corpus_size = corpus_size;
+            meta.since_time = now;
+            meta.since_executions = executions;
+            return Ok(());
+        }
+        if !self.plateau(meta, now, executions) {
+            return Ok(());
+        }
+        let Some(escalation) = self.escalations.get(meta.escalations).copied() else {
+            return Ok(());
+        };
+        meta.since_time = now;
+        meta.since_executions = executions;
+
+        if !escalation.apply(state) {
+            // Another client escalated this way before, move on to the next one on the next plateau
+            state.metadata_mut::<PlateauMetadata>()?.escalations += 1;
+            return Ok(());
+        }
+        log::info!("Coverage plateau reached, escalating with {escalation:?}");
+        manager.fire(
+            state,
+            escalation.to_event::<<Self::State as UsesInput>::Input>()?,
+        )
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not running the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not running the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> PlateauMonitorStage<E, EM, Z> {
+    /// Create a new [`PlateauMonitorStage`], detecting a plateau after `time` without a new corpus entry.
+    ///
+    /// Add the escalations with [`Self::escalate`].
+    #[must_use]
+    pub fn new(time: Duration) -> Self {
+        Self {
+            time: Some(time),
+            executions: None,
+            escalations: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Create a new [`PlateauMonitorStage`], detecting a plateau after `executions` without a new corpus entry.
+    #[must_use]
+    pub fn with_executions(executions: u64) -> Self {
+        Self {
+            time: None,
+            executions: Some(executions),
+            escalations: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Also detect a plateau after `executions` without a new corpus entry, whichever comes first
+    #[must_use]
+    pub fn or_executions(mut self, executions: u64) -> Self {
+        self.executions = Some(executions);
+        self
+    }
+
+    /// Add an escalation, fired on the next plateau after all the previously added ones
+    #[must_use]
+    pub fn escalate(mut self, escalation: Escalation) -> Self {
+        self.escalations.push(escalation);
+        self
+    }
+
+    /// If the campaign is on a plateau
+    fn plateau(&self, meta: &PlateauMetadata, now: Duration, executions: u64) -> bool {
+        self.time
+            .is_some_and(|time| now.saturating_sub(meta.since_time) >= time)
+            || self
+                .executions
+                .is_some_and(|execs| executions.saturating_sub(meta.since_executions) >= execs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{Escalation, EscalationMetadata, PlateauMetadata, PlateauMonitorStage};
+    use crate::{schedulers::powersched::BaseSchedule, state::NopState, HasMetadata};
+
+    #[test]
+    fn test_plateau() {
+        let stage = PlateauMonitorStage::<(), (), ()>::new(Duration::from_secs(60))
+            .or_executions(1000)
+            .escalate(Escalation::EnableCmpLog);
+        let meta = PlateauMetadata {
+            since_time: Duration::from_secs(100),
+            since_executions: 500,
+            ..PlateauMetadata::default()
+        };
+        assert!(!stage.plateau(&meta, Duration::from_secs(159), 1499));
+        assert!(stage.plateau(&meta, Duration::from_secs(160), 1499));
+        assert!(stage.plateau(&meta, Duration::from_secs(159), 1500));
+    }
+
+    #[test]
+    fn test_escalation_apply() {
+        let mut state = NopState::<()>::new();
+        assert!(Escalation::EnableCmpLog.apply(&mut state));
+        assert!(EscalationMetadata::cmplog_enabled(&state));
+        assert_eq!(state.metadata::<PlateauMetadata>().unwrap().escalations, 1);
+
+        // Receiving the same escalation from another client changes nothing
+        assert!(!Escalation::EnableCmpLog.apply(&mut state));
+        assert_eq!(state.metadata::<PlateauMetadata>().unwrap().escalations, 1);
+        assert_eq!(
+            state.metadata::<EscalationMetadata>().unwrap().applied(),
+            &[Escalation::EnableCmpLog]
+        );
+
+        assert!(Escalation::HavocStacking(9).apply(&mut state));
+        assert_eq!(state.metadata::<PlateauMetadata>().unwrap().escalations, 2);
+    }
+
+    #[test]
+    fn test_escalation_roundtrip() {
+        let escalation = Escalation::SetPowerSchedule(BaseSchedule::EXPLORE);
+        let buf = postcard::to_allocvec(&escalation).unwrap();
+        assert_eq!(
+            postcard::from_bytes::<Escalation>(&buf).unwrap(),
+            escalation
+        );
+    }
+}
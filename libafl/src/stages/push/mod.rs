@@ -272,6 +272,8 @@ where
             self.push_stage_helper_mut().end_of_iter(shared_state, true);
             return Some(Err(err));
         }
+        // From now on, each call evaluates the input handed out by the previous one
+        self.push_stage_helper_mut().initialized = true;
 
         //for i in 0..num {
         let ret = self.pre_exec(
@@ -302,6 +304,10 @@ where
                 self.push_stage_helper_mut().end_of_iter(shared_state, true);
                 return Some(Err(err));
             };
+        } else if let Some(Err(err)) = ret {
+            // No input was handed out, so there is nothing to evaluate in the next call
+            self.push_stage_helper_mut().end_of_iter(shared_state, true);
+            return Some(Err(err));
         } else {
             self.push_stage_helper_mut().reset_exit_kind();
         }
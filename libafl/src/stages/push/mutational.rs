@@ -148,7 +148,9 @@ where
         mark_feature_time!(state, PerfFeature::GetInputFromCorpus);
 
         start_timer!(state);
-        self.mutator.mutate(state, &mut input).unwrap();
+        if let Err(err) = self.mutator.mutate(state, &mut input) {
+            return Some(Err(err));
+        }
         mark_feature_time!(state, PerfFeature::Mutate);
 
         self.push_stage_helper_mut()
@@ -0,0 +1,208 @@
+//! The [`QueueCycleStage`] follows the queue cycles of the scheduler, see [`HasQueueCycles`].
+//!
+//! It counts the cycles without any new testcase, which tell that the campaign stalls: the stage can switch the
+//! power schedule then, and [`QueueCycleMetadata::stale`] can enable expensive stages in an
+//! [`crate::stages::IfStage`]:
+//!
+//! ```rust,ignore
+//! let deterministic = IfStage::new(
+//!     |_, _, state: &mut StdState<_, _, _, _>, _| Ok(QueueCycleMetadata::stale(state, 3)),
+//!     tuple_list!(colorization, tracing, i2s),
+//! );
+//! let mut stages = tuple_list!(calibration, power, QueueCycleStage::new(), deterministic);
+//! ```
+
+use alloc::borrow::Cow;
+use core::marker::PhantomData;
+
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Corpus,
+    events::{Event, EventFirer},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    schedulers::{powersched::BaseSchedule, HasQueueCycles, SchedulerMetadata},
+    stages::Stage,
+    state::{HasCorpus, UsesState},
+    Error, HasMetadata, HasScheduler,
+};
+
+/// The queue cycles of the campaign, as seen by the [`QueueCycleStage`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct QueueCycleMetadata {
+    /// The queue cycles done by the scheduler
+    pub cycles_done: u64,
+    /// The queue cycles done in a row without a new testcase
+    pub cycles_wo_finds: u64,
+    /// The size of the corpus when the current cycle started
+    corpus_size: usize,
+}
+
+impl_serdeany!(QueueCycleMetadata);
+
+impl QueueCycleMetadata {
+    /// Records that the scheduler is at its `queue_cycles`th cycle, with a corpus of `corpus_size` testcases.
+    ///
+    /// Returns if a queue cycle was completed since the last update.
+    pub fn update(&mut self, queue_cycles: u64, corpus_size: usize) -> bool {
+        if queue_cycles <= self.cycles_done {
+            return false;
+        }
+        self.cycles_done = queue_cycles;
+        if corpus_size > self.corpus_size {
+            self.cycles_wo_finds = 0;
+        } else {
+            self.cycles_wo_finds += 1;
+        }
+        self.corpus_size = corpus_size;
+        true
+    }
+
+    /// If the campaign went at least `cycles` queue cycles without a new testcase
+    #[must_use]
+    pub fn stale<S>(state: &S, cycles: u64) -> bool
+    where
+        S: HasMetadata,
+    {
+        state
+            .metadata_map()
+            .get::<Self>()
+            .is_some_and(|meta| meta.cycles_wo_finds >= cycles)
+    }
+}
+
+/// A stage recording the queue cycles of the scheduler into the [`QueueCycleMetadata`], and reporting them as the
+/// `cycles_done` and `cycles_wo_finds` user stats.
+///
+/// Place it after the mutational stages. With [`QueueCycleStage::switch_schedule`], it switches the power schedule
+/// of the [`SchedulerMetadata`] once the campaign stalls.
+#[derive(Debug, Clone)]
+pub struct QueueCycleStage<E, EM, Z> {
+    switch_schedule: Option<(u64, BaseSchedule)>,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> UsesState for QueueCycleStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for QueueCycleStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: EventFirer<State = Self::State>,
+    Z: HasScheduler<State = Self::State>,
+    Z::Scheduler: HasQueueCycles,
+    Self::State: HasCorpus + HasMetadata,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let queue_cycles = fuzzer.scheduler().queue_cycles();
+        let corpus_size = state.corpus().count();
+        let meta = state.metadata_or_insert_with(QueueCycleMetadata::default);
+        if !meta.update(queue_cycles, corpus_size) {
+            return Ok(());
+        }
+        let (cycles_done, cycles_wo_finds) = (meta.cycles_done, meta.cycles_wo_finds);
+
+        if let Some((cycles, base)) = self.switch_schedule {
+            if cycles_wo_finds == cycles {
+                if let Some(psmeta) = state.metadata_map_mut().get_mut::<SchedulerMetadata>() {
+                    psmeta.set_base_schedule(base);
+                    log::info!(
+                        "No new testcase in {cycles} queue cycles, switched to the {base:?} schedule"
+                    );
+                }
+            }
+        }
+
+        // Across clients, the campaign only stalls if all of them do
+        for (name, value, op) in [
+            ("cycles_done", cycles_done, AggregatorOps::Max),
+            ("cycles_wo_finds", cycles_wo_finds, AggregatorOps::Min),
+        ] {
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::from(name),
+                    value: UserStats::new(UserStatsValue::Number(value), op),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not running the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not running the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> QueueCycleStage<E, EM, Z> {
+    /// Create a new [`QueueCycleStage`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            switch_schedule: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Switch the power schedule to `base` once the campaign went `cycles` queue cycles without a new testcase,
+    /// e.g. from `fast` to `explore`.
+    #[must_use]
+    pub fn switch_schedule(mut self, cycles: u64, base: BaseSchedule) -> Self {
+        self.switch_schedule = Some((cycles, base));
+        self
+    }
+}
+
+impl<E, EM, Z> Default for QueueCycleStage<E, EM, Z> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QueueCycleMetadata;
+
+    #[test]
+    fn test_queue_cycles() {
+        let mut meta = QueueCycleMetadata::default();
+        assert!(!meta.update(0, 2));
+        // The initial corpus counts as a find
+        assert!(meta.update(1, 2));
+        assert_eq!((meta.cycles_done, meta.cycles_wo_finds), (1, 0));
+
+        // No new testcase
+        assert!(!meta.update(1, 2));
+        assert!(meta.update(2, 2));
+        assert_eq!((meta.cycles_done, meta.cycles_wo_finds), (2, 1));
+
+        // A new testcase resets the cycles without finds
+        assert!(!meta.update(2, 3));
+        assert!(meta.update(3, 3));
+        assert_eq!((meta.cycles_done, meta.cycles_wo_finds), (3, 0));
+    }
+}
@@ -0,0 +1,340 @@
+//! The [`RecordTraceStage`] records a replayable trace of each new objective, for time-travel debugging.
+//!
+//! Each new solution is re-run under a recorder, such as [`rr`](https://rr-project.org/) or the record/replay
+//! mode of `qemu-system`, in a separate process. The trace directory is stored in the
+//! [`RecordedTraceMetadata`] of the solution, so that the crash can be replayed interactively,
+//! e.g. with `rr replay <trace_dir>`, without finding it again.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{marker::PhantomData, time::Duration};
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::Instant,
+};
+
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId},
+    inputs::{Input, UsesInput},
+    stages::{for_each_new_solution, SolutionProgress, Stage},
+    state::{HasSolutions, UsesState},
+    Error, HasMetadata,
+};
+
+/// The placeholder in the arguments of a [`RecordCommand`] for the file containing the input
+pub const INPUT_PLACEHOLDER: &str = "@@";
+
+/// The placeholder in the arguments of a [`RecordCommand`] for the trace directory
+pub const TRACE_PLACEHOLDER: &str = "@TRACE@";
+
+/// The command recording a run of the target on an input
+#[derive(Debug, Clone)]
+pub struct RecordCommand {
+    recorder: String,
+    program: OsString,
+    args: Vec<OsString>,
+    create_trace_dir: bool,
+}
+
+impl RecordCommand {
+    /// A custom recorder. In `args`, [`INPUT_PLACEHOLDER`] is replaced by the input file,
+    /// and [`TRACE_PLACEHOLDER`] by the trace directory, which does not exist yet.
+    /// The input is also passed on `stdin`.
+    pub fn new<P, A, AS>(recorder: &str, program: P, args: A) -> Self
+    where
+        P: Into<OsString>,
+        A: IntoIterator<Item = AS>,
+        AS: Into<OsString>,
+    {
+        Self {
+            recorder: recorder.to_string(),
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            create_trace_dir: false,
+        }
+    }
+
+    /// Create the trace directory before the recording starts, for recorders that only write files into it
+    #[must_use]
+    pub fn create_trace_dir(mut self, create_trace_dir: bool) -> Self {
+        self.create_trace_dir = create_trace_dir;
+        self
+    }
+
+    /// Record the target with `rr record`, the trace can be replayed with `rr replay <trace_dir>`.
+    ///
+    /// The `target_args` of the target use [`INPUT_PLACEHOLDER`] for the input file.
+    pub fn rr<P, A, AS>(target: P, target_args: A) -> Self
+    where
+        P: Into<OsString>,
+        A: IntoIterator<Item = AS>,
+        AS: Into<OsString>,
+    {
+        let mut args: Vec<OsString> = vec!["record".into(), "-o".into(), TRACE_PLACEHOLDER.into()];
+        args.push(target.into());
+        args.extend(target_args.into_iter().map(Into::into));
+        Self::new("rr", "rr", args)
+    }
+
+    /// Record an emulated target with the record/replay mode of `qemu-system`.
+    ///
+    /// The replay log is written to `replay.bin` in the trace directory, replay it by starting qemu with the
+    /// same arguments and `-icount shift=auto,rr=replay,rrfile=<trace_dir>/replay.bin`.
+    /// The `qemu_args` use [`INPUT_PLACEHOLDER`] for the input file, e.g. to attach it as a drive.
+    pub fn qemu_system<P, A, AS>(qemu: P, qemu_args: A) -> Self
+    where
+        P: Into<OsString>,
+        A: IntoIterator<Item = AS>,
+        AS: Into<OsString>,
+    {
+        let mut args: Vec<OsString> = qemu_args.into_iter().map(Into::into).collect();
+        args.push("-icount".into());
+        args.push(format!("shift=auto,rr=record,rrfile={TRACE_PLACEHOLDER}/replay.bin").into());
+        Self::new("qemu", qemu, args).create_trace_dir(true)
+    }
+
+    /// The name of the recorder, stored in [`RecordedTraceMetadata::recorder`]
+    #[must_use]
+    pub fn recorder(&self) -> &str {
+        &self.recorder
+    }
+
+    fn command(&self, input_file: &Path, trace_dir: &Path) -> Command {
+        let replace = |arg: &OsString| {
+            let Some(arg) = arg.to_str() else {
+                return arg.clone();
+            };
+            arg.replace(INPUT_PLACEHOLDER, &input_file.to_string_lossy())
+                .replace(TRACE_PLACEHOLDER, &trace_dir.to_string_lossy())
+                .into()
+        };
+        let mut command = Command::new(replace(&self.program));
+        command.args(self.args.iter().map(replace));
+        command
+    }
+}
+
+/// The replayable trace of a solution, recorded by the [`RecordTraceStage`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedTraceMetadata {
+    /// The directory of the trace
+    pub trace_dir: PathBuf,
+    /// The recorder that wrote the trace, e.g. `rr`
+    pub recorder: String,
+    /// The exit code of the recorded run, `None` if it was killed by a signal or timed out
+    pub exit_code: Option<i32>,
+}
+
+impl_serdeany!(RecordedTraceMetadata);
+
+/// The last solution already recorded by the [`RecordTraceStage`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct RecordTraceProgressMetadata {
+    last_solution: Option<CorpusId>,
+}
+
+impl_serdeany!(RecordTraceProgressMetadata);
+
+impl SolutionProgress for RecordTraceProgressMetadata {
+    fn last_solution(&self) -> Option<CorpusId> {
+        self.last_solution
+    }
+
+    fn set_last_solution(&mut self, id: CorpusId) {
+        self.last_solution = Some(id);
+    }
+}
+
+/// A stage recording a replayable trace of each new solution, see the [module-level documentation](self).
+///
+/// The recorded run is not part of the campaign, it does not count as an execution.
+/// Add it after the fuzzing stages. Failing recordings are logged, but do not stop the fuzzer.
+#[derive(Debug)]
+pub struct RecordTraceStage<EM, Z> {
+    command: RecordCommand,
+    traces_dir: PathBuf,
+    timeout: Duration,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+impl<EM, Z> UsesState for RecordTraceStage<EM, Z>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for RecordTraceStage<EM, Z>
+where
+    EM: UsesState,
+    E: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasSolutions + HasMetadata,
+    <Self::State as HasSolutions>::Solutions: Corpus<Input = <Self::State as UsesInput>::Input>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        for_each_new_solution::<RecordTraceProgressMetadata, _, _>(state, |state, id| {
+            if let Some(meta) = self.record(state, id)? {
+                // Replace the testcase, so that on-disk solutions store the metadata, too
+                let mut testcase = state.solutions().get(id)?.borrow().clone();
+                testcase.add_metadata(meta);
+                state.solutions_mut().replace(id, testcase)?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // The target runs in a separate process, and the progress is stored after each solution
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<EM, Z> RecordTraceStage<EM, Z>
+where
+    EM: UsesState,
+{
+    /// Create a new [`RecordTraceStage`], writing the traces to subdirectories of `traces_dir`.
+    ///
+    /// Recordings taking longer than `timeout` are killed, their trace is probably incomplete.
+    pub fn new<P>(command: RecordCommand, traces_dir: P, timeout: Duration) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let traces_dir = traces_dir.into();
+        fs::create_dir_all(&traces_dir)?;
+        Ok(Self {
+            command,
+            traces_dir,
+            timeout,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Record the solution `id`, returns `None` if the recorder could not be started
+    fn record(
+        &self,
+        state: &EM::State,
+        id: CorpusId,
+    ) -> Result<Option<RecordedTraceMetadata>, Error>
+    where
+        EM::State: HasSolutions,
+        <EM::State as HasSolutions>::Solutions: Corpus<Input = <EM::State as UsesInput>::Input>,
+    {
+        let testcase = state.solutions().get(id)?.borrow();
+        if let Some(meta) = testcase.metadata_map().get::<RecordedTraceMetadata>() {
+            return Ok(Some(meta.clone()));
+        }
+        let name = testcase
+            .filename()
+            .clone()
+            .unwrap_or_else(|| format!("id_{id}"));
+        drop(testcase);
+        let input = state.solutions().cloned_input_for_id(id)?;
+
+        let trace_dir = self.traces_dir.join(format!("{name}.trace"));
+        if trace_dir.exists() {
+            // Recorded by a previous run of this client, before it restarted
+            fs::remove_dir_all(&trace_dir)?;
+        }
+        if self.command.create_trace_dir {
+            fs::create_dir_all(&trace_dir)?;
+        }
+        let input_file = self.traces_dir.join(format!("{name}.input"));
+        input.to_file(&input_file)?;
+
+        let mut command = self.command.command(&input_file, &trace_dir);
+        command
+            .stdin(fs::File::open(&input_file)?)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                log::warn!(
+                    "Failed to start the {} recorder for solution {id}: {err}",
+                    self.command.recorder()
+                );
+                return Ok(None);
+            }
+        };
+
+        let start = Instant::now();
+        let exit_code = loop {
+            if let Some(status) = child.try_wait()? {
+                break status.code();
+            }
+            if start.elapsed() > self.timeout {
+                log::warn!("Recording of solution {id} timed out, the trace may be incomplete");
+                child.kill()?;
+                child.wait()?;
+                break None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        log::info!(
+            "Recorded solution {id} with {} to {}",
+            self.command.recorder(),
+            trace_dir.display()
+        );
+
+        Ok(Some(RecordedTraceMetadata {
+            trace_dir,
+            recorder: self.command.recorder().to_string(),
+            exit_code,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use std::path::Path;
+
+    use super::RecordCommand;
+
+    #[test]
+    fn test_record_command() {
+        let command = RecordCommand::rr("./target", ["-f", "@@"])
+            .command(Path::new("/tmp/input"), Path::new("/tmp/trace"));
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(command.get_program(), "rr");
+        assert_eq!(
+            args,
+            ["record", "-o", "/tmp/trace", "./target", "-f", "/tmp/input"]
+        );
+        // rr refuses to record into an existing directory, qemu only writes its replay log into it
+        assert!(!RecordCommand::rr("./target", ["@@"]).create_trace_dir);
+        assert!(RecordCommand::qemu_system("qemu-system-x86_64", ["-hda", "@@"]).create_trace_dir);
+    }
+}
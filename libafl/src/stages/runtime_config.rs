@@ -0,0 +1,84 @@
+//! The [`RuntimeConfigStage`] applies the timeout of the [`crate::state::RuntimeConfig`] to an in-process executor.
+
+use core::{marker::PhantomData, time::Duration};
+
+use crate::{
+    executors::{hooks::inprocess::HasTimeout, inprocess::HasInProcessHooks, Executor},
+    stages::Stage,
+    state::{HasRuntimeConfig, UsesState},
+    Error, HasMetadata,
+};
+
+/// A stage changing the timeout of an in-process executor, once a new timeout was received in an
+/// [`crate::events::Event::ConfigUpdate`]
+#[derive(Debug)]
+pub struct RuntimeConfigStage<E, EM, Z> {
+    /// The timeout applied last
+    timeout: Option<Duration>,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> UsesState for RuntimeConfigStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for RuntimeConfigStage<E, EM, Z>
+where
+    E: Executor<EM, Z> + HasInProcessHooks<E::State>,
+    EM: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasMetadata,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        let timeout = state.runtime_config().timeout;
+        if timeout != self.timeout {
+            if let Some(timeout) = timeout {
+                log::info!("Changing the execution timeout to {timeout:?}");
+                executor
+                    .inprocess_hooks_mut()
+                    .timer_mut()
+                    .set_exec_timeout(timeout);
+            }
+            self.timeout = timeout;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> RuntimeConfigStage<E, EM, Z> {
+    /// Create a new [`RuntimeConfigStage`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            timeout: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E, EM, Z> Default for RuntimeConfigStage<E, EM, Z> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,308 @@
+//! The [`StateDumpStage`] snapshots the progress of a running fuzzer on demand, e.g. before machine maintenance.
+//!
+//! A dump is requested with `SIGUSR1`, once [`install_state_dump_handler`] ran, or with [`request_state_dump`].
+//! The signal handler only sets a flag: the stage does the actual work between two fuzzing rounds, when the state is
+//! consistent. It serializes the state, writes all testcases of the corpus and the solutions to disk, and logs a
+//! summary. The fuzzer keeps running afterwards.
+
+#[cfg(unix)]
+use alloc::vec::Vec;
+use alloc::{borrow::Cow, string::String};
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(unix)]
+use libafl_bolts::os::unix_signals::{
+    setup_signal_handler, siginfo_t, ucontext_t, Signal, SignalHandler,
+};
+use libafl_bolts::{fs::write_file_atomic, Named};
+
+use crate::{
+    corpus::Corpus,
+    events::{EventFirer, LogSeverity},
+    inputs::Input,
+    stages::Stage,
+    state::{HasCorpus, HasExecutions, HasSolutions, UsesState},
+    Error,
+};
+
+/// The file name of the serialized state in the dump directory
+pub const STATE_DUMP_FILE: &str = "state.postcard";
+
+/// Set by the signal handler, cleared by the [`StateDumpStage`]
+static STATE_DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Requests a dump from the [`StateDumpStage`]s of this process, at their next run
+pub fn request_state_dump() {
+    STATE_DUMP_REQUESTED.store(true, Ordering::Release);
+}
+
+/// The `SIGUSR1` handler, requesting a state dump
+#[cfg(unix)]
+#[derive(Debug)]
+struct StateDumpSignalHandler;
+
+#[cfg(unix)]
+static mut STATE_DUMP_SIGNAL_HANDLER: StateDumpSignalHandler = StateDumpSignalHandler;
+
+#[cfg(unix)]
+impl SignalHandler for StateDumpSignalHandler {
+    unsafe fn handle(
+        &mut self,
+        _signal: Signal,
+        _info: &mut siginfo_t,
+        _context: Option<&mut ucontext_t>,
+    ) {
+        // Only async-signal-safe work in here, the stage does the rest
+        request_state_dump();
+    }
+
+    fn signals(&self) -> Vec<Signal> {
+        vec![Signal::SigUser1]
+    }
+}
+
+/// Installs a `SIGUSR1` handler, requesting a dump from the [`StateDumpStage`]s of this process.
+///
+/// Send the signal to a client, e.g. with `kill -USR1 <pid>`, to snapshot its progress.
+#[cfg(unix)]
+pub fn install_state_dump_handler() -> Result<(), Error> {
+    // # Safety
+    // The handler is a static, and only sets an atomic flag.
+    unsafe { setup_signal_handler(core::ptr::addr_of_mut!(STATE_DUMP_SIGNAL_HANDLER)) }
+}
+
+/// Writes all testcases of a corpus to `dir`, keeping the files which are already there.
+///
+/// Inputs which are not in memory are copied from their file, or loaded and dropped again after writing them,
+/// so a dump does not pin an on-disk corpus in memory.
+fn dump_corpus<C>(corpus: &C, dir: &Path) -> Result<usize, Error>
+where
+    C: Corpus,
+{
+    fs::create_dir_all(dir)?;
+    for id in corpus.ids() {
+        let mut testcase = corpus.get(id)?.borrow_mut();
+        if testcase.input().is_none() {
+            if let Some(file_path) = testcase.file_path() {
+                let name = testcase
+                    .filename()
+                    .clone()
+                    .or_else(|| Some(file_path.file_name()?.to_string_lossy().into_owned()));
+                if let Some(name) = name {
+                    let path = dir.join(name);
+                    if !path.exists() {
+                        fs::copy(file_path, path)?;
+                    }
+                    continue;
+                }
+            }
+        }
+
+        let loaded = testcase.input().is_none();
+        corpus.load_input_into(&mut testcase)?;
+        let name = match testcase.filename() {
+            Some(name) => name.clone(),
+            None => testcase.input().as_ref().unwrap().generate_name(Some(id)),
+        };
+        let path = dir.join(name);
+        if !path.exists() {
+            testcase.input().as_ref().unwrap().to_file(path)?;
+        }
+        if loaded {
+            *testcase.input_mut() = None;
+        }
+    }
+    Ok(corpus.count())
+}
+
+/// A stage dumping the state, the corpus and the solutions to a directory when requested, see the
+/// [module documentation](self).
+///
+/// The dump directory contains the [`STATE_DUMP_FILE`], and the `corpus` and `solutions` directories.
+/// Every dump overwrites the state of the previous one.
+#[derive(Debug)]
+pub struct StateDumpStage<E, EM, Z> {
+    dir: PathBuf,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> UsesState for StateDumpStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Named for StateDumpStage<E, EM, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("StateDumpStage");
+        &NAME
+    }
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for StateDumpStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: EventFirer<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasCorpus + HasSolutions + HasExecutions,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        if !STATE_DUMP_REQUESTED.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let summary = self.dump(state)?;
+        log::info!("{summary}");
+        manager.log(state, LogSeverity::Info, summary)
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not running the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not running the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> StateDumpStage<E, EM, Z> {
+    /// Creates a new [`StateDumpStage`], dumping to `dir`
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            dir: dir.into(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// The dump directory
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl<E, EM, Z> StateDumpStage<E, EM, Z>
+where
+    E: UsesState,
+    E::State: HasCorpus + HasSolutions + HasExecutions,
+{
+    /// Dumps the state, the corpus and the solutions right away, returns a summary of the dump
+    pub fn dump(&self, state: &E::State) -> Result<String, Error> {
+        fs::create_dir_all(&self.dir)?;
+        let serialized = postcard::to_allocvec(state)?;
+        write_file_atomic(self.dir.join(STATE_DUMP_FILE), &serialized)?;
+        let corpus = dump_corpus(state.corpus(), &self.dir.join("corpus"))?;
+        let solutions = dump_corpus(state.solutions(), &self.dir.join("solutions"))?;
+        Ok(format!(
+            "Dumped the state ({} bytes, {} executions), {corpus} testcases and {solutions} solutions to {}",
+            serialized.len(),
+            state.executions(),
+            self.dir.display()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{
+        dump_corpus, request_state_dump, StateDumpStage, STATE_DUMP_FILE, STATE_DUMP_REQUESTED,
+    };
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        fuzzer::NopFuzzer,
+        inputs::{BytesInput, Input},
+        stages::Stage,
+        state::{HasCorpus, StdState},
+    };
+
+    #[test]
+    fn test_state_dump_stage() {
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"hello".to_vec())))
+            .unwrap();
+
+        let dir = env::temp_dir().join(format!("libafl_state_dump_{}", process::id()));
+        let mut stage = StateDumpStage::new(&dir);
+        // The stage does not run the target, any executor will do
+        let mut executor = NopFuzzer::new();
+        let mut fuzzer = NopFuzzer::new();
+        let mut mgr = NopEventManager::new();
+
+        // Nothing happens without a request
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)
+            .unwrap();
+        assert!(!dir.exists());
+
+        request_state_dump();
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)
+            .unwrap();
+        assert!(!STATE_DUMP_REQUESTED.load(core::sync::atomic::Ordering::Acquire));
+        assert!(dir.join(STATE_DUMP_FILE).exists());
+        assert_eq!(fs::read_dir(dir.join("corpus")).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dump_corpus_keeps_inputs_on_disk() {
+        let dir = env::temp_dir().join(format!("libafl_state_dump_on_disk_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join("on_disk");
+        BytesInput::new(b"on disk".to_vec())
+            .to_file(&file_path)
+            .unwrap();
+
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        let mut testcase = Testcase::new(BytesInput::new(b"on disk".to_vec()));
+        *testcase.file_path_mut() = Some(file_path);
+        *testcase.input_mut() = None;
+        let id = corpus.add(testcase).unwrap();
+
+        assert_eq!(dump_corpus(&corpus, &dir.join("corpus")).unwrap(), 1);
+        assert_eq!(
+            fs::read(dir.join("corpus").join("on_disk")).unwrap(),
+            b"on disk"
+        );
+        // The input was copied, not loaded into memory
+        assert!(corpus.get(id).unwrap().borrow().input().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
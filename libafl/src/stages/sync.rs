@@ -7,13 +7,21 @@ use alloc::{
 use core::{marker::PhantomData, time::Duration};
 use std::path::{Path, PathBuf};
 
-use libafl_bolts::{current_time, fs::find_new_files_rec, shmem::ShMemProvider, Named};
+use libafl_bolts::{
+    codec::{Codec, PostcardCodec},
+    current_time,
+    fs::find_new_files_rec,
+    shmem::ShMemProvider,
+    Named,
+};
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "control_server")]
+use crate::events::control::ControlMetadata;
 #[cfg(feature = "introspection")]
 use crate::state::HasClientPerfMonitor;
 use crate::{
-    corpus::{Corpus, CorpusId},
+    corpus::{Corpus, CorpusId, GlobalTestcaseId},
     events::{llmp::LlmpEventConverter, Event, EventConfig, EventFirer},
     executors::{Executor, ExitKind, HasObservers},
     fuzzer::{Evaluator, EvaluatorObservers, ExecutionProcessor},
@@ -96,8 +104,16 @@ where
             .get::<SyncFromDiskMetadata>()
             .map(|m| m.last_time);
 
+        // A sync requested by the operator skips the interval
+        #[cfg(feature = "control_server")]
+        let requested = state
+            .metadata_mut::<ControlMetadata>()
+            .is_ok_and(ControlMetadata::take_sync_request);
+        #[cfg(not(feature = "control_server"))]
+        let requested = false;
+
         if let Some(last) = last {
-            if current_time().saturating_sub(last) < self.interval {
+            if !requested && current_time().saturating_sub(last) < self.interval {
                 return Ok(());
             }
         }
@@ -223,19 +239,21 @@ impl SyncFromBrokerMetadata {
 
 /// A stage that loads testcases from disk to sync with other fuzzers such as AFL++
 #[derive(Debug)]
-pub struct SyncFromBrokerStage<DI, IC, ICB, S, SP>
+pub struct SyncFromBrokerStage<DI, IC, ICB, S, SP, C = PostcardCodec>
 where
+    C: Codec,
     SP: ShMemProvider + 'static,
     S: UsesInput,
     IC: InputConverter<From = S::Input, To = DI>,
     ICB: InputConverter<From = DI, To = S::Input>,
     DI: Input,
 {
-    client: LlmpEventConverter<DI, IC, ICB, S, SP>,
+    client: LlmpEventConverter<DI, IC, ICB, S, SP, C>,
 }
 
-impl<DI, IC, ICB, S, SP> UsesState for SyncFromBrokerStage<DI, IC, ICB, S, SP>
+impl<DI, IC, ICB, S, SP, C> UsesState for SyncFromBrokerStage<DI, IC, ICB, S, SP, C>
 where
+    C: Codec,
     SP: ShMemProvider + 'static,
     S: State,
     IC: InputConverter<From = S::Input, To = DI>,
@@ -245,8 +263,9 @@ where
     type State = S;
 }
 
-impl<E, EM, IC, ICB, DI, S, SP, Z> Stage<E, EM, Z> for SyncFromBrokerStage<DI, IC, ICB, S, SP>
+impl<E, EM, IC, ICB, DI, S, SP, Z, C> Stage<E, EM, Z> for SyncFromBrokerStage<DI, IC, ICB, S, SP, C>
 where
+    C: Codec,
     EM: UsesState<State = S> + EventFirer,
     S: State + HasExecutions + HasCorpus + HasRand + HasMetadata,
     SP: ShMemProvider,
@@ -279,6 +298,7 @@ where
 
             while let Some(id) = cur_id {
                 let input = state.corpus().cloned_input_for_id(id)?;
+                let global_id = GlobalTestcaseId::of(&input)?;
 
                 self.client.fire(
                     state,
@@ -290,6 +310,8 @@ where
                         client_config: EventConfig::AlwaysUnique,
                         time: current_time(),
                         forward_id: None,
+                        global_id,
+                        parent_global_id: None,
                         #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
                         node_id: None,
                     },
@@ -331,8 +353,9 @@ where
     }
 }
 
-impl<DI, IC, ICB, S, SP> SyncFromBrokerStage<DI, IC, ICB, S, SP>
+impl<DI, IC, ICB, S, SP, C> SyncFromBrokerStage<DI, IC, ICB, S, SP, C>
 where
+    C: Codec,
     SP: ShMemProvider + 'static,
     S: UsesInput,
     IC: InputConverter<From = S::Input, To = DI>,
@@ -341,7 +364,7 @@ where
 {
     /// Creates a new [`SyncFromBrokerStage`]
     #[must_use]
-    pub fn new(client: LlmpEventConverter<DI, IC, ICB, S, SP>) -> Self {
+    pub fn new(client: LlmpEventConverter<DI, IC, ICB, S, SP, C>) -> Self {
         Self { client }
     }
 }
@@ -0,0 +1,180 @@
+//! The [`TimeBudgetStage`] limits the wall-clock time spent in the wrapped stages.
+//!
+//! Long deterministic or tracing stages can starve havoc. This wrapper accounts for the time spent
+//! in its stages, persisted in [`TimeBudgetMetadata`], and skips them while they are over budget.
+
+use alloc::borrow::Cow;
+use core::{marker::PhantomData, time::Duration};
+
+use libafl_bolts::{current_time, impl_serdeany, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    stages::{HasNestedStageStatus, Stage, StagesTuple},
+    state::{HasStartTime, UsesState},
+    Error, HasNamedMetadata,
+};
+
+/// The time accounting of a [`TimeBudgetStage`], stored as named metadata
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct TimeBudgetMetadata {
+    /// The total time spent in the wrapped stages
+    pub spent: Duration,
+    /// The time the wrapped stages overran their per-invocation budget, which still has to be paid back
+    pub debt: Duration,
+    /// How often the wrapped stages were run
+    pub runs: u64,
+    /// How often the wrapped stages were skipped, because they were over budget
+    pub skips: u64,
+}
+
+impl_serdeany!(TimeBudgetMetadata);
+
+/// A stage wrapper limiting the wall-clock time the wrapped stages may consume.
+///
+/// - With a per-invocation budget, each run that takes longer than the budget builds up a debt.
+///   Each following invocation pays back one budget's worth of debt instead of running the stages,
+///   so that on average, the stages take at most the budget per invocation.
+/// - With a campaign share, the stages are skipped while the time spent in them
+///   is larger than the given share of the total campaign time, e.g. `0.2` for at most 20% in calibration.
+#[derive(Debug)]
+pub struct TimeBudgetStage<E, EM, ST, Z> {
+    name: Cow<'static, str>,
+    stages: ST,
+    per_invocation: Option<Duration>,
+    max_share: Option<f64>,
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, ST, Z> UsesState for TimeBudgetStage<E, EM, ST, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, ST, Z> Named for TimeBudgetStage<E, EM, ST, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, ST, Z> Stage<E, EM, Z> for TimeBudgetStage<E, EM, ST, Z>
+where
+    E: UsesState,
+    EM: UsesState<State = Self::State>,
+    ST: StagesTuple<E, EM, Self::State, Z>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasNestedStageStatus + HasNamedMetadata + HasStartTime,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let resuming = state.current_stage_id()?.is_some();
+        let campaign_time = current_time().saturating_sub(*state.start_time());
+        let meta = *state.named_metadata_or_insert_with(&self.name, TimeBudgetMetadata::default);
+
+        if !resuming && self.over_budget(&meta, campaign_time) {
+            let meta = state.named_metadata_mut::<TimeBudgetMetadata>(&self.name)?;
+            if let Some(per_invocation) = self.per_invocation {
+                meta.debt = meta.debt.saturating_sub(per_invocation);
+            }
+            meta.skips += 1;
+            return Ok(());
+        }
+
+        let start = current_time();
+        self.stages.perform_all(fuzzer, executor, state, manager)?;
+        let elapsed = current_time().saturating_sub(start);
+
+        let meta = state.named_metadata_mut::<TimeBudgetMetadata>(&self.name)?;
+        meta.spent += elapsed;
+        meta.runs += 1;
+        if let Some(per_invocation) = self.per_invocation {
+            meta.debt += elapsed.saturating_sub(per_invocation);
+        }
+        Ok(())
+    }
+
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        state.enter_inner_stage()?;
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        state.exit_inner_stage()?;
+        Ok(())
+    }
+}
+
+impl<E, EM, ST, Z> TimeBudgetStage<E, EM, ST, Z> {
+    /// Create a new [`TimeBudgetStage`] without any budget.
+    ///
+    /// Use [`Self::with_per_invocation`] and [`Self::with_max_share`] to limit the stages.
+    /// The `name` identifies the accounting metadata, and has to be unique.
+    #[must_use]
+    pub fn new(name: &'static str, stages: ST) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            stages,
+            per_invocation: None,
+            max_share: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Limit the (average) time the stages may take per invocation
+    #[must_use]
+    pub fn with_per_invocation(mut self, budget: Duration) -> Self {
+        self.per_invocation = Some(budget);
+        self
+    }
+
+    /// Limit the share of the total campaign time spent in the stages, between `0.0` and `1.0`
+    #[must_use]
+    pub fn with_max_share(mut self, max_share: f64) -> Self {
+        self.max_share = Some(max_share);
+        self
+    }
+
+    /// If the stages have to be skipped, given the accounting and the current campaign time
+    fn over_budget(&self, meta: &TimeBudgetMetadata, campaign_time: Duration) -> bool {
+        let in_debt = self.per_invocation.is_some() && meta.debt > Duration::ZERO;
+        let over_share = self
+            .max_share
+            .is_some_and(|share| meta.spent.as_secs_f64() > share * campaign_time.as_secs_f64());
+        in_debt || over_share
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{TimeBudgetMetadata, TimeBudgetStage};
+
+    #[test]
+    fn test_time_budget() {
+        let stage = TimeBudgetStage::<(), (), (), ()>::new("test", ())
+            .with_per_invocation(Duration::from_millis(10))
+            .with_max_share(0.5);
+
+        let mut meta = TimeBudgetMetadata {
+            spent: Duration::from_secs(1),
+            ..TimeBudgetMetadata::default()
+        };
+        assert!(!stage.over_budget(&meta, Duration::from_secs(10)));
+        assert!(stage.over_budget(&meta, Duration::from_secs(1)));
+
+        meta.debt = Duration::from_millis(1);
+        assert!(stage.over_budget(&meta, Duration::from_secs(10)));
+    }
+}
@@ -0,0 +1,154 @@
+//! The [`TokenSyncStage`] shares the dictionary of a client with all other clients.
+//!
+//! Tokens found at runtime, e.g. by the cmplog [`crate::stages::ColorizationStage`] or read from the autotokens
+//! section, are only known to the client that found them. This stage broadcasts new entries of the
+//! [`Tokens`] metadata as [`Event::NewTokens`], so that all nodes converge on the same dictionary.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{Event, EventFirer},
+    mutators::Tokens,
+    stages::Stage,
+    state::UsesState,
+    Error, HasMetadata,
+};
+
+/// The number of tokens a [`TokenSyncStage`] already shared with the other clients
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct TokenSyncMetadata {
+    /// The tokens before this index in [`Tokens`] are known to the other clients
+    pub synced: usize,
+}
+
+impl_serdeany!(TokenSyncMetadata);
+
+/// Add the tokens received from another client to the [`Tokens`] of this client.
+///
+/// Returns the number of tokens that were new. Received tokens are not shared again by the [`TokenSyncStage`].
+pub fn import_tokens<S>(state: &mut S, tokens: &[Vec<u8>]) -> usize
+where
+    S: HasMetadata,
+{
+    let tokens_meta = state.metadata_or_insert_with(Tokens::new);
+    let old_len = tokens_meta.len();
+    tokens_meta.add_tokens(tokens);
+    let new_len = tokens_meta.len();
+
+    // Only skip the imported tokens if everything before them was shared already
+    let sync_meta = state.metadata_or_insert_with(TokenSyncMetadata::default);
+    if sync_meta.synced == old_len {
+        sync_meta.synced = new_len;
+    }
+    new_len - old_len
+}
+
+/// A stage firing an [`Event::NewTokens`] with the tokens this client found since the last run
+#[derive(Debug)]
+pub struct TokenSyncStage<EM> {
+    phantom: PhantomData<EM>,
+}
+
+impl<EM> UsesState for TokenSyncStage<EM>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for TokenSyncStage<EM>
+where
+    EM: EventFirer,
+    E: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasMetadata,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let synced = state
+            .metadata_map()
+            .get::<TokenSyncMetadata>()
+            .map_or(0, |meta| meta.synced);
+        let Some(tokens) = state.metadata_map().get::<Tokens>() else {
+            return Ok(());
+        };
+        if tokens.len() <= synced {
+            return Ok(());
+        }
+
+        let new_tokens = tokens.tokens()[synced..].to_vec();
+        let len = tokens.len();
+        manager.fire(state, Event::NewTokens { tokens: new_tokens })?;
+        state.add_metadata(TokenSyncMetadata { synced: len });
+        Ok(())
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl<EM> TokenSyncStage<EM> {
+    /// Create a new [`TokenSyncStage`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<EM> Default for TokenSyncStage<EM> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{import_tokens, TokenSyncMetadata};
+    use crate::{mutators::Tokens, state::NopState, HasMetadata};
+
+    #[test]
+    fn test_import_tokens() {
+        let mut state = NopState::<()>::new();
+        assert_eq!(
+            import_tokens(&mut state, &[b"GET".to_vec(), b"PUT".to_vec()]),
+            2
+        );
+        assert_eq!(state.metadata::<TokenSyncMetadata>().unwrap().synced, 2);
+
+        // A local token is not shared yet, so imported tokens are shared again with it
+        state
+            .metadata_mut::<Tokens>()
+            .unwrap()
+            .add_token(&b"POST".to_vec());
+        assert_eq!(
+            import_tokens(&mut state, &[b"GET".to_vec(), b"HEAD".to_vec()]),
+            1
+        );
+        assert_eq!(state.metadata::<TokenSyncMetadata>().unwrap().synced, 2);
+    }
+}
@@ -7,6 +7,7 @@ use core::{
     cell::{Ref, RefMut},
     fmt::Debug,
     marker::PhantomData,
+    num::NonZeroUsize,
     time::Duration,
 };
 #[cfg(feature = "std")]
@@ -16,8 +17,13 @@ use std::{
 };
 
 #[cfg(feature = "std")]
-use libafl_bolts::core_affinity::{CoreId, Cores};
 use libafl_bolts::{
+    core_affinity::{CoreId, Cores},
+    staterestore::DeltaSerialize,
+    AsIter,
+};
+use libafl_bolts::{
+    impl_serdeany,
     rands::{Rand, StdRand},
     serdeany::{NamedSerdeAnyMap, SerdeAnyMap},
 };
@@ -30,6 +36,12 @@ pub use stack::StageStack;
 use crate::monitors::ClientPerfMonitor;
 #[cfg(feature = "scalability_introspection")]
 use crate::monitors::ScalabilityMonitor;
+#[cfg(feature = "std")]
+use crate::{
+    corpus::SeedSelector,
+    executors::{Executor, HasObservers},
+    observers::{MapObserver, ObserversTuple},
+};
 use crate::{
     corpus::{Corpus, CorpusId, HasCurrentCorpusId, HasTestcase, InMemoryCorpus, Testcase},
     events::{Event, EventFirer, LogSeverity},
@@ -37,6 +49,7 @@ use crate::{
     fuzzer::{Evaluator, ExecuteInputResult},
     generators::Generator,
     inputs::{Input, NopInput, UsesInput},
+    schedulers::powersched::{BaseSchedule, PowerSchedule, SchedulerMetadata},
     stages::{HasCurrentStageId, HasNestedStageStatus, StageId},
     Error, HasMetadata, HasNamedMetadata,
 };
@@ -53,6 +66,7 @@ pub trait State:
     + DeserializeOwned
     + MaybeHasClientPerfMonitor
     + MaybeHasScalabilityMonitor
+    + MaybeDeltaSerialize
     + HasCurrentCorpusId
     + HasCurrentStageId
     + Stoppable
@@ -166,6 +180,19 @@ impl<T> MaybeHasScalabilityMonitor for T {}
 #[cfg(feature = "scalability_introspection")]
 impl<T> MaybeHasScalabilityMonitor for T where T: HasScalabilityMonitor {}
 
+/// Intermediate trait for `DeltaSerialize`, which needs `std`
+#[cfg(feature = "std")]
+pub trait MaybeDeltaSerialize: DeltaSerialize {}
+/// Intermediate trait for `DeltaSerialize`, which needs `std`
+#[cfg(not(feature = "std"))]
+pub trait MaybeDeltaSerialize {}
+
+#[cfg(not(feature = "std"))]
+impl<T> MaybeDeltaSerialize for T {}
+
+#[cfg(feature = "std")]
+impl<T> MaybeDeltaSerialize for T where T: DeltaSerialize {}
+
 /// Trait for offering a [`ScalabilityMonitor`]
 #[cfg(feature = "scalability_introspection")]
 pub trait HasScalabilityMonitor {
@@ -223,6 +250,87 @@ pub trait HasLastReportTime {
     fn last_report_time_mut(&mut self) -> &mut Option<Duration>;
 }
 
+/// Parameters of a running fuzzer which can be changed without restarting it, see [`HasRuntimeConfig`].
+///
+/// Unset parameters keep the value the fuzzer was built with.
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    /// The execution timeout, applied by the [`crate::stages::RuntimeConfigStage`]
+    pub timeout: Option<Duration>,
+    /// The maximum number of iterations of a [`crate::stages::StdMutationalStage`]
+    pub max_iterations: Option<NonZeroUsize>,
+    /// The power schedule, `Some(None)` disables power scheduling
+    pub power_schedule: Option<Option<BaseSchedule>>,
+}
+
+impl_serdeany!(RuntimeConfig);
+
+/// The [`RuntimeConfig`] of states which did not receive any update yet
+static DEFAULT_RUNTIME_CONFIG: RuntimeConfig = RuntimeConfig {
+    timeout: None,
+    max_iterations: None,
+    power_schedule: None,
+};
+
+impl RuntimeConfig {
+    /// Take over all parameters set in `update`, keeping the others
+    pub fn update(&mut self, update: &RuntimeConfig) {
+        if update.timeout.is_some() {
+            self.timeout = update.timeout;
+        }
+        if update.max_iterations.is_some() {
+            self.max_iterations = update.max_iterations;
+        }
+        if update.power_schedule.is_some() {
+            self.power_schedule = update.power_schedule;
+        }
+    }
+}
+
+/// Trait for states with a [`RuntimeConfig`], updated by [`Event::ConfigUpdate`]s.
+///
+/// Implemented for all states with metadata, the config is stored as metadata.
+pub trait HasRuntimeConfig {
+    /// The runtime config
+    fn runtime_config(&self) -> &RuntimeConfig;
+
+    /// The runtime config (mutable)
+    fn runtime_config_mut(&mut self) -> &mut RuntimeConfig;
+
+    /// Apply the parameters set in `update`.
+    ///
+    /// A new power schedule takes effect right away, the other parameters when the components using them run next.
+    fn apply_runtime_config(&mut self, update: &RuntimeConfig);
+}
+
+impl<S> HasRuntimeConfig for S
+where
+    S: HasMetadata,
+{
+    fn runtime_config(&self) -> &RuntimeConfig {
+        self.metadata_map()
+            .get::<RuntimeConfig>()
+            .unwrap_or(&DEFAULT_RUNTIME_CONFIG)
+    }
+
+    fn runtime_config_mut(&mut self) -> &mut RuntimeConfig {
+        self.metadata_or_insert_with(RuntimeConfig::default)
+    }
+
+    fn apply_runtime_config(&mut self, update: &RuntimeConfig) {
+        self.runtime_config_mut().update(update);
+        if let Some(schedule) = update.power_schedule {
+            if let Ok(meta) = self.metadata_mut::<SchedulerMetadata>() {
+                meta.set_strat(schedule.map(PowerSchedule::new));
+            }
+        }
+    }
+}
+
 /// Struct that holds the options for input loading
 #[cfg(feature = "std")]
 pub struct LoadConfig<'a, I, S, Z> {
@@ -967,6 +1075,48 @@ where
         )
     }
 
+    /// Loads initial inputs from the passed-in `in_dirs`, after reducing them with the given [`SeedSelector`].
+    ///
+    /// All seeds are executed once, and only the selected subset is loaded into the corpus.
+    /// The rejected seeds are recorded in the [`crate::corpus::SeedSelectionMetadata`].
+    pub fn load_initial_inputs_with_selection<CO, E, EM, O, Z>(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        in_dirs: &[PathBuf],
+        selector: &SeedSelector<CO, O>,
+    ) -> Result<(), Error>
+    where
+        CO: AsRef<O>,
+        for<'a> O: MapObserver + AsIter<'a, Item = O::Entry>,
+        E: Executor<EM, Z, State = Self> + HasObservers,
+        E::Observers: ObserversTuple<I, Self>,
+        EM: EventFirer<State = Self>,
+        Z: Evaluator<E, EM, State = Self>,
+    {
+        // Only select once, a restarted fuzzer continues loading the selected seeds
+        if self.remaining_initial_files.is_none() {
+            let mut seeds = Vec::new();
+            self.walk_initial_inputs(in_dirs, |path| {
+                seeds.push(path.clone());
+                Ok(())
+            })?;
+            let selected = selector.select(fuzzer, executor, manager, self, seeds)?;
+            self.remaining_initial_files = Some(selected);
+        }
+        self.continue_loading_initial_inputs_custom(
+            fuzzer,
+            executor,
+            manager,
+            LoadConfig {
+                loader: &mut |_, _, path| I::from_file(path),
+                forced: false,
+                exit_on_solution: false,
+            },
+        )
+    }
+
     fn calculate_corpus_size(&mut self) -> Result<usize, Error> {
         let mut count: usize = 0;
         loop {
@@ -1070,6 +1220,41 @@ where
     }
 }
 
+/// Delta snapshots of the state leave out the inputs already stored in the base snapshot of the corpora
+#[cfg(feature = "std")]
+impl<C, I, R, SC> DeltaSerialize for StdState<I, C, R, SC>
+where
+    C: Corpus,
+    SC: Corpus,
+    Self: Serialize + DeserializeOwned,
+{
+    fn mark_snapshot(&mut self) {
+        self.corpus.mark_snapshot();
+        self.solutions.mark_snapshot();
+    }
+
+    fn serialize_delta(&self) -> Result<Vec<u8>, Error> {
+        let corpus_inputs = self.corpus.take_snapshot_inputs();
+        let solutions_inputs = self.solutions.take_snapshot_inputs();
+        let serialized = postcard::to_allocvec(self);
+        self.corpus.put_snapshot_inputs(corpus_inputs);
+        self.solutions.put_snapshot_inputs(solutions_inputs);
+        Ok(serialized?)
+    }
+
+    fn apply_delta(&mut self, delta: &[u8]) -> Result<(), Error> {
+        let state: Self = postcard::from_bytes(delta)?;
+        state
+            .corpus
+            .put_snapshot_inputs(self.corpus.take_snapshot_inputs());
+        state
+            .solutions
+            .put_snapshot_inputs(self.solutions.take_snapshot_inputs());
+        *self = state;
+        Ok(())
+    }
+}
+
 impl<C, I, R, SC> StdState<I, C, R, SC>
 where
     I: Input,
@@ -1337,6 +1522,23 @@ impl<I> HasRand for NopState<I> {
 
 impl<I> State for NopState<I> where I: Input {}
 
+#[cfg(feature = "std")]
+impl<I> DeltaSerialize for NopState<I>
+where
+    Self: Serialize + DeserializeOwned,
+{
+    fn mark_snapshot(&mut self) {}
+
+    fn serialize_delta(&self) -> Result<Vec<u8>, Error> {
+        Ok(postcard::to_allocvec(self)?)
+    }
+
+    fn apply_delta(&mut self, delta: &[u8]) -> Result<(), Error> {
+        *self = postcard::from_bytes(delta)?;
+        Ok(())
+    }
+}
+
 impl<I> HasCurrentCorpusId for NopState<I> {
     fn set_corpus_id(&mut self, _id: CorpusId) -> Result<(), Error> {
         Ok(())
@@ -1389,10 +1591,34 @@ impl<I> HasScalabilityMonitor for NopState<I> {
 
 #[cfg(test)]
 mod test {
-    use crate::{inputs::BytesInput, state::StdState};
+    use core::time::Duration;
+
+    use crate::{
+        inputs::BytesInput,
+        state::{HasRuntimeConfig, NopState, RuntimeConfig, StdState},
+    };
 
     #[test]
     fn test_std_state() {
         StdState::nop::<BytesInput>().expect("couldn't instantiate the test state");
     }
+
+    #[test]
+    fn test_runtime_config() {
+        let mut state = NopState::<BytesInput>::new();
+        assert_eq!(state.runtime_config(), &RuntimeConfig::default());
+
+        state.apply_runtime_config(&RuntimeConfig {
+            timeout: Some(Duration::from_millis(500)),
+            ..RuntimeConfig::default()
+        });
+        state.apply_runtime_config(&RuntimeConfig {
+            power_schedule: Some(None),
+            ..RuntimeConfig::default()
+        });
+        let config = state.runtime_config();
+        assert_eq!(config.timeout, Some(Duration::from_millis(500)));
+        assert_eq!(config.max_iterations, None);
+        assert_eq!(config.power_schedule, Some(None));
+    }
 }
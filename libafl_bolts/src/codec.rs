@@ -0,0 +1,145 @@
+//! Serialization codecs, to select the format of state snapshots and event payloads.
+//!
+//! [`PostcardCodec`] is the default everywhere: it is compact and works in `no_std`. Self-describing formats,
+//! such as [`CborCodec`], tolerate added or reordered fields between versions, at the cost of size.
+
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Error;
+
+/// A serialization format
+pub trait Codec: Debug {
+    /// The name of the format
+    const NAME: &'static str;
+
+    /// Serializes the value
+    fn encode<T>(value: &T) -> Result<Vec<u8>, Error>
+    where
+        T: Serialize + ?Sized;
+
+    /// Deserializes a value from the bytes
+    fn decode<T>(bytes: &[u8]) -> Result<T, Error>
+    where
+        T: DeserializeOwned;
+}
+
+/// The compact, `no_std` compatible [`postcard`] format. The default codec.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PostcardCodec;
+
+impl Codec for PostcardCodec {
+    const NAME: &'static str = "postcard";
+
+    fn encode<T>(value: &T) -> Result<Vec<u8>, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        Ok(postcard::to_allocvec(value)?)
+    }
+
+    fn decode<T>(bytes: &[u8]) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(postcard::from_bytes(bytes)?)
+    }
+}
+
+/// The self-describing CBOR format, using `ciborium`
+#[cfg(feature = "codec_cbor")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CborCodec;
+
+#[cfg(feature = "codec_cbor")]
+impl Codec for CborCodec {
+    const NAME: &'static str = "cbor";
+
+    fn encode<T>(value: &T) -> Result<Vec<u8>, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes)
+            .map_err(|err| Error::serialize(format!("Could not encode CBOR: {err}")))?;
+        Ok(bytes)
+    }
+
+    fn decode<T>(bytes: &[u8]) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        ciborium::from_reader(bytes)
+            .map_err(|err| Error::serialize(format!("Could not decode CBOR: {err}")))
+    }
+}
+
+/// The [`bincode`] format, with its default options
+#[cfg(feature = "codec_bincode")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "codec_bincode")]
+impl Codec for BincodeCodec {
+    const NAME: &'static str = "bincode";
+
+    fn encode<T>(value: &T) -> Result<Vec<u8>, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        bincode::serialize(value)
+            .map_err(|err| Error::serialize(format!("Could not encode bincode: {err}")))
+    }
+
+    fn decode<T>(bytes: &[u8]) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        bincode::deserialize(bytes)
+            .map_err(|err| Error::serialize(format!("Could not decode bincode: {err}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use serde::{Deserialize, Serialize};
+
+    #[cfg(feature = "codec_bincode")]
+    use super::BincodeCodec;
+    #[cfg(feature = "codec_cbor")]
+    use super::CborCodec;
+    use super::{Codec, PostcardCodec};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Snapshot {
+        name: String,
+        entries: Vec<u64>,
+        time: Option<u32>,
+    }
+
+    fn roundtrip<C>()
+    where
+        C: Codec,
+    {
+        let snapshot = Snapshot {
+            name: String::from(C::NAME),
+            entries: vec![1, 2, 3],
+            time: Some(42),
+        };
+        let bytes = C::encode(&snapshot).unwrap();
+        assert_eq!(C::decode::<Snapshot>(&bytes).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_codecs() {
+        roundtrip::<PostcardCodec>();
+        #[cfg(feature = "codec_cbor")]
+        roundtrip::<CborCodec>();
+        #[cfg(feature = "codec_bincode")]
+        roundtrip::<BincodeCodec>();
+    }
+}
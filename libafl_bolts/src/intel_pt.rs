@@ -0,0 +1,448 @@
+//! Hardware tracing of processes with [Intel PT](https://www.intel.com/content/www/us/en/support/articles/000056730/processors.html),
+//! through the `perf` subsystem of Linux, see [`crate::perf`].
+//!
+//! [`IntelPT`] records the control flow of another process into a ring buffer shared with the kernel,
+//! without instrumenting it. [`PtDecoder`] turns the recorded packets into edge coverage for an AFL-style map.
+//!
+//! The decoder does not disassemble the target: each conditional branch (`TNT` bit) is treated as an edge out of
+//! the last indirect branch target (`TIP` packet), identified by the outcomes of the last
+//! [`TNT_HISTORY_BITS`] conditional branches since then. This is coarser than the real control flow graph, but
+//! needs neither the binary nor its memory layout, and is stable across executions. Like in AFL, each edge
+//! only depends on the block it leaves and the block it enters, so that loops do not flood the map.
+//! The trace is split at its synchronization points (`PSB` packets), which reset the decoder, so that the
+//! coverage of each segment can be cached: segments seen before, like the startup of the target, are not decoded again.
+
+use alloc::vec::Vec;
+
+use hashbrown::HashMap;
+
+use crate::{
+    hash_std,
+    perf::{check_permissions, mix, pmu_config_bit, pmu_type, PerfEvent, PerfEventAttr},
+    Error,
+};
+
+/// The `perf` PMU of Intel PT
+const INTEL_PT_PMU: &str = "intel_pt";
+
+/// A Packet Stream Boundary, the synchronization point of the trace
+const PSB: [u8; 16] = [
+    0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82, 0x02, 0x82,
+];
+
+/// The default size of the trace buffer, 4 MiB
+pub const DEFAULT_AUX_SIZE: usize = 4 << 20;
+
+/// The default maximum number of trace segments cached by the [`PtDecoder`]
+pub const DEFAULT_CACHE_ENTRIES: usize = 1 << 14;
+
+/// The number of conditional branch outcomes telling apart the blocks after an indirect branch target
+pub const TNT_HISTORY_BITS: u32 = 8;
+
+/// Check whether Intel PT tracing is available on this machine, and the current user may use it
+pub fn check_availability() -> Result<(), Error> {
+    if pmu_type(INTEL_PT_PMU).is_none() {
+        return Err(Error::unsupported(
+            "Intel PT is not available, the CPU does not support it or it is disabled in a VM",
+        ));
+    }
+    check_permissions()
+}
+
+/// An Intel PT trace of a process, recorded by `perf`.
+///
+/// The trace starts with [`IntelPT::enable`], and is recorded into a ring buffer of `aux_size` bytes.
+/// Read it with [`IntelPT::read_trace`] after the traced process ran, before the buffer overflows.
+#[derive(Debug)]
+pub struct IntelPT {
+    event: PerfEvent,
+}
+
+impl IntelPT {
+    /// Trace the user space code of the process `pid`, only the process itself, not its children.
+    ///
+    /// `aux_size` is rounded up to a power of two number of pages.
+    pub fn new(pid: libc::pid_t, aux_size: usize) -> Result<Self, Error> {
+        let Some(type_) = pmu_type(INTEL_PT_PMU) else {
+            return Err(Error::unsupported("Intel PT is not available"));
+        };
+        // Trace branches, and emit a `TIP` for each `ret` instead of compressing them into `TNT`s
+        let config =
+            pmu_config_bit(INTEL_PT_PMU, "branch")? | pmu_config_bit(INTEL_PT_PMU, "noretcomp")?;
+        let event = PerfEvent::new(&PerfEventAttr::new(type_, config), pid, 0, aux_size)?;
+        Ok(Self { event })
+    }
+
+    /// Only trace the given address ranges, see [`PerfEvent::set_filter`]
+    pub fn set_filter(&mut self, filter: &str) -> Result<(), Error> {
+        self.event.set_filter(filter)
+    }
+
+    /// Start tracing
+    pub fn enable(&mut self) -> Result<(), Error> {
+        self.event.enable()
+    }
+
+    /// Stop tracing
+    pub fn disable(&mut self) -> Result<(), Error> {
+        self.event.disable()
+    }
+
+    /// The size of the trace buffer
+    #[must_use]
+    pub fn aux_size(&self) -> usize {
+        self.event.aux_size()
+    }
+
+    /// Append the trace recorded since the last call to `trace`, and free its space in the buffer.
+    ///
+    /// If the traced code produced more than [`IntelPT::aux_size`] bytes in between, the rest of the trace was dropped.
+    pub fn read_trace(&mut self, trace: &mut Vec<u8>) -> Result<(), Error> {
+        self.event.read_aux(trace)
+    }
+}
+
+/// Decodes Intel PT traces into edge coverage, see the [module-level documentation](self).
+#[derive(Debug)]
+pub struct PtDecoder {
+    map_size: usize,
+    cache: HashMap<u64, Vec<u32>>,
+    max_cache_entries: usize,
+    cache_hits: u64,
+}
+
+impl PtDecoder {
+    /// A decoder for coverage maps of `map_size` entries, caching up to [`DEFAULT_CACHE_ENTRIES`] segments
+    #[must_use]
+    pub fn new(map_size: usize) -> Self {
+        Self::with_cache_entries(map_size, DEFAULT_CACHE_ENTRIES)
+    }
+
+    /// A decoder for coverage maps of `map_size` entries, caching up to `max_cache_entries` segments.
+    ///
+    /// Once the cache is full, it is cleared.
+    #[must_use]
+    pub fn with_cache_entries(map_size: usize, max_cache_entries: usize) -> Self {
+        assert!(
+            map_size > 0 && map_size <= u32::MAX as usize,
+            "Invalid map size {map_size}"
+        );
+        Self {
+            map_size,
+            cache: HashMap::new(),
+            max_cache_entries,
+            cache_hits: 0,
+        }
+    }
+
+    /// The number of segments found in the cache so far
+    #[must_use]
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits
+    }
+
+    /// Decode the `trace` and count its edges in `map`.
+    ///
+    /// Everything before the first `PSB` in the trace is skipped, the decoder can not synchronize on it.
+    pub fn decode(&mut self, trace: &[u8], map: &mut [u8]) {
+        assert_eq!(map.len(), self.map_size, "Map size changed");
+
+        let mut starts = Vec::new();
+        let mut idx = 0;
+        while let Some(offset) = find_psb(&trace[idx..]) {
+            starts.push(idx + offset);
+            idx += offset + PSB.len();
+        }
+        starts.push(trace.len());
+
+        for segment in starts.windows(2) {
+            let segment = &trace[segment[0]..segment[1]];
+            let key = hash_std(segment);
+            if let Some(edges) = self.cache.get(&key) {
+                self.cache_hits += 1;
+                hit_all(map, edges);
+                continue;
+            }
+            let edges = decode_segment(segment, self.map_size);
+            hit_all(map, &edges);
+            if self.cache.len() >= self.max_cache_entries {
+                self.cache.clear();
+            }
+            self.cache.insert(key, edges);
+        }
+    }
+}
+
+fn find_psb(trace: &[u8]) -> Option<usize> {
+    trace.windows(PSB.len()).position(|window| window == PSB)
+}
+
+fn hit_all(map: &mut [u8], edges: &[u32]) {
+    for edge in edges {
+        let entry = &mut map[*edge as usize];
+        *entry = entry.saturating_add(1);
+    }
+}
+
+/// The state of the decoder within a segment
+struct SegmentDecoder {
+    map_size: u64,
+    last_ip: u64,
+    /// The last indirect branch target
+    anchor: u64,
+    /// The outcomes of the conditional branches since the anchor, the latest [`TNT_HISTORY_BITS`] of them,
+    /// below a marker bit
+    history: u64,
+    prev: u64,
+    edges: Vec<u32>,
+}
+
+impl SegmentDecoder {
+    /// Enter `block`, recording the edge from the previous block, as in AFL
+    #[allow(clippy::cast_possible_truncation)]
+    fn enter(&mut self, block: u64) {
+        self.edges
+            .push(((self.prev ^ block) % self.map_size) as u32);
+        self.prev = block >> 1;
+    }
+
+    /// Enter the target of an indirect branch
+    fn indirect(&mut self, ip: u64) {
+        self.anchor = ip;
+        self.history = 1;
+        self.enter(mix(ip));
+    }
+
+    /// The taken and not-taken bits of a `TNT` packet, below the stop bit, oldest first
+    fn tnt(&mut self, payload: u64) {
+        if payload == 0 {
+            return;
+        }
+        let count = 63 - payload.leading_zeros();
+        for bit in (0..count).rev() {
+            let taken = (payload >> bit) & 1;
+            self.history = (self.history << 1) | taken;
+            if self.history >> TNT_HISTORY_BITS > 1 {
+                // Forget the oldest outcome, keeping the marker bit
+                self.history =
+                    (self.history & ((1 << TNT_HISTORY_BITS) - 1)) | (1 << TNT_HISTORY_BITS);
+            }
+            self.enter(mix(self.anchor ^ (self.history << 48)));
+        }
+    }
+
+    /// Decompress the target of a `TIP`-like packet, returns `None` if it is suppressed or invalid
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
+    fn ip(&mut self, ip_bytes: u8, payload: &[u8]) -> Option<u64> {
+        let value = payload
+            .iter()
+            .rev()
+            .fold(0_u64, |value, byte| (value << 8) | u64::from(*byte));
+        let ip = match ip_bytes {
+            1 => (self.last_ip & !0xffff) | value,
+            2 => (self.last_ip & !0xffff_ffff) | value,
+            3 => {
+                // Sign-extended from bit 47
+                ((value << 16) as i64 >> 16) as u64
+            }
+            4 => (self.last_ip & !0xffff_ffff_ffff) | value,
+            6 => value,
+            _ => return None,
+        };
+        self.last_ip = ip;
+        Some(ip)
+    }
+}
+
+/// The payload size of a `TIP`-like packet
+fn ip_len(ip_bytes: u8) -> Option<usize> {
+    match ip_bytes {
+        0 => Some(0),
+        1 => Some(2),
+        2 => Some(4),
+        3 | 4 => Some(6),
+        6 => Some(8),
+        _ => None,
+    }
+}
+
+/// Decode the edges of a segment, starting at a `PSB`.
+///
+/// Stops at the first unknown or truncated packet.
+#[allow(clippy::cast_possible_truncation)]
+fn decode_segment(segment: &[u8], map_size: usize) -> Vec<u32> {
+    let mut decoder = SegmentDecoder {
+        map_size: map_size as u64,
+        last_ip: 0,
+        anchor: 0,
+        history: 1,
+        prev: 0,
+        edges: Vec::new(),
+    };
+
+    let mut idx = 0;
+    while idx < segment.len() {
+        let rest = &segment[idx..];
+        let header = rest[0];
+        let len = match header {
+            // PAD
+            0x00 => 1,
+            0x02 => {
+                let Some(ext) = rest.get(1) else {
+                    break;
+                };
+                match ext {
+                    // PSB
+                    0x82 => PSB.len(),
+                    // PSBEND, TraceStop, BEP, EXSTOP
+                    0x23 | 0x83 | 0x33 | 0xb3 | 0x62 | 0xe2 => 2,
+                    // OVF: packets were lost, do not connect the edges around it
+                    0xf3 => {
+                        decoder.prev = 0;
+                        2
+                    }
+                    // Long TNT
+                    0xa3 => {
+                        let Some(payload) = rest.get(2..8) else {
+                            break;
+                        };
+                        decoder.tnt(
+                            payload
+                                .iter()
+                                .rev()
+                                .fold(0, |value, byte| (value << 8) | u64::from(*byte)),
+                        );
+                        8
+                    }
+                    // CBR, PWRE
+                    0x03 | 0x22 => 4,
+                    // BBP
+                    0x63 => 3,
+                    // PIP
+                    0x43 => 8,
+                    // VMCS, TMA, PWRX
+                    0xc8 | 0x73 | 0xa2 => 7,
+                    // MWAIT
+                    0xc2 => 10,
+                    // MNT
+                    0xc3 => 11,
+                    // PTWRITE, with a 4 or 8 byte payload
+                    ext if ext & 0x1f == 0x12 => {
+                        if (ext >> 5) & 0x3 == 0 {
+                            6
+                        } else {
+                            10
+                        }
+                    }
+                    _ => break,
+                }
+            }
+            // MODE
+            0x99 => 2,
+            // TSC
+            0x19 => 8,
+            // MTC
+            0x59 => 2,
+            // Short TNT, a stop bit and up to 6 branches in bits 7:1
+            header if header & 1 == 0 => {
+                decoder.tnt(u64::from(header >> 1));
+                1
+            }
+            // TIP, TIP.PGE, TIP.PGD, FUP
+            header if matches!(header & 0x1f, 0x0d | 0x11 | 0x01 | 0x1d) => {
+                let ip_bytes = header >> 5;
+                let Some(ip_len) = ip_len(ip_bytes) else {
+                    break;
+                };
+                let Some(payload) = rest.get(1..=ip_len) else {
+                    break;
+                };
+                let ip = decoder.ip(ip_bytes, payload);
+                match header & 0x1f {
+                    // Indirect branch, or tracing (re)started at the target
+                    0x0d | 0x11 => {
+                        if let Some(ip) = ip {
+                            decoder.indirect(ip);
+                        }
+                    }
+                    // Tracing stopped, e.g. leaving the filtered range
+                    0x01 => decoder.prev = 0,
+                    // FUP, the source of an asynchronous event
+                    _ => {}
+                }
+                1 + ip_len
+            }
+            // CYC, with continuation bytes if the extension bit is set
+            header if header & 0x3 == 0x3 => {
+                let mut len = 1;
+                if header & 0x4 != 0 {
+                    while rest.get(len).is_some_and(|byte| byte & 1 != 0) {
+                        len += 1;
+                    }
+                    len += 1;
+                }
+                len
+            }
+            _ => break,
+        };
+        idx += len;
+    }
+    decoder.edges
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{decode_segment, PtDecoder, PSB, TNT_HISTORY_BITS};
+
+    /// A segment with a `TIP` to `0x401000`, a short `TNT` with the branches taken, not taken,
+    /// and a `TIP` to `0x401234` (compressed to its lower two bytes)
+    fn segment() -> Vec<u8> {
+        let mut segment = PSB.to_vec();
+        segment.extend([0x02, 0x23]);
+        segment.extend([0x6d, 0x00, 0x10, 0x40, 0x00, 0x00, 0x00]);
+        segment.push(0b0000_1100);
+        segment.extend([0x2d, 0x34, 0x12]);
+        segment
+    }
+
+    #[test]
+    fn test_decode_segment() {
+        let edges = decode_segment(&segment(), 1 << 16);
+        assert_eq!(edges.len(), 4);
+        // Same trace, same edges
+        assert_eq!(edges, decode_segment(&segment(), 1 << 16));
+        // A different branch outcome changes the edges
+        let mut other = segment();
+        other[25] = 0b0000_1010;
+        assert_ne!(edges, decode_segment(&other, 1 << 16));
+    }
+
+    #[test]
+    fn test_decode_loop() {
+        // A loop taking its back edge 120 times, after a `TIP` to `0x401000`
+        let mut segment = PSB.to_vec();
+        segment.extend([0x6d, 0x00, 0x10, 0x40, 0x00, 0x00, 0x00]);
+        segment.extend([0b1111_1110; 20]);
+        let edges = decode_segment(&segment, 1 << 16);
+        assert_eq!(edges.len(), 121);
+        let mut distinct = edges.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        // The iterations after the first few take the same edge
+        assert!(distinct.len() <= TNT_HISTORY_BITS as usize + 2);
+    }
+
+    #[test]
+    fn test_decoder_cache() {
+        let mut decoder = PtDecoder::new(1 << 16);
+        let mut trace = vec![0xff, 0xff];
+        trace.extend(segment());
+        trace.extend(segment());
+        let mut map = vec![0; 1 << 16];
+        decoder.decode(&trace, &mut map);
+        assert_eq!(decoder.cache_hits(), 1);
+        assert_eq!(map.iter().map(|count| u32::from(*count)).sum::<u32>(), 8);
+    }
+}
@@ -87,6 +87,8 @@ pub mod build_id;
     feature = "std"
 ))]
 pub mod cli;
+#[cfg(feature = "alloc")]
+pub mod codec;
 #[cfg(feature = "gzip")]
 pub mod compress;
 #[cfg(feature = "std")]
@@ -94,6 +96,8 @@ pub mod core_affinity;
 pub mod cpu;
 #[cfg(feature = "std")]
 pub mod fs;
+#[cfg(all(feature = "intel_pt", target_os = "linux", target_arch = "x86_64"))]
+pub mod intel_pt;
 #[cfg(feature = "alloc")]
 pub mod llmp;
 pub mod math;
@@ -102,6 +106,8 @@ pub mod minibsod;
 pub mod os;
 #[cfg(feature = "alloc")]
 pub mod ownedref;
+#[cfg(all(feature = "perf", target_os = "linux"))]
+pub mod perf;
 pub mod rands;
 #[cfg(feature = "alloc")]
 pub mod serdeany;
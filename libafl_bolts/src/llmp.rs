@@ -959,6 +959,12 @@ where
         self.id
     }
 
+    /// The [`ShMemProvider`] this sender allocates its pages with
+    #[must_use]
+    pub fn shmem_provider_mut(&mut self) -> &mut SP {
+        &mut self.shmem_provider
+    }
+
     /// Completely reset the current sender map.
     /// Afterwards, no receiver should read from it at a different location.
     /// This is only useful if all connected llmp parties start over, for example after a crash.
@@ -1528,6 +1534,28 @@ where
 
     /// Send a `buf` with the given `flags`.
     pub fn send_buf_with_flags(&mut self, tag: Tag, flags: Flags, buf: &[u8]) -> Result<(), Error> {
+        self.send_buf_from(None, tag, flags, buf)
+    }
+
+    /// Send a `buf` with the given `flags` on behalf of the client `sender`, like a forwarded message
+    pub fn send_buf_with_flags_as(
+        &mut self,
+        sender: ClientId,
+        tag: Tag,
+        flags: Flags,
+        buf: &[u8],
+    ) -> Result<(), Error> {
+        self.send_buf_from(Some(sender), tag, flags, buf)
+    }
+
+    /// Send a `buf`, from this sender or, if set, on behalf of `sender`
+    fn send_buf_from(
+        &mut self,
+        sender: Option<ClientId>,
+        tag: Tag,
+        flags: Flags,
+        buf: &[u8],
+    ) -> Result<(), Error> {
         // Make sure we don't reuse already allocated tags
         if tag == LLMP_TAG_NEW_SHM_CLIENT
             || tag == LLMP_TAG_END_OF_PAGE
@@ -1543,9 +1571,12 @@ where
             let msg = self.alloc_next(buf.len())?;
             (*msg).tag = tag;
             (*msg).flags = flags;
+            if let Some(sender) = sender {
+                (*msg).sender = sender;
+            }
             buf.as_ptr()
                 .copy_to_nonoverlapping((*msg).buf.as_mut_ptr(), buf.len());
-            self.send(msg, true)
+            self.send(msg, sender.is_none())
         }
     }
 
@@ -1576,6 +1607,15 @@ where
         )
     }
 
+    /// If all receivers left the current page of this sender, e.g. because the broker released this client after
+    /// it did not send a message for too long. Messages sent on this page would never be read.
+    #[must_use]
+    pub fn is_abandoned(&self) -> bool {
+        let page = unsafe { &*self.out_shmems.last().unwrap().page() };
+        let joined = page.receivers_joined_count.load(Ordering::Relaxed);
+        joined > 0 && page.receivers_left_count.load(Ordering::Relaxed) >= joined
+    }
+
     /// Send information that this client is exiting.
     /// The other side may free up all allocated memory.
     /// We are no longer allowed to send anything afterwards.
@@ -2073,6 +2113,12 @@ where
     pub exit_cleanly_after: Option<NonZeroUsize>,
     /// Clients that should be removed soon
     clients_to_remove: Vec<ClientId>,
+    /// Clients that did not send a message for longer than `client_timeout`, but may still come back
+    #[cfg(feature = "std")]
+    lost_clients: Vec<ClientId>,
+    /// Clients that did not send a message for this long are considered lost
+    #[cfg(feature = "std")]
+    client_timeout: Option<Duration>,
     /// The `ShMemProvider` to use
     shmem_provider: SP,
 }
@@ -2237,6 +2283,16 @@ where
     fn on_timeout(&mut self) -> Result<(), Error> {
         Ok(())
     }
+
+    /// Hook called whenever a client did not send a message for longer than the client timeout,
+    /// see [`LlmpBrokerInner::set_client_timeout`].
+    fn on_client_lost(
+        &mut self,
+        _broker_inner: &mut LlmpBrokerInner<SP>,
+        _client_id: ClientId,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 /// A tuple of Llmp hooks. They are evaluated sequentially, and returns if one decides to filter out the evaluated message.
@@ -2257,6 +2313,13 @@ where
 
     /// Call all hook callbacks on timeout.
     fn on_timeout_all(&mut self) -> Result<(), Error>;
+
+    /// Call all hook callbacks on a lost client.
+    fn on_client_lost_all(
+        &mut self,
+        inner: &mut LlmpBrokerInner<SP>,
+        client_id: ClientId,
+    ) -> Result<(), Error>;
 }
 
 impl<SP> LlmpHookTuple<SP> for ()
@@ -2278,6 +2341,14 @@ where
     fn on_timeout_all(&mut self) -> Result<(), Error> {
         Ok(())
     }
+
+    fn on_client_lost_all(
+        &mut self,
+        _inner: &mut LlmpBrokerInner<SP>,
+        _client_id: ClientId,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 impl<Head, Tail, SP> LlmpHookTuple<SP> for (Head, Tail)
@@ -2315,6 +2386,15 @@ where
         self.0.on_timeout()?;
         self.1.on_timeout_all()
     }
+
+    fn on_client_lost_all(
+        &mut self,
+        inner: &mut LlmpBrokerInner<SP>,
+        client_id: ClientId,
+    ) -> Result<(), Error> {
+        self.0.on_client_lost(inner, client_id)?;
+        self.1.on_client_lost_all(inner, client_id)
+    }
 }
 
 impl<SP> LlmpBroker<(), SP>
@@ -2643,12 +2723,76 @@ where
                 }
             }
             // log::trace!("{:#?}", self.llmp_clients);
+            #[cfg(feature = "std")]
+            {
+                let clients_to_remove = &self.inner.clients_to_remove;
+                self.inner
+                    .lost_clients
+                    .retain(|client_id| !clients_to_remove.contains(client_id));
+            }
         }
 
         self.inner.clients_to_remove.clear();
+
+        #[cfg(feature = "std")]
+        self.detect_lost_clients()?;
+
         Ok(new_messages)
     }
 
+    /// Marks all clients that did not send a message for longer than the client timeout as lost,
+    /// and calls the `on_client_lost` hooks for them. Releases the clients that stayed lost for another timeout.
+    #[cfg(feature = "std")]
+    fn detect_lost_clients(&mut self) -> Result<(), Error> {
+        let Some(timeout) = self.inner.client_timeout else {
+            return Ok(());
+        };
+        let now = current_time();
+
+        let lost_clients = &self.inner.lost_clients;
+        let mut released = vec![];
+        self.inner.llmp_clients.retain_mut(|client| {
+            let release = lost_clients.contains(&client.id)
+                && now.saturating_sub(client.last_msg_time) > timeout.saturating_mul(2);
+            if release {
+                released.push(client.id);
+                // Leave the page, so that a respawned client does not reattach to it, see
+                // `LlmpSender::is_abandoned`
+                unsafe {
+                    (*client.current_recv_shmem.page_mut()).receiver_left();
+                }
+            }
+            !release
+        });
+        if !released.is_empty() {
+            log::warn!("Clients {released:?} did not come back, releasing them.");
+            self.inner
+                .lost_clients
+                .retain(|client_id| !released.contains(client_id));
+        }
+
+        let newly_lost: Vec<ClientId> = self
+            .inner
+            .llmp_clients
+            .iter()
+            .filter(|client| {
+                !self.inner.listeners.contains(&client.id)
+                    && !self.inner.lost_clients.contains(&client.id)
+                    && now.saturating_sub(client.last_msg_time) > timeout
+            })
+            .map(|client| client.id)
+            .collect();
+
+        for client_id in newly_lost {
+            log::warn!(
+                "Client {client_id:?} did not send a message for {timeout:?}, considering it lost."
+            );
+            self.inner.lost_clients.push(client_id);
+            self.hooks.on_client_lost_all(&mut self.inner, client_id)?;
+        }
+        Ok(())
+    }
+
     /// Broker broadcast to its own page for all others to read
     /// Returns `true` if new messages were broker-ed
     /// It is supposed that the message is never unmapped.
@@ -2682,6 +2826,16 @@ where
                             // set the recv time
                             // We don't do that in recv() to keep calls to `current_time` to a minimum.
                             self.inner.llmp_clients[pos].last_msg_time = current_time();
+                            // We keep listening to lost clients, they may only have been stuck for a while.
+                            if let Some(lost_idx) = self
+                                .inner
+                                .lost_clients
+                                .iter()
+                                .position(|id| *id == client_id)
+                            {
+                                log::info!("Lost client {client_id:?} is back.");
+                                self.inner.lost_clients.swap_remove(lost_idx);
+                            }
                         }
                         return Ok(new_messages);
                     }
@@ -2856,6 +3010,10 @@ where
             },
             llmp_clients: vec![],
             clients_to_remove: Vec::new(),
+            #[cfg(feature = "std")]
+            lost_clients: Vec::new(),
+            #[cfg(feature = "std")]
+            client_timeout: None,
             listeners: vec![],
             exit_cleanly_after: None,
             num_clients_seen: 0,
@@ -2877,6 +3035,12 @@ where
         )
     }
 
+    /// The [`ShMemProvider`] of the broker, e.g. to map shared memory referenced by a message
+    #[must_use]
+    pub fn shmem_provider_mut(&mut self) -> &mut SP {
+        &mut self.shmem_provider
+    }
+
     /// Create a new [`LlmpBrokerInner`] attaching to a TCP port
     #[cfg(feature = "std")]
     pub fn create_attach_to_tcp(shmem_provider: SP, port: u16) -> Result<Self, Error> {
@@ -2910,6 +3074,25 @@ where
         self.exit_cleanly_after = Some(n_clients);
     }
 
+    /// Consider clients lost if they did not send any message for `timeout`.
+    ///
+    /// Lost clients are reported to the [`LlmpHook::on_client_lost`] hooks. The broker still reads their pages,
+    /// so a client that was merely stuck (or got respawned onto its old page) is picked up again as soon as it
+    /// sends the next message, and they still count as connected, e.g. for `exit_cleanly_after`.
+    /// A client that stays silent for another `timeout` after it got lost is released: the broker drops its pages,
+    /// as if it had exited. Listeners are never considered lost.
+    #[cfg(feature = "std")]
+    pub fn set_client_timeout(&mut self, timeout: Duration) {
+        self.client_timeout = Some(timeout);
+    }
+
+    /// The clients currently considered lost, see [`Self::set_client_timeout`]
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn lost_clients(&self) -> &[ClientId] {
+        &self.lost_clients
+    }
+
     /// Add a client to this broker.
     /// Will set an appropriate [`ClientId`] before pushing the client to the internal vec.
     /// Will increase `num_clients_seen`.
@@ -3060,7 +3243,15 @@ where
     /// talking to other brokers via TCP, and accepting new clients over this port.
     #[inline]
     fn has_clients(&self) -> bool {
-        self.llmp_clients.len() > self.listeners.len()
+        self.num_clients() > 0
+    }
+
+    /// The number of clients currently connected, receiving the messages broadcast by this broker.
+    /// Ignores listener threads that belong to the broker. Lost clients count until they are released,
+    /// see [`Self::set_client_timeout`].
+    #[must_use]
+    pub fn num_clients(&self) -> usize {
+        self.llmp_clients.len().saturating_sub(self.listeners.len())
     }
 
     /// Broadcasts the given buf to all clients
@@ -3073,6 +3264,20 @@ where
         self.llmp_out.send_buf_with_flags(tag, flags, buf)
     }
 
+    /// Broadcasts a `buf` with the given `flags` on behalf of the client `sender`, like a forwarded message.
+    ///
+    /// Clients usually skip their own messages, so `sender` will not handle it, e.g. when a hook replaced its message.
+    pub fn forward_buf_with_flags(
+        &mut self,
+        sender: ClientId,
+        tag: Tag,
+        flags: Flags,
+        buf: &[u8],
+    ) -> Result<(), Error> {
+        self.llmp_out
+            .send_buf_with_flags_as(sender, tag, flags, buf)
+    }
+
     /// Launches a thread using a tcp listener socket, on which new clients may connect to this broker.
     /// Does so on the given port.
     #[cfg(feature = "std")]
@@ -3524,15 +3729,23 @@ where
     }
 
     /// Create an existing client from description
+    ///
+    /// Fails if the broker released this client in the meantime, see [`LlmpSender::is_abandoned`]; the client then
+    /// has to register with the broker again.
     pub fn existing_client_from_description(
         shmem_provider: SP,
         description: &LlmpClientDescription,
     ) -> Result<Self, Error> {
+        let sender =
+            LlmpSender::on_existing_from_description(shmem_provider.clone(), &description.sender)?;
+        if sender.is_abandoned() {
+            return Err(Error::illegal_state(format!(
+                "The broker released client {:?}, it has to register again",
+                sender.id
+            )));
+        }
         Ok(Self {
-            sender: LlmpSender::on_existing_from_description(
-                shmem_provider.clone(),
-                &description.sender,
-            )?,
+            sender,
             receiver: LlmpReceiver::on_existing_from_description(
                 shmem_provider,
                 &description.receiver,
@@ -3564,6 +3777,12 @@ where
         &mut self.receiver
     }
 
+    /// The [`ShMemProvider`] of this client
+    #[must_use]
+    pub fn shmem_provider_mut(&mut self) -> &mut SP {
+        self.sender.shmem_provider_mut()
+    }
+
     /// Waits for the sender to be save to unmap.
     /// If a receiver is involved on the other side, this function should always be called.
     pub fn await_safe_to_unmap_blocking(&self) {
@@ -3787,11 +4006,15 @@ mod tests {
     use serial_test::serial;
 
     use super::{
-        LlmpClient,
+        next_shmem_size, Flags, LlmpBroker, LlmpBrokerInner, LlmpClient,
         LlmpConnection::{self, IsBroker, IsClient},
-        Tag,
+        LlmpHook, LlmpMsgHookResult, LlmpSharedMap, Tag,
+    };
+    use crate::{
+        shmem::{ShMemProvider, StdShMemProvider},
+        tuples::tuple_list,
+        ClientId, Error,
     };
-    use crate::shmem::{ShMemProvider, StdShMemProvider};
 
     #[test]
     #[serial]
@@ -3841,4 +4064,90 @@ mod tests {
         // We want at least the tcp and sender clients.
         assert_eq!(broker.inner.llmp_clients.len(), 2);
     }
+
+    /// Counts the lost clients
+    #[derive(Debug, Default)]
+    struct LostClientsHook {
+        lost: Vec<ClientId>,
+    }
+
+    impl<SP> LlmpHook<SP> for LostClientsHook
+    where
+        SP: ShMemProvider,
+    {
+        fn on_new_message(
+            &mut self,
+            _broker_inner: &mut LlmpBrokerInner<SP>,
+            _client_id: ClientId,
+            _msg_tag: &mut Tag,
+            _msg_flags: &mut Flags,
+            _msg: &mut [u8],
+            _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
+        ) -> Result<LlmpMsgHookResult, Error> {
+            Ok(LlmpMsgHookResult::ForwardToClients)
+        }
+
+        fn on_client_lost(
+            &mut self,
+            _broker_inner: &mut LlmpBrokerInner<SP>,
+            client_id: ClientId,
+        ) -> Result<(), Error> {
+            self.lost.push(client_id);
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_llmp_stalled_client() {
+        let mut shmem_provider = StdShMemProvider::new().unwrap();
+        let mut broker = LlmpBroker::new(
+            shmem_provider.clone(),
+            tuple_list!(LostClientsHook::default()),
+        )
+        .unwrap();
+        broker
+            .inner_mut()
+            .set_client_timeout(Duration::from_millis(50));
+
+        // A client that never sends a message
+        let client = LlmpClient::new(
+            shmem_provider.clone(),
+            LlmpSharedMap::new(
+                ClientId(0),
+                shmem_provider.new_shmem(next_shmem_size(0)).unwrap(),
+            ),
+            ClientId(0),
+        )
+        .unwrap();
+        let description = client.describe().unwrap();
+        let page = LlmpSharedMap::existing(
+            shmem_provider
+                .shmem_from_description(description.sender.shmem)
+                .unwrap(),
+        );
+        let client_id = broker.inner_mut().register_client(page);
+        broker.broker_once().unwrap();
+        assert!(broker.hooks.0.lost.is_empty());
+
+        // Lost, but it may still come back: keep the broker running
+        sleep(Duration::from_millis(60));
+        broker.broker_once().unwrap();
+        assert_eq!(broker.hooks.0.lost, [client_id]);
+        assert_eq!(broker.inner.lost_clients(), [client_id]);
+        assert!(broker.inner.has_clients());
+
+        // Still silent after another timeout, released
+        sleep(Duration::from_millis(60));
+        broker.broker_once().unwrap();
+        assert_eq!(broker.hooks.0.lost, [client_id]);
+        assert!(broker.inner.lost_clients().is_empty());
+        assert!(!broker.inner.has_clients());
+
+        // A respawned client may not reattach to its released page, no one would read it
+        assert!(client.sender().is_abandoned());
+        assert!(
+            LlmpClient::existing_client_from_description(shmem_provider, &description).is_err()
+        );
+    }
 }
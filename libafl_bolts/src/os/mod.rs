@@ -19,6 +19,7 @@ pub mod pipes;
 use alloc::borrow::Cow;
 #[cfg(all(unix, feature = "std"))]
 use core::ffi::CStr;
+use core::time::Duration;
 #[cfg(feature = "std")]
 use std::{env, process::Command};
 #[cfg(all(unix, feature = "std"))]
@@ -32,6 +33,7 @@ use std::{fs::File, os::fd::AsRawFd, sync::OnceLock};
 pub mod windows_exceptions;
 #[cfg(unix)]
 use libc::pid_t;
+use serde::{Deserialize, Serialize};
 #[cfg(all(windows, feature = "std"))]
 pub use windows_exceptions::CTRL_C_EXIT;
 
@@ -137,6 +139,75 @@ pub fn peak_rss_mb_child_processes() -> Result<i64, Error> {
     Ok(rss.ru_maxrss >> 10)
 }
 
+/// The resource usage of a process, e.g. to diagnose sick fuzzer clients
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessMetrics {
+    /// The resident set size, in bytes. The peak resident set size on systems without `/proc`.
+    pub rss_bytes: u64,
+    /// The CPU time spent by the process so far, in user and system mode
+    pub cpu_time: Duration,
+    /// The number of open file descriptors, if known
+    pub open_fds: Option<u64>,
+    /// The load average of the host over the last minute, if known
+    pub load_average: Option<f64>,
+}
+
+#[cfg(all(unix, feature = "std"))]
+impl ProcessMetrics {
+    /// The resource usage of the current process
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    pub fn current() -> Result<Self, Error> {
+        use core::mem;
+        use std::{fs, io};
+
+        use libc::{rusage, RUSAGE_SELF};
+
+        let usage = unsafe {
+            let mut usage = mem::MaybeUninit::<rusage>::uninit();
+            if libc::getrusage(RUSAGE_SELF, usage.as_mut_ptr()) == -1 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(usage.assume_init())
+            }
+        }?;
+        let timeval = |tv: libc::timeval| {
+            Duration::new(tv.tv_sec as u64, (tv.tv_usec as u32).saturating_mul(1000))
+        };
+        let cpu_time = timeval(usage.ru_utime) + timeval(usage.ru_stime);
+
+        // `ru_maxrss` is in KiB, except on Apple systems
+        #[cfg(target_vendor = "apple")]
+        let peak_rss = usage.ru_maxrss as u64;
+        #[cfg(not(target_vendor = "apple"))]
+        let peak_rss = (usage.ru_maxrss as u64) << 10;
+        // The second field of `statm` is the number of resident pages
+        let rss_bytes = fs::read_to_string("/proc/self/statm")
+            .ok()
+            .and_then(|statm| statm.split_whitespace().nth(1)?.parse::<u64>().ok())
+            .map_or(peak_rss, |pages| {
+                pages * unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64
+            });
+
+        // Do not count the fd of the directory itself
+        let open_fds = ["/proc/self/fd", "/dev/fd"].iter().find_map(|dir| {
+            fs::read_dir(dir)
+                .ok()
+                .map(|entries| (entries.count() as u64).saturating_sub(1))
+        });
+
+        let mut load = [0.0_f64; 1];
+        let load_average =
+            (unsafe { libc::getloadavg(load.as_mut_ptr(), 1) } == 1).then_some(load[0]);
+
+        Ok(Self {
+            rss_bytes,
+            cpu_time,
+            open_fds,
+            load_average,
+        })
+    }
+}
+
 /// "Safe" wrapper around dup2
 ///
 /// # Safety
@@ -175,3 +246,21 @@ pub fn null_fd() -> Result<RawFd, Error> {
         Ok(NULL_FILE.get_or_init(move || null_file).as_raw_fd())
     }
 }
+
+#[cfg(all(test, unix, feature = "std"))]
+mod tests {
+    use super::ProcessMetrics;
+
+    #[test]
+    fn test_process_metrics() {
+        let metrics = ProcessMetrics::current().unwrap();
+        assert!(metrics.rss_bytes > 0);
+        // The fds listing itself is not counted, but the test harness keeps a few files open
+        if let Some(open_fds) = metrics.open_fds {
+            assert!(open_fds > 0);
+        }
+
+        // The CPU time never goes backwards
+        assert!(ProcessMetrics::current().unwrap().cpu_time >= metrics.cpu_time);
+    }
+}
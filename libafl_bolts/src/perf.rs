@@ -0,0 +1,583 @@
+//! Hardware branch tracing of processes through the `perf` subsystem of Linux.
+//!
+//! [`PerfEvent`] is a `perf` event of another process, with its sample and AUX ring buffers mapped.
+//! On top of it, [`BranchTracer`] records the branches taken by a process, for the coverage of binaries
+//! without instrumentation on machines without Intel PT (see [`crate::intel_pt`]):
+//!
+//! * [`BranchSource::Lbr`] samples the last branch record (LBR) of the CPU, or its equivalent on other vendors.
+//!   Every `sample_period` branches, the kernel stores the last 8 to 32 branches the target took. This is cheap,
+//!   but only a fraction of the edges is seen, and which one depends on timing: rare edges may be missed, and the
+//!   coverage of an input varies between executions. Use a low period, and expect more unstable entries.
+//! * [`BranchSource::Bts`] uses the branch trace store (BTS) of Intel CPUs, which records *every* branch.
+//!   The coverage is exact, but the target runs many times slower, and long executions overflow the buffer.
+
+use alloc::vec::Vec;
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{fence, Ordering},
+};
+use std::{ffi::CString, fs};
+
+use crate::Error;
+
+/// The size of the `perf_event_attr` we pass, `PERF_ATTR_SIZE_VER5`
+const PERF_ATTR_SIZE: u32 = 112;
+
+/// The `ioctl`s of a `perf` event fd, see `linux/perf_event.h`
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+const PERF_EVENT_IOC_SET_FILTER: libc::c_ulong = 0x4008_2406;
+
+/// Offsets into the `perf_event_mmap_page`, see `linux/perf_event.h`
+const DATA_HEAD_OFFSET: usize = 1024;
+const DATA_TAIL_OFFSET: usize = 1032;
+const AUX_HEAD_OFFSET: usize = 1056;
+const AUX_TAIL_OFFSET: usize = 1064;
+const AUX_OFFSET_OFFSET: usize = 1072;
+const AUX_SIZE_OFFSET: usize = 1080;
+
+/// Don't count, or record, until enabled
+pub const ATTR_DISABLED: u64 = 1 << 0;
+/// Only record user space
+pub const ATTR_EXCLUDE_KERNEL: u64 = 1 << 5;
+/// Don't record the hypervisor
+pub const ATTR_EXCLUDE_HV: u64 = 1 << 6;
+
+/// `PERF_TYPE_HARDWARE`
+const PERF_TYPE_HARDWARE: u32 = 0;
+/// `PERF_COUNT_HW_BRANCH_INSTRUCTIONS`
+const PERF_COUNT_HW_BRANCH_INSTRUCTIONS: u64 = 4;
+/// `PERF_SAMPLE_BRANCH_STACK`
+const PERF_SAMPLE_BRANCH_STACK: u64 = 1 << 11;
+/// `PERF_SAMPLE_BRANCH_USER | PERF_SAMPLE_BRANCH_ANY`
+const PERF_SAMPLE_BRANCH_USER_ANY: u64 = (1 << 0) | (1 << 3);
+/// `PERF_RECORD_SAMPLE`
+const PERF_RECORD_SAMPLE: u32 = 9;
+
+/// The attributes of a `perf` event, `struct perf_event_attr` up to `PERF_ATTR_SIZE_VER5`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct PerfEventAttr {
+    pub type_: u32,
+    pub size: u32,
+    pub config: u64,
+    pub sample_period: u64,
+    pub sample_type: u64,
+    pub read_format: u64,
+    /// The bitfield of flags, e.g. [`ATTR_DISABLED`]
+    pub flags: u64,
+    pub wakeup_events: u32,
+    pub bp_type: u32,
+    pub config1: u64,
+    pub config2: u64,
+    pub branch_sample_type: u64,
+    pub sample_regs_user: u64,
+    pub sample_stack_user: u32,
+    pub clockid: i32,
+    pub sample_regs_intr: u64,
+    pub aux_watermark: u32,
+    pub sample_max_stack: u16,
+    pub reserved: u16,
+}
+
+impl PerfEventAttr {
+    /// Attributes of an event of the given `type_` and `config`, disabled, and for user space only
+    #[must_use]
+    pub fn new(type_: u32, config: u64) -> Self {
+        Self {
+            type_,
+            size: PERF_ATTR_SIZE,
+            config,
+            flags: ATTR_DISABLED | ATTR_EXCLUDE_KERNEL | ATTR_EXCLUDE_HV,
+            ..Self::default()
+        }
+    }
+}
+
+/// The type of the dynamic `perf` PMU `pmu`, e.g. `intel_pt`, or `None` if this machine does not have it
+#[must_use]
+pub fn pmu_type(pmu: &str) -> Option<u32> {
+    fs::read_to_string(format!("/sys/bus/event_source/devices/{pmu}/type"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// The bit of a config term of the PMU `pmu`, from `format/<term>` in sysfs, e.g. `config:13`
+pub fn pmu_config_bit(pmu: &str, term: &str) -> Result<u64, Error> {
+    let format = fs::read_to_string(format!("/sys/bus/event_source/devices/{pmu}/format/{term}"))?;
+    format
+        .trim()
+        .strip_prefix("config:")
+        .and_then(|bit| bit.parse::<u32>().ok())
+        .map(|bit| 1 << bit)
+        .ok_or_else(|| Error::unsupported(format!("Unexpected format of {pmu}/{term}: {format}")))
+}
+
+/// Check whether the current user may trace other processes with `perf`
+pub fn check_permissions() -> Result<(), Error> {
+    let paranoid = fs::read_to_string("/proc/sys/kernel/perf_event_paranoid")?;
+    // # Safety
+    // `geteuid` has no preconditions
+    if paranoid.trim().parse::<i32>().unwrap_or(i32::MAX) > 1 && unsafe { libc::geteuid() } != 0 {
+        return Err(Error::unsupported(
+            "Tracing other processes with perf needs root or `/proc/sys/kernel/perf_event_paranoid` <= 1",
+        ));
+    }
+    Ok(())
+}
+
+/// The spreading function of block addresses over coverage maps
+pub(crate) fn mix(mut value: u64) -> u64 {
+    // The finalizer of `splitmix64`
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+/// A `perf` event of a process, with its ring buffers mapped
+#[derive(Debug)]
+pub struct PerfEvent {
+    fd: libc::c_int,
+    base: *mut c_void,
+    page_size: usize,
+    data_size: usize,
+    aux: *mut u8,
+    aux_size: usize,
+}
+
+impl PerfEvent {
+    /// Open the event described by `attr` for the process `pid`, only the process itself, not its children.
+    ///
+    /// The sample ring buffer has `data_size` bytes, and the AUX buffer, for events writing their own format like
+    /// Intel PT, `aux_size` bytes, if not 0. Both are rounded up to a power of two number of pages.
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    pub fn new(
+        attr: &PerfEventAttr,
+        pid: libc::pid_t,
+        data_size: usize,
+        aux_size: usize,
+    ) -> Result<Self, Error> {
+        // # Safety
+        // The attributes are a valid `perf_event_attr` of `PERF_ATTR_SIZE` bytes.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                ptr::from_ref(attr),
+                pid,
+                -1,
+                -1,
+                libc::PERF_FLAG_FD_CLOEXEC,
+            )
+        } as libc::c_int;
+        if fd == -1 {
+            return Err(Error::last_os_error(format!(
+                "Failed to open a perf event of type {} for {pid}",
+                attr.type_
+            )));
+        }
+
+        // # Safety
+        // `sysconf` has no preconditions
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let round = |size: usize| size.div_ceil(page_size).next_power_of_two() * page_size;
+        let mut event = Self {
+            fd,
+            base: libc::MAP_FAILED,
+            page_size,
+            data_size: round(data_size.max(1)),
+            aux: ptr::null_mut(),
+            aux_size: 0,
+        };
+        // # Safety
+        // Maps the buffers of the event, the kernel checks the sizes.
+        unsafe {
+            let base_size = event.base_size();
+            event.base = libc::mmap(
+                ptr::null_mut(),
+                base_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            );
+            if event.base == libc::MAP_FAILED {
+                return Err(Error::last_os_error("Failed to map the perf buffer"));
+            }
+            if aux_size > 0 {
+                let aux_size = round(aux_size);
+                event
+                    .word(AUX_OFFSET_OFFSET)
+                    .write_volatile(base_size as u64);
+                event.word(AUX_SIZE_OFFSET).write_volatile(aux_size as u64);
+                let aux = libc::mmap(
+                    ptr::null_mut(),
+                    aux_size,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    fd,
+                    base_size as libc::off_t,
+                );
+                if aux == libc::MAP_FAILED {
+                    return Err(Error::last_os_error("Failed to map the perf AUX buffer"));
+                }
+                event.aux = aux.cast();
+                event.aux_size = aux_size;
+            }
+        }
+        Ok(event)
+    }
+
+    /// The metadata page and the sample buffer
+    fn base_size(&self) -> usize {
+        self.page_size + self.data_size
+    }
+
+    /// A word of the metadata page
+    fn word(&self, offset: usize) -> *mut u64 {
+        // # Safety
+        // The metadata page is mapped, and the offsets are within it.
+        unsafe { self.base.byte_add(offset).cast() }
+    }
+
+    /// Only record the given address ranges, in the `perf` filter syntax, e.g.
+    /// `filter 0x1000/0x500@/usr/bin/target` for `0x500` bytes at offset `0x1000` of the `target` binary.
+    pub fn set_filter(&mut self, filter: &str) -> Result<(), Error> {
+        let filter = CString::new(filter)
+            .map_err(|_| Error::illegal_argument("The perf filter contains a NUL byte"))?;
+        // # Safety
+        // The filter is a valid C string.
+        if unsafe { libc::ioctl(self.fd, PERF_EVENT_IOC_SET_FILTER, filter.as_ptr()) } == -1 {
+            return Err(Error::last_os_error("Failed to set the perf filter"));
+        }
+        Ok(())
+    }
+
+    /// Start recording
+    pub fn enable(&mut self) -> Result<(), Error> {
+        self.ioctl(PERF_EVENT_IOC_ENABLE)
+    }
+
+    /// Stop recording
+    pub fn disable(&mut self) -> Result<(), Error> {
+        self.ioctl(PERF_EVENT_IOC_DISABLE)
+    }
+
+    fn ioctl(&mut self, request: libc::c_ulong) -> Result<(), Error> {
+        // # Safety
+        // The request takes no argument.
+        if unsafe { libc::ioctl(self.fd, request, 0) } == -1 {
+            return Err(Error::last_os_error("perf ioctl failed"));
+        }
+        Ok(())
+    }
+
+    /// The size of the AUX buffer, 0 if there is none
+    #[must_use]
+    pub fn aux_size(&self) -> usize {
+        self.aux_size
+    }
+
+    /// Append the records written to the sample buffer since the last call, and free their space.
+    pub fn read_data(&mut self, data: &mut Vec<u8>) -> Result<(), Error> {
+        // # Safety
+        // The sample buffer follows the metadata page.
+        let buf = unsafe { self.base.byte_add(self.page_size).cast::<u8>() };
+        self.read_ring(
+            DATA_HEAD_OFFSET,
+            DATA_TAIL_OFFSET,
+            buf,
+            self.data_size,
+            data,
+        )
+    }
+
+    /// Append the data written to the AUX buffer since the last call, and free its space.
+    ///
+    /// If the event wrote more than [`PerfEvent::aux_size`] bytes in between, the rest was dropped.
+    pub fn read_aux(&mut self, data: &mut Vec<u8>) -> Result<(), Error> {
+        if self.aux.is_null() {
+            return Err(Error::illegal_state("This perf event has no AUX buffer"));
+        }
+        self.read_ring(
+            AUX_HEAD_OFFSET,
+            AUX_TAIL_OFFSET,
+            self.aux,
+            self.aux_size,
+            data,
+        )
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn read_ring(
+        &mut self,
+        head_offset: usize,
+        tail_offset: usize,
+        buf: *mut u8,
+        size: usize,
+        data: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        // # Safety
+        // The buffers are mapped, the kernel only writes the head.
+        unsafe {
+            let head = self.word(head_offset).read_volatile();
+            // Pairs with the kernel's barrier after writing the data
+            fence(Ordering::Acquire);
+            let tail = self.word(tail_offset).read_volatile();
+            if head.wrapping_sub(tail) > size as u64 {
+                return Err(Error::illegal_state(
+                    "The perf buffer is inconsistent, the records were lost",
+                ));
+            }
+            let ring = core::slice::from_raw_parts(buf, size);
+            let start = (tail % size as u64) as usize;
+            let end = (head % size as u64) as usize;
+            if head != tail {
+                if start < end {
+                    data.extend_from_slice(&ring[start..end]);
+                } else {
+                    data.extend_from_slice(&ring[start..]);
+                    data.extend_from_slice(&ring[..end]);
+                }
+            }
+            // We are done reading before the kernel may overwrite it
+            fence(Ordering::Release);
+            self.word(tail_offset).write_volatile(head);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PerfEvent {
+    fn drop(&mut self) {
+        // # Safety
+        // Unmaps what we mapped, and closes our fd.
+        unsafe {
+            if !self.aux.is_null() {
+                libc::munmap(self.aux.cast(), self.aux_size);
+            }
+            if self.base != libc::MAP_FAILED {
+                libc::munmap(self.base, self.base_size());
+            }
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Where a [`BranchTracer`] gets the branches from, see the [module-level documentation](self)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchSource {
+    /// Sample the last branch record every `sample_period` branches
+    Lbr {
+        /// The number of branches between two samples
+        sample_period: u64,
+    },
+    /// Record all branches with the branch trace store
+    Bts,
+}
+
+impl BranchSource {
+    /// The default sample period of [`BranchSource::Lbr`]
+    pub const DEFAULT_SAMPLE_PERIOD: u64 = 1000;
+
+    /// Use [`BranchSource::Lbr`] if branch stack sampling works on this machine, else [`BranchSource::Bts`] if it is
+    /// available, else fail.
+    pub fn detect() -> Result<Self, Error> {
+        let lbr = Self::Lbr {
+            sample_period: Self::DEFAULT_SAMPLE_PERIOD,
+        };
+        // Open, but never enable, an event for ourselves
+        if PerfEvent::new(&lbr.attr()?, 0, 0, 0).is_ok() {
+            return Ok(lbr);
+        }
+        if pmu_type("intel_bts").is_some() {
+            return Ok(Self::Bts);
+        }
+        Err(Error::unsupported(
+            "Neither branch stack sampling nor the branch trace store are available on this machine",
+        ))
+    }
+
+    fn attr(&self) -> Result<PerfEventAttr, Error> {
+        Ok(match self {
+            Self::Lbr { sample_period } => PerfEventAttr {
+                sample_period: *sample_period,
+                sample_type: PERF_SAMPLE_BRANCH_STACK,
+                branch_sample_type: PERF_SAMPLE_BRANCH_USER_ANY,
+                ..PerfEventAttr::new(PERF_TYPE_HARDWARE, PERF_COUNT_HW_BRANCH_INSTRUCTIONS)
+            },
+            Self::Bts => {
+                let Some(type_) = pmu_type("intel_bts") else {
+                    return Err(Error::unsupported(
+                        "The branch trace store is not available on this machine",
+                    ));
+                };
+                PerfEventAttr::new(type_, 0)
+            }
+        })
+    }
+}
+
+/// Records the branches taken by a process, from a [`BranchSource`]
+#[derive(Debug)]
+pub struct BranchTracer {
+    event: PerfEvent,
+    source: BranchSource,
+    buf: Vec<u8>,
+}
+
+impl BranchTracer {
+    /// Record the branches of the process `pid` into buffers of `buffer_size` bytes.
+    ///
+    /// The recording starts with [`BranchTracer::enable`].
+    pub fn new(pid: libc::pid_t, source: BranchSource, buffer_size: usize) -> Result<Self, Error> {
+        let attr = source.attr()?;
+        let event = match source {
+            BranchSource::Lbr { .. } => PerfEvent::new(&attr, pid, buffer_size, 0)?,
+            // The branch trace store writes into the AUX buffer
+            BranchSource::Bts => PerfEvent::new(&attr, pid, 0, buffer_size)?,
+        };
+        Ok(Self {
+            event,
+            source,
+            buf: Vec::new(),
+        })
+    }
+
+    /// The source of the branches
+    #[must_use]
+    pub fn source(&self) -> BranchSource {
+        self.source
+    }
+
+    /// Start recording
+    pub fn enable(&mut self) -> Result<(), Error> {
+        self.event.enable()
+    }
+
+    /// Stop recording
+    pub fn disable(&mut self) -> Result<(), Error> {
+        self.event.disable()
+    }
+
+    /// Append the `(from, to)` addresses of the branches recorded since the last call
+    pub fn read_branches(&mut self, branches: &mut Vec<(u64, u64)>) -> Result<(), Error> {
+        self.buf.clear();
+        match self.source {
+            BranchSource::Lbr { .. } => {
+                self.event.read_data(&mut self.buf)?;
+                parse_lbr_samples(&self.buf, branches);
+            }
+            BranchSource::Bts => {
+                self.event.read_aux(&mut self.buf)?;
+                parse_bts_records(&self.buf, branches);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_ne_bytes(
+        buf.get(offset..offset + 8)?.try_into().unwrap(),
+    ))
+}
+
+/// Parse the `PERF_RECORD_SAMPLE`s with only a `PERF_SAMPLE_BRANCH_STACK` of the sample buffer
+#[allow(clippy::cast_possible_truncation)]
+fn parse_lbr_samples(buf: &[u8], branches: &mut Vec<(u64, u64)>) {
+    let mut offset = 0;
+    // struct perf_event_header { u32 type; u16 misc; u16 size; }
+    while let Some(header) = buf.get(offset..offset + 8) {
+        let type_ = u32::from_ne_bytes(header[0..4].try_into().unwrap());
+        let size = usize::from(u16::from_ne_bytes(header[6..8].try_into().unwrap()));
+        if size < 8 {
+            break;
+        }
+        if type_ == PERF_RECORD_SAMPLE {
+            let record = &buf[offset..(offset + size).min(buf.len())];
+            // u64 nr; struct perf_branch_entry { u64 from; u64 to; u64 flags; } [nr]
+            let nr = read_u64(record, 8).unwrap_or(0) as usize;
+            for entry in 0..nr {
+                let entry_offset = 16 + entry * 24;
+                let (Some(from), Some(to)) = (
+                    read_u64(record, entry_offset),
+                    read_u64(record, entry_offset + 8),
+                ) else {
+                    break;
+                };
+                branches.push((from, to));
+            }
+        }
+        offset += size;
+    }
+}
+
+/// Parse the `struct bts_record { u64 from; u64 to; u64 misc; }`s of the AUX buffer
+fn parse_bts_records(buf: &[u8], branches: &mut Vec<(u64, u64)>) {
+    for record in buf.chunks_exact(24) {
+        let (Some(from), Some(to)) = (read_u64(record, 0), read_u64(record, 8)) else {
+            break;
+        };
+        branches.push((from, to));
+    }
+}
+
+/// Count the branches as edges in an AFL-style coverage `map`
+#[allow(clippy::cast_possible_truncation)]
+pub fn record_edges(branches: &[(u64, u64)], map: &mut [u8]) {
+    for (from, to) in branches {
+        let idx = ((mix(*from) >> 1) ^ mix(*to)) % map.len() as u64;
+        let entry = &mut map[idx as usize];
+        *entry = entry.saturating_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{parse_bts_records, parse_lbr_samples, record_edges, PERF_RECORD_SAMPLE};
+
+    #[test]
+    fn test_parse_lbr_samples() {
+        let mut buf = Vec::new();
+        // A sample with two branches
+        buf.extend(PERF_RECORD_SAMPLE.to_ne_bytes());
+        buf.extend(0_u16.to_ne_bytes());
+        buf.extend(64_u16.to_ne_bytes());
+        buf.extend(2_u64.to_ne_bytes());
+        for (from, to) in [(0x1000_u64, 0x2000_u64), (0x2004, 0x1000)] {
+            buf.extend(from.to_ne_bytes());
+            buf.extend(to.to_ne_bytes());
+            buf.extend(0_u64.to_ne_bytes());
+        }
+        // Another record type, skipped
+        buf.extend(2_u32.to_ne_bytes());
+        buf.extend(0_u16.to_ne_bytes());
+        buf.extend(16_u16.to_ne_bytes());
+        buf.extend(0_u64.to_ne_bytes());
+
+        let mut branches = Vec::new();
+        parse_lbr_samples(&buf, &mut branches);
+        assert_eq!(branches, [(0x1000, 0x2000), (0x2004, 0x1000)]);
+
+        let mut map = vec![0; 1 << 16];
+        record_edges(&branches, &mut map);
+        assert_eq!(map.iter().map(|count| u32::from(*count)).sum::<u32>(), 2);
+    }
+
+    #[test]
+    fn test_parse_bts_records() {
+        let buf: Vec<u8> = [0x1000_u64, 0x2000, 0, 0x2004, 0x1000, 0]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        let mut branches = Vec::new();
+        parse_bts_records(&buf, &mut branches);
+        assert_eq!(branches, [(0x1000, 0x2000), (0x2004, 0x1000)]);
+    }
+}
@@ -0,0 +1,150 @@
+//! A [`Rand`] wrapper reseeding itself in forked children.
+//!
+//! After a `fork`, parent and child share the state of their generators, so without reseeding, a fork-based
+//! executor would run the same mutations in every child. The [`ForkSafeRand`] notices the fork and reseeds the
+//! child from the OS entropy source, see [`os_random_seed`].
+
+#[cfg(unix)]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(unix)]
+use std::sync::Once;
+
+use serde::{Deserialize, Serialize};
+
+use super::{os_random_seed, Rand, StdRand};
+
+/// The number of forks the current process is away from the first process that registered the fork handler
+#[cfg(unix)]
+static FORK_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(unix)]
+static REGISTER_FORK_HANDLER: Once = Once::new();
+
+#[cfg(unix)]
+extern "C" fn on_fork_child() {
+    FORK_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The current fork generation. A change means that this process is a forked child.
+///
+/// This is a relaxed atomic load after the first call, cheap enough for each generated number.
+#[cfg(unix)]
+fn fork_generation() -> usize {
+    REGISTER_FORK_HANDLER.call_once(|| {
+        // # Safety
+        // The handler only increments an atomic, which is async-signal-safe.
+        unsafe {
+            libc::pthread_atfork(None, None, Some(on_fork_child));
+        }
+    });
+    FORK_GENERATION.load(Ordering::Relaxed)
+}
+
+/// There is no `fork` on this platform.
+#[cfg(not(unix))]
+fn fork_generation() -> usize {
+    0
+}
+
+/// The standard [`ForkSafeRand`]
+pub type StdForkSafeRand = ForkSafeRand<StdRand>;
+
+/// A [`Rand`] reseeding the wrapped generator from [`os_random_seed`] when it is used in a forked child.
+///
+/// Use it as the RNG of the state, e.g. with fork-based executors mutating in the child,
+/// so that each child gets its own stream of random numbers.
+/// Note that the reseeding breaks the reproducibility of an explicit seed in the children.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkSafeRand<R> {
+    inner: R,
+    #[serde(skip, default = "fork_generation")]
+    generation: usize,
+}
+
+impl<R> ForkSafeRand<R>
+where
+    R: Rand,
+{
+    /// Wrap a generator, making it fork-safe
+    #[must_use]
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            generation: fork_generation(),
+        }
+    }
+
+    /// The wrapped generator
+    #[must_use]
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwrap the generator
+    #[must_use]
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> ForkSafeRand<R>
+where
+    R: Rand + Default,
+{
+    /// Create a fork-safe generator, seeded with the given seed
+    #[must_use]
+    pub fn with_seed(seed: u64) -> Self {
+        let mut inner = R::default();
+        inner.set_seed(seed);
+        Self::new(inner)
+    }
+}
+
+impl<R> Default for ForkSafeRand<R>
+where
+    R: Rand + Default,
+{
+    /// Creates a fork-safe generator, seeded with [`os_random_seed`]
+    fn default() -> Self {
+        Self::with_seed(os_random_seed())
+    }
+}
+
+impl<R> Rand for ForkSafeRand<R>
+where
+    R: Rand,
+{
+    fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed);
+        self.generation = fork_generation();
+    }
+
+    #[inline]
+    fn next(&mut self) -> u64 {
+        let generation = fork_generation();
+        if generation != self.generation {
+            self.generation = generation;
+            self.inner.set_seed(os_random_seed());
+        }
+        self.inner.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rands::{Rand, StdForkSafeRand, StdRand};
+
+    #[test]
+    fn test_fork_safe_rand() {
+        let mut rand = StdForkSafeRand::with_seed(1337);
+        let mut reference = StdRand::with_seed(1337);
+        assert_eq!(rand.next(), reference.next());
+
+        #[cfg(unix)]
+        {
+            // Pretend that we were forked
+            super::on_fork_child();
+            assert_ne!(rand.next(), reference.next());
+        }
+    }
+}
@@ -13,6 +13,11 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 #[cfg(feature = "alloc")]
 pub mod loaded_dice;
 
+#[cfg(feature = "std")]
+pub mod fork_safe;
+#[cfg(feature = "std")]
+pub use fork_safe::{ForkSafeRand, StdForkSafeRand};
+
 #[cfg(all(not(feature = "std"), target_has_atomic = "ptr"))]
 static SEED_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
@@ -28,6 +33,29 @@ pub fn random_seed() -> u64 {
     4
 }
 
+/// Return a seed from the entropy source of the OS, `/dev/urandom` on `unix`.
+///
+/// Unlike [`random_seed`], this returns different seeds in a parent and its forked children.
+/// Falls back to [`random_seed`], mixed with the process id, if the entropy source can not be read.
+#[cfg(feature = "std")]
+#[must_use]
+pub fn os_random_seed() -> u64 {
+    #[cfg(unix)]
+    {
+        use std::{fs::File, io::Read};
+
+        let mut bytes = [0_u8; 8];
+        if File::open("/dev/urandom")
+            .and_then(|mut urandom| urandom.read_exact(&mut bytes))
+            .is_ok()
+        {
+            return u64::from_ne_bytes(bytes);
+        }
+    }
+    let mut seed = random_seed() ^ u64::from(std::process::id());
+    splitmix64(&mut seed)
+}
+
 #[cfg(all(not(feature = "std"), target_has_atomic = "ptr"))]
 fn random_seed_deterministic() -> u64 {
     let mut seed = SEED_COUNTER.fetch_add(1, Ordering::Relaxed) as u64;
@@ -53,6 +81,29 @@ fn splitmix64(x: &mut u64) -> u64 {
     z ^ (z >> 31)
 }
 
+/// Derive the seed of a client from the master seed of a campaign, the id of its node, and the id of the client.
+///
+/// The seeds of different clients are uncorrelated, also for clients with the same id on different nodes,
+/// so a whole campaign can be reproduced from its master seed. See [`node_id`] for a node-unique id.
+#[must_use]
+pub fn derive_seed(master_seed: u64, node_id: u64, client_id: u64) -> u64 {
+    let mut x = master_seed;
+    let mut x = splitmix64(&mut x) ^ node_id;
+    let mut x = splitmix64(&mut x) ^ client_id;
+    splitmix64(&mut x)
+}
+
+/// An id for this fuzzing node, for [`derive_seed`]: a hash of the hostname and the port of the broker,
+/// so several nodes on one host get different ids, too.
+#[cfg(feature = "std")]
+#[must_use]
+pub fn node_id(broker_port: u16) -> u64 {
+    let hostname = hostname::get().unwrap_or_default();
+    let mut bytes = hostname.to_string_lossy().into_owned().into_bytes();
+    bytes.extend_from_slice(&broker_port.to_le_bytes());
+    crate::hash_std(&bytes)
+}
+
 /// The standard [`Rand`] implementation for `LibAFL`.
 ///
 /// It is usually the right choice, with very good speed and a reasonable randomness.
@@ -553,7 +604,7 @@ mod tests {
     use crate::{
         nonzero,
         rands::{
-            Rand, RomuDuoJrRand, RomuTrioRand, Sfc64Rand, StdRand, XorShift64Rand,
+            derive_seed, Rand, RomuDuoJrRand, RomuTrioRand, Sfc64Rand, StdRand, XorShift64Rand,
             Xoshiro256PlusPlusRand,
         },
     };
@@ -577,6 +628,25 @@ mod tests {
         test_single_rand(&mut Sfc64Rand::with_seed(0));
     }
 
+    #[test]
+    fn test_derive_seed() {
+        assert_eq!(derive_seed(1337, 0, 0), derive_seed(1337, 0, 0));
+        assert_ne!(derive_seed(1337, 0, 0), derive_seed(1337, 0, 1));
+        assert_ne!(derive_seed(1337, 0, 0), derive_seed(1338, 0, 0));
+        // The same client on another node
+        assert_ne!(derive_seed(1337, 0, 0), derive_seed(1337, 1, 0));
+        assert_ne!(derive_seed(1337, 1, 0), derive_seed(1337, 0, 1));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_node_id() {
+        use crate::rands::node_id;
+
+        assert_eq!(node_id(1337), node_id(1337));
+        assert_ne!(node_id(1337), node_id(1338));
+    }
+
     #[test]
     fn test_romutrio_golden() {
         // https://github.com/ziglang/zig/blob/130fb5cb0fb9039e79450c9db58d6590c5bee3b3/lib/std/Random/RomuTrio.zig#L75-L95
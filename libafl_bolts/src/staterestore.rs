@@ -1,11 +1,16 @@
 //! Stores and restores state when a client needs to relaunch.
 //! Uses a [`ShMem`] up to a threshold, then write to disk.
-use alloc::string::{String, ToString};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::{
     hash::{BuildHasher, Hasher},
     marker::PhantomData,
-    mem::size_of,
+    mem::{align_of, size_of},
     ptr, slice,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
 };
 use std::{
     env::temp_dir,
@@ -16,9 +21,11 @@ use std::{
 };
 
 use ahash::RandomState;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
+    codec::{Codec, PostcardCodec},
+    current_milliseconds,
     shmem::{ShMem, ShMemProvider},
     AsSlice, Error,
 };
@@ -26,11 +33,45 @@ use crate::{
 /// If the saved page content equals exactly this buf, the restarted child wants to exit cleanly.
 const EXITING_MAGIC: &[u8; 16] = b"LIBAFL_EXIT_NOW\0";
 
+/// Once a delta grows larger than the base snapshot divided by this, [`StateRestorer::save_delta`] takes a new base.
+const DELTA_REBASE_DIVISOR: usize = 2;
+
+/// A type that can be serialized as the changes to an earlier snapshot of itself.
+///
+/// Used by [`StateRestorer::save_delta`], so that restarts only have to write what changed since the last
+/// full snapshot, instead of the complete state including e.g. all corpus inputs.
+pub trait DeltaSerialize {
+    /// Marks the current contents as the base snapshot, which later deltas are relative to.
+    ///
+    /// Called right before the base snapshot is serialized.
+    fn mark_snapshot(&mut self);
+
+    /// Serialize the changes since the base snapshot.
+    ///
+    /// The delta has to contain all changes since [`Self::mark_snapshot`],
+    /// even if an earlier delta has already been applied to this value.
+    fn serialize_delta(&self) -> Result<Vec<u8>, Error>;
+
+    /// Apply a delta from [`Self::serialize_delta`] to the deserialized base snapshot
+    fn apply_delta(&mut self, delta: &[u8]) -> Result<(), Error>;
+}
+
+/// What [`StateRestorer::save_delta`] writes: the tmpfile containing the base snapshot and its size,
+/// the delta to apply to it (if a state was saved), and the serialized data saved alongside the state.
+#[derive(Debug, Serialize, Deserialize)]
+struct DeltaContent {
+    base: Option<(String, usize)>,
+    delta: Option<Vec<u8>>,
+    extra: Vec<u8>,
+}
+
 /// The struct stored on the shared map, containing either the data, or the filename to read contents from.
 #[repr(C)]
 struct StateShMemContent {
     is_disk: bool,
     buf_len: usize,
+    /// The milliseconds since the `UNIX_EPOCH` of the last [`StateRestorer::heartbeat`], `0` if there was none
+    heartbeat: AtomicU64,
     buf: [u8; 0],
 }
 
@@ -64,33 +105,28 @@ impl StateShMemContent {
 /// If the state gets larger than the preallocated [`ShMem`] shared map,
 /// it will instead write to disk, and store the file name into the map.
 /// Writing to [`StateRestorer`] multiple times is not allowed.
+///
+/// The state is serialized with the [`Codec`] `C`, [`PostcardCodec`] by default, see [`StateRestorer::with_codec`].
 #[derive(Debug, Clone)]
-pub struct StateRestorer<SP>
+pub struct StateRestorer<SP, C = PostcardCodec>
 where
     SP: ShMemProvider,
 {
     shmem: SP::ShMem,
-    phantom: PhantomData<*const SP>,
+    /// The tmpfile of the base snapshot for [`Self::save_delta`], and its size
+    delta_base: Option<(String, usize)>,
+    phantom: PhantomData<(*const SP, C)>,
 }
 
 impl<SP> StateRestorer<SP>
 where
     SP: ShMemProvider,
 {
-    /// Get the map size backing this [`StateRestorer`].
-    pub fn mapsize(&self) -> usize {
-        self.shmem.len()
-    }
-
-    /// Writes this [`StateRestorer`] to env variable, to be restored later
-    pub fn write_to_env(&self, env_name: &str) -> Result<(), Error> {
-        self.shmem.write_to_env(env_name)
-    }
-
     /// Create a [`StateRestorer`] from `env` variable name
     pub fn from_env(shmem_provider: &mut SP, env_name: &str) -> Result<Self, Error> {
         Ok(Self {
             shmem: shmem_provider.existing_from_env(env_name)?,
+            delta_base: None,
             phantom: PhantomData,
         })
     }
@@ -99,12 +135,43 @@ where
     pub fn new(shmem: SP::ShMem) -> Self {
         let mut ret = Self {
             shmem,
+            delta_base: None,
             phantom: PhantomData,
         };
         ret.reset();
+        ret.clear_heartbeat();
         ret
     }
 
+    /// Serialize the state with another [`Codec`]. The restarted child has to use the same codec.
+    #[must_use]
+    pub fn with_codec<C>(self) -> StateRestorer<SP, C>
+    where
+        C: Codec,
+    {
+        StateRestorer {
+            shmem: self.shmem,
+            delta_base: self.delta_base,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<SP, C> StateRestorer<SP, C>
+where
+    SP: ShMemProvider,
+    C: Codec,
+{
+    /// Get the map size backing this [`StateRestorer`].
+    pub fn mapsize(&self) -> usize {
+        self.shmem.len()
+    }
+
+    /// Writes this [`StateRestorer`] to env variable, to be restored later
+    pub fn write_to_env(&self, env_name: &str) -> Result<(), Error> {
+        self.shmem.write_to_env(env_name)
+    }
+
     /// Saves a state to the connected [`ShMem`], or a tmpfile, if its serialized size get too large.
     pub fn save<S>(&mut self, state: &S) -> Result<(), Error>
     where
@@ -116,7 +183,7 @@ where
             ));
         }
 
-        let serialized = postcard::to_allocvec(state)?;
+        let serialized = C::encode(state)?;
 
         if size_of::<StateShMemContent>() + serialized.len() > self.shmem.len() {
             // generate a filename
@@ -172,6 +239,97 @@ where
         Ok(())
     }
 
+    /// Saves a state as a delta to a base snapshot kept in a tmpfile, together with the (small) `extra` data.
+    ///
+    /// The first call, and every call where the delta grew too large, writes a new base snapshot.
+    /// All others only store the [`DeltaSerialize::serialize_delta`] of the state.
+    /// Without a state, only `extra` is stored, and the base snapshot is kept for the next call.
+    /// Restore it with [`Self::restore_delta`].
+    pub fn save_delta<S, T>(&mut self, state: Option<&mut S>, extra: &T) -> Result<(), Error>
+    where
+        S: Serialize + DeltaSerialize,
+        T: Serialize + ?Sized,
+    {
+        let delta = match state {
+            Some(state) => Some(self.delta_with_base(state)?),
+            None => None,
+        };
+        let extra = C::encode(extra)?;
+        self.save(&DeltaContent {
+            base: self.delta_base.clone(),
+            delta,
+            extra,
+        })
+    }
+
+    /// Serializes the delta of `state`, and writes a new base snapshot first if there is none or the delta is too large
+    fn delta_with_base<S>(&mut self, state: &mut S) -> Result<Vec<u8>, Error>
+    where
+        S: Serialize + DeltaSerialize,
+    {
+        let delta = state.serialize_delta()?;
+        if self
+            .delta_base
+            .as_ref()
+            .is_some_and(|(_, base_len)| delta.len() <= base_len / DELTA_REBASE_DIVISOR)
+        {
+            return Ok(delta);
+        }
+
+        state.mark_snapshot();
+        let serialized = C::encode(state)?;
+
+        let mut hasher = RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+        hasher.write(&serialized[serialized.len().saturating_sub(4096)..]);
+        let base_file = format!("{:016x}.libafl_base", hasher.finish());
+        File::create(temp_dir().join(&base_file))?.write_all(&serialized)?;
+
+        if let Some((old_file, _)) = &self.delta_base {
+            if *old_file != base_file {
+                drop(fs::remove_file(temp_dir().join(old_file)));
+            }
+        }
+        self.delta_base = Some((base_file, serialized.len()));
+        state.serialize_delta()
+    }
+
+    /// Restores the state (if one was saved) and the extra data saved with [`Self::save_delta`],
+    /// if any are available.
+    ///
+    /// Following calls to [`Self::save_delta`] keep using the same base snapshot.
+    pub fn restore_delta<S, T>(&mut self) -> Result<Option<(Option<S>, T)>, Error>
+    where
+        S: DeserializeOwned + DeltaSerialize,
+        T: DeserializeOwned,
+    {
+        let Some(content) = self.restore::<DeltaContent>()? else {
+            return Ok(None);
+        };
+        let state = match (&content.delta, &content.base) {
+            (Some(delta), Some((base_file, _))) => {
+                let mut base = vec![];
+                File::open(temp_dir().join(base_file))?.read_to_end(&mut base)?;
+                if base.is_empty() {
+                    return Err(Error::illegal_state(format!(
+                        "Could not restore base snapshot from file {base_file}"
+                    )));
+                }
+                let mut state: S = C::decode(&base)?;
+                state.apply_delta(delta)?;
+                Some(state)
+            }
+            (Some(_), None) => {
+                return Err(Error::illegal_state(
+                    "Found a delta snapshot without a base snapshot",
+                ))
+            }
+            (None, _) => None,
+        };
+        let extra = C::decode(&content.extra)?;
+        self.delta_base = content.base;
+        Ok(Some((state, extra)))
+    }
+
     /// Reset this [`StateRestorer`] to an empty state.
     pub fn reset(&mut self) {
         let mapsize = self.mapsize();
@@ -184,10 +342,35 @@ where
         content_mut.buf_len = 0;
     }
 
+    /// When called from a child, tells the restarter/parent process that it is still making progress.
+    pub fn heartbeat(&self) {
+        self.content()
+            .heartbeat
+            .store(current_milliseconds(), Ordering::Relaxed);
+    }
+
+    /// The time since the `UNIX_EPOCH` of the last [`Self::heartbeat`], if the child sent one since the last
+    /// [`Self::clear_heartbeat`]
+    #[must_use]
+    pub fn last_heartbeat(&self) -> Option<Duration> {
+        match self.content().heartbeat.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Some(Duration::from_millis(millis)),
+        }
+    }
+
+    /// Forgets the last [`Self::heartbeat`], e.g. before the restarter spawns the next child
+    pub fn clear_heartbeat(&self) {
+        self.content().heartbeat.store(0, Ordering::Relaxed);
+    }
+
     /// When called from a child, informs the restarter/parent process
     /// that it should no longer respawn the child.
     pub fn send_exiting(&mut self) {
         self.reset();
+        if let Some((base_file, _)) = self.delta_base.take() {
+            drop(fs::remove_file(temp_dir().join(base_file)));
+        }
 
         let len = EXITING_MAGIC.len();
 
@@ -216,7 +399,7 @@ where
     fn content_mut(&mut self) -> &mut StateShMemContent {
         let ptr = self.shmem.as_slice().as_ptr();
         debug_assert_eq!(
-            ptr.align_offset(size_of::<StateShMemContent>()),
+            ptr.align_offset(align_of::<StateShMemContent>()),
             0,
             "Beginning of the page is not aligned at {ptr:?}!"
         );
@@ -278,7 +461,7 @@ where
             }
             state = &file_content;
         }
-        let deserialized = postcard::from_bytes(state)?;
+        let deserialized = C::decode(state)?;
         Ok(Some(deserialized))
     }
 }
@@ -349,5 +532,99 @@ mod tests {
         state_restorer.reset();
         assert!(!state_restorer.has_content());
         assert!(!tmpfile.exists());
+
+        // The heartbeat survives resets
+        assert!(state_restorer.last_heartbeat().is_none());
+        state_restorer.heartbeat();
+        state_restorer.reset();
+        assert!(state_restorer.last_heartbeat().is_some());
+        state_restorer.clear_heartbeat();
+        assert!(state_restorer.last_heartbeat().is_none());
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(not(target_os = "haiku"))]
+    fn test_state_restore_delta() {
+        use alloc::{string::String, vec::Vec};
+
+        use serde::{Deserialize, Serialize};
+
+        use crate::{
+            shmem::{ShMemProvider, StdShMemProvider},
+            staterestore::{DeltaSerialize, StateRestorer},
+            Error,
+        };
+
+        #[derive(Serialize, Deserialize)]
+        struct AppendOnly {
+            entries: Vec<u64>,
+            base_len: usize,
+        }
+
+        impl DeltaSerialize for AppendOnly {
+            fn mark_snapshot(&mut self) {
+                self.base_len = self.entries.len();
+            }
+
+            fn serialize_delta(&self) -> Result<Vec<u8>, Error> {
+                Ok(postcard::to_allocvec(&self.entries[self.base_len..])?)
+            }
+
+            fn apply_delta(&mut self, delta: &[u8]) -> Result<(), Error> {
+                let added: Vec<u64> = postcard::from_bytes(delta)?;
+                self.entries.truncate(self.base_len);
+                self.entries.extend(added);
+                Ok(())
+            }
+        }
+
+        let mut shmem_provider = StdShMemProvider::new().unwrap();
+        let shmem = shmem_provider.new_shmem(1024).unwrap();
+        let mut state_restorer = StateRestorer::<StdShMemProvider>::new(shmem);
+
+        let mut state = AppendOnly {
+            entries: (0..100).collect(),
+            base_len: 0,
+        };
+        state_restorer
+            .save_delta(Some(&mut state), "first")
+            .unwrap();
+        assert_eq!(state.base_len, 100);
+
+        state.entries.push(100);
+        state_restorer.reset();
+        state_restorer
+            .save_delta(Some(&mut state), "second")
+            .unwrap();
+        // Only the delta is stored, the base was not retaken
+        assert_eq!(state.base_len, 100);
+        assert!(!state_restorer.content().is_disk);
+
+        let (Some(restored), extra) = state_restorer
+            .restore_delta::<AppendOnly, String>()
+            .unwrap()
+            .unwrap()
+        else {
+            panic!("The state was not restored");
+        };
+        assert_eq!(restored.entries, (0..=100).collect::<Vec<_>>());
+        assert_eq!(extra, "second");
+
+        // Without a state, the base snapshot is kept for later deltas
+        state_restorer.reset();
+        state_restorer
+            .save_delta(None::<&mut AppendOnly>, "third")
+            .unwrap();
+        let (restored, extra) = state_restorer
+            .restore_delta::<AppendOnly, String>()
+            .unwrap()
+            .unwrap();
+        assert!(restored.is_none());
+        assert_eq!(extra, "third");
+        assert!(state_restorer.delta_base.is_some());
+
+        state_restorer.send_exiting();
     }
 }
@@ -0,0 +1,66 @@
+/*
+ * The C API of LibAFL, to embed LibAFL clients in existing C and C++ fuzzing drivers.
+ *
+ * Link against the `libafl_capi` static or dynamic library.
+ * Functions returning an `int` return `0` on success and `-1` on error;
+ * functions returning a pointer return `NULL` on error.
+ * `libafl_last_error` returns the message of the last error on the calling thread.
+ */
+
+#ifndef LIBAFL_H
+#define LIBAFL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The exit kinds a harness returns */
+#define LIBAFL_EXIT_OK 0
+#define LIBAFL_EXIT_CRASH 1
+#define LIBAFL_EXIT_OOM 2
+#define LIBAFL_EXIT_TIMEOUT 3
+
+/* A fuzzer, opaque to the host */
+typedef struct LibaflFuzzer libafl_fuzzer_t;
+
+/* Runs the input of `len` bytes at `data`, and returns one of the `LIBAFL_EXIT_*` constants.
+ * Crashes and timeouts have to be detected by the host, e.g. by running the target in a forked child. */
+typedef int (*libafl_harness_fn)(const uint8_t *data, size_t len, void *user_data);
+
+/* Create a new fuzzer, observing the coverage map of `map_size` bytes at `map`, which has to stay valid
+ * until the fuzzer is freed. Crashes and timeouts are stored in `crashes_dir`.
+ * If `broker_port` is not 0, the fuzzer attaches to the LLMP broker on this port, sharing its corpus
+ * with the other clients; otherwise, it runs on its own. */
+libafl_fuzzer_t *libafl_fuzzer_new(uint8_t *map, size_t map_size, const char *crashes_dir,
+                                   uint16_t broker_port, uint64_t seed);
+
+/* Add the seed of `len` bytes at `data` to the corpus. At least one seed is needed before fuzzing. */
+int libafl_add_seed(libafl_fuzzer_t *fuzzer, const uint8_t *data, size_t len);
+
+/* Fuzz one entry of the corpus, calling `harness` with `user_data` for each mutated input.
+ * Returns 1 if the fuzzer was asked to stop, e.g. by the broker. */
+int libafl_fuzz_one(libafl_fuzzer_t *fuzzer, libafl_harness_fn harness, void *user_data);
+
+/* The number of executions so far */
+uint64_t libafl_fuzzer_executions(const libafl_fuzzer_t *fuzzer);
+
+/* The number of entries in the corpus */
+size_t libafl_fuzzer_corpus_count(const libafl_fuzzer_t *fuzzer);
+
+/* The number of crashes and timeouts found so far */
+size_t libafl_fuzzer_solutions_count(const libafl_fuzzer_t *fuzzer);
+
+/* Free the fuzzer. Passing NULL does nothing. */
+void libafl_fuzzer_free(libafl_fuzzer_t *fuzzer);
+
+/* The message of the last error on this thread, valid until the next failing call on this thread */
+const char *libafl_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* LIBAFL_H */
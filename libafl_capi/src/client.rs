@@ -0,0 +1,167 @@
+use core::{ffi::c_void, time::Duration};
+use std::path::PathBuf;
+
+use libafl::{
+    corpus::{Corpus, InMemoryCorpus, OnDiskCorpus, Testcase},
+    events::ProgressReporter,
+    feedback_or_fast,
+    feedbacks::{CrashFeedback, FastOrFeedback, MaxMapFeedback, TimeoutFeedback},
+    fuzzer::{Evaluator, Fuzzer, HasScheduler, StdFuzzer},
+    inputs::BytesInput,
+    mutators::{havoc_mutations, HavocMutationsType, StdScheduledMutator},
+    observers::{HitcountsMapObserver, StdMapObserver},
+    schedulers::{QueueScheduler, Scheduler},
+    stages::StdMutationalStage,
+    state::{HasCorpus, HasExecutions, HasSolutions, StdState, UsesState},
+    Error,
+};
+use libafl_bolts::{
+    rands::StdRand,
+    tuples::{tuple_list, tuple_list_type},
+};
+
+use crate::executor::{CallbackExecutor, HarnessFn};
+
+/// How often clients report their progress to the monitor
+const STATS_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The state of the clients
+pub type CapiState =
+    StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, OnDiskCorpus<BytesInput>>;
+/// The observer of the coverage map of the host
+pub type CapiMapObserver = HitcountsMapObserver<StdMapObserver<'static, u8, false>>;
+/// The observers of the clients
+pub type CapiObservers = tuple_list_type!(CapiMapObserver);
+/// The executor of the clients
+pub type CapiExecutor = CallbackExecutor<CapiObservers, CapiState>;
+/// The fuzzer of the clients
+pub type CapiFuzzer = StdFuzzer<
+    QueueScheduler,
+    MaxMapFeedback<CapiMapObserver, CapiMapObserver>,
+    FastOrFeedback<CrashFeedback, TimeoutFeedback>,
+    CapiState,
+>;
+/// The stages of the clients, mutating with the havoc mutations
+pub type CapiStages<EM> = tuple_list_type!(StdMutationalStage<
+    CapiExecutor,
+    EM,
+    BytesInput,
+    StdScheduledMutator<HavocMutationsType>,
+    CapiFuzzer,
+>);
+
+/// A fuzzer client, independent of its event manager
+pub trait FuzzOne {
+    /// Add a seed to the corpus, without running it
+    fn add_seed(&mut self, seed: Vec<u8>) -> Result<(), Error>;
+
+    /// Fuzz one corpus entry, calling `harness` for each input
+    fn fuzz_one(&mut self, harness: HarnessFn, user_data: *mut c_void) -> Result<(), Error>;
+
+    /// The number of executions so far
+    fn executions(&self) -> u64;
+
+    /// The number of entries in the corpus
+    fn corpus_count(&self) -> usize;
+
+    /// The number of solutions found so far
+    fn solutions_count(&self) -> usize;
+}
+
+/// A fuzzer client with the standard components, reporting to the event manager `EM`
+#[derive(Debug)]
+pub struct Client<EM> {
+    fuzzer: CapiFuzzer,
+    executor: CapiExecutor,
+    stages: CapiStages<EM>,
+    state: CapiState,
+    mgr: EM,
+}
+
+impl<EM> Client<EM>
+where
+    EM: UsesState<State = CapiState>,
+    CapiFuzzer: Evaluator<CapiExecutor, EM>,
+{
+    /// Create a new [`Client`], observing the coverage map of `map_size` entries at `map`,
+    /// and storing solutions in `crashes_dir`.
+    ///
+    /// # Safety
+    /// The map must be valid for reads and writes of `map_size` bytes, and outlive the [`Client`].
+    pub unsafe fn new(
+        map: *mut u8,
+        map_size: usize,
+        crashes_dir: PathBuf,
+        seed: u64,
+        mgr: EM,
+    ) -> Result<Self, Error> {
+        let observer =
+            HitcountsMapObserver::new(StdMapObserver::from_mut_ptr("edges", map, map_size));
+
+        let mut feedback = MaxMapFeedback::new(&observer);
+        let mut objective = feedback_or_fast!(CrashFeedback::new(), TimeoutFeedback::new());
+
+        let state = StdState::new(
+            StdRand::with_seed(seed),
+            InMemoryCorpus::new(),
+            OnDiskCorpus::new(crashes_dir)?,
+            &mut feedback,
+            &mut objective,
+        )?;
+
+        Ok(Self {
+            fuzzer: StdFuzzer::new(QueueScheduler::new(), feedback, objective),
+            executor: CallbackExecutor::new(tuple_list!(observer)),
+            stages: tuple_list!(StdMutationalStage::new(StdScheduledMutator::new(
+                havoc_mutations()
+            ))),
+            state,
+            mgr,
+        })
+    }
+}
+
+impl<EM> FuzzOne for Client<EM>
+where
+    EM: ProgressReporter<State = CapiState>,
+    CapiFuzzer: Fuzzer<CapiExecutor, EM, CapiStages<EM>>,
+{
+    fn add_seed(&mut self, seed: Vec<u8>) -> Result<(), Error> {
+        let id = self
+            .state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(seed)))?;
+        <QueueScheduler as Scheduler<BytesInput, _>>::on_add(
+            self.fuzzer.scheduler_mut(),
+            &mut self.state,
+            id,
+        )
+    }
+
+    fn fuzz_one(&mut self, harness: HarnessFn, user_data: *mut c_void) -> Result<(), Error> {
+        if self.state.corpus().count() == 0 {
+            return Err(Error::empty("Add at least one seed before fuzzing"));
+        }
+        self.executor.set_harness(harness, user_data);
+        self.fuzzer.fuzz_one(
+            &mut self.stages,
+            &mut self.executor,
+            &mut self.state,
+            &mut self.mgr,
+        )?;
+        self.mgr
+            .maybe_report_progress(&mut self.state, STATS_TIMEOUT)
+    }
+
+    fn executions(&self) -> u64 {
+        *self.state.executions()
+    }
+
+    fn corpus_count(&self) -> usize {
+        self.state.corpus().count()
+    }
+
+    fn solutions_count(&self) -> usize {
+        self.state.solutions().count()
+    }
+}
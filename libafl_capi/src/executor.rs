@@ -0,0 +1,113 @@
+use core::{
+    ffi::{c_int, c_void},
+    marker::PhantomData,
+};
+
+use libafl::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::HasTargetBytes,
+    observers::ObserversTuple,
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+use libafl_bolts::{tuples::RefIndexable, AsSlice};
+
+/// The harness ran the input without issues
+pub const LIBAFL_EXIT_OK: c_int = 0;
+/// The harness crashed on the input
+pub const LIBAFL_EXIT_CRASH: c_int = 1;
+/// The harness ran out of memory on the input
+pub const LIBAFL_EXIT_OOM: c_int = 2;
+/// The harness timed out on the input
+pub const LIBAFL_EXIT_TIMEOUT: c_int = 3;
+
+/// The harness of the host: runs the input of `len` bytes at `data`, and returns one of the
+/// `LIBAFL_EXIT_*` constants. `user_data` is passed through from `libafl_fuzz_one`.
+pub type HarnessFn =
+    unsafe extern "C" fn(data: *const u8, len: usize, user_data: *mut c_void) -> c_int;
+
+/// An executor calling the harness of the host for each input.
+///
+/// The harness runs in the process of the host, which has to detect crashes and timeouts itself,
+/// e.g. by running the target in a forked child, and report them in the return value.
+#[derive(Debug)]
+pub struct CallbackExecutor<OT, S> {
+    harness: Option<(HarnessFn, *mut c_void)>,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S> CallbackExecutor<OT, S> {
+    /// Create a new [`CallbackExecutor`], without a harness yet
+    #[must_use]
+    pub fn new(observers: OT) -> Self {
+        Self {
+            harness: None,
+            observers,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Set the harness called for the next inputs
+    pub fn set_harness(&mut self, harness: HarnessFn, user_data: *mut c_void) {
+        self.harness = Some((harness, user_data));
+    }
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for CallbackExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        let Some((harness, user_data)) = self.harness else {
+            return Err(Error::illegal_state(
+                "No harness set for the CallbackExecutor",
+            ));
+        };
+        *state.executions_mut() += 1;
+
+        let target_bytes = input.target_bytes();
+        let bytes = target_bytes.as_slice();
+        match unsafe { harness(bytes.as_ptr(), bytes.len(), user_data) } {
+            LIBAFL_EXIT_OK => Ok(ExitKind::Ok),
+            LIBAFL_EXIT_CRASH => Ok(ExitKind::Crash),
+            LIBAFL_EXIT_OOM => Ok(ExitKind::Oom),
+            LIBAFL_EXIT_TIMEOUT => Ok(ExitKind::Timeout),
+            ret => Err(Error::illegal_argument(format!(
+                "The harness returned the unknown exit kind {ret}"
+            ))),
+        }
+    }
+}
+
+impl<OT, S> UsesState for CallbackExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<OT, S> HasObservers for CallbackExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
@@ -0,0 +1,309 @@
+//! The `extern "C"` functions of the API, as declared in `include/libafl.h`.
+//!
+//! Functions returning an `int` return `0` on success and `-1` on error; functions returning a pointer
+//! return `NULL` on error. The message of the last error on this thread is returned by [`libafl_last_error`].
+
+use core::{
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CStr},
+    fmt::{self, Debug, Formatter},
+    ptr,
+};
+use std::{ffi::CString, path::PathBuf};
+
+use libafl::{
+    events::{EventConfig, LlmpEventManager, SimpleEventManager},
+    monitors::SimpleMonitor,
+    Error,
+};
+use libafl_bolts::shmem::{ShMemProvider, StdShMemProvider};
+
+use crate::{
+    client::{Client, FuzzOne},
+    executor::HarnessFn,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Remember `err` as last error, to be returned by [`libafl_last_error`]
+fn set_last_error(err: &Error) {
+    log::error!("{err}");
+    let msg = CString::new(err.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = msg);
+}
+
+/// Log the status of the [`SimpleMonitor`]
+fn log_status(status: &str) {
+    log::info!("{status}");
+}
+
+/// A fuzzer, opaque to the host
+pub struct LibaflFuzzer {
+    client: Box<dyn FuzzOne>,
+}
+
+impl Debug for LibaflFuzzer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LibaflFuzzer")
+            .field("executions", &self.client.executions())
+            .field("corpus_count", &self.client.corpus_count())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Create the client, attaching to the broker on `broker_port` if it is not `0`
+unsafe fn new_client(
+    map: *mut u8,
+    map_size: usize,
+    crashes_dir: PathBuf,
+    broker_port: u16,
+    seed: u64,
+) -> Result<Box<dyn FuzzOne>, Error> {
+    if map.is_null() || map_size == 0 {
+        return Err(Error::illegal_argument("The coverage map may not be empty"));
+    }
+    if broker_port == 0 {
+        let mgr = SimpleEventManager::new(SimpleMonitor::new(log_status as fn(&str)));
+        Ok(Box::new(Client::new(
+            map,
+            map_size,
+            crashes_dir,
+            seed,
+            mgr,
+        )?))
+    } else {
+        let mgr = LlmpEventManager::builder().build_on_port(
+            StdShMemProvider::new()?,
+            broker_port,
+            EventConfig::AlwaysUnique,
+            None,
+        )?;
+        Ok(Box::new(Client::new(
+            map,
+            map_size,
+            crashes_dir,
+            seed,
+            mgr,
+        )?))
+    }
+}
+
+/// Create a new fuzzer, observing the coverage map of `map_size` bytes at `map`, and storing crashes and
+/// timeouts in `crashes_dir`.
+///
+/// If `broker_port` is not `0`, the fuzzer attaches as a client to the LLMP broker on this port, sharing
+/// its corpus with all other clients; otherwise, it runs on its own.
+///
+/// # Safety
+/// `map` must be valid for reads and writes of `map_size` bytes until the fuzzer is freed,
+/// and `crashes_dir` must be a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn libafl_fuzzer_new(
+    map: *mut u8,
+    map_size: usize,
+    crashes_dir: *const c_char,
+    broker_port: u16,
+    seed: u64,
+) -> *mut LibaflFuzzer {
+    if crashes_dir.is_null() {
+        set_last_error(&Error::illegal_argument("No crashes dir given"));
+        return ptr::null_mut();
+    }
+    let crashes_dir = PathBuf::from(CStr::from_ptr(crashes_dir).to_string_lossy().into_owned());
+    match new_client(map, map_size, crashes_dir, broker_port, seed) {
+        Ok(client) => Box::into_raw(Box::new(LibaflFuzzer { client })),
+        Err(err) => {
+            set_last_error(&err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Add the seed of `len` bytes at `data` to the corpus of `fuzzer`.
+///
+/// # Safety
+/// `fuzzer` must come from [`libafl_fuzzer_new`], and `data` must be valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn libafl_add_seed(
+    fuzzer: *mut LibaflFuzzer,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    let Some(fuzzer) = fuzzer.as_mut() else {
+        set_last_error(&Error::illegal_argument("No fuzzer given"));
+        return -1;
+    };
+    let seed = if len == 0 {
+        vec![]
+    } else {
+        core::slice::from_raw_parts(data, len).to_vec()
+    };
+    match fuzzer.client.add_seed(seed) {
+        Ok(()) => 0,
+        Err(err) => {
+            set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Fuzz one entry of the corpus, calling `harness` with `user_data` for each mutated input.
+///
+/// Returns `1` if the fuzzer was asked to stop, e.g. by the broker.
+///
+/// # Safety
+/// `fuzzer` must come from [`libafl_fuzzer_new`], and `harness` must be safe to call with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn libafl_fuzz_one(
+    fuzzer: *mut LibaflFuzzer,
+    harness: Option<HarnessFn>,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(fuzzer) = fuzzer.as_mut() else {
+        set_last_error(&Error::illegal_argument("No fuzzer given"));
+        return -1;
+    };
+    let Some(harness) = harness else {
+        set_last_error(&Error::illegal_argument("No harness given"));
+        return -1;
+    };
+    match fuzzer.client.fuzz_one(harness, user_data) {
+        Ok(()) => 0,
+        Err(Error::ShuttingDown) => 1,
+        Err(err) => {
+            set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// The number of executions of `fuzzer` so far
+///
+/// # Safety
+/// `fuzzer` must come from [`libafl_fuzzer_new`].
+#[no_mangle]
+pub unsafe extern "C" fn libafl_fuzzer_executions(fuzzer: *const LibaflFuzzer) -> u64 {
+    fuzzer
+        .as_ref()
+        .map_or(0, |fuzzer| fuzzer.client.executions())
+}
+
+/// The number of entries in the corpus of `fuzzer`
+///
+/// # Safety
+/// `fuzzer` must come from [`libafl_fuzzer_new`].
+#[no_mangle]
+pub unsafe extern "C" fn libafl_fuzzer_corpus_count(fuzzer: *const LibaflFuzzer) -> usize {
+    fuzzer
+        .as_ref()
+        .map_or(0, |fuzzer| fuzzer.client.corpus_count())
+}
+
+/// The number of crashes and timeouts found by `fuzzer` so far
+///
+/// # Safety
+/// `fuzzer` must come from [`libafl_fuzzer_new`].
+#[no_mangle]
+pub unsafe extern "C" fn libafl_fuzzer_solutions_count(fuzzer: *const LibaflFuzzer) -> usize {
+    fuzzer
+        .as_ref()
+        .map_or(0, |fuzzer| fuzzer.client.solutions_count())
+}
+
+/// Free `fuzzer`. Passing `NULL` does nothing.
+///
+/// # Safety
+/// `fuzzer` must come from [`libafl_fuzzer_new`], and may not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn libafl_fuzzer_free(fuzzer: *mut LibaflFuzzer) {
+    if !fuzzer.is_null() {
+        drop(Box::from_raw(fuzzer));
+    }
+}
+
+/// The message of the last error on this thread, valid until the next call failing on this thread
+#[no_mangle]
+pub extern "C" fn libafl_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        ffi::{c_int, c_void},
+        ptr,
+    };
+    use std::{
+        ffi::{CStr, CString},
+        fs, process,
+    };
+
+    use super::{
+        libafl_add_seed, libafl_fuzz_one, libafl_fuzzer_executions, libafl_fuzzer_free,
+        libafl_fuzzer_new, libafl_fuzzer_solutions_count, libafl_last_error,
+    };
+    use crate::executor::{LIBAFL_EXIT_CRASH, LIBAFL_EXIT_OK};
+
+    unsafe extern "C" fn harness(data: *const u8, len: usize, user_data: *mut c_void) -> c_int {
+        let map = user_data.cast::<u8>();
+        let data = core::slice::from_raw_parts(data, len);
+        *map = 1;
+        if data.first() == Some(&b'a') {
+            *map.add(1) = 1;
+            return LIBAFL_EXIT_CRASH;
+        }
+        LIBAFL_EXIT_OK
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_fuzz_one() {
+        let mut map = vec![0_u8; 16];
+        let dir = std::env::temp_dir().join(format!("libafl_capi_crashes_{}", process::id()));
+        let crashes_dir = CString::new(dir.to_str().unwrap()).unwrap();
+        unsafe {
+            let fuzzer =
+                libafl_fuzzer_new(map.as_mut_ptr(), map.len(), crashes_dir.as_ptr(), 0, 1337);
+            assert!(!fuzzer.is_null());
+            assert_eq!(
+                libafl_fuzz_one(fuzzer, Some(harness), map.as_mut_ptr().cast()),
+                -1,
+                "fuzzing without seeds"
+            );
+            assert_eq!(libafl_add_seed(fuzzer, b"b".as_ptr(), 1), 0);
+            assert_eq!(libafl_add_seed(fuzzer, b"a".as_ptr(), 1), 0);
+            for _ in 0..100 {
+                assert_eq!(
+                    libafl_fuzz_one(fuzzer, Some(harness), map.as_mut_ptr().cast()),
+                    0
+                );
+                if libafl_fuzzer_solutions_count(fuzzer) > 0 {
+                    break;
+                }
+            }
+            assert!(libafl_fuzzer_executions(fuzzer) > 0);
+            assert!(libafl_fuzzer_solutions_count(fuzzer) > 0);
+            libafl_fuzzer_free(fuzzer);
+        }
+        drop(map);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_null_fuzzer() {
+        unsafe {
+            assert_eq!(libafl_add_seed(ptr::null_mut(), b"a".as_ptr(), 1), -1);
+            assert!(CStr::from_ptr(libafl_last_error())
+                .to_str()
+                .unwrap()
+                .contains("No fuzzer given"));
+            assert_eq!(libafl_fuzz_one(ptr::null_mut(), None, ptr::null_mut()), -1);
+            assert!(CStr::from_ptr(libafl_last_error())
+                .to_str()
+                .unwrap()
+                .contains("No fuzzer given"));
+        }
+    }
+}
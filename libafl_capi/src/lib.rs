@@ -0,0 +1,60 @@
+/*!
+The C API of `LibAFL`, to embed `LibAFL` clients in existing C and C++ fuzzing drivers.
+
+The host creates a fuzzer over its own coverage map with `libafl_fuzzer_new`, adds seeds, and then pumps
+it with `libafl_fuzz_one`, passing the harness to call for each input. See `include/libafl.h` for the API.
+*/
+
+#![cfg_attr(not(test), warn(
+    missing_debug_implementations,
+    missing_docs,
+    //trivial_casts,
+    trivial_numeric_casts,
+    unused_extern_crates,
+    unused_import_braces,
+    unused_qualifications,
+    //unused_results
+))]
+#![cfg_attr(test, deny(
+    missing_debug_implementations,
+    //trivial_casts,
+    trivial_numeric_casts,
+    unused_extern_crates,
+    unused_import_braces,
+    unused_qualifications,
+    unused_must_use,
+    //unused_results
+))]
+#![cfg_attr(
+    test,
+    deny(
+        bad_style,
+        dead_code,
+        improper_ctypes,
+        non_shorthand_field_patterns,
+        no_mangle_generic_items,
+        overflowing_literals,
+        path_statements,
+        patterns_in_fns_without_body,
+        unconditional_recursion,
+        unused,
+        unused_allocation,
+        unused_comparisons,
+        unused_parens,
+        while_true
+    )
+)]
+
+/// The executor calling back into the host
+pub mod executor;
+pub use executor::{
+    CallbackExecutor, HarnessFn, LIBAFL_EXIT_CRASH, LIBAFL_EXIT_OK, LIBAFL_EXIT_OOM,
+    LIBAFL_EXIT_TIMEOUT,
+};
+
+/// The fuzzer clients behind the API
+pub mod client;
+
+/// The `extern "C"` functions
+pub mod ffi;
+pub use ffi::*;
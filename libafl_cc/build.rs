@@ -501,6 +501,18 @@ pub const LIBAFL_CC_LLVM_VERSION: Option<usize> = None;
         true,
     );
 
+    #[cfg(feature = "directed-distance")]
+    build_pass(
+        bindir_path,
+        out_dir,
+        &cxxflags,
+        &ldflags,
+        src_dir,
+        "directed-distance-pass.cc",
+        None,
+        true,
+    );
+
     #[cfg(feature = "dump-cfg")]
     build_pass(
         bindir_path,
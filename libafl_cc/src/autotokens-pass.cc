@@ -312,6 +312,7 @@ bool AutoTokensPass::runOnModule(Module &M) {
           bool   isStrncmp = true;
           bool   isStrcasecmp = true;
           bool   isStrncasecmp = true;
+          bool   isStrstr = true;
           bool   isIntMemcpy = true;
           bool   isStdString = true;
           bool   addedNull = false;
@@ -327,6 +328,8 @@ bool AutoTokensPass::runOnModule(Module &M) {
           isStrncmp &= !FuncName.compare("strncmp");
           isStrcasecmp &= !FuncName.compare("strcasecmp");
           isStrncasecmp &= !FuncName.compare("strncasecmp");
+          isStrstr &= (!FuncName.compare("strstr") ||
+                       !FuncName.compare("strcasestr"));
           isIntMemcpy &= !FuncName.compare("llvm.memcpy.p0i8.p0i8.i64");
           isStdString &= ((FuncName.find("basic_string") != std::string::npos &&
                            FuncName.find("compare") != std::string::npos) ||
@@ -334,7 +337,7 @@ bool AutoTokensPass::runOnModule(Module &M) {
                            FuncName.find("find") != std::string::npos));
 
           if (!isStrcmp && !isMemcmp && !isStrncmp && !isStrcasecmp &&
-              !isStrncasecmp && !isIntMemcpy && !isStdString)
+              !isStrncasecmp && !isStrstr && !isIntMemcpy && !isStdString)
             continue;
 
           /* Verify the strcmp/memcmp/strncmp/strcasecmp/strncasecmp function
@@ -368,18 +371,24 @@ bool AutoTokensPass::runOnModule(Module &M) {
               FT->getParamType(0) ==
                   IntegerType::getInt8Ty(M.getContext())->getPointerTo(0) &&
               FT->getParamType(2)->isIntegerTy();
+          isStrstr &=
+              FT->getNumParams() == 2 && FT->getReturnType()->isPointerTy() &&
+              FT->getParamType(0) == FT->getParamType(1) &&
+              FT->getParamType(0) ==
+                  IntegerType::getInt8Ty(M.getContext())->getPointerTo(0);
           isStdString &= FT->getNumParams() >= 2 &&
                          FT->getParamType(0)->isPointerTy() &&
                          FT->getParamType(1)->isPointerTy();
 
           if (!isStrcmp && !isMemcmp && !isStrncmp && !isStrcasecmp &&
-              !isStrncasecmp && !isIntMemcpy && !isStdString)
+              !isStrncasecmp && !isStrstr && !isIntMemcpy && !isStdString)
             continue;
 
           /* is a str{n,}{case,}cmp/memcmp, check if we have
            * str{case,}cmp(x, "const") or str{case,}cmp("const", x)
            * strn{case,}cmp(x, "const", ..) or strn{case,}cmp("const", x, ..)
-           * memcmp(x, "const", ..) or memcmp("const", x, ..) */
+           * memcmp(x, "const", ..) or memcmp("const", x, ..)
+           * str{case,}str(x, "const") or str{case,}str("const", x) */
           Value *Str1P = callInst->getArgOperand(0),
                 *Str2P = callInst->getArgOperand(1);
           std::string Str1, Str2;
@@ -516,8 +525,9 @@ bool AutoTokensPass::runOnModule(Module &M) {
           }
 
           // add null byte if this is a string compare function and a null
-          // was not already added
-          if (!isMemcmp) {
+          // was not already added. Not for strstr needles, which are found
+          // inside the haystack, so a trailing null byte would never match.
+          if (!isMemcmp && !isStrstr) {
             if (addedNull == false && thestring[optLen - 1] != '\0') {
               thestring.append("\0", 1);  // add null byte
               optLen++;
@@ -531,6 +541,13 @@ bool AutoTokensPass::runOnModule(Module &M) {
             }
           }
 
+          if (isStrstr) {
+            // cut the needle before its null byte, and any garbage after it
+            size_t offset = thestring.find('\0', 0);
+            if (offset < optLen) optLen = offset;
+            thestring = thestring.substr(0, optLen);
+          }
+
           // we take the longer string, even if the compare was to a
           // shorter part. Note that depending on the optimizer of the
           // compiler this can be wrong, but it is more likely that this
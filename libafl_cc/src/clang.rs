@@ -36,6 +36,8 @@ pub enum LLVMPasses {
     CoverageAccounting,
     /// The dump cfg pass
     DumpCfg,
+    /// The directed distance pass, instrumenting the distance of each basic block to target sites
+    DirectedDistance,
     #[cfg(unix)]
     /// The `CmpLog` Instruction pass
     CmpLogInstructions,
@@ -64,6 +66,8 @@ impl LLVMPasses {
             LLVMPasses::DumpCfg => {
                 PathBuf::from(env!("OUT_DIR")).join(format!("dump-cfg-pass.{}", dll_extension()))
             }
+            LLVMPasses::DirectedDistance => PathBuf::from(env!("OUT_DIR"))
+                .join(format!("directed-distance-pass.{}", dll_extension())),
             #[cfg(unix)]
             LLVMPasses::CmpLogInstructions => PathBuf::from(env!("OUT_DIR"))
                 .join(format!("cmplog-instructions-pass.{}", dll_extension())),
@@ -0,0 +1,313 @@
+/*
+   LibAFL - Directed distance LLVM pass
+   --------------------------------------------------
+
+   Copyright 2024 AFLplusplus Project. All rights reserved.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+   Computes the static distance of each basic block to a set of target sites,
+   as in AFLGo, and instruments each basic block that can reach a target to add
+   its distance to __libafl_directed_distance_sum and increment
+   __libafl_directed_distance_count. The runtime is in libafl_targets, with the
+   `directed` feature.
+
+   The target sites are read from the file given with
+   `-mllvm -directed_targets=<file>`, or the LIBAFL_DIRECTED_TARGETS environment
+   variable, one `file:line` per line. Files match by suffix, so paths relative
+   to the project root work. The target needs debug info, e.g.
+   -gline-tables-only.
+
+   The distances are computed per module: within a function, a basic block is
+   as far from a target as the shortest path in the CFG, and a call to a
+   function that can reach a target counts as CALL_WEIGHT times the distance of
+   that function in the call graph of the module.
+
+*/
+
+#include "common-llvm.h"
+
+#include <fstream>
+#include <functional>
+#include <queue>
+#include <string>
+#include <utility>
+#include <vector>
+
+#include "llvm/ADT/DenseMap.h"
+#include "llvm/IR/BasicBlock.h"
+#include "llvm/IR/CFG.h"
+#include "llvm/IR/DebugInfo.h"
+#include "llvm/IR/IRBuilder.h"
+#include "llvm/IR/InstrTypes.h"
+#include "llvm/IR/Module.h"
+#include "llvm/Support/CommandLine.h"
+
+// Without this, Can't build with llvm-14 & old PM
+#if LLVM_VERSION_MAJOR >= 14 && !defined(USE_NEW_PM)
+  #include "llvm/Pass.h"
+#endif
+
+using namespace llvm;
+
+/* The weight of a call edge, relative to an edge in the CFG */
+#define CALL_WEIGHT 10
+
+static cl::opt<std::string> TargetsFile(
+    "directed_targets",
+    cl::desc("File with the target sites, one file:line per line"),
+    cl::init(std::string("")), cl::NotHidden);
+static cl::opt<bool> Debug("debug-directed-distance",
+                           cl::desc("Debug prints"), cl::init(false),
+                           cl::NotHidden);
+
+namespace {
+
+struct TargetSite {
+  std::string file;
+  unsigned    line;
+};
+
+#ifdef USE_NEW_PM
+class DirectedDistancePass : public PassInfoMixin<DirectedDistancePass> {
+ public:
+  DirectedDistancePass() {
+#else
+class DirectedDistancePass : public ModulePass {
+ public:
+  static char ID;
+  DirectedDistancePass() : ModulePass(ID) {
+#endif
+  }
+
+#ifdef USE_NEW_PM
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+#else
+  bool runOnModule(Module &M) override;
+#endif
+
+ protected:
+  std::vector<TargetSite> targets;
+
+  void readTargets() {
+    std::string path = TargetsFile;
+    if (path.empty() && getenv("LIBAFL_DIRECTED_TARGETS")) {
+      path = getenv("LIBAFL_DIRECTED_TARGETS");
+    }
+    if (path.empty()) {
+      FATAL(
+          "No target sites, set -mllvm -directed_targets=<file> or "
+          "LIBAFL_DIRECTED_TARGETS\n");
+    }
+    std::ifstream in(path);
+    if (!in) { FATAL("Could not open the target sites %s\n", path.c_str()); }
+    std::string entry;
+    while (std::getline(in, entry)) {
+      size_t sep = entry.rfind(':');
+      if (sep == std::string::npos || sep == 0) { continue; }
+      targets.push_back(
+          {entry.substr(0, sep), (unsigned)atoi(entry.c_str() + sep + 1)});
+    }
+  }
+
+  bool isTarget(Instruction &I) {
+    const DebugLoc &Loc = I.getDebugLoc();
+    if (!Loc) { return false; }
+    unsigned line = Loc.getLine();
+    auto    *Scope = dyn_cast<DIScope>(Loc.getScope());
+    if (!Scope) { return false; }
+    std::string file = Scope->getFilename().str();
+    for (auto &target : targets) {
+      if (target.line != line || file.size() < target.file.size()) {
+        continue;
+      }
+      if (file.compare(file.size() - target.file.size(), target.file.size(),
+                       target.file) == 0) {
+        return true;
+      }
+    }
+    return false;
+  }
+};
+
+}  // namespace
+
+#ifdef USE_NEW_PM
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {LLVM_PLUGIN_API_VERSION, "DirectedDistancePass", "v0.1",
+          /* lambda to insert our pass into the pass pipeline. */
+          [](PassBuilder &PB) {
+  #if LLVM_VERSION_MAJOR <= 13
+            using OptimizationLevel = typename PassBuilder::OptimizationLevel;
+  #endif
+            PB.registerOptimizerLastEPCallback(
+                [](ModulePassManager &MPM, OptimizationLevel OL) {
+                  MPM.addPass(DirectedDistancePass());
+                });
+          }};
+}
+#else
+char DirectedDistancePass::ID = 0;
+#endif
+
+#ifdef USE_NEW_PM
+PreservedAnalyses DirectedDistancePass::run(Module            &M,
+                                            ModuleAnalysisManager &MAM) {
+#else
+bool DirectedDistancePass::runOnModule(Module &M) {
+#endif
+  LLVMContext &C = M.getContext();
+  IntegerType *Int64Ty = IntegerType::getInt64Ty(C);
+
+  readTargets();
+
+  /* Find the target basic blocks, and the calls of each function */
+
+  DenseMap<BasicBlock *, bool>                    targetBBs;
+  DenseMap<Function *, std::vector<Function *>>   callers;
+  DenseMap<BasicBlock *, std::vector<Function *>> callsInBB;
+  std::queue<Function *>                          worklist;
+  DenseMap<Function *, unsigned>                  funcDistance;
+
+  for (auto &F : M) {
+    if (F.isDeclaration() || isIgnoreFunction(&F)) { continue; }
+    for (auto &BB : F) {
+      for (auto &I : BB) {
+        if (isTarget(I)) { targetBBs[&BB] = true; }
+        if (auto *Call = dyn_cast<CallBase>(&I)) {
+          Function *Callee = Call->getCalledFunction();
+          if (Callee && !Callee->isDeclaration()) {
+            callsInBB[&BB].push_back(Callee);
+            callers[Callee].push_back(&F);
+          }
+        }
+      }
+      if (targetBBs.count(&BB) && !funcDistance.count(&F)) {
+        funcDistance[&F] = 0;
+        worklist.push(&F);
+      }
+    }
+  }
+
+  /* Distance of each function to a function containing a target, in the call
+   * graph */
+
+  while (!worklist.empty()) {
+    Function *F = worklist.front();
+    worklist.pop();
+    unsigned distance = funcDistance[F] + 1;
+    for (Function *Caller : callers[F]) {
+      if (!funcDistance.count(Caller)) {
+        funcDistance[Caller] = distance;
+        worklist.push(Caller);
+      }
+    }
+  }
+
+  /* Distance of each basic block, shortest path to a target or a call to a
+   * function reaching a target, then instrument */
+
+  GlobalVariable *DistanceSum =
+      new GlobalVariable(M, Int64Ty, false, GlobalValue::ExternalLinkage, 0,
+                         "__libafl_directed_distance_sum");
+  GlobalVariable *DistanceCount =
+      new GlobalVariable(M, Int64Ty, false, GlobalValue::ExternalLinkage, 0,
+                         "__libafl_directed_distance_count");
+
+  int inst_blocks = 0;
+
+  for (auto &F : M) {
+    if (F.isDeclaration() || !funcDistance.count(&F)) { continue; }
+
+    typedef std::pair<uint64_t, BasicBlock *> Entry;
+    std::priority_queue<Entry, std::vector<Entry>, std::greater<Entry>> queue;
+    DenseMap<BasicBlock *, uint64_t> bbDistance;
+
+    for (auto &BB : F) {
+      uint64_t seed = UINT64_MAX;
+      if (targetBBs.count(&BB)) { seed = 0; }
+      for (Function *Callee : callsInBB[&BB]) {
+        if (funcDistance.count(Callee)) {
+          seed = std::min(seed,
+                          (uint64_t)CALL_WEIGHT * (funcDistance[Callee] + 1));
+        }
+      }
+      if (seed != UINT64_MAX) { queue.push({seed, &BB}); }
+    }
+
+    while (!queue.empty()) {
+      uint64_t    distance = queue.top().first;
+      BasicBlock *BB = queue.top().second;
+      queue.pop();
+      if (bbDistance.count(BB)) { continue; }
+      bbDistance[BB] = distance;
+      for (BasicBlock *Pred : predecessors(BB)) {
+        if (!bbDistance.count(Pred)) { queue.push({distance + 1, Pred}); }
+      }
+    }
+
+    for (auto &BB : F) {
+      if (!bbDistance.count(&BB)) { continue; }
+
+      BasicBlock::iterator IP = BB.getFirstInsertionPt();
+      if (IP == BB.end()) { continue; }
+      IRBuilder<> IRB(&(*IP));
+
+      LoadInst *Sum = IRB.CreateLoad(
+#if LLVM_VERSION_MAJOR >= 14
+          Int64Ty,
+#endif
+          DistanceSum);
+      Sum->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+      IRB.CreateStore(
+             IRB.CreateAdd(Sum, ConstantInt::get(Int64Ty, bbDistance[&BB])),
+             DistanceSum)
+          ->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+
+      LoadInst *Count = IRB.CreateLoad(
+#if LLVM_VERSION_MAJOR >= 14
+          Int64Ty,
+#endif
+          DistanceCount);
+      Count->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+      IRB.CreateStore(IRB.CreateAdd(Count, ConstantInt::get(Int64Ty, 1)),
+                      DistanceCount)
+          ->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+
+      inst_blocks++;
+    }
+  }
+
+  if (Debug) {
+    fprintf(stderr,
+            "Directed distance: %u target blocks, %u functions reaching them, "
+            "instrumented %d blocks.\n",
+            (unsigned)targetBBs.size(), (unsigned)funcDistance.size(),
+            inst_blocks);
+  }
+
+#ifdef USE_NEW_PM
+  auto PA = PreservedAnalyses::none();
+  return PA;
+#else
+  return true;
+#endif
+}
+
+#ifndef USE_NEW_PM
+static void registerDirectedDistancePass(const PassManagerBuilder &,
+                                         legacy::PassManagerBase &PM) {
+  PM.add(new DirectedDistancePass());
+}
+
+static RegisterStandardPasses RegisterDirectedDistancePass(
+    PassManagerBuilder::EP_OptimizerLast, registerDirectedDistancePass);
+
+static RegisterStandardPasses RegisterDirectedDistancePass0(
+    PassManagerBuilder::EP_EnabledOnOptLevel0, registerDirectedDistancePass);
+#endif
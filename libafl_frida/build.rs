@@ -21,6 +21,11 @@ fn main() {
     // clang++ -shared -fPIC -O0 -o test_harness.so test_harness.cpp
     // Check if we have clang++ installed
 
+    if target_os == "ios" {
+        // The tests can't run on the device, so there is no need for the test harness
+        return;
+    }
+
     if target_family == "windows" {
         let compiler = cc::Build::new()
             .cpp(true)
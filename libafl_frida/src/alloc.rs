@@ -76,6 +76,8 @@ pub struct Allocator {
     base_mapping_addr: usize,
     /// The current mapping address
     current_mapping_addr: usize,
+    /// Statistics of the allocations
+    stats: AllocatorStats,
 }
 
 macro_rules! map_to_shadow {
@@ -103,6 +105,21 @@ pub struct AllocationMetadata {
     pub is_malloc_zero: bool,
 }
 
+/// Statistics of the allocations served by the [`Allocator`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AllocatorStats {
+    /// The number of allocations not freed yet
+    pub live_allocations: usize,
+    /// The number of bytes in allocations not freed yet
+    pub live_bytes: usize,
+    /// The highest number of live allocations since the last reset
+    pub peak_allocations: usize,
+    /// The highest number of live bytes since the last reset
+    pub peak_bytes: usize,
+    /// The number of allocations since the last reset
+    pub total_allocations: usize,
+}
+
 impl Allocator {
     /// Creates a new [`Allocator`] (not supported on this platform!)
     #[cfg(not(any(
@@ -251,6 +268,12 @@ impl Allocator {
         let address = (metadata.address + self.page_size) as *mut c_void;
 
         self.allocations.insert(address as usize, metadata);
+        self.stats.live_allocations += 1;
+        self.stats.live_bytes += size;
+        self.stats.total_allocations += 1;
+        self.stats.peak_allocations =
+            std::cmp::max(self.stats.peak_allocations, self.stats.live_allocations);
+        self.stats.peak_bytes = std::cmp::max(self.stats.peak_bytes, self.stats.live_bytes);
         log::trace!(
             "serving address: {:#x}, size: {:#x}",
             address as usize,
@@ -277,6 +300,9 @@ impl Allocator {
                 metadata.clone(),
                 Backtrace::new(),
             )));
+        } else {
+            self.stats.live_allocations = self.stats.live_allocations.saturating_sub(1);
+            self.stats.live_bytes = self.stats.live_bytes.saturating_sub(metadata.size);
         }
         let shadow_mapping_start = map_to_shadow!(self, ptr as usize);
 
@@ -347,6 +373,15 @@ impl Allocator {
         }
 
         self.total_allocation_size = 0;
+        self.stats.peak_allocations = self.stats.live_allocations;
+        self.stats.peak_bytes = self.stats.live_bytes;
+        self.stats.total_allocations = 0;
+    }
+
+    /// The statistics of the allocations since the last [`reset`](Self::reset)
+    #[must_use]
+    pub fn stats(&self) -> AllocatorStats {
+        self.stats
     }
 
     /// Gets the usable size of the allocation, by allocated pointer
@@ -830,6 +865,7 @@ impl Default for Allocator {
             total_allocation_size: 0,
             base_mapping_addr: 0,
             current_mapping_addr: 0,
+            stats: AllocatorStats::default(),
         }
     }
 }
@@ -892,3 +928,31 @@ fn check_shadow() {
     let allocation = unsafe { allocator.alloc(0x3c, 0) };
     assert!(allocator.check_shadow(unsafe { allocation.offset(0x3a) }, 2));
 }
+
+#[test]
+#[cfg(not(windows))] // not working yet
+fn check_stats() {
+    let mut allocator = Allocator::default();
+    allocator.init();
+
+    let first = unsafe { allocator.alloc(8, 8) };
+    let second = unsafe { allocator.alloc(0x20, 8) };
+    assert_eq!(allocator.stats().live_allocations, 2);
+    assert_eq!(allocator.stats().live_bytes, 0x28);
+
+    unsafe { allocator.release(first) };
+    let stats = allocator.stats();
+    assert_eq!(stats.live_allocations, 1);
+    assert_eq!(stats.live_bytes, 0x20);
+    assert_eq!(stats.peak_allocations, 2);
+    assert_eq!(stats.peak_bytes, 0x28);
+    assert_eq!(stats.total_allocations, 2);
+
+    // the freed allocation stays poisoned until the reset
+    assert!(!allocator.check_shadow(first, 1));
+    allocator.reset();
+    let stats = allocator.stats();
+    assert_eq!(stats.peak_allocations, 1);
+    assert_eq!(stats.total_allocations, 0);
+    assert!(allocator.check_shadow(second, 0x20));
+}
@@ -15,7 +15,10 @@ use std::{
     ffi::{c_char, c_void},
     ptr::write_volatile,
     rc::Rc,
-    sync::MutexGuard,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        MutexGuard,
+    },
 };
 
 use backtrace::Backtrace;
@@ -51,7 +54,10 @@ use crate::utils::{instruction_width, writer_register};
 use crate::utils::{operand_details, AccessType};
 use crate::{
     alloc::Allocator,
-    asan::errors::{AsanError, AsanErrors, AsanReadWriteError, ASAN_ERRORS},
+    asan::{
+        errors::{AsanError, AsanErrors, AsanReadWriteError, ASAN_ERRORS},
+        stats::ALLOCATOR_STATS,
+    },
     helper::{FridaRuntime, SkipRange},
     utils::disas_count,
 };
@@ -110,6 +116,18 @@ const ASAN_EH_FRAME_FDE_OFFSET: u32 = 20;
 #[cfg(target_arch = "aarch64")]
 const ASAN_EH_FRAME_FDE_ADDRESS_OFFSET: u32 = 28;
 
+/// Set by [`request_allocations_reset`], to reset the allocations after the current run
+static ASAN_RESET_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Request the [`AsanRuntime`] to reset its allocations after the current run, even if it keeps
+/// allocations across runs.
+///
+/// Call this from the harness when the persistent target resets its own state, so that freed
+/// memory may be reused again.
+pub fn request_allocations_reset() {
+    ASAN_RESET_REQUESTED.store(true, Ordering::Relaxed);
+}
+
 /// The `FRIDA` address sanitizer runtime, providing address sanitization.
 ///
 /// When executing in `ASan`, each memory access will get checked, using `FRIDA` stalker under the hood.
@@ -118,6 +136,7 @@ const ASAN_EH_FRAME_FDE_ADDRESS_OFFSET: u32 = 28;
 /// this helps finding mem errors early.
 pub struct AsanRuntime {
     check_for_leaks_enabled: bool,
+    persistent_allocations: bool,
     current_report_impl: u64,
     allocator: Allocator,
     regs: [usize; ASAN_SAVE_REGISTER_COUNT],
@@ -151,6 +170,7 @@ impl Debug for AsanRuntime {
         f.debug_struct("AsanRuntime")
             .field("stalked_addresses", &self.stalked_addresses)
             .field("continue_on_error", &self.continue_on_error)
+            .field("persistent_allocations", &self.persistent_allocations)
             .field("module_map", &"<ModuleMap>")
             .field("skip_ranges", &self.skip_ranges)
             .field("suppressed_addresses", &self.suppressed_addresses)
@@ -221,7 +241,10 @@ impl FridaRuntime for AsanRuntime {
         unsafe {
             self.poison(slice.as_ptr() as usize, slice.len());
         }
-        self.reset_allocations();
+        *ALLOCATOR_STATS.lock().unwrap() = self.allocator.stats();
+        if !self.persistent_allocations || ASAN_RESET_REQUESTED.swap(false, Ordering::Relaxed) {
+            self.reset_allocations();
+        }
 
         Ok(())
     }
@@ -249,6 +272,18 @@ impl AsanRuntime {
         }
     }
 
+    /// Keep allocations across runs, instead of resetting them after each run.
+    ///
+    /// Freed memory then stays poisoned for the next runs, catching use-after-frees spanning
+    /// iterations of a persistent loop, e.g. of objects cached by the target. As memory is not
+    /// reused, reset the allocations explicitly from time to time with [`request_allocations_reset`]
+    /// or [`Self::reset_allocations`], before reaching the maximum total allocation size.
+    #[must_use]
+    pub fn with_persistent_allocations(mut self, persistent: bool) -> Self {
+        self.persistent_allocations = persistent;
+        self
+    }
+
     /// Reset all allocations so that they can be reused for new allocation requests.
     #[allow(clippy::unused_self)]
     pub fn reset_allocations(&mut self) {
@@ -2704,6 +2739,7 @@ impl Default for AsanRuntime {
     fn default() -> Self {
         Self {
             check_for_leaks_enabled: false,
+            persistent_allocations: false,
             current_report_impl: 0,
             allocator: Allocator::default(),
             regs: [0; ASAN_SAVE_REGISTER_COUNT],
@@ -3,3 +3,4 @@ pub mod asan_rt;
 pub mod errors;
 #[allow(missing_docs)]
 pub mod hook_funcs;
+pub mod stats;
@@ -0,0 +1,63 @@
+//! Statistics of the allocations of the `libafl_frida` address sanitizer, as observer.
+use std::{borrow::Cow, sync::Mutex};
+
+use libafl::{executors::ExitKind, observers::Observer, Error};
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::alloc::AllocatorStats;
+
+/// The [`AllocatorStats`] of the last run, published by the `AsanRuntime` after each execution
+pub static ALLOCATOR_STATS: Mutex<AllocatorStats> = Mutex::new(AllocatorStats {
+    live_allocations: 0,
+    live_bytes: 0,
+    peak_allocations: 0,
+    peak_bytes: 0,
+    total_allocations: 0,
+});
+
+/// An observer for the [`AllocatorStats`] of the `AsanRuntime` after a `Frida` executor run,
+/// e.g. to find inputs leaking memory or using a lot of it.
+///
+/// Unless the runtime keeps allocations across runs, the statistics cover the last run only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllocatorStatsObserver {
+    name: Cow<'static, str>,
+    stats: AllocatorStats,
+}
+
+impl AllocatorStatsObserver {
+    /// Creates a new [`AllocatorStatsObserver`] with the given name
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            stats: AllocatorStats::default(),
+        }
+    }
+
+    /// The [`AllocatorStats`] of the last run
+    #[must_use]
+    pub fn stats(&self) -> &AllocatorStats {
+        &self.stats
+    }
+}
+
+impl<I, S> Observer<I, S> for AllocatorStatsObserver {
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.stats = *ALLOCATOR_STATS.lock().unwrap();
+        Ok(())
+    }
+}
+
+impl Named for AllocatorStatsObserver {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
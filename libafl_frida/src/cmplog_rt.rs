@@ -37,6 +37,10 @@ use libafl_targets::{cmps::__libafl_targets_cmplog_instructions, CMPLOG_MAP_W};
 use rangemap::RangeMap;
 
 use crate::helper::FridaRuntime;
+#[cfg(target_arch = "x86_64")]
+use crate::utils::code_address;
+#[cfg(target_arch = "aarch64")]
+use crate::utils::strip_code_pointer;
 #[cfg(all(feature = "cmplog", target_arch = "aarch64"))]
 use crate::utils::{disas_count, writer_register};
 
@@ -246,7 +250,7 @@ impl CmpLogRuntime {
                 ; self_addr:
                 ; .qword core::ptr::from_mut(self) as *mut c_void as i64
                 ; populate_lists:
-                ; .qword  CmpLogRuntime::populate_lists as *mut c_void as i64
+                ; .qword strip_code_pointer(CmpLogRuntime::populate_lists as *mut c_void) as i64
                 ; done:
             );};
         }
@@ -553,7 +557,11 @@ impl CmpLogRuntime {
         let block = InstructionBlock::new(&insts, 0);
         let block = BlockEncoder::encode(64, block, DecoderOptions::NONE).unwrap();
         writer.put_bytes(block.code_buffer.as_slice());
-        writer.put_call_address((CmpLogRuntime::populate_lists as usize).try_into().unwrap());
+        writer.put_call_address(
+            code_address(CmpLogRuntime::populate_lists as usize)
+                .try_into()
+                .unwrap(),
+        );
 
         writer.put_bytes(&self.restore_registers.clone().unwrap());
     }
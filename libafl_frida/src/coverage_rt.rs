@@ -187,9 +187,12 @@ impl CoverageRuntime {
     }
 
     /// Emits coverage mapping into the current basic block.
+    ///
+    /// The coverage id is derived from `location`, which is the address of the block, or a location
+    /// stable across regeneration for code generated at runtime.
     #[inline]
-    pub fn emit_coverage_mapping(&mut self, address: u64, output: &StalkerOutput) {
-        let h64 = hash_std(&address.to_le_bytes());
+    pub fn emit_coverage_mapping(&mut self, location: u64, output: &StalkerOutput) {
+        let h64 = hash_std(&location.to_le_bytes());
         let writer = output.writer();
 
         // Since the AARCH64 instruction set requires that a register be used if
@@ -24,9 +24,12 @@ use libafl_bolts::tuples::RefIndexable;
 
 #[cfg(not(test))]
 use crate::asan::errors::AsanErrors;
-use crate::helper::{FridaInstrumentationHelper, FridaRuntimeTuple};
 #[cfg(windows)]
 use crate::windows_hooks::initialize;
+use crate::{
+    helper::{FridaInstrumentationHelper, FridaRuntimeTuple},
+    utils::code_address,
+};
 
 /// The [`FridaInProcessExecutor`] is an [`Executor`] that executes the target in the same process, usinig [`frida`](https://frida.re/) for binary-only instrumentation.
 pub struct FridaInProcessExecutor<'a, 'b, 'c, H, OT, RT, S>
@@ -85,6 +88,18 @@ where
     ) -> Result<ExitKind, Error> {
         self.helper.pre_exec(input)?;
         if self.helper.stalker_enabled() {
+            let invalidated = self.helper.take_invalidated();
+            if self.followed && invalidated {
+                // Drop all instrumented code, so regenerated JIT code gets instrumented anew
+                log::debug!("instrumented code was invalidated, re-following");
+                if let Some(thread_id) = self.thread_id {
+                    self.stalker.unfollow(thread_id.try_into().unwrap());
+                } else {
+                    self.stalker.unfollow_me();
+                }
+                self.stalker.garbage_collect();
+                self.followed = false;
+            }
             if self.followed {
                 self.stalker.activate(NativePointer(core::ptr::null_mut()));
             } else {
@@ -184,12 +199,16 @@ where
         thread_id: Option<u32>,
     ) -> Self {
         let mut stalker = Stalker::new(gum);
+        if let Some(trust_threshold) = helper.trust_threshold() {
+            stalker.set_trust_threshold(trust_threshold);
+        }
         // Include the current module (the fuzzer) in stalked ranges. We clone the ranges so that
         // we don't add it to the INSTRUMENTED ranges.
         let mut ranges = helper.ranges().clone();
+        let fuzzer_address = code_address(Self::new as usize);
         for module in frida_gum::Module::enumerate_modules() {
-            if module.base_address < Self::new as usize
-                && (Self::new as usize) < module.base_address + module.size
+            if module.base_address < fuzzer_address
+                && fuzzer_address < module.base_address + module.size
             {
                 ranges.insert(
                     module.base_address..(module.base_address + module.size),
@@ -2,6 +2,7 @@ use core::fmt::{self, Debug, Formatter};
 use std::{
     cell::{Ref, RefCell, RefMut},
     fs,
+    ops::Range,
     path::{Path, PathBuf},
     rc::Rc,
 };
@@ -15,9 +16,9 @@ use libafl::{
     inputs::{HasTargetBytes, Input},
     Error,
 };
-use libafl_bolts::{cli::FuzzerOptions, tuples::MatchFirstType};
+use libafl_bolts::{cli::FuzzerOptions, hash_std, tuples::MatchFirstType};
 use libafl_targets::drcov::DrCovBasicBlock;
-#[cfg(unix)]
+#[cfg(all(unix, not(target_os = "ios")))]
 use nix::sys::mman::{mmap_anonymous, MapFlags, ProtFlags};
 use rangemap::RangeMap;
 #[cfg(target_arch = "aarch64")]
@@ -29,7 +30,10 @@ use yaxpeax_x86::amd64::InstDecoder;
 
 #[cfg(feature = "cmplog")]
 use crate::cmplog_rt::CmpLogRuntime;
-use crate::{asan::asan_rt::AsanRuntime, coverage_rt::CoverageRuntime, drcov_rt::DrCovRuntime};
+use crate::{
+    asan::asan_rt::AsanRuntime, coverage_rt::CoverageRuntime, drcov_rt::DrCovRuntime,
+    utils::code_address,
+};
 
 /// The Runtime trait
 pub trait FridaRuntime: 'static + Debug {
@@ -143,6 +147,8 @@ pub struct FridaInstrumentationHelperBuilder {
     instrument_module_predicate: Option<Box<dyn FnMut(&ModuleDetails) -> bool>>,
     skip_module_predicate: Box<dyn FnMut(&ModuleDetails) -> bool>,
     skip_ranges: Vec<SkipRange>,
+    jit_regions: Vec<(Range<usize>, String)>,
+    trust_threshold: Option<i32>,
 }
 
 impl FridaInstrumentationHelperBuilder {
@@ -244,6 +250,33 @@ impl FridaInstrumentationHelperBuilder {
         self
     }
 
+    /// Instrument a region of code generated at runtime, e.g. the code cache of a JIT.
+    ///
+    /// Coverage ids of blocks in the region depend on the `name` and the offset in the region,
+    /// instead of the absolute address, so regenerated code at the same offset keeps its ids.
+    /// Regions only known later can be added with [`FridaInstrumentationHelper::add_jit_region`],
+    /// but unless [`disable_excludes`](Self::disable_excludes) is set, they must not be excluded
+    /// from the stalker, i.e. lie in a range known when the executor is created.
+    #[must_use]
+    pub fn jit_region<N: Into<String>>(mut self, range: Range<usize>, name: N) -> Self {
+        self.jit_regions.push((range, name.into()));
+        self
+    }
+
+    /// Set the trust threshold of the [`Stalker`](https://frida.re/docs/stalker/): the number of
+    /// executions of a block before the stalker assumes it does not change anymore.
+    ///
+    /// Until then, the stalker compares the block with its original code on each execution, and
+    /// re-instruments it if it changed. `-1` never trusts any block, which catches all code
+    /// regenerated in place, at a high cost. Defaults to frida's default of `1`.
+    #[must_use]
+    pub fn trust_threshold(self, trust_threshold: i32) -> Self {
+        Self {
+            trust_threshold: Some(trust_threshold),
+            ..self
+        }
+    }
+
     /// Build a [`FridaInstrumentationHelper`]
     pub fn build<RT: FridaRuntimeTuple>(
         self,
@@ -256,6 +289,8 @@ impl FridaInstrumentationHelperBuilder {
             mut instrument_module_predicate,
             mut skip_module_predicate,
             skip_ranges,
+            jit_regions: initial_jit_regions,
+            trust_threshold,
         } = self;
 
         let mut module_filter = Box::new(move |module| {
@@ -277,6 +312,7 @@ impl FridaInstrumentationHelperBuilder {
         // These moves MUST occur before the runtimes are init-ed
         let ranges = Rc::new(RefCell::new(ranges));
         let runtimes = Rc::new(RefCell::new(runtimes));
+        let jit_regions = Rc::new(RefCell::new(RangeMap::new()));
 
         if stalker_enabled {
             for (i, module) in module_map.values().iter().enumerate() {
@@ -303,22 +339,33 @@ impl FridaInstrumentationHelperBuilder {
                     }
                 }
             }
+            for (range, name) in initial_jit_regions {
+                let id = next_range_id(&ranges.borrow());
+                ranges
+                    .borrow_mut()
+                    .insert(range.clone(), (id, name.clone()));
+                jit_regions.borrow_mut().insert(range, name);
+            }
             runtimes
                 .borrow_mut()
                 .init_all(gum, &ranges.borrow(), &module_map);
         }
 
-        let transformer = FridaInstrumentationHelper::build_transformer(gum, &ranges, &runtimes);
+        let transformer =
+            FridaInstrumentationHelper::build_transformer(gum, &ranges, &jit_regions, &runtimes);
 
-        #[cfg(unix)]
+        #[cfg(all(unix, not(target_os = "ios")))]
         FridaInstrumentationHelper::<'_, RT>::workaround_gum_allocate_near();
 
         FridaInstrumentationHelper {
             transformer,
             ranges,
+            jit_regions,
             runtimes,
             stalker_enabled,
             disable_excludes,
+            trust_threshold,
+            invalidated: false,
         }
     }
 }
@@ -331,6 +378,8 @@ impl Debug for FridaInstrumentationHelperBuilder {
             .field("instrument_module_predicate", &"<closure>")
             .field("skip_module_predicate", &"<closure>")
             .field("skip_ranges", &self.skip_ranges)
+            .field("jit_regions", &self.jit_regions)
+            .field("trust_threshold", &self.trust_threshold)
             .field("disable_excludes", &self.disable_excludes);
         dbg_me.finish()
     }
@@ -347,9 +396,11 @@ impl Default for FridaInstrumentationHelperBuilder {
                 let range = module.range();
                 let start = range.base_address().0 as usize;
                 let range = start..(start + range.size());
-                range.contains(&(Self::new as usize))
+                range.contains(&code_address(Self::new as usize))
             }),
             skip_ranges: Vec::new(),
+            jit_regions: Vec::new(),
+            trust_threshold: None,
         }
     }
 }
@@ -358,9 +409,12 @@ impl Default for FridaInstrumentationHelperBuilder {
 pub struct FridaInstrumentationHelper<'a, RT: 'a> {
     transformer: Transformer<'a>,
     ranges: Rc<RefCell<RangeMap<usize, (u16, String)>>>,
+    jit_regions: Rc<RefCell<RangeMap<usize, String>>>,
     runtimes: Rc<RefCell<RT>>,
     stalker_enabled: bool,
     pub(crate) disable_excludes: bool,
+    trust_threshold: Option<i32>,
+    invalidated: bool,
 }
 
 impl<RT> Debug for FridaInstrumentationHelper<'_, RT> {
@@ -368,6 +422,7 @@ impl<RT> Debug for FridaInstrumentationHelper<'_, RT> {
         let mut dbg_me = f.debug_struct("FridaInstrumentationHelper");
         dbg_me
             .field("ranges", &self.ranges)
+            .field("jit_regions", &self.jit_regions)
             .field("module_map", &"<ModuleMap>")
             .field("stalker_enabled", &self.stalker_enabled);
         dbg_me.finish()
@@ -387,6 +442,15 @@ pub fn get_module_size(module_name: &str) -> usize {
     code_size
 }
 
+/// The next free id for a range, after all ids of modules and JIT regions in `ranges`
+fn next_range_id(ranges: &RangeMap<usize, (u16, String)>) -> u16 {
+    ranges
+        .iter()
+        .map(|(_, (id, _))| id.saturating_add(1))
+        .max()
+        .unwrap_or(0)
+}
+
 fn pathlist_contains_module<I, P>(list: I, module: &ModuleDetails) -> bool
 where
     I: IntoIterator<Item = P>,
@@ -450,9 +514,11 @@ where
     fn build_transformer(
         gum: &'a Gum,
         ranges: &Rc<RefCell<RangeMap<usize, (u16, String)>>>,
+        jit_regions: &Rc<RefCell<RangeMap<usize, String>>>,
         runtimes: &Rc<RefCell<RT>>,
     ) -> Transformer<'a> {
         let ranges = Rc::clone(ranges);
+        let jit_regions = Rc::clone(jit_regions);
         let runtimes = Rc::clone(runtimes);
 
         #[cfg(target_arch = "x86_64")]
@@ -462,7 +528,14 @@ where
         let decoder = <ARMv8 as Arch>::Decoder::default();
 
         Transformer::from_callback(gum, move |basic_block, output| {
-            Self::transform(basic_block, &output, &ranges, &runtimes, decoder);
+            Self::transform(
+                basic_block,
+                &output,
+                &ranges,
+                &jit_regions,
+                &runtimes,
+                decoder,
+            );
         })
     }
 
@@ -471,6 +544,7 @@ where
         basic_block: StalkerIterator,
         output: &StalkerOutput,
         ranges: &Rc<RefCell<RangeMap<usize, (u16, String)>>>,
+        jit_regions: &Rc<RefCell<RangeMap<usize, String>>>,
         runtimes_unborrowed: &Rc<RefCell<RT>>,
         decoder: InstDecoder,
     ) {
@@ -494,7 +568,8 @@ where
                     );
                     if let Some(rt) = runtimes.match_first_type_mut::<CoverageRuntime>() {
                         let start = output.writer().pc();
-                        rt.emit_coverage_mapping(address, output);
+                        let location = Self::coverage_location(&jit_regions.borrow(), address);
+                        rt.emit_coverage_mapping(location, output);
                         log::trace!(
                             "emitted coverage info mapping for {:x} at {:x}-{:x}",
                             address,
@@ -599,6 +674,16 @@ where
         }
     }
 
+    /// The location of the block at `address` for coverage: its address, or, in a JIT region,
+    /// its offset in the region mixed with the name of the region, to stay stable when the code
+    /// is regenerated at another address of the region or in another run.
+    fn coverage_location(jit_regions: &RangeMap<usize, String>, address: u64) -> u64 {
+        match jit_regions.get_key_value(&(address as usize)) {
+            Some((range, name)) => hash_std(name.as_bytes()) ^ (address - range.start as u64),
+            None => address,
+        }
+    }
+
     /// Clean up all runtimes
     pub fn deinit(&mut self, gum: &Gum) {
         (*self.runtimes).borrow_mut().deinit_all(gum);
@@ -623,7 +708,8 @@ where
     */
 
     // workaround frida's frida-gum-allocate-near bug:
-    #[cfg(unix)]
+    // (not on iOS, where the address space of apps is too small to reserve these regions)
+    #[cfg(all(unix, not(target_os = "ios")))]
     fn workaround_gum_allocate_near() {
         unsafe {
             for _ in 0..512 {
@@ -697,4 +783,59 @@ where
     pub fn ranges_mut(&mut self) -> RefMut<RangeMap<usize, (u16, String)>> {
         (*self.ranges).borrow_mut()
     }
+
+    /// The regions of code generated at runtime, with their names
+    #[must_use]
+    pub fn jit_regions(&self) -> Ref<RangeMap<usize, String>> {
+        self.jit_regions.borrow()
+    }
+
+    /// Instrument a new region of code generated at runtime, see
+    /// [`FridaInstrumentationHelperBuilder::jit_region`].
+    ///
+    /// Code already instrumented in the region is invalidated.
+    pub fn add_jit_region<N: Into<String>>(&mut self, range: Range<usize>, name: N) {
+        let name = name.into();
+        log::info!(
+            "adding jit region {name}: {:x}-{:x}",
+            range.start,
+            range.end
+        );
+        let id = next_range_id(&self.ranges.borrow());
+        (*self.ranges)
+            .borrow_mut()
+            .insert(range.clone(), (id, name.clone()));
+        (*self.jit_regions).borrow_mut().insert(range, name);
+        self.invalidated = true;
+    }
+
+    /// Stop instrumenting a region of code generated at runtime, e.g. when the JIT unmaps it.
+    ///
+    /// Code already instrumented in the region is invalidated.
+    pub fn remove_jit_region(&mut self, range: Range<usize>) {
+        log::info!("removing jit region {:x}-{:x}", range.start, range.end);
+        (*self.ranges).borrow_mut().remove(range.clone());
+        (*self.jit_regions).borrow_mut().remove(range);
+        self.invalidated = true;
+    }
+
+    /// Invalidate the instrumented code of a JIT region, e.g. when the JIT flushes its code cache.
+    ///
+    /// The stalker drops all instrumented code before the next execution, and instruments the
+    /// regenerated code anew, with the same coverage ids for blocks at the same offsets.
+    pub fn invalidate_jit_region(&mut self, range: Range<usize>) {
+        log::debug!("invalidating jit region {:x}-{:x}", range.start, range.end);
+        self.invalidated = true;
+    }
+
+    /// Returns if instrumented code was invalidated since the last call, and resets the flag
+    pub fn take_invalidated(&mut self) -> bool {
+        core::mem::take(&mut self.invalidated)
+    }
+
+    /// The trust threshold of the stalker, if set
+    #[must_use]
+    pub fn trust_threshold(&self) -> Option<i32> {
+        self.trust_threshold
+    }
 }
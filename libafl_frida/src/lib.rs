@@ -3,6 +3,10 @@ The [`Frida`](https://frida.re) executor is a binary-only mode for `LibAFL`.
 
 It can report coverage and, on supported architectures, even reports memory access errors.
 
+Coverage and `CmpLog` also work on arm64 iOS, for on-device fuzzing of iOS frameworks on jailbroken devices.
+The instrumentation only goes to the code generated by the stalker, which takes care of the W^X restrictions itself,
+and pointers to our own functions have their pointer authentication codes stripped before being called.
+
 Additional documentation is available in [the `LibAFL` book](https://aflplus.plus/libafl-book/advanced_features/frida.html).
 
 */
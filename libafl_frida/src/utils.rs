@@ -17,6 +17,30 @@ use yaxpeax_x86::amd64::Operand;
 #[cfg(target_arch = "x86_64")]
 use yaxpeax_x86::amd64::{InstDecoder, Instruction, RegSpec};
 
+/// Strip the pointer authentication code (PAC) from a code pointer.
+///
+/// On arm64e, e.g. on iOS, function pointers are signed, and the signature has to be removed
+/// before the pointer is called with a plain `blr` or compared with an address. Elsewhere, this is a no-op.
+#[must_use]
+#[inline]
+pub fn strip_code_pointer(ptr: *mut core::ffi::c_void) -> *mut core::ffi::c_void {
+    #[cfg(all(target_vendor = "apple", target_arch = "aarch64"))]
+    {
+        unsafe { frida_gum_sys::gum_strip_code_pointer(ptr) }
+    }
+    #[cfg(not(all(target_vendor = "apple", target_arch = "aarch64")))]
+    {
+        ptr
+    }
+}
+
+/// The address of the function `func`, without pointer authentication code, see [`strip_code_pointer`]
+#[must_use]
+#[inline]
+pub fn code_address(func: usize) -> usize {
+    strip_code_pointer(func as *mut core::ffi::c_void) as usize
+}
+
 /// Determine the size of an SIMD register
 #[cfg(target_arch = "aarch64")]
 #[inline]
@@ -1,6 +1,6 @@
 use alloc::rc::Rc;
 use core::{cell::RefCell, fmt::Debug};
-use std::borrow::Cow;
+use std::{borrow::Cow, path::PathBuf};
 
 use libafl::{
     alloc,
@@ -80,13 +80,15 @@ impl LibfuzzerCrashCauseMetadata {
 #[derive(Debug)]
 pub struct LibfuzzerCrashCauseFeedback {
     artifact_prefix: ArtifactPrefix,
+    exact_artifact_path: Option<PathBuf>,
     exit_kind: ExitKind,
 }
 
 impl LibfuzzerCrashCauseFeedback {
-    pub fn new(artifact_prefix: ArtifactPrefix) -> Self {
+    pub fn new(artifact_prefix: ArtifactPrefix, exact_artifact_path: Option<PathBuf>) -> Self {
         Self {
             artifact_prefix,
+            exact_artifact_path,
             exit_kind: ExitKind::Ok,
         }
     }
@@ -101,6 +103,10 @@ impl Named for LibfuzzerCrashCauseFeedback {
 
 impl LibfuzzerCrashCauseFeedback {
     fn set_filename<I: Input>(&self, prefix: &str, testcase: &mut Testcase<I>) {
+        if let Some(path) = &self.exact_artifact_path {
+            *testcase.file_path_mut() = Some(path.clone());
+            return;
+        }
         let base = if let Some(filename) = testcase.filename() {
             filename.clone()
         } else {
@@ -7,7 +7,7 @@ use std::{
     net::TcpListener,
     os::fd::AsRawFd,
     str::FromStr,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use libafl::{
@@ -25,8 +25,10 @@ use libafl::{
 };
 use libafl_bolts::{
     core_affinity::Cores,
+    current_time, impl_serdeany,
     shmem::{ShMemProvider, StdShMemProvider},
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{feedbacks::LibfuzzerCrashCauseMetadata, fuzz_with, options::LibfuzzerOptions};
 
@@ -53,6 +55,17 @@ fn destroy_output_fds(options: &LibfuzzerOptions) {
     }
 }
 
+/// Interval between the progress reports while fuzzing for `-max_total_time`, as in [`Fuzzer::fuzz_loop`]
+const MONITOR_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// When the campaign started; kept in the state so `-max_total_time` also covers the restarts
+#[derive(Deserialize, Serialize, Debug)]
+pub struct FuzzStartTimeMetadata {
+    start: Duration,
+}
+
+impl_serdeany!(FuzzStartTimeMetadata);
+
 fn do_fuzz<F, ST, E, S, EM>(
     options: &LibfuzzerOptions,
     fuzzer: &mut F,
@@ -97,6 +110,25 @@ where
             return Err(Error::shutting_down());
         }
     }
+    if let Some(max_total_time) = options.max_total_time() {
+        let start = state
+            .metadata_or_insert_with(|| FuzzStartTimeMetadata {
+                start: current_time(),
+            })
+            .start;
+        while current_time().saturating_sub(start) < max_total_time {
+            mgr.maybe_report_progress(state, MONITOR_TIMEOUT)?;
+            fuzzer.fuzz_one(stages, executor, state, mgr)?;
+        }
+        mgr.report_progress(state)?;
+        log::info!(
+            "Done: fuzzed for the -max_total_time of {}s",
+            max_total_time.as_secs()
+        );
+        // tell a restarting manager not to respawn us
+        mgr.on_shutdown()?;
+        return Err(Error::shutting_down());
+    }
     fuzzer.fuzz_loop(stages, executor, state, mgr)?;
     Ok(())
 }
@@ -114,10 +114,12 @@ struct CustomMutationStatus {
     std_no_crossover: bool,
     custom_mutation: bool,
     custom_crossover: bool,
+    /// the custom mutator is one of the havoc mutations, instead of replacing them
+    havoc_custom: bool,
 }
 
 impl CustomMutationStatus {
-    fn new() -> Self {
+    fn new(options: &LibfuzzerOptions) -> Self {
         let custom_mutation = libafl_targets::libfuzzer::has_custom_mutator();
         let custom_crossover = libafl_targets::libfuzzer::has_custom_crossover();
 
@@ -127,6 +129,8 @@ impl CustomMutationStatus {
         let std_no_mutate = !std_mutational && custom_mutation && !custom_crossover;
         // we use libafl mutations, but not libafl crossover
         let std_no_crossover = !std_mutational && !custom_mutation && custom_crossover;
+        // we use the custom mutator together with the libafl mutations
+        let havoc_custom = custom_mutation && options.havoc_with_custom();
 
         Self {
             std_mutational,
@@ -134,6 +138,7 @@ impl CustomMutationStatus {
             std_no_crossover,
             custom_mutation,
             custom_crossover,
+            havoc_custom,
         }
     }
 }
@@ -185,7 +190,7 @@ macro_rules! fuzz_with {
         let edge_maker = &$edge_maker;
 
         let closure = |mut state: Option<_>, mut mgr, _cpu_id| {
-            let mutator_status = CustomMutationStatus::new();
+            let mutator_status = CustomMutationStatus::new(&$options);
             let grimoire_metadata = should_use_grimoire(&mut state, &$options, &mutator_status)?;
             let grimoire = grimoire_metadata.should();
 
@@ -252,7 +257,10 @@ macro_rules! fuzz_with {
 
             // A feedback to choose if an input is a solution or not
             let mut objective = feedback_or_fast!(
-                LibfuzzerCrashCauseFeedback::new($options.artifact_prefix().clone()),
+                LibfuzzerCrashCauseFeedback::new(
+                    $options.artifact_prefix().clone(),
+                    $options.exact_artifact_path().cloned()
+                ),
                 OomFeedback,
                 feedback_and_fast!(
                     CrashFeedback::new(),
@@ -379,7 +387,18 @@ macro_rules! fuzz_with {
             let std_mutator_no_mutate = StdScheduledMutator::with_max_stack_pow(havoc_crossover(),3);
 
             let cm_power: StdPowerMutationalStage<_, _, BytesInput, _, _> = StdPowerMutationalStage::new(custom_mutator);
-            let cm_power = IfStage::new(|_, _, _, _| Ok(mutator_status.custom_mutation.into()), (cm_power, ()));
+            let cm_power = IfStage::new(|_, _, _, _| Ok((mutator_status.custom_mutation && !mutator_status.havoc_custom).into()), (cm_power, ()));
+
+            // with `-havoc_with_custom=1`, the custom mutator is scheduled as one more havoc mutation
+            let havoc_custom_mutator = StdScheduledMutator::new(
+                havoc_mutations_no_crossover()
+                    .merge(tokens_mutations())
+                    .merge(tuple_list!(unsafe {
+                        LLVMCustomMutator::mutate_unchecked(StdScheduledMutator::new(havoc_mutations_no_crossover().merge(tokens_mutations())))
+                    })),
+            );
+            let hc_power: StdPowerMutationalStage<_, _, BytesInput, _, _> = StdPowerMutationalStage::new(havoc_custom_mutator);
+            let hc_power = IfStage::new(|_, _, _, _| Ok(mutator_status.havoc_custom.into()), (hc_power, ()));
             let cm_std_power = StdMutationalStage::new(std_mutator_no_mutate);
             let cm_std_power =
                 IfStage::new(|_, _, _, _| Ok(mutator_status.std_no_mutate.into()), (cm_std_power, ()));
@@ -506,6 +525,7 @@ macro_rules! fuzz_with {
                 cm_i2s,
                 std_power,
                 cm_power,
+                hc_power,
                 cm_std_power,
                 cc_std_power,
                 cc_power,
@@ -116,7 +116,10 @@ pub fn merge(
 
     // A feedback to choose if an input is a solution or not
     let mut objective = feedback_or_fast!(
-        LibfuzzerCrashCauseFeedback::new(options.artifact_prefix().clone()),
+        LibfuzzerCrashCauseFeedback::new(
+            options.artifact_prefix().clone(),
+            options.exact_artifact_path().cloned(),
+        ),
         OomFeedback,
         CrashFeedback::new(),
         TimeoutFeedback::new()
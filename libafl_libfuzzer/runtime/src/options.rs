@@ -120,8 +120,11 @@ pub struct LibfuzzerOptions {
     dedup: bool,
     shrink: bool,
     skip_tracing: bool,
+    havoc_with_custom: bool,
     tui: bool,
     runs: usize,
+    max_total_time: Option<Duration>,
+    exact_artifact_path: Option<PathBuf>,
     close_fd_mask: u8,
     unknown: Vec<String>,
 }
@@ -216,6 +219,10 @@ impl LibfuzzerOptions {
         self.skip_tracing
     }
 
+    pub fn havoc_with_custom(&self) -> bool {
+        self.havoc_with_custom
+    }
+
     pub fn tui(&self) -> bool {
         self.tui
     }
@@ -224,6 +231,14 @@ impl LibfuzzerOptions {
         self.runs
     }
 
+    pub fn max_total_time(&self) -> Option<Duration> {
+        self.max_total_time
+    }
+
+    pub fn exact_artifact_path(&self) -> Option<&PathBuf> {
+        self.exact_artifact_path.as_ref()
+    }
+
     pub fn close_fd_mask(&self) -> u8 {
         self.close_fd_mask
     }
@@ -254,8 +269,11 @@ struct LibfuzzerOptionsBuilder<'a> {
     dedup: bool,
     shrink: bool,
     skip_tracing: bool,
+    havoc_with_custom: bool,
     tui: bool,
     runs: usize,
+    max_total_time: Option<Duration>,
+    exact_artifact_path: Option<&'a str>,
     close_fd_mask: u8,
     unknown: Vec<&'a str>,
 }
@@ -279,7 +297,7 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                         self.dirs.push(dir);
                     }
                     Flag { name, value } => match name {
-                        "merge" => {
+                        "merge" | "set_cover_merge" => {
                             if parse_or_bail!(name, value, u64) > 0
                                 && *self.mode.get_or_insert(LibfuzzerMode::Merge)
                                     != LibfuzzerMode::Merge
@@ -342,6 +360,9 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                         "dedup" => self.dedup = parse_or_bail!(name, value, u64) > 0,
                         "shrink" => self.shrink = parse_or_bail!(name, value, u64) > 0,
                         "skip_tracing" => self.skip_tracing = parse_or_bail!(name, value, u64) > 0,
+                        "havoc_with_custom" => {
+                            self.havoc_with_custom = parse_or_bail!(name, value, u64) > 0;
+                        }
                         "tui" => {
                             self.tui = parse_or_bail!(name, value, u64) > 0;
                             if self.tui {
@@ -357,6 +378,13 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                             }
                         }
                         "runs" => self.runs = parse_or_bail!(name, value, usize),
+                        "max_total_time" => {
+                            self.max_total_time = match parse_or_bail!(name, value, u64) {
+                                0 => None,
+                                secs => Some(Duration::from_secs(secs)),
+                            };
+                        }
+                        "exact_artifact_path" => self.exact_artifact_path = Some(value),
                         "close_fd_mask" => self.close_fd_mask = parse_or_bail!(name, value, u8),
                         _ => {
                             self.unknown.push(arg);
@@ -401,8 +429,11 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
             dedup: self.dedup,
             shrink: self.shrink,
             skip_tracing: self.skip_tracing,
+            havoc_with_custom: self.havoc_with_custom,
             tui: self.tui,
             runs: self.runs,
+            max_total_time: self.max_total_time,
+            exact_artifact_path: self.exact_artifact_path.map(PathBuf::from),
             close_fd_mask: self.close_fd_mask,
             unknown: self.unknown.into_iter().map(ToString::to_string).collect(),
         }
@@ -1,6 +1,7 @@
 use std::{
     ffi::c_int,
     fs::{read, write},
+    time::Instant,
 };
 
 use libafl::{
@@ -74,61 +75,69 @@ fn minimize_crash_with_mutator<M: Mutator<BytesInput, TMinState>>(
 
     let exit_kind = fuzzer.execute_input(&mut state, &mut executor, &mut mgr, &input)?;
 
-    let size = input.len();
+    let original_size = input.len();
     let id = state.corpus_mut().add(Testcase::new(input))?;
+    let runs = if options.runs() == 0 {
+        128
+    } else {
+        options.runs()
+    };
+    let start = Instant::now();
 
-    match exit_kind {
-        ExitKind::Crash => {
-            let factory = CrashFeedback::new();
-            let tmin = StdTMinMutationalStage::new(
-                mutator,
-                factory,
-                if options.runs() == 0 {
-                    128
-                } else {
-                    options.runs()
-                },
-            );
-            let mut stages = tuple_list!(tmin);
-            fuzzer.fuzz_one(&mut stages, &mut executor, &mut state, &mut mgr)?;
-        }
-        ExitKind::Timeout => {
-            let factory = TimeoutFeedback::new();
-            let tmin = StdTMinMutationalStage::new(
-                mutator,
-                factory,
-                if options.runs() == 0 {
-                    128
-                } else {
-                    options.runs()
-                },
-            );
+    // Like libfuzzer, keep minimising until a round brings no progress or we run out of time
+    macro_rules! minimise_rounds {
+        ($factory:expr) => {{
+            let tmin = StdTMinMutationalStage::new(mutator, $factory, runs);
             let mut stages = tuple_list!(tmin);
-            fuzzer.fuzz_one(&mut stages, &mut executor, &mut state, &mut mgr)?;
+            let mut size = original_size;
+            loop {
+                fuzzer.fuzz_one(&mut stages, &mut executor, &mut state, &mut mgr)?;
+                let new_size = state.testcase_mut(id)?.load_input(state.corpus())?.len();
+                if new_size >= size
+                    || options
+                        .max_total_time()
+                        .is_some_and(|max| start.elapsed() >= max)
+                {
+                    break;
+                }
+                eprintln!("Minimised input from {size} to {new_size} bytes");
+                size = new_size;
+            }
+        }};
+    }
+
+    match exit_kind {
+        ExitKind::Crash => minimise_rounds!(CrashFeedback::new()),
+        ExitKind::Timeout => minimise_rounds!(TimeoutFeedback::new()),
+        kind => {
+            return Err(Error::illegal_argument(format!(
+                "The input does not crash or time out (exit kind {kind:?}), nothing to minimise"
+            )))
         }
-        kind => unimplemented!("Unsupported exit kind for test minification: {:?}", kind),
     }
 
     let mut testcase = state.testcase_mut(id)?;
     let input = testcase.load_input(state.corpus())?.bytes().to_vec();
     drop(testcase);
-    if input.len() >= size {
+    if input.len() >= original_size {
         eprintln!(
             "Unable to reduce {}",
             options.dirs()[0].as_path().as_os_str().to_str().unwrap()
         );
     } else {
-        let mut dest = options.artifact_prefix().dir().clone();
-        dest.push(format!(
-            "{}minimized-from-{}",
-            options.artifact_prefix().filename_prefix(),
-            options.dirs()[0].file_name().unwrap().to_str().unwrap()
-        ));
+        let dest = if let Some(path) = options.exact_artifact_path() {
+            path.clone()
+        } else {
+            let mut dest = options.artifact_prefix().dir().clone();
+            dest.push(format!(
+                "{}minimized-from-{}",
+                options.artifact_prefix().filename_prefix(),
+                options.dirs()[0].file_name().unwrap().to_str().unwrap()
+            ));
+            dest
+        };
         write(&dest, input)?;
-        println!(
-            "Wrote minimised input to {}",
-            dest.file_name().unwrap().to_str().unwrap()
-        );
+        println!("Wrote minimised input to {}", dest.display());
     }
 
     Ok(())
@@ -149,7 +158,7 @@ pub fn minimize_crash(
             .collect::<Vec<_>>()
             .join(", ")
     );
-    let mutator_status = CustomMutationStatus::new();
+    let mutator_status = CustomMutationStatus::new(options);
 
     let state = StdState::new(
         StdRand::new(),
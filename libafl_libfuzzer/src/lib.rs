@@ -42,6 +42,9 @@
 //! - `-skip_tracing=n`, with `n` = 1 causing `libafl_libfuzzer` to disable cmplog tracing.
 //!   - you should do this if your target performs many comparisons on memory sequences which are
 //!     not contained in the input
+//! - `-havoc_with_custom=n`, with `n` = 1 scheduling `LLVMFuzzerCustomMutator` as one of the havoc
+//!   mutations, instead of using it exclusively.
+//!   - useful if your custom mutator is not the only way to reach interesting inputs
 //! - `-tui=n`, with `n` = 1 enabling a graphical terminal interface.
 //!   - experimental; some users report inconsistent behaviour with tui enabled
 //!
@@ -49,8 +52,12 @@
 //!
 //! ### Supported flags from libfuzzer
 //!
-//! - `-merge`
+//! - `-merge` and `-set_cover_merge`
+//!   - in `libafl_libfuzzer`, these are synonymous
 //! - `-minimize_crash`
+//!   - minimisation is repeated until it makes no more progress, or `-max_total_time` is exceeded
+//! - `-exact_artifact_path`
+//!   - used for the minimised input with `-minimize_crash`
 //! - `-artifact_prefix`
 //! - `-timeout`
 //!   - unlike libfuzzer, `libafl_libfuzzer` supports partial second timeouts (e.g. `-timeout=.5`)
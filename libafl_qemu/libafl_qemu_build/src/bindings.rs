@@ -165,6 +165,14 @@ pub fn generate(
         .allowlist_function("cpu_memory_rw_debug")
         .allowlist_function("cpu_physical_memory_rw")
         .allowlist_function("cpu_reset")
+        .allowlist_function("cpu_interrupt")
+        .allowlist_function("cpu_reset_interrupt")
+        .allowlist_var("CPU_INTERRUPT_.*")
+        .allowlist_function("memory_region_init_io")
+        .allowlist_function("memory_region_add_subregion_overlap")
+        .allowlist_function("get_system_memory")
+        .allowlist_type("MemoryRegionOps")
+        .allowlist_type("device_endian")
         .allowlist_function("cpu_synchronize_state")
         .allowlist_function("cpu_get_phys_page_attrs_debug")
         .allowlist_function("tlb_plugin_lookup")
@@ -194,6 +202,7 @@ pub fn generate(
         bindings
             .allowlist_type("ARMCPU")
             .allowlist_type("ARMv7MState")
+            .allowlist_function("armv7m_nvic_set_pending")
     } else {
         bindings
     };
@@ -1,4 +1,11 @@
-use std::{cell::UnsafeCell, cmp::max, fmt::Debug, ptr, ptr::addr_of};
+use std::{
+    cell::{Cell, UnsafeCell},
+    cmp::max,
+    fmt::Debug,
+    ptr,
+    ptr::addr_of,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use hashbrown::{hash_map::Entry, HashMap};
 use libafl::{inputs::UsesInput, observers::VariableLengthMapObserver, HasMetadata};
@@ -102,6 +109,9 @@ pub trait EdgeCoverageVariant<AF, PF>: 'static + Debug {
     {
         panic!("Func no hitcount is not supported.")
     }
+
+    /// Called before each run
+    fn pre_exec(&mut self) {}
 }
 
 #[derive(Debug)]
@@ -354,6 +364,73 @@ impl StdEdgeCoverageChildModule {
     }
 }
 
+/// Edge coverage salted with the index of the guest thread, so that the coverage of concurrently running threads
+/// does not interleave in the same map entries.
+///
+/// Threads are numbered in the order in which they first hit an edge in each run. The first thread is not salted,
+/// so single-threaded targets get the same coverage as with the [`EdgeCoverageChildVariant`].
+/// Meant for multithreaded usermode targets.
+#[derive(Debug)]
+pub struct EdgeCoverageThreadVariant;
+pub type StdEdgeCoverageThreadModule =
+    EdgeCoverageModule<StdAddressFilter, StdPageFilter, EdgeCoverageThreadVariant>;
+pub type StdEdgeCoverageThreadModuleBuilder =
+    EdgeCoverageModuleBuilder<StdAddressFilter, StdPageFilter, EdgeCoverageThreadVariant, false>;
+
+impl<AF, PF> EdgeCoverageVariant<AF, PF> for EdgeCoverageThreadVariant {
+    const DO_SIDE_EFFECTS: bool = false;
+
+    fn fn_hitcount<ET, S>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>)
+    where
+        AF: AddressFilter,
+        ET: EmulatorModuleTuple<S>,
+        PF: PageFilter,
+        S: Unpin + UsesInput + HasMetadata,
+    {
+        emulator_modules.edges(
+            Hook::Function(gen_thread_edge_ids::<AF, ET, PF, S>),
+            Hook::Raw(trace_edge_hitcount_thread),
+        );
+    }
+
+    fn fn_no_hitcount<ET, S>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>)
+    where
+        AF: AddressFilter,
+        ET: EmulatorModuleTuple<S>,
+        PF: PageFilter,
+        S: Unpin + UsesInput + HasMetadata,
+    {
+        emulator_modules.edges(
+            Hook::Function(gen_thread_edge_ids::<AF, ET, PF, S>),
+            Hook::Raw(trace_edge_single_thread),
+        );
+    }
+
+    fn pre_exec(&mut self) {
+        NEXT_THREAD_INDEX.store(0, Ordering::Relaxed);
+        THREAD_GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Default for StdEdgeCoverageThreadModuleBuilder {
+    fn default() -> Self {
+        Self {
+            variant: EdgeCoverageThreadVariant,
+            address_filter: StdAddressFilter::default(),
+            page_filter: StdPageFilter::default(),
+            use_hitcounts: true,
+            use_jit: false,
+        }
+    }
+}
+
+impl StdEdgeCoverageThreadModule {
+    #[must_use]
+    pub fn builder() -> StdEdgeCoverageThreadModuleBuilder {
+        EdgeCoverageModuleBuilder::default()
+    }
+}
+
 #[derive(Debug)]
 pub struct EdgeCoverageModuleBuilder<AF, PF, V, const IS_INITIALIZED: bool> {
     variant: V,
@@ -561,6 +638,17 @@ where
         }
     }
 
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.variant.pre_exec();
+    }
+
     fn address_filter(&self) -> &Self::ModuleAddressFilter {
         &self.address_filter
     }
@@ -800,3 +888,71 @@ pub unsafe extern "C" fn trace_block_transition_single(_: *const (), id: u64) {
         });
     }
 }
+
+/// Incremented before each run, invalidating the thread indexes of the previous run
+static THREAD_GENERATION: AtomicU64 = AtomicU64::new(0);
+/// The index of the next thread hitting an edge in this run
+static NEXT_THREAD_INDEX: AtomicU64 = AtomicU64::new(0);
+thread_local!(static THREAD_SALT: Cell<(u64, u64)> = const { Cell::new((u64::MAX, 0)) });
+
+/// The salt of the current guest thread, see [`EdgeCoverageThreadVariant`]
+fn thread_salt() -> u64 {
+    let generation = THREAD_GENERATION.load(Ordering::Relaxed);
+    THREAD_SALT.with(|salt| {
+        let (salt_generation, value) = salt.get();
+        if salt_generation == generation {
+            return value;
+        }
+        let index = NEXT_THREAD_INDEX.fetch_add(1, Ordering::Relaxed);
+        let value = if index == 0 { 0 } else { hash_me(index) };
+        salt.set((generation, value));
+        value
+    })
+}
+
+pub fn gen_thread_edge_ids<AF, ET, PF, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    state: Option<&mut S>,
+    src: GuestAddr,
+    dest: GuestAddr,
+) -> Option<u64>
+where
+    AF: AddressFilter,
+    ET: EmulatorModuleTuple<S>,
+    PF: PageFilter,
+    S: Unpin + UsesInput + HasMetadata,
+{
+    let id = gen_hashed_edge_ids::<AF, ET, PF, S, EdgeCoverageThreadVariant>(
+        emulator_modules,
+        state,
+        src,
+        dest,
+    );
+    // Salted ids may hit any entry, so the whole map is in use
+    unsafe {
+        assert_ne!(*addr_of!(LIBAFL_QEMU_EDGES_MAP_SIZE_PTR), ptr::null_mut());
+        *LIBAFL_QEMU_EDGES_MAP_SIZE_PTR = LIBAFL_QEMU_EDGES_MAP_ALLOCATED_SIZE;
+    }
+    id
+}
+
+/// # Safety
+/// Increases the salted id at `EDGES_MAP_PTR` - potentially racey if called concurrently.
+pub unsafe extern "C" fn trace_edge_hitcount_thread(_: *const (), id: u64) {
+    unsafe {
+        let idx = ((id ^ thread_salt()) as usize) & LIBAFL_QEMU_EDGES_MAP_MASK_MAX;
+        let ptr = LIBAFL_QEMU_EDGES_MAP_PTR.add(idx);
+        *ptr = (*ptr).wrapping_add(1);
+    }
+}
+
+/// # Safety
+/// Fine.
+/// Worst case we set the byte to 1 multiple times.
+pub unsafe extern "C" fn trace_edge_single_thread(_: *const (), id: u64) {
+    unsafe {
+        let idx = ((id ^ thread_salt()) as usize) & LIBAFL_QEMU_EDGES_MAP_MASK_MAX;
+        let ptr = LIBAFL_QEMU_EDGES_MAP_PTR.add(idx);
+        *ptr = 1;
+    }
+}
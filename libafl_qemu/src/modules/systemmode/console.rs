@@ -0,0 +1,228 @@
+//! Capture the console output of the guest, for output-based feedbacks and crash reports.
+//!
+//! The [`ConsoleCaptureModule`] reads what the emulated devices wrote to their character devices during each
+//! run, and passes it to a [`StdOutObserver`]. Route the consoles of the firmware to files, with `append=on` so
+//! the module can truncate them between runs:
+//! - a PL011 or 16550 UART, with `-chardev file,id=uart,path=uart.log,append=on -serial chardev:uart`
+//! - ARM semihosting, with `-chardev file,id=semi,path=semi.log,append=on -semihosting-config enable=on,chardev=semi`
+//!
+//! Store the output in the testcases with a [`libafl::feedbacks::stdio::StdOutToMetadataFeedback`], or add the
+//! panic logs of crashing inputs to their [`libafl::feedbacks::CrashBundleFeedback`].
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    ptr::addr_of_mut,
+};
+
+use libafl::{
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{ObserversTuple, StdOutObserver},
+};
+use libafl_bolts::tuples::{Handle, Handled, MatchNameRef};
+
+use crate::modules::{
+    EmulatorModule, EmulatorModuleTuple, EmulatorModules, NopAddressFilter, NopPageFilter,
+    NOP_ADDRESS_FILTER, NOP_PAGE_FILTER,
+};
+
+/// The default maximum number of console bytes captured per run
+pub const DEFAULT_MAX_CONSOLE_LEN: usize = 64 * 1024;
+
+/// The file backing a character device of the guest
+#[derive(Debug)]
+struct ChardevFile {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl ChardevFile {
+    fn new(path: PathBuf) -> Self {
+        Self { path, file: None }
+    }
+
+    /// The open file, opened on first use, as QEMU creates it at startup
+    fn file(&mut self) -> io::Result<&mut File> {
+        if self.file.is_none() {
+            self.file = Some(
+                OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(&self.path)?,
+            );
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    /// Drop the output of the previous runs
+    fn reset(&mut self) -> io::Result<()> {
+        self.file()?.set_len(0)
+    }
+
+    /// Append the output of this run to `buf`, up to `max_len` bytes in total
+    fn read_into(&mut self, buf: &mut Vec<u8>, max_len: usize) -> io::Result<()> {
+        let remaining = max_len.saturating_sub(buf.len()) as u64;
+        let file = self.file()?;
+        file.seek(SeekFrom::Start(0))?;
+        file.take(remaining).read_to_end(buf)?;
+        Ok(())
+    }
+}
+
+/// Passes the console output of each run to a [`StdOutObserver`].
+///
+/// The outputs of all character device files are concatenated, in the order they were added.
+#[derive(Debug)]
+pub struct ConsoleCaptureModule {
+    observer_handle: Handle<StdOutObserver>,
+    chardevs: Vec<ChardevFile>,
+    max_len: usize,
+}
+
+impl ConsoleCaptureModule {
+    /// Creates a new [`ConsoleCaptureModule`], without any character device yet
+    #[must_use]
+    pub fn new(observer: &StdOutObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            chardevs: Vec::new(),
+            max_len: DEFAULT_MAX_CONSOLE_LEN,
+        }
+    }
+
+    /// Captures the output written to the file backing a character device, opened with `append=on`
+    #[must_use]
+    pub fn with_chardev_file<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.chardevs
+            .push(ChardevFile::new(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Sets the maximum number of bytes captured per run, the rest of the output is dropped
+    #[must_use]
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// The console output of the current run
+    fn read_console(&mut self) -> Vec<u8> {
+        let mut output = Vec::new();
+        for chardev in &mut self.chardevs {
+            if let Err(err) = chardev.read_into(&mut output, self.max_len) {
+                log::warn!(
+                    "Cannot read the console at {}: {err}",
+                    chardev.path.display()
+                );
+            }
+        }
+        output
+    }
+}
+
+impl<S> EmulatorModule<S> for ConsoleCaptureModule
+where
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = NopAddressFilter;
+    type ModulePageFilter = NopPageFilter;
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        for chardev in &mut self.chardevs {
+            if let Err(err) = chardev.reset() {
+                log::warn!(
+                    "Cannot reset the console at {}: {err}",
+                    chardev.path.display()
+                );
+            }
+        }
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        observers: &mut OT,
+        _exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        let output = self.read_console();
+        if let Some(observer) = observers.get_mut(&self.observer_handle) {
+            observer.observe_stdout(&output);
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { addr_of_mut!(NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { addr_of_mut!(NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::OpenOptions, io::Write, path::Path};
+
+    use libafl::observers::StdOutObserver;
+
+    use super::ConsoleCaptureModule;
+
+    #[test]
+    fn test_console_capture() {
+        let dir = std::env::temp_dir();
+        let uart = dir.join(format!("libafl_qemu_uart_{}.log", std::process::id()));
+        let semi = dir.join(format!("libafl_qemu_semi_{}.log", std::process::id()));
+        let observer = StdOutObserver::new("console");
+        let mut module = ConsoleCaptureModule::new(&observer)
+            .with_chardev_file(&uart)
+            .with_chardev_file(&semi)
+            .with_max_len(8);
+
+        for chardev in &mut module.chardevs {
+            chardev.reset().unwrap();
+        }
+        let append = |path: &Path, data: &[u8]| {
+            let mut file = OpenOptions::new().append(true).open(path).unwrap();
+            file.write_all(data).unwrap();
+        };
+        append(&uart, b"boot\n");
+        append(&semi, b"panic!\n");
+        assert_eq!(module.read_console(), b"boot\npan");
+
+        for chardev in &mut module.chardevs {
+            chardev.reset().unwrap();
+        }
+        append(&semi, b"ok");
+        assert_eq!(module.read_console(), b"ok");
+
+        std::fs::remove_file(uart).unwrap();
+        std::fs::remove_file(semi).unwrap();
+    }
+}
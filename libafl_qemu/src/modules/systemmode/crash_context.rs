@@ -0,0 +1,225 @@
+//! Capture the registers and memory of the guest when the firmware crashes.
+//!
+//! The [`CrashContextModule`] fills a [`CrashContextObserver`] with the full register file of the guest,
+//! and windows of memory around the program counter, the stack pointer and the faulting address. Store it
+//! in the solutions with a [`libafl::feedbacks::CrashContextFeedback`], so firmware crashes can be triaged
+//! without re-running them under a debugger.
+
+use core::{mem::size_of, ptr::addr_of_mut};
+
+use libafl::{
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{AccessType, CrashContext, CrashContextObserver, MemoryWindow, ObserversTuple},
+};
+use libafl_bolts::tuples::{Handle, Handled, MatchNameRef};
+use libafl_qemu_sys::{GuestAddr, GuestReg};
+use strum::IntoEnumIterator;
+
+use crate::{
+    modules::{
+        EmulatorModule, EmulatorModuleTuple, EmulatorModules, NopAddressFilter, NopPageFilter,
+        NOP_ADDRESS_FILTER, NOP_PAGE_FILTER,
+    },
+    Regs, CPU,
+};
+
+/// The default number of bytes captured around each address
+pub const DEFAULT_WINDOW_SIZE: usize = 256;
+
+/// Captures the context of the guest when it reaches one of its crash or abort handlers.
+///
+/// The context is captured by instruction hooks on the given addresses, typically the fault handlers and
+/// `abort` of the firmware, before the breakpoint or the harness ends the run. If the run crashed without
+/// reaching one of them, the context is taken from the guest in `post_exec`, which is only meaningful if the
+/// harness did not restore a snapshot yet.
+///
+/// The context of a crash at a fault handler has the signal `SIGSEGV` if the faulting address is known, see
+/// [`CrashContextModule::with_fault_address_register`], else `SIGABRT`.
+#[derive(Debug)]
+pub struct CrashContextModule {
+    observer_handle: Handle<CrashContextObserver>,
+    crash_addrs: Vec<GuestAddr>,
+    window_size: usize,
+    fault_address_register: Option<GuestAddr>,
+    captured: Option<CrashContext>,
+}
+
+impl CrashContextModule {
+    /// Creates a new [`CrashContextModule`] capturing the context at the given crash or abort handlers
+    #[must_use]
+    pub fn new(observer: &CrashContextObserver, crash_addrs: Vec<GuestAddr>) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            crash_addrs,
+            window_size: DEFAULT_WINDOW_SIZE,
+            fault_address_register: None,
+            captured: None,
+        }
+    }
+
+    /// Sets the number of bytes captured around each address
+    #[must_use]
+    pub fn with_window_size(mut self, window_size: usize) -> Self {
+        self.window_size = window_size;
+        self
+    }
+
+    /// Reads the faulting address from the memory-mapped register at `addr`, e.g. the `BFAR` of
+    /// Cortex-M CPUs at `0xE000ED38`
+    #[must_use]
+    pub fn with_fault_address_register(mut self, addr: GuestAddr) -> Self {
+        self.fault_address_register = Some(addr);
+        self
+    }
+
+    /// The addresses of the crash and abort handlers
+    #[must_use]
+    pub fn crash_addrs(&self) -> &[GuestAddr] {
+        &self.crash_addrs
+    }
+
+    /// Capture the context of the guest on `cpu`
+    #[allow(clippy::cast_possible_truncation, clippy::useless_conversion)]
+    fn capture(&self, cpu: &CPU) -> CrashContext {
+        let registers: Vec<(String, u64)> = Regs::iter()
+            .filter_map(|reg| {
+                let value = cpu.read_reg::<_, GuestReg>(reg).ok()?;
+                Some((format!("{reg:?}").to_lowercase(), u64::from(value)))
+            })
+            .collect();
+        let pc = cpu.read_reg::<_, GuestAddr>(Regs::Pc).ok();
+        let sp = cpu.read_reg::<_, GuestAddr>(Regs::Sp).ok();
+        let fault_addr = self.fault_address_register.and_then(|register| {
+            let mut buf = [0; size_of::<GuestAddr>()];
+            cpu.read_mem(register, &mut buf).ok()?;
+            Some(GuestAddr::from_le_bytes(buf))
+        });
+
+        let half = (self.window_size / 2) as GuestAddr;
+        let mut memory = Vec::new();
+        if let Some(pc) = pc {
+            memory.extend(self.window(cpu, "pc", pc.saturating_sub(half)));
+        }
+        if let Some(sp) = sp {
+            memory.extend(self.window(cpu, "sp", sp));
+        }
+        if let Some(fault_addr) = fault_addr {
+            memory.extend(self.window(cpu, "fault", fault_addr.saturating_sub(half)));
+        }
+
+        CrashContext {
+            signal: if fault_addr.is_some() {
+                libc::SIGSEGV
+            } else {
+                libc::SIGABRT
+            },
+            fault_addr: fault_addr.map(|addr| addr as usize),
+            access: AccessType::Unknown,
+            pc: pc.map(|pc| pc as usize),
+            sp: sp.map(|sp| sp as usize),
+            registers,
+            memory,
+        }
+    }
+
+    /// Read `window_size` bytes of guest memory at `addr`, `None` if it is not mapped
+    #[allow(clippy::cast_possible_truncation)]
+    fn window(&self, cpu: &CPU, label: &str, addr: GuestAddr) -> Option<MemoryWindow> {
+        let mut bytes = vec![0; self.window_size];
+        cpu.read_mem(addr, &mut bytes).ok()?;
+        Some(MemoryWindow {
+            label: label.to_string(),
+            addr: addr as usize,
+            bytes,
+        })
+    }
+}
+
+impl<S> EmulatorModule<S> for CrashContextModule
+where
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = NopAddressFilter;
+    type ModulePageFilter = NopPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        for addr in &self.crash_addrs {
+            emulator_modules.instruction_function(*addr, crash_context_hook::<ET, S>, true);
+        }
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.captured = None;
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        let captured = self.captured.take();
+        if *exit_kind != ExitKind::Crash {
+            return;
+        }
+        let context = captured.unwrap_or_else(|| {
+            let qemu = emulator_modules.qemu();
+            let cpu = qemu.current_cpu().unwrap_or_else(|| qemu.cpu_from_index(0));
+            self.capture(&cpu)
+        });
+        if let Some(observer) = observers.get_mut(&self.observer_handle) {
+            observer.set_context(Some(context));
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { addr_of_mut!(NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { addr_of_mut!(NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+pub fn crash_context_hook<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    _pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let qemu = emulator_modules.qemu();
+    let Some(cpu) = qemu.current_cpu() else {
+        return;
+    };
+    let module = emulator_modules.get_mut::<CrashContextModule>().unwrap();
+    // Keep the context of the first handler reached, e.g. a fault escalated to `abort`
+    if module.captured.is_none() {
+        module.captured = Some(module.capture(&cpu));
+    }
+}
@@ -0,0 +1,224 @@
+//! Inject interrupts into the guest at points chosen by the fuzzer.
+//!
+//! Firmware often only reaches its interrupt handlers when a peripheral or timer fires, which, in an
+//! emulator, depends on the virtual clock and thus on wall-clock behavior. The
+//! [`InterruptInjectionModule`] instead raises interrupts after a number of executed blocks, taken from
+//! dedicated bytes at the end of each input, so the interrupt handling paths become reachable and runs
+//! stay reproducible. Timer interrupts are injected like any other line, by raising the timer's interrupt.
+
+use core::ptr::addr_of_mut;
+
+use libafl::inputs::{HasTargetBytes, UsesInput};
+use libafl_bolts::AsSlice;
+
+use crate::{
+    modules::{
+        EmulatorModule, EmulatorModuleTuple, EmulatorModules, NopAddressFilter, NopPageFilter,
+        NOP_ADDRESS_FILTER, NOP_PAGE_FILTER,
+    },
+    qemu::Hook,
+};
+
+/// The number of input bytes describing one interrupt, see [`InterruptSchedule::from_bytes`]
+pub const INTERRUPT_EVENT_LEN: usize = 3;
+
+/// An interrupt line the [`InterruptInjectionModule`] can raise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptLine {
+    /// Raise the CPU interrupt request lines in the mask, see [`crate::CPU::interrupt`]
+    Cpu(i32),
+    /// Set the external interrupt pending in the NVIC of a Cortex-M CPU, see [`crate::CPU::set_nvic_pending`]
+    #[cfg(cpu_target = "arm")]
+    Nvic(u32),
+}
+
+/// When to raise which interrupt line during a run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterruptSchedule {
+    /// The number of executed blocks after which to raise the line, and the index of the line, sorted by blocks
+    events: Vec<(u64, usize)>,
+}
+
+impl InterruptSchedule {
+    /// Parse a schedule of interrupts among `lines` lines.
+    ///
+    /// Each [`INTERRUPT_EVENT_LEN`] bytes are one interrupt: the number of blocks since the previous interrupt,
+    /// as a little-endian `u16`, and the line, modulo `lines`. Trailing bytes are ignored.
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8], lines: usize) -> Self {
+        if lines == 0 {
+            return Self::default();
+        }
+        let mut blocks = 0;
+        let events = bytes
+            .chunks_exact(INTERRUPT_EVENT_LEN)
+            .map(|event| {
+                blocks += u64::from(u16::from_le_bytes([event[0], event[1]]));
+                (blocks, usize::from(event[2]) % lines)
+            })
+            .collect();
+        Self { events }
+    }
+
+    /// The interrupts, as the number of executed blocks after which to raise them, and the index of the line
+    #[must_use]
+    pub fn events(&self) -> &[(u64, usize)] {
+        &self.events
+    }
+
+    /// If no interrupt is scheduled
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Raises interrupts after fuzzer-chosen numbers of executed blocks.
+///
+/// The last `schedule_len` bytes of each input are the [`InterruptSchedule`]; the harness should only pass
+/// the rest of the input, from [`InterruptInjectionModule::payload`], to the target.
+#[derive(Debug)]
+pub struct InterruptInjectionModule {
+    lines: Vec<InterruptLine>,
+    schedule_len: usize,
+    schedule: InterruptSchedule,
+    next_event: usize,
+    blocks: u64,
+}
+
+impl InterruptInjectionModule {
+    /// Create a new [`InterruptInjectionModule`] raising the given `lines`, reading up to `max_interrupts`
+    /// interrupts from the end of each input
+    #[must_use]
+    pub fn new(lines: Vec<InterruptLine>, max_interrupts: usize) -> Self {
+        Self {
+            lines,
+            schedule_len: max_interrupts * INTERRUPT_EVENT_LEN,
+            schedule: InterruptSchedule::default(),
+            next_event: 0,
+            blocks: 0,
+        }
+    }
+
+    /// The interrupt lines of this module
+    #[must_use]
+    pub fn lines(&self) -> &[InterruptLine] {
+        &self.lines
+    }
+
+    /// The interrupts of the current run
+    #[must_use]
+    pub fn schedule(&self) -> &InterruptSchedule {
+        &self.schedule
+    }
+
+    /// Split the bytes of an input into the payload for the target and the interrupt schedule
+    #[must_use]
+    pub fn split_input<'a>(&self, bytes: &'a [u8]) -> (&'a [u8], &'a [u8]) {
+        bytes.split_at(bytes.len().saturating_sub(self.schedule_len))
+    }
+
+    /// The bytes of an input to pass to the target, without the interrupt schedule
+    #[must_use]
+    pub fn payload<'a>(&self, bytes: &'a [u8]) -> &'a [u8] {
+        self.split_input(bytes).0
+    }
+}
+
+impl<S> EmulatorModule<S> for InterruptInjectionModule
+where
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    type ModuleAddressFilter = NopAddressFilter;
+    type ModulePageFilter = NopPageFilter;
+
+    fn init_module<ET>(&self, emulator_modules: &mut EmulatorModules<ET, S>)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.blocks(
+            Hook::Empty,
+            Hook::Empty,
+            Hook::Function(interrupt_injection_block_exec::<ET, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        let bytes = input.target_bytes();
+        let (_, schedule) = self.split_input(bytes.as_slice());
+        self.schedule = InterruptSchedule::from_bytes(schedule, self.lines.len());
+        self.next_event = 0;
+        self.blocks = 0;
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { addr_of_mut!(NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { addr_of_mut!(NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+pub fn interrupt_injection_block_exec<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    let qemu = emulator_modules.qemu();
+    let module = emulator_modules
+        .get_mut::<InterruptInjectionModule>()
+        .unwrap();
+    module.blocks += 1;
+
+    while let Some(&(blocks, line)) = module.schedule.events.get(module.next_event) {
+        if blocks > module.blocks {
+            break;
+        }
+        module.next_event += 1;
+        let Some(cpu) = qemu.current_cpu() else {
+            continue;
+        };
+        match module.lines[line] {
+            InterruptLine::Cpu(mask) => cpu.interrupt(mask),
+            #[cfg(cpu_target = "arm")]
+            InterruptLine::Nvic(irq) => {
+                if !cpu.set_nvic_pending(irq) {
+                    log::warn!("Cannot inject IRQ {irq}, the CPU has no NVIC");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InterruptSchedule;
+
+    #[test]
+    fn test_interrupt_schedule() {
+        let schedule = InterruptSchedule::from_bytes(&[10, 0, 1, 0, 1, 5, 0, 0], 2);
+        assert_eq!(schedule.events(), &[(10, 1), (266, 1)]);
+        assert!(InterruptSchedule::from_bytes(&[10, 0, 1], 0).is_empty());
+    }
+}
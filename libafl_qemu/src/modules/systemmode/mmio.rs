@@ -0,0 +1,497 @@
+//! Fake MMIO peripherals, whose registers read values drawn from the fuzzing input.
+//!
+//! To fuzz firmware without writing a QEMU device model for each of its peripherals, the
+//! [`MmioFuzzModule`] maps [`MmioRegion`]`s` over the peripherals' address ranges: writes are ignored,
+//! and each read returns a value following the [`MmioPolicy`] of the register, usually the next bytes of
+//! the current input, as in firmware re-hosting. The regions are registered in the system memory on the
+//! first run, with a higher priority than the regions of the machine.
+//!
+//! Drivers and network firmware also read the data of their peripherals from DMA buffers in RAM. A
+//! [`DmaBuffer`] is refilled from the input when its [`DmaTrigger`] fires: when the guest executes an
+//! address, e.g. the function handling a receive interrupt, or writes a doorbell register of a fake region.
+
+use core::{
+    ffi::{c_uint, c_void},
+    mem::MaybeUninit,
+    ptr::{addr_of_mut, null_mut},
+};
+use std::{collections::BTreeMap, ffi::CString};
+
+use hashbrown::HashMap;
+use libafl::inputs::{HasTargetBytes, UsesInput};
+use libafl_bolts::AsSlice;
+use libafl_qemu_sys::{
+    device_endian_DEVICE_NATIVE_ENDIAN, get_system_memory, hwaddr,
+    memory_region_add_subregion_overlap, memory_region_init_io, GuestAddr, GuestPhysAddr,
+    MemoryRegion, MemoryRegionOps,
+};
+
+use crate::{
+    modules::{
+        EmulatorModule, EmulatorModuleTuple, EmulatorModules, NopAddressFilter, NopPageFilter,
+        NOP_ADDRESS_FILTER, NOP_PAGE_FILTER,
+    },
+    Qemu,
+};
+
+/// The priority of the fake regions over the regions of the machine
+const MMIO_REGION_PRIORITY: i32 = 1;
+
+/// How a register of an [`MmioRegion`] answers reads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MmioPolicy {
+    /// Always read the same value, e.g. for a status register the firmware polls until it is ready
+    Fixed(u64),
+    /// Read the values in turn, then the last one forever
+    Sequence(Vec<u64>),
+    /// Read the next bytes of the input, as many as the access size, in little endian.
+    ///
+    /// Once the input is exhausted, reads return `0`.
+    FromInput,
+    /// Read one of the values, chosen by the next byte of the input, e.g. for the valid states of a register
+    ChoiceFromInput(Vec<u64>),
+}
+
+/// A fake MMIO peripheral
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MmioRegion {
+    name: String,
+    base: GuestPhysAddr,
+    size: u64,
+    default: MmioPolicy,
+    registers: BTreeMap<u64, MmioPolicy>,
+}
+
+impl MmioRegion {
+    /// Create a new [`MmioRegion`] of `size` bytes at the physical address `base`, whose registers read
+    /// from the input
+    #[must_use]
+    pub fn new<N>(name: N, base: GuestPhysAddr, size: u64) -> Self
+    where
+        N: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            base,
+            size,
+            default: MmioPolicy::FromInput,
+            registers: BTreeMap::new(),
+        }
+    }
+
+    /// Set the policy of the registers without their own policy
+    #[must_use]
+    pub fn with_default(mut self, policy: MmioPolicy) -> Self {
+        self.default = policy;
+        self
+    }
+
+    /// Set the policy of the register at `offset` in the region
+    #[must_use]
+    pub fn with_register(mut self, offset: u64, policy: MmioPolicy) -> Self {
+        self.registers.insert(offset, policy);
+        self
+    }
+
+    /// The name of the region
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The physical address of the region
+    #[must_use]
+    pub fn base(&self) -> GuestPhysAddr {
+        self.base
+    }
+
+    /// The size of the region, in bytes
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The policy of the register at `offset` in the region
+    #[must_use]
+    pub fn policy(&self, offset: u64) -> &MmioPolicy {
+        self.registers.get(&offset).unwrap_or(&self.default)
+    }
+}
+
+/// When a [`DmaBuffer`] is refilled from the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DmaTrigger {
+    /// Before the guest executes the instruction at this address
+    Address(GuestAddr),
+    /// When the guest writes the register at `offset` of the fake [`MmioRegion`] named `region`
+    Doorbell {
+        /// The name of the region
+        region: String,
+        /// The offset of the register in the region
+        offset: u64,
+    },
+}
+
+/// A guest DMA buffer, refilled from the input each time its trigger fires
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DmaBuffer {
+    base: GuestPhysAddr,
+    len: usize,
+    trigger: DmaTrigger,
+}
+
+impl DmaBuffer {
+    /// Create a new [`DmaBuffer`] of `len` bytes at the physical address `base`
+    #[must_use]
+    pub fn new(base: GuestPhysAddr, len: usize, trigger: DmaTrigger) -> Self {
+        Self { base, len, trigger }
+    }
+
+    /// The physical address of the buffer
+    #[must_use]
+    pub fn base(&self) -> GuestPhysAddr {
+        self.base
+    }
+
+    /// The length of the buffer, in bytes
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// If the buffer is empty
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// When the buffer is refilled
+    #[must_use]
+    pub fn trigger(&self) -> &DmaTrigger {
+        &self.trigger
+    }
+
+    /// Refill the buffer with the next bytes of the input, padded with zeros once it is exhausted
+    fn fill(&self, input: &mut MmioInput) {
+        let mut data = vec![0; self.len];
+        input.take_into(&mut data);
+        let Some(qemu) = Qemu::get() else {
+            return;
+        };
+        unsafe { qemu.write_phys_mem(self.base, &data) };
+    }
+}
+
+/// The input of the current run, consumed by the reads of the fake regions
+#[derive(Debug, Default)]
+struct MmioInput {
+    bytes: Vec<u8>,
+    cursor: usize,
+    /// The position of each [`MmioPolicy::Sequence`] register, by region and offset
+    positions: HashMap<(usize, u64), usize>,
+}
+
+impl MmioInput {
+    fn reset(&mut self, bytes: &[u8]) {
+        self.bytes.clear();
+        self.bytes.extend_from_slice(bytes);
+        self.cursor = 0;
+        self.positions.clear();
+    }
+
+    /// Fill `buf` with the next bytes of the input, padded with zeros once exhausted
+    fn take_into(&mut self, buf: &mut [u8]) {
+        let start = self.cursor.min(self.bytes.len());
+        let end = (self.cursor + buf.len()).min(self.bytes.len());
+        buf[..end - start].copy_from_slice(&self.bytes[start..end]);
+        buf[end - start..].fill(0);
+        self.cursor += buf.len();
+    }
+
+    /// Take the next `len` bytes of the input, in little endian, padded with zeros once exhausted
+    fn take(&mut self, len: usize) -> u64 {
+        let mut value = [0; 8];
+        self.take_into(&mut value[..len]);
+        u64::from_le_bytes(value)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn read(&mut self, region: usize, offset: u64, policy: &MmioPolicy, size: usize) -> u64 {
+        let size = size.clamp(1, 8);
+        let value = match policy {
+            MmioPolicy::Fixed(value) => *value,
+            MmioPolicy::Sequence(values) => {
+                let position = self.positions.entry((region, offset)).or_insert(0);
+                let value = values.get(*position).or(values.last()).copied();
+                *position += 1;
+                value.unwrap_or(0)
+            }
+            MmioPolicy::FromInput => self.take(size),
+            MmioPolicy::ChoiceFromInput(values) => {
+                if values.is_empty() {
+                    0
+                } else {
+                    values[self.take(1) as usize % values.len()]
+                }
+            }
+        };
+        if size < 8 {
+            value & ((1 << (size * 8)) - 1)
+        } else {
+            value
+        }
+    }
+}
+
+/// What QEMU passes to the callbacks of a fake region
+#[derive(Debug)]
+struct MmioRegionContext {
+    input: *mut MmioInput,
+    index: usize,
+    region: MmioRegion,
+    /// The DMA buffers refilled on writes, by register offset
+    doorbells: Vec<(u64, DmaBuffer)>,
+}
+
+unsafe extern "C" fn mmio_read(opaque: *mut c_void, addr: hwaddr, size: c_uint) -> u64 {
+    let ctx = &*(opaque as *const MmioRegionContext);
+    let input = &mut *ctx.input;
+    input.read(ctx.index, addr, ctx.region.policy(addr), size as usize)
+}
+
+unsafe extern "C" fn mmio_write(opaque: *mut c_void, addr: hwaddr, _data: u64, _size: c_uint) {
+    let ctx = &*(opaque as *const MmioRegionContext);
+    let input = &mut *ctx.input;
+    for (_, dma) in ctx.doorbells.iter().filter(|(offset, _)| *offset == addr) {
+        dma.fill(input);
+    }
+}
+
+/// Maps fake MMIO peripherals, reading from the input, over the guest's physical memory.
+///
+/// The bytes of the input are consumed in the order the firmware reads the registers.
+#[doc(alias = "QemuMmioFuzzHelper")]
+#[derive(Debug)]
+pub struct MmioFuzzModule {
+    regions: Vec<MmioRegion>,
+    dma_buffers: Vec<DmaBuffer>,
+    input: Box<MmioInput>,
+    registered: bool,
+}
+
+impl MmioFuzzModule {
+    /// Create a new [`MmioFuzzModule`] without regions
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+            dma_buffers: Vec::new(),
+            input: Box::default(),
+            registered: false,
+        }
+    }
+
+    /// Add a fake region, before the first run
+    #[must_use]
+    pub fn with_region(mut self, region: MmioRegion) -> Self {
+        self.regions.push(region);
+        self
+    }
+
+    /// Add a DMA buffer, before the first run.
+    ///
+    /// The region of a [`DmaTrigger::Doorbell`] has to be added with [`MmioFuzzModule::with_region`].
+    #[must_use]
+    pub fn with_dma(mut self, dma: DmaBuffer) -> Self {
+        self.dma_buffers.push(dma);
+        self
+    }
+
+    /// The DMA buffers
+    #[must_use]
+    pub fn dma_buffers(&self) -> &[DmaBuffer] {
+        &self.dma_buffers
+    }
+
+    /// The fake regions
+    #[must_use]
+    pub fn regions(&self) -> &[MmioRegion] {
+        &self.regions
+    }
+
+    /// The number of input bytes the reads consumed in this run
+    #[must_use]
+    pub fn consumed(&self) -> usize {
+        self.input.cursor.min(self.input.bytes.len())
+    }
+
+    /// If the reads consumed the whole input in this run
+    #[must_use]
+    pub fn is_exhausted(&self) -> bool {
+        self.input.cursor >= self.input.bytes.len()
+    }
+
+    /// Register the regions in the system memory of QEMU, and hook the addresses triggering DMA.
+    ///
+    /// The regions, and their callbacks, stay alive as long as QEMU runs.
+    fn register<ET, S>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>)
+    where
+        ET: EmulatorModuleTuple<S>,
+        S: Unpin + UsesInput,
+        S::Input: HasTargetBytes,
+    {
+        for dma in &self.dma_buffers {
+            match &dma.trigger {
+                DmaTrigger::Address(addr) => {
+                    // The block containing the address may be translated already, e.g. by the boot code
+                    emulator_modules.instruction_function(*addr, dma_address_hook::<ET, S>, true);
+                }
+                DmaTrigger::Doorbell { region, .. } => assert!(
+                    self.regions.iter().any(|r| r.name == *region),
+                    "No MMIO region {region} for the DMA doorbell"
+                ),
+            }
+        }
+
+        let input: *mut MmioInput = &mut *self.input;
+        let ops: &'static mut MemoryRegionOps =
+            Box::leak(Box::new(unsafe { MaybeUninit::zeroed().assume_init() }));
+        ops.read = Some(mmio_read);
+        ops.write = Some(mmio_write);
+        ops.endianness = device_endian_DEVICE_NATIVE_ENDIAN;
+        let ops: &'static MemoryRegionOps = ops;
+
+        for (index, region) in self.regions.iter().enumerate() {
+            let name = CString::new(region.name.as_str()).expect("Invalid MMIO region name");
+            let ctx = Box::leak(Box::new(MmioRegionContext {
+                input,
+                index,
+                region: region.clone(),
+                doorbells: self
+                    .dma_buffers
+                    .iter()
+                    .filter_map(|dma| match &dma.trigger {
+                        DmaTrigger::Doorbell {
+                            region: name,
+                            offset,
+                        } if *name == region.name => Some((*offset, dma.clone())),
+                        _ => None,
+                    })
+                    .collect(),
+            }));
+            // QEMU initializes the object in place
+            let mr: &'static mut MemoryRegion =
+                Box::leak(Box::new(unsafe { MaybeUninit::zeroed().assume_init() }));
+            unsafe {
+                memory_region_init_io(
+                    mr,
+                    null_mut(),
+                    ops,
+                    (ctx as *mut MmioRegionContext).cast(),
+                    name.as_ptr(),
+                    region.size,
+                );
+                memory_region_add_subregion_overlap(
+                    get_system_memory(),
+                    region.base,
+                    mr,
+                    MMIO_REGION_PRIORITY,
+                );
+            }
+        }
+        self.registered = true;
+    }
+}
+
+impl Default for MmioFuzzModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> EmulatorModule<S> for MmioFuzzModule
+where
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    type ModuleAddressFilter = NopAddressFilter;
+    type ModulePageFilter = NopPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        if !self.registered {
+            self.register(emulator_modules);
+        }
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.input.reset(input.target_bytes().as_slice());
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { addr_of_mut!(NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { addr_of_mut!(NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+pub fn dma_address_hook<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    let module = emulator_modules.get_mut::<MmioFuzzModule>().unwrap();
+    for dma in &module.dma_buffers {
+        if dma.trigger == DmaTrigger::Address(pc) {
+            dma.fill(&mut module.input);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MmioInput, MmioPolicy, MmioRegion};
+
+    #[test]
+    fn test_mmio_policies() {
+        let region = MmioRegion::new("uart", 0x4000_0000, 0x100)
+            .with_register(0x0, MmioPolicy::Fixed(0x80))
+            .with_register(0x4, MmioPolicy::Sequence(vec![1, 2]))
+            .with_register(0x8, MmioPolicy::ChoiceFromInput(vec![7, 9]));
+        let mut input = MmioInput::default();
+        input.reset(&[0x11, 0x22, 0x33, 0x03]);
+
+        assert_eq!(input.read(0, 0x0, region.policy(0x0), 4), 0x80);
+        assert_eq!(input.read(0, 0x4, region.policy(0x4), 4), 1);
+        assert_eq!(input.read(0, 0x4, region.policy(0x4), 4), 2);
+        assert_eq!(input.read(0, 0x4, region.policy(0x4), 4), 2);
+        assert_eq!(input.read(0, 0xc, region.policy(0xc), 2), 0x2211);
+        assert_eq!(input.read(0, 0x8, region.policy(0x8), 4), 9);
+        // Exhausted after a partial read
+        assert_eq!(input.read(0, 0xc, region.policy(0xc), 4), 0x03);
+        assert_eq!(input.read(0, 0xc, region.policy(0xc), 1), 0);
+    }
+}
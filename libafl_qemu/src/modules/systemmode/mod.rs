@@ -1 +1,11 @@
+pub mod console;
+pub use console::ConsoleCaptureModule;
 
+pub mod crash_context;
+pub use crash_context::CrashContextModule;
+
+pub mod interrupts;
+pub use interrupts::{InterruptInjectionModule, InterruptLine, InterruptSchedule};
+
+pub mod mmio;
+pub use mmio::{DmaBuffer, DmaTrigger, MmioFuzzModule, MmioPolicy, MmioRegion};
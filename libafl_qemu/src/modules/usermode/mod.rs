@@ -17,3 +17,6 @@ pub use asan::{init_qemu_with_asan, AsanModule};
 pub mod asan_guest;
 #[cfg(not(cpu_target = "hexagon"))]
 pub use asan_guest::{init_qemu_with_asan_guest, AsanGuestModule};
+
+pub mod thread_sched;
+pub use thread_sched::{ThreadSchedulerModule, ThreadSchedulingPolicy};
@@ -0,0 +1,379 @@
+//! Serialize the scheduling of guest threads, so multithreaded targets behave (mostly) deterministically.
+//!
+//! In usermode, every guest thread is a host thread, and the host kernel decides how they interleave.
+//! The [`ThreadSchedulerModule`] passes a baton between the guest threads: only the thread holding it runs
+//! translated code, and the baton is handed over following the [`ThreadSchedulingPolicy`]. Syscalls always
+//! release the baton, as they may block on another guest thread.
+//!
+//! The next holder is the runnable thread with the next higher thread id, wrapping around, not the thread that
+//! happened to ask first. Threads created with `clone` are runnable right away, so the baton waits for them even
+//! if the host did not start them yet.
+
+use std::{
+    cell::Cell,
+    collections::BTreeSet,
+    num::NonZeroUsize,
+    path::Path,
+    ptr::addr_of_mut,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+};
+
+use libafl::inputs::UsesInput;
+use libafl_qemu_sys::GuestAddr;
+use libc::pid_t;
+
+use crate::{
+    emu::EmulatorModules,
+    modules::{EmulatorModule, EmulatorModuleTuple, NopAddressFilter, NOP_ADDRESS_FILTER},
+    qemu::{Hook, SyscallHookResult},
+    SYS_clone, SYS_clone3,
+};
+
+/// When a guest thread hands the baton over to the next one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadSchedulingPolicy {
+    /// Switch threads after `quantum` executed blocks, and at each syscall
+    RoundRobin {
+        /// The number of blocks a thread may execute before it yields
+        quantum: NonZeroUsize,
+    },
+    /// Only switch threads at syscalls, i.e. run each thread until it (potentially) blocks.
+    ///
+    /// Guest threads spinning on a lock held by another thread, without ever doing a syscall, hang forever.
+    RunToBlock,
+}
+
+/// The baton, shared by all guest threads
+#[derive(Debug)]
+struct Baton {
+    /// The thread holding the baton, or the thread it is reserved for
+    owner: Option<pid_t>,
+    /// The threads running translated code, or about to: the candidates for the baton
+    runnable: BTreeSet<pid_t>,
+    /// Bumped for each run, to invalidate the batons of the previous one
+    generation: u64,
+}
+
+impl Baton {
+    /// The runnable thread after `tid`, in the order of their ids, wrapping around
+    fn next_after(&self, tid: pid_t) -> Option<pid_t> {
+        self.runnable
+            .range(tid.saturating_add(1)..)
+            .next()
+            .or_else(|| self.runnable.first())
+            .copied()
+    }
+}
+
+/// Passes the baton between the guest threads
+#[derive(Debug)]
+struct Scheduler {
+    baton: Mutex<Baton>,
+    condvar: Condvar,
+}
+
+impl Scheduler {
+    const fn new() -> Self {
+        Self {
+            baton: Mutex::new(Baton {
+                owner: None,
+                runnable: BTreeSet::new(),
+                generation: 0,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Waits for the baton, returning the generation it was acquired in
+    fn acquire(&self, me: pid_t) -> u64 {
+        let mut baton = self.baton.lock().unwrap();
+        loop {
+            baton.runnable.insert(me);
+            if baton.owner.is_none() {
+                baton.owner = Some(me);
+            }
+            let generation = baton.generation;
+            baton = self
+                .condvar
+                .wait_while(baton, |baton| {
+                    baton.generation == generation && baton.owner != Some(me)
+                })
+                .unwrap();
+            if baton.generation == generation {
+                return generation;
+            }
+            // The run ended while we waited, queue up in the next one
+        }
+    }
+
+    /// Registers a thread that will run soon, e.g. right after it was created
+    fn spawned(&self, tid: pid_t) {
+        self.baton.lock().unwrap().runnable.insert(tid);
+    }
+
+    /// Hands the baton over to the next runnable thread. If `runnable` is false, this thread will not run
+    /// translated code until it calls [`Self::acquire`] again, e.g. because it does a syscall.
+    fn hand_over(&self, me: pid_t, generation: u64, runnable: bool) {
+        let mut baton = self.baton.lock().unwrap();
+        if baton.generation != generation || baton.owner != Some(me) {
+            return;
+        }
+        if !runnable {
+            baton.runnable.remove(&me);
+        }
+        baton.owner = baton.next_after(me);
+        self.condvar.notify_all();
+    }
+
+    /// Forgets all threads of the previous run, waking up the ones still waiting for the baton
+    fn reset(&self) -> u64 {
+        let mut baton = self.baton.lock().unwrap();
+        baton.owner = None;
+        baton.runnable.clear();
+        baton.generation += 1;
+        self.condvar.notify_all();
+        baton.generation
+    }
+}
+
+static SCHEDULER: Scheduler = Scheduler::new();
+/// The generation of [`SCHEDULER`], to check if a thread holds the baton without taking the lock
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+/// The number of blocks a thread may execute before it yields, `usize::MAX` to only yield at syscalls
+static QUANTUM: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+thread_local! {
+    /// The generation of the baton held by this thread, if any
+    static HELD_GENERATION: Cell<Option<u64>> = const { Cell::new(None) };
+    /// The number of blocks this thread may still execute before yielding
+    static REMAINING_QUANTUM: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The id of the current thread, the same for the guest and the host
+fn current_tid() -> pid_t {
+    // # Safety
+    // Always successful
+    unsafe { libc::gettid() }
+}
+
+fn held_generation() -> Option<u64> {
+    HELD_GENERATION
+        .get()
+        .filter(|generation| *generation == GENERATION.load(Ordering::Acquire))
+}
+
+/// Lets only one guest thread run at a time, and switches between them in a deterministic order.
+///
+/// This does not make the target fully deterministic, since the outcome of syscalls (and thus the time
+/// at which a thread blocks) still depends on the host, but it removes most of the scheduling noise from
+/// coverage and makes crashes of racy targets much easier to replay.
+#[derive(Debug)]
+pub struct ThreadSchedulerModule {
+    policy: ThreadSchedulingPolicy,
+}
+
+impl ThreadSchedulerModule {
+    /// Create a new [`ThreadSchedulerModule`] with the given policy
+    #[must_use]
+    pub fn new(policy: ThreadSchedulingPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Create a new [`ThreadSchedulerModule`] switching threads every `quantum` blocks
+    #[must_use]
+    pub fn round_robin(quantum: NonZeroUsize) -> Self {
+        Self::new(ThreadSchedulingPolicy::RoundRobin { quantum })
+    }
+
+    /// Create a new [`ThreadSchedulerModule`] switching threads only at syscalls
+    #[must_use]
+    pub fn run_to_block() -> Self {
+        Self::new(ThreadSchedulingPolicy::RunToBlock)
+    }
+
+    /// The policy of this module
+    #[must_use]
+    pub fn policy(&self) -> ThreadSchedulingPolicy {
+        self.policy
+    }
+
+    fn quantum(&self) -> usize {
+        match self.policy {
+            ThreadSchedulingPolicy::RoundRobin { quantum } => quantum.get(),
+            ThreadSchedulingPolicy::RunToBlock => usize::MAX,
+        }
+    }
+}
+
+impl<S> EmulatorModule<S> for ThreadSchedulerModule
+where
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = NopAddressFilter;
+
+    fn init_module<ET>(&self, emulator_modules: &mut EmulatorModules<ET, S>)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        QUANTUM.store(self.quantum(), Ordering::Release);
+        // Raw hooks, so that no thread borrows the emulator modules while it waits for the baton
+        emulator_modules.blocks(Hook::Empty, Hook::Empty, Hook::Raw(thread_sched_block_exec));
+        emulator_modules.syscalls(Hook::Raw(thread_sched_syscall));
+        emulator_modules.after_syscalls(Hook::Raw(thread_sched_after_syscall));
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        QUANTUM.store(self.quantum(), Ordering::Release);
+        GENERATION.store(SCHEDULER.reset(), Ordering::Release);
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { addr_of_mut!(NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+pub extern "C" fn thread_sched_block_exec(_: *const (), _id: u64) {
+    let quantum = QUANTUM.load(Ordering::Acquire);
+    let me = current_tid();
+
+    let Some(generation) = held_generation() else {
+        HELD_GENERATION.set(Some(SCHEDULER.acquire(me)));
+        REMAINING_QUANTUM.set(quantum);
+        return;
+    };
+
+    let remaining = REMAINING_QUANTUM.get().saturating_sub(1);
+    if remaining == 0 {
+        // Let the next thread run, this returns right away if no other thread is runnable
+        SCHEDULER.hand_over(me, generation, true);
+        HELD_GENERATION.set(Some(SCHEDULER.acquire(me)));
+        REMAINING_QUANTUM.set(quantum);
+    } else {
+        REMAINING_QUANTUM.set(remaining);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn thread_sched_syscall(
+    _: *const (),
+    _sys_num: i32,
+    _a0: GuestAddr,
+    _a1: GuestAddr,
+    _a2: GuestAddr,
+    _a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> SyscallHookResult {
+    // The syscall may wait for another guest thread, or exit this one.
+    // The baton is taken again at the next executed block.
+    if let Some(generation) = HELD_GENERATION.take() {
+        SCHEDULER.hand_over(current_tid(), generation, false);
+    }
+    SyscallHookResult::new(None)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn thread_sched_after_syscall(
+    _: *const (),
+    result: GuestAddr,
+    sys_num: i32,
+    _a0: GuestAddr,
+    _a1: GuestAddr,
+    _a2: GuestAddr,
+    _a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> GuestAddr {
+    let sys_num = i64::from(sys_num);
+    if sys_num == SYS_clone || sys_num == SYS_clone3 {
+        // The new thread is runnable now, the host did not necessarily start it yet.
+        // Forked processes have their own scheduler, and do not show up in our tasks.
+        if let Ok(tid) = pid_t::try_from(result) {
+            if tid > 0 && Path::new(&format!("/proc/self/task/{tid}")).exists() {
+                SCHEDULER.spawned(tid);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use super::Scheduler;
+
+    #[test]
+    fn test_baton_order() {
+        let scheduler = Scheduler::new();
+        let generation = scheduler.reset();
+        assert_eq!(scheduler.acquire(20), generation);
+        // Spawned threads get the baton in the order of their ids, whenever they arrive
+        scheduler.spawned(30);
+        scheduler.spawned(10);
+
+        let order = Arc::new(Mutex::new(vec![]));
+        let scheduler = Arc::new(scheduler);
+        let handles: Vec<_> = [30, 10]
+            .into_iter()
+            .map(|tid| {
+                let scheduler = scheduler.clone();
+                let order = order.clone();
+                thread::spawn(move || {
+                    let generation = scheduler.acquire(tid);
+                    order.lock().unwrap().push(tid);
+                    scheduler.hand_over(tid, generation, false);
+                })
+            })
+            .collect();
+
+        scheduler.hand_over(20, generation, false);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), [30, 10]);
+        assert!(scheduler.baton.lock().unwrap().owner.is_none());
+    }
+
+    #[test]
+    fn test_reset_wakes_waiters() {
+        let scheduler = Arc::new(Scheduler::new());
+        let generation = scheduler.reset();
+        assert_eq!(scheduler.acquire(1), generation);
+
+        // Waits for thread 1, which never hands the baton over
+        let waiter = {
+            let scheduler = scheduler.clone();
+            thread::spawn(move || scheduler.acquire(2))
+        };
+        while !scheduler.baton.lock().unwrap().runnable.contains(&2) {
+            thread::yield_now();
+        }
+
+        // The next run starts, the waiter gets the baton of the new generation
+        let next_generation = scheduler.reset();
+        assert_eq!(waiter.join().unwrap(), next_generation);
+        assert_eq!(scheduler.baton.lock().unwrap().owner, Some(2));
+    }
+}
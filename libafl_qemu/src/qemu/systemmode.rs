@@ -140,6 +140,41 @@ impl CPU {
         }
     }
 
+    /// Raise the interrupt request lines in `mask`, a combination of the `CPU_INTERRUPT_*` flags of
+    /// `libafl_qemu_sys`, e.g. [`libafl_qemu_sys::CPU_INTERRUPT_HARD`]. The CPU takes the interrupt at
+    /// the end of the current translation block, if it is not masked.
+    pub fn interrupt(&self, mask: i32) {
+        unsafe { libafl_qemu_sys::cpu_interrupt(self.ptr, mask) };
+    }
+
+    /// Lower the interrupt request lines in `mask`, see [`CPU::interrupt`]
+    pub fn reset_interrupt(&self, mask: i32) {
+        unsafe { libafl_qemu_sys::cpu_reset_interrupt(self.ptr, mask) };
+    }
+
+    /// Set the external interrupt `irq` pending in the NVIC of a Cortex-M CPU.
+    ///
+    /// Returns `false` if the CPU has no NVIC, i.e. it is not an M-profile CPU.
+    #[cfg(cpu_target = "arm")]
+    pub fn set_nvic_pending(&self, irq: u32) -> bool {
+        /// The exception number of the first external interrupt
+        const NVIC_FIRST_IRQ: i32 = 16;
+
+        unsafe {
+            let arm_cpu = self.ptr.cast::<libafl_qemu_sys::ARMCPU>();
+            let nvic = (*arm_cpu).env.nvic;
+            if nvic.is_null() {
+                return false;
+            }
+            libafl_qemu_sys::armv7m_nvic_set_pending(
+                nvic.cast(),
+                NVIC_FIRST_IRQ + irq as i32,
+                false,
+            );
+        }
+        true
+    }
+
     /// Read a value from a guest address, taking into account the potential MMU / MPU.
     ///
     /// # Safety
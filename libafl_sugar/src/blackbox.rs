@@ -0,0 +1,212 @@
+//! A fully black-box fuzzer, for targets without instrumentation of any kind.
+//! Runs are only told apart by their exit status, their output, and their execution time,
+//! see [`libafl::feedbacks::BlackBoxFeedback`].
+use std::{fs, net::SocketAddr, path::PathBuf, time::Duration};
+
+use libafl::{
+    corpus::{CachedOnDiskCorpus, Corpus, OnDiskCorpus},
+    events::{launcher::Launcher, EventConfig, EventRestarter, LlmpRestartingEventManager},
+    executors::CommandExecutor,
+    feedback_or_fast,
+    feedbacks::{BlackBoxFeedback, CrashFeedback, TimeoutFeedback},
+    fuzzer::{Fuzzer, StdFuzzer},
+    generators::RandBytesGenerator,
+    monitors::MultiMonitor,
+    mutators::{
+        havoc_mutations::havoc_mutations,
+        scheduled::{tokens_mutations, StdScheduledMutator},
+        token_mutations::Tokens,
+    },
+    observers::{ExitStatusObserver, StdOutObserver, TimeObserver},
+    schedulers::QueueScheduler,
+    stages::StdMutationalStage,
+    state::{HasCorpus, StdState},
+    Error, HasMetadata,
+};
+use libafl_bolts::{
+    core_affinity::Cores,
+    nonzero,
+    rands::StdRand,
+    shmem::{ShMemProvider, StdShMemProvider},
+    tuples::{tuple_list, Handled, Merge},
+};
+use typed_builder::TypedBuilder;
+
+use crate::{load_initial_inputs, CORPUS_CACHE_SIZE, DEFAULT_TIMEOUT_SECS};
+
+/// The placeholder in the arguments for the file containing the input.
+/// Without it, the input is delivered via stdin.
+pub const INPUT_PLACEHOLDER: &str = "@@";
+
+/// Creates a black-box fuzzer, running the target as a plain child process.
+#[derive(Debug, TypedBuilder)]
+pub struct BlackBoxSugar<'a> {
+    /// Laucher configuration (default is random)
+    #[builder(default = None, setter(strip_option))]
+    configuration: Option<String>,
+    /// Timeout of the executor
+    #[builder(default = None)]
+    timeout: Option<u64>,
+    /// Input directories
+    input_dirs: &'a [PathBuf],
+    /// Output directory
+    output_dir: PathBuf,
+    /// Dictionary
+    #[builder(default = None)]
+    tokens_file: Option<PathBuf>,
+    #[builder(default = 1337_u16)]
+    broker_port: u16,
+    /// The list of cores to run on
+    cores: &'a Cores,
+    /// The `ip:port` address of another broker to connect our new broker to for multi-machine
+    /// clusters.
+    #[builder(default = None, setter(strip_option))]
+    remote_broker_addr: Option<SocketAddr>,
+    /// Path to program to execute
+    program: String,
+    /// Arguments of the program to execute, [`INPUT_PLACEHOLDER`] is replaced by the input file
+    arguments: &'a [String],
+    /// Tell runs apart by the bucket of their execution time, too.
+    /// Disable it for targets with a noisy execution time.
+    #[builder(default = true)]
+    time_buckets: bool,
+    /// Fuzz `iterations` number of times, instead of indefinitely; implies use of `fuzz_loop_for`
+    #[builder(default = None)]
+    iterations: Option<u64>,
+}
+
+impl BlackBoxSugar<'_> {
+    /// Runs the fuzzer.
+    #[allow(clippy::too_many_lines)]
+    pub fn run(&mut self) {
+        let conf = match self.configuration.as_ref() {
+            Some(name) => EventConfig::from_name(name),
+            None => EventConfig::AlwaysUnique,
+        };
+
+        let timeout = Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS));
+
+        let mut out_dir = self.output_dir.clone();
+        if fs::create_dir(&out_dir).is_err() {
+            log::info!("Out dir at {:?} already exists.", &out_dir);
+            assert!(
+                out_dir.is_dir(),
+                "Out dir at {:?} is not a valid directory!",
+                &out_dir
+            );
+        }
+        let mut crashes = out_dir.clone();
+        crashes.push("crashes");
+        out_dir.push("queue");
+
+        let shmem_provider = StdShMemProvider::new().expect("Failed to init shared memory");
+
+        let monitor = MultiMonitor::new(|s| log::info!("{s}"));
+
+        // Create an observation channel to keep track of the execution time
+        let time_observer = TimeObserver::new("time");
+        let time_ref = time_observer.handle();
+
+        let mut run_client = |state: Option<_>,
+                              mut mgr: LlmpRestartingEventManager<_, _, _>,
+                              _core_id| {
+            let time_observer = time_observer.clone();
+
+            // The only observable behavior of the target: how it exits, and what it prints
+            let exit_status_observer = ExitStatusObserver::new("exit_status");
+            let stdout_observer = StdOutObserver::new("stdout");
+
+            // Feedback to rate the interestingness of an input, by the behavior of the target
+            let mut feedback = BlackBoxFeedback::new("blackbox")
+                .exit_status(&exit_status_observer)
+                .stdout(&stdout_observer);
+            if self.time_buckets {
+                feedback = feedback.time(&time_observer);
+            }
+
+            // A feedback to choose if an input is a solution or not
+            let mut objective = feedback_or_fast!(CrashFeedback::new(), TimeoutFeedback::new());
+
+            // If not restarting, create a State from scratch
+            let mut state = state.unwrap_or_else(|| {
+                StdState::new(
+                    // RNG
+                    StdRand::new(),
+                    // Corpus that will be evolved, we keep a part in memory for performance
+                    CachedOnDiskCorpus::new(out_dir.clone(), CORPUS_CACHE_SIZE).unwrap(),
+                    // Corpus in which we store solutions (crashes in this example),
+                    // on disk so the user can get them after stopping the fuzzer
+                    OnDiskCorpus::new(crashes.clone()).unwrap(),
+                    &mut feedback,
+                    &mut objective,
+                )
+                .unwrap()
+            });
+
+            // Without coverage, there is nothing to minimize the corpus by
+            let scheduler = QueueScheduler::new();
+
+            // A fuzzer with feedbacks and a corpus scheduler
+            let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
+
+            let mut builder = CommandExecutor::builder();
+            builder
+                .program(&self.program)
+                .timeout(timeout)
+                .exit_status_observer(exit_status_observer.handle())
+                .stdout_observer(stdout_observer.handle());
+            for arg in self.arguments {
+                if arg == INPUT_PLACEHOLDER {
+                    builder.arg_input_file_std();
+                } else {
+                    builder.arg(arg);
+                }
+            }
+            let mut executor = builder.build(tuple_list!(
+                exit_status_observer,
+                stdout_observer,
+                time_observer
+            ))?;
+
+            if let Some(tokens_file) = &self.tokens_file {
+                // if a token file is provided, load it into our set of tokens
+                let mut tokens = Tokens::new();
+                tokens.add_from_file(tokens_file)?;
+                state.add_metadata(tokens);
+            }
+
+            load_initial_inputs!(state, fuzzer, executor, mgr, self.input_dirs);
+
+            // Setup a basic mutator
+            let mutator = StdScheduledMutator::new(havoc_mutations().merge(tokens_mutations()));
+            let mut stages = tuple_list!(StdMutationalStage::new(mutator));
+
+            if let Some(iters) = self.iterations {
+                fuzzer.fuzz_loop_for(&mut stages, &mut executor, &mut state, &mut mgr, iters)?;
+                mgr.on_restart(&mut state)?;
+                std::process::exit(0);
+            } else {
+                fuzzer.fuzz_loop(&mut stages, &mut executor, &mut state, &mut mgr)?;
+            }
+
+            Ok(())
+        };
+
+        let launcher = Launcher::builder()
+            .shmem_provider(shmem_provider)
+            .configuration(conf)
+            .monitor(monitor)
+            .run_client(&mut run_client)
+            .cores(self.cores)
+            .broker_port(self.broker_port)
+            .remote_broker_addr(self.remote_broker_addr)
+            .time_ref(Some(time_ref));
+        #[cfg(unix)]
+        let launcher = launcher.stdout_file(Some("/dev/null"));
+        match launcher.build().launch() {
+            Ok(()) => (),
+            Err(Error::ShuttingDown) => log::info!("\nFuzzing stopped by user. Good Bye."),
+            Err(err) => panic!("Fuzzingg failed {err:?}"),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Ensemble fuzzing: instead of running the same fuzzer on each core, give the clients diverse roles.
+//!
+//! With the `ensemble` option of a sugar, each client gets an [`EnsembleRole`] depending on its position
+//! in the list of cores: a different power schedule and mix of mutators per client, one client syncing
+//! new inputs from disk, and one client using `CmpLog`, if the sugar supports it.
+//! The clients share their finds over `LLMP`, as without the option.
+
+use std::{path::PathBuf, time::Duration};
+
+use libafl::schedulers::powersched::PowerSchedule;
+
+/// How often the sync client looks for new inputs in the sync directories
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The power schedules the clients rotate through
+const SCHEDULES: [fn() -> PowerSchedule; 6] = [
+    PowerSchedule::fast,
+    PowerSchedule::explore,
+    PowerSchedule::coe,
+    PowerSchedule::exploit,
+    PowerSchedule::lin,
+    PowerSchedule::quad,
+];
+
+/// The mutators the clients rotate through
+const MUTATOR_MIXES: [MutatorMix; 3] =
+    [MutatorMix::Havoc, MutatorMix::HavocTokens, MutatorMix::MOpt];
+
+/// The mutators of an ensemble client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutatorMix {
+    /// The havoc mutations
+    Havoc,
+    /// The havoc mutations and the token mutations, using the dictionary
+    HavocTokens,
+    /// The havoc mutations and the token mutations, scheduled by `MOpt`
+    MOpt,
+}
+
+/// The role of a client in an ensemble
+#[derive(Debug, Clone, Copy)]
+pub struct EnsembleRole {
+    /// The power schedule of the client
+    pub schedule: PowerSchedule,
+    /// The mutators of the client
+    pub mutators: MutatorMix,
+    /// If the client runs the `CmpLog` tracing and input-to-state stages
+    pub cmplog: bool,
+    /// If the client periodically syncs new inputs from the sync directories
+    pub sync: bool,
+}
+
+impl EnsembleRole {
+    /// The role of the client at `index` in the list of cores.
+    ///
+    /// The first client syncs from disk, the second one uses `CmpLog` if `cmplog` is supported,
+    /// and all clients rotate through the power schedules and mutator mixes.
+    #[must_use]
+    pub fn for_client(index: usize, cmplog: bool) -> Self {
+        Self {
+            schedule: SCHEDULES[index % SCHEDULES.len()](),
+            mutators: if cmplog && index == 1 {
+                MutatorMix::HavocTokens
+            } else {
+                MUTATOR_MIXES[index % MUTATOR_MIXES.len()]
+            },
+            cmplog: cmplog && index == 1,
+            sync: index == 0,
+        }
+    }
+
+    /// The directories this client syncs from: the input directories, so new seeds can be added
+    /// while fuzzing, and the extra `sync_dirs`, e.g. the queues of other fuzzers.
+    /// Empty unless this is the sync client.
+    #[must_use]
+    pub fn sync_dirs(&self, input_dirs: &[PathBuf], sync_dirs: &[PathBuf]) -> Vec<PathBuf> {
+        if self.sync {
+            input_dirs.iter().chain(sync_dirs).cloned().collect()
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Run the fuzz loop of an ensemble client, with the mutators of its `role`.
+///
+/// The stages are the calibration stage, the given extra stages, e.g. for `CmpLog`, the sync stage
+/// and the power mutational stage. With `iterations`, the client exits afterwards.
+macro_rules! ensemble_fuzz_loop {
+    (@run $fuzzer:ident, $stages:ident, $executor:ident, $state:ident, $mgr:ident, $iterations:expr) => {
+        if let Some(iters) = $iterations {
+            $fuzzer.fuzz_loop_for(&mut $stages, &mut $executor, &mut $state, &mut $mgr, iters)?;
+            $mgr.on_restart(&mut $state)?;
+            std::process::exit(0);
+        } else {
+            $fuzzer.fuzz_loop(&mut $stages, &mut $executor, &mut $state, &mut $mgr)?;
+        }
+    };
+    (
+        $role:expr,
+        $fuzzer:ident,
+        $executor:ident,
+        $state:ident,
+        $mgr:ident,
+        $iterations:expr,
+        $calibration:ident,
+        $sync:ident
+        $(, $stage:ident)*
+    ) => {
+        match $role.mutators {
+            MutatorMix::Havoc => {
+                let mutator = StdScheduledMutator::new(havoc_mutations());
+                let power: StdPowerMutationalStage<_, _, BytesInput, _, _> =
+                    StdPowerMutationalStage::new(mutator);
+                let mut stages = tuple_list!($calibration, $($stage,)* $sync, power);
+                $crate::ensemble::ensemble_fuzz_loop!(@run $fuzzer, stages, $executor, $state, $mgr, $iterations);
+            }
+            MutatorMix::HavocTokens => {
+                let mutator = StdScheduledMutator::new(havoc_mutations().merge(tokens_mutations()));
+                let power: StdPowerMutationalStage<_, _, BytesInput, _, _> =
+                    StdPowerMutationalStage::new(mutator);
+                let mut stages = tuple_list!($calibration, $($stage,)* $sync, power);
+                $crate::ensemble::ensemble_fuzz_loop!(@run $fuzzer, stages, $executor, $state, $mgr, $iterations);
+            }
+            MutatorMix::MOpt => {
+                let mutator = StdMOptMutator::new::<BytesInput, _>(
+                    &mut $state,
+                    havoc_mutations().merge(tokens_mutations()),
+                    7,
+                    5,
+                )?;
+                let power: StdPowerMutationalStage<_, _, BytesInput, _, _> =
+                    StdPowerMutationalStage::new(mutator);
+                let mut stages = tuple_list!($calibration, $($stage,)* $sync, power);
+                $crate::ensemble::ensemble_fuzz_loop!(@run $fuzzer, stages, $executor, $state, $mgr, $iterations);
+            }
+        }
+    };
+}
+pub(crate) use ensemble_fuzz_loop;
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use libafl::schedulers::powersched::BaseSchedule;
+
+    use super::{EnsembleRole, MutatorMix};
+
+    #[test]
+    fn test_ensemble_roles() {
+        let sync = EnsembleRole::for_client(0, true);
+        assert!(sync.sync && !sync.cmplog);
+        assert_eq!(*sync.schedule.base(), BaseSchedule::FAST);
+        assert_eq!(sync.mutators, MutatorMix::Havoc);
+
+        // The second client uses CmpLog, with the tokens it finds
+        let cmplog = EnsembleRole::for_client(1, true);
+        assert!(cmplog.cmplog && !cmplog.sync);
+        assert_eq!(cmplog.mutators, MutatorMix::HavocTokens);
+        assert!(!EnsembleRole::for_client(1, false).cmplog);
+
+        let third = EnsembleRole::for_client(2, true);
+        assert!(!third.cmplog && !third.sync);
+        assert_eq!(*third.schedule.base(), BaseSchedule::COE);
+        assert_eq!(third.mutators, MutatorMix::MOpt);
+
+        // The schedules and mutators rotate
+        let seventh = EnsembleRole::for_client(6, true);
+        assert_eq!(*seventh.schedule.base(), BaseSchedule::FAST);
+        assert_eq!(seventh.mutators, MutatorMix::Havoc);
+        assert!(!seventh.sync);
+
+        let input_dirs = [PathBuf::from("in")];
+        let sync_dirs = [PathBuf::from("other")];
+        assert_eq!(sync.sync_dirs(&input_dirs, &sync_dirs).len(), 2);
+        assert!(third.sync_dirs(&input_dirs, &sync_dirs).is_empty());
+    }
+}
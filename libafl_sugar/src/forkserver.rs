@@ -10,15 +10,17 @@ use libafl::{
     feedbacks::{CrashFeedback, MaxMapFeedback, TimeFeedback, TimeoutFeedback},
     fuzzer::{Fuzzer, StdFuzzer},
     generators::RandBytesGenerator,
+    inputs::BytesInput,
     monitors::MultiMonitor,
     mutators::{
         havoc_mutations::havoc_mutations,
         scheduled::{tokens_mutations, StdScheduledMutator},
         token_mutations::Tokens,
+        StdMOptMutator,
     },
     observers::{CanTrack, HitcountsMapObserver, StdMapObserver, TimeObserver},
-    schedulers::{IndexesLenTimeMinimizerScheduler, QueueScheduler},
-    stages::StdMutationalStage,
+    schedulers::{IndexesLenTimeMinimizerScheduler, PowerQueueScheduler, QueueScheduler},
+    stages::{CalibrationStage, StdMutationalStage, StdPowerMutationalStage, SyncFromDiskStage},
     state::{HasCorpus, StdState},
     Error, HasMetadata,
 };
@@ -32,7 +34,10 @@ use libafl_bolts::{
 };
 use typed_builder::TypedBuilder;
 
-use crate::{CORPUS_CACHE_SIZE, DEFAULT_TIMEOUT_SECS};
+use crate::{
+    ensemble::{ensemble_fuzz_loop, EnsembleRole, MutatorMix, SYNC_INTERVAL},
+    load_initial_inputs, CORPUS_CACHE_SIZE, DEFAULT_TIMEOUT_SECS,
+};
 
 /// Creates a Forkserver-based fuzzer.
 #[derive(Debug, TypedBuilder)]
@@ -74,6 +79,12 @@ pub struct ForkserverBytesCoverageSugar<'a> {
     /// Fuzz `iterations` number of times, instead of indefinitely; implies use of `fuzz_loop_for`
     #[builder(default = None)]
     iterations: Option<u64>,
+    /// Give the clients diverse roles, with different power schedules and mutators, see [`crate::ensemble`]
+    #[builder(setter(strip_bool))]
+    ensemble: bool,
+    /// Extra directories the sync client of the ensemble imports new inputs from, e.g. the queues of other fuzzers
+    #[builder(default = &[])]
+    sync_dirs: &'a [PathBuf],
 }
 
 #[allow(clippy::similar_names)]
@@ -121,9 +132,14 @@ impl ForkserverBytesCoverageSugar<'_> {
 
         let mut run_client = |state: Option<_>,
                               mut mgr: LlmpRestartingEventManager<_, _, _>,
-                              _core_id| {
+                              core_id| {
             let time_observer = time_observer.clone();
 
+            // The role of this client, if fuzzing as ensemble
+            let role = self.ensemble.then(|| {
+                EnsembleRole::for_client(self.cores.position(core_id).unwrap_or(0), false)
+            });
+
             // Coverage map shared between target and fuzzer
             let mut shmem = shmem_provider_client.new_shmem(MAP_SIZE).unwrap();
             shmem.write_to_env("__AFL_SHM_ID").unwrap();
@@ -166,8 +182,83 @@ impl ForkserverBytesCoverageSugar<'_> {
                 .unwrap()
             });
 
-            // Create an empty set of tokens, first populated by the target program
-            let mut tokens = Tokens::new();
+            // The executor is the same for all clients, only the scheduler and the stages depend on the role
+            let build_executor = |edges_observer| {
+                // Create an empty set of tokens, first populated by the target program
+                let mut tokens = Tokens::new();
+
+                let forkserver = if self.shmem_testcase {
+                    ForkserverExecutor::builder()
+                        .program(self.program.clone())
+                        .parse_afl_cmdline(self.arguments)
+                        .is_persistent(true)
+                        .autotokens(&mut tokens)
+                        .coverage_map_size(MAP_SIZE)
+                        .timeout(timeout)
+                        .debug_child(self.debug_output)
+                        .shmem_provider(&mut shmem_provider_client)
+                        .build_dynamic_map(edges_observer, tuple_list!(time_observer))
+                } else {
+                    ForkserverExecutor::builder()
+                        .program(self.program.clone())
+                        .parse_afl_cmdline(self.arguments)
+                        .is_persistent(true)
+                        .autotokens(&mut tokens)
+                        .coverage_map_size(MAP_SIZE)
+                        .timeout(timeout)
+                        .debug_child(self.debug_output)
+                        .build_dynamic_map(edges_observer, tuple_list!(time_observer))
+                };
+
+                let executor = forkserver.unwrap();
+                if let Some(tokens_file) = &self.tokens_file {
+                    // if a token file is provided, load it into our set of tokens
+                    tokens.add_from_file(tokens_file)?;
+                }
+                Ok::<_, Error>((executor, tokens))
+            };
+
+            if let Some(role) = role {
+                log::info!("Fuzzing as ensemble client with role {role:?}");
+
+                // Calibrate the new testcases, for the power schedule
+                let calibration = CalibrationStage::new(&MaxMapFeedback::new(&edges_observer));
+
+                // A minimization+power queue policy, with the power schedule of the role
+                let scheduler = IndexesLenTimeMinimizerScheduler::new(
+                    &edges_observer,
+                    PowerQueueScheduler::new(&mut state, &edges_observer, role.schedule),
+                );
+
+                // A fuzzer with feedbacks and a corpus scheduler
+                let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
+
+                let (mut executor, tokens) = build_executor(edges_observer)?;
+                if !tokens.is_empty() {
+                    // add any known tokens to the state
+                    state.add_metadata(tokens);
+                }
+
+                load_initial_inputs!(state, fuzzer, executor, mgr, self.input_dirs);
+
+                // Import new inputs from disk, if this is the sync client
+                let sync = SyncFromDiskStage::with_from_file(
+                    role.sync_dirs(self.input_dirs, self.sync_dirs),
+                    SYNC_INTERVAL,
+                );
+
+                ensemble_fuzz_loop!(
+                    role,
+                    fuzzer,
+                    executor,
+                    state,
+                    mgr,
+                    self.iterations,
+                    calibration,
+                    sync
+                );
+                return Ok(());
+            }
 
             // A minimization+queue policy to get testcasess from the corpus
             let scheduler =
@@ -176,71 +267,13 @@ impl ForkserverBytesCoverageSugar<'_> {
             // A fuzzer with feedbacks and a corpus scheduler
             let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
 
-            let forkserver = if self.shmem_testcase {
-                ForkserverExecutor::builder()
-                    .program(self.program.clone())
-                    .parse_afl_cmdline(self.arguments)
-                    .is_persistent(true)
-                    .autotokens(&mut tokens)
-                    .coverage_map_size(MAP_SIZE)
-                    .timeout(timeout)
-                    .debug_child(self.debug_output)
-                    .shmem_provider(&mut shmem_provider_client)
-                    .build_dynamic_map(edges_observer, tuple_list!(time_observer))
-            } else {
-                ForkserverExecutor::builder()
-                    .program(self.program.clone())
-                    .parse_afl_cmdline(self.arguments)
-                    .is_persistent(true)
-                    .autotokens(&mut tokens)
-                    .coverage_map_size(MAP_SIZE)
-                    .timeout(timeout)
-                    .debug_child(self.debug_output)
-                    .build_dynamic_map(edges_observer, tuple_list!(time_observer))
-            };
-
-            let mut executor = forkserver.unwrap();
-            if let Some(tokens_file) = &self.tokens_file {
-                // if a token file is provided, load it into our set of tokens
-                tokens.add_from_file(tokens_file)?;
-            }
-
+            let (mut executor, tokens) = build_executor(edges_observer)?;
             if !tokens.is_empty() {
                 // add any known tokens to the state
                 state.add_metadata(tokens);
             }
 
-            // In case the corpus is empty (on first run), reset
-            if state.must_load_initial_inputs() {
-                if self.input_dirs.is_empty() {
-                    // Generator of printable bytearrays of max size 32
-                    let mut generator = RandBytesGenerator::new(nonzero!(32));
-
-                    // Generate 8 initial inputs
-                    state
-                        .generate_initial_inputs(
-                            &mut fuzzer,
-                            &mut executor,
-                            &mut generator,
-                            &mut mgr,
-                            8,
-                        )
-                        .expect("Failed to generate the initial corpus");
-                    log::info!(
-                        "We imported {} inputs from the generator.",
-                        state.corpus().count()
-                    );
-                } else {
-                    log::info!("Loading from {:?}", &self.input_dirs);
-                    // Load from disk
-                    state
-                        .load_initial_inputs(&mut fuzzer, &mut executor, &mut mgr, self.input_dirs)
-                        .unwrap_or_else(|_| {
-                            panic!("Failed to load initial corpus at {:?}", &self.input_dirs);
-                        });
-                    log::info!("We imported {} inputs from disk.", state.corpus().count());
-                }
-            }
+            load_initial_inputs!(state, fuzzer, executor, mgr, self.input_dirs);
 
             if self.tokens_file.is_some() {
                 // Setup a basic mutator
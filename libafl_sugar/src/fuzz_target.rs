@@ -0,0 +1,233 @@
+//! A drop-in replacement for the `fuzz_target!` macro of `cargo-fuzz`.
+//!
+//! Replacing `libfuzzer_sys::fuzz_target!` by [`crate::libafl_fuzz_target!`] turns a `cargo-fuzz` target into
+//! a multi-core [`InMemoryBytesCoverageSugar`] fuzzer, using the `SanitizerCoverage` 8-bit counters of the
+//! target, havoc and input-to-state mutations, and `LLMP` to share the corpus between the cores.
+//!
+//! The options are given as `libFuzzer`-style flags, see [`FuzzTargetOptions::parse`].
+
+use std::{env, net::SocketAddr, path::PathBuf};
+
+use libafl::Error;
+use libafl_bolts::core_affinity::Cores;
+
+use crate::InMemoryBytesCoverageSugar;
+
+/// Re-exported for the `Arbitrary` form of [`crate::libafl_fuzz_target!`]
+#[cfg(feature = "arbitrary")]
+pub use arbitrary;
+
+/// Create the `main` function of a fuzzer for the given harness, like the `fuzz_target!` macro of `cargo-fuzz`.
+///
+/// The target has to be instrumented with the `inline-8bit-counters` of `SanitizerCoverage`, as `cargo fuzz build`
+/// does, and built with `-Cpanic=abort`, so that panics are reported as crashes.
+/// As with `cargo-fuzz`, the crate has to be `#![no_main]`, since the macro defines the C `main` function.
+///
+/// ```rust,ignore
+/// #![no_main]
+/// use libafl_sugar::libafl_fuzz_target;
+///
+/// libafl_fuzz_target!(|data: &[u8]| {
+///     if data == b"abc" {
+///         panic!("Found it!");
+///     }
+/// });
+/// ```
+///
+/// With the `arbitrary` feature, the harness may take any type implementing `arbitrary::Arbitrary` instead,
+/// built from the bytes of each input. Inputs too short to build it are skipped, as in `cargo-fuzz`.
+///
+/// ```rust,ignore
+/// libafl_fuzz_target!(|data: (u8, String)| {
+///     if data.0 == 42 && data.1 == "abc" {
+///         panic!("Found it!");
+///     }
+/// });
+/// ```
+#[macro_export]
+macro_rules! libafl_fuzz_target {
+    (|$data:ident: &[u8]| $body:expr) => {
+        #[no_mangle]
+        pub extern "C" fn main(
+            _argc: ::core::ffi::c_int,
+            _argv: *const *const ::core::ffi::c_char,
+        ) -> ::core::ffi::c_int {
+            $crate::fuzz_target::fuzz_target_main(|$data: &[u8]| {
+                $body;
+            });
+            0
+        }
+    };
+    (|$data:ident: $dty:ty| $body:expr) => {
+        #[no_mangle]
+        pub extern "C" fn main(
+            _argc: ::core::ffi::c_int,
+            _argv: *const *const ::core::ffi::c_char,
+        ) -> ::core::ffi::c_int {
+            $crate::fuzz_target::fuzz_target_main(|bytes: &[u8]| {
+                use $crate::fuzz_target::arbitrary::{Arbitrary, Unstructured};
+                let Ok($data) =
+                    <$dty as Arbitrary<'_>>::arbitrary_take_rest(Unstructured::new(bytes))
+                else {
+                    return;
+                };
+                $body;
+            });
+            0
+        }
+    };
+}
+
+/// The options of a fuzzer created by [`crate::libafl_fuzz_target!`]
+#[derive(Debug, Clone)]
+pub struct FuzzTargetOptions {
+    /// The corpus directories to load the initial inputs from
+    pub input_dirs: Vec<PathBuf>,
+    /// The directory to store the queue and the crashes in
+    pub output_dir: PathBuf,
+    /// The cores to run on
+    pub cores: Cores,
+    /// The port of the broker
+    pub broker_port: u16,
+    /// The `ip:port` address of another broker to connect to
+    pub remote_broker_addr: Option<SocketAddr>,
+    /// The dictionary
+    pub tokens_file: Option<PathBuf>,
+    /// The timeout of a run, in seconds
+    pub timeout: Option<u64>,
+    /// Whether to use the input-to-state stage
+    pub use_cmplog: bool,
+    /// Fuzz this many iterations per core, instead of indefinitely
+    pub iterations: Option<u64>,
+}
+
+impl Default for FuzzTargetOptions {
+    fn default() -> Self {
+        Self {
+            input_dirs: vec![],
+            output_dir: PathBuf::from("libafl_out"),
+            cores: Cores::from_cmdline("0").unwrap(),
+            broker_port: 1337,
+            remote_broker_addr: None,
+            tokens_file: None,
+            timeout: None,
+            use_cmplog: true,
+            iterations: None,
+        }
+    }
+}
+
+impl FuzzTargetOptions {
+    /// Parse the options from `libFuzzer`-style arguments, without the name of the program.
+    ///
+    /// Positional arguments are the corpus directories. The supported flags are
+    /// `-cores=<cores>` (e.g. `0-3` or `all`), `-broker_port=<port>`, `-remote_broker_addr=<ip:port>`,
+    /// `-out=<dir>`, `-dict=<file>`, `-timeout=<secs>`, `-runs=<iterations>` and `-cmplog=<0|1>`.
+    /// Other `libFuzzer` flags are ignored with a warning.
+    pub fn parse<I>(args: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Self::default();
+        for arg in args {
+            let Some(flag) = arg.strip_prefix('-') else {
+                options.input_dirs.push(PathBuf::from(arg));
+                continue;
+            };
+            let flag = flag.trim_start_matches('-');
+            let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+            let invalid = || Error::illegal_argument(format!("Invalid value of -{name}: {value}"));
+            match name {
+                "cores" => options.cores = Cores::from_cmdline(value)?,
+                "broker_port" => options.broker_port = value.parse().map_err(|_| invalid())?,
+                "remote_broker_addr" => {
+                    options.remote_broker_addr = Some(value.parse().map_err(|_| invalid())?);
+                }
+                "out" => options.output_dir = PathBuf::from(value),
+                "dict" => options.tokens_file = Some(PathBuf::from(value)),
+                "timeout" => options.timeout = Some(value.parse().map_err(|_| invalid())?),
+                "runs" => options.iterations = Some(value.parse().map_err(|_| invalid())?),
+                "cmplog" => options.use_cmplog = value != "0",
+                _ => log::warn!("Ignoring unsupported flag {arg}"),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Run the fuzzer with these options on the given harness
+    pub fn run<H>(&self, harness: H)
+    where
+        H: FnMut(&[u8]),
+    {
+        let builder = InMemoryBytesCoverageSugar::builder()
+            .input_dirs(&self.input_dirs)
+            .output_dir(self.output_dir.clone())
+            .cores(&self.cores)
+            .broker_port(self.broker_port)
+            .tokens_file(self.tokens_file.clone())
+            .timeout(self.timeout)
+            .use_cmplog(Some(self.use_cmplog))
+            .iterations(self.iterations)
+            .use_counters_maps(true)
+            .harness(harness);
+        match self.remote_broker_addr {
+            Some(addr) => builder.remote_broker_addr(addr).build().run(),
+            None => builder.build().run(),
+        }
+    }
+}
+
+/// The `main` of a fuzzer created by [`crate::libafl_fuzz_target!`]: parse the [`FuzzTargetOptions`]
+/// from the command line, and fuzz the harness
+pub fn fuzz_target_main<H>(harness: H)
+where
+    H: FnMut(&[u8]),
+{
+    match FuzzTargetOptions::parse(env::args().skip(1)) {
+        Ok(options) => options.run(harness),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::FuzzTargetOptions;
+
+    #[test]
+    fn test_parse_options() {
+        let options = FuzzTargetOptions::parse(
+            [
+                "corpus",
+                "-cores=0-1",
+                "--out=out",
+                "-dict=fuzz.dict",
+                "-timeout=5",
+                "-runs=100",
+                "-cmplog=0",
+                "-max_len=64",
+                "seeds",
+            ]
+            .map(String::from),
+        )
+        .unwrap();
+        assert_eq!(
+            options.input_dirs,
+            [PathBuf::from("corpus"), PathBuf::from("seeds")]
+        );
+        assert_eq!(options.cores.ids.len(), 2);
+        assert_eq!(options.output_dir, PathBuf::from("out"));
+        assert_eq!(options.tokens_file, Some(PathBuf::from("fuzz.dict")));
+        assert_eq!(options.timeout, Some(5));
+        assert_eq!(options.iterations, Some(100));
+        assert!(!options.use_cmplog);
+        assert_eq!(options.broker_port, 1337);
+
+        assert!(FuzzTargetOptions::parse(["-broker_port=x".to_string()]).is_err());
+        assert!(FuzzTargetOptions::parse(["-remote_broker_addr=x".to_string()]).is_err());
+    }
+}
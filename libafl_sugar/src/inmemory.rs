@@ -34,10 +34,10 @@ use libafl_bolts::{
     tuples::{tuple_list, Handled, Merge},
     AsSlice,
 };
-use libafl_targets::{edges_map_mut_ptr, CmpLogObserver};
+use libafl_targets::{edges_map_mut_ptr, extra_counters, CmpLogObserver};
 use typed_builder::TypedBuilder;
 
-use crate::{CORPUS_CACHE_SIZE, DEFAULT_TIMEOUT_SECS};
+use crate::{load_initial_inputs, CORPUS_CACHE_SIZE, DEFAULT_TIMEOUT_SECS};
 
 /// In-Memory fuzzing made easy.
 /// Use this sugar for scaling `libfuzzer`-style fuzzers.
@@ -77,6 +77,10 @@ where
     /// The map size used for the fuzzer
     #[builder(default = 65536usize)]
     map_size: usize,
+    /// Observe the `inline-8bit-counters` of `SanitizerCoverage`, as instrumented by `cargo fuzz`, instead of the
+    /// edges map of `trace-pc-guard`. The map size is then given by the target.
+    #[builder(default = false)]
+    use_counters_maps: bool,
     /// Fuzz `iterations` number of times, instead of indefinitely; implies use of `fuzz_loop_for`
     #[builder(default = None)]
     iterations: Option<u64>,
@@ -97,6 +101,7 @@ where
             .field("broker_port", &self.broker_port)
             .field("cores", &self.cores)
             .field("remote_broker_addr", &self.remote_broker_addr)
+            .field("use_counters_maps", &self.use_counters_maps)
             .field(
                 "harness",
                 if self.harness.is_some() {
@@ -109,6 +114,20 @@ where
     }
 }
 
+/// The `inline-8bit-counters` map of the target, which has to be a single instrumented module
+fn counters_map() -> Result<OwnedMutSlice<'static, u8>, Error> {
+    // The maps were registered by the constructors of the module, before `main`
+    let counters = unsafe { extra_counters() };
+    <[_; 1]>::try_from(counters)
+        .map(|[counters]| counters)
+        .map_err(|counters| {
+            Error::illegal_state(format!(
+                "Expected one module instrumented with 8-bit counters, found {}",
+                counters.len()
+            ))
+        })
+}
+
 #[allow(clippy::similar_names)]
 impl<H> InMemoryBytesCoverageSugar<'_, H>
 where
@@ -153,11 +172,13 @@ where
             let time_observer = time_observer.clone();
 
             // Create an observation channel using the coverage map
+            let coverage_map = if self.use_counters_maps {
+                counters_map()?
+            } else {
+                unsafe { OwnedMutSlice::from_raw_parts_mut(edges_map_mut_ptr(), self.map_size) }
+            };
             let edges_observer = HitcountsMapObserver::new(unsafe {
-                StdMapObserver::from_mut_slice(
-                    "edges",
-                    OwnedMutSlice::from_raw_parts_mut(edges_map_mut_ptr(), self.map_size),
-                )
+                StdMapObserver::from_mut_slice("edges", coverage_map)
             })
             .track_indices();
 
@@ -226,37 +247,7 @@ where
                 tuple_list!(cmplog_observer),
             );
 
-            // In case the corpus is empty (on first run), reset
-            if state.must_load_initial_inputs() {
-                if self.input_dirs.is_empty() {
-                    // Generator of printable bytearrays of max size 32
-                    let mut generator = RandBytesGenerator::new(nonzero!(32));
-
-                    // Generate 8 initial inputs
-                    state
-                        .generate_initial_inputs(
-                            &mut fuzzer,
-                            &mut executor,
-                            &mut generator,
-                            &mut mgr,
-                            8,
-                        )
-                        .expect("Failed to generate the initial corpus");
-                    log::info!(
-                        "We imported {} inputs from the generator.",
-                        state.corpus().count()
-                    );
-                } else {
-                    log::info!("Loading from {:?}", &self.input_dirs);
-                    // Load from disk
-                    state
-                        .load_initial_inputs(&mut fuzzer, &mut executor, &mut mgr, self.input_dirs)
-                        .unwrap_or_else(|_| {
-                            panic!("Failed to load initial corpus at {:?}", &self.input_dirs);
-                        });
-                    log::info!("We imported {} inputs from disk.", state.corpus().count());
-                }
-            }
+            load_initial_inputs!(state, fuzzer, executor, mgr, self.input_dirs);
 
             // Setup a tracing stage in which we log comparisons
             let tracing = ShadowTracingStage::new(&mut executor);
@@ -41,6 +41,8 @@
     )
 )]
 
+pub mod ensemble;
+
 #[allow(clippy::ignored_unit_patterns)]
 pub mod inmemory;
 pub use inmemory::InMemoryBytesCoverageSugar;
@@ -57,12 +59,61 @@ pub mod forkserver;
 #[cfg(target_family = "unix")]
 pub use forkserver::ForkserverBytesCoverageSugar;
 
+#[cfg(target_family = "unix")]
+pub mod blackbox;
+#[cfg(target_family = "unix")]
+pub use blackbox::BlackBoxSugar;
+
+pub mod push;
+pub use push::PushStageBytesCoverageSugar;
+
+pub mod fuzz_target;
+pub use fuzz_target::FuzzTargetOptions;
+
 /// Default timeout for a run
 pub const DEFAULT_TIMEOUT_SECS: u64 = 1200;
 /// Default cache size for the corpus in memory.
 /// Anything else will be on disk.
 pub const CORPUS_CACHE_SIZE: usize = 4096;
 
+/// Load the initial inputs from the input directories, or generate some if there are none.
+macro_rules! load_initial_inputs {
+    ($state:ident, $fuzzer:ident, $executor:ident, $mgr:ident, $input_dirs:expr) => {
+        // In case the corpus is empty (on first run), reset
+        if $state.must_load_initial_inputs() {
+            if $input_dirs.is_empty() {
+                // Generator of printable bytearrays of max size 32
+                let mut generator = RandBytesGenerator::new(nonzero!(32));
+
+                // Generate 8 initial inputs
+                $state
+                    .generate_initial_inputs(
+                        &mut $fuzzer,
+                        &mut $executor,
+                        &mut generator,
+                        &mut $mgr,
+                        8,
+                    )
+                    .expect("Failed to generate the initial corpus");
+                log::info!(
+                    "We imported {} inputs from the generator.",
+                    $state.corpus().count()
+                );
+            } else {
+                log::info!("Loading from {:?}", $input_dirs);
+                // Load from disk
+                $state
+                    .load_initial_inputs(&mut $fuzzer, &mut $executor, &mut $mgr, $input_dirs)
+                    .unwrap_or_else(|_| {
+                        panic!("Failed to load initial corpus at {:?}", $input_dirs);
+                    });
+                log::info!("We imported {} inputs from disk.", $state.corpus().count());
+            }
+        }
+    };
+}
+pub(crate) use load_initial_inputs;
+
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 
@@ -72,6 +123,7 @@ use pyo3::prelude::*;
 #[pyo3(name = "libafl_sugar")]
 pub fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
     inmemory::pybind::register(m)?;
+    push::pybind::register(m)?;
     #[cfg(target_os = "linux")]
     {
         qemu::pybind::register(m)?;
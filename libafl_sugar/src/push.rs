@@ -0,0 +1,382 @@
+//! Push-stage fuzzing made easy.
+//! Use this sugar to drive `LibAFL` from your own harness loop, e.g. in Python or an emulator:
+//! it hands out one input after the other, and you report the coverage and exit kind of each of them.
+
+use core::{
+    cell::{Cell, RefCell},
+    fmt::{self, Debug, Formatter},
+};
+use std::{path::PathBuf, rc::Rc};
+
+use libafl::{
+    corpus::{Corpus, InMemoryCorpus, OnDiskCorpus, Testcase},
+    events::SimpleEventManager,
+    executors::ExitKind,
+    feedback_or_fast,
+    feedbacks::{CrashFeedback, MaxMapFeedback, TimeoutFeedback},
+    fuzzer::StdFuzzer,
+    inputs::BytesInput,
+    monitors::SimpleMonitor,
+    mutators::Mutator,
+    observers::StdMapObserver,
+    schedulers::{QueueScheduler, Scheduler},
+    stages::push::{PushStageSharedState, StdMutationalPushStage},
+    state::{HasCorpus, HasSolutions, StdState},
+    Error,
+};
+use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+/// The state of the [`PushStageBytesCoverageSugar`], for which its mutator has to be implemented
+pub type PushStageState =
+    StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, OnDiskCorpus<BytesInput>>;
+
+/// Push-stage fuzzing made easy.
+/// Hands out mutated inputs one by one, instead of running a harness itself.
+///
+/// Before asking for the next input, write the coverage of the last one to [`Self::coverage_map_mut`],
+/// and report how it ended with [`Self::set_exit_kind`]. Unreported inputs count as [`ExitKind::Ok`].
+pub struct PushStageBytesCoverageSugar {
+    stage: Box<dyn Iterator<Item = Result<BytesInput, Error>>>,
+    exit_kind: Rc<Cell<Option<ExitKind>>>,
+    /// The sizes of the corpus and of the solutions, read from the shared state of the stage
+    counts: Box<dyn Fn() -> (usize, usize)>,
+    /// The coverage map, observed by the stage. Declared after the stage, so it outlives the observer.
+    map: Vec<u8>,
+}
+
+impl Debug for PushStageBytesCoverageSugar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PushStageBytesCoverageSugar")
+            .field("exit_kind", &self.exit_kind)
+            .field("map_size", &self.map.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PushStageBytesCoverageSugar {
+    /// Create a new [`PushStageBytesCoverageSugar`], mutating the `seeds` with `mutator`.
+    ///
+    /// Inputs covering new entries of the map of `map_size` entries are added to the corpus,
+    /// crashes and timeouts are stored in `crashes_dir`.
+    pub fn new<M>(
+        map_size: usize,
+        seeds: Vec<Vec<u8>>,
+        crashes_dir: PathBuf,
+        mutator: M,
+    ) -> Result<Self, Error>
+    where
+        M: Mutator<BytesInput, PushStageState> + 'static,
+    {
+        if seeds.is_empty() {
+            return Err(Error::illegal_argument(
+                "The push stage needs at least one seed",
+            ));
+        }
+
+        let mut map = vec![0; map_size];
+        // The heap buffer of the map does not move when the `Vec` itself is moved into `Self`
+        let observer =
+            unsafe { StdMapObserver::from_mut_ptr("edges", map.as_mut_ptr(), map.len()) };
+
+        let mut feedback = MaxMapFeedback::new(&observer);
+        let mut objective = feedback_or_fast!(CrashFeedback::new(), TimeoutFeedback::new());
+
+        let mut state = StdState::new(
+            StdRand::new(),
+            InMemoryCorpus::new(),
+            OnDiskCorpus::new(crashes_dir)?,
+            &mut feedback,
+            &mut objective,
+        )?;
+
+        let mut scheduler = QueueScheduler::new();
+        for seed in seeds {
+            let id = state
+                .corpus_mut()
+                .add(Testcase::new(BytesInput::new(seed)))?;
+            <QueueScheduler as Scheduler<BytesInput, _>>::on_add(&mut scheduler, &mut state, id)?;
+        }
+
+        let fuzzer = StdFuzzer::new(scheduler, feedback, objective);
+        let mgr = SimpleEventManager::new(SimpleMonitor::new(|s| log::info!("{s}")));
+
+        let exit_kind = Rc::new(Cell::new(None));
+        let shared_state = Rc::new(RefCell::new(Some(PushStageSharedState::new(
+            fuzzer,
+            state,
+            tuple_list!(observer),
+            mgr,
+        ))));
+        let counts = {
+            let shared_state = shared_state.clone();
+            // The stage only takes the shared state out while it runs, inside `next_input`
+            Box::new(move || {
+                shared_state.borrow().as_ref().map_or((0, 0), |shared| {
+                    (
+                        shared.state.corpus().count(),
+                        shared.state.solutions().count(),
+                    )
+                })
+            })
+        };
+        let stage = StdMutationalPushStage::new(mutator, shared_state, exit_kind.clone());
+
+        Ok(Self {
+            stage: Box::new(stage),
+            exit_kind,
+            counts,
+            map,
+        })
+    }
+
+    /// Evaluate the last input, and get the next one.
+    ///
+    /// The coverage map is cleared before the input is returned.
+    pub fn next_input(&mut self) -> Option<Result<BytesInput, Error>> {
+        if self.exit_kind.get().is_none() {
+            self.exit_kind.set(Some(ExitKind::Ok));
+        }
+        // The stage ends after the mutations of each corpus entry; the next round starts with the next entry
+        let next = self.stage.next().or_else(|| self.stage.next());
+        self.map.fill(0);
+        next
+    }
+
+    /// The coverage map, to be written by the harness for each input
+    pub fn coverage_map_mut(&mut self) -> &mut [u8] {
+        &mut self.map
+    }
+
+    /// Report how the execution of the last input ended
+    pub fn set_exit_kind(&mut self, exit_kind: ExitKind) {
+        self.exit_kind.set(Some(exit_kind));
+    }
+
+    /// The number of inputs in the corpus
+    #[must_use]
+    pub fn corpus_count(&self) -> usize {
+        (self.counts)().0
+    }
+
+    /// The number of crashes and timeouts found
+    #[must_use]
+    pub fn solutions_count(&self) -> usize {
+        (self.counts)().1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use libafl::{
+        executors::ExitKind,
+        mutators::{havoc_mutations::havoc_mutations, StdScheduledMutator},
+    };
+
+    use super::PushStageBytesCoverageSugar;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_push_stage_sugar() {
+        let crashes_dir = env::temp_dir().join(format!("libafl_sugar_push_{}", std::process::id()));
+        let mut sugar = PushStageBytesCoverageSugar::new(
+            16,
+            vec![b"seed".to_vec()],
+            crashes_dir.clone(),
+            StdScheduledMutator::new(havoc_mutations()),
+        )
+        .unwrap();
+        assert_eq!(sugar.corpus_count(), 1);
+
+        sugar.next_input().unwrap().unwrap();
+        sugar.coverage_map_mut()[3] = 1;
+        // Evaluates the first input, which covered a new map entry
+        sugar.next_input().unwrap().unwrap();
+        assert_eq!(sugar.corpus_count(), 2);
+
+        sugar.set_exit_kind(ExitKind::Crash);
+        sugar.next_input().unwrap().unwrap();
+        assert_eq!(sugar.corpus_count(), 2);
+        assert_eq!(sugar.solutions_count(), 1);
+
+        fs::remove_dir_all(crashes_dir).unwrap();
+    }
+}
+
+/// Python bindings for this sugar
+#[cfg(feature = "python")]
+pub mod pybind {
+    use std::{borrow::Cow, num::NonZero, path::PathBuf};
+
+    use libafl::{
+        executors::ExitKind,
+        inputs::{BytesInput, HasMutatorBytes},
+        mutators::{
+            havoc_mutations::havoc_mutations, MutationResult, Mutator, StdScheduledMutator,
+        },
+        state::HasRand,
+        Error,
+    };
+    use libafl_bolts::{
+        rands::Rand,
+        tuples::{tuple_list, Merge},
+        Named,
+    };
+    use pyo3::{
+        exceptions::{PyRuntimeError, PyValueError},
+        prelude::*,
+        types::PyBytes,
+    };
+
+    use crate::push;
+
+    /// Calls the `mutate` method of one of the given Python objects as [`Mutator`].
+    ///
+    /// `mutate` gets the input as `bytes`, and returns the mutated input, or `None` to skip the mutation.
+    #[derive(Debug)]
+    pub struct PythonMutator {
+        mutators: Vec<PyObject>,
+    }
+
+    impl PythonMutator {
+        /// Create a new [`PythonMutator`], picking a random one of `mutators` for each mutation
+        #[must_use]
+        pub fn new(mutators: Vec<PyObject>) -> Self {
+            Self { mutators }
+        }
+    }
+
+    impl Named for PythonMutator {
+        fn name(&self) -> &Cow<'static, str> {
+            static NAME: Cow<'static, str> = Cow::Borrowed("PythonMutator");
+            &NAME
+        }
+    }
+
+    impl<S> Mutator<BytesInput, S> for PythonMutator
+    where
+        S: HasRand,
+    {
+        fn mutate(
+            &mut self,
+            state: &mut S,
+            input: &mut BytesInput,
+        ) -> Result<MutationResult, Error> {
+            let Some(len) = NonZero::new(self.mutators.len()) else {
+                return Ok(MutationResult::Skipped);
+            };
+            let mutator = &self.mutators[state.rand_mut().below(len)];
+            // Acquiring the GIL is cheap if it is already held, e.g. while in `PushStageFuzzer.__next__`
+            Python::with_gil(|py| -> PyResult<MutationResult> {
+                let mutated =
+                    mutator.call_method1(py, "mutate", (PyBytes::new_bound(py, input.bytes()),))?;
+                if mutated.is_none(py) {
+                    return Ok(MutationResult::Skipped);
+                }
+                *input = BytesInput::new(mutated.extract::<Vec<u8>>(py)?);
+                Ok(MutationResult::Mutated)
+            })
+            .map_err(|err| Error::illegal_state(format!("Python mutator failed: {err}")))
+        }
+    }
+
+    /// Push-stage fuzzing made easy.
+    /// Iterate over it to get the inputs, run them in your own harness loop, and `report` how each one ran.
+    ///
+    /// The inputs are mutated by the havoc mutations and the Python `mutators`, objects with a
+    /// `mutate(bytes) -> bytes | None` method.
+    ///
+    /// The GIL stays held in all methods: `LibAFL` only runs between two inputs, and calls the Python
+    /// mutators on the same thread, so releasing it would gain nothing. The fuzzer must not be shared
+    /// between threads.
+    #[pyclass(unsendable)]
+    #[derive(Debug)]
+    struct PushStageFuzzer {
+        inner: push::PushStageBytesCoverageSugar,
+    }
+
+    #[pymethods]
+    impl PushStageFuzzer {
+        /// Create a new [`PushStageFuzzer`]
+        #[new]
+        #[pyo3(signature = (
+            seeds,
+            map_size=65536,
+            crashes_dir=None,
+            mutators=None
+        ))]
+        fn new(
+            seeds: Vec<Vec<u8>>,
+            map_size: usize,
+            crashes_dir: Option<PathBuf>,
+            mutators: Option<Vec<PyObject>>,
+        ) -> PyResult<Self> {
+            let mutator = StdScheduledMutator::new(havoc_mutations().merge(tuple_list!(
+                PythonMutator::new(mutators.unwrap_or_default())
+            )));
+            let inner = push::PushStageBytesCoverageSugar::new(
+                map_size,
+                seeds,
+                crashes_dir.unwrap_or_else(|| PathBuf::from("crashes")),
+                mutator,
+            )
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            Ok(Self { inner })
+        }
+
+        fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+            slf
+        }
+
+        /// Evaluate the last input, and get the next one
+        fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<Py<PyBytes>>> {
+            match self.inner.next_input() {
+                Some(Ok(input)) => Ok(Some(PyBytes::new_bound(py, input.bytes()).unbind())),
+                Some(Err(err)) => Err(PyRuntimeError::new_err(err.to_string())),
+                None => Ok(None),
+            }
+        }
+
+        /// Report how the last input ran: `exit_kind` is one of `ok`, `crash`, `oom` or `timeout`,
+        /// and `coverage`, if given, is copied to the start of the coverage map
+        #[pyo3(signature = (exit_kind="ok", coverage=None))]
+        fn report(&mut self, exit_kind: &str, coverage: Option<&[u8]>) -> PyResult<()> {
+            let exit_kind = match exit_kind {
+                "ok" => ExitKind::Ok,
+                "crash" => ExitKind::Crash,
+                "oom" => ExitKind::Oom,
+                "timeout" => ExitKind::Timeout,
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                        "Unknown exit kind {exit_kind}"
+                    )))
+                }
+            };
+            if let Some(coverage) = coverage {
+                let map = self.inner.coverage_map_mut();
+                let len = map.len().min(coverage.len());
+                map[..len].copy_from_slice(&coverage[..len]);
+            }
+            self.inner.set_exit_kind(exit_kind);
+            Ok(())
+        }
+
+        /// Count a hit of the coverage map entry at `index` for the last input
+        fn hit(&mut self, index: usize) -> PyResult<()> {
+            let entry = self
+                .inner
+                .coverage_map_mut()
+                .get_mut(index)
+                .ok_or_else(|| PyValueError::new_err(format!("Index {index} out of the map")))?;
+            *entry = entry.saturating_add(1);
+            Ok(())
+        }
+    }
+
+    /// Register the module
+    pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+        m.add_class::<PushStageFuzzer>()?;
+        Ok(())
+    }
+}
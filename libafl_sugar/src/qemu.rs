@@ -20,11 +20,14 @@ use libafl::{
         havoc_mutations::havoc_mutations,
         scheduled::{tokens_mutations, StdScheduledMutator},
         token_mutations::Tokens,
-        I2SRandReplace,
+        I2SRandReplace, StdMOptMutator,
     },
     observers::{CanTrack, HitcountsMapObserver, TimeObserver, VariableMapObserver},
-    schedulers::{IndexesLenTimeMinimizerScheduler, QueueScheduler},
-    stages::{ShadowTracingStage, StdMutationalStage},
+    schedulers::{IndexesLenTimeMinimizerScheduler, PowerQueueScheduler, QueueScheduler},
+    stages::{
+        CalibrationStage, ShadowTracingStage, StdMutationalStage, StdPowerMutationalStage,
+        SyncFromDiskStage,
+    },
     state::{HasCorpus, StdState},
     HasMetadata,
 };
@@ -44,7 +47,83 @@ use libafl_qemu::{modules::edges::StdEdgeCoverageModule, Emulator, QemuExecutor}
 use libafl_targets::{edges_map_mut_ptr, CmpLogObserver, EDGES_MAP_DEFAULT_SIZE, MAX_EDGES_FOUND};
 use typed_builder::TypedBuilder;
 
-use crate::{CORPUS_CACHE_SIZE, DEFAULT_TIMEOUT_SECS};
+use crate::{
+    ensemble::{ensemble_fuzz_loop, EnsembleRole, MutatorMix, SYNC_INTERVAL},
+    load_initial_inputs, CORPUS_CACHE_SIZE, DEFAULT_TIMEOUT_SECS,
+};
+
+/// Build the `QemuExecutor` of a client running the harness, with the edge coverage module.
+/// With `cmplog`, it also runs the `CmpLog` module, and is wrapped in a `ShadowExecutor` with the `CmpLog` observer.
+macro_rules! qemu_executor {
+    (@modules cmplog, $edges_observer:ident) => {{
+        #[cfg(not(any(feature = "mips", feature = "hexagon")))]
+        {
+            tuple_list!(
+                StdEdgeCoverageModule::builder()
+                    .map_observer($edges_observer.as_mut())
+                    .build()
+                    .unwrap(),
+                CmpLogModule::default(),
+            )
+        }
+        #[cfg(any(feature = "mips", feature = "hexagon"))]
+        {
+            tuple_list!(StdEdgeCoverageModule::builder()
+                .map_observer($edges_observer.as_mut())
+                .build()
+                .unwrap())
+        }
+    }};
+    (@modules plain, $edges_observer:ident) => {
+        tuple_list!(StdEdgeCoverageModule::builder()
+            .map_observer($edges_observer.as_mut())
+            .build()
+            .unwrap())
+    };
+    (@wrap cmplog, $executor:ident, $cmplog_observer:ident) => {
+        let mut $executor = ShadowExecutor::new($executor, tuple_list!($cmplog_observer));
+    };
+    (@wrap plain, $executor:ident, $cmplog_observer:ident) => {
+        let mut $executor = $executor;
+    };
+    (
+        $kind:ident,
+        $executor:ident,
+        $qemu:ident,
+        $harness_bytes:ident,
+        $edges_observer:ident,
+        $time_observer:ident,
+        $cmplog_observer:ident,
+        $fuzzer:ident,
+        $state:ident,
+        $mgr:ident,
+        $timeout:ident
+    ) => {
+        let modules = qemu_executor!(@modules $kind, $edges_observer);
+
+        // The wrapped harness function, calling out to the LLVM-style harness
+        let mut harness =
+            |_emulator: &mut Emulator<_, _, _, _, _>, _state: &mut _, input: &BytesInput| {
+                let target = input.target_bytes();
+                let buf = target.as_slice();
+                $harness_bytes(buf);
+                ExitKind::Ok
+            };
+
+        let emulator = Emulator::empty().qemu($qemu).modules(modules).build()?;
+
+        let $executor = QemuExecutor::new(
+            emulator,
+            &mut harness,
+            tuple_list!($edges_observer, $time_observer),
+            &mut $fuzzer,
+            &mut $state,
+            &mut $mgr,
+            $timeout,
+        )?;
+        qemu_executor!(@wrap $kind, $executor, $cmplog_observer);
+    };
+}
 
 /// Sugar to create a `libfuzzer`-style fuzzer that uses
 /// `QEMU`-based binary-only instrumentation
@@ -85,6 +164,13 @@ where
     /// Fuzz `iterations` number of times, instead of indefinitely; implies use of `fuzz_loop_for`
     #[builder(default = None)]
     iterations: Option<u64>,
+    /// Give the clients diverse roles, with different power schedules and mutators, see [`crate::ensemble`].
+    /// One client uses `CmpLog`, unless `use_cmplog` is `false`.
+    #[builder(setter(strip_bool))]
+    ensemble: bool,
+    /// Extra directories the sync client of the ensemble imports new inputs from, e.g. the queues of other fuzzers
+    #[builder(default = &[])]
+    sync_dirs: &'a [PathBuf],
 }
 
 impl<H> Debug for QemuBytesCoverageSugar<'_, H>
@@ -111,6 +197,8 @@ where
                 },
             )
             .field("iterations", &self.iterations)
+            .field("ensemble", &self.ensemble)
+            .field("sync_dirs", &self.sync_dirs)
             .finish()
     }
 }
@@ -154,9 +242,18 @@ where
 
         let mut run_client = |state: Option<_>,
                               mut mgr: LlmpRestartingEventManager<_, _, _>,
-                              _core_id| {
+                              core_id| {
             let time_observer = time_observer.clone();
 
+            // The role of this client, if fuzzing as ensemble
+            let role = self.ensemble.then(|| {
+                EnsembleRole::for_client(
+                    self.cores.position(core_id).unwrap_or(0),
+                    self.use_cmplog.unwrap_or(true)
+                        && cfg!(not(any(feature = "mips", feature = "hexagon"))),
+                )
+            });
+
             // Create an observation channel using the coverage map
             let mut edges_observer = unsafe {
                 HitcountsMapObserver::new(VariableMapObserver::from_mut_slice(
@@ -205,6 +302,95 @@ where
                 }
             }
 
+            if let Some(role) = role {
+                log::info!("Fuzzing as ensemble client with role {role:?}");
+
+                // Calibrate the new testcases, for the power schedule
+                let calibration = CalibrationStage::new(&MaxMapFeedback::new(&edges_observer));
+
+                // A minimization+power queue policy, with the power schedule of the role
+                let scheduler = IndexesLenTimeMinimizerScheduler::new(
+                    &edges_observer,
+                    PowerQueueScheduler::new(&mut state, &edges_observer, role.schedule),
+                );
+
+                // A fuzzer with feedbacks and a corpus scheduler
+                let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
+
+                // Import new inputs from disk, if this is the sync client
+                let sync = SyncFromDiskStage::with_from_file(
+                    role.sync_dirs(self.input_dirs, self.sync_dirs),
+                    SYNC_INTERVAL,
+                );
+
+                if role.cmplog {
+                    qemu_executor!(
+                        cmplog,
+                        executor,
+                        qemu,
+                        harness_bytes,
+                        edges_observer,
+                        time_observer,
+                        cmplog_observer,
+                        fuzzer,
+                        state,
+                        mgr,
+                        timeout
+                    );
+
+                    load_initial_inputs!(state, fuzzer, executor, mgr, self.input_dirs);
+
+                    // Setup a tracing stage in which we log comparisons
+                    let tracing = ShadowTracingStage::new(&mut executor);
+
+                    // Setup a randomic Input2State stage
+                    let i2s = StdMutationalStage::new(StdScheduledMutator::new(tuple_list!(
+                        I2SRandReplace::new()
+                    )));
+
+                    ensemble_fuzz_loop!(
+                        role,
+                        fuzzer,
+                        executor,
+                        state,
+                        mgr,
+                        self.iterations,
+                        calibration,
+                        sync,
+                        tracing,
+                        i2s
+                    );
+                } else {
+                    qemu_executor!(
+                        plain,
+                        executor,
+                        qemu,
+                        harness_bytes,
+                        edges_observer,
+                        time_observer,
+                        cmplog_observer,
+                        fuzzer,
+                        state,
+                        mgr,
+                        timeout
+                    );
+
+                    load_initial_inputs!(state, fuzzer, executor, mgr, self.input_dirs);
+
+                    ensemble_fuzz_loop!(
+                        role,
+                        fuzzer,
+                        executor,
+                        state,
+                        mgr,
+                        self.iterations,
+                        calibration,
+                        sync
+                    );
+                }
+                return Ok(());
+            }
+
             // A minimization+queue policy to get testcasess from the corpus
             let scheduler =
                 IndexesLenTimeMinimizerScheduler::new(&edges_observer, QueueScheduler::new());
@@ -214,84 +400,21 @@ where
 
             // The wrapped harness function, calling out to the LLVM-style harness
             if self.use_cmplog.unwrap_or(false) {
-                let modules = {
-                    #[cfg(not(any(feature = "mips", feature = "hexagon")))]
-                    {
-                        tuple_list!(
-                            StdEdgeCoverageModule::builder()
-                                .map_observer(edges_observer.as_mut())
-                                .build()
-                                .unwrap(),
-                            CmpLogModule::default(),
-                        )
-                    }
-                    #[cfg(any(feature = "mips", feature = "hexagon"))]
-                    {
-                        tuple_list!(StdEdgeCoverageModule::builder()
-                            .map_observer(edges_observer.as_mut())
-                            .build()
-                            .unwrap())
-                    }
-                };
-
-                let mut harness = |_emulator: &mut Emulator<_, _, _, _, _>,
-                                   _state: &mut _,
-                                   input: &BytesInput| {
-                    let target = input.target_bytes();
-                    let buf = target.as_slice();
-                    harness_bytes(buf);
-                    ExitKind::Ok
-                };
-
-                let emulator = Emulator::empty().qemu(qemu).modules(modules).build()?;
-
-                let executor = QemuExecutor::new(
-                    emulator,
-                    &mut harness,
-                    tuple_list!(edges_observer, time_observer),
-                    &mut fuzzer,
-                    &mut state,
-                    &mut mgr,
-                    timeout,
-                )?;
-                let mut executor = ShadowExecutor::new(executor, tuple_list!(cmplog_observer));
-
-                // In case the corpus is empty (on first run), reset
-                if state.must_load_initial_inputs() {
-                    if self.input_dirs.is_empty() {
-                        // Generator of printable bytearrays of max size 32
-                        let mut generator = RandBytesGenerator::new(nonzero!(32));
-
-                        // Generate 8 initial inputs
-                        state
-                            .generate_initial_inputs(
-                                &mut fuzzer,
-                                &mut executor,
-                                &mut generator,
-                                &mut mgr,
-                                8,
-                            )
-                            .expect("Failed to generate the initial corpus");
-                        log::info!(
-                            "We imported {} inputs from the generator.",
-                            state.corpus().count()
-                        );
-                    } else {
-                        log::info!("Loading from {:?}", &self.input_dirs);
-                        // Load from disk
-                        state
-                            .load_initial_inputs(
-                                &mut fuzzer,
-                                &mut executor,
-                                &mut mgr,
-                                self.input_dirs,
-                            )
-                            .unwrap_or_else(|_| {
-                                panic!("Failed to load initial corpus at {:?}", &self.input_dirs);
-                            });
-                        log::info!("We imported {} inputs from disk.", state.corpus().count());
-                    }
-                }
+                qemu_executor!(
+                    cmplog,
+                    executor,
+                    qemu,
+                    harness_bytes,
+                    edges_observer,
+                    time_observer,
+                    cmplog_observer,
+                    fuzzer,
+                    state,
+                    mgr,
+                    timeout
+                );
+
+                load_initial_inputs!(state, fuzzer, executor, mgr, self.input_dirs);
 
                 // Setup a tracing stage in which we log comparisons
                 let tracing = ShadowTracingStage::new(&mut executor);
@@ -346,68 +469,21 @@ where
                     }
                 }
             } else {
-                let modules = tuple_list!(StdEdgeCoverageModule::builder()
-                    .map_observer(edges_observer.as_mut())
-                    .build()
-                    .unwrap());
-
-                let mut harness = |_emulator: &mut Emulator<_, _, _, _, _>,
-                                   _state: &mut _,
-                                   input: &BytesInput| {
-                    let target = input.target_bytes();
-                    let buf = target.as_slice();
-                    harness_bytes(buf);
-                    ExitKind::Ok
-                };
-
-                let emulator = Emulator::empty().qemu(qemu).modules(modules).build()?;
-
-                let mut executor = QemuExecutor::new(
-                    emulator,
-                    &mut harness,
-                    tuple_list!(edges_observer, time_observer),
-                    &mut fuzzer,
-                    &mut state,
-                    &mut mgr,
-                    timeout,
-                )?;
-
-                // In case the corpus is empty (on first run), reset
-                if state.must_load_initial_inputs() {
-                    if self.input_dirs.is_empty() {
-                        // Generator of printable bytearrays of max size 32
-                        let mut generator = RandBytesGenerator::new(nonzero!(32));
-
-                        // Generate 8 initial inputs
-                        state
-                            .generate_initial_inputs(
-                                &mut fuzzer,
-                                &mut executor,
-                                &mut generator,
-                                &mut mgr,
-                                8,
-                            )
-                            .expect("Failed to generate the initial corpus");
-                        log::info!(
-                            "We imported {} inputs from the generator.",
-                            state.corpus().count()
-                        );
-                    } else {
-                        log::info!("Loading from {:?}", &self.input_dirs);
-                        // Load from disk
-                        state
-                            .load_initial_inputs(
-                                &mut fuzzer,
-                                &mut executor,
-                                &mut mgr,
-                                self.input_dirs,
-                            )
-                            .unwrap_or_else(|_| {
-                                panic!("Failed to load initial corpus at {:?}", &self.input_dirs);
-                            });
-                        log::info!("We imported {} inputs from disk.", state.corpus().count());
-                    }
-                }
+                qemu_executor!(
+                    plain,
+                    executor,
+                    qemu,
+                    harness_bytes,
+                    edges_observer,
+                    time_observer,
+                    cmplog_observer,
+                    fuzzer,
+                    state,
+                    mgr,
+                    timeout
+                );
+
+                load_initial_inputs!(state, fuzzer, executor, mgr, self.input_dirs);
 
                 if self.tokens_file.is_some() {
                     // Setup a basic mutator
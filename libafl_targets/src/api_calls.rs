@@ -0,0 +1,249 @@
+//! Interpret the [`CallSequenceInput`]s of API-sequence fuzzing in the harness.
+//!
+//! Register a closure for each function of the [`libafl::inputs::calls::ApiSpec`], in the same order, and call
+//! [`ApiInterpreter::run`] from the harness. The closures get the arguments of each call, with the handles the
+//! earlier calls returned, and return the handle of their call, if any. The handles left at the end of a run are
+//! passed to the cleanup closure, so they do not leak between runs.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt::{self, Debug, Formatter};
+
+use libafl::{
+    executors::ExitKind,
+    inputs::calls::{CallArg, CallSequenceInput},
+};
+
+/// The value of an argument of a call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgValue<'a, H> {
+    /// An integer
+    Int(u64),
+    /// A byte buffer
+    Bytes(&'a [u8]),
+    /// The handle returned by an earlier call, `None` if that call returned none, or a releasing call released it
+    Handle(Option<&'a H>),
+}
+
+impl<'a, H> ArgValue<'a, H> {
+    /// The integer, if this is an integer
+    #[must_use]
+    pub fn as_int(&self) -> Option<u64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The byte buffer, if this is a byte buffer
+    #[must_use]
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Self::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// The handle, if this is a live handle
+    #[must_use]
+    pub fn as_handle(&self) -> Option<&'a H> {
+        match self {
+            Self::Handle(handle) => *handle,
+            _ => None,
+        }
+    }
+}
+
+/// The closure of a function, and if it releases its handle arguments
+struct ApiFunction<'a, H> {
+    call: Box<dyn FnMut(&[ArgValue<'_, H>]) -> Option<H> + 'a>,
+    releases: bool,
+}
+
+/// Runs the calls of [`CallSequenceInput`]s on the closures of the functions of the API.
+pub struct ApiInterpreter<'a, H> {
+    functions: Vec<ApiFunction<'a, H>>,
+    cleanup: Option<Box<dyn FnMut(H) + 'a>>,
+}
+
+impl<H> Debug for ApiInterpreter<'_, H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiInterpreter")
+            .field("functions", &self.functions.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H> Default for ApiInterpreter<'_, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, H> ApiInterpreter<'a, H> {
+    /// Creates a new [`ApiInterpreter`], without any function yet
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            functions: Vec::new(),
+            cleanup: None,
+        }
+    }
+
+    /// Adds the closure of the function with the next index
+    #[must_use]
+    pub fn with_function<F>(mut self, function: F) -> Self
+    where
+        F: FnMut(&[ArgValue<'_, H>]) -> Option<H> + 'a,
+    {
+        self.functions.push(ApiFunction {
+            call: Box::new(function),
+            releases: false,
+        });
+        self
+    }
+
+    /// Adds the closure of the function with the next index, which releases the handles it is passed, e.g.
+    /// `free` or `close`. The later calls get `None` for these handles, and they are not cleaned up.
+    #[must_use]
+    pub fn with_releasing_function<F>(mut self, function: F) -> Self
+    where
+        F: FnMut(&[ArgValue<'_, H>]) -> Option<H> + 'a,
+    {
+        self.functions.push(ApiFunction {
+            call: Box::new(function),
+            releases: true,
+        });
+        self
+    }
+
+    /// Sets the closure releasing the handles left at the end of a run, in the reverse order of their creation
+    #[must_use]
+    pub fn with_cleanup<F>(mut self, cleanup: F) -> Self
+    where
+        F: FnMut(H) + 'a,
+    {
+        self.cleanup = Some(Box::new(cleanup));
+        self
+    }
+
+    /// The number of functions
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    /// If there are no functions
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// Runs the calls of the input in order. Calls of unknown functions are skipped.
+    pub fn run(&mut self, input: &CallSequenceInput) -> ExitKind {
+        let mut handles: Vec<Option<H>> = Vec::with_capacity(input.calls().len());
+        for call in input.calls() {
+            let Some(function) = self.functions.get_mut(call.function) else {
+                handles.push(None);
+                continue;
+            };
+            let args: Vec<ArgValue<'_, H>> = call
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    CallArg::Int(value) => ArgValue::Int(*value),
+                    CallArg::Bytes(bytes) => ArgValue::Bytes(bytes),
+                    CallArg::Result(idx) => {
+                        ArgValue::Handle(handles.get(*idx).and_then(Option::as_ref))
+                    }
+                })
+                .collect();
+            let handle = (function.call)(&args);
+            drop(args);
+
+            if function.releases {
+                for idx in call.dependencies() {
+                    if let Some(released) = handles.get_mut(idx) {
+                        *released = None;
+                    }
+                }
+            }
+            handles.push(handle);
+        }
+
+        if let Some(cleanup) = &mut self.cleanup {
+            for handle in handles.into_iter().rev().flatten() {
+                cleanup(handle);
+            }
+        }
+        ExitKind::Ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+    use core::cell::{Cell, RefCell};
+
+    use libafl::{
+        executors::ExitKind,
+        inputs::calls::{Call, CallArg, CallSequenceInput},
+    };
+
+    use super::ApiInterpreter;
+
+    #[test]
+    fn test_api_interpreter_run() {
+        let next_fd = Cell::new(10_u32);
+        let log = RefCell::new(Vec::new());
+        let mut interpreter = ApiInterpreter::new()
+            // open(name) -> fd
+            .with_function(|_args| {
+                let fd = next_fd.get();
+                next_fd.set(fd + 1);
+                Some(fd)
+            })
+            // write(fd, data)
+            .with_function(|args| {
+                let data = args[1].as_bytes().unwrap();
+                log.borrow_mut()
+                    .push(("write", args[0].as_handle().copied(), data.len()));
+                None
+            })
+            // close(fd)
+            .with_releasing_function(|args| {
+                log.borrow_mut()
+                    .push(("close", args[0].as_handle().copied(), 0));
+                None
+            })
+            .with_cleanup(|fd| log.borrow_mut().push(("cleanup", Some(fd), 0)));
+        assert_eq!(interpreter.len(), 3);
+
+        let input = CallSequenceInput::new(vec![
+            Call::new(0, vec![CallArg::Bytes(b"a".to_vec())]),
+            Call::new(0, vec![CallArg::Bytes(b"b".to_vec())]),
+            Call::new(1, vec![CallArg::Result(0), CallArg::Bytes(b"x".to_vec())]),
+            Call::new(2, vec![CallArg::Result(0)]),
+            // The handle was released by the close
+            Call::new(1, vec![CallArg::Result(0), CallArg::Bytes(b"yy".to_vec())]),
+            // Unknown functions are skipped, and return no handle
+            Call::new(7, vec![CallArg::Int(1)]),
+            Call::new(1, vec![CallArg::Result(5), CallArg::Bytes(vec![])]),
+            Call::new(0, vec![CallArg::Bytes(b"c".to_vec())]),
+        ]);
+        assert_eq!(interpreter.run(&input), ExitKind::Ok);
+        drop(interpreter);
+
+        assert_eq!(
+            log.into_inner(),
+            vec![
+                ("write", Some(10), 1),
+                ("close", Some(10), 0),
+                ("write", None, 2),
+                ("write", None, 0),
+                // The open handles, newest first
+                ("cleanup", Some(12), 0),
+                ("cleanup", Some(11), 0),
+            ]
+        );
+    }
+}
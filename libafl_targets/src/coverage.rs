@@ -18,7 +18,7 @@ use core::ptr::addr_of;
 use core::ptr::addr_of_mut;
 
 #[cfg(any(target_os = "linux", target_vendor = "apple"))]
-use libafl::{mutators::Tokens, Error};
+use libafl::{mutators::Tokens, Error, HasMetadata};
 
 use crate::{ACCOUNTING_MAP_SIZE, DDG_MAP_SIZE, EDGES_MAP_ALLOCATED_SIZE, EDGES_MAP_DEFAULT_SIZE};
 
@@ -83,6 +83,22 @@ pub fn autotokens() -> Result<Tokens, Error> {
     }
 }
 
+/// Add the tokens from the compile-time token section to the [`Tokens`] metadata of the `state`,
+/// like AFL++'s autodictionary. Call this once at startup, before fuzzing.
+///
+/// Returns the number of tokens that were not known before.
+#[cfg(any(target_os = "linux", target_vendor = "apple"))]
+pub fn add_autotokens_to_state<S>(state: &mut S) -> Result<usize, Error>
+where
+    S: HasMetadata,
+{
+    let autotokens = autotokens()?;
+    let tokens = state.metadata_or_insert_with(Tokens::new);
+    let before = tokens.len();
+    tokens.add_tokens(autotokens.tokens());
+    Ok(tokens.len() - before)
+}
+
 /// The actual size we use for the map of edges.
 /// This is used for forkserver backend
 #[allow(non_upper_case_globals)]
@@ -95,7 +111,7 @@ pub static mut __afl_map_size: usize = EDGES_MAP_DEFAULT_SIZE;
     feature = "sancov_ngram4",
     feature = "sancov_ctx"
 ))]
-use libafl::observers::StdMapObserver;
+use libafl::observers::{StdMapObserver, VariableMapObserver};
 #[cfg(any(
     feature = "sancov_pcguard_edges",
     feature = "sancov_pcguard_hitcounts",
@@ -162,6 +178,56 @@ where
     StdMapObserver::from_mut_slice(name, edges_map_mut_slice())
 }
 
+/// Gets a new [`StdMapObserver`] of the coverage of functions, one entry per function.
+///
+/// Either build the target with `-fsanitize-coverage=func,trace-pc-guard`, which only instruments the entry block
+/// of each function, or with `-fsanitize-coverage=trace-pc-guard,pc-table` and use the `sancov_pcguard_functions`
+/// feature, which numbers the guards per function. For huge targets, whose edges maps exceed the caches, this trades
+/// the precision of edges for throughput. The size of the map is the number of functions, so create the observer
+/// after the target was initialized.
+///
+/// # Safety
+/// This will dereference [`edges_map_mut_ptr`] and crash if it is not a valid address.
+#[cfg(any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts"))]
+pub unsafe fn std_functions_map_observer<'a, S>(name: S) -> StdMapObserver<'a, u8, false>
+where
+    S: Into<Cow<'static, str>>,
+{
+    let functions = MAX_EDGES_FOUND;
+    assert!(
+        functions > 0,
+        "No instrumented functions found, create the observer after the target was initialized"
+    );
+    StdMapObserver::from_mut_slice(
+        name,
+        OwnedMutSlice::from_raw_parts_mut(edges_map_mut_ptr(), functions),
+    )
+}
+
+/// Gets a new [`VariableMapObserver`] over the whole edges map, with a length following [`MAX_EDGES_FOUND`].
+///
+/// The length of a [`std_edges_map_observer`] is fixed when it is created.
+/// This observer also covers the edges of modules that are initialized later (e.g. loaded with `dlopen`),
+/// as the SanCov guard init increases [`MAX_EDGES_FOUND`], while feedbacks never look at the unused rest of the map.
+///
+/// # Safety
+/// This will dereference [`edges_map_mut_ptr`], for up to [`EDGES_MAP_ALLOCATED_SIZE`] entries,
+/// and [`MAX_EDGES_FOUND`].
+#[cfg(any(
+    feature = "sancov_pcguard_edges",
+    feature = "sancov_pcguard_hitcounts",
+    feature = "sancov_ngram4",
+    feature = "sancov_ctx"
+))]
+pub unsafe fn dynamic_edges_map_observer<'a>(name: &'static str) -> VariableMapObserver<'a, u8> {
+    VariableMapObserver::from_mut_ptr(
+        name,
+        edges_map_mut_ptr(),
+        EDGES_MAP_ALLOCATED_SIZE,
+        addr_of_mut!(MAX_EDGES_FOUND),
+    )
+}
+
 /// Gets the current edges map pt
 /// It will usually take `EDGES_MAP`, but `EDGES_MAP_PTR`,
 /// if built with the `pointer_maps` feature.
@@ -0,0 +1,409 @@
+//! Coverage reports of a corpus, in [`lcov`](https://github.com/linux-test-project/lcov) format or as an HTML summary.
+//!
+//! The [`CoverageCollector`] replays inputs in-process, on a target built with
+//! `-fsanitize-coverage=trace-pc-guard,pc-table` (and without the `sancov_ngram4` or `sancov_ctx` features,
+//! so that each entry of the edges map is one guard). It counts the inputs hitting each edge, then
+//! [`CoverageCollector::report`] maps the edges back to source lines, using the `SanitizerCoverage` PC table
+//! and `llvm-symbolizer`, see [`crate::sancov_symbolize`]. The target needs debug info for the lines,
+//! e.g. `-g` or `-gline-tables-only`.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Write as _, ptr::addr_of};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use libafl::Error;
+
+use crate::{
+    edges_map_mut_ptr,
+    sancov_symbolize::{module_offset, symbolize_module, SourceLocation},
+    sanitizer_cov_pc_table, MAX_EDGES_FOUND,
+};
+
+/// Replays inputs on the instrumented target, and counts how many inputs hit each edge
+#[derive(Debug, Default)]
+pub struct CoverageCollector {
+    hits: Vec<u64>,
+    inputs: usize,
+}
+
+impl CoverageCollector {
+    /// Create a new [`CoverageCollector`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of inputs run so far
+    #[must_use]
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    /// The number of edges hit by at least one input
+    #[must_use]
+    pub fn edges_hit(&self) -> usize {
+        self.hits.iter().filter(|hits| **hits > 0).count()
+    }
+
+    /// Run the harness on one input, and record the edges it hits
+    pub fn run<H>(&mut self, harness: &mut H, input: &[u8])
+    where
+        H: FnMut(&[u8]),
+    {
+        // # Safety
+        // The edges map is only written by the instrumentation of the harness, which does not run concurrently.
+        let map = unsafe {
+            let len = *addr_of!(MAX_EDGES_FOUND);
+            core::slice::from_raw_parts_mut(edges_map_mut_ptr(), len)
+        };
+        map.fill(0);
+        harness(input);
+
+        if self.hits.len() < map.len() {
+            self.hits.resize(map.len(), 0);
+        }
+        for (hits, entry) in self.hits.iter_mut().zip(map.iter()) {
+            if *entry != 0 {
+                *hits += 1;
+            }
+        }
+        self.inputs += 1;
+    }
+
+    /// Run the harness on all files in the given directories, e.g. the queue of a campaign.
+    ///
+    /// Hidden files, such as the metadata files of an `OnDiskCorpus`, are skipped.
+    /// An input crashing the target ends the replay, so crashes should not be in these directories.
+    /// Returns the number of inputs run.
+    pub fn run_dirs<H>(&mut self, harness: &mut H, dirs: &[PathBuf]) -> Result<usize, Error>
+    where
+        H: FnMut(&[u8]),
+    {
+        let mut count = 0;
+        for dir in dirs {
+            let mut paths = fs::read_dir(dir)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            paths.sort();
+            for path in paths {
+                let hidden = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                if hidden || !path.is_file() {
+                    continue;
+                }
+                log::debug!("Replaying {}", path.display());
+                let input = fs::read(&path)?;
+                self.run(harness, &input);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Map the edges to source lines, and build a [`CoverageReport`].
+    ///
+    /// Edges whose module or line can not be resolved are left out of the report.
+    pub fn report(&self) -> Result<CoverageReport, Error> {
+        let pcs: Vec<(usize, bool)> = sanitizer_cov_pc_table()
+            .flatten()
+            .map(|entry| (entry.addr(), entry.is_function_entry()))
+            .collect();
+        if pcs.is_empty() {
+            return Err(Error::illegal_state(
+                "No SanitizerCoverage PC table registered, build the target with -fsanitize-coverage=pc-table",
+            ));
+        }
+
+        // Group the PCs by module, to run the symbolizer once per module
+        let mut modules: BTreeMap<PathBuf, Vec<(usize, usize)>> = BTreeMap::new();
+        for (idx, (pc, _)) in pcs.iter().enumerate() {
+            if let Some((module, offset)) = module_offset(*pc) {
+                modules.entry(module).or_default().push((idx, offset));
+            }
+        }
+
+        let mut report = CoverageReport::default();
+        for (module, entries) in modules {
+            let offsets: Vec<usize> = entries.iter().map(|(_, offset)| *offset).collect();
+            let symbols = symbolize_module(&module, &offsets)?;
+            for ((idx, _), (_, location)) in entries.iter().zip(symbols) {
+                let Some(location) = location else {
+                    continue;
+                };
+                let hits = self.hits.get(*idx).copied().unwrap_or(0);
+                report.add(location, hits, pcs[*idx].1);
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// The coverage of a function
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    /// The line of the function entry
+    pub line: u32,
+    /// The number of inputs calling the function
+    pub hits: u64,
+}
+
+/// The coverage of a source file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileCoverage {
+    /// The number of inputs hitting each instrumented line
+    pub lines: BTreeMap<u32, u64>,
+    /// The instrumented functions, by name
+    pub functions: BTreeMap<String, FunctionCoverage>,
+}
+
+impl FileCoverage {
+    /// The number of lines hit by at least one input
+    #[must_use]
+    pub fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|hits| **hits > 0).count()
+    }
+
+    /// The number of functions called by at least one input
+    #[must_use]
+    pub fn functions_hit(&self) -> usize {
+        self.functions.values().filter(|f| f.hits > 0).count()
+    }
+}
+
+/// The line coverage of a corpus, per source file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    files: BTreeMap<String, FileCoverage>,
+}
+
+impl CoverageReport {
+    /// The coverage of each source file
+    #[must_use]
+    pub fn files(&self) -> &BTreeMap<String, FileCoverage> {
+        &self.files
+    }
+
+    /// Add the hits of an edge at `location`. If it is a function entry, the hits also count for the function.
+    pub fn add(&mut self, location: SourceLocation, hits: u64, function_entry: bool) {
+        let file = self.files.entry(location.file).or_default();
+        let line = file.lines.entry(location.line).or_default();
+        *line = (*line).max(hits);
+        if function_entry {
+            let function = file
+                .functions
+                .entry(location.function)
+                .or_insert(FunctionCoverage {
+                    line: location.line,
+                    hits: 0,
+                });
+            function.hits = function.hits.max(hits);
+        }
+    }
+
+    /// The number of instrumented lines, and the number of lines hit
+    #[must_use]
+    pub fn lines_summary(&self) -> (usize, usize) {
+        self.files.values().fold((0, 0), |(found, hit), file| {
+            (found + file.lines.len(), hit + file.lines_hit())
+        })
+    }
+
+    /// The report in `lcov` tracefile format, e.g. for `genhtml`
+    #[must_use]
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for (path, file) in &self.files {
+            writeln!(out, "TN:\nSF:{path}").unwrap();
+            for (name, function) in &file.functions {
+                writeln!(out, "FN:{},{name}", function.line).unwrap();
+            }
+            for (name, function) in &file.functions {
+                writeln!(out, "FNDA:{},{name}", function.hits).unwrap();
+            }
+            writeln!(out, "FNF:{}", file.functions.len()).unwrap();
+            writeln!(out, "FNH:{}", file.functions_hit()).unwrap();
+            for (line, hits) in &file.lines {
+                writeln!(out, "DA:{line},{hits}").unwrap();
+            }
+            writeln!(out, "LF:{}", file.lines.len()).unwrap();
+            writeln!(out, "LH:{}", file.lines_hit()).unwrap();
+            out.push_str("end_of_record\n");
+        }
+        out
+    }
+
+    /// A self-contained HTML page, summarizing the line and function coverage per file
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        let (lines_found, lines_hit) = self.lines_summary();
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>LibAFL coverage report</title>\n\
+             <style>body{font-family:sans-serif}table{border-collapse:collapse}\
+             td,th{border:1px solid #ccc;padding:2px 8px}td.num{text-align:right}</style></head><body>\n",
+        );
+        writeln!(
+            out,
+            "<h1>Coverage report</h1>\n<p>{lines_hit} of {lines_found} lines hit ({})</p>",
+            percent(lines_hit, lines_found)
+        )
+        .unwrap();
+        out.push_str(
+            "<table>\n<tr><th>File</th><th>Lines hit</th><th>Lines</th><th>%</th>\
+             <th>Functions hit</th><th>Functions</th></tr>\n",
+        );
+        for (path, file) in &self.files {
+            writeln!(
+                out,
+                "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td>\
+                 <td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+                escape_html(path),
+                file.lines_hit(),
+                file.lines.len(),
+                percent(file.lines_hit(), file.lines.len()),
+                file.functions_hit(),
+                file.functions.len()
+            )
+            .unwrap();
+        }
+        out.push_str("</table>\n</body></html>\n");
+        out
+    }
+
+    /// Parse a report from an `lcov` tracefile, e.g. one written by [`Self::write_lcov`].
+    ///
+    /// Only the line and function records are read; the summary records are recomputed from them.
+    pub fn from_lcov(lcov: &str) -> Result<Self, Error> {
+        fn parse<T: core::str::FromStr>(value: &str, line: &str) -> Result<T, Error> {
+            value
+                .parse()
+                .map_err(|_| Error::illegal_argument(format!("Invalid lcov record: {line}")))
+        }
+
+        let mut report = Self::default();
+        let mut file: Option<&mut FileCoverage> = None;
+        for line in lcov.lines() {
+            let (record, value) = line.split_once(':').unwrap_or((line, ""));
+            if record == "SF" {
+                file = Some(report.files.entry(value.to_string()).or_default());
+                continue;
+            }
+            if record == "end_of_record" {
+                file = None;
+                continue;
+            }
+            let Some(file) = file.as_deref_mut() else {
+                continue;
+            };
+            match record {
+                "FN" => {
+                    let (fn_line, name) = value.split_once(',').ok_or_else(|| {
+                        Error::illegal_argument(format!("Invalid lcov record: {line}"))
+                    })?;
+                    file.functions.entry(name.to_string()).or_default().line =
+                        parse(fn_line, line)?;
+                }
+                "FNDA" => {
+                    let (hits, name) = value.split_once(',').ok_or_else(|| {
+                        Error::illegal_argument(format!("Invalid lcov record: {line}"))
+                    })?;
+                    file.functions.entry(name.to_string()).or_default().hits = parse(hits, line)?;
+                }
+                "DA" => {
+                    let mut parts = value.split(',');
+                    let da_line = parse(parts.next().unwrap_or_default(), line)?;
+                    let hits = parse(parts.next().unwrap_or_default(), line)?;
+                    file.lines.insert(da_line, hits);
+                }
+                _ => {}
+            }
+        }
+        Ok(report)
+    }
+
+    /// Write the report in `lcov` tracefile format
+    pub fn write_lcov<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.to_lcov())?;
+        Ok(())
+    }
+
+    /// Write the HTML summary of the report
+    pub fn write_html<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.to_html())?;
+        Ok(())
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn percent(hit: usize, found: usize) -> String {
+    if found == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}%", hit as f64 * 100.0 / found as f64)
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoverageReport;
+    use crate::sancov_symbolize::SourceLocation;
+
+    fn location(file: &str, line: u32, function: &str) -> SourceLocation {
+        SourceLocation {
+            file: file.into(),
+            line,
+            function: function.into(),
+        }
+    }
+
+    #[test]
+    fn test_lcov_round_trip() {
+        let mut report = CoverageReport::default();
+        report.add(location("src/main.c", 3, "main"), 5, true);
+        report.add(location("src/main.c", 4, "main"), 5, false);
+        report.add(location("src/main.c", 8, "main"), 0, false);
+        report.add(location("src/parse.c", 10, "parse"), 2, true);
+        report.add(location("src/parse.c", 12, "parse"), 1, false);
+        report.add(location("src/parse.c", 20, "unused"), 0, true);
+        // The same line hit through another edge keeps the maximum
+        report.add(location("src/parse.c", 12, "parse"), 2, false);
+
+        assert_eq!(report.lines_summary(), (6, 4));
+        let parse = &report.files()["src/parse.c"];
+        assert_eq!(parse.lines[&12], 2);
+        assert_eq!(parse.functions_hit(), 1);
+
+        let lcov = report.to_lcov();
+        assert!(lcov.contains("SF:src/parse.c\n"));
+        assert!(lcov.contains("FN:20,unused\n"));
+        assert!(lcov.contains("FNDA:2,parse\n"));
+        assert!(lcov.contains("DA:12,2\n"));
+        assert!(lcov.contains("LF:3\nLH:2\n"));
+
+        let parsed = CoverageReport::from_lcov(&lcov).unwrap();
+        assert_eq!(parsed, report);
+        assert_eq!(parsed.to_lcov(), lcov);
+
+        assert!(CoverageReport::from_lcov("SF:a.c\nDA:x,1\nend_of_record\n").is_err());
+    }
+}
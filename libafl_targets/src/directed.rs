@@ -0,0 +1,33 @@
+//! The runtime of the `DirectedDistance` pass of `libafl_cc`, for directed fuzzing as in `AFLGo`.
+//!
+//! Each instrumented basic block adds its static distance to the target sites to
+//! [`__libafl_directed_distance_sum`], and increments [`__libafl_directed_distance_count`].
+//! [`directed_distance_observer`] creates a [`DistanceObserver`] on these counters.
+
+use core::ptr::addr_of_mut;
+
+use libafl::observers::DistanceObserver;
+use libafl_bolts::ownedref::OwnedMutPtr;
+
+/// The sum of the distances of the basic blocks executed in this run
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static mut __libafl_directed_distance_sum: u64 = 0;
+
+/// The number of basic blocks executed in this run that can reach a target site
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static mut __libafl_directed_distance_count: u64 = 0;
+
+/// Creates a [`DistanceObserver`] on the counters of the `DirectedDistance` pass
+///
+/// # Safety
+/// The observer resets and reads the global counters, which the instrumented target may not update concurrently.
+#[must_use]
+pub unsafe fn directed_distance_observer(name: &'static str) -> DistanceObserver {
+    DistanceObserver::new(
+        name,
+        OwnedMutPtr::from_raw_mut(addr_of_mut!(__libafl_directed_distance_sum)),
+        OwnedMutPtr::from_raw_mut(addr_of_mut!(__libafl_directed_distance_count)),
+    )
+}
@@ -16,6 +16,9 @@
 #endif
 #include <sys/wait.h>
 #include <sys/types.h>
+#ifdef __linux__
+  #include <sys/prctl.h>
+#endif
 
 #define write_error(s) \
   fprintf(stderr, "Error at %s:%d: %s\n", __FILE__, __LINE__, s)
@@ -26,6 +29,7 @@
 #define SHMEM_FUZZ_HDR_SIZE 4
 #define SHM_ENV_VAR "__AFL_SHM_ID"
 #define SHM_FUZZ_ENV_VAR "__AFL_SHM_FUZZ_ID"
+#define FORK_AWARE_ENV_VAR "LIBAFL_FORK_AWARE_COVERAGE"
 #define DEFAULT_PERMISSION 0600
 
 /* Reporting errors */
@@ -81,6 +85,9 @@ static void (*old_sigterm_handler)(int) = 0;
 
 static uint8_t is_persistent;
 
+/* Wait for the processes the child forked, so their coverage is complete */
+static uint8_t is_fork_aware;
+
 void __afl_set_persistent_mode(uint8_t mode) {
   is_persistent = mode;
 }
@@ -107,6 +114,16 @@ static void at_exit(int signal) {
   _exit(0);
 }
 
+/* Wait until all processes forked by the child are gone. They share its
+   process group, and, on Linux, are reparented to us once the child exits. */
+
+static void wait_for_descendants(pid_t pgid) {
+  while (kill(-pgid, 0) == 0) {
+    while (waitpid(-pgid, NULL, WNOHANG) > 0) {}
+    usleep(50);
+  }
+}
+
 /* SHM fuzzing setup. */
 
 void __afl_map_shm(void) {
@@ -297,6 +314,16 @@ void __afl_start_forkserver(void) {
 
   if (__afl_sharedmem_fuzzing) { map_input_shared_memory(); }
 
+  if (getenv(FORK_AWARE_ENV_VAR)) {
+    is_fork_aware = 1;
+#ifdef __linux__
+    // Orphaned descendants of the child become ours, so we can reap them
+    if (prctl(PR_SET_CHILD_SUBREAPER, 1) < 0) {
+      write_error("prctl(PR_SET_CHILD_SUBREAPER)");
+    }
+#endif
+  }
+
   while (1) {
     int status;
 
@@ -341,11 +368,18 @@ void __afl_start_forkserver(void) {
         signal(SIGCHLD, old_sigchld_handler);
         signal(SIGTERM, old_sigterm_handler);
 
+        // Lead a new process group, so the fuzzer can kill all descendants
+        if (is_fork_aware) { setpgid(0, 0); }
+
         close(FORKSRV_FD);
         close(FORKSRV_FD + 1);
         return;
       }
 
+      // Set it from here as well, in case the fuzzer kills the group before
+      // the child ran
+      if (is_fork_aware) { setpgid(child_pid, child_pid); }
+
     } else {
       /* Special handling for persistent mode: if the child is alive but
          currently stopped, simply restart it with SIGCONT. */
@@ -370,7 +404,13 @@ void __afl_start_forkserver(void) {
        a successful run. In this case, we want to wake it up without forking
        again. */
 
-    if (WIFSTOPPED(status)) child_stopped = 1;
+    if (WIFSTOPPED(status)) {
+      child_stopped = 1;
+    } else if (is_fork_aware) {
+      /* The forked processes write to the shared map until they exit, the
+         status is only relayed afterwards. */
+      wait_for_descendants(child_pid);
+    }
 
     /* Relay wait status to pipe, then loop back. */
 
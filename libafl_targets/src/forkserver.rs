@@ -18,6 +18,10 @@ pub fn map_shared_memory() {
 
 /// Start the forkserver from this point. Any shared memory must be created before.
 ///
+/// If `LIBAFL_FORK_AWARE_COVERAGE` is set, e.g. by `ForkserverExecutorBuilder::fork_aware_coverage`,
+/// the forkserver waits for all processes forked by each run before it reports the exit status,
+/// so that their coverage ends up in the map as well.
+///
 /// # Note
 ///
 /// The forkserver logic is written in C and this code is a wrapper.
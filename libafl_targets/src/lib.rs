@@ -107,6 +107,16 @@ pub use coverage::*;
 pub mod value_profile;
 pub use value_profile::*;
 
+/// The interpreter of API call sequences in the harness
+pub mod api_calls;
+pub use api_calls::{ApiInterpreter, ArgValue};
+
+/// The runtime of the directed distance pass
+#[cfg(feature = "directed")]
+pub mod directed;
+#[cfg(feature = "directed")]
+pub use directed::*;
+
 /// The module to hook call instructions
 #[cfg(feature = "function-logging")]
 pub mod call;
@@ -120,6 +130,28 @@ pub use cmps::*;
 #[cfg(feature = "std")]
 pub mod drcov;
 
+#[cfg(all(
+    feature = "std",
+    feature = "coverage",
+    target_os = "linux",
+    any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts")
+))]
+pub mod coverage_report;
+
+#[cfg(all(
+    feature = "std",
+    target_os = "linux",
+    any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts")
+))]
+pub mod sancov_symbolize;
+
+#[cfg(all(
+    feature = "std",
+    target_os = "linux",
+    any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts")
+))]
+pub mod patch;
+
 #[cfg(all(windows, feature = "std", feature = "windows_asan"))]
 pub mod windows_asan;
 #[cfg(all(windows, feature = "std", feature = "windows_asan"))]
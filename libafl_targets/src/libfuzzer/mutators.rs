@@ -202,6 +202,9 @@ where
 /// controls whether this mutator invokes `LLVMFuzzerCustomMutate` and `LLVMFuzzerCustomCrossover`.
 /// You should avoid using crossover-like mutators with custom mutators as this may lead to the
 /// injection of some input portions to another in ways which violate structure.
+///
+/// Each call invokes the custom function exactly once, so this mutator can also be merged into the
+/// mutations of a [`libafl::mutators::StdScheduledMutator`], to be scheduled along with the havoc mutations.
 #[derive(Debug)]
 pub struct LLVMCustomMutator<S, SM, const CROSSOVER: bool> {
     mutator: Rc<RefCell<SM>>,
@@ -359,7 +362,7 @@ where
 
 impl<S, SM> Named for LLVMCustomMutator<S, SM, true> {
     fn name(&self) -> &Cow<'static, str> {
-        static NAME: Cow<'static, str> = Cow::Borrowed("LLVMCustomMutator");
+        static NAME: Cow<'static, str> = Cow::Borrowed("LLVMCustomCrossover");
         &NAME
     }
 }
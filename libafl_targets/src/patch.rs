@@ -0,0 +1,314 @@
+//! Target edges of a patch, for directed fuzzing toward recently changed code.
+//!
+//! The target has to be built with `-fsanitize-coverage=trace-pc-guard,pc-table` and debug info, e.g.
+//! `-gline-tables-only`, and without the `sancov_ngram4` or `sancov_ctx` features. [`edge_locations`] maps
+//! every edge of the edges map to its source line, see [`crate::sancov_symbolize`], and can be saved with
+//! [`write_edge_locations`], so later runs skip the symbolizer. [`ChangedLines`] parses a unified diff, e.g.
+//! the output of `git diff -U0`, and selects the edges on the changed lines, to use as the
+//! `libafl::feedbacks::TargetEdgesMetadata` of the state.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use libafl::Error;
+
+use crate::{
+    sancov_symbolize::{module_offset, symbolize_module, SourceLocation},
+    sanitizer_cov_pc_table,
+};
+
+/// The lines a patch added or changed, per file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangedLines {
+    files: BTreeMap<PathBuf, Vec<u32>>,
+}
+
+impl ChangedLines {
+    /// Parse a unified diff, taking the line numbers of the new version of each file.
+    ///
+    /// Added lines are changed, and so are the lines following removed lines, as the code around them changed.
+    #[must_use]
+    pub fn from_unified_diff(diff: &str) -> Self {
+        let mut changed = Self::default();
+        let mut file: Option<PathBuf> = None;
+        let (mut line, mut old_left, mut new_left) = (0, 0, 0);
+        for text in diff.lines() {
+            if old_left > 0 || new_left > 0 {
+                // In a hunk, counting the lines of deleted files, too, to find the end of the hunk
+                let added = text.starts_with('+');
+                if added || text.starts_with('-') {
+                    if let Some(file) = &file {
+                        changed.add(file.clone(), line);
+                    }
+                    if added {
+                        line += 1;
+                        new_left = new_left.saturating_sub(1);
+                    } else {
+                        old_left = old_left.saturating_sub(1);
+                    }
+                } else if !text.starts_with('\\') {
+                    line += 1;
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                }
+            } else if let Some(path) = text.strip_prefix("+++ ") {
+                let path = path.split('\t').next().unwrap_or_default().trim();
+                file = (path != "/dev/null")
+                    .then(|| PathBuf::from(path.strip_prefix("b/").unwrap_or(path)));
+            } else if let Some(hunk) = text.strip_prefix("@@ ") {
+                // `@@ -old_start,old_len +new_start,new_len @@`, the lengths default to 1
+                let mut ranges = hunk.split_whitespace().take(2).map(|range| {
+                    let mut parts = range.trim_start_matches(['-', '+']).split(',');
+                    let start: u32 = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+                    let len: u32 = parts
+                        .next()
+                        .map_or(Some(1), |s| s.parse().ok())
+                        .unwrap_or(0);
+                    (start, len)
+                });
+                let (_, old_len) = ranges.next().unwrap_or_default();
+                let (new_start, new_len) = ranges.next().unwrap_or_default();
+                (line, old_left, new_left) = (new_start, old_len, new_len);
+            }
+        }
+        changed
+    }
+
+    /// Read and parse a unified diff file
+    pub fn from_diff_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self::from_unified_diff(&fs::read_to_string(path)?))
+    }
+
+    /// Mark a line of a file as changed
+    pub fn add(&mut self, file: PathBuf, line: u32) {
+        if line == 0 {
+            return;
+        }
+        let lines = self.files.entry(file).or_default();
+        if let Err(pos) = lines.binary_search(&line) {
+            lines.insert(pos, line);
+        }
+    }
+
+    /// The changed lines of each file, sorted
+    #[must_use]
+    pub fn files(&self) -> &BTreeMap<PathBuf, Vec<u32>> {
+        &self.files
+    }
+
+    /// If no line changed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// If the source location is on a changed line.
+    ///
+    /// The paths of a diff are relative to the repository, while the debug info may have absolute paths,
+    /// so a file matches if its path ends with the path in the diff.
+    #[must_use]
+    pub fn contains(&self, location: &SourceLocation) -> bool {
+        let file = Path::new(&location.file);
+        self.files.iter().any(|(path, lines)| {
+            file.ends_with(path) && lines.binary_search(&location.line).is_ok()
+        })
+    }
+
+    /// The indices of the edges on changed lines
+    #[must_use]
+    pub fn target_edges(&self, locations: &[(usize, SourceLocation)]) -> Vec<usize> {
+        locations
+            .iter()
+            .filter(|(_, location)| self.contains(location))
+            .map(|(idx, _)| *idx)
+            .collect()
+    }
+}
+
+/// The source location of each edge of the edges map, using the PC tables and `llvm-symbolizer`.
+///
+/// Edges whose module or line can not be resolved are left out.
+pub fn edge_locations() -> Result<Vec<(usize, SourceLocation)>, Error> {
+    let pcs: Vec<usize> = sanitizer_cov_pc_table()
+        .flatten()
+        .map(|entry| entry.addr())
+        .collect();
+    if pcs.is_empty() {
+        return Err(Error::illegal_state(
+            "No SanitizerCoverage PC table registered, build the target with -fsanitize-coverage=pc-table",
+        ));
+    }
+
+    // Group the PCs by module, to run the symbolizer once per module
+    let mut modules: BTreeMap<PathBuf, Vec<(usize, usize)>> = BTreeMap::new();
+    for (idx, pc) in pcs.iter().enumerate() {
+        if let Some((module, offset)) = module_offset(*pc) {
+            modules.entry(module).or_default().push((idx, offset));
+        }
+    }
+
+    let mut locations = Vec::new();
+    for (module, entries) in modules {
+        let offsets: Vec<usize> = entries.iter().map(|(_, offset)| *offset).collect();
+        let symbols = symbolize_module(&module, &offsets)?;
+        for ((idx, _), (_, location)) in entries.into_iter().zip(symbols) {
+            if let Some(location) = location {
+                locations.push((idx, location));
+            }
+        }
+    }
+    locations.sort_by_key(|(idx, _)| *idx);
+    Ok(locations)
+}
+
+/// Write the source location of each edge to a file, one `index<TAB>file<TAB>line<TAB>function` line per edge
+pub fn write_edge_locations<P>(path: P, locations: &[(usize, SourceLocation)]) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let mut text = String::new();
+    for (idx, location) in locations {
+        writeln!(
+            text,
+            "{idx}\t{}\t{}\t{}",
+            location.file, location.line, location.function
+        )
+        .unwrap();
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
+
+/// Read the source locations of the edges from a file written by [`write_edge_locations`]
+pub fn read_edge_locations<P>(path: P) -> Result<Vec<(usize, SourceLocation)>, Error>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            let idx = fields.next().and_then(|idx| idx.parse().ok());
+            let file = fields.next();
+            let line_no = fields.next().and_then(|line_no| line_no.parse().ok());
+            let function = fields.next().unwrap_or_default();
+            match (idx, file, line_no) {
+                (Some(idx), Some(file), Some(line_no)) => Ok((
+                    idx,
+                    SourceLocation {
+                        file: file.to_string(),
+                        line: line_no,
+                        function: function.to_string(),
+                    },
+                )),
+                _ => Err(Error::illegal_argument(format!(
+                    "Invalid edge location in {}: {line}",
+                    path.display()
+                ))),
+            }
+        })
+        .collect()
+}
+
+/// The indices of the edges on the lines changed by the diff at `diff_path`, symbolizing the edges of the target
+pub fn target_edges_for_diff<P>(diff_path: P) -> Result<Vec<usize>, Error>
+where
+    P: AsRef<Path>,
+{
+    let changed = ChangedLines::from_diff_file(diff_path)?;
+    Ok(changed.target_edges(&edge_locations()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec, vec::Vec};
+    use std::path::PathBuf;
+
+    use super::ChangedLines;
+    use crate::sancov_symbolize::SourceLocation;
+
+    const DIFF: &str = "\
+diff --git a/src/a.c b/src/a.c
+index 1111111..2222222 100644
+--- a/src/a.c
++++ b/src/a.c
+@@ -3,3 +3,4 @@ int main() {
+ context3
+-old4
++new4
++new5
+ context6
+@@ -20,0 +22,2 @@ int main() {
++new22
++new23
+diff --git a/src/b.c b/src/b.c
+--- a/src/b.c
++++ b/src/b.c\t2024-01-01 00:00:00
+@@ -10,2 +10 @@
+-removed10
+ kept10
+\\ No newline at end of file
+diff --git a/gone.c b/gone.c
+deleted file mode 100644
+--- a/gone.c
++++ /dev/null
+@@ -1,2 +0,0 @@
+-gone1
+-gone2
+diff --git a/src/c.c b/src/c.c
+--- a/src/c.c
++++ b/src/c.c
+@@ -1 +1 @@
+-x
++y
+";
+
+    #[test]
+    fn test_changed_lines_from_unified_diff() {
+        let changed = ChangedLines::from_unified_diff(DIFF);
+        let files = changed
+            .files()
+            .iter()
+            .map(|(file, lines)| (file.clone(), lines.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![
+                (PathBuf::from("src/a.c"), vec![4, 5, 22, 23]),
+                (PathBuf::from("src/b.c"), vec![10]),
+                (PathBuf::from("src/c.c"), vec![1]),
+            ]
+        );
+
+        let location = |file: &str, line| SourceLocation {
+            file: file.to_string(),
+            line,
+            function: "main".to_string(),
+        };
+        assert!(changed.contains(&location("/home/user/project/src/a.c", 22)));
+        assert!(!changed.contains(&location("/home/user/project/src/a.c", 6)));
+        assert!(!changed.contains(&location("gone.c", 1)));
+        assert_eq!(
+            changed.target_edges(&[
+                (0, location("src/b.c", 10)),
+                (1, location("src/b.c", 11)),
+                (2, location("src/c.c", 1)),
+            ]),
+            vec![0, 2]
+        );
+        assert!(ChangedLines::from_unified_diff("").is_empty());
+    }
+}
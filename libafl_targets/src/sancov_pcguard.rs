@@ -3,6 +3,8 @@
 #[rustversion::nightly]
 #[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
 use core::simd::num::SimdUint;
+#[cfg(feature = "sancov_pcguard_atomic")]
+use core::sync::atomic::{AtomicU8, Ordering};
 use core::{
     mem::align_of,
     ptr::{addr_of, addr_of_mut},
@@ -71,6 +73,10 @@ pub static SHR_8: Ngram8 = Ngram8::from_array([1, 1, 1, 1, 1, 1, 1, 1]);
 
 static mut PC_TABLES: Vec<&'static [PcTableEntry]> = Vec::new();
 
+/// The guards of the module initialized last, until its PC table numbers them per function
+#[cfg(feature = "sancov_pcguard_functions")]
+static mut LAST_GUARDS: (*mut u32, *mut u32) = (core::ptr::null_mut(), core::ptr::null_mut());
+
 use alloc::vec::Vec;
 #[cfg(any(
     feature = "sancov_ngram4",
@@ -248,12 +254,19 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard(guard: *mut u32) {
         {
             EDGES_MAP_PTR.add(pos).write(1);
         }
-        #[cfg(feature = "sancov_pcguard_hitcounts")]
+        #[cfg(all(
+            feature = "sancov_pcguard_hitcounts",
+            not(feature = "sancov_pcguard_atomic")
+        ))]
         {
             let addr = EDGES_MAP_PTR.add(pos);
             let val = addr.read().wrapping_add(1);
             addr.write(val);
         }
+        #[cfg(feature = "sancov_pcguard_atomic")]
+        {
+            AtomicU8::from_ptr(EDGES_MAP_PTR.add(pos)).fetch_add(1, Ordering::Relaxed);
+        }
     }
     #[cfg(not(feature = "pointer_maps"))]
     {
@@ -262,11 +275,19 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard(guard: *mut u32) {
         {
             *(edges_map).get_unchecked_mut(pos) = 1;
         }
-        #[cfg(feature = "sancov_pcguard_hitcounts")]
+        #[cfg(all(
+            feature = "sancov_pcguard_hitcounts",
+            not(feature = "sancov_pcguard_atomic")
+        ))]
         {
             let val = (*edges_map.get_unchecked(pos)).wrapping_add(1);
             *edges_map.get_unchecked_mut(pos) = val;
         }
+        #[cfg(feature = "sancov_pcguard_atomic")]
+        {
+            // Processes forked by the target increment the same shared map concurrently
+            AtomicU8::from_ptr(edges_map.as_mut_ptr().add(pos)).fetch_add(1, Ordering::Relaxed);
+        }
     }
 }
 
@@ -285,6 +306,11 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard_init(mut start: *mut u32
         return;
     }
 
+    #[cfg(feature = "sancov_pcguard_functions")]
+    {
+        LAST_GUARDS = (start, stop);
+    }
+
     while start < stop {
         *start = MAX_EDGES_FOUND as u32;
         start = start.offset(1);
@@ -320,13 +346,49 @@ unsafe extern "C" fn __sanitizer_cov_pcs_init(pcs_beg: *const usize, pcs_end: *c
         "Unaligned PC Table - start: {pcs_beg:x?} end: {pcs_end:x?}"
     );
 
+    // Each entry is a pair of `usize`s
+    let table = slice::from_raw_parts(pcs_beg as *const PcTableEntry, len / 2);
     let pc_tables = &mut *addr_of_mut!(PC_TABLES);
-    pc_tables.push(slice::from_raw_parts(pcs_beg as *const PcTableEntry, len));
+    pc_tables.push(table);
+
+    #[cfg(feature = "sancov_pcguard_functions")]
+    number_guards_per_function(table);
+}
+
+/// Number the guards of the module initialized last per function, instead of per edge, using its PC table.
+///
+/// All guards of a function share the index of its entry block, so the edges map holds one entry per function,
+/// counting the blocks executed in it for hitcounts. The SanCov guard init calls `__sanitizer_cov_pcs_init` right
+/// after `__sanitizer_cov_trace_pc_guard_init` for each module, so the guards of this module are the last numbered.
+#[cfg(feature = "sancov_pcguard_functions")]
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+unsafe fn number_guards_per_function(table: &[PcTableEntry]) {
+    let (start, stop) = core::mem::replace(
+        &mut *addr_of_mut!(LAST_GUARDS),
+        (core::ptr::null_mut(), core::ptr::null_mut()),
+    );
+    if start.is_null() {
+        return;
+    }
+    let guards = slice::from_raw_parts_mut(start, stop.offset_from(start) as usize);
+    if guards.len() != table.len() {
+        return;
+    }
+
+    MAX_EDGES_FOUND = MAX_EDGES_FOUND.wrapping_sub(guards.len());
+    let mut function = None;
+    for (guard, entry) in guards.iter_mut().zip(table) {
+        if function.is_none() || entry.is_function_entry() {
+            function = Some(MAX_EDGES_FOUND as u32);
+            MAX_EDGES_FOUND = MAX_EDGES_FOUND.wrapping_add(1);
+        }
+        *guard = function.unwrap();
+    }
 }
 
 /// An entry to the `sanitizer_cov` `pc_table`
 #[repr(C, packed)]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct PcTableEntry {
     addr: usize,
     flags: usize,
@@ -355,3 +417,46 @@ pub fn sanitizer_cov_pc_table<'a>() -> impl Iterator<Item = &'a [PcTableEntry]>
         pc_tables.iter().copied()
     }
 }
+
+/// Returns the number of function entries in the PC tables.
+///
+/// With the `sancov_pcguard_functions` feature, this is the number of entries of the edges map.
+#[must_use]
+pub fn sanitizer_cov_function_count() -> usize {
+    sanitizer_cov_pc_table()
+        .flatten()
+        .filter(|entry| entry.is_function_entry())
+        .count()
+}
+
+/// Returns the PC table entry of the edge at `idx` in the edges map.
+///
+/// The guards are numbered in the order the modules were initialized, as are the PC tables, so this
+/// only holds without the `sancov_ngram4`, `sancov_ngram8` and `sancov_ctx` features, which hash the edges,
+/// and without the `sancov_pcguard_functions` feature, which numbers the functions instead.
+#[must_use]
+pub fn sanitizer_cov_pc_for_index(idx: usize) -> Option<&'static PcTableEntry> {
+    table_for_index(idx).map(|(table, idx)| &table[idx])
+}
+
+/// Returns the PC table entry of the function containing the edge at `idx` in the edges map,
+/// i.e. the last function entry at or before it in its PC table.
+#[must_use]
+pub fn sanitizer_cov_function_entry_for_index(idx: usize) -> Option<&'static PcTableEntry> {
+    let (table, idx) = table_for_index(idx)?;
+    table[..=idx]
+        .iter()
+        .rev()
+        .find(|entry| entry.is_function_entry())
+}
+
+/// The PC table containing the edge at `idx`, and the index of the edge in it
+fn table_for_index(mut idx: usize) -> Option<(&'static [PcTableEntry], usize)> {
+    for table in sanitizer_cov_pc_table() {
+        if idx < table.len() {
+            return Some((table, idx));
+        }
+        idx -= table.len();
+    }
+    None
+}
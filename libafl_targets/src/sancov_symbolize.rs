@@ -0,0 +1,367 @@
+//! Symbolization of the edges of the `SanitizerCoverage` edges map, using the PC tables of the target.
+//!
+//! The target has to be built with `-fsanitize-coverage=trace-pc-guard,pc-table`. The [`EdgeSymbolizer`] maps
+//! an index of the edges map to its PC, and the PC to its function, the offset in it, and its source line,
+//! with `llvm-symbolizer`. The [`LastNewEdgeFeedback`] uses it to show the function of the last new edge
+//! in the monitor.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{ffi::CStr, fmt, marker::PhantomData};
+use std::{
+    env,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use hashbrown::HashMap;
+use libafl::{
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    feedbacks::{Feedback, MapNoveltiesMetadata, StateInitializer},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    Error, HasMetadata,
+};
+use libafl_bolts::Named;
+
+use crate::{sanitizer_cov_function_entry_for_index, sanitizer_cov_pc_for_index};
+
+/// The environment variable to set the path of `llvm-symbolizer`
+pub const LLVM_SYMBOLIZER_PATH_ENV: &str = "LLVM_SYMBOLIZER_PATH";
+
+/// A line of source code, in a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The source file
+    pub file: String,
+    /// The line in the file, starting at 1
+    pub line: u32,
+    /// The function the line is in
+    pub function: String,
+}
+
+/// The symbolized PC of an edge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdgeSymbol {
+    /// The PC of the edge
+    pub pc: usize,
+    /// The module containing the PC
+    pub module: PathBuf,
+    /// The function containing the PC, if known
+    pub function: Option<String>,
+    /// The offset of the PC from the entry of its function
+    pub offset: Option<usize>,
+    /// The source line of the PC, if the module has debug info
+    pub location: Option<SourceLocation>,
+}
+
+impl fmt::Display for EdgeSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.function, self.offset) {
+            (Some(function), Some(offset)) => write!(f, "{function}+{offset:#x}")?,
+            (Some(function), None) => write!(f, "{function}")?,
+            _ => write!(f, "{:#x}", self.pc)?,
+        }
+        if let Some(location) = &self.location {
+            write!(f, " ({}:{})", location.file, location.line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Symbolizes indices of the edges map, caching the results.
+///
+/// It keeps one `llvm-symbolizer` process running for all lookups, started on the first one.
+#[derive(Debug, Default)]
+pub struct EdgeSymbolizer {
+    cache: HashMap<usize, Option<EdgeSymbol>>,
+    process: Option<SymbolizerProcess>,
+}
+
+impl EdgeSymbolizer {
+    /// Create a new [`EdgeSymbolizer`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Symbolize the edge at `idx` of the edges map
+    pub fn symbolize_index(&mut self, idx: usize) -> Result<Option<EdgeSymbol>, Error> {
+        Ok(self.symbolize_indices(&[idx])?.pop().flatten())
+    }
+
+    /// Symbolize the edges at the given indices of the edges map.
+    ///
+    /// Edges without an entry in the PC tables, or outside of any loaded module, are `None`.
+    pub fn symbolize_indices(
+        &mut self,
+        indices: &[usize],
+    ) -> Result<Vec<Option<EdgeSymbol>>, Error> {
+        for idx in indices {
+            if self.cache.contains_key(idx) {
+                continue;
+            }
+            let symbol_addr = sanitizer_cov_pc_for_index(*idx)
+                .and_then(|entry| module_offset(entry.addr()).map(|m| (entry.addr(), m)));
+            let Some((pc, (module, addr))) = symbol_addr else {
+                self.cache.insert(*idx, None);
+                continue;
+            };
+
+            let process = match &mut self.process {
+                Some(process) => process,
+                process @ None => process.insert(SymbolizerProcess::spawn()?),
+            };
+            let (function, location) = match process.symbolize(&module, addr) {
+                Ok(symbol) => symbol,
+                Err(err) => {
+                    // Start a fresh symbolizer for the next lookup
+                    self.process = None;
+                    return Err(err);
+                }
+            };
+            let offset = sanitizer_cov_function_entry_for_index(*idx)
+                .map(|entry| pc - entry.addr())
+                .filter(|_| function.is_some());
+            self.cache.insert(
+                *idx,
+                Some(EdgeSymbol {
+                    pc,
+                    module,
+                    function,
+                    offset,
+                    location,
+                }),
+            );
+        }
+
+        Ok(indices.iter().map(|idx| self.cache[idx].clone()).collect())
+    }
+}
+
+/// A running `llvm-symbolizer`, answering one lookup at a time
+#[derive(Debug)]
+struct SymbolizerProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl SymbolizerProcess {
+    /// Start `llvm-symbolizer`, reading the lookups from its stdin
+    fn spawn() -> Result<Self, Error> {
+        let mut child = symbolizer_command(None)?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    /// Symbolize one address of a module, to its function and source line
+    fn symbolize(
+        &mut self,
+        module: &Path,
+        addr: usize,
+    ) -> Result<(Option<String>, Option<SourceLocation>), Error> {
+        writeln!(self.stdin, "\"{}\" {addr:#x}", module.display())?;
+        self.stdin.flush()?;
+
+        // The block of the address ends with an empty line
+        let mut block = String::new();
+        loop {
+            let start = block.len();
+            if self.stdout.read_line(&mut block)? == 0 {
+                return Err(Error::illegal_state("llvm-symbolizer exited unexpectedly"));
+            }
+            if block[start..].trim().is_empty() && !block[..start].trim().is_empty() {
+                break;
+            }
+        }
+        Ok(parse_symbol(&block))
+    }
+}
+
+impl Drop for SymbolizerProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Start `llvm-symbolizer` with piped stdin and stdout, for the addresses of `module` or, without it, for
+/// `"module" address` lines
+fn symbolizer_command(module: Option<&Path>) -> Result<Child, Error> {
+    let symbolizer =
+        env::var(LLVM_SYMBOLIZER_PATH_ENV).unwrap_or_else(|_| "llvm-symbolizer".to_string());
+    let mut command = Command::new(&symbolizer);
+    if let Some(module) = module {
+        command.arg(format!("--obj={}", module.display()));
+    }
+    command
+        .arg("--no-inlines")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| {
+            Error::illegal_state(format!(
+                "Failed to run {symbolizer} ({err}), set {LLVM_SYMBOLIZER_PATH_ENV} to its path"
+            ))
+        })
+}
+
+/// Parse the output of `llvm-symbolizer` for one address: the function, then `file:line:column`
+fn parse_symbol(block: &str) -> (Option<String>, Option<SourceLocation>) {
+    let mut lines = block.trim_start_matches('\n').lines();
+    let function = lines
+        .next()
+        .map(str::trim)
+        .filter(|function| *function != "??")
+        .map(ToString::to_string);
+    let location = lines.next().and_then(|line| {
+        let mut parts = line.trim().rsplitn(3, ':');
+        let _column = parts.next()?;
+        let line: u32 = parts.next()?.parse().ok()?;
+        let file = parts.next()?;
+        if line == 0 || file == "??" {
+            return None;
+        }
+        Some(SourceLocation {
+            file: file.to_string(),
+            line,
+            function: function.clone().unwrap_or_default(),
+        })
+    });
+    (function, location)
+}
+
+/// The module containing `pc`, and the address to symbolize `pc` at in it
+pub(crate) fn module_offset(pc: usize) -> Option<(PathBuf, usize)> {
+    // # Safety
+    // `dladdr` only reads the loader's tables, and the ELF header of the module it found.
+    unsafe {
+        let mut info: libc::Dl_info = core::mem::zeroed();
+        if libc::dladdr(pc as *const _, &mut info) == 0 || info.dli_fname.is_null() {
+            return None;
+        }
+        let base = info.dli_fbase as usize;
+        let path = CStr::from_ptr(info.dli_fname)
+            .to_string_lossy()
+            .into_owned();
+        let path = if path.is_empty() {
+            env::current_exe().ok()?
+        } else {
+            PathBuf::from(path)
+        };
+        // `e_type` of the ELF header: non-PIE executables (`ET_EXEC`) are symbolized by absolute address
+        let e_type = *((base + 16) as *const u16);
+        let offset = if e_type == 2 { pc } else { pc - base };
+        Some((path, offset))
+    }
+}
+
+/// Symbolize addresses of a module with one batched `llvm-symbolizer` run, to their function and source line
+pub(crate) fn symbolize_module(
+    module: &Path,
+    addrs: &[usize],
+) -> Result<Vec<(Option<String>, Option<SourceLocation>)>, Error> {
+    let mut child = symbolizer_command(Some(module))?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let addrs_text: String = addrs.iter().map(|addr| format!("{addr:#x}\n")).collect();
+    // Write from another thread, so the symbolizer never blocks on a full stdout pipe
+    let writer = std::thread::spawn(move || stdin.write_all(addrs_text.as_bytes()));
+    let mut output = String::new();
+    stdout.read_to_string(&mut output)?;
+    writer.join().unwrap()?;
+    child.wait()?;
+
+    // One block per address: the function, then `file:line:column`, then an empty line
+    let mut symbols: Vec<(Option<String>, Option<SourceLocation>)> = output
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(parse_symbol)
+        .collect();
+    symbols.resize(addrs.len(), (None, None));
+    Ok(symbols)
+}
+
+/// A feedback showing the symbolized last new edge in the monitor, as the `last new edge` user stat.
+///
+/// It reads the [`MapNoveltiesMetadata`] of new testcases, so the edges observer has to track novelties,
+/// and the feedback has to come after the map feedback, e.g. `feedback_or!(map_feedback, LastNewEdgeFeedback::new())`.
+/// It never considers an input interesting by itself.
+#[derive(Debug, Default)]
+pub struct LastNewEdgeFeedback {
+    symbolizer: EdgeSymbolizer,
+}
+
+impl LastNewEdgeFeedback {
+    /// Create a new [`LastNewEdgeFeedback`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Named for LastNewEdgeFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("LastNewEdgeFeedback");
+        &NAME
+    }
+}
+
+impl<S> StateInitializer<S> for LastNewEdgeFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for LastNewEdgeFeedback
+where
+    EM: EventFirer<State = S>,
+{
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Some(&idx) = testcase
+            .metadata_map()
+            .get::<MapNoveltiesMetadata>()
+            .and_then(|novelties| novelties.last())
+        else {
+            return Ok(());
+        };
+        let description = match self.symbolizer.symbolize_index(idx) {
+            Ok(Some(symbol)) => symbol.to_string(),
+            Ok(None) => format!("edge {idx}"),
+            Err(err) => {
+                log::debug!("Failed to symbolize edge {idx}: {err}");
+                format!("edge {idx}")
+            }
+        };
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: Cow::Borrowed("last new edge"),
+                value: UserStats::new(
+                    UserStatsValue::String(description.into()),
+                    AggregatorOps::None,
+                ),
+                phantom: PhantomData,
+            },
+        )
+    }
+}
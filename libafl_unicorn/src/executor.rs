@@ -0,0 +1,540 @@
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+
+use libafl::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::HasTargetBytes,
+    observers::ObserversTuple,
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+use libafl_bolts::{tuples::RefIndexable, AsSlice};
+use unicorn_engine::{
+    unicorn_const::{uc_error, Arch, Mode, Permission},
+    Context, Unicorn,
+};
+
+/// The default timeout of each execution
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The data of the [`Unicorn`] engine, recording the coverage of the running snippet
+#[derive(Debug)]
+pub struct UnicornHost {
+    map: *mut u8,
+    map_size: usize,
+    prev_loc: u64,
+    crashed: bool,
+}
+
+impl UnicornHost {
+    /// Records the edge from the previous block to the block at `address`
+    #[allow(clippy::cast_possible_truncation)]
+    fn cov_block(&mut self, address: u64) {
+        if self.map_size == 0 {
+            return;
+        }
+        let cur_loc = (address >> 4) ^ (address << 8);
+        let idx = ((cur_loc ^ self.prev_loc) % self.map_size as u64) as usize;
+        unsafe {
+            let entry = self.map.add(idx);
+            *entry = (*entry).wrapping_add(1);
+        }
+        self.prev_loc = cur_loc >> 1;
+    }
+}
+
+/// Sets up the engine before the snapshot is taken, e.g. maps memory or installs hooks
+type EngineSetup = Box<dyn FnOnce(&mut Unicorn<'static, UnicornHost>) -> Result<(), uc_error>>;
+
+/// Turn an error of the [`Unicorn`] engine into an [`Error`]
+fn uc_err(what: &str, err: uc_error) -> Error {
+    Error::unknown(format!("{what}: {err:?}"))
+}
+
+/// Map the error stopping an execution to an [`ExitKind`], if it is a fault of the snippet
+fn fault_exit_kind(err: uc_error) -> Result<ExitKind, Error> {
+    match err {
+        uc_error::READ_UNMAPPED
+        | uc_error::WRITE_UNMAPPED
+        | uc_error::FETCH_UNMAPPED
+        | uc_error::READ_PROT
+        | uc_error::WRITE_PROT
+        | uc_error::FETCH_PROT
+        | uc_error::READ_UNALIGNED
+        | uc_error::WRITE_UNALIGNED
+        | uc_error::FETCH_UNALIGNED
+        | uc_error::INSN_INVALID
+        | uc_error::EXCEPTION => {
+            log::debug!("Snippet faulted: {err:?}");
+            Ok(ExitKind::Crash)
+        }
+        err => Err(uc_err("Unicorn execution failed", err)),
+    }
+}
+
+/// A writable memory region, with its contents at the time of the snapshot
+#[derive(Debug)]
+struct MemorySnapshot {
+    address: u64,
+    data: Vec<u8>,
+}
+
+/// Executes a snippet of machine code in the [`Unicorn`] engine, starting from a snapshot for each input.
+///
+/// Each input is written to the input buffer set with [`UnicornExecutorBuilder::input_at`], and the
+/// snippet runs from its entry until it reaches the exit address. The edges between its basic blocks are
+/// counted in the map set with [`UnicornExecutorBuilder::coverage_map`].
+/// Memory faults, invalid instructions and the crash addresses are crashes; exceeding the limits is a timeout.
+pub struct UnicornExecutor<OT, S> {
+    emu: Unicorn<'static, UnicornHost>,
+    context: Context,
+    memory: Vec<MemorySnapshot>,
+    entry: u64,
+    exit: u64,
+    input_address: u64,
+    input_max_len: usize,
+    input_ptr_register: Option<i32>,
+    input_len_register: Option<i32>,
+    timeout: Option<Duration>,
+    max_instructions: Option<usize>,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl UnicornExecutor<(), ()> {
+    /// Create a builder for [`UnicornExecutor`]
+    #[must_use]
+    pub fn builder(arch: Arch, mode: Mode) -> UnicornExecutorBuilder {
+        UnicornExecutorBuilder::new(arch, mode)
+    }
+}
+
+impl<OT, S> Debug for UnicornExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnicornExecutor")
+            .field("entry", &self.entry)
+            .field("exit", &self.exit)
+            .field("input_address", &self.input_address)
+            .field("input_max_len", &self.input_max_len)
+            .field("timeout", &self.timeout)
+            .field("max_instructions", &self.max_instructions)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> UnicornExecutor<OT, S> {
+    /// The [`Unicorn`] engine, e.g. to inspect the state after an execution
+    #[must_use]
+    pub fn emu(&self) -> &Unicorn<'static, UnicornHost> {
+        &self.emu
+    }
+
+    /// Restore the snapshot, and place the input in memory
+    fn prepare(&mut self, bytes: &[u8]) -> Result<(), uc_error> {
+        self.emu.context_restore(&self.context)?;
+        for region in &self.memory {
+            self.emu.mem_write(region.address, &region.data)?;
+        }
+
+        let bytes = &bytes[..bytes.len().min(self.input_max_len)];
+        self.emu.mem_write(self.input_address, bytes)?;
+        if let Some(reg) = self.input_ptr_register {
+            self.emu.reg_write(reg, self.input_address)?;
+        }
+        if let Some(reg) = self.input_len_register {
+            self.emu.reg_write(reg, bytes.len() as u64)?;
+        }
+
+        let host = self.emu.get_data_mut();
+        host.prev_loc = 0;
+        host.crashed = false;
+        Ok(())
+    }
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for UnicornExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        let target_bytes = input.target_bytes();
+        self.prepare(target_bytes.as_slice())
+            .map_err(|err| uc_err("Could not restore the snapshot", err))?;
+
+        #[allow(clippy::cast_possible_truncation)]
+        let timeout = self.timeout.map_or(0, |timeout| timeout.as_micros() as u64);
+        let res = self.emu.emu_start(
+            self.entry,
+            self.exit,
+            timeout,
+            self.max_instructions.unwrap_or(0),
+        );
+        if let Err(err) = res {
+            return fault_exit_kind(err);
+        }
+        if self.emu.get_data().crashed {
+            return Ok(ExitKind::Crash);
+        }
+
+        // Unicorn stops without error when it runs out of time or instructions
+        let pc = self
+            .emu
+            .pc_read()
+            .map_err(|err| uc_err("Could not read the pc", err))?;
+        if pc == self.exit {
+            Ok(ExitKind::Ok)
+        } else {
+            Ok(ExitKind::Timeout)
+        }
+    }
+}
+
+impl<OT, S> UsesState for UnicornExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<OT, S> HasObservers for UnicornExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+/// Builder for [`UnicornExecutor`]
+pub struct UnicornExecutorBuilder {
+    arch: Arch,
+    mode: Mode,
+    map: *mut u8,
+    map_size: usize,
+    entry: Option<u64>,
+    exit: Option<u64>,
+    input: Option<(u64, usize)>,
+    input_ptr_register: Option<i32>,
+    input_len_register: Option<i32>,
+    crash_addresses: Vec<u64>,
+    timeout: Option<Duration>,
+    max_instructions: Option<usize>,
+    setups: Vec<EngineSetup>,
+}
+
+impl Debug for UnicornExecutorBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnicornExecutorBuilder")
+            .field("arch", &self.arch)
+            .field("mode", &self.mode)
+            .field("map_size", &self.map_size)
+            .field("entry", &self.entry)
+            .field("exit", &self.exit)
+            .field("input", &self.input)
+            .field("crash_addresses", &self.crash_addresses)
+            .field("timeout", &self.timeout)
+            .field("max_instructions", &self.max_instructions)
+            .finish_non_exhaustive()
+    }
+}
+
+impl UnicornExecutorBuilder {
+    /// Constructor, for code of the given architecture and mode
+    #[must_use]
+    pub fn new(arch: Arch, mode: Mode) -> Self {
+        Self {
+            arch,
+            mode,
+            map: core::ptr::null_mut(),
+            map_size: 0,
+            entry: None,
+            exit: None,
+            input: None,
+            input_ptr_register: None,
+            input_len_register: None,
+            crash_addresses: vec![],
+            timeout: Some(DEFAULT_TIMEOUT),
+            max_instructions: None,
+            setups: vec![],
+        }
+    }
+
+    /// Map a memory region of `size` bytes at `address`, both aligned to 4 KiB
+    #[must_use]
+    pub fn map(self, address: u64, size: usize, perms: Permission) -> Self {
+        self.setup(move |emu| emu.mem_map(address, size, perms))
+    }
+
+    /// Write `data` to the mapped memory at `address`, e.g. the code of the snippet
+    #[must_use]
+    pub fn write(self, address: u64, data: Vec<u8>) -> Self {
+        self.setup(move |emu| emu.mem_write(address, &data))
+    }
+
+    /// Set the register `reg` (e.g. a [`unicorn_engine::RegisterARM`]) to `value` in the snapshot
+    #[must_use]
+    pub fn register<R: Into<i32>>(self, reg: R, value: u64) -> Self {
+        let reg = reg.into();
+        self.setup(move |emu| emu.reg_write(reg, value))
+    }
+
+    /// Set up the engine with `setup` before the snapshot is taken, e.g. to install hooks emulating peripherals
+    #[must_use]
+    pub fn setup<F>(mut self, setup: F) -> Self
+    where
+        F: FnOnce(&mut Unicorn<'static, UnicornHost>) -> Result<(), uc_error> + 'static,
+    {
+        self.setups.push(Box::new(setup));
+        self
+    }
+
+    /// The address the snippet starts at. This option is required.
+    /// For Thumb code on ARM, set the lowest bit.
+    #[must_use]
+    pub fn entry(mut self, entry: u64) -> Self {
+        self.entry = Some(entry);
+        self
+    }
+
+    /// The address the execution stops at, e.g. the return address set in the link register.
+    /// This option is required.
+    #[must_use]
+    pub fn exit(mut self, exit: u64) -> Self {
+        self.exit = Some(exit);
+        self
+    }
+
+    /// Place each input in the mapped memory at `address`, truncated to `max_len` bytes.
+    /// This option is required.
+    #[must_use]
+    pub fn input_at(mut self, address: u64, max_len: usize) -> Self {
+        self.input = Some((address, max_len));
+        self
+    }
+
+    /// Pass the address of the input to the snippet in the register `reg`
+    #[must_use]
+    pub fn input_ptr_register<R: Into<i32>>(mut self, reg: R) -> Self {
+        self.input_ptr_register = Some(reg.into());
+        self
+    }
+
+    /// Pass the length of the input to the snippet in the register `reg`
+    #[must_use]
+    pub fn input_len_register<R: Into<i32>>(mut self, reg: R) -> Self {
+        self.input_len_register = Some(reg.into());
+        self
+    }
+
+    /// Report executions reaching `address` as crashes, e.g. the address of a panic or assert handler
+    #[must_use]
+    pub fn crash_address(mut self, address: u64) -> Self {
+        self.crash_addresses.push(address);
+        self
+    }
+
+    /// Report executions running longer than `timeout` as [`ExitKind::Timeout`]. Defaults to [`DEFAULT_TIMEOUT`].
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Report executions running more than `max_instructions` as [`ExitKind::Timeout`]. Unlimited by default, only
+    /// the [`Self::timeout`] applies.
+    #[must_use]
+    pub fn max_instructions(mut self, max_instructions: usize) -> Self {
+        self.max_instructions = Some(max_instructions);
+        self
+    }
+
+    /// Set the coverage map the edges between the basic blocks of the snippet are counted in.
+    /// Observe it with a map observer, e.g. [`libafl::observers::StdMapObserver::from_mut_ptr`].
+    ///
+    /// # Safety
+    /// The map must be valid for writes of `map_size` bytes, and outlive the [`UnicornExecutor`].
+    /// It will be written to during each execution. This may not happen concurrently.
+    #[must_use]
+    pub unsafe fn coverage_map(mut self, map: *mut u8, map_size: usize) -> Self {
+        self.map = map;
+        self.map_size = map_size;
+        self
+    }
+
+    /// Set up the engine, take the snapshot, and build the [`UnicornExecutor`]
+    pub fn build<OT, S>(self, observers: OT) -> Result<UnicornExecutor<OT, S>, Error> {
+        let (Some(entry), Some(exit)) = (self.entry, self.exit) else {
+            return Err(Error::illegal_argument(
+                "UnicornExecutor::builder: no entry or exit set!",
+            ));
+        };
+        let Some((input_address, input_max_len)) = self.input else {
+            return Err(Error::illegal_argument(
+                "UnicornExecutor::builder: no input placement set!",
+            ));
+        };
+        if self.map.is_null() {
+            return Err(Error::illegal_argument(
+                "UnicornExecutor::builder: no coverage map set!",
+            ));
+        }
+
+        let host = UnicornHost {
+            map: self.map,
+            map_size: self.map_size,
+            prev_loc: 0,
+            crashed: false,
+        };
+        let mut emu = Unicorn::new_with_data(self.arch, self.mode, host)
+            .map_err(|err| uc_err("Could not create the Unicorn engine", err))?;
+        for setup in self.setups {
+            setup(&mut emu).map_err(|err| uc_err("Could not set up the Unicorn engine", err))?;
+        }
+
+        emu.add_block_hook(1, 0, |emu, address, _size| {
+            emu.get_data_mut().cov_block(address);
+        })
+        .map_err(|err| uc_err("Could not add the coverage hook", err))?;
+        for address in self.crash_addresses {
+            emu.add_code_hook(address, address, |emu, _address, _size| {
+                emu.get_data_mut().crashed = true;
+                if let Err(err) = emu.emu_stop() {
+                    log::error!("Could not stop at the crash address: {err:?}");
+                }
+            })
+            .map_err(|err| uc_err("Could not add the crash hook", err))?;
+        }
+
+        let context = emu
+            .context_init()
+            .map_err(|err| uc_err("Could not save the registers", err))?;
+        let regions = emu
+            .mem_regions()
+            .map_err(|err| uc_err("Could not list the memory regions", err))?;
+        let memory = regions
+            .into_iter()
+            .filter(|region| region.perms.contains(Permission::WRITE))
+            .map(|region| {
+                #[allow(clippy::cast_possible_truncation)]
+                let size = (region.end - region.begin + 1) as usize;
+                emu.mem_read_as_vec(region.begin, size)
+                    .map(|data| MemorySnapshot {
+                        address: region.begin,
+                        data,
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| uc_err("Could not save the memory", err))?;
+
+        Ok(UnicornExecutor {
+            emu,
+            context,
+            memory,
+            entry,
+            exit,
+            input_address,
+            input_max_len,
+            input_ptr_register: self.input_ptr_register,
+            input_len_register: self.input_len_register,
+            timeout: self.timeout,
+            max_instructions: self.max_instructions,
+            observers,
+            phantom: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        state::NopState,
+    };
+    use unicorn_engine::{
+        unicorn_const::{Arch, Mode, Permission},
+        RegisterX86,
+    };
+
+    use super::UnicornExecutor;
+
+    const CODE: u64 = 0x1000;
+    const INPUT: u64 = 0x2000;
+
+    /// Crashes on `A`, loops forever on `B`
+    const SNIPPET: [u8; 15] = [
+        0x8a, 0x01, // mov al, [ecx]
+        0x3c, 0x41, // cmp al, 0x41
+        0x75, 0x02, // jne +2
+        0x8b, 0x00, // mov eax, [eax] (unmapped)
+        0x3c, 0x42, // cmp al, 0x42
+        0x75, 0x02, // jne +2
+        0xeb, 0xfe, // jmp $
+        0x90, // nop (exit)
+    ];
+
+    #[test]
+    fn test_unicorn_executor() {
+        let mut map = vec![0_u8; 1024];
+        let mut executor = unsafe {
+            UnicornExecutor::builder(Arch::X86, Mode::MODE_32)
+                .map(CODE, 0x1000, Permission::READ | Permission::EXEC)
+                .map(INPUT, 0x1000, Permission::READ | Permission::WRITE)
+                .write(CODE, SNIPPET.to_vec())
+                .entry(CODE)
+                .exit(CODE + SNIPPET.len() as u64 - 1)
+                .input_at(INPUT, 0x100)
+                .input_ptr_register(RegisterX86::ECX)
+                .input_len_register(RegisterX86::EDX)
+                .max_instructions(1000)
+                .coverage_map(map.as_mut_ptr(), map.len())
+        }
+        .build(())
+        .unwrap();
+
+        let mut state = NopState::<BytesInput>::new();
+        let mut run = |executor: &mut UnicornExecutor<(), NopState<BytesInput>>, bytes: &[u8]| {
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut state,
+                    &mut NopEventManager::new(),
+                    &BytesInput::new(bytes.to_vec()),
+                )
+                .unwrap()
+        };
+        assert_eq!(run(&mut executor, b"x"), ExitKind::Ok);
+        assert_eq!(run(&mut executor, b"A"), ExitKind::Crash);
+        assert_eq!(run(&mut executor, b"B"), ExitKind::Timeout);
+        assert_eq!(run(&mut executor, b"y"), ExitKind::Ok);
+        drop(executor);
+        assert!(map.iter().any(|hits| *hits > 0));
+    }
+}
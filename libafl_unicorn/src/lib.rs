@@ -0,0 +1,49 @@
+/*!
+The Unicorn backend for `LibAFL`, running snippets of firmware in the [Unicorn engine](https://www.unicorn-engine.org/).
+*/
+
+#![cfg_attr(not(test), warn(
+    missing_debug_implementations,
+    missing_docs,
+    //trivial_casts,
+    trivial_numeric_casts,
+    unused_extern_crates,
+    unused_import_braces,
+    unused_qualifications,
+    //unused_results
+))]
+#![cfg_attr(test, deny(
+    missing_debug_implementations,
+    //trivial_casts,
+    trivial_numeric_casts,
+    unused_extern_crates,
+    unused_import_braces,
+    unused_qualifications,
+    unused_must_use,
+    //unused_results
+))]
+#![cfg_attr(
+    test,
+    deny(
+        bad_style,
+        dead_code,
+        improper_ctypes,
+        non_shorthand_field_patterns,
+        no_mangle_generic_items,
+        overflowing_literals,
+        path_statements,
+        patterns_in_fns_without_body,
+        unconditional_recursion,
+        unused,
+        unused_allocation,
+        unused_comparisons,
+        unused_parens,
+        while_true
+    )
+)]
+
+/// The Unicorn executor
+pub mod executor;
+pub use executor::{UnicornExecutor, UnicornExecutorBuilder, UnicornHost, DEFAULT_TIMEOUT};
+/// The Unicorn engine, to set up the executor with the same version
+pub use unicorn_engine;
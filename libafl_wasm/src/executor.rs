@@ -0,0 +1,521 @@
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use std::{path::Path, sync::Arc, thread};
+
+use libafl::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::HasTargetBytes,
+    observers::ObserversTuple,
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+use libafl_bolts::{tuples::RefIndexable, AsSlice};
+use wasmtime::{Caller, Config, Engine, InstancePre, Linker, Module, Store, Trap};
+
+use crate::instrument::{instrument_coverage, COVERAGE_FUNC, COVERAGE_MODULE};
+
+/// The data of each wasmtime [`Store`], recording the coverage of the running instance
+#[derive(Debug)]
+pub struct WasmHost {
+    map: *mut u8,
+    map_size: usize,
+    prev_loc: u32,
+}
+
+impl WasmHost {
+    /// Records the edge from the previous block to the block `id`
+    fn cov_block(&mut self, id: u32) {
+        if self.map_size == 0 {
+            return;
+        }
+        let idx = (id ^ self.prev_loc) as usize % self.map_size;
+        unsafe {
+            let entry = self.map.add(idx);
+            *entry = (*entry).wrapping_add(1);
+        }
+        self.prev_loc = id >> 1;
+    }
+}
+
+/// The default timeout of each execution
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The interval the epoch of the engine is incremented in, the granularity of the timeout
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Increments the epoch of an [`Engine`] every [`EPOCH_TICK`] on a background thread, until it is dropped
+#[derive(Debug)]
+struct EpochTicker {
+    stop: Arc<AtomicBool>,
+}
+
+impl EpochTicker {
+    fn start(engine: Engine) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                thread::sleep(EPOCH_TICK);
+                engine.increment_epoch();
+            }
+        });
+        Self { stop }
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Sets up additional imports of the module on the [`Linker`]
+type LinkerSetup = Box<dyn FnOnce(&mut Linker<WasmHost>) -> wasmtime::Result<()>>;
+
+/// Map the error of a Wasm execution to an [`ExitKind`].
+///
+/// Errors other than traps, e.g. an input too large for the memory of the module, are crashes as well.
+fn exit_kind(err: &wasmtime::Error) -> ExitKind {
+    match err.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel | Trap::Interrupt) => ExitKind::Timeout,
+        Some(trap) => {
+            log::debug!("Wasm module trapped: {trap}");
+            ExitKind::Crash
+        }
+        None => {
+            log::warn!("Wasm execution failed: {err:?}");
+            ExitKind::Crash
+        }
+    }
+}
+
+/// Executes a WebAssembly module in [wasmtime](https://wasmtime.dev/), in a fresh instance for each input.
+///
+/// The module is instrumented for coverage, see [`crate::instrument`], and the edges between its blocks
+/// are counted in the map set with [`WasmExecutorBuilder::coverage_map`].
+/// Traps and other failed executions are crashes; executions running longer than the timeout, or out of fuel,
+/// are timeouts.
+pub struct WasmExecutor<OT, S> {
+    engine: Engine,
+    instance_pre: InstancePre<WasmHost>,
+    map: *mut u8,
+    map_size: usize,
+    blocks: usize,
+    entrypoint: String,
+    allocator: String,
+    memory: String,
+    fuel: Option<u64>,
+    timeout: Duration,
+    /// The timeout, in epoch ticks
+    epoch_deadline: u64,
+    _epoch_ticker: EpochTicker,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl WasmExecutor<(), ()> {
+    /// Create a builder for [`WasmExecutor`]
+    #[must_use]
+    pub fn builder() -> WasmExecutorBuilder {
+        WasmExecutorBuilder::new()
+    }
+}
+
+impl<OT, S> Debug for WasmExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmExecutor")
+            .field("map_size", &self.map_size)
+            .field("blocks", &self.blocks)
+            .field("entrypoint", &self.entrypoint)
+            .field("allocator", &self.allocator)
+            .field("memory", &self.memory)
+            .field("fuel", &self.fuel)
+            .field("timeout", &self.timeout)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> WasmExecutor<OT, S> {
+    /// The number of instrumented blocks in the module
+    #[must_use]
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Instantiate the module, copy the input into its memory and call the entrypoint
+    fn execute(&self, store: &mut Store<WasmHost>, bytes: &[u8]) -> wasmtime::Result<()> {
+        let instance = self.instance_pre.instantiate(&mut *store)?;
+        let memory = instance
+            .get_memory(&mut *store, &self.memory)
+            .ok_or_else(|| wasmtime::Error::msg("The Wasm module does not export its memory"))?;
+        let allocator = instance.get_typed_func::<i32, i32>(&mut *store, &self.allocator)?;
+        let entrypoint =
+            instance.get_typed_func::<(i32, i32), i32>(&mut *store, &self.entrypoint)?;
+
+        let len = i32::try_from(bytes.len())?;
+        let ptr = allocator.call(&mut *store, len)?;
+        #[allow(clippy::cast_sign_loss)]
+        memory.write(&mut *store, ptr as u32 as usize, bytes)?;
+        entrypoint.call(&mut *store, (ptr, len))?;
+        Ok(())
+    }
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for WasmExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        let mut store = Store::new(
+            &self.engine,
+            WasmHost {
+                map: self.map,
+                map_size: self.map_size,
+                prev_loc: 0,
+            },
+        );
+        if let Some(fuel) = self.fuel {
+            store
+                .set_fuel(fuel)
+                .map_err(|err| Error::unknown(format!("Could not set fuel: {err}")))?;
+        }
+        store.set_epoch_deadline(self.epoch_deadline);
+
+        let target_bytes = input.target_bytes();
+        match self.execute(&mut store, target_bytes.as_slice()) {
+            Ok(()) => Ok(ExitKind::Ok),
+            Err(err) => Ok(exit_kind(&err)),
+        }
+    }
+}
+
+impl<OT, S> UsesState for WasmExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<OT, S> HasObservers for WasmExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+/// Builder for [`WasmExecutor`]
+pub struct WasmExecutorBuilder {
+    wasm: Option<Vec<u8>>,
+    map: *mut u8,
+    map_size: usize,
+    entrypoint: String,
+    allocator: String,
+    memory: String,
+    fuel: Option<u64>,
+    timeout: Duration,
+    linker_setups: Vec<LinkerSetup>,
+}
+
+impl Debug for WasmExecutorBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmExecutorBuilder")
+            .field("map_size", &self.map_size)
+            .field("entrypoint", &self.entrypoint)
+            .field("allocator", &self.allocator)
+            .field("memory", &self.memory)
+            .field("fuel", &self.fuel)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for WasmExecutorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WasmExecutorBuilder {
+    /// Constructor
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            wasm: None,
+            map: core::ptr::null_mut(),
+            map_size: 0,
+            entrypoint: "LLVMFuzzerTestOneInput".to_string(),
+            allocator: "malloc".to_string(),
+            memory: "memory".to_string(),
+            fuel: None,
+            timeout: DEFAULT_TIMEOUT,
+            linker_setups: vec![],
+        }
+    }
+
+    /// The Wasm module to fuzz, in binary format.
+    /// This option (or [`Self::module_file`]) is required.
+    #[must_use]
+    pub fn module(mut self, wasm: Vec<u8>) -> Self {
+        self.wasm = Some(wasm);
+        self
+    }
+
+    /// Read the Wasm module to fuzz from a file
+    pub fn module_file<P: AsRef<Path>>(self, path: P) -> Result<Self, Error> {
+        let wasm = std::fs::read(path)?;
+        Ok(self.module(wasm))
+    }
+
+    /// The exported function called with the pointer to and length of each input, returning an `i32`.
+    /// Defaults to `LLVMFuzzerTestOneInput`.
+    #[must_use]
+    pub fn entrypoint<N: Into<String>>(mut self, entrypoint: N) -> Self {
+        self.entrypoint = entrypoint.into();
+        self
+    }
+
+    /// The exported function allocating the memory for each input, taking its length and returning a pointer.
+    /// Defaults to `malloc`.
+    #[must_use]
+    pub fn allocator<N: Into<String>>(mut self, allocator: N) -> Self {
+        self.allocator = allocator.into();
+        self
+    }
+
+    /// The exported memory of the module. Defaults to `memory`.
+    #[must_use]
+    pub fn memory<N: Into<String>>(mut self, memory: N) -> Self {
+        self.memory = memory.into();
+        self
+    }
+
+    /// Limit each execution to this amount of fuel, roughly the number of executed instructions.
+    /// Executions running out of fuel are reported as [`ExitKind::Timeout`]. Unlimited by default, only the
+    /// [`Self::timeout`] applies.
+    #[must_use]
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    /// Interrupt executions running longer than `timeout`, reporting them as [`ExitKind::Timeout`].
+    /// Defaults to [`DEFAULT_TIMEOUT`]. The timeout is checked every few milliseconds.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Define additional imports of the module, such as WASI, on the [`Linker`]
+    #[must_use]
+    pub fn linker<F>(mut self, setup: F) -> Self
+    where
+        F: FnOnce(&mut Linker<WasmHost>) -> wasmtime::Result<()> + 'static,
+    {
+        self.linker_setups.push(Box::new(setup));
+        self
+    }
+
+    /// Set the coverage map the edges between the blocks of the module are counted in.
+    /// Observe it with a map observer, e.g. [`libafl::observers::StdMapObserver::from_mut_ptr`].
+    ///
+    /// # Safety
+    /// The map must be valid for writes of `map_size` bytes, and outlive the [`WasmExecutor`].
+    /// It will be written to during each execution. This may not happen concurrently.
+    #[must_use]
+    pub unsafe fn coverage_map(mut self, map: *mut u8, map_size: usize) -> Self {
+        self.map = map;
+        self.map_size = map_size;
+        self
+    }
+
+    /// Instrument and compile the module, and build the [`WasmExecutor`]
+    pub fn build<OT, S>(self, observers: OT) -> Result<WasmExecutor<OT, S>, Error> {
+        let Some(wasm) = self.wasm else {
+            return Err(Error::illegal_argument(
+                "WasmExecutor::builder: no module set!",
+            ));
+        };
+        if self.map.is_null() {
+            return Err(Error::illegal_argument(
+                "WasmExecutor::builder: no coverage map set!",
+            ));
+        }
+        let (instrumented, blocks) = instrument_coverage(&wasm)?;
+
+        let mut config = Config::new();
+        config.consume_fuel(self.fuel.is_some());
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config)
+            .map_err(|err| Error::unknown(format!("Could not create the Wasm engine: {err}")))?;
+        let module = Module::new(&engine, &instrumented).map_err(|err| {
+            Error::illegal_argument(format!("Could not compile the Wasm module: {err}"))
+        })?;
+        for name in [&self.entrypoint, &self.allocator, &self.memory] {
+            if module.get_export(name).is_none() {
+                return Err(Error::illegal_argument(format!(
+                    "The Wasm module does not export {name}"
+                )));
+            }
+        }
+
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap(
+                COVERAGE_MODULE,
+                COVERAGE_FUNC,
+                |mut caller: Caller<'_, WasmHost>, id: i32| {
+                    #[allow(clippy::cast_sign_loss)]
+                    caller.data_mut().cov_block(id as u32);
+                },
+            )
+            .map_err(|err| {
+                Error::unknown(format!("Could not define the coverage import: {err}"))
+            })?;
+        for setup in self.linker_setups {
+            setup(&mut linker).map_err(|err| {
+                Error::illegal_argument(format!("Could not define imports: {err}"))
+            })?;
+        }
+        let instance_pre = linker.instantiate_pre(&module).map_err(|err| {
+            Error::illegal_argument(format!("Could not link the Wasm module: {err}"))
+        })?;
+
+        let epoch_deadline = u64::try_from(self.timeout.as_nanos().div_ceil(EPOCH_TICK.as_nanos()))
+            .unwrap_or(u64::MAX)
+            .max(1);
+        let epoch_ticker = EpochTicker::start(engine.clone());
+
+        Ok(WasmExecutor {
+            engine,
+            instance_pre,
+            map: self.map,
+            map_size: self.map_size,
+            blocks,
+            entrypoint: self.entrypoint,
+            allocator: self.allocator,
+            memory: self.memory,
+            fuel: self.fuel,
+            timeout: self.timeout,
+            epoch_deadline,
+            _epoch_ticker: epoch_ticker,
+            observers,
+            phantom: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        state::NopState,
+    };
+
+    use super::WasmExecutor;
+
+    const HARNESS: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "malloc") (param i32) (result i32)
+            i32.const 16)
+          (func (export "LLVMFuzzerTestOneInput") (param i32 i32) (result i32)
+            (if (i32.eq (i32.load8_u (local.get 0)) (i32.const 65))
+              (then unreachable))
+            (if (i32.eq (i32.load8_u (local.get 0)) (i32.const 66))
+              (then (loop (br 0))))
+            i32.const 0))
+    "#;
+
+    #[test]
+    fn test_wasm_executor() {
+        let mut map = vec![0_u8; 1024];
+        let wasm = wat::parse_str(HARNESS).unwrap();
+        let mut executor = unsafe {
+            WasmExecutor::builder()
+                .module(wasm)
+                .fuel(10_000)
+                .coverage_map(map.as_mut_ptr(), map.len())
+        }
+        .build(())
+        .unwrap();
+        assert!(executor.blocks() > 0);
+
+        let mut state = NopState::<BytesInput>::new();
+        let mut run = |executor: &mut WasmExecutor<(), NopState<BytesInput>>, bytes: &[u8]| {
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut state,
+                    &mut NopEventManager::new(),
+                    &BytesInput::new(bytes.to_vec()),
+                )
+                .unwrap()
+        };
+        assert_eq!(run(&mut executor, b"x"), ExitKind::Ok);
+        assert_eq!(run(&mut executor, b"A"), ExitKind::Crash);
+        assert_eq!(run(&mut executor, b"B"), ExitKind::Timeout);
+        drop(executor);
+        assert!(map.iter().any(|hits| *hits > 0));
+    }
+
+    #[test]
+    fn test_wasm_executor_timeout() {
+        let mut map = vec![0_u8; 1024];
+        let wasm = wat::parse_str(HARNESS).unwrap();
+        // Without fuel, endless loops are interrupted after the timeout
+        let mut executor = unsafe {
+            WasmExecutor::builder()
+                .module(wasm)
+                .timeout(Duration::from_millis(50))
+                .coverage_map(map.as_mut_ptr(), map.len())
+        }
+        .build(())
+        .unwrap();
+
+        let mut state = NopState::<BytesInput>::new();
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut state,
+                &mut NopEventManager::new(),
+                &BytesInput::new(b"B".to_vec()),
+            )
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Timeout);
+        drop(executor);
+    }
+}
@@ -0,0 +1,79 @@
+//! Adds coverage callbacks to a Wasm module.
+//!
+//! At the start of each block (function bodies, `block`, `loop`, `if` and `else` arms), a call to the
+//! imported [`COVERAGE_MODULE`]`.`[`COVERAGE_FUNC`] is inserted, passing a unique id of the block.
+//! The [`crate::WasmExecutor`] defines this import and turns the ids into edges in its coverage map.
+
+use libafl::Error;
+use walrus::{
+    ir::{dfs_in_order, Call, Const, Instr, InstrLocId, InstrSeq, InstrSeqId, Value, Visitor},
+    FunctionId, LocalFunction, Module, ValType,
+};
+
+/// The module of the import called at each block
+pub const COVERAGE_MODULE: &str = "libafl";
+/// The name of the import called at each block
+pub const COVERAGE_FUNC: &str = "__libafl_cov_block";
+
+/// Collects all instruction sequences of a function
+#[derive(Debug, Default)]
+struct BlockCollector {
+    blocks: Vec<InstrSeqId>,
+}
+
+impl<'instr> Visitor<'instr> for BlockCollector {
+    fn start_instr_seq(&mut self, instr_seq: &'instr InstrSeq) {
+        self.blocks.push(instr_seq.id());
+    }
+}
+
+/// Instruments all blocks of `func`, numbering them from `next_id` on
+fn instrument_function(func: &mut LocalFunction, cov_func: FunctionId, next_id: &mut u32) {
+    let mut collector = BlockCollector::default();
+    dfs_in_order(&mut collector, func, func.entry_block());
+
+    for block in collector.blocks {
+        // Spread the ids over the map, like the random ids of AFL
+        let id = next_id.wrapping_mul(0x9e37_79b1);
+        *next_id += 1;
+        #[allow(clippy::cast_possible_wrap)]
+        let callback = [
+            (
+                Instr::Const(Const {
+                    value: Value::I32(id as i32),
+                }),
+                InstrLocId::default(),
+            ),
+            (Instr::Call(Call { func: cov_func }), InstrLocId::default()),
+        ];
+        func.block_mut(block).instrs.splice(0..0, callback);
+    }
+}
+
+/// Instruments the Wasm module in `wasm` for coverage, returning the new module and the number of
+/// instrumented blocks.
+pub fn instrument_coverage(wasm: &[u8]) -> Result<(Vec<u8>, usize), Error> {
+    let mut module = Module::from_buffer(wasm).map_err(|err| {
+        Error::illegal_argument(format!("Could not parse the Wasm module: {err}"))
+    })?;
+    if module
+        .imports
+        .find(COVERAGE_MODULE, COVERAGE_FUNC)
+        .is_some()
+    {
+        return Err(Error::illegal_argument(
+            "The Wasm module is already instrumented",
+        ));
+    }
+
+    let ty = module.types.add(&[ValType::I32], &[]);
+    let (cov_func, _) = module.add_import_func(COVERAGE_MODULE, COVERAGE_FUNC, ty);
+
+    let mut next_id = 0;
+    for (_, func) in module.funcs.iter_local_mut() {
+        instrument_function(func, cov_func, &mut next_id);
+    }
+    log::info!("Instrumented {next_id} blocks of the Wasm module");
+
+    Ok((module.emit_wasm(), next_id as usize))
+}